@@ -0,0 +1,53 @@
+//! Times the graph analyses on a synthetic 100k-path closure:
+//! `cargo bench --bench analysis`. The nix module is compiled in directly, as
+//! the crate has no library target, so most of it goes unused here.
+
+#![allow(dead_code, unused_imports)]
+
+#[path = "../src/nix/mod.rs"]
+mod nix;
+#[path = "../src/util.rs"]
+mod util;
+
+use std::time::Duration;
+
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+
+use nix::closure::fill_closure_sizes;
+use nix::diff::diff_closures;
+use nix::dominators::fill_retained_sizes;
+use nix::{NixControl, generate_demo_graph};
+
+const NODE_COUNT: usize = 100_000;
+
+fn analysis(c: &mut Criterion) {
+    let graph = generate_demo_graph(NODE_COUNT);
+    let base = generate_demo_graph(NODE_COUNT - 1000);
+    let control = NixControl::default();
+
+    let mut group = c.benchmark_group("100k paths");
+    group
+        .sample_size(10)
+        .measurement_time(Duration::from_secs(20));
+    group.bench_function("closure sizes", |b| {
+        b.iter_batched_ref(
+            || graph.nodes.clone(),
+            fill_closure_sizes,
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("dominators and retained sizes", |b| {
+        b.iter_batched_ref(
+            || graph.nodes.clone(),
+            |nodes| fill_retained_sizes(nodes, &graph.root_id, &control),
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("closure diff", |b| {
+        b.iter(|| diff_closures(&base, &graph, &control))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, analysis);
+criterion_main!(benches);
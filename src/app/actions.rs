@@ -0,0 +1,278 @@
+use eframe::egui::{self, Context, Ui};
+
+use super::graph::ZOOM_STEP;
+use super::{LabelPanel, VerificationState, ViewModel, WorkspaceRequest};
+
+/// Every user-triggerable app action, so menus and other entry points share
+/// one list of labels, enablement rules and behavior.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum Action {
+    ReloadClosure,
+    ToggleGenerationsWindow,
+    ExportNeighborhood,
+    SaveGraph,
+    Quit,
+    ToggleControlsPanel,
+    ToggleDetailsPanel,
+    ToggleFpsDisplay,
+    ToggleQuadtreeOverlay,
+    TogglePhysicsHeat,
+    ToggleMeasureMode,
+    ToggleLegendOverlay,
+    TogglePublicationMode,
+    NavigateBack,
+    NavigateForward,
+    ResetView,
+    CenterOnSelection,
+    FitToView,
+    ZoomIn,
+    ZoomOut,
+    RebuildGraph,
+    ToggleFindingsWindow,
+    ToggleDiffWindow,
+    ToggleLockWindow,
+    ToggleAttributionWindow,
+    VerifySelected,
+    SimulateRemoval,
+    OpenSelectionAsWorkspace,
+    FocusSearch,
+    ClearSelection,
+    ToggleKeyHelp,
+}
+
+impl Action {
+    pub(super) const MENUS: [(&'static str, &'static [Action]); 3] = [
+        (
+            "File",
+            &[
+                Self::ReloadClosure,
+                Self::ToggleGenerationsWindow,
+                Self::ExportNeighborhood,
+                Self::SaveGraph,
+                Self::Quit,
+            ],
+        ),
+        (
+            "View",
+            &[
+                Self::ToggleControlsPanel,
+                Self::ToggleDetailsPanel,
+                Self::ToggleFpsDisplay,
+                Self::ToggleQuadtreeOverlay,
+                Self::TogglePhysicsHeat,
+                Self::ToggleMeasureMode,
+                Self::ToggleLegendOverlay,
+                Self::TogglePublicationMode,
+                Self::NavigateBack,
+                Self::NavigateForward,
+                Self::ResetView,
+                Self::CenterOnSelection,
+                Self::FitToView,
+                Self::ZoomIn,
+                Self::ZoomOut,
+                Self::RebuildGraph,
+                Self::ToggleKeyHelp,
+            ],
+        ),
+        (
+            "Analyze",
+            &[
+                Self::ToggleFindingsWindow,
+                Self::ToggleDiffWindow,
+                Self::ToggleLockWindow,
+                Self::ToggleAttributionWindow,
+                Self::VerifySelected,
+                Self::SimulateRemoval,
+                Self::OpenSelectionAsWorkspace,
+            ],
+        ),
+    ];
+
+    pub(super) fn label(self) -> &'static str {
+        match self {
+            Self::ReloadClosure => "Reload closure (Ctrl+R)",
+            Self::ToggleGenerationsWindow => "System generations",
+            Self::ExportNeighborhood => "Export selection neighborhood",
+            Self::SaveGraph => "Save graph",
+            Self::Quit => "Quit",
+            Self::ToggleControlsPanel => "Controls panel",
+            Self::ToggleDetailsPanel => "Details panel",
+            Self::ToggleFpsDisplay => "FPS display",
+            Self::ToggleQuadtreeOverlay => "Quadtree overlay",
+            Self::TogglePhysicsHeat => "Physics heat overlay",
+            Self::ToggleMeasureMode => "Measure (M)",
+            Self::ToggleLegendOverlay => "Legend and help (F1)",
+            Self::TogglePublicationMode => "Publication mode",
+            Self::NavigateBack => "Back (Alt+←)",
+            Self::NavigateForward => "Forward (Alt+→)",
+            Self::ResetView => "Reset view",
+            Self::CenterOnSelection => "Center on selection (Enter)",
+            Self::FitToView => "Fit graph to view (F)",
+            Self::ZoomIn => "Zoom in (+)",
+            Self::ZoomOut => "Zoom out (-)",
+            Self::RebuildGraph => "Rebuild graph",
+            Self::ToggleFindingsWindow => "Findings",
+            Self::ToggleDiffWindow => "Closure diff",
+            Self::ToggleLockWindow => "Closure lockfile",
+            Self::ToggleAttributionWindow => "Top-level attribution",
+            Self::VerifySelected => "Verify selected path",
+            Self::SimulateRemoval => "What would removal save?",
+            Self::OpenSelectionAsWorkspace => "Open selection as workspace",
+            Self::FocusSearch => "Search",
+            Self::ClearSelection => "Clear selection",
+            Self::ToggleKeyHelp => "Keyboard shortcuts (?)",
+        }
+    }
+}
+
+impl ViewModel {
+    pub(in crate::app) fn action_enabled(&self, action: Action, is_loading: bool) -> bool {
+        match action {
+            Action::ReloadClosure => !is_loading && !self.is_workspace,
+            Action::ExportNeighborhood | Action::CenterOnSelection | Action::ClearSelection => {
+                self.selected.is_some()
+            }
+            Action::VerifySelected => self.selected.as_ref().is_some_and(|id| {
+                !matches!(
+                    self.verifications.get(id),
+                    Some(VerificationState::Running(..))
+                )
+            }),
+            Action::SimulateRemoval => self
+                .selected
+                .as_ref()
+                .is_some_and(|id| *id != self.graph.root_id),
+            Action::OpenSelectionAsWorkspace => self.selected.is_some() && !self.is_workspace,
+            Action::NavigateBack => self.navigation.can_go_back(),
+            Action::NavigateForward => self.navigation.can_go_forward(),
+            _ => true,
+        }
+    }
+
+    /// Current state of toggle actions, `None` for one-shot actions.
+    pub(in crate::app) fn action_checked(&self, action: Action) -> Option<bool> {
+        match action {
+            Action::ToggleControlsPanel => Some(self.show_controls_panel),
+            Action::ToggleDetailsPanel => Some(self.show_details_panel),
+            Action::ToggleFpsDisplay => Some(self.show_fps_bar),
+            Action::ToggleQuadtreeOverlay => Some(self.show_quadtree_overlay),
+            Action::TogglePhysicsHeat => Some(self.show_physics_heat),
+            Action::ToggleMeasureMode => Some(self.measure_mode),
+            Action::ToggleLegendOverlay => Some(self.show_legend_overlay),
+            Action::TogglePublicationMode => Some(self.publication_mode),
+            Action::ToggleFindingsWindow => Some(self.show_findings_window),
+            Action::ToggleDiffWindow => Some(self.show_diff_window),
+            Action::ToggleLockWindow => Some(self.show_lock_window),
+            Action::ToggleGenerationsWindow => Some(self.show_generations_window),
+            Action::ToggleAttributionWindow => Some(self.show_attribution_window),
+            Action::ToggleKeyHelp => Some(self.show_key_help),
+            _ => None,
+        }
+    }
+
+    pub(in crate::app) fn run_action(
+        &mut self,
+        ctx: &Context,
+        action: Action,
+        reload_requested: &mut bool,
+    ) {
+        match action {
+            Action::ReloadClosure => *reload_requested = true,
+            Action::ExportNeighborhood => {
+                if let Some(id) = self.selected.clone() {
+                    self.export_neighborhood(&id);
+                }
+            }
+            Action::SaveGraph => self.save_graph_snapshot(),
+            Action::Quit => ctx.send_viewport_cmd(egui::ViewportCommand::Close),
+            Action::ToggleControlsPanel => self.show_controls_panel ^= true,
+            Action::ToggleDetailsPanel => self.show_details_panel ^= true,
+            Action::ToggleFpsDisplay => self.show_fps_bar ^= true,
+            Action::ToggleQuadtreeOverlay => self.show_quadtree_overlay ^= true,
+            Action::TogglePhysicsHeat => self.show_physics_heat ^= true,
+            Action::ToggleMeasureMode => {
+                self.measure_mode ^= true;
+                self.annotation_tool = None;
+            }
+            Action::ToggleLegendOverlay => self.show_legend_overlay ^= true,
+            Action::TogglePublicationMode => self.publication_mode ^= true,
+            Action::NavigateBack => self.navigate_back(),
+            Action::NavigateForward => self.navigate_forward(),
+            Action::ResetView => self.reset_view(),
+            Action::CenterOnSelection => {
+                if let Some(id) = self.selected.clone() {
+                    self.focus_on(&id);
+                }
+            }
+            Action::FitToView => self.fit_to_view(),
+            Action::ZoomIn => self.zoom_by(ZOOM_STEP),
+            Action::ZoomOut => self.zoom_by(ZOOM_STEP.recip()),
+            Action::RebuildGraph => {
+                self.graph_cache = None;
+                self.graph_dirty = true;
+            }
+            Action::ToggleFindingsWindow => self.show_findings_window ^= true,
+            Action::ToggleDiffWindow => self.show_diff_window ^= true,
+            Action::ToggleLockWindow => self.show_lock_window ^= true,
+            Action::ToggleGenerationsWindow => self.show_generations_window ^= true,
+            Action::ToggleAttributionWindow => self.show_attribution_window ^= true,
+            Action::VerifySelected => {
+                if let Some(id) = self.selected.clone() {
+                    self.start_verification(&id);
+                }
+            }
+            Action::SimulateRemoval => {
+                if let Some(id) = self.selected.clone() {
+                    self.simulate_removal(&id);
+                    self.show_details_panel = true;
+                }
+            }
+            Action::OpenSelectionAsWorkspace => {
+                self.workspace_request = self.selected.clone().map(WorkspaceRequest::Open);
+            }
+            Action::FocusSearch => {
+                self.show_controls_panel = true;
+                self.focus_search = true;
+            }
+            Action::ClearSelection => self.set_selected(None),
+            Action::ToggleKeyHelp => self.show_key_help ^= true,
+        }
+    }
+
+    pub(in crate::app) fn draw_action_menus(
+        &mut self,
+        ui: &mut Ui,
+        reload_requested: &mut bool,
+        is_loading: bool,
+    ) {
+        for (title, actions) in Action::MENUS {
+            ui.menu_button(title, |ui| {
+                for &action in actions {
+                    let enabled = self.action_enabled(action, is_loading);
+                    let clicked = match self.action_checked(action) {
+                        Some(mut checked) => ui
+                            .add_enabled(enabled, egui::Checkbox::new(&mut checked, action.label()))
+                            .clicked(),
+                        None => ui
+                            .add_enabled(enabled, egui::Button::new(action.label()))
+                            .clicked(),
+                    };
+                    if clicked {
+                        self.run_action(ui.ctx(), action, reload_requested);
+                    }
+                }
+                if title == "View" {
+                    ui.separator();
+                    ui.menu_button("Store hashes", |ui| {
+                        for panel in LabelPanel::ALL {
+                            let mut shown = self.store_hashes.shown(panel);
+                            if ui.checkbox(&mut shown, panel.label()).changed() {
+                                self.store_hashes.set(panel, shown);
+                            }
+                        }
+                    });
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, anyhow};
+use eframe::egui::Color32;
+use serde::{Deserialize, Serialize};
+
+use super::pins::state_file;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) enum AnnotationShape {
+    /// Text in a box, with a leader line to the point it explains.
+    Callout,
+    Arrow,
+    /// A ring highlighting everything inside it.
+    Circle,
+}
+
+impl AnnotationShape {
+    pub(super) const ALL: [Self; 3] = [Self::Callout, Self::Arrow, Self::Circle];
+
+    pub(super) fn label(self) -> &'static str {
+        match self {
+            Self::Callout => "Callout",
+            Self::Arrow => "Arrow",
+            Self::Circle => "Circle",
+        }
+    }
+}
+
+/// What an annotation says about the graph, which decides its color.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) enum AnnotationRole {
+    Note,
+    Finding,
+    Warning,
+}
+
+impl AnnotationRole {
+    pub(super) const ALL: [Self; 3] = [Self::Note, Self::Finding, Self::Warning];
+
+    pub(super) fn label(self) -> &'static str {
+        match self {
+            Self::Note => "Note",
+            Self::Finding => "Finding",
+            Self::Warning => "Warning",
+        }
+    }
+
+    /// Saturated enough to read on both the dark and the publication theme.
+    pub(super) fn color(self) -> Color32 {
+        match self {
+            Self::Note => Color32::from_rgb(59, 130, 246),
+            Self::Finding => Color32::from_rgb(217, 119, 6),
+            Self::Warning => Color32::from_rgb(220, 38, 38),
+        }
+    }
+}
+
+/// A shape drawn on the canvas between two points in world space, so it
+/// stays put on the graph while panning and zooming.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(super) struct Annotation {
+    pub(super) shape: AnnotationShape,
+    pub(super) role: AnnotationRole,
+    pub(super) start: [f32; 2],
+    pub(super) end: [f32; 2],
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub(super) text: String,
+}
+
+/// Annotations are keyed by the package name of the root, so they survive
+/// new generations of the same system.
+fn annotations_path() -> Option<PathBuf> {
+    state_file("annotations.json")
+}
+
+fn load_all_annotations() -> BTreeMap<String, Vec<Annotation>> {
+    let Some(path) = annotations_path() else {
+        return BTreeMap::new();
+    };
+
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+pub(super) fn load_annotations(root: &str) -> Vec<Annotation> {
+    load_all_annotations().remove(root).unwrap_or_default()
+}
+
+/// Saves `annotations` in place of those saved earlier for `root`.
+pub(super) fn save_annotations(root: &str, annotations: &[Annotation]) -> Result<()> {
+    let path =
+        annotations_path().ok_or_else(|| anyhow!("no state directory to store annotations in"))?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+
+    let mut all = load_all_annotations();
+    if annotations.is_empty() {
+        all.remove(root);
+    } else {
+        all.insert(root.to_owned(), annotations.to_vec());
+    }
+    let raw = serde_json::to_string_pretty(&all).context("failed to encode annotations")?;
+    fs::write(&path, raw).with_context(|| format!("failed to write {}", path.display()))
+}
@@ -0,0 +1,91 @@
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::nix::SizeMetric;
+
+use super::pins::state_file;
+
+/// A saved camera position, recalled from the bookmark list or with the
+/// number key of its slot.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(super) struct ViewBookmark {
+    pub(super) name: String,
+    pub(super) pan: [f32; 2],
+    pub(super) zoom: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) filters: Option<BookmarkFilters>,
+}
+
+/// The node filters active when a bookmark was saved, if it keeps them.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub(super) struct BookmarkFilters {
+    pub(super) metric: SizeMetric,
+    pub(super) min_size_mb: f32,
+    pub(super) max_nodes: usize,
+}
+
+/// A path starred from the details panel, keyed by the hash-stripped store
+/// name like pins, so it is found again after rebuilds.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(super) struct NodeBookmark {
+    pub(super) name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub(super) note: String,
+}
+
+fn bookmarks_path() -> Option<PathBuf> {
+    state_file("bookmarks.json")
+}
+
+pub(super) fn load_bookmarks() -> Vec<ViewBookmark> {
+    let Some(path) = bookmarks_path() else {
+        return Vec::new();
+    };
+
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+pub(super) fn save_bookmarks(bookmarks: &[ViewBookmark]) -> Result<()> {
+    let path =
+        bookmarks_path().ok_or_else(|| anyhow!("no state directory to store bookmarks in"))?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+
+    let raw = serde_json::to_string_pretty(bookmarks).context("failed to encode bookmarks")?;
+    fs::write(&path, raw).with_context(|| format!("failed to write {}", path.display()))
+}
+
+fn node_bookmarks_path() -> Option<PathBuf> {
+    state_file("starred.json")
+}
+
+pub(super) fn load_node_bookmarks() -> Vec<NodeBookmark> {
+    let Some(path) = node_bookmarks_path() else {
+        return Vec::new();
+    };
+
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+pub(super) fn save_node_bookmarks(bookmarks: &[NodeBookmark]) -> Result<()> {
+    let path = node_bookmarks_path()
+        .ok_or_else(|| anyhow!("no state directory to store starred paths in"))?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+
+    let raw = serde_json::to_string_pretty(bookmarks).context("failed to encode starred paths")?;
+    fs::write(&path, raw).with_context(|| format!("failed to write {}", path.display()))
+}
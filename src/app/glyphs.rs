@@ -0,0 +1,319 @@
+use std::f32::consts::TAU;
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+use eframe::egui::{
+    self, Align2, Color32, ColorImage, FontId, Painter, Pos2, Rect, TextureHandle, TextureId, Vec2,
+    pos2, vec2,
+};
+
+use crate::nix::{NodeClass, NodeClasses};
+use crate::util::parse_store_name;
+
+/// Nodes smaller than this on screen are drawn without a glyph.
+pub(super) const GLYPH_MIN_RADIUS: f32 = 14.0;
+
+/// Side of one icon in the atlas, in pixels.
+const ICON_CELL: usize = 64;
+
+/// A category of path recognizable at a glance, drawn inside large nodes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum NodeGlyph {
+    Python,
+    Service,
+    Docs,
+    Font,
+    Kernel,
+    Source,
+}
+
+/// How a glyph is drawn: a few letters from the font atlas, or a cell of the
+/// icon atlas.
+enum GlyphArt {
+    Letters(&'static str),
+    Icon(Icon),
+}
+
+#[derive(Clone, Copy)]
+enum Icon {
+    Gear,
+    Book,
+    Chip,
+    Archive,
+}
+
+impl Icon {
+    const ALL: [Self; 4] = [Self::Gear, Self::Book, Self::Chip, Self::Archive];
+
+    /// Signed distance from `p`, in cell units spanning -1..1, to the icon's
+    /// outline; negative inside.
+    fn distance(self, p: Vec2) -> f32 {
+        match self {
+            Self::Gear => {
+                let teeth = (0..8)
+                    .map(|tooth| {
+                        let angle = tooth as f32 / 8.0 * TAU;
+                        let rotated = vec2(
+                            p.x * angle.cos() + p.y * angle.sin(),
+                            p.y * angle.cos() - p.x * angle.sin(),
+                        );
+                        box_distance(rotated - vec2(0.72, 0.0), vec2(0.17, 0.13))
+                    })
+                    .fold(f32::MAX, f32::min);
+                let body = (p.length() - 0.6).min(teeth);
+                body.max(0.24 - p.length())
+            }
+            Self::Book => {
+                let pages = box_distance(p - vec2(-0.37, 0.0), vec2(0.31, 0.56))
+                    .min(box_distance(p - vec2(0.37, 0.0), vec2(0.31, 0.56)));
+                let lines = [-0.26, 0.0, 0.26]
+                    .into_iter()
+                    .flat_map(|y| [vec2(-0.37, y), vec2(0.37, y)])
+                    .map(|center| box_distance(p - center, vec2(0.19, 0.035)))
+                    .fold(f32::MAX, f32::min);
+                pages.max(-lines)
+            }
+            Self::Chip => {
+                let pins = [-0.3, 0.0, 0.3]
+                    .into_iter()
+                    .flat_map(|offset| {
+                        [
+                            vec2(offset, 0.56),
+                            vec2(offset, -0.56),
+                            vec2(0.56, offset),
+                            vec2(-0.56, offset),
+                        ]
+                    })
+                    .map(|center| box_distance(p - center, vec2(0.07, 0.07)))
+                    .fold(f32::MAX, f32::min);
+                let body = box_distance(p, vec2(0.44, 0.44))
+                    .max(-box_distance(p, vec2(0.26, 0.26)))
+                    .min(box_distance(p, vec2(0.13, 0.13)));
+                body.min(pins)
+            }
+            Self::Archive => {
+                let lid = box_distance(p - vec2(0.0, -0.44), vec2(0.7, 0.14));
+                let body = box_distance(p - vec2(0.0, 0.16), vec2(0.6, 0.42));
+                let slot = box_distance(p - vec2(0.0, 0.02), vec2(0.2, 0.06));
+                lid.min(body.max(-slot))
+            }
+        }
+    }
+}
+
+fn box_distance(p: Vec2, half_size: Vec2) -> f32 {
+    let q = vec2(p.x.abs(), p.y.abs()) - half_size;
+    vec2(q.x.max(0.0), q.y.max(0.0)).length() + q.x.max(q.y).min(0.0)
+}
+
+impl NodeGlyph {
+    pub(super) const ALL: [Self; 6] = [
+        Self::Python,
+        Self::Service,
+        Self::Docs,
+        Self::Font,
+        Self::Kernel,
+        Self::Source,
+    ];
+
+    pub(super) fn label(self) -> &'static str {
+        match self {
+            Self::Python => "Python",
+            Self::Service => "Services",
+            Self::Docs => "Documentation",
+            Self::Font => "Fonts",
+            Self::Kernel => "Kernels / firmware",
+            Self::Source => "Sources",
+        }
+    }
+
+    fn art(self) -> GlyphArt {
+        match self {
+            Self::Python => GlyphArt::Letters("Py"),
+            Self::Service => GlyphArt::Icon(Icon::Gear),
+            Self::Docs => GlyphArt::Icon(Icon::Book),
+            Self::Font => GlyphArt::Letters("Aa"),
+            Self::Kernel => GlyphArt::Icon(Icon::Chip),
+            Self::Source => GlyphArt::Icon(Icon::Archive),
+        }
+    }
+
+    /// The glyph of a path named `name` (without its hash), if it falls in a
+    /// category.
+    pub(super) fn of(name: &str, classes: NodeClasses) -> Option<Self> {
+        let parsed = parse_store_name(name);
+        if parsed.is_derivation {
+            return None;
+        }
+        Some(if classes.contains(NodeClass::DocOutput) {
+            Self::Docs
+        } else if classes.contains(NodeClass::Font) {
+            Self::Font
+        } else if classes.contains(NodeClass::KernelFirmware) {
+            Self::Kernel
+        } else if name.starts_with("unit-")
+            || [".service", ".socket", ".timer", ".target"]
+                .iter()
+                .any(|suffix| name.ends_with(suffix))
+        {
+            Self::Service
+        } else if parsed.pname.starts_with("python") {
+            Self::Python
+        } else if classes.contains(NodeClass::FixedOutput) {
+            Self::Source
+        } else {
+            return None;
+        })
+    }
+}
+
+/// Rasterizes every icon into one row of white cells whose alpha is the
+/// icon's coverage, to be tinted when drawn.
+fn rasterize_icons() -> ColorImage {
+    let width = ICON_CELL * Icon::ALL.len();
+    let mut pixels = vec![Color32::TRANSPARENT; width * ICON_CELL];
+    let scale = ICON_CELL as f32 / 2.0;
+    for (cell, icon) in Icon::ALL.into_iter().enumerate() {
+        for y in 0..ICON_CELL {
+            for x in 0..ICON_CELL {
+                let p = vec2(x as f32 + 0.5, y as f32 + 0.5) / scale - Vec2::splat(1.0);
+                let coverage = (0.5 - icon.distance(p) * scale).clamp(0.0, 1.0);
+                pixels[y * width + cell * ICON_CELL + x] =
+                    Color32::from_white_alpha((coverage * 255.0) as u8);
+            }
+        }
+    }
+    ColorImage::new([width, ICON_CELL], pixels)
+}
+
+/// The icon atlas, rasterized on a worker thread and uploaded on the first
+/// frame after it is done. The image is kept for rendering exported PNGs.
+pub(super) enum GlyphAtlas {
+    Pending(Receiver<ColorImage>),
+    Ready(TextureHandle, Arc<ColorImage>),
+    Unavailable,
+}
+
+impl GlyphAtlas {
+    pub(super) fn spawn() -> Self {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = tx.send(rasterize_icons());
+        });
+        Self::Pending(rx)
+    }
+
+    /// The atlas texture once it is ready.
+    pub(super) fn texture(&mut self, ctx: &egui::Context) -> Option<TextureId> {
+        if let Self::Pending(rx) = self {
+            match rx.try_recv() {
+                Ok(image) => {
+                    let texture = ctx.load_texture(
+                        "node_glyphs",
+                        image.clone(),
+                        egui::TextureOptions::LINEAR,
+                    );
+                    *self = Self::Ready(texture, Arc::new(image));
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => *self = Self::Unavailable,
+            }
+        }
+        match self {
+            Self::Ready(texture, _) => Some(texture.id()),
+            _ => None,
+        }
+    }
+
+    /// The uploaded atlas and its image.
+    pub(super) fn image(&self) -> Option<(TextureId, Arc<ColorImage>)> {
+        match self {
+            Self::Ready(texture, image) => Some((texture.id(), Arc::clone(image))),
+            _ => None,
+        }
+    }
+}
+
+/// Dark glyphs on light nodes and light ones on dark nodes.
+fn glyph_tint(fill: Color32) -> Color32 {
+    let luminance = 0.299 * fill.r() as f32 + 0.587 * fill.g() as f32 + 0.114 * fill.b() as f32;
+    if luminance > 150.0 {
+        Color32::from_black_alpha(170)
+    } else {
+        Color32::from_white_alpha(210)
+    }
+}
+
+/// Draws `glyph` centered in a node of `radius` filled with `fill`. Icons
+/// are skipped while the atlas is still being rasterized.
+pub(super) fn draw_node_glyph(
+    painter: &Painter,
+    atlas: Option<TextureId>,
+    glyph: NodeGlyph,
+    center: Pos2,
+    radius: f32,
+    fill: Color32,
+) {
+    let tint = glyph_tint(fill);
+    match glyph.art() {
+        GlyphArt::Letters(text) => {
+            painter.text(
+                center,
+                Align2::CENTER_CENTER,
+                text,
+                FontId::proportional(radius * 0.75),
+                tint,
+            );
+        }
+        GlyphArt::Icon(icon) => {
+            let Some(texture) = atlas else {
+                return;
+            };
+            let cell_width = 1.0 / Icon::ALL.len() as f32;
+            let left = icon as usize as f32 * cell_width;
+            let uv = Rect::from_min_max(pos2(left, 0.0), pos2(left + cell_width, 1.0));
+            let rect = Rect::from_center_size(center, Vec2::splat(radius * 1.1));
+            painter.image(texture, rect, uv, tint);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glyphs_follow_classes_before_names() {
+        let docs = NodeClasses::NONE.with(NodeClass::DocOutput);
+        assert_eq!(
+            NodeGlyph::of("python3-3.12.4-doc", docs),
+            Some(NodeGlyph::Docs)
+        );
+        assert_eq!(
+            NodeGlyph::of("python3-3.12.4", NodeClasses::NONE),
+            Some(NodeGlyph::Python)
+        );
+        assert_eq!(
+            NodeGlyph::of("unit-sshd.service", NodeClasses::NONE),
+            Some(NodeGlyph::Service)
+        );
+        assert_eq!(NodeGlyph::of("python3-3.12.4.drv", NodeClasses::NONE), None);
+        assert_eq!(NodeGlyph::of("zlib-1.3.1", NodeClasses::NONE), None);
+    }
+
+    #[test]
+    fn icons_cover_their_centers_but_not_their_corners() {
+        let atlas = rasterize_icons();
+        let alpha = |cell: usize, x: usize, y: usize| {
+            atlas.pixels[y * atlas.width() + cell * ICON_CELL + x].a()
+        };
+        for cell in 0..Icon::ALL.len() {
+            assert_eq!(alpha(cell, 0, 0), 0);
+        }
+        // The gear's hole, the chip's die and the archive's body.
+        assert_eq!(alpha(0, ICON_CELL / 2, ICON_CELL / 2), 0);
+        assert_eq!(alpha(2, ICON_CELL / 2, ICON_CELL / 2), 255);
+        assert_eq!(alpha(3, ICON_CELL / 2, ICON_CELL * 3 / 4), 255);
+    }
+}
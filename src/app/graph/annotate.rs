@@ -0,0 +1,170 @@
+use eframe::egui::{self, Align2, Color32, FontId, Painter, Pos2, Rect, Stroke, Vec2, vec2};
+
+use super::super::ViewModel;
+use super::super::annotations::{Annotation, AnnotationShape};
+use super::super::render_utils::{RenderTheme, screen_to_world, world_to_screen};
+
+const ANNOTATION_STROKE: f32 = 2.5;
+const ARROW_HEAD_LENGTH: f32 = 14.0;
+const CALLOUT_PADDING: f32 = 6.0;
+/// Drags shorter than this on screen are taken as misclicks.
+const MIN_ANNOTATION_DRAG: f32 = 6.0;
+
+fn draw_arrow_head(painter: &Painter, start: Pos2, end: Pos2, color: Color32) {
+    let direction = (end - start).normalized();
+    let normal = direction.rot90() * (ARROW_HEAD_LENGTH * 0.45);
+    let base = end - direction * ARROW_HEAD_LENGTH;
+    painter.add(egui::Shape::convex_polygon(
+        vec![end, base + normal, base - normal],
+        color,
+        Stroke::NONE,
+    ));
+}
+
+/// Text in a box centered on `center`, outlined in the role color.
+fn draw_callout_box(
+    painter: &Painter,
+    center: Pos2,
+    text: &str,
+    color: Color32,
+    theme: &RenderTheme,
+) {
+    let galley = painter.layout(
+        text.to_owned(),
+        FontId::proportional(13.0),
+        theme.overlay_text,
+        280.0,
+    );
+    let frame = Rect::from_center_size(center, galley.size() + Vec2::splat(CALLOUT_PADDING * 2.0));
+    painter.rect(
+        frame,
+        5.0,
+        theme.overlay_fill,
+        Stroke::new(1.5, color),
+        egui::StrokeKind::Outside,
+    );
+    painter.galley(
+        frame.min + Vec2::splat(CALLOUT_PADDING),
+        galley,
+        theme.overlay_text,
+    );
+}
+
+fn draw_annotation(
+    painter: &Painter,
+    [start, end]: [Pos2; 2],
+    annotation: &Annotation,
+    opacity: f32,
+    theme: &RenderTheme,
+) {
+    let color = annotation.role.color().gamma_multiply(opacity);
+    let stroke = Stroke::new(ANNOTATION_STROKE, color);
+    let text = match annotation.text.as_str() {
+        "" => annotation.role.label(),
+        text => text,
+    };
+
+    match annotation.shape {
+        AnnotationShape::Callout => {
+            painter.line_segment([start, end], stroke);
+            painter.circle_filled(start, ANNOTATION_STROKE * 1.6, color);
+            draw_callout_box(painter, end, text, color, theme);
+        }
+        AnnotationShape::Arrow => {
+            let direction = (end - start).normalized();
+            painter.line_segment([start, end - direction * ARROW_HEAD_LENGTH], stroke);
+            draw_arrow_head(painter, start, end, color);
+            if !annotation.text.is_empty() {
+                let anchor = if direction.x > 0.0 {
+                    Align2::RIGHT_CENTER
+                } else {
+                    Align2::LEFT_CENTER
+                };
+                painter.text(
+                    start - direction * 6.0,
+                    anchor,
+                    text,
+                    FontId::proportional(14.0),
+                    color,
+                );
+            }
+        }
+        AnnotationShape::Circle => {
+            let radius = start.distance(end);
+            painter.circle(start, radius, color.gamma_multiply(0.08), stroke);
+            if !annotation.text.is_empty() {
+                painter.text(
+                    start - vec2(0.0, radius + 4.0),
+                    Align2::CENTER_BOTTOM,
+                    text,
+                    FontId::proportional(14.0),
+                    color,
+                );
+            }
+        }
+    }
+}
+
+impl ViewModel {
+    /// Primary drags draw a new annotation with the chosen tool, from where
+    /// the drag started to where it ends.
+    pub(in crate::app) fn handle_annotation_drag(&mut self, rect: Rect, response: &egui::Response) {
+        let Some(shape) = self.annotation_tool else {
+            return;
+        };
+
+        if response.drag_started_by(egui::PointerButton::Primary)
+            && let Some(origin) = response.ctx.input(|input| input.pointer.press_origin())
+        {
+            let start = screen_to_world(rect, self.pan, self.zoom, origin);
+            self.annotation_draft = Some([start, start]);
+        }
+
+        if response.dragged_by(egui::PointerButton::Primary)
+            && let Some(pointer) = response.interact_pointer_pos()
+            && let Some([_, end]) = self.annotation_draft.as_mut()
+        {
+            *end = screen_to_world(rect, self.pan, self.zoom, pointer);
+        }
+
+        if response.drag_stopped_by(egui::PointerButton::Primary)
+            && let Some([start, end]) = self.annotation_draft.take()
+            && (end - start).length() * self.zoom >= MIN_ANNOTATION_DRAG
+        {
+            self.annotations.push(Annotation {
+                shape,
+                role: self.annotation_role,
+                start: [start.x, start.y],
+                end: [end.x, end.y],
+                text: self.annotation_text.trim().to_owned(),
+            });
+            self.persist_annotations();
+        }
+    }
+
+    /// Saved annotations, plus the one being dragged out.
+    pub(in crate::app) fn draw_annotations(
+        &self,
+        painter: &Painter,
+        rect: Rect,
+        theme: &RenderTheme,
+    ) {
+        let to_screen = |[x, y]: [f32; 2]| world_to_screen(rect, self.pan, self.zoom, vec2(x, y));
+        for annotation in &self.annotations {
+            let points = [to_screen(annotation.start), to_screen(annotation.end)];
+            draw_annotation(painter, points, annotation, 1.0, theme);
+        }
+
+        if let (Some(shape), Some([start, end])) = (self.annotation_tool, self.annotation_draft) {
+            let draft = Annotation {
+                shape,
+                role: self.annotation_role,
+                start: [start.x, start.y],
+                end: [end.x, end.y],
+                text: self.annotation_text.trim().to_owned(),
+            };
+            let points = [to_screen(draft.start), to_screen(draft.end)];
+            draw_annotation(painter, points, &draft, 0.6, theme);
+        }
+    }
+}
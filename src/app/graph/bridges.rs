@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+
+use eframe::egui::{Color32, Painter, Rect, Shape, Stroke};
+
+use super::super::render_utils::{RenderTheme, edge_visible};
+use super::super::{RenderGraph, ViewModel};
+
+pub(super) const BRIDGE_DASH: f32 = 6.0;
+pub(super) const BRIDGE_GAP: f32 = 4.0;
+
+/// Draws bridge edges as dashed straight lines, whatever the edge style.
+pub(super) fn draw_bridge_edges(
+    painter: &Painter,
+    rect: Rect,
+    cache: &RenderGraph,
+    zoom: f32,
+    theme: &RenderTheme,
+) {
+    let width = zoom.sqrt().clamp(0.6, 2.6) * theme.stroke_scale;
+    let stroke = Stroke::new(width, bridge_color(theme));
+    for &(src, dst) in &cache.bridge_edges {
+        let start = cache.view_scratch.screen_positions[src];
+        let end = cache.view_scratch.screen_positions[dst];
+        if edge_visible(rect, start, end, 2.5) {
+            painter.extend(Shape::dashed_line(
+                &[start, end],
+                stroke,
+                BRIDGE_DASH,
+                BRIDGE_GAP,
+            ));
+        }
+    }
+}
+
+/// The color of bridge edges, a fainter take on the theme's edge color.
+pub(super) fn bridge_color(theme: &RenderTheme) -> Color32 {
+    let [r, g, b, _] = theme.edge.to_array();
+    Color32::from_rgba_unmultiplied(r, g, b, 150)
+}
+
+impl ViewModel {
+    /// Whether `id` is left out of the graph on purpose, by hiding, an ignore
+    /// pattern or a class filter, rather than for being too small.
+    pub(super) fn hidden_on_purpose(&self, id: &str) -> bool {
+        self.hidden_nodes.contains(id)
+            || self.is_ignored(id)
+            || self
+                .graph
+                .nodes
+                .get(id)
+                .is_some_and(|node| !self.passes_class_filter(node.classes))
+    }
+
+    /// Edges between drawn nodes whose only connection runs through paths
+    /// hidden on purpose, so hiding does not split the graph into islands.
+    /// What each hidden path reaches is worked out once and shared by every
+    /// drawn node referring to it.
+    pub(super) fn collect_bridge_edges(
+        &self,
+        ids: &[String],
+        index_by_id: &HashMap<String, usize>,
+        edges: &[(usize, usize)],
+    ) -> Vec<(usize, usize)> {
+        let mut reach = HiddenReach::default();
+        let mut bridges = Vec::new();
+        for (source_index, source_id) in ids.iter().enumerate() {
+            let members = self
+                .condensed_cycle(source_id)
+                .unwrap_or(std::slice::from_ref(source_id));
+            let hidden = members
+                .iter()
+                .filter_map(|id| self.graph.nodes.get(id))
+                .flat_map(|node| &node.references)
+                .filter(|id| self.is_hidden_link(id, index_by_id));
+            for hidden_id in hidden {
+                let component = reach.visit(self, hidden_id, index_by_id);
+                bridges.extend(
+                    reach.targets[component]
+                        .iter()
+                        .filter(|&&target_index| {
+                            target_index != source_index
+                                && edges.binary_search(&(source_index, target_index)).is_err()
+                        })
+                        .map(|&target_index| (source_index, target_index)),
+                );
+            }
+        }
+        bridges.sort_unstable();
+        bridges.dedup();
+        bridges
+    }
+
+    /// Whether a reference to `id` leads into the hidden part of the graph
+    /// rather than to a drawn node.
+    fn is_hidden_link(&self, id: &str, index_by_id: &HashMap<String, usize>) -> bool {
+        !index_by_id.contains_key(self.condensed_id(id)) && self.hidden_on_purpose(id)
+    }
+}
+
+/// The drawn nodes reachable from hidden paths through hidden paths only.
+/// Hidden paths are grouped into strongly connected components with
+/// Tarjan's algorithm, which finishes a component after every component it
+/// reaches, so each one's targets are built from theirs.
+#[derive(Default)]
+struct HiddenReach<'a> {
+    component_of: HashMap<&'a str, usize>,
+    /// Sorted drawn node indices, per component.
+    targets: Vec<Vec<usize>>,
+    order: HashMap<&'a str, usize>,
+    low_link: HashMap<&'a str, usize>,
+    stack: Vec<&'a str>,
+}
+
+impl<'a> HiddenReach<'a> {
+    /// The component of `start`, walking the hidden paths it reaches first
+    /// when it was not visited yet.
+    fn visit(
+        &mut self,
+        model: &'a ViewModel,
+        start: &'a str,
+        index_by_id: &HashMap<String, usize>,
+    ) -> usize {
+        if let Some(&component) = self.component_of.get(start) {
+            return component;
+        }
+
+        let references = |id: &str| {
+            model
+                .graph
+                .nodes
+                .get(id)
+                .map_or(&[][..], |node| node.references.as_slice())
+        };
+        self.enter(start);
+        let mut frames = vec![(start, 0)];
+        while let Some(&mut (node, ref mut next)) = frames.last_mut() {
+            if let Some(child) = references(node).get(*next) {
+                *next += 1;
+                let child = child.as_str();
+                if !model.is_hidden_link(child, index_by_id) {
+                    continue;
+                }
+                if !self.order.contains_key(child) {
+                    self.enter(child);
+                    frames.push((child, 0));
+                } else if !self.component_of.contains_key(child) {
+                    // Still on the stack: part of the component being built.
+                    self.lower(node, self.order[child]);
+                }
+                continue;
+            }
+
+            frames.pop();
+            if let Some(&(parent, _)) = frames.last() {
+                self.lower(parent, self.low_link[node]);
+            }
+            if self.low_link[node] != self.order[node] {
+                continue;
+            }
+
+            let component = self.targets.len();
+            let split = self
+                .stack
+                .iter()
+                .rposition(|&member| member == node)
+                .expect("a component's root is on the stack");
+            let members = self.stack.split_off(split);
+            for &member in &members {
+                self.component_of.insert(member, component);
+            }
+            let mut targets = Vec::new();
+            for reference in members.iter().flat_map(|&member| references(member)) {
+                match index_by_id.get(model.condensed_id(reference)) {
+                    Some(&target_index) => targets.push(target_index),
+                    None => match self.component_of.get(reference.as_str()) {
+                        Some(&other) if other != component => {
+                            targets.extend_from_slice(&self.targets[other]);
+                        }
+                        _ => {}
+                    },
+                }
+            }
+            targets.sort_unstable();
+            targets.dedup();
+            self.targets.push(targets);
+        }
+
+        self.component_of[start]
+    }
+
+    fn enter(&mut self, id: &'a str) {
+        let order = self.order.len();
+        self.order.insert(id, order);
+        self.low_link.insert(id, order);
+        self.stack.push(id);
+    }
+
+    fn lower(&mut self, id: &'a str, order: usize) {
+        let low_link = self
+            .low_link
+            .get_mut(id)
+            .expect("visited paths have a low link");
+        *low_link = (*low_link).min(order);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nix::{SystemGraph, generate_demo_graph};
+
+    fn graph(references: &[(&str, &[&str])]) -> SystemGraph {
+        let mut graph = generate_demo_graph(2);
+        let template = graph.nodes.values().next().unwrap().clone();
+        graph.nodes = references
+            .iter()
+            .map(|(id, references)| {
+                let mut node = template.clone();
+                node.id = (*id).to_owned();
+                node.references = references.iter().map(|id| (*id).to_owned()).collect();
+                (node.id.clone(), node)
+            })
+            .collect();
+        graph.root_id = "root".to_owned();
+        graph
+    }
+
+    #[test]
+    fn hiding_a_middle_node_bridges_across_it() {
+        // a reaches b only through the hidden m and n, and c directly too.
+        let mut model = ViewModel::new(
+            graph(&[
+                ("root", &["a"]),
+                ("a", &["m", "c"]),
+                ("m", &["n", "b", "c"]),
+                ("n", &["m", "b"]),
+                ("b", &[]),
+                ("c", &[]),
+            ]),
+            None,
+        );
+        model.hidden_nodes.extend(["m".to_owned(), "n".to_owned()]);
+
+        let ids = ["root", "a", "b", "c"].map(str::to_owned);
+        let index_by_id = ids
+            .iter()
+            .enumerate()
+            .map(|(index, id)| (id.clone(), index))
+            .collect();
+        let edges = [(0, 1), (1, 3)];
+        assert_eq!(
+            model.collect_bridge_edges(&ids, &index_by_id, &edges),
+            [(1, 2)]
+        );
+    }
+}
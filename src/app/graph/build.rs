@@ -1,14 +1,22 @@
 use std::collections::{HashMap, HashSet};
 
+use anyhow::Result;
 use eframe::egui::{Vec2, vec2};
 
-use crate::util::stable_pair;
+use crate::nix::SizeMetric;
+use crate::util::{short_name, stable_pair};
 
+use super::super::layouts::{LayoutSnapshot, save_layout};
+use super::super::physics::PhysicsActivity;
 use super::super::render_utils::node_radius;
 use super::super::{PhysicsScratch, RenderGraph, RenderNode, ViewModel, ViewScratch};
 
 impl ViewModel {
     fn filtered_node_ids(&self) -> Vec<String> {
+        if self.explore_mode {
+            return self.explore_node_ids();
+        }
+
         let threshold = self.metric_threshold_value();
 
         let mut ranked = self
@@ -16,6 +24,34 @@ impl ViewModel {
             .nodes
             .iter()
             .filter_map(|(id, node)| {
+                if id != &self.graph.root_id && self.is_ignored(id) {
+                    return None;
+                }
+
+                if id != &self.graph.root_id && !self.name_filter.allows(id, &node.full_path) {
+                    return None;
+                }
+
+                if id != &self.graph.root_id && !self.passes_class_filter(node.classes) {
+                    return None;
+                }
+
+                if id != &self.graph.root_id && self.hidden_nodes.contains(id) {
+                    return None;
+                }
+
+                if id != &self.graph.root_id && self.hidden_stores.contains(&node.store_dir) {
+                    return None;
+                }
+
+                if self.gc_sole_only
+                    && let Some(gc_roots) = self.gc_roots()
+                    && !gc_roots.is_sole(id)
+                    && id != &self.graph.root_id
+                {
+                    return None;
+                }
+
                 let metric = node.metric(self.metric);
 
                 let always_include =
@@ -29,6 +65,7 @@ impl ViewModel {
             })
             .collect::<Vec<_>>();
 
+        #[allow(clippy::unnecessary_sort_by)]
         ranked.sort_by(|a, b| b.0.cmp(&a.0));
 
         let target_nodes = self.max_nodes.clamp(2, self.graph.node_count().max(2));
@@ -59,6 +96,43 @@ impl ViewModel {
         ids
     }
 
+    /// Replaces cycle members by their stand-in in the condensed view.
+    fn condense_node_ids(&self, ids: Vec<String>) -> Vec<String> {
+        if !self.condense_cycles || self.cycles.is_empty() {
+            return ids;
+        }
+
+        let mut seen = HashSet::new();
+        ids.into_iter()
+            .map(|id| self.condensed_id(&id).to_owned())
+            .filter(|id| seen.insert(id.clone()))
+            .collect()
+    }
+
+    /// A cycle's stand-in sums its members' metric, apart from the closure
+    /// size they all share.
+    fn render_metric(&self, id: &str) -> u64 {
+        let metric = match self.condensed_cycle(id) {
+            Some(cycle) => {
+                let values = cycle
+                    .iter()
+                    .filter_map(|member| self.graph.nodes.get(member))
+                    .map(|node| node.metric(self.metric));
+                if self.metric == SizeMetric::ClosureSize {
+                    values.max().unwrap_or(1)
+                } else {
+                    values.sum()
+                }
+            }
+            None => self
+                .graph
+                .nodes
+                .get(id)
+                .map_or(1, |node| node.metric(self.metric)),
+        };
+        metric.max(1)
+    }
+
     fn make_render_node(
         id: String,
         index: usize,
@@ -87,6 +161,8 @@ impl ViewModel {
             velocity: direction * initial_speed,
             metric_value,
             base_radius,
+            pinned: false,
+            layout_static: false,
         }
     }
 
@@ -97,15 +173,16 @@ impl ViewModel {
     ) -> Vec<(usize, usize)> {
         let mut edges = Vec::new();
         for (source_index, source_id) in ids.iter().enumerate() {
-            let Some(node) = self.graph.nodes.get(source_id) else {
-                continue;
-            };
-
-            for target_id in &node.references {
-                if let Some(&target_index) = index_by_id.get(target_id)
-                    && source_index != target_index
-                {
-                    edges.push((source_index, target_index));
+            let members = self
+                .condensed_cycle(source_id)
+                .unwrap_or(std::slice::from_ref(source_id));
+            for node in members.iter().filter_map(|id| self.graph.nodes.get(id)) {
+                for target_id in &node.references {
+                    if let Some(&target_index) = index_by_id.get(self.condensed_id(target_id))
+                        && source_index != target_index
+                    {
+                        edges.push((source_index, target_index));
+                    }
                 }
             }
         }
@@ -118,7 +195,7 @@ impl ViewModel {
         self.render_graph_revision = self.render_graph_revision.wrapping_add(1);
         self.search_match_cache = None;
 
-        let ids = self.filtered_node_ids();
+        let ids = self.condense_node_ids(self.filtered_node_ids());
 
         if ids.is_empty() {
             self.graph_cache = None;
@@ -132,12 +209,7 @@ impl ViewModel {
         let mut max_metric = 0u64;
         let mut metrics = Vec::with_capacity(ids.len());
         for id in &ids {
-            let metric = self
-                .graph
-                .nodes
-                .get(id)
-                .map(|node| node.metric(self.metric).max(1))
-                .unwrap_or(1);
+            let metric = self.render_metric(id);
             metrics.push(metric);
             min_metric = min_metric.min(metric);
             max_metric = max_metric.max(metric);
@@ -160,7 +232,18 @@ impl ViewModel {
         }
         let root_index = index_by_id.get(&self.graph.root_id).copied();
         let edges = self.collect_edges(&ids, &index_by_id);
+        let edge_weights = edges
+            .iter()
+            .map(|&(source, target)| self.graph.edge_weight(&ids[source], &ids[target]))
+            .collect::<Vec<_>>();
+        let max_edge_weight = edge_weights.iter().copied().max().unwrap_or(1);
+        let bridge_edges = if self.bridge_hidden {
+            self.collect_bridge_edges(&ids, &index_by_id, &edges)
+        } else {
+            Vec::new()
+        };
 
+        let fresh = self.graph_cache.is_none();
         if let Some(mut cache) = self.graph_cache.take() {
             let mut prior_nodes = cache
                 .nodes
@@ -201,6 +284,9 @@ impl ViewModel {
 
             cache.nodes = next_nodes;
             cache.edges = edges;
+            cache.bridge_edges = bridge_edges;
+            cache.edge_weights = edge_weights;
+            cache.max_edge_weight = max_edge_weight;
             cache.index_by_id = index_by_id;
             cache.outgoing = outgoing;
             cache.incoming = incoming;
@@ -238,6 +324,9 @@ impl ViewModel {
             self.graph_cache = Some(RenderGraph {
                 nodes,
                 edges,
+                bridge_edges,
+                edge_weights,
+                max_edge_weight,
                 index_by_id,
                 outgoing,
                 incoming,
@@ -246,8 +335,10 @@ impl ViewModel {
                 max_metric,
                 physics_scratch: PhysicsScratch {
                     forces: Vec::new(),
+                    collisions: Vec::new(),
                     positions: Vec::new(),
                     radii: Vec::new(),
+                    activity: PhysicsActivity::default(),
                 },
                 view_scratch: ViewScratch {
                     screen_positions: Vec::new(),
@@ -258,14 +349,158 @@ impl ViewModel {
                     draw_order_dirty: true,
                     quadtree_positions: Vec::new(),
                     quadtree_cells: Vec::new(),
+                    clusterable: Vec::new(),
+                    node_clusters: Vec::new(),
+                    cluster_of: Vec::new(),
                 },
             });
         }
 
+        if fresh {
+            self.restore_saved_layout();
+        }
+        self.apply_pins();
+        self.apply_graph_layout();
+        self.apply_static_layout();
         if let Some(cache) = &self.graph_cache {
             self.visible_node_count = cache.nodes.len();
             self.visible_edge_count = cache.edges.len();
         }
         self.graph_dirty = false;
+        self.check_graph_integrity("rebuilding the render graph");
+    }
+
+    /// Moves the nodes of a fresh render graph to their saved positions, and
+    /// new paths next to a restored neighbor, so only what changed settles.
+    pub(in crate::app) fn restore_saved_layout(&mut self) {
+        const NEIGHBOR_OFFSET: f32 = 24.0;
+
+        let Some(layout) = self.saved_layout.take() else {
+            return;
+        };
+        if !self.remember_layout && layout.file.is_none() {
+            return;
+        }
+        let Some(cache) = self.graph_cache.as_mut() else {
+            return;
+        };
+
+        let mut restored = vec![false; cache.nodes.len()];
+        for (node, restored) in cache.nodes.iter_mut().zip(&mut restored) {
+            if let Some(&position) = layout.positions.get(short_name(&node.id)) {
+                node.world_pos = position;
+                node.velocity = Vec2::ZERO;
+                *restored = true;
+            }
+        }
+
+        let restored_count = restored.iter().filter(|restored| **restored).count();
+        for index in 0..cache.nodes.len() {
+            if restored[index] || cache.nodes[index].velocity == Vec2::ZERO {
+                continue;
+            }
+            let neighbor = cache.incoming[index]
+                .iter()
+                .chain(&cache.outgoing[index])
+                .copied()
+                .find(|&neighbor| restored[neighbor]);
+            if let Some(neighbor) = neighbor {
+                let anchor = cache.nodes[neighbor].world_pos;
+                let node = &mut cache.nodes[index];
+                node.world_pos = anchor + node.velocity.normalized() * NEIGHBOR_OFFSET;
+            }
+        }
+
+        self.layout_status = Some(match &layout.file {
+            Some(file) => format!(
+                "Imported {} of {} positions from {} ({:.0}% of its paths present).",
+                restored_count,
+                cache.nodes.len(),
+                file.display(),
+                layout.overlap * 100.0
+            ),
+            None => format!(
+                "Restored {} of {} positions from the layout of {} ({:.0}% still present).",
+                restored_count,
+                cache.nodes.len(),
+                layout.root,
+                layout.overlap * 100.0
+            ),
+        });
+    }
+
+    /// Saves the current node positions for the next load of this closure.
+    pub(in crate::app) fn persist_layout(&self) -> Result<()> {
+        let Some(cache) = self.graph_cache.as_ref().filter(|_| self.remember_layout) else {
+            return Ok(());
+        };
+
+        save_layout(LayoutSnapshot {
+            root: short_name(&self.graph.root_id).to_owned(),
+            positions: cache
+                .nodes
+                .iter()
+                .map(|node| {
+                    (
+                        short_name(&node.id).to_owned(),
+                        [node.world_pos.x, node.world_pos.y],
+                    )
+                })
+                .collect(),
+        })
+    }
+
+    pub(in crate::app) fn apply_pins(&mut self) {
+        let Some(cache) = self.graph_cache.as_mut() else {
+            return;
+        };
+
+        for node in &mut cache.nodes {
+            match self.pinned_positions.get(short_name(&node.id)) {
+                Some(&position) => {
+                    node.world_pos = position;
+                    node.velocity = Vec2::ZERO;
+                    node.pinned = true;
+                }
+                None => node.pinned = false,
+            }
+        }
+    }
+
+    /// Places layout-static nodes evenly on a ring just outside the current
+    /// layout; pinned positions take precedence.
+    pub(in crate::app) fn apply_static_layout(&mut self) {
+        const RING_MARGIN: f32 = 120.0;
+
+        let Some(cache) = self.graph_cache.as_mut() else {
+            return;
+        };
+
+        let mut ring = Vec::new();
+        for (index, node) in cache.nodes.iter_mut().enumerate() {
+            node.layout_static = self.static_nodes.contains(&node.id);
+            if node.layout_static && !node.pinned {
+                ring.push(index);
+            }
+        }
+        if ring.is_empty() {
+            return;
+        }
+
+        let radius = cache
+            .nodes
+            .iter()
+            .filter(|node| !node.layout_static)
+            .map(|node| node.world_pos.length() + node.base_radius)
+            .fold(0.0_f32, f32::max)
+            + RING_MARGIN;
+        ring.sort_by(|&a, &b| cache.nodes[a].id.cmp(&cache.nodes[b].id));
+        for (slot, &index) in ring.iter().enumerate() {
+            let angle = (slot as f32 / ring.len() as f32) * std::f32::consts::TAU
+                - std::f32::consts::FRAC_PI_2;
+            let node = &mut cache.nodes[index];
+            node.world_pos = vec2(angle.cos(), angle.sin()) * radius;
+            node.velocity = Vec2::ZERO;
+        }
     }
 }
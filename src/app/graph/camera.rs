@@ -0,0 +1,191 @@
+use eframe::egui::{Rect, Vec2, vec2};
+
+use super::super::ViewModel;
+use super::super::navigation::NavigationEntry;
+
+pub(in crate::app) const MIN_ZOOM: f32 = 0.05;
+pub(in crate::app) const MAX_ZOOM: f32 = 6.0;
+/// Zoom step of the zoom shortcuts.
+pub(in crate::app) const ZOOM_STEP: f32 = 1.25;
+/// Screen pixels left around the graph when fitting it to the view.
+const FIT_MARGIN: f32 = 40.0;
+/// Focusing a node zooms in to at least this, so it is large enough to see.
+const FOCUS_MIN_ZOOM: f32 = 0.8;
+const CAMERA_ANIMATION_SECONDS: f64 = 0.4;
+
+/// Where an animated camera move ends.
+enum CameraTarget {
+    /// Follows a node, which may still be moving into place.
+    Node(String),
+    /// A fixed point in world space.
+    Point(Vec2),
+}
+
+/// A camera move eased over `CAMERA_ANIMATION_SECONDS`; panning or zooming
+/// by hand cancels it.
+pub(in crate::app) struct CameraAnimation {
+    from_center: Vec2,
+    from_zoom: f32,
+    target: CameraTarget,
+    to_zoom: f32,
+    /// Set on the first frame, so time spent before drawing does not count.
+    started: Option<f64>,
+    /// The pan and zoom set last, to notice the user taking over.
+    applied: (Vec2, f32),
+}
+
+impl ViewModel {
+    /// The world position in the middle of the canvas.
+    fn camera_center(&self) -> Vec2 {
+        -self.pan / self.zoom
+    }
+
+    fn animate_camera(&mut self, target: CameraTarget, to_zoom: f32) {
+        self.camera_animation = Some(CameraAnimation {
+            from_center: self.camera_center(),
+            from_zoom: self.zoom,
+            target,
+            to_zoom: to_zoom.clamp(MIN_ZOOM, MAX_ZOOM),
+            started: None,
+            applied: (self.pan, self.zoom),
+        });
+    }
+
+    /// Glides the camera to center `id`, zooming in if it would be drawn too
+    /// small; the move starts once `id` is drawn.
+    pub(in crate::app) fn focus_on(&mut self, id: &str) {
+        self.animate_camera(
+            CameraTarget::Node(id.to_owned()),
+            self.zoom.max(FOCUS_MIN_ZOOM),
+        );
+    }
+
+    /// Selects `id` and focuses the camera on it, drawing it first if the
+    /// filters left it out.
+    pub(in crate::app) fn reveal_node(&mut self, id: &str) {
+        self.include_node_in_current_graph(id);
+        self.set_selected(Some(id.to_owned()));
+        self.focus_on(id);
+    }
+
+    /// The selection and camera now, as the navigation history keeps them.
+    pub(in crate::app) fn navigation_entry(&self) -> NavigationEntry {
+        NavigationEntry {
+            selected: self.selected.clone(),
+            pan: self.pan,
+            zoom: self.zoom,
+        }
+    }
+
+    /// Returns to the selection and camera before the last selection change.
+    pub(in crate::app) fn navigate_back(&mut self) {
+        let current = self.navigation_entry();
+        if let Some(entry) = self.navigation.back(current) {
+            self.restore_navigation(entry);
+        }
+    }
+
+    /// Undoes the last `navigate_back`.
+    pub(in crate::app) fn navigate_forward(&mut self) {
+        let current = self.navigation_entry();
+        if let Some(entry) = self.navigation.forward(current) {
+            self.restore_navigation(entry);
+        }
+    }
+
+    /// Selects what `entry` had selected, if it is still in the closure, and
+    /// glides the camera back to where it was.
+    fn restore_navigation(&mut self, entry: NavigationEntry) {
+        let selected = entry
+            .selected
+            .filter(|id| self.graph.nodes.contains_key(id));
+        if let Some(id) = &selected {
+            self.include_node_in_current_graph(id);
+        }
+        self.replace_selection(selected);
+        self.zoom_to_point(-entry.pan / entry.zoom, entry.zoom);
+    }
+
+    /// Advances the camera animation to `now`; returns whether it is still
+    /// running.
+    pub(in crate::app) fn step_camera_animation(&mut self, now: f64) -> bool {
+        let Some(animation) = self.camera_animation.as_mut() else {
+            return false;
+        };
+        if animation.applied != (self.pan, self.zoom) {
+            self.camera_animation = None;
+            return false;
+        }
+
+        let to_center = match &animation.target {
+            CameraTarget::Point(center) => *center,
+            CameraTarget::Node(id) => {
+                let position = self.graph_cache.as_ref().and_then(|cache| {
+                    let &index = cache.index_by_id.get(id)?;
+                    Some(cache.nodes[index].world_pos)
+                });
+                match position {
+                    Some(position) => position,
+                    None => {
+                        self.camera_animation = None;
+                        return false;
+                    }
+                }
+            }
+        };
+
+        let started = *animation.started.get_or_insert(now);
+        let t = ((now - started) / CAMERA_ANIMATION_SECONDS).clamp(0.0, 1.0) as f32;
+        let eased = 1.0 - (1.0 - t).powi(3);
+        let zoom = animation.from_zoom * (animation.to_zoom / animation.from_zoom).powf(eased);
+        let center = animation.from_center + (to_center - animation.from_center) * eased;
+        self.zoom = zoom;
+        self.pan = -center * zoom;
+        animation.applied = (self.pan, self.zoom);
+
+        if t >= 1.0 {
+            self.camera_animation = None;
+            return false;
+        }
+        true
+    }
+
+    /// Glides the camera to center the world point `center` at `zoom`.
+    pub(in crate::app) fn zoom_to_point(&mut self, center: Vec2, zoom: f32) {
+        self.animate_camera(CameraTarget::Point(center), zoom);
+    }
+
+    /// Zooms by `factor` around the middle of the canvas.
+    pub(in crate::app) fn zoom_by(&mut self, factor: f32) {
+        let zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.pan *= zoom / self.zoom;
+        self.zoom = zoom;
+    }
+
+    /// The center and zoom that fit every drawn node on `canvas`.
+    pub(in crate::app) fn fitted_camera(&self, canvas: Rect) -> Option<(Vec2, f32)> {
+        let bounds = self
+            .graph_cache
+            .as_ref()?
+            .nodes
+            .iter()
+            .map(|node| Rect::from_center_size(node.world_pos.to_pos2(), Vec2::splat(1.0)))
+            .reduce(|bounds, node| bounds.union(node))?;
+
+        let available = (canvas.size() - Vec2::splat(2.0 * FIT_MARGIN)).max(vec2(1.0, 1.0));
+        let zoom = (available.x / bounds.width())
+            .min(available.y / bounds.height())
+            .clamp(MIN_ZOOM, MAX_ZOOM);
+        Some((bounds.center().to_vec2(), zoom))
+    }
+
+    /// Glides the camera so every drawn node fits on the canvas last drawn.
+    pub(in crate::app) fn fit_to_view(&mut self) {
+        if let Some((center, zoom)) = self
+            .canvas_rect
+            .and_then(|canvas| self.fitted_camera(canvas))
+        {
+            self.animate_camera(CameraTarget::Point(center), zoom);
+        }
+    }
+}
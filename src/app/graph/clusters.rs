@@ -0,0 +1,157 @@
+use eframe::egui::{Align2, Color32, FontId, Painter, Pos2, Rect, Stroke, Vec2};
+
+use crate::format::format_count;
+
+use super::super::physics::node_clusters;
+use super::super::render_utils::{RenderTheme, metric_color, world_to_screen};
+use super::super::{RenderGraph, RenderNode, ViewModel};
+
+/// Nodes are clustered only below this zoom.
+const CLUSTER_MAX_ZOOM: f32 = 0.5;
+/// Side on screen of the largest cell drawn as one cluster, reached at the
+/// lowest zoom; cells shrink toward nothing as the zoom nears
+/// [`CLUSTER_MAX_ZOOM`], so clusters split up gradually.
+const CLUSTER_CELL_PIXELS: f32 = 40.0;
+/// Nodes larger than this on screen are always drawn on their own.
+const CLUSTER_NODE_RADIUS: f32 = 6.0;
+/// Zooming into a cluster multiplies the zoom by this.
+const CLUSTER_ZOOM_FACTOR: f32 = 3.0;
+
+fn cluster_radius(members: usize) -> f32 {
+    (3.0 + (members as f32).sqrt() * 2.2).clamp(6.0, 30.0)
+}
+
+/// Folds small visible nodes into clusters when `enabled`, except those
+/// `keep` holds on to. Members are hidden and moved on screen to their
+/// cluster, so their edges end there.
+pub(super) fn cluster_small_nodes(
+    enabled: bool,
+    rect: Rect,
+    pan: Vec2,
+    zoom: f32,
+    cache: &mut RenderGraph,
+    keep: impl Fn(usize, &RenderNode) -> bool,
+) {
+    let scratch = &mut cache.view_scratch;
+    scratch.node_clusters.clear();
+    scratch.cluster_of.clear();
+    scratch.cluster_of.resize(cache.nodes.len(), None);
+    if !enabled || zoom >= CLUSTER_MAX_ZOOM {
+        return;
+    }
+
+    scratch.clusterable.clear();
+    scratch
+        .clusterable
+        .extend(cache.nodes.iter().enumerate().map(|(index, node)| {
+            scratch.visible_mask[index]
+                && scratch.screen_radii[index] <= CLUSTER_NODE_RADIUS
+                && !keep(index, node)
+        }));
+    let cell_pixels = CLUSTER_CELL_PIXELS * (1.0 - zoom / CLUSTER_MAX_ZOOM);
+    node_clusters(
+        &cache.nodes,
+        &mut scratch.quadtree_positions,
+        cell_pixels / zoom,
+        &scratch.clusterable,
+        &mut scratch.node_clusters,
+    );
+
+    for (cluster_index, cluster) in scratch.node_clusters.iter().enumerate() {
+        let center = world_to_screen(rect, pan, zoom, cluster.center);
+        for &member in &cluster.members {
+            scratch.cluster_of[member] = Some(cluster_index);
+            scratch.visible_mask[member] = false;
+            scratch.screen_positions[member] = center;
+        }
+    }
+    let visible_mask = &scratch.visible_mask;
+    scratch.visible_indices.retain(|&index| visible_mask[index]);
+}
+
+/// Whether an edge runs between two members of the same cluster.
+pub(super) fn inside_cluster(cache: &RenderGraph, src: usize, dst: usize) -> bool {
+    let cluster_of = &cache.view_scratch.cluster_of;
+    matches!(
+        (cluster_of.get(src), cluster_of.get(dst)),
+        (Some(Some(a)), Some(Some(b))) if a == b
+    )
+}
+
+/// The cluster drawn under `pointer`, if any.
+pub(super) fn cluster_at(
+    rect: Rect,
+    pan: Vec2,
+    zoom: f32,
+    cache: &RenderGraph,
+    pointer: Pos2,
+) -> Option<usize> {
+    cache.view_scratch.node_clusters.iter().position(|cluster| {
+        world_to_screen(rect, pan, zoom, cluster.center).distance(pointer)
+            <= cluster_radius(cluster.members.len())
+    })
+}
+
+/// Draws each cluster as one circle colored by its members' mean metric,
+/// with how many nodes it holds.
+pub(super) fn draw_node_clusters(
+    painter: &Painter,
+    rect: Rect,
+    pan: Vec2,
+    zoom: f32,
+    cache: &RenderGraph,
+    hovered: Option<usize>,
+    theme: &RenderTheme,
+) {
+    for (index, cluster) in cache.view_scratch.node_clusters.iter().enumerate() {
+        let center = world_to_screen(rect, pan, zoom, cluster.center);
+        let radius = cluster_radius(cluster.members.len());
+        let mean = cluster.metric_total / cluster.members.len() as u64;
+        let fill = if hovered == Some(index) {
+            Color32::from_rgb(255, 164, 101)
+        } else {
+            metric_color(mean, cache.min_metric, cache.max_metric)
+        };
+
+        painter.circle_filled(center, radius, fill.gamma_multiply(0.85));
+        painter.circle_stroke(
+            center,
+            radius,
+            Stroke::new(1.6 * theme.stroke_scale, theme.node_outline),
+        );
+        if radius >= 8.0 {
+            painter.text(
+                center,
+                Align2::CENTER_CENTER,
+                format_count(cluster.members.len() as u64),
+                FontId::proportional((radius * 0.75).clamp(8.0, 14.0)),
+                Color32::from_white_alpha(230),
+            );
+        }
+    }
+}
+
+impl ViewModel {
+    /// One line on the cluster under the pointer.
+    pub(super) fn cluster_summary(&self, cache: &RenderGraph, cluster: usize) -> Option<String> {
+        let cluster = cache.view_scratch.node_clusters.get(cluster)?;
+        Some(format!(
+            "{} paths, {} in total; click to zoom in",
+            format_count(cluster.members.len() as u64),
+            Self::format_metric_value(self.metric, cluster.metric_total)
+        ))
+    }
+
+    /// Zooms in on a cluster so its members start to separate.
+    pub(super) fn zoom_into_cluster(&mut self, cluster: usize) {
+        let Some(center) = self
+            .graph_cache
+            .as_ref()
+            .and_then(|cache| cache.view_scratch.node_clusters.get(cluster))
+            .map(|cluster| cluster.center)
+        else {
+            return;
+        };
+        self.zoom_to_point(center, self.zoom * CLUSTER_ZOOM_FACTOR);
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::{HashSet, VecDeque};
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use eframe::egui::{Color32, Painter, Pos2, Stroke, vec2};
+
+use crate::nix::SystemGraph;
+use crate::util::{short_name, stable_pair};
+
+use super::super::render_utils::RenderTheme;
+use super::super::{RenderGraph, ViewModel};
+
+/// How far from their referrer expanded dependencies start out.
+const EXPAND_OFFSET: f32 = 40.0;
+/// Nodes drawn smaller than this get no expand badge.
+const BADGE_MIN_NODE_RADIUS: f32 = 5.0;
+
+/// The center and radius of the expand badge on the upper right of a node
+/// drawn at `position`.
+pub(super) fn badge_circle(position: Pos2, radius: f32) -> (Pos2, f32) {
+    let size = (radius * 0.42).clamp(5.0, 9.0);
+    (position + vec2(radius, -radius) * FRAC_1_SQRT_2, size)
+}
+
+/// A plus on collapsed nodes, a minus on expanded ones.
+pub(super) fn draw_expand_badge(
+    painter: &Painter,
+    center: Pos2,
+    size: f32,
+    expanded: bool,
+    theme: &RenderTheme,
+) {
+    painter.circle_filled(center, size, Color32::from_rgb(46, 58, 74));
+    painter.circle_stroke(
+        center,
+        size,
+        Stroke::new(1.0 * theme.stroke_scale, theme.node_outline),
+    );
+
+    let arm = size * 0.55;
+    let stroke = Stroke::new(1.5, Color32::from_rgb(226, 232, 240));
+    painter.line_segment([center - vec2(arm, 0.0), center + vec2(arm, 0.0)], stroke);
+    if !expanded {
+        painter.line_segment([center - vec2(0.0, arm), center + vec2(0.0, arm)], stroke);
+    }
+}
+
+/// Whether render node `index` carries an expand badge: it has
+/// dependencies and is drawn large enough to click one.
+pub(super) fn shows_expand_badge(graph: &SystemGraph, cache: &RenderGraph, index: usize) -> bool {
+    cache.view_scratch.screen_radii[index] >= BADGE_MIN_NODE_RADIUS
+        && graph
+            .nodes
+            .get(&cache.nodes[index].id)
+            .is_some_and(|node| !node.references.is_empty())
+}
+
+/// Render node index of the visible expand badge under `pointer`, if any.
+pub(super) fn badge_at(graph: &SystemGraph, cache: &RenderGraph, pointer: Pos2) -> Option<usize> {
+    cache
+        .view_scratch
+        .visible_indices
+        .iter()
+        .copied()
+        .filter(|&index| shows_expand_badge(graph, cache, index))
+        .find(|&index| {
+            let (center, size) = badge_circle(
+                cache.view_scratch.screen_positions[index],
+                cache.view_scratch.screen_radii[index],
+            );
+            center.distance_sq(pointer) <= size * size
+        })
+}
+
+impl ViewModel {
+    /// The path exploration starts from: the chosen anchor while it is in the
+    /// graph, the root otherwise.
+    pub(in crate::app) fn explore_anchor_id(&self) -> &str {
+        self.explore_anchor
+            .as_deref()
+            .filter(|id| self.graph.nodes.contains_key(*id))
+            .unwrap_or(&self.graph.root_id)
+    }
+
+    /// The anchor and everything reachable from it through expanded nodes,
+    /// plus the selection; size thresholds do not apply.
+    pub(super) fn explore_node_ids(&self) -> Vec<String> {
+        let anchor = self.explore_anchor_id();
+        let mut seen = HashSet::from([anchor]);
+        let mut ids = vec![anchor.to_owned()];
+        let mut queue = VecDeque::from([anchor]);
+
+        while let Some(id) = queue.pop_front() {
+            if !self.expanded_nodes.contains(id) {
+                continue;
+            }
+            let Some(node) = self.graph.nodes.get(id) else {
+                continue;
+            };
+            for reference in &node.references {
+                if !self.hidden_on_purpose(reference) && seen.insert(reference.as_str()) {
+                    ids.push(reference.clone());
+                    queue.push_back(reference);
+                }
+            }
+        }
+
+        if let Some(selected) = &self.selected
+            && self.graph.nodes.contains_key(selected)
+            && seen.insert(selected.as_str())
+        {
+            ids.push(selected.clone());
+        }
+
+        ids
+    }
+
+    pub(in crate::app) fn toggle_expanded(&mut self, id: &str) {
+        if self.expanded_nodes.contains(id) {
+            self.expanded_nodes.remove(id);
+            self.graph_dirty = true;
+        } else {
+            self.expand_node(id);
+        }
+    }
+
+    /// Draws the direct dependencies of `id` from now on, starting them out
+    /// around it.
+    pub(in crate::app) fn expand_node(&mut self, id: &str) {
+        let drawn = self
+            .graph_cache
+            .as_ref()
+            .map(|cache| cache.index_by_id.keys().cloned().collect::<HashSet<_>>())
+            .unwrap_or_default();
+        self.expanded_nodes.insert(id.to_owned());
+        self.rebuild_render_graph();
+
+        let references = self
+            .graph
+            .nodes
+            .get(id)
+            .map(|node| node.references.clone())
+            .unwrap_or_default();
+        for reference in references {
+            let reference = self.condensed_id(&reference).to_owned();
+            if !drawn.contains(&reference) {
+                self.place_next_to(id, &reference);
+            }
+        }
+    }
+
+    /// Switches to explore mode anchored at `id`, with nothing expanded.
+    pub(in crate::app) fn explore_from(&mut self, id: &str) {
+        self.explore_mode = true;
+        self.explore_anchor = Some(id.to_owned());
+        self.expanded_nodes.clear();
+        self.graph_dirty = true;
+    }
+
+    pub(in crate::app) fn collapse_all(&mut self) {
+        self.expanded_nodes.clear();
+        self.graph_dirty = true;
+    }
+
+    /// Starts `child`, just drawn, a short way from `parent` in its own
+    /// stable direction, unless it is pinned elsewhere.
+    pub(in crate::app) fn place_next_to(&mut self, parent: &str, child: &str) {
+        if self.pinned_positions.contains_key(short_name(child)) {
+            return;
+        }
+
+        if let Some(cache) = self.graph_cache.as_mut()
+            && let Some(&parent) = cache.index_by_id.get(parent)
+            && let Some(&child_index) = cache.index_by_id.get(child)
+        {
+            let (x, y) = stable_pair(child);
+            let direction = vec2(x, y).normalized();
+            cache.nodes[child_index].world_pos =
+                cache.nodes[parent].world_pos + direction * EXPAND_OFFSET;
+        }
+    }
+}
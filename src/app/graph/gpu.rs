@@ -0,0 +1,364 @@
+//! Instanced drawing of node circles and edge segments with OpenGL, so a
+//! frame of the graph costs two draw calls instead of tessellating every
+//! shape. Highlights, labels and other decorations stay on the egui painter.
+
+use std::sync::Arc;
+
+use eframe::egui::{self, Color32, Id, PaintCallback, Pos2, Rect, Shape, Stroke};
+use eframe::egui_glow::{self, CallbackFn};
+use eframe::glow::{self, HasContext};
+
+use super::super::EdgeStyle;
+use super::super::render_utils::edge_segments;
+
+/// Floats per node instance: center, radius and outline width, then the
+/// fill and outline colors.
+const NODE_FLOATS: usize = 12;
+/// Floats per edge instance: both ends, the width and the color.
+const EDGE_FLOATS: usize = 9;
+
+fn renderer_id() -> Id {
+    Id::new("graph_gpu_renderer")
+}
+
+const NODE_VERTEX: &str = r#"
+in vec4 a_circle;
+in vec4 a_fill;
+in vec4 a_outline;
+uniform vec2 u_rect_min;
+uniform vec2 u_rect_size;
+uniform float u_pixels_per_point;
+out vec2 v_local;
+out float v_radius;
+out float v_outline_width;
+out vec4 v_fill;
+out vec4 v_outline;
+
+void main() {
+    vec2 corner = vec2(float(gl_VertexID & 1), float(gl_VertexID >> 1)) * 2.0 - 1.0;
+    float extent = a_circle.z + a_circle.w * 0.5 + 1.0 / u_pixels_per_point;
+    vec2 unit = (a_circle.xy + corner * extent - u_rect_min) / u_rect_size;
+    gl_Position = vec4(unit.x * 2.0 - 1.0, 1.0 - unit.y * 2.0, 0.0, 1.0);
+    v_local = corner * extent;
+    v_radius = a_circle.z;
+    v_outline_width = a_circle.w;
+    v_fill = a_fill;
+    v_outline = a_outline;
+}
+"#;
+
+const NODE_FRAGMENT: &str = r#"
+uniform float u_pixels_per_point;
+in vec2 v_local;
+in float v_radius;
+in float v_outline_width;
+in vec4 v_fill;
+in vec4 v_outline;
+out vec4 out_color;
+
+void main() {
+    float from_center = length(v_local);
+    float fill = clamp((v_radius - from_center) * u_pixels_per_point + 0.5, 0.0, 1.0);
+    float ring = clamp(
+        (v_outline_width * 0.5 - abs(from_center - v_radius)) * u_pixels_per_point + 0.5,
+        0.0,
+        1.0
+    );
+    vec4 color = v_fill * fill;
+    color = v_outline * ring + color * (1.0 - v_outline.a * ring);
+    if (color.a <= 0.0) {
+        discard;
+    }
+    out_color = color;
+}
+"#;
+
+const EDGE_VERTEX: &str = r#"
+in vec4 a_segment;
+in float a_width;
+in vec4 a_color;
+uniform vec2 u_rect_min;
+uniform vec2 u_rect_size;
+uniform float u_pixels_per_point;
+out float v_across;
+out float v_half_width;
+out vec4 v_color;
+
+void main() {
+    vec2 corner = vec2(float(gl_VertexID & 1), float(gl_VertexID >> 1));
+    vec2 direction = a_segment.zw - a_segment.xy;
+    float span = length(direction);
+    direction = span > 0.0 ? direction / span : vec2(1.0, 0.0);
+    float extent = a_width * 0.5 + 1.0 / u_pixels_per_point;
+    float across = (corner.y * 2.0 - 1.0) * extent;
+    vec2 position = mix(a_segment.xy, a_segment.zw, corner.x) + vec2(-direction.y, direction.x) * across;
+    vec2 unit = (position - u_rect_min) / u_rect_size;
+    gl_Position = vec4(unit.x * 2.0 - 1.0, 1.0 - unit.y * 2.0, 0.0, 1.0);
+    v_across = across;
+    v_half_width = a_width * 0.5;
+    v_color = a_color;
+}
+"#;
+
+const EDGE_FRAGMENT: &str = r#"
+uniform float u_pixels_per_point;
+in float v_across;
+in float v_half_width;
+in vec4 v_color;
+out vec4 out_color;
+
+void main() {
+    float coverage = clamp((v_half_width - abs(v_across)) * u_pixels_per_point + 0.5, 0.0, 1.0);
+    if (coverage <= 0.0) {
+        discard;
+    }
+    out_color = v_color * coverage;
+}
+"#;
+
+/// One shader program drawing a quad per instance of a buffer.
+struct InstancedPass {
+    program: glow::Program,
+    vertex_array: glow::VertexArray,
+    buffer: glow::Buffer,
+    floats_per_instance: usize,
+    u_rect_min: Option<glow::UniformLocation>,
+    u_rect_size: Option<glow::UniformLocation>,
+    u_pixels_per_point: Option<glow::UniformLocation>,
+}
+
+impl InstancedPass {
+    /// Compiles the pass; `attributes` are the per-instance inputs in
+    /// buffer order, with how many floats each takes.
+    unsafe fn new(
+        gl: &glow::Context,
+        header: &str,
+        vertex: &str,
+        fragment: &str,
+        attributes: &[(&str, i32)],
+    ) -> Result<Self, String> {
+        unsafe {
+            let program = gl.create_program()?;
+            let mut shaders = Vec::new();
+            for (kind, source) in [
+                (glow::VERTEX_SHADER, vertex),
+                (glow::FRAGMENT_SHADER, fragment),
+            ] {
+                let shader = gl.create_shader(kind)?;
+                gl.shader_source(shader, &format!("{header}{source}"));
+                gl.compile_shader(shader);
+                if !gl.get_shader_compile_status(shader) {
+                    let log = gl.get_shader_info_log(shader);
+                    gl.delete_shader(shader);
+                    gl.delete_program(program);
+                    return Err(log);
+                }
+                gl.attach_shader(program, shader);
+                shaders.push(shader);
+            }
+            for (location, (name, _)) in attributes.iter().enumerate() {
+                gl.bind_attrib_location(program, location as u32, name);
+            }
+            gl.link_program(program);
+            for shader in shaders {
+                gl.detach_shader(program, shader);
+                gl.delete_shader(shader);
+            }
+            if !gl.get_program_link_status(program) {
+                let log = gl.get_program_info_log(program);
+                gl.delete_program(program);
+                return Err(log);
+            }
+
+            let vertex_array = gl.create_vertex_array()?;
+            let buffer = gl.create_buffer()?;
+            gl.bind_vertex_array(Some(vertex_array));
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(buffer));
+            let floats_per_instance = attributes.iter().map(|(_, size)| *size as usize).sum();
+            let stride = (floats_per_instance * size_of::<f32>()) as i32;
+            let mut offset = 0;
+            for (location, (_, size)) in attributes.iter().enumerate() {
+                let location = location as u32;
+                gl.enable_vertex_attrib_array(location);
+                gl.vertex_attrib_pointer_f32(location, *size, glow::FLOAT, false, stride, offset);
+                gl.vertex_attrib_divisor(location, 1);
+                offset += size * size_of::<f32>() as i32;
+            }
+            gl.bind_vertex_array(None);
+            gl.bind_buffer(glow::ARRAY_BUFFER, None);
+
+            Ok(Self {
+                program,
+                vertex_array,
+                buffer,
+                floats_per_instance,
+                u_rect_min: gl.get_uniform_location(program, "u_rect_min"),
+                u_rect_size: gl.get_uniform_location(program, "u_rect_size"),
+                u_pixels_per_point: gl.get_uniform_location(program, "u_pixels_per_point"),
+            })
+        }
+    }
+
+    /// Uploads `instances` and draws them over `info`'s viewport, whose
+    /// rect they are placed in, in points.
+    unsafe fn draw(&self, gl: &glow::Context, info: &egui::PaintCallbackInfo, instances: &[f32]) {
+        let count = instances.len() / self.floats_per_instance;
+        if count == 0 {
+            return;
+        }
+        let bytes = instances
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect::<Vec<_>>();
+        let rect = info.viewport;
+        unsafe {
+            gl.use_program(Some(self.program));
+            gl.uniform_2_f32(self.u_rect_min.as_ref(), rect.min.x, rect.min.y);
+            gl.uniform_2_f32(self.u_rect_size.as_ref(), rect.width(), rect.height());
+            gl.uniform_1_f32(self.u_pixels_per_point.as_ref(), info.pixels_per_point);
+            gl.bind_vertex_array(Some(self.vertex_array));
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.buffer));
+            gl.buffer_data_u8_slice(glow::ARRAY_BUFFER, &bytes, glow::STREAM_DRAW);
+            gl.draw_arrays_instanced(glow::TRIANGLE_STRIP, 0, 4, count as i32);
+            gl.bind_vertex_array(None);
+            gl.bind_buffer(glow::ARRAY_BUFFER, None);
+        }
+    }
+
+    unsafe fn destroy(&self, gl: &glow::Context) {
+        unsafe {
+            gl.delete_program(self.program);
+            gl.delete_vertex_array(self.vertex_array);
+            gl.delete_buffer(self.buffer);
+        }
+    }
+}
+
+/// The node and edge passes, shared by every view through the egui context.
+pub(in crate::app) struct GraphRenderer {
+    nodes: InstancedPass,
+    edges: InstancedPass,
+}
+
+impl GraphRenderer {
+    /// Compiles the passes, if the context runs OpenGL 3.3 or OpenGL ES 3.0,
+    /// which instancing and `gl_VertexID` need.
+    fn new(gl: &glow::Context) -> Result<Self, String> {
+        let version = gl.version();
+        let header = if version.is_embedded && version.major >= 3 {
+            "#version 300 es\nprecision highp float;\n"
+        } else if !version.is_embedded && (version.major, version.minor) >= (3, 3) {
+            "#version 330 core\n"
+        } else {
+            return Err(format!(
+                "OpenGL {}.{} has no instanced drawing",
+                version.major, version.minor
+            ));
+        };
+
+        unsafe {
+            let nodes = InstancedPass::new(
+                gl,
+                header,
+                NODE_VERTEX,
+                NODE_FRAGMENT,
+                &[("a_circle", 4), ("a_fill", 4), ("a_outline", 4)],
+            )?;
+            let edges = InstancedPass::new(
+                gl,
+                header,
+                EDGE_VERTEX,
+                EDGE_FRAGMENT,
+                &[("a_segment", 4), ("a_width", 1), ("a_color", 4)],
+            )
+            .inspect_err(|_| nodes.destroy(gl))?;
+            Ok(Self { nodes, edges })
+        }
+    }
+
+    /// Releases the passes' GL objects.
+    pub(in crate::app) fn destroy(&self, gl: &glow::Context) {
+        unsafe {
+            self.nodes.destroy(gl);
+            self.edges.destroy(gl);
+        }
+    }
+}
+
+/// Compiles the renderer and makes it available to every view through `ctx`.
+pub(in crate::app) fn install_graph_renderer(
+    ctx: &egui::Context,
+    gl: &glow::Context,
+) -> Result<Arc<GraphRenderer>, String> {
+    let renderer = Arc::new(GraphRenderer::new(gl)?);
+    ctx.data_mut(|data| data.insert_temp(renderer_id(), Arc::clone(&renderer)));
+    Ok(renderer)
+}
+
+pub(super) fn graph_renderer(ctx: &egui::Context) -> Option<Arc<GraphRenderer>> {
+    ctx.data(|data| data.get_temp(renderer_id()))
+}
+
+fn rgba(color: Color32) -> [f32; 4] {
+    color.to_array().map(|channel| f32::from(channel) / 255.0)
+}
+
+/// The node circles and edge segments of one frame, in screen points.
+#[derive(Default)]
+pub(super) struct GpuBatch {
+    nodes: Vec<f32>,
+    edges: Vec<f32>,
+}
+
+impl GpuBatch {
+    pub(super) fn push_node(&mut self, center: Pos2, radius: f32, fill: Color32, outline: Stroke) {
+        self.nodes
+            .extend_from_slice(&[center.x, center.y, radius, outline.width]);
+        self.nodes.extend_from_slice(&rgba(fill));
+        self.nodes.extend_from_slice(&rgba(outline.color));
+    }
+
+    /// Adds an edge as the straight segments its style is drawn with.
+    pub(super) fn push_edge(&mut self, start: Pos2, end: Pos2, style: EdgeStyle, stroke: Stroke) {
+        let color = rgba(stroke.color);
+        edge_segments(start, end, style, |from, to| {
+            self.edges
+                .extend_from_slice(&[from.x, from.y, to.x, to.y, stroke.width]);
+            self.edges.extend_from_slice(&color);
+        });
+    }
+
+    /// A shape drawing the edges gathered so far over `rect`.
+    pub(super) fn take_edges(&mut self, renderer: &Arc<GraphRenderer>, rect: Rect) -> Shape {
+        debug_assert_eq!(self.edges.len() % EDGE_FLOATS, 0);
+        let edges = std::mem::take(&mut self.edges);
+        let renderer = Arc::clone(renderer);
+        paint_callback(rect, move |info, gl| unsafe {
+            renderer.edges.draw(gl, &info, &edges);
+        })
+    }
+
+    /// A shape drawing the nodes gathered so far over `rect`.
+    pub(super) fn take_nodes(&mut self, renderer: &Arc<GraphRenderer>, rect: Rect) -> Shape {
+        debug_assert_eq!(self.nodes.len() % NODE_FLOATS, 0);
+        let nodes = std::mem::take(&mut self.nodes);
+        let renderer = Arc::clone(renderer);
+        paint_callback(rect, move |info, gl| unsafe {
+            renderer.nodes.draw(gl, &info, &nodes);
+        })
+    }
+}
+
+fn paint_callback(
+    rect: Rect,
+    draw: impl Fn(egui::PaintCallbackInfo, &glow::Context) + Send + Sync + 'static,
+) -> Shape {
+    Shape::Callback(PaintCallback {
+        rect,
+        callback: Arc::new(CallbackFn::new(
+            move |info, painter: &egui_glow::Painter| {
+                draw(info, painter.gl());
+            },
+        )),
+    })
+}
@@ -0,0 +1,175 @@
+use eframe::egui::{Align2, Color32, FontId, Painter, Pos2, Rect, Stroke, Vec2, pos2, vec2};
+
+use super::super::render_utils::{RenderTheme, blend_color};
+use super::super::{RenderGraph, RenderNode, ViewModel};
+
+const HEAT_MARGIN: f32 = 10.0;
+const HEAT_PADDING: f32 = 8.0;
+const HEAT_ROW_HEIGHT: f32 = 17.0;
+
+pub(in crate::app) const HEAT_COOL: Color32 = Color32::from_rgb(90, 150, 235);
+pub(in crate::app) const HEAT_HOT: Color32 = Color32::from_rgb(245, 86, 60);
+pub(in crate::app) const HEAT_ASLEEP: Color32 = Color32::from_rgb(70, 84, 104);
+const HEAT_HELD: Color32 = Color32::from_gray(96);
+const FORCE_COLOR: Color32 = Color32::from_rgba_unmultiplied_const(255, 236, 180, 200);
+
+fn is_held(node: &RenderNode) -> bool {
+    node.pinned || node.layout_static
+}
+
+/// Step physics zeroes the velocity of nodes below its sleep thresholds.
+fn is_asleep(node: &RenderNode) -> bool {
+    node.velocity == Vec2::ZERO
+}
+
+/// Cool blue for slow nodes to hot red at the speed cap; the square root
+/// keeps the slow drift that stops a layout from settling visible.
+pub(super) fn heat_color(node: &RenderNode, speed_cap: f32) -> Color32 {
+    if is_held(node) {
+        HEAT_HELD
+    } else if is_asleep(node) {
+        HEAT_ASLEEP
+    } else {
+        let heat = (node.velocity.length() / speed_cap.max(0.001))
+            .sqrt()
+            .clamp(0.0, 1.0);
+        blend_color(HEAT_COOL, HEAT_HOT, heat)
+    }
+}
+
+/// Draws the net force on a node as a line leaving its rim.
+pub(super) fn draw_force_line(painter: &Painter, position: Pos2, radius: f32, force: Vec2) {
+    let magnitude = force.length();
+    if magnitude < 0.01 {
+        return;
+    }
+
+    let direction = force / magnitude;
+    let length = (magnitude.sqrt() * 2.4).min(42.0);
+    let start = position + direction * radius;
+    painter.line_segment(
+        [start, start + direction * length],
+        Stroke::new(1.3, FORCE_COLOR),
+    );
+}
+
+impl ViewModel {
+    pub(in crate::app) fn physics_heat_shown(&self) -> bool {
+        self.show_physics_heat && !self.publication_mode
+    }
+
+    fn physics_heat_rows(&self, cache: &RenderGraph) -> Vec<String> {
+        let activity = cache.physics_scratch.activity;
+        let (mut moving, mut asleep, mut held) = (0usize, 0usize, 0usize);
+        let mut peak_speed = 0.0_f32;
+        for node in &cache.nodes {
+            if is_held(node) {
+                held += 1;
+            } else if is_asleep(node) {
+                asleep += 1;
+            } else {
+                moving += 1;
+                peak_speed = peak_speed.max(node.velocity.length());
+            }
+        }
+
+        let mut rows = vec![
+            if !self.live_physics {
+                "Physics heat (live physics is off)".to_owned()
+            } else if self.background_paused {
+                "Physics heat (paused in the background)".to_owned()
+            } else if activity.parallel {
+                "Physics heat (parallel)".to_owned()
+            } else {
+                "Physics heat".to_owned()
+            },
+            format!("{moving} moving, {asleep} asleep, {held} pinned or excluded"),
+            format!(
+                "Peak speed {peak_speed:.2} of {:.1} cap",
+                activity.speed_cap
+            ),
+        ];
+
+        let sources = [
+            ("repulsion", activity.repulsion, "lower Repulsion"),
+            ("collision", activity.collision, "lower Collision"),
+            ("edge springs", activity.springs, "lower Edge spring"),
+            (
+                "centering",
+                activity.pull,
+                "lower Spread correction or raise Target spread",
+            ),
+        ];
+        let total = sources.iter().map(|(_, force, _)| force).sum::<f32>();
+        if total <= 0.0 {
+            return rows;
+        }
+
+        rows.push(format!(
+            "Forces: {}",
+            sources
+                .iter()
+                .map(|(name, force, _)| format!("{name} {:.0}%", force / total * 100.0))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+        if moving == 0 {
+            rows.push("Settled: every free node is asleep".to_owned());
+        } else if peak_speed >= activity.speed_cap * 0.95 {
+            rows.push(
+                "Nodes hit the speed cap: lower Intensity or raise Velocity damping".to_owned(),
+            );
+        } else if let Some((name, _, advice)) = sources.iter().max_by(|a, b| a.1.total_cmp(&b.1)) {
+            rows.push(format!("Mostly {name}: {advice} to settle sooner"));
+        }
+        rows
+    }
+
+    /// Summarizes node motion and the force balance in the canvas's top
+    /// right corner.
+    pub(in crate::app) fn draw_physics_heat_overlay(
+        &self,
+        painter: &Painter,
+        rect: Rect,
+        cache: &RenderGraph,
+        theme: &RenderTheme,
+    ) {
+        let rows = self.physics_heat_rows(cache);
+        let font = FontId::proportional(12.0);
+        let galleys = rows
+            .into_iter()
+            .map(|text| painter.layout_no_wrap(text, font.clone(), theme.overlay_text))
+            .collect::<Vec<_>>();
+
+        let text_width = galleys
+            .iter()
+            .map(|galley| galley.size().x)
+            .fold(0.0_f32, f32::max);
+        let size = vec2(
+            text_width + HEAT_PADDING * 2.0,
+            galleys.len() as f32 * HEAT_ROW_HEIGHT + HEAT_PADDING * 2.0,
+        );
+        let frame = Rect::from_min_size(
+            pos2(
+                rect.right() - HEAT_MARGIN - size.x,
+                rect.top() + HEAT_MARGIN,
+            ),
+            size,
+        );
+        painter.rect_filled(frame, 6.0, theme.overlay_fill);
+
+        for (row, galley) in galleys.into_iter().enumerate() {
+            let top = frame.top() + HEAT_PADDING + row as f32 * HEAT_ROW_HEIGHT;
+            let position = Align2::LEFT_CENTER
+                .align_size_within_rect(
+                    galley.size(),
+                    Rect::from_min_size(
+                        pos2(frame.left() + HEAT_PADDING, top),
+                        vec2(text_width, HEAT_ROW_HEIGHT),
+                    ),
+                )
+                .min;
+            painter.galley(position, galley, theme.overlay_text);
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use std::time::SystemTime;
+
+use crate::nix::{ContentKind, PathPreview, preview_path};
+
+use super::super::jobs::Job;
+use super::super::{HoverCard, LabelPanel, PathPreviewState, ViewModel};
+
+/// Previews fetched at once; hovering further nodes waits for these.
+const MAX_RUNNING_PREVIEWS: usize = 3;
+/// Previews kept before finished ones are dropped again.
+const MAX_CACHED_PREVIEWS: usize = 256;
+
+fn format_registered(registered: SystemTime) -> String {
+    let Ok(age) = SystemTime::now().duration_since(registered) else {
+        return "registered just now".to_owned();
+    };
+    match age.as_secs() / 86_400 {
+        0 => "registered today".to_owned(),
+        1 => "registered yesterday".to_owned(),
+        days => format!("registered {days} days ago"),
+    }
+}
+
+/// The second line of the hover readout, once a preview is in.
+fn preview_line(preview: &PathPreview) -> String {
+    let mut parts = Vec::new();
+    if preview.entry_count > 0 {
+        let mut entries = preview
+            .entries
+            .iter()
+            .map(|(name, kind)| match kind {
+                ContentKind::Directory => format!("{name}/"),
+                _ => name.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        if preview.entry_count > preview.entries.len() {
+            entries.push_str(&format!(
+                " +{} more",
+                preview.entry_count - preview.entries.len()
+            ));
+        }
+        parts.push(entries);
+    }
+    if preview.content_addressed {
+        parts.push("content-addressed".to_owned());
+    } else if preview.signatures.is_empty() {
+        parts.push("unsigned".to_owned());
+    } else {
+        let keys = preview
+            .signatures
+            .iter()
+            .map(|signature| {
+                signature
+                    .split_once(':')
+                    .map_or(signature.as_str(), |(key, _)| key)
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        parts.push(format!("signed by {keys}"));
+    }
+    if let Some(registered) = preview.registered {
+        parts.push(format_registered(registered));
+    }
+    parts.join("  |  ")
+}
+
+impl ViewModel {
+    pub(in crate::app) fn poll_path_previews(&mut self) -> bool {
+        let mut running = false;
+
+        for (id, state) in &mut self.path_previews {
+            let PathPreviewState::Running(job) = state else {
+                continue;
+            };
+
+            match job.poll() {
+                Some(result) => {
+                    if let Some(card) = self.hover_card.as_mut()
+                        && card.id == *id
+                    {
+                        card.preview = result.as_ref().ok().map(preview_line);
+                    }
+                    *state = PathPreviewState::Done(result);
+                }
+                None => running = true,
+            }
+        }
+
+        running
+    }
+
+    /// Fetches the preview of `id` in the background unless it is cached or
+    /// enough previews are already running.
+    fn prefetch_path_preview(&mut self, id: &str) {
+        if self.path_previews.contains_key(id) {
+            return;
+        }
+        let Some(node) = self.graph.nodes.get(id) else {
+            return;
+        };
+        let running = self
+            .path_previews
+            .values()
+            .filter(|state| matches!(state, PathPreviewState::Running(_)))
+            .count();
+        if running >= MAX_RUNNING_PREVIEWS {
+            return;
+        }
+        if self.path_previews.len() >= MAX_CACHED_PREVIEWS {
+            self.path_previews
+                .retain(|_, state| matches!(state, PathPreviewState::Running(_)));
+        }
+
+        let full_path = node.full_path.clone();
+        let job = Job::spawn(&self.nix_settings, move |control| {
+            preview_path(control, &full_path)
+        });
+
+        self.path_previews
+            .insert(id.to_owned(), PathPreviewState::Running(job));
+    }
+
+    /// The first line of the hover readout, from the closure query alone.
+    fn hover_summary(&self, id: &str) -> Option<String> {
+        let node = self.graph.nodes.get(id)?;
+        Some(format!(
+            "{}  |  {}  |  refs {}",
+            self.path_label(LabelPanel::Graph, &node.id),
+            Self::format_metric_value(self.metric, node.metric(self.metric)),
+            node.references.len()
+        ))
+    }
+
+    /// Keeps the hover card on render node `index`, building it only when the
+    /// hovered node, the render graph or the size metric changed.
+    pub(super) fn refresh_hover_card(&mut self, index: usize) {
+        if self.hover_card.as_ref().is_some_and(|card| {
+            card.index == index
+                && card.render_graph_revision == self.render_graph_revision
+                && card.metric == self.metric
+        }) {
+            return;
+        }
+
+        let Some(id) = self
+            .graph_cache
+            .as_ref()
+            .and_then(|cache| cache.nodes.get(index))
+            .map(|node| node.id.clone())
+        else {
+            self.hover_card = None;
+            return;
+        };
+        let Some(summary) = self.hover_summary(&id) else {
+            self.hover_card = None;
+            return;
+        };
+
+        self.prefetch_path_preview(&id);
+        let preview = match self.path_previews.get(&id) {
+            Some(PathPreviewState::Done(Ok(preview))) => Some(preview_line(preview)),
+            _ => None,
+        };
+        self.hover_card = Some(HoverCard {
+            index,
+            render_graph_revision: self.render_graph_revision,
+            metric: self.metric,
+            id,
+            summary,
+            preview,
+        });
+    }
+}
@@ -1,7 +1,13 @@
-use eframe::egui::{self, Pos2, Rect, Ui};
+use std::collections::HashMap;
 
-use super::super::ViewModel;
-use super::super::render_utils::{circle_visible, screen_to_world};
+use eframe::egui::{self, Pos2, Rect, Ui, Vec2};
+
+use crate::util::short_name;
+
+use super::super::pins::save_pins;
+use super::super::render_utils::{circle_visible, edge_hit, edge_point, screen_to_world};
+use super::super::{EdgeStyle, RenderGraph, ViewModel};
+use super::camera::{MAX_ZOOM, MIN_ZOOM};
 
 impl ViewModel {
     pub(in crate::app) fn handle_graph_zoom(
@@ -25,7 +31,7 @@ impl ViewModel {
         let world_before = screen_to_world(rect, self.pan, self.zoom, pointer);
 
         let zoom_factor = (1.0 + (scroll * 0.0018)).clamp(0.85, 1.15);
-        self.zoom = (self.zoom * zoom_factor).clamp(0.05, 6.0);
+        self.zoom = (self.zoom * zoom_factor).clamp(MIN_ZOOM, MAX_ZOOM);
         self.pan = pointer - rect.center() - (world_before * self.zoom);
     }
 
@@ -37,6 +43,115 @@ impl ViewModel {
         }
     }
 
+    /// Dragging a node with the primary button moves it, holding it still
+    /// until released; Shift pins it there, and a pinned node keeps its pin
+    /// where it is dropped. Returns true once a drag ends so the caller can
+    /// persist the pins.
+    pub(in crate::app) fn handle_node_drag(
+        cache: &mut RenderGraph,
+        dragged_node: &mut Option<String>,
+        pinned_positions: &mut HashMap<String, Vec2>,
+        response: &egui::Response,
+        zoom: f32,
+    ) -> bool {
+        if response.drag_started_by(egui::PointerButton::Primary) {
+            let press_origin = response.ctx.input(|input| input.pointer.press_origin());
+            *dragged_node = press_origin
+                .and_then(|origin| {
+                    let scratch = &cache.view_scratch;
+                    scratch
+                        .visible_indices
+                        .iter()
+                        .copied()
+                        .filter(|&index| {
+                            scratch.screen_positions[index].distance(origin)
+                                <= scratch.screen_radii[index]
+                        })
+                        .min_by(|&a, &b| {
+                            scratch.screen_positions[a]
+                                .distance(origin)
+                                .total_cmp(&scratch.screen_positions[b].distance(origin))
+                        })
+                })
+                .map(|index| cache.nodes[index].id.clone());
+        }
+
+        if response.dragged_by(egui::PointerButton::Primary)
+            && let Some(id) = dragged_node.as_deref()
+            && let Some(&index) = cache.index_by_id.get(id)
+        {
+            let node = &mut cache.nodes[index];
+            node.world_pos += response.drag_delta() / zoom;
+            node.velocity = Vec2::ZERO;
+            node.pinned = true;
+            let key = short_name(&node.id);
+            if response.ctx.input(|input| input.modifiers.shift)
+                || pinned_positions.contains_key(key)
+            {
+                pinned_positions.insert(key.to_owned(), node.world_pos);
+            }
+        }
+
+        if !response.drag_stopped() {
+            return false;
+        }
+        let Some(id) = dragged_node.take() else {
+            return false;
+        };
+        if let Some(&index) = cache.index_by_id.get(&id) {
+            cache.nodes[index].pinned = pinned_positions.contains_key(short_name(&id));
+        }
+        true
+    }
+
+    pub(in crate::app) fn set_pinned(&mut self, id: &str, pinned: bool) {
+        let key = short_name(id);
+        if pinned {
+            let position = self
+                .graph_cache
+                .as_ref()
+                .and_then(|cache| Some(cache.nodes[*cache.index_by_id.get(id)?].world_pos))
+                .unwrap_or(Vec2::ZERO);
+            self.pinned_positions.insert(key.to_owned(), position);
+        } else {
+            self.pinned_positions.remove(key);
+        }
+
+        self.apply_pins();
+        self.persist_pins();
+    }
+
+    pub(in crate::app) fn unpin_all(&mut self) {
+        self.pinned_positions.clear();
+        self.apply_pins();
+        self.persist_pins();
+    }
+
+    pub(in crate::app) fn set_layout_static(&mut self, id: &str, layout_static: bool) {
+        if layout_static {
+            self.static_nodes.insert(id.to_owned());
+        } else {
+            self.static_nodes.remove(id);
+        }
+        self.apply_static_layout();
+    }
+
+    pub(in crate::app) fn release_static_nodes(&mut self) {
+        self.static_nodes.clear();
+        self.apply_static_layout();
+    }
+
+    pub(in crate::app) fn persist_pins(&mut self) {
+        // Workspace layouts are independent of the saved full-graph layout.
+        if self.is_workspace {
+            return;
+        }
+
+        self.pins_status = save_pins(&self.pinned_positions)
+            .err()
+            .map(|error| format!("Failed to save pins: {error:#}"));
+    }
+
     pub(in crate::app) fn visible_indices_into(
         rect: Rect,
         screen_positions: &[Pos2],
@@ -78,6 +193,47 @@ impl ViewModel {
         })
     }
 
+    /// The edge under the pointer, with how far along it the pointer is.
+    /// Edges within a cluster are not picked.
+    pub(in crate::app) fn hovered_edge(
+        ui: &Ui,
+        edges: &[(usize, usize)],
+        visible_mask: &[bool],
+        screen_positions: &[Pos2],
+        edge_style: EdgeStyle,
+    ) -> Option<(usize, f32)> {
+        const EDGE_PICK_RADIUS: f32 = 5.0;
+
+        let pointer = ui.input(|input| input.pointer.hover_pos())?;
+        edges
+            .iter()
+            .enumerate()
+            .filter_map(|(index, &(source, target))| {
+                let source_visible = visible_mask.get(source).copied().unwrap_or(false);
+                let target_visible = visible_mask.get(target).copied().unwrap_or(false);
+                if !source_visible && !target_visible {
+                    return None;
+                }
+
+                let start = *screen_positions.get(source)?;
+                let end = *screen_positions.get(target)?;
+                let bounds = Rect::from_two_pos(start, end)
+                    .union(Rect::from_center_size(
+                        edge_point(start, end, edge_style, 0.5),
+                        Vec2::ZERO,
+                    ))
+                    .expand(EDGE_PICK_RADIUS);
+                if start == end || !bounds.contains(pointer) {
+                    return None;
+                }
+
+                let (along, distance) = edge_hit(start, end, edge_style, pointer);
+                (distance <= EDGE_PICK_RADIUS).then_some((index, along, distance))
+            })
+            .min_by(|a, b| a.2.total_cmp(&b.2))
+            .map(|(index, along, _)| (index, along))
+    }
+
     pub(in crate::app) fn apply_graph_selection(&mut self, selected: Option<String>) {
         self.set_selected(selected);
     }
@@ -0,0 +1,304 @@
+use eframe::egui::{Align2, Color32, FontId, Painter, Pos2, Rect, Shape, Stroke, pos2, vec2};
+
+use crate::nix::{PathChange, RootMembership, SizeMetric};
+
+use super::super::render_utils::{
+    RenderTheme, diff_color, draw_pin_glyph, metric_color, node_class_color, root_color,
+    share_color,
+};
+use super::super::{LabelPanel, RenderGraph, ViewModel};
+use super::bridges::{BRIDGE_DASH, BRIDGE_GAP, bridge_color};
+use super::heat::{HEAT_ASLEEP, HEAT_COOL, HEAT_HOT};
+use super::view::CYCLE_COLOR;
+
+const LEGEND_MARGIN: f32 = 10.0;
+const LEGEND_PADDING: f32 = 8.0;
+const LEGEND_ROW_HEIGHT: f32 = 17.0;
+const LEGEND_SWATCH_WIDTH: f32 = 26.0;
+
+enum Swatch {
+    Gradient(Color32, Color32),
+    Fill(Color32),
+    Ring(Color32),
+    Dot(Color32),
+    Line(Color32),
+    Dashed(Color32),
+    Pin,
+    None,
+}
+
+fn metric_label(metric: SizeMetric) -> &'static str {
+    match metric {
+        SizeMetric::NarSize => "NAR size",
+        SizeMetric::ClosureSize => "closure size",
+        SizeMetric::ClosureCount => "closure path count",
+        SizeMetric::Dependencies => "dependency count",
+        SizeMetric::ReverseDependencies => "reverse dependency count",
+        SizeMetric::RetainedSize => "retained size",
+    }
+}
+
+impl ViewModel {
+    /// Explains the encodings that are active right now, so screenshots of
+    /// the canvas stay self-explanatory.
+    fn legend_rows(
+        &self,
+        cache: &RenderGraph,
+        selection_active: bool,
+        theme: &RenderTheme,
+    ) -> Vec<(Swatch, String)> {
+        let mut rows = vec![(
+            Swatch::Gradient(
+                metric_color(cache.min_metric, cache.min_metric, cache.max_metric),
+                metric_color(cache.max_metric, cache.min_metric, cache.max_metric),
+            ),
+            format!(
+                "Size{}: {} (log scale, {} to {})",
+                if self.root_coloring()
+                    || self.color_by_dominator_share
+                    || self.physics_heat_shown()
+                {
+                    ""
+                } else {
+                    " and color"
+                },
+                metric_label(self.metric),
+                Self::format_metric_value(self.metric, cache.min_metric),
+                Self::format_metric_value(self.metric, cache.max_metric)
+            ),
+        )];
+
+        if self.physics_heat_shown() {
+            rows.push((
+                Swatch::Gradient(HEAT_COOL, HEAT_HOT),
+                "Color: speed, from slow to the speed cap".to_owned(),
+            ));
+            rows.push((Swatch::Fill(HEAT_ASLEEP), "Asleep".to_owned()));
+            rows.push((
+                Swatch::Line(Color32::from_rgb(255, 236, 180)),
+                "Line: net force on the node".to_owned(),
+            ));
+        } else if self.color_by_dominator_share {
+            rows.push((
+                Swatch::Gradient(share_color(0.0), share_color(1.0)),
+                "Color: share of the immediate dominator's retained size, 0% to 100%".to_owned(),
+            ));
+        } else if self.root_coloring() {
+            for (index, root) in self.graph.roots.iter().enumerate() {
+                rows.push((
+                    Swatch::Fill(root_color(RootMembership::Only(index))),
+                    format!("Only in {}", self.path_label(LabelPanel::Graph, root)),
+                ));
+            }
+            if self.graph.roots.len() > 2 {
+                rows.push((
+                    Swatch::Fill(root_color(RootMembership::Partial)),
+                    "In some roots".to_owned(),
+                ));
+            }
+            rows.push((
+                Swatch::Fill(root_color(RootMembership::Shared)),
+                "Shared by all roots".to_owned(),
+            ));
+        }
+
+        if selection_active {
+            rows.push((
+                Swatch::Fill(Color32::from_rgb(245, 206, 93)),
+                "Gold: selection and its path from the root".to_owned(),
+            ));
+            rows.push((
+                Swatch::Fill(Color32::from_rgb(246, 137, 92)),
+                "Orange: direct dependencies and referrers".to_owned(),
+            ));
+        }
+
+        let query = self.search.trim();
+        if !query.is_empty() && self.selected.is_none() {
+            rows.push((
+                Swatch::Fill(Color32::from_rgb(103, 196, 255)),
+                format!("Blue: matches for \"{query}\""),
+            ));
+        }
+
+        for class in self.badge_classes.iter() {
+            if self.graph.class_count(class) > 0 {
+                rows.push((
+                    Swatch::Dot(node_class_color(class)),
+                    format!("Badge: {}", class.label()),
+                ));
+            }
+        }
+
+        if !cache.bridge_edges.is_empty() {
+            rows.push((
+                Swatch::Dashed(bridge_color(theme)),
+                "Dashed: reaches through hidden paths".to_owned(),
+            ));
+        }
+        if self.verifications.values().any(|state| state.is_mismatch()) {
+            rows.push((
+                Swatch::Ring(Color32::from_rgb(236, 98, 92)),
+                "Red ring: NAR verification mismatch".to_owned(),
+            ));
+        }
+        if self.condense_cycles && !self.cycles.is_empty() {
+            rows.push((
+                Swatch::Ring(CYCLE_COLOR),
+                "Teal ring: reference cycle drawn as one node".to_owned(),
+            ));
+        }
+        if !self.marked.is_empty() {
+            rows.push((
+                Swatch::Ring(Color32::from_rgb(168, 120, 230)),
+                "Violet ring: marked (Ctrl+click)".to_owned(),
+            ));
+        }
+        if cache.nodes.iter().any(|node| node.pinned) {
+            rows.push((Swatch::Pin, "Pinned".to_owned()));
+        }
+        if cache.nodes.iter().any(|node| node.layout_static) {
+            rows.push((
+                Swatch::Ring(Color32::from_gray(150)),
+                "Excluded from physics".to_owned(),
+            ));
+        }
+        if self.highlight_diff
+            && let Some(diff) = self.closure_diff()
+        {
+            let since = self.path_label(LabelPanel::Graph, &diff.base_root);
+            for (change, delta, text) in [
+                (PathChange::Added, 1, "Green ring: new since"),
+                (
+                    PathChange::Changed(String::new()),
+                    1,
+                    "Red ring: grew since",
+                ),
+                (
+                    PathChange::Changed(String::new()),
+                    -1,
+                    "Blue ring: shrank since",
+                ),
+            ] {
+                rows.push((
+                    Swatch::Ring(diff_color(&change, delta)),
+                    format!("{text} {since}"),
+                ));
+            }
+        }
+
+        rows.push((
+            Swatch::Line(Color32::from_gray(150)),
+            format!(
+                "Edges ({}): from a path to what it references",
+                self.edge_style.label().to_lowercase()
+            ),
+        ));
+
+        for binding in [
+            "Click: select a node or an edge midpoint",
+            "Drag a node: move it, Shift+drag: pin it",
+            "Right click a node: actions menu",
+            "Click a +/- badge: expand or collapse (explore mode)",
+            "Right/middle drag: pan, scroll: zoom",
+            "M: measure a dragged rectangle",
+            "?: all keyboard shortcuts",
+        ] {
+            rows.push((Swatch::None, binding.to_owned()));
+        }
+
+        rows
+    }
+
+    pub(in crate::app) fn draw_legend_overlay(
+        &self,
+        painter: &Painter,
+        rect: Rect,
+        cache: &RenderGraph,
+        selection_active: bool,
+        theme: &RenderTheme,
+    ) {
+        let rows = self.legend_rows(cache, selection_active, theme);
+        let font = FontId::proportional(12.0);
+        let text_color = theme.overlay_text;
+        let galleys = rows
+            .iter()
+            .map(|(_, text)| painter.layout_no_wrap(text.clone(), font.clone(), text_color))
+            .collect::<Vec<_>>();
+
+        let text_width = galleys
+            .iter()
+            .map(|galley| galley.size().x)
+            .fold(0.0_f32, f32::max);
+        let size = vec2(
+            LEGEND_SWATCH_WIDTH + text_width + LEGEND_PADDING * 2.0,
+            rows.len() as f32 * LEGEND_ROW_HEIGHT + LEGEND_PADDING * 2.0,
+        );
+        let frame = Rect::from_min_size(
+            pos2(
+                rect.left() + LEGEND_MARGIN,
+                rect.bottom() - LEGEND_MARGIN - size.y,
+            ),
+            size,
+        );
+        painter.rect_filled(frame, 6.0, theme.overlay_fill);
+
+        for (row, ((swatch, _), galley)) in rows.iter().zip(galleys).enumerate() {
+            let top = frame.top() + LEGEND_PADDING + row as f32 * LEGEND_ROW_HEIGHT;
+            let center = pos2(
+                frame.left() + LEGEND_PADDING + LEGEND_SWATCH_WIDTH * 0.4,
+                top + LEGEND_ROW_HEIGHT * 0.5,
+            );
+            draw_swatch(painter, center, swatch, theme);
+            painter.galley(
+                Align2::LEFT_CENTER
+                    .align_size_within_rect(
+                        galley.size(),
+                        Rect::from_min_size(
+                            pos2(frame.left() + LEGEND_PADDING + LEGEND_SWATCH_WIDTH, top),
+                            vec2(text_width, LEGEND_ROW_HEIGHT),
+                        ),
+                    )
+                    .min,
+                galley,
+                text_color,
+            );
+        }
+    }
+}
+
+fn draw_swatch(painter: &Painter, center: Pos2, swatch: &Swatch, theme: &RenderTheme) {
+    match *swatch {
+        Swatch::Gradient(low, high) => {
+            painter.circle_filled(center - vec2(5.0, 0.0), 3.0, low);
+            painter.circle_filled(center + vec2(4.0, 0.0), 6.0, high);
+        }
+        Swatch::Fill(color) => {
+            painter.circle_filled(center, 5.5, color);
+        }
+        Swatch::Ring(color) => {
+            painter.circle_stroke(center, 5.5, Stroke::new(1.6, color));
+        }
+        Swatch::Dot(color) => {
+            painter.circle_filled(center, 3.0, color);
+        }
+        Swatch::Line(color) => {
+            painter.line_segment(
+                [center - vec2(8.0, 0.0), center + vec2(8.0, 0.0)],
+                Stroke::new(1.4, color),
+            );
+        }
+        Swatch::Dashed(color) => {
+            painter.extend(Shape::dashed_line(
+                &[center - vec2(8.0, 0.0), center + vec2(8.0, 0.0)],
+                Stroke::new(1.4, color),
+                BRIDGE_DASH * 0.5,
+                BRIDGE_GAP * 0.5,
+            ));
+        }
+        Swatch::Pin => {
+            draw_pin_glyph(painter, center + vec2(-2.0, 5.0), 9.0, theme);
+        }
+        Swatch::None => {}
+    }
+}
@@ -0,0 +1,226 @@
+use eframe::egui::{
+    self, Align2, Color32, FontId, Painter, Rect, Stroke, StrokeKind, Vec2, pos2, vec2,
+};
+
+use crate::format::{format_bytes, format_count};
+
+use super::super::render_utils::{RenderTheme, screen_to_world, world_to_screen};
+use super::super::{LabelPanel, RenderGraph, ViewModel};
+
+const RULER_WIDTH: f32 = 18.0;
+/// The left ruler is wider to fit its horizontal labels.
+const RULER_LEFT_WIDTH: f32 = 40.0;
+const RULER_MIN_TICK_SPACING: f32 = 70.0;
+const MEASURE_MARGIN: f32 = 10.0;
+const MEASURE_PADDING: f32 = 8.0;
+const MEASURE_ROW_HEIGHT: f32 = 17.0;
+const MEASURE_COLOR: Color32 = Color32::from_rgb(120, 220, 170);
+
+/// The smallest 1, 2 or 5 times a power of ten world units that leaves
+/// `RULER_MIN_TICK_SPACING` pixels between ticks at `zoom`.
+fn ruler_step(zoom: f32) -> f32 {
+    let minimum = RULER_MIN_TICK_SPACING / zoom.max(0.001);
+    let magnitude = 10.0_f32.powf(minimum.log10().floor());
+    [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .map(|factor| factor * magnitude)
+        .find(|step| *step >= minimum)
+        .unwrap_or(magnitude * 10.0)
+}
+
+impl ViewModel {
+    /// Primary drags span the measuring rectangle while measure mode is on;
+    /// Escape clears it.
+    pub(in crate::app) fn handle_measure_drag(&mut self, rect: Rect, response: &egui::Response) {
+        if response.drag_started_by(egui::PointerButton::Primary)
+            && let Some(origin) = response.ctx.input(|input| input.pointer.press_origin())
+        {
+            let start = screen_to_world(rect, self.pan, self.zoom, origin);
+            self.measure_rect = Some([start, start]);
+        }
+
+        if response.dragged_by(egui::PointerButton::Primary)
+            && let Some(pointer) = response.interact_pointer_pos()
+            && let Some([_, end]) = self.measure_rect.as_mut()
+        {
+            *end = screen_to_world(rect, self.pan, self.zoom, pointer);
+        }
+
+        if response.hovered()
+            && response
+                .ctx
+                .input(|input| input.key_pressed(egui::Key::Escape))
+        {
+            self.measure_rect = None;
+        }
+    }
+
+    fn measurement_rows(&self, cache: &RenderGraph, [start, end]: [Vec2; 2]) -> Vec<String> {
+        let min = start.min(end);
+        let max = start.max(end);
+        let size = max - min;
+
+        let mut count = 0usize;
+        let mut nar_total = 0u64;
+        let mut largest: Option<(&str, u64)> = None;
+        for node in &cache.nodes {
+            let position = node.world_pos;
+            if position.x < min.x || position.y < min.y || position.x > max.x || position.y > max.y
+            {
+                continue;
+            }
+            count += 1;
+
+            let nar_size = self
+                .condensed_cycle(&node.id)
+                .unwrap_or(std::slice::from_ref(&node.id))
+                .iter()
+                .filter_map(|id| self.graph.nodes.get(id))
+                .map(|record| record.nar_size)
+                .sum::<u64>();
+            nar_total += nar_size;
+            if largest.is_none_or(|(_, size)| nar_size > size) {
+                largest = Some((&node.id, nar_size));
+            }
+        }
+
+        let mut rows = vec![
+            format!(
+                "{:.0} × {:.0} world units, {:.0} across",
+                size.x,
+                size.y,
+                size.length()
+            ),
+            format!(
+                "{} of {} drawn nodes inside, {} NAR in total",
+                format_count(count as u64),
+                format_count(cache.nodes.len() as u64),
+                format_bytes(nar_total)
+            ),
+        ];
+        if let Some((id, size)) = largest {
+            rows.push(format!(
+                "Largest: {} ({})",
+                self.path_label(LabelPanel::Graph, id),
+                format_bytes(size)
+            ));
+        }
+        rows
+    }
+
+    /// Rulers along the top and left edge in world units, plus the measuring
+    /// rectangle and its readout.
+    pub(in crate::app) fn draw_measurement(
+        &self,
+        painter: &Painter,
+        rect: Rect,
+        cache: &RenderGraph,
+        theme: &RenderTheme,
+    ) {
+        self.draw_rulers(painter, rect, theme);
+
+        let Some(measure_rect) = self.measure_rect else {
+            return;
+        };
+        let [start, end] = measure_rect;
+        let screen_rect = Rect::from_two_pos(
+            world_to_screen(rect, self.pan, self.zoom, start),
+            world_to_screen(rect, self.pan, self.zoom, end),
+        );
+        painter.rect(
+            screen_rect,
+            0.0,
+            MEASURE_COLOR.gamma_multiply(0.12),
+            Stroke::new(1.4, MEASURE_COLOR),
+            StrokeKind::Middle,
+        );
+
+        let font = FontId::proportional(12.0);
+        let galleys = self
+            .measurement_rows(cache, measure_rect)
+            .into_iter()
+            .map(|text| painter.layout_no_wrap(text, font.clone(), theme.overlay_text))
+            .collect::<Vec<_>>();
+        let text_width = galleys
+            .iter()
+            .map(|galley| galley.size().x)
+            .fold(0.0_f32, f32::max);
+        let size = vec2(
+            text_width + MEASURE_PADDING * 2.0,
+            galleys.len() as f32 * MEASURE_ROW_HEIGHT + MEASURE_PADDING * 2.0,
+        );
+        let frame = Rect::from_min_size(
+            pos2(
+                rect.left() + RULER_LEFT_WIDTH + MEASURE_MARGIN,
+                rect.bottom() - MEASURE_MARGIN - size.y,
+            ),
+            size,
+        );
+        painter.rect_filled(frame, 6.0, theme.overlay_fill);
+        for (row, galley) in galleys.into_iter().enumerate() {
+            let top = frame.top() + MEASURE_PADDING + row as f32 * MEASURE_ROW_HEIGHT;
+            painter.galley(
+                pos2(frame.left() + MEASURE_PADDING, top),
+                galley,
+                theme.overlay_text,
+            );
+        }
+    }
+
+    fn draw_rulers(&self, painter: &Painter, rect: Rect, theme: &RenderTheme) {
+        let step = ruler_step(self.zoom);
+        let font = FontId::monospace(10.0);
+        let stroke = Stroke::new(1.0, theme.overlay_text);
+        let top = Rect::from_min_size(rect.min, vec2(rect.width(), RULER_WIDTH));
+        let left = Rect::from_min_size(rect.min, vec2(RULER_LEFT_WIDTH, rect.height()));
+        painter.rect_filled(top, 0.0, theme.overlay_fill);
+        painter.rect_filled(left, 0.0, theme.overlay_fill);
+
+        let world_min = screen_to_world(rect, self.pan, self.zoom, rect.min);
+        let world_max = screen_to_world(rect, self.pan, self.zoom, rect.max);
+
+        let mut x = (world_min.x / step).ceil() * step;
+        while x <= world_max.x {
+            let screen_x = world_to_screen(rect, self.pan, self.zoom, vec2(x, 0.0)).x;
+            if screen_x > left.right() {
+                painter.line_segment(
+                    [
+                        pos2(screen_x, top.bottom() - 6.0),
+                        pos2(screen_x, top.bottom()),
+                    ],
+                    stroke,
+                );
+                painter.text(
+                    pos2(screen_x + 3.0, top.top() + 2.0),
+                    Align2::LEFT_TOP,
+                    format!("{:.0}", x + 0.0),
+                    font.clone(),
+                    theme.overlay_text,
+                );
+            }
+            x += step;
+        }
+
+        let mut y = (world_min.y / step).ceil() * step;
+        while y <= world_max.y {
+            let screen_y = world_to_screen(rect, self.pan, self.zoom, vec2(0.0, y)).y;
+            if screen_y > top.bottom() {
+                painter.line_segment(
+                    [
+                        pos2(left.right() - 6.0, screen_y),
+                        pos2(left.right(), screen_y),
+                    ],
+                    stroke,
+                );
+                painter.text(
+                    pos2(left.left() + 2.0, screen_y + 2.0),
+                    Align2::LEFT_TOP,
+                    format!("{:.0}", y + 0.0),
+                    font.clone(),
+                    theme.overlay_text,
+                );
+            }
+            y += step;
+        }
+    }
+}
@@ -1,3 +1,18 @@
+mod annotate;
+mod bridges;
 mod build;
+mod camera;
+mod clusters;
+mod explore;
+mod gpu;
+mod heat;
+mod hover;
 mod interaction;
+mod legend;
+mod measure;
+mod quadtree_overlay;
+mod validate;
 mod view;
+
+pub(super) use camera::{CameraAnimation, ZOOM_STEP};
+pub(super) use gpu::{GraphRenderer, install_graph_renderer};
@@ -0,0 +1,127 @@
+use eframe::egui::{Align2, Color32, FontId, Painter, Pos2, Rect, Stroke, StrokeKind, Vec2, vec2};
+
+use crate::format::format_count;
+
+use super::super::physics::QuadtreeCell;
+use super::super::render_utils::{RenderTheme, blend_color, screen_to_world, world_to_screen};
+use super::super::{QuadtreeShading, ViewModel};
+use super::heat::{HEAT_COOL, HEAT_HOT};
+
+const CELL_LINE: Color32 = Color32::from_rgb(106, 198, 255);
+
+fn cell_contains(cell: &QuadtreeCell, point: Vec2) -> bool {
+    (point.x - cell.center.x).abs() <= cell.half_extent
+        && (point.y - cell.center.y).abs() <= cell.half_extent
+}
+
+fn cell_value(cell: &QuadtreeCell, shading: QuadtreeShading) -> f32 {
+    match shading {
+        QuadtreeShading::Outline => 0.0,
+        QuadtreeShading::Occupancy => cell.count as f32,
+        QuadtreeShading::Size => cell.metric_total as f32,
+    }
+}
+
+/// Per-area value of a cell, which stays comparable across tree depths.
+fn cell_density(cell: &QuadtreeCell, shading: QuadtreeShading) -> f32 {
+    let side = (cell.half_extent * 2.0).max(1.0);
+    cell_value(cell, shading) / (side * side)
+}
+
+fn cell_screen_rect(rect: Rect, pan: Vec2, zoom: f32, cell: &QuadtreeCell) -> Rect {
+    let half = vec2(cell.half_extent, cell.half_extent);
+    Rect::from_min_max(
+        world_to_screen(rect, pan, zoom, cell.center - half),
+        world_to_screen(rect, pan, zoom, cell.center + half),
+    )
+}
+
+/// Draws the quadtree partition, filling leaf cells by node count or size
+/// metric per area.
+pub(super) fn draw_quadtree_overlay(
+    painter: &Painter,
+    rect: Rect,
+    pan: Vec2,
+    zoom: f32,
+    cells: &[QuadtreeCell],
+    shading: QuadtreeShading,
+) {
+    let screen_rect = |cell: &QuadtreeCell| cell_screen_rect(rect, pan, zoom, cell);
+
+    if shading != QuadtreeShading::Outline {
+        let max_density = cells
+            .iter()
+            .filter(|cell| cell.is_leaf)
+            .map(|cell| cell_density(cell, shading))
+            .fold(0.0_f32, f32::max);
+        if max_density > 0.0 {
+            for cell in cells.iter().filter(|cell| cell.is_leaf) {
+                let heat = (cell_density(cell, shading) / max_density).sqrt();
+                let [r, g, b, _] = blend_color(HEAT_COOL, HEAT_HOT, heat).to_array();
+                painter.rect_filled(
+                    screen_rect(cell),
+                    0.0,
+                    Color32::from_rgba_unmultiplied(r, g, b, (24.0 + heat * 96.0) as u8),
+                );
+            }
+        }
+    }
+
+    for cell in cells {
+        let alpha = if cell.is_leaf { 110 } else { 55 };
+        let line_width: f32 = (1.4_f32 - (cell.depth as f32 * 0.09_f32)).clamp(0.45_f32, 1.4_f32);
+        let [r, g, b, _] = CELL_LINE.to_array();
+        painter.rect_stroke(
+            screen_rect(cell),
+            0.0,
+            Stroke::new(line_width, Color32::from_rgba_unmultiplied(r, g, b, alpha)),
+            StrokeKind::Middle,
+        );
+    }
+}
+
+impl ViewModel {
+    /// Outlines the leaf cell under `pointer` and lists what it holds.
+    pub(super) fn draw_quadtree_cell_info(
+        &self,
+        painter: &Painter,
+        rect: Rect,
+        cells: &[QuadtreeCell],
+        pointer: Pos2,
+        theme: &RenderTheme,
+    ) {
+        let world = screen_to_world(rect, self.pan, self.zoom, pointer);
+        let Some(cell) = cells
+            .iter()
+            .filter(|cell| cell_contains(cell, world))
+            .max_by_key(|cell| cell.depth)
+        else {
+            return;
+        };
+
+        painter.rect_stroke(
+            cell_screen_rect(rect, self.pan, self.zoom, cell),
+            0.0,
+            Stroke::new(1.8, CELL_LINE),
+            StrokeKind::Middle,
+        );
+        let text = format!(
+            "Depth {}, {:.0} units wide\n{} nodes, {}",
+            cell.depth,
+            cell.half_extent * 2.0,
+            format_count(cell.count as u64),
+            Self::format_metric_value(self.metric, cell.metric_total)
+        );
+        let galley = painter.layout_no_wrap(text, FontId::proportional(12.0), theme.overlay_text);
+        let frame =
+            Rect::from_min_size(pointer + vec2(16.0, 16.0), galley.size() + vec2(12.0, 8.0));
+        painter.rect_filled(frame, 4.0, theme.overlay_fill);
+        painter.galley(
+            Align2::LEFT_TOP
+                .align_size_within_rect(galley.size(), frame.shrink2(vec2(6.0, 4.0)))
+                .min,
+            galley,
+            theme.overlay_text,
+        );
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::HashSet;
+
+use super::super::{RenderGraph, ViewModel};
+
+impl RenderGraph {
+    fn integrity_errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        let node_count = self.nodes.len();
+
+        if self.index_by_id.len() != node_count {
+            errors.push(format!(
+                "index_by_id has {} entries for {node_count} nodes",
+                self.index_by_id.len()
+            ));
+        }
+        for (index, node) in self.nodes.iter().enumerate() {
+            if self.index_by_id.get(&node.id) != Some(&index) {
+                errors.push(format!("index_by_id does not map {} to {index}", node.id));
+            }
+        }
+
+        if let Some(root) = self.root_index
+            && root >= node_count
+        {
+            errors.push(format!("root index {root} is out of bounds"));
+        }
+
+        if self.outgoing.len() != node_count || self.incoming.len() != node_count {
+            errors.push(format!(
+                "adjacency lists have {}/{} entries for {node_count} nodes",
+                self.outgoing.len(),
+                self.incoming.len()
+            ));
+            return errors;
+        }
+
+        if self.edge_weights.len() != self.edges.len() {
+            errors.push(format!(
+                "{} edge weights for {} edges",
+                self.edge_weights.len(),
+                self.edges.len()
+            ));
+        }
+
+        let mut edges = HashSet::with_capacity(self.edges.len());
+        for &(source, target) in &self.edges {
+            if source >= node_count || target >= node_count {
+                errors.push(format!("edge ({source}, {target}) is out of bounds"));
+                continue;
+            }
+            if source == target {
+                errors.push(format!("edge ({source}, {target}) is a self loop"));
+            }
+            if !edges.insert((source, target)) {
+                errors.push(format!("edge ({source}, {target}) is duplicated"));
+            }
+        }
+
+        for &(source, target) in &self.bridge_edges {
+            if source >= node_count || target >= node_count {
+                errors.push(format!("bridge edge ({source}, {target}) is out of bounds"));
+            }
+        }
+
+        let outgoing = self
+            .outgoing
+            .iter()
+            .enumerate()
+            .flat_map(|(source, targets)| targets.iter().map(move |&target| (source, target)))
+            .collect::<HashSet<_>>();
+        let incoming = self
+            .incoming
+            .iter()
+            .enumerate()
+            .flat_map(|(target, sources)| sources.iter().map(move |&source| (source, target)))
+            .collect::<HashSet<_>>();
+        if outgoing != edges {
+            errors.push("outgoing adjacency does not match the edge list".to_owned());
+        }
+        if incoming != edges {
+            errors.push("incoming adjacency does not match the edge list".to_owned());
+        }
+
+        errors
+    }
+}
+
+impl ViewModel {
+    /// Panics with every broken invariant when validation is enabled, which is
+    /// always the case in debug builds and with `--validate`.
+    pub(in crate::app) fn check_graph_integrity(&mut self, operation: &str) {
+        if !self.validate_graph {
+            return;
+        }
+
+        let mut errors = Vec::new();
+        if !self.graph_validated {
+            errors.extend(self.graph.integrity_errors());
+            self.graph_validated = true;
+        }
+        if let Some(cache) = &self.graph_cache {
+            errors.extend(cache.integrity_errors());
+        }
+
+        assert!(
+            errors.is_empty(),
+            "graph integrity check failed after {operation}:\n{}",
+            errors.join("\n")
+        );
+    }
+}
@@ -1,18 +1,39 @@
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::time::Duration;
 
-use eframe::egui::{self, Align2, Color32, FontId, Sense, Stroke, Ui, Vec2, vec2};
+use eframe::egui::{self, Align2, Color32, FontId, Sense, Shape, Stroke, Ui, Vec2, vec2};
 use fuzzy_matcher::FuzzyMatcher;
 use fuzzy_matcher::skim::SkimMatcherV2;
 
-use crate::util::short_name;
+use crate::nix::SystemGraph;
+use crate::util::{parse_store_name, short_name};
 
+use super::super::glyphs::{GLYPH_MIN_RADIUS, NodeGlyph, draw_node_glyph};
 use super::super::highlight::build_highlight_state_for_selected_id;
+use super::super::image_export::ImageExtent;
 use super::super::physics::{quadtree_cells, step_physics};
 use super::super::render_utils::{
-    blend_color, dim_color, draw_background, edge_visible, metric_color, world_to_screen,
+    RenderTheme, blend_color, diff_color, dim_color, draw_background, draw_class_badges, draw_edge,
+    draw_edge_arrow, draw_node_label, draw_partial_edge, draw_pin_glyph, edge_visible,
+    edge_weight_scale, metric_color, root_color, share_color, store_badge, world_to_screen,
 };
-use super::super::{PhysicsConfig, ViewModel};
+use super::super::{ComparisonState, LabelPanel, PhysicsConfig, ViewModel};
+use super::bridges::draw_bridge_edges;
+use super::clusters::{cluster_at, cluster_small_nodes, draw_node_clusters, inside_cluster};
+use super::explore::{badge_at, badge_circle, draw_expand_badge, shows_expand_badge};
+use super::gpu::{GpuBatch, graph_renderer};
+use super::heat::{draw_force_line, heat_color};
+use super::quadtree_overlay::draw_quadtree_overlay;
+
+/// Below this many screen pixels per frame the layout counts as idle.
+const IDLE_SCREEN_SPEED: f32 = 0.05;
+const IDLE_REPAINT_INTERVAL: Duration = Duration::from_millis(250);
+pub(super) const CYCLE_COLOR: Color32 = Color32::from_rgb(86, 204, 182);
+/// Share of an edge at either end where a click selects that endpoint.
+const EDGE_END_SHARE: f32 = 0.3;
+/// Arrowheads are drawn from this zoom up.
+const EDGE_ARROW_MIN_ZOOM: f32 = 0.6;
 
 fn fuzzy_match_score(matcher: &SkimMatcherV2, text: &str, query: &str) -> Option<i64> {
     matcher
@@ -20,6 +41,31 @@ fn fuzzy_match_score(matcher: &SkimMatcherV2, text: &str, query: &str) -> Option
         .or_else(|| matcher.fuzzy_match(&text.to_ascii_lowercase(), &query.to_ascii_lowercase()))
 }
 
+/// Whether path `id` matches a search. `pname:` and `version:` queries
+/// compare against the derivation's metadata, falling back to the parsed
+/// store name; a version matches by prefix. Anything else fuzzy-matches the
+/// store name.
+fn matches_search(graph: &SystemGraph, matcher: &SkimMatcherV2, id: &str, query: &str) -> bool {
+    let derivation = graph
+        .nodes
+        .get(id)
+        .and_then(|node| node.derivation.as_deref());
+    let store_name = parse_store_name(short_name(id));
+    if let Some(wanted) = query.strip_prefix("pname:").map(str::trim) {
+        let pname = derivation
+            .and_then(|info| info.pname.as_deref())
+            .unwrap_or(store_name.pname);
+        return pname.eq_ignore_ascii_case(wanted);
+    }
+    if let Some(wanted) = query.strip_prefix("version:").map(str::trim) {
+        return derivation
+            .and_then(|info| info.version.as_deref())
+            .or(store_name.version)
+            .is_some_and(|version| version.starts_with(wanted));
+    }
+    fuzzy_match_score(matcher, short_name(id), query).is_some()
+}
+
 impl ViewModel {
     fn update_screen_space(
         rect: egui::Rect,
@@ -89,6 +135,10 @@ impl ViewModel {
             return Some(Arc::clone(&cached.matches));
         }
 
+        if self.background_paused {
+            return None;
+        }
+
         let cache = self.graph_cache.as_ref()?;
         let matcher = SkimMatcherV2::default();
         let matches = cache
@@ -96,7 +146,7 @@ impl ViewModel {
             .iter()
             .enumerate()
             .filter_map(|(index, node)| {
-                if fuzzy_match_score(&matcher, short_name(&node.id), search_query).is_some() {
+                if matches_search(&self.graph, &matcher, &node.id, search_query) {
                     Some(index)
                 } else {
                     None
@@ -114,23 +164,72 @@ impl ViewModel {
         Some(matches)
     }
 
+    /// Every path in the graph, drawn or not, whose name matches the search
+    /// the same way the canvas highlights do.
+    pub(in crate::app) fn search_matching_ids(&self) -> Vec<String> {
+        let search_query = self.search.trim();
+        if search_query.is_empty() {
+            return Vec::new();
+        }
+
+        let matcher = SkimMatcherV2::default();
+        self.graph
+            .nodes
+            .keys()
+            .filter(|id| matches_search(&self.graph, &matcher, id, search_query))
+            .cloned()
+            .collect()
+    }
+
     pub(in crate::app) fn draw_graph(&mut self, ui: &mut Ui) {
         if self.graph_dirty {
             self.rebuild_render_graph();
         }
 
         let (rect, response) = ui.allocate_exact_size(ui.available_size(), Sense::click_and_drag());
+        self.canvas_rect = Some(rect);
         let painter = ui.painter_at(rect);
 
-        draw_background(&painter, rect, self.pan, self.zoom);
+        // An image export captures what this frame paints, drawn without
+        // instancing or hover text, and for the whole graph with the camera
+        // fitted to it until the frame is done.
+        let image_request = self.image_export_request.take();
+        let first_export_shape = ui.ctx().graphics(|layers| {
+            layers
+                .get(painter.layer_id())
+                .map_or(0, |shapes| shapes.next_idx().0)
+        });
+        let restore_camera = image_request
+            .as_ref()
+            .filter(|request| request.extent == ImageExtent::World)
+            .and_then(|_| self.fitted_camera(rect))
+            .map(|(center, zoom)| {
+                let camera = (self.pan, self.zoom);
+                self.zoom = zoom;
+                self.pan = -center * zoom;
+                camera
+            });
+
+        let theme = RenderTheme::for_mode(self.publication_mode);
+        draw_background(&painter, rect, self.pan, self.zoom, theme);
 
         self.handle_graph_zoom(ui, rect, &response);
         self.handle_graph_pan(&response);
+        if self.step_camera_animation(ui.input(|input| input.time)) {
+            self.frame_rate_cap.request_frame(ui.ctx());
+        }
+        if self.annotation_tool.is_some() {
+            self.handle_annotation_drag(rect, &response);
+        } else if self.measure_mode {
+            self.handle_measure_drag(rect, &response);
+        }
 
         let pseudo_matches = self.cached_pseudo_matches();
         let pan = self.pan;
         let zoom = self.zoom;
-        let show_quadtree_overlay = self.show_quadtree_overlay;
+        let show_quadtree_overlay = self.show_quadtree_overlay && theme.show_overlays;
+        let physics_heat = self.physics_heat_shown();
+        let layout_frozen = self.layout_frozen();
         let interaction_active = response.dragged();
         let frame_delta_seconds = ui
             .ctx()
@@ -145,8 +244,15 @@ impl ViewModel {
             target_spread: self.physics_target_spread,
             spread_force: self.physics_spread_force,
             delta_seconds: frame_delta_seconds,
+            parallel: self.parallel_physics,
         };
 
+        let impact_front = self.impact_pulse_front(ui.input(|input| input.time));
+        let glyph_atlas = self.node_glyphs.texture(ui.ctx());
+        let gpu_renderer = (self.gpu_rendering && image_request.is_none())
+            .then(|| graph_renderer(ui.ctx()))
+            .flatten();
+
         let Some(cache) = self.graph_cache.as_mut() else {
             self.visible_node_count = 0;
             self.visible_edge_count = 0;
@@ -155,12 +261,24 @@ impl ViewModel {
         };
 
         let mut physics_moving = false;
-        if self.live_physics {
+        if self.live_physics && !self.background_paused && !layout_frozen {
             physics_moving = step_physics(cache, physics);
         }
 
-        if physics_moving || interaction_active {
-            ui.ctx().request_repaint();
+        let visibly_moving = physics_moving
+            && (!self.idle_throttle || {
+                let max_speed_sq = cache
+                    .nodes
+                    .iter()
+                    .map(|node| node.velocity.length_sq())
+                    .fold(0.0_f32, f32::max);
+                max_speed_sq.sqrt() * zoom >= IDLE_SCREEN_SPEED
+            });
+
+        if visibly_moving || interaction_active {
+            self.frame_rate_cap.request_frame(ui.ctx());
+        } else if physics_moving {
+            ui.ctx().request_repaint_after(IDLE_REPAINT_INTERVAL);
         }
 
         Self::update_screen_space(rect, pan, zoom, cache);
@@ -182,33 +300,44 @@ impl ViewModel {
         }
         self.visible_node_count = cache.view_scratch.visible_indices.len();
 
+        let highlight = self
+            .selected
+            .as_ref()
+            .and_then(|id| build_highlight_state_for_selected_id(&self.graph, cache, id));
+        cluster_small_nodes(
+            self.cluster_at_low_zoom,
+            rect,
+            pan,
+            zoom,
+            cache,
+            |index, node| {
+                node.pinned
+                    || self.selected.as_deref() == Some(node.id.as_str())
+                    || self.marked.contains(&node.id)
+                    || highlight.as_ref().is_some_and(|state| {
+                        state.related_nodes.contains(&index)
+                            || state.root_path_nodes.contains(&index)
+                    })
+                    || pseudo_matches
+                        .as_ref()
+                        .is_some_and(|matches| matches.contains(&index))
+            },
+        );
+
         if show_quadtree_overlay {
             quadtree_cells(
                 &cache.nodes,
                 &mut cache.view_scratch.quadtree_positions,
                 &mut cache.view_scratch.quadtree_cells,
             );
-            for cell in &cache.view_scratch.quadtree_cells {
-                let min = cell.center - vec2(cell.half_extent, cell.half_extent);
-                let max = cell.center + vec2(cell.half_extent, cell.half_extent);
-                let top_left = world_to_screen(rect, self.pan, self.zoom, vec2(min.x, min.y));
-                let top_right = world_to_screen(rect, self.pan, self.zoom, vec2(max.x, min.y));
-                let bottom_right = world_to_screen(rect, self.pan, self.zoom, vec2(max.x, max.y));
-                let bottom_left = world_to_screen(rect, self.pan, self.zoom, vec2(min.x, max.y));
-
-                let alpha = if cell.is_leaf { 110 } else { 55 };
-                let line_width: f32 =
-                    (1.4_f32 - (cell.depth as f32 * 0.09_f32)).clamp(0.45_f32, 1.4_f32);
-                let stroke = Stroke::new(
-                    line_width,
-                    Color32::from_rgba_unmultiplied(106, 198, 255, alpha),
-                );
-
-                painter.line_segment([top_left, top_right], stroke);
-                painter.line_segment([top_right, bottom_right], stroke);
-                painter.line_segment([bottom_right, bottom_left], stroke);
-                painter.line_segment([bottom_left, top_left], stroke);
-            }
+            draw_quadtree_overlay(
+                &painter,
+                rect,
+                pan,
+                zoom,
+                &cache.view_scratch.quadtree_cells,
+                self.quadtree_shading,
+            );
         }
 
         let hovered = Self::hovered_index(
@@ -218,26 +347,93 @@ impl ViewModel {
             &cache.view_scratch.screen_radii,
         );
 
-        if hovered.is_some() {
+        let hovered_cluster = hovered
+            .is_none()
+            .then(|| response.hover_pos())
+            .flatten()
+            .and_then(|pointer| cluster_at(rect, pan, zoom, cache, pointer));
+
+        let hovered_edge = (response.hovered()
+            && !interaction_active
+            && hovered.is_none()
+            && hovered_cluster.is_none())
+        .then(|| {
+            Self::hovered_edge(
+                ui,
+                &cache.edges,
+                &cache.view_scratch.visible_mask,
+                &cache.view_scratch.screen_positions,
+                self.edge_style,
+            )
+        })
+        .flatten();
+
+        if hovered.is_some() || hovered_cluster.is_some() || hovered_edge.is_some() {
             ui.output_mut(|output| {
                 output.cursor_icon = egui::CursorIcon::PointingHand;
             });
         }
 
+        let pins_changed = !self.measure_mode
+            && self.annotation_tool.is_none()
+            && Self::handle_node_drag(
+                cache,
+                &mut self.dragged_node,
+                &mut self.pinned_positions,
+                &response,
+                zoom,
+            );
+
+        let badge_click = response.clicked_by(egui::PointerButton::Primary) && self.explore_mode;
+        let pending_expand_toggle = badge_click
+            .then(|| response.interact_pointer_pos())
+            .flatten()
+            .and_then(|pointer| badge_at(&self.graph, cache, pointer))
+            .map(|index| cache.nodes[index].id.clone());
+
+        let clicked =
+            response.clicked_by(egui::PointerButton::Primary) && pending_expand_toggle.is_none();
+        let toggle_mark = clicked && ui.input(|input| input.modifiers.command);
+        let pending_cluster_zoom = hovered_cluster.filter(|_| clicked);
+        // Clicking near either end of an edge selects that endpoint; the
+        // middle selects the edge itself.
+        let mut pending_edge_selection = None;
+        let mut pending_endpoint = None;
+        if clicked && let Some((index, along)) = hovered_edge {
+            let (source, target) = cache.edges[index];
+            let source_id = cache.nodes[source].id.clone();
+            let target_id = cache.nodes[target].id.clone();
+            if along < EDGE_END_SHARE {
+                pending_endpoint = Some(source_id);
+            } else if along > 1.0 - EDGE_END_SHARE {
+                pending_endpoint = Some(target_id);
+            } else {
+                pending_edge_selection = Some((source_id, target_id));
+            }
+        }
+
         let pending_selection =
-            if response.clicked_by(egui::PointerButton::Primary) {
-                Some(hovered.and_then(|(index, _distance)| {
-                    cache.nodes.get(index).map(|node| node.id.clone())
-                }))
+            if clicked && pending_edge_selection.is_none() && pending_cluster_zoom.is_none() {
+                Some(
+                    hovered
+                        .and_then(|(index, _distance)| {
+                            cache.nodes.get(index).map(|node| node.id.clone())
+                        })
+                        .or(pending_endpoint),
+                )
             } else {
                 None
             };
 
+        let selected_edge_indices = self.selected_edge.as_ref().and_then(|(source, target)| {
+            Some((
+                *cache.index_by_id.get(source)?,
+                *cache.index_by_id.get(target)?,
+            ))
+        });
+
         let hovered_index = hovered.map(|(index, _)| index);
-        let highlight = self
-            .selected
-            .as_ref()
-            .and_then(|id| build_highlight_state_for_selected_id(&self.graph, cache, id));
+        let hovered_edge_pair = hovered_edge.and_then(|(index, _)| cache.edges.get(index).copied());
         let selection_active = highlight.as_ref().is_some_and(|state| {
             !state.related_nodes.is_empty()
                 || !state.related_edges.is_empty()
@@ -262,7 +458,10 @@ impl ViewModel {
         let density_cell_size = (28.0 + (1.0 - edge_detail) * 20.0).clamp(28.0, 52.0);
         let mut edge_density_by_cell: HashMap<u64, u16> = HashMap::new();
         for &(src, dst) in &cache.edges {
-            if src >= cache.nodes.len() || dst >= cache.nodes.len() {
+            if src >= cache.nodes.len()
+                || dst >= cache.nodes.len()
+                || inside_cluster(cache, src, dst)
+            {
                 continue;
             }
 
@@ -292,9 +491,18 @@ impl ViewModel {
             *entry = entry.saturating_add(1);
         }
 
+        draw_bridge_edges(&painter, rect, cache, self.zoom, theme);
+
+        // Instanced circles and edges are drawn in the slots reserved here,
+        // beneath what the painter adds after them.
+        let mut gpu_batch = gpu_renderer.as_ref().map(|_| GpuBatch::default());
+        let edge_slot = painter.add(Shape::Noop);
         let mut visible_edge_count = 0usize;
-        for &(src, dst) in &cache.edges {
-            if src >= cache.nodes.len() || dst >= cache.nodes.len() {
+        for (&(src, dst), &weight) in cache.edges.iter().zip(&cache.edge_weights) {
+            if src >= cache.nodes.len()
+                || dst >= cache.nodes.len()
+                || inside_cluster(cache, src, dst)
+            {
                 continue;
             }
 
@@ -325,7 +533,10 @@ impl ViewModel {
                 (false, false)
             };
 
-            let highlighted_edge = is_root_path_edge || is_related_edge;
+            let is_selected_edge = selected_edge_indices == Some((src, dst));
+            let is_hovered_edge = hovered_edge_pair == Some((src, dst));
+            let highlighted_edge =
+                is_root_path_edge || is_related_edge || is_selected_edge || is_hovered_edge;
             if !highlighted_edge {
                 let mid = start + (end - start) * 0.5;
                 let cell_x = ((mid.x - rect.left()) / density_cell_size).floor() as i32;
@@ -362,7 +573,18 @@ impl ViewModel {
                 }
             }
 
-            let (line_width, line_color) = if is_root_path_edge {
+            let weight_scale = edge_weight_scale(weight, cache.max_edge_weight);
+            let (line_width, line_color) = if is_selected_edge {
+                (
+                    (3.8 * zoom_sqrt).clamp(2.0, 6.4),
+                    Color32::from_rgb(245, 206, 93),
+                )
+            } else if is_hovered_edge {
+                (
+                    (3.0 * zoom_sqrt).clamp(1.6, 5.2),
+                    Color32::from_rgb(255, 164, 101),
+                )
+            } else if is_root_path_edge {
                 (
                     (3.3 * zoom_sqrt).clamp(1.7, 5.8),
                     Color32::from_rgb(246, 206, 104),
@@ -374,27 +596,77 @@ impl ViewModel {
                 )
             } else if highlight.is_some() {
                 let edge_alpha = (120.0 + edge_detail * 48.0) as u8;
+                let [r, g, b, _] = theme.dimmed_edge.to_array();
                 (
-                    (0.82 * zoom_sqrt).clamp(0.45, 2.0),
-                    Color32::from_rgba_unmultiplied(80, 90, 104, edge_alpha),
+                    (0.82 * zoom_sqrt).clamp(0.45, 2.0) * weight_scale,
+                    Color32::from_rgba_unmultiplied(r, g, b, edge_alpha),
                 )
             } else {
                 let edge_alpha = (160.0 + edge_detail * 56.0) as u8;
+                let [r, g, b, _] = theme.edge.to_array();
                 (
-                    (1.18 * zoom_sqrt).clamp(0.60, 3.4),
-                    Color32::from_rgba_unmultiplied(72, 72, 72, edge_alpha),
+                    (1.18 * zoom_sqrt).clamp(0.60, 3.4) * weight_scale,
+                    Color32::from_rgba_unmultiplied(r, g, b, edge_alpha),
                 )
             };
 
-            painter.line_segment([start, end], Stroke::new(line_width, line_color));
+            let stroke = Stroke::new(line_width * theme.stroke_scale, line_color);
+            if let Some(batch) = gpu_batch.as_mut() {
+                batch.push_edge(start, end, self.edge_style, stroke);
+            } else {
+                draw_edge(&painter, start, end, self.edge_style, stroke);
+            }
+            if self.show_edge_arrows && zoom >= EDGE_ARROW_MIN_ZOOM {
+                draw_edge_arrow(
+                    &painter,
+                    start,
+                    end,
+                    self.edge_style,
+                    cache.view_scratch.screen_radii[dst],
+                    (line_width * 2.2 + 3.0 * zoom_sqrt).clamp(4.0, 14.0),
+                    line_color,
+                );
+            }
             visible_edge_count += 1;
         }
         self.visible_edge_count = visible_edge_count;
+        if let (Some(renderer), Some(batch)) = (&gpu_renderer, gpu_batch.as_mut()) {
+            painter.set(edge_slot, batch.take_edges(renderer, rect));
+        }
+
+        let impact = impact_front.zip(self.impact_pulse.as_ref());
+        let pulse_color = Color32::from_rgb(255, 228, 150);
+        if let Some((front, pulse)) = impact {
+            let hop = front.floor() as u32;
+            let progress = front.fract() as f32;
+            let stroke = Stroke::new((2.6 * zoom_sqrt).clamp(1.4, 4.6), pulse_color);
+            for &(src, dst) in &cache.edges {
+                if pulse.hops.get(dst) != Some(&hop) || pulse.hops.get(src) != Some(&(hop + 1)) {
+                    continue;
+                }
+
+                // The pulse travels from a path to the paths referencing it.
+                let start = cache.view_scratch.screen_positions[dst];
+                let end = cache.view_scratch.screen_positions[src];
+                if edge_visible(rect, start, end, 2.5) {
+                    draw_partial_edge(&painter, start, end, self.edge_style, progress, stroke);
+                }
+            }
+        }
+
+        draw_node_clusters(&painter, rect, pan, zoom, cache, hovered_cluster, theme);
 
         let selected_color = Color32::from_rgb(245, 206, 93);
         let mut selection_animating = false;
+        let diff = match &self.comparison {
+            Some(ComparisonState::Done(Ok(diff))) if self.highlight_diff => Some(diff),
+            _ => None,
+        };
 
+        let root_coloring = self.color_by_root && self.graph.is_multi_root();
+        let share_coloring = self.color_by_dominator_share;
         Self::ensure_draw_order(cache);
+        let node_slot = painter.add(Shape::Noop);
         for index in cache.view_scratch.draw_order.iter().copied() {
             if !cache
                 .view_scratch
@@ -422,8 +694,16 @@ impl ViewModel {
                 .as_ref()
                 .is_some_and(|matches| matches.contains(&index));
 
-            let base_color =
-                metric_color(render_node.metric_value, cache.min_metric, cache.max_metric);
+            let base_color = if physics_heat {
+                heat_color(render_node, cache.physics_scratch.activity.speed_cap)
+            } else if share_coloring {
+                share_color(self.graph.dominator_share(&render_node.id).unwrap_or(1.0))
+            } else {
+                match root_coloring.then(|| self.graph.membership(&render_node.id)) {
+                    Some(Some(membership)) => root_color(membership),
+                    _ => metric_color(render_node.metric_value, cache.min_metric, cache.max_metric),
+                }
+            };
             let unselected_color = if is_hovered {
                 Color32::from_rgb(255, 164, 101)
             } else if is_root_path {
@@ -450,7 +730,20 @@ impl ViewModel {
 
             let color = blend_color(unselected_color, selected_color, selection_mix);
 
-            painter.circle_filled(position, radius, color);
+            let stroke_width = if is_root_path {
+                1.8
+            } else if is_pseudo_match {
+                1.55
+            } else {
+                1.0
+            } + (selection_mix * 1.2);
+            let outline = Stroke::new(stroke_width * theme.stroke_scale, theme.node_outline);
+            if let Some(batch) = gpu_batch.as_mut() {
+                batch.push_node(position, radius, color, outline);
+            } else {
+                painter.circle_filled(position, radius, color);
+                painter.circle_stroke(position, radius, outline);
+            }
             if selection_mix > 0.0 {
                 let halo_strength = (selection_mix * (1.0 - selection_mix) * 4.0).clamp(0.0, 1.0);
                 let halo_alpha = (30.0 + (halo_strength * 145.0)) as u8;
@@ -464,21 +757,124 @@ impl ViewModel {
                 );
             }
 
-            let stroke_width = if is_root_path {
-                1.8
-            } else if is_pseudo_match {
-                1.55
-            } else {
-                1.0
-            } + (selection_mix * 1.2);
-            painter.circle_stroke(
-                position,
-                radius,
-                Stroke::new(
-                    stroke_width,
-                    Color32::from_rgba_unmultiplied(15, 15, 15, 190),
-                ),
-            );
+            if let Some(node) = self.graph.nodes.get(&render_node.id) {
+                if self.show_node_glyphs
+                    && radius >= GLYPH_MIN_RADIUS
+                    && let Some(glyph) = NodeGlyph::of(short_name(&node.id), node.classes)
+                {
+                    draw_node_glyph(&painter, glyph_atlas, glyph, position, radius, color);
+                }
+                let badges = node.classes.intersection(self.badge_classes);
+                if !badges.is_empty() && radius >= 4.0 {
+                    draw_class_badges(&painter, position, radius, badges, theme);
+                }
+            }
+
+            if self.explore_mode && shows_expand_badge(&self.graph, cache, index) {
+                let (center, size) = badge_circle(position, radius);
+                draw_expand_badge(
+                    &painter,
+                    center,
+                    size,
+                    self.expanded_nodes.contains(&render_node.id),
+                    theme,
+                );
+            }
+
+            if physics_heat
+                && !render_node.pinned
+                && !render_node.layout_static
+                && let Some(&force) = cache.physics_scratch.forces.get(index)
+            {
+                draw_force_line(&painter, position, radius, force);
+            }
+
+            if render_node.layout_static {
+                painter.circle_stroke(
+                    position,
+                    radius + 3.0,
+                    Stroke::new(1.0, Color32::from_gray(150)),
+                );
+            }
+
+            // Nodes are held while dragged; only saved pins get the glyph.
+            if render_node.pinned
+                && self
+                    .pinned_positions
+                    .contains_key(short_name(&render_node.id))
+            {
+                draw_pin_glyph(
+                    &painter,
+                    position + vec2(0.0, -radius),
+                    (radius * 0.6).clamp(6.0, 11.0),
+                    theme,
+                );
+            }
+
+            if let Some((front, pulse)) = impact
+                && let Some(&hop) = pulse.hops.get(index)
+                && hop != u32::MAX
+                && (0.0..1.0).contains(&(front - f64::from(hop)))
+            {
+                let wave = (front - f64::from(hop)) as f32;
+                painter.circle_stroke(
+                    position,
+                    radius + 3.0 + wave * 9.0,
+                    Stroke::new(
+                        2.0,
+                        Color32::from_rgba_unmultiplied(
+                            255,
+                            228,
+                            150,
+                            ((1.0 - wave) * 220.0) as u8,
+                        ),
+                    ),
+                );
+            }
+
+            if let Some(entry) = diff.and_then(|diff| diff.entry(&render_node.id)) {
+                painter.circle_stroke(
+                    position,
+                    radius + 4.5,
+                    Stroke::new(
+                        2.2 * theme.stroke_scale,
+                        diff_color(&entry.change, entry.closure_delta),
+                    ),
+                );
+            }
+
+            let leads_condensed_cycle = self.condense_cycles
+                && self
+                    .cycle_by_id
+                    .get(&render_node.id)
+                    .is_some_and(|&cycle| self.cycles[cycle][0] == render_node.id);
+            if leads_condensed_cycle {
+                painter.circle_stroke(
+                    position,
+                    radius + 5.5,
+                    Stroke::new(2.0 * theme.stroke_scale, CYCLE_COLOR),
+                );
+            }
+
+            if self.marked.contains(&render_node.id) {
+                painter.circle_stroke(
+                    position,
+                    radius + 6.5,
+                    Stroke::new(2.0, Color32::from_rgb(168, 120, 230)),
+                );
+            }
+
+            if self
+                .verifications
+                .get(&render_node.id)
+                .is_some_and(|state| state.is_mismatch())
+            {
+                painter.circle_stroke(
+                    position,
+                    radius + 2.5,
+                    Stroke::new(2.0, Color32::from_rgb(236, 98, 92)),
+                );
+            }
 
             let highlighted = is_selected || is_root_path || is_related;
             let should_draw_label = highlighted
@@ -487,40 +883,178 @@ impl ViewModel {
                 || radius > 17.0
                 || self.zoom > 1.35;
             if should_draw_label {
-                painter.text(
+                let store_chip = (!self.store_dirs.is_empty())
+                    .then(|| self.graph.nodes.get(&render_node.id))
+                    .flatten()
+                    .map(|node| store_badge(&node.store_dir));
+                draw_node_label(
+                    &painter,
                     position + vec2(radius + 5.0, 0.0),
-                    Align2::LEFT_CENTER,
-                    short_name(&render_node.id),
-                    FontId::proportional(12.0),
-                    Color32::from_gray(238),
+                    self.store_hashes.label(LabelPanel::Graph, &render_node.id),
+                    store_chip,
+                    self.label_pname_only,
+                    theme,
                 );
             }
         }
 
-        if selection_animating {
-            ui.ctx().request_repaint();
+        if let (Some(renderer), Some(batch)) = (&gpu_renderer, gpu_batch.as_mut()) {
+            painter.set(node_slot, batch.take_nodes(renderer, rect));
         }
 
-        if let Some((hovered_index, _)) = hovered
-            && let Some(node) = self.graph.nodes.get(&cache.nodes[hovered_index].id)
+        if selection_animating || impact.is_some() {
+            self.frame_rate_cap.request_frame(ui.ctx());
+        }
+
+        if response.hovered() {
+            self.handle_bookmark_keys(ui);
+        }
+        if ui.input(|input| input.key_pressed(egui::Key::F1)) {
+            self.show_legend_overlay ^= true;
+        }
+        if response.hovered()
+            && !ui.ctx().wants_keyboard_input()
+            && ui.input(|input| input.modifiers.is_none() && input.key_pressed(egui::Key::M))
+        {
+            self.measure_mode ^= true;
+            self.annotation_tool = None;
+        }
+        let hover_overlays = theme.show_overlays && image_request.is_none();
+        if let Some(index) = hovered_index
+            && hover_overlays
         {
-            let panel_text = format!(
-                "{}  |  {}  |  refs {}",
-                short_name(&node.id),
-                Self::format_metric_value(self.metric, node.metric(self.metric)),
-                node.references.len()
+            self.refresh_hover_card(index);
+            if let Some(card) = &self.hover_card {
+                painter.text(
+                    rect.left_top() + vec2(10.0, 10.0),
+                    Align2::LEFT_TOP,
+                    &card.summary,
+                    FontId::proportional(13.0),
+                    theme.overlay_text,
+                );
+                if let Some(preview) = &card.preview {
+                    painter.text(
+                        rect.left_top() + vec2(10.0, 28.0),
+                        Align2::LEFT_TOP,
+                        preview,
+                        FontId::proportional(12.0),
+                        theme.overlay_text.gamma_multiply(0.75),
+                    );
+                }
+            }
+        } else if let Some((source, target)) = hovered_edge_pair
+            && hover_overlays
+            && let Some(cache) = self.graph_cache.as_ref()
+        {
+            painter.text(
+                rect.left_top() + vec2(10.0, 10.0),
+                Align2::LEFT_TOP,
+                format!(
+                    "{} \u{2192} {}",
+                    self.path_label(LabelPanel::Graph, &cache.nodes[source].id),
+                    self.path_label(LabelPanel::Graph, &cache.nodes[target].id)
+                ),
+                FontId::proportional(13.0),
+                theme.overlay_text,
             );
+            painter.text(
+                rect.left_top() + vec2(10.0, 28.0),
+                Align2::LEFT_TOP,
+                "Click near an end to select that path, or the middle for edge details.",
+                FontId::proportional(12.0),
+                theme.overlay_text.gamma_multiply(0.75),
+            );
+        } else if let Some(cluster) = hovered_cluster
+            && hover_overlays
+            && let Some(summary) = self
+                .graph_cache
+                .as_ref()
+                .and_then(|cache| self.cluster_summary(cache, cluster))
+        {
             painter.text(
                 rect.left_top() + vec2(10.0, 10.0),
                 Align2::LEFT_TOP,
-                panel_text,
+                summary,
                 FontId::proportional(13.0),
-                Color32::from_gray(240),
+                theme.overlay_text,
+            );
+        }
+        if response.secondary_clicked() {
+            self.context_node = hovered_index
+                .zip(self.graph_cache.as_ref())
+                .map(|(index, cache)| cache.nodes[index].id.clone());
+        }
+        if let Some(id) = self.context_node.clone() {
+            response.context_menu(|ui| self.node_context_menu(ui, &id));
+        }
+        if show_quadtree_overlay
+            && hover_overlays
+            && hovered_index.is_none()
+            && let Some(pointer) = response.hover_pos()
+            && let Some(cache) = self.graph_cache.as_ref()
+        {
+            self.draw_quadtree_cell_info(
+                &painter,
+                rect,
+                &cache.view_scratch.quadtree_cells,
+                pointer,
+                theme,
             );
         }
+        if self.show_annotations {
+            self.draw_annotations(&painter, rect, theme);
+        }
+        if self.measure_mode
+            && let Some(cache) = self.graph_cache.as_ref()
+        {
+            self.draw_measurement(&painter, rect, cache, theme);
+        }
+        if self.show_legend_overlay
+            && let Some(cache) = self.graph_cache.as_ref()
+        {
+            self.draw_legend_overlay(&painter, rect, cache, selection_active, theme);
+        }
+
+        if physics_heat && let Some(cache) = self.graph_cache.as_ref() {
+            self.draw_physics_heat_overlay(&painter, rect, cache, theme);
+        }
 
-        if let Some(selected) = pending_selection {
-            self.apply_graph_selection(selected);
+        if let Some(request) = image_request {
+            let shapes = ui.ctx().graphics(|layers| {
+                layers
+                    .get(painter.layer_id())
+                    .map(|shapes| {
+                        shapes
+                            .all_entries()
+                            .skip(first_export_shape)
+                            .cloned()
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default()
+            });
+            self.write_image_export(ui.ctx(), &request, rect, shapes);
+            if let Some((pan, zoom)) = restore_camera {
+                self.pan = pan;
+                self.zoom = zoom;
+                ui.ctx().request_repaint();
+            }
+        }
+
+        if pins_changed {
+            self.persist_pins();
+        }
+
+        if let Some(id) = pending_expand_toggle {
+            self.toggle_expanded(&id);
+        } else if let Some(cluster) = pending_cluster_zoom {
+            self.zoom_into_cluster(cluster);
+        } else if let Some(edge) = pending_edge_selection {
+            self.set_selected_edge(Some(edge));
+        } else if let Some(selected) = pending_selection {
+            match selected {
+                Some(id) if toggle_mark => self.toggle_marked(&id),
+                selected => self.apply_graph_selection(selected),
+            }
         }
     }
 }
@@ -0,0 +1,71 @@
+use std::collections::VecDeque;
+
+use super::super::{ImpactPulse, RenderGraph, ViewModel};
+
+/// Seconds the pulse needs to travel one hop.
+const IMPACT_HOP_SECONDS: f64 = 0.1;
+/// Deep closures would otherwise keep the pulse running for seconds.
+const MAX_IMPACT_HOPS: u32 = 24;
+
+/// Hop distance from `start` to every rendered path that (transitively)
+/// references it; unreachable nodes get `u32::MAX`.
+fn impact_hops(cache: &RenderGraph, start: usize) -> (Vec<u32>, u32) {
+    let mut hops = vec![u32::MAX; cache.nodes.len()];
+    let mut max_hop = 0;
+    let mut queue = VecDeque::from([start]);
+    hops[start] = 0;
+
+    while let Some(index) = queue.pop_front() {
+        let next_hop = hops[index] + 1;
+        if next_hop > MAX_IMPACT_HOPS {
+            continue;
+        }
+
+        for &referrer in &cache.incoming[index] {
+            if hops[referrer] == u32::MAX {
+                hops[referrer] = next_hop;
+                max_hop = max_hop.max(next_hop);
+                queue.push_back(referrer);
+            }
+        }
+    }
+
+    (hops, max_hop)
+}
+
+impl ViewModel {
+    /// Starts a pulse when the selection changes and returns how many hops
+    /// the pulse front has travelled, or `None` once it has settled.
+    pub(in crate::app) fn impact_pulse_front(&mut self, now: f64) -> Option<f64> {
+        if !self.impact_pulse_enabled {
+            self.impact_pulse = None;
+            return None;
+        }
+
+        let Some(selected) = self.selected.as_deref() else {
+            self.impact_pulse = None;
+            return None;
+        };
+        let cache = self.graph_cache.as_ref()?;
+        let &start = cache.index_by_id.get(selected)?;
+
+        let pulse = match &mut self.impact_pulse {
+            Some(pulse) if pulse.selected == selected => pulse,
+            slot => slot.insert(ImpactPulse {
+                selected: selected.to_owned(),
+                graph_revision: self.render_graph_revision,
+                started: now,
+                hops: Vec::new(),
+                max_hop: 0,
+            }),
+        };
+
+        if pulse.hops.is_empty() || pulse.graph_revision != self.render_graph_revision {
+            (pulse.hops, pulse.max_hop) = impact_hops(cache, start);
+            pulse.graph_revision = self.render_graph_revision;
+        }
+
+        let front = (now - pulse.started) / IMPACT_HOP_SECONDS;
+        (front <= f64::from(pulse.max_hop) + 1.0).then_some(front)
+    }
+}
@@ -3,8 +3,11 @@ use std::collections::HashSet;
 use crate::nix::SystemGraph;
 
 mod collect;
+mod impact;
+mod paths;
 
 use self::collect::collect_related_paths_by_id;
+pub(super) use self::paths::all_paths_from_root;
 use super::{HighlightState, RenderGraph};
 
 pub(super) fn build_highlight_state_for_selected_id(
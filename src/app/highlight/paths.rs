@@ -1,60 +1,133 @@
-use std::collections::{HashSet, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 
-use super::super::RenderGraph;
+use crate::nix::SystemGraph;
 
-pub(super) fn shortest_root_path(
-    cache: &RenderGraph,
-    root_index: usize,
-    target_index: usize,
-) -> (HashSet<usize>, HashSet<(usize, usize)>) {
-    if root_index >= cache.nodes.len() || target_index >= cache.nodes.len() {
-        return (HashSet::new(), HashSet::new());
+/// Enumerates up to `limit` distinct dependency chains from the root to
+/// `target`, like `nix why-depends --all`, shortest first.
+///
+/// The search only walks paths that can still reach the target, trying the
+/// closest and then most heavily referenced successors first, so the first
+/// chains found are also the most telling ones.
+pub(in crate::app) fn all_paths_from_root(
+    graph: &SystemGraph,
+    target: &str,
+    limit: usize,
+) -> Vec<Vec<String>> {
+    let Some((target, _)) = graph.nodes.get_key_value(target) else {
+        return Vec::new();
+    };
+    let root = graph.root_id.as_str();
+    if limit == 0 || !graph.nodes.contains_key(root) {
+        return Vec::new();
+    }
+    if target == root {
+        return vec![vec![graph.root_id.clone()]];
     }
 
-    let mut path_nodes = HashSet::new();
-    let mut path_edges = HashSet::new();
-
-    if root_index == target_index {
-        path_nodes.insert(root_index);
-        return (path_nodes, path_edges);
+    // Distance from every path that can reach the target, over referrers.
+    let mut distance = HashMap::from([(target.as_str(), 0usize)]);
+    let mut queue = VecDeque::from([target.as_str()]);
+    while let Some(current) = queue.pop_front() {
+        let next_distance = distance[current] + 1;
+        for referrer in &graph.nodes[current].referrers {
+            if let Some((referrer, _)) = graph.nodes.get_key_value(referrer.as_str())
+                && !distance.contains_key(referrer.as_str())
+            {
+                distance.insert(referrer, next_distance);
+                queue.push_back(referrer);
+            }
+        }
+    }
+    if !distance.contains_key(root) {
+        return Vec::new();
     }
 
-    let mut queue = VecDeque::from([root_index]);
-    let mut visited = vec![false; cache.nodes.len()];
-    let mut parent = vec![usize::MAX; cache.nodes.len()];
-    visited[root_index] = true;
+    let successors = |id: &str| {
+        let mut next = graph.nodes[id]
+            .references
+            .iter()
+            .filter_map(|reference| {
+                distance
+                    .get_key_value(reference.as_str())
+                    .map(|(&reference, &distance)| (reference, distance))
+            })
+            .collect::<Vec<_>>();
+        next.sort_by(|(a, a_distance), (b, b_distance)| {
+            a_distance
+                .cmp(b_distance)
+                .then_with(|| graph.edge_weight(id, b).cmp(&graph.edge_weight(id, a)))
+                .then_with(|| a.cmp(b))
+        });
+        next.into_iter()
+            .map(|(reference, _)| reference)
+            .collect::<Vec<_>>()
+    };
 
-    while let Some(node) = queue.pop_front() {
-        if node == target_index {
-            break;
-        }
+    let mut paths = Vec::new();
+    let mut chain = vec![root];
+    let mut on_chain = HashSet::from([root]);
+    let mut stack = vec![(successors(root), 0usize)];
+    while let Some((next, cursor)) = stack.last_mut() {
+        let Some(&child) = next.get(*cursor) else {
+            stack.pop();
+            if let Some(id) = chain.pop() {
+                on_chain.remove(id);
+            }
+            continue;
+        };
+        *cursor += 1;
 
-        for &next in &cache.outgoing[node] {
-            if !visited[next] {
-                visited[next] = true;
-                parent[next] = node;
-                queue.push_back(next);
+        if child == target {
+            paths.push(
+                chain
+                    .iter()
+                    .chain([&child])
+                    .map(|&id| id.to_owned())
+                    .collect::<Vec<_>>(),
+            );
+            if paths.len() >= limit {
+                break;
             }
+        } else if on_chain.insert(child) {
+            chain.push(child);
+            stack.push((successors(child), 0));
         }
     }
 
-    if !visited[target_index] {
-        return (path_nodes, path_edges);
-    }
+    paths.sort_by_key(Vec::len);
+    paths
+}
 
-    let mut cursor = target_index;
-    path_nodes.insert(cursor);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    while cursor != root_index {
-        let prev = parent[cursor];
-        if prev == usize::MAX {
-            break;
-        }
+    #[test]
+    fn enumerates_every_chain_up_to_the_limit() {
+        let graph = crate::nix::generate_demo_graph(400);
+        let target = graph
+            .nodes
+            .values()
+            .filter(|node| node.referrers.len() >= 3)
+            .max_by_key(|node| (node.referrers.len(), &node.id))
+            .expect("demo graph has shared paths");
 
-        path_edges.insert((prev, cursor));
-        path_nodes.insert(prev);
-        cursor = prev;
-    }
+        let paths = all_paths_from_root(&graph, &target.id, 12);
+        assert!(paths.len() > 1);
+        assert!(paths.len() <= 12);
+        assert_eq!(
+            paths[0].len(),
+            graph.shortest_path_from_root(&target.id).unwrap().len()
+        );
 
-    (path_nodes, path_edges)
+        let mut distinct = HashSet::new();
+        for path in &paths {
+            assert_eq!(path.first(), Some(&graph.root_id));
+            assert_eq!(path.last(), Some(&target.id));
+            for pair in path.windows(2) {
+                assert!(graph.nodes[&pair[0]].references.contains(&pair[1]));
+            }
+            assert!(distinct.insert(path.clone()));
+        }
+    }
 }
@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::fs;
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::nix::SystemGraph;
+use crate::util::{parse_store_name, short_name};
+
+use super::pins::state_file;
+
+/// Older snapshots of a series are dropped once it holds this many.
+const MAX_SNAPSHOTS: usize = 24;
+
+/// Closure sizes of every package across the distinct system closures this
+/// app has loaded, so slow growth shows up across generations. Closures only
+/// share a series when their roots share a package name, so that e.g. the
+/// `nixos-system-*` generations of one host are not mixed with a flake
+/// output loaded in between.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub(super) struct SizeHistory {
+    snapshots: Vec<Snapshot>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Snapshot {
+    root_id: String,
+    closure_sizes: HashMap<String, u64>,
+}
+
+/// Versions are left out of the key so that a package stays on one series
+/// through upgrades.
+fn history_key(id: &str) -> String {
+    let parsed = parse_store_name(short_name(id));
+    let mut key = parsed.pname.to_owned();
+    if let Some(output) = parsed.output {
+        key.push('-');
+        key.push_str(output);
+    }
+    if parsed.is_derivation {
+        key.push_str(".drv");
+    }
+    key
+}
+
+impl SizeHistory {
+    pub(super) fn load() -> Self {
+        state_file("history.json")
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default()
+    }
+
+    pub(super) fn save(&self) -> Result<()> {
+        let path = state_file("history.json")
+            .ok_or_else(|| anyhow!("no state directory to store size history in"))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+
+        let raw = serde_json::to_string(self).context("failed to encode size history")?;
+        fs::write(&path, raw).with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    /// Appends a snapshot unless a closure with the same root was recorded
+    /// before. Returns whether anything changed.
+    pub(super) fn record(&mut self, graph: &SystemGraph) -> bool {
+        if self
+            .snapshots
+            .iter()
+            .any(|snapshot| snapshot.root_id == graph.root_id)
+        {
+            return false;
+        }
+
+        let mut closure_sizes = HashMap::with_capacity(graph.nodes.len());
+        for node in graph.nodes.values() {
+            let size = closure_sizes.entry(history_key(&node.id)).or_insert(0);
+            *size = node.closure_size.max(*size);
+        }
+
+        let series = history_key(&graph.root_id);
+        let recorded = self.series_snapshots(&series).count();
+        if recorded >= MAX_SNAPSHOTS
+            && let Some(oldest) = self
+                .snapshots
+                .iter()
+                .position(|snapshot| history_key(&snapshot.root_id) == series)
+        {
+            self.snapshots.remove(oldest);
+        }
+        self.snapshots.push(Snapshot {
+            root_id: graph.root_id.clone(),
+            closure_sizes,
+        });
+        true
+    }
+
+    /// Closure size of the package behind `id` in each snapshot of the series
+    /// `root_id` belongs to, oldest first, or `None` where it was not part of
+    /// that closure.
+    pub(super) fn series(&self, root_id: &str, id: &str) -> Vec<Option<u64>> {
+        let key = history_key(id);
+        self.series_snapshots(&history_key(root_id))
+            .map(|snapshot| snapshot.closure_sizes.get(&key).copied())
+            .collect()
+    }
+
+    fn series_snapshots(&self, series: &str) -> impl Iterator<Item = &Snapshot> {
+        self.snapshots
+            .iter()
+            .filter(move |snapshot| history_key(&snapshot.root_id) == series)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::nix::generate_demo_graph;
+
+    use super::*;
+
+    fn system(graph: &mut SystemGraph, root_id: &str) {
+        let root = graph.nodes.remove(&graph.root_id).unwrap();
+        graph.root_id = root_id.to_owned();
+        graph.nodes.insert(root_id.to_owned(), root);
+    }
+
+    #[test]
+    fn records_each_distinct_root_once() {
+        let mut graph = generate_demo_graph(40);
+        let mut history = SizeHistory::default();
+        let (a, b) = (
+            "aaaa-nixos-system-host-24.05.1",
+            "bbbb-nixos-system-host-24.05.2",
+        );
+
+        system(&mut graph, a);
+        assert!(history.record(&graph));
+        assert!(!history.record(&graph));
+        system(&mut graph, b);
+        assert!(history.record(&graph));
+        // Going back to an earlier generation records nothing new.
+        system(&mut graph, a);
+        assert!(!history.record(&graph));
+
+        let package = graph.nodes.keys().find(|id| *id != a).unwrap().clone();
+        let series = history.series(a, &package);
+        assert_eq!(series.len(), 2);
+        assert_eq!(series[0], series[1]);
+        assert!(series[0].is_some());
+    }
+
+    #[test]
+    fn unrelated_roots_keep_separate_series() {
+        let mut graph = generate_demo_graph(40);
+        let mut history = SizeHistory::default();
+        for root in [
+            "aaaa-nixos-system-host-24.05.1",
+            "bbbb-hello-2.12",
+            "cccc-nixos-system-host-24.05.2",
+            "dddd-nixos-system-other-24.05.1",
+        ] {
+            system(&mut graph, root);
+            assert!(history.record(&graph));
+        }
+
+        let package = graph.nodes.keys().next().unwrap().clone();
+        assert_eq!(
+            history
+                .series("eeee-nixos-system-host-24.11.1", &package)
+                .len(),
+            2
+        );
+        assert_eq!(history.series("ffff-hello-2.13", &package).len(), 1);
+        assert_eq!(
+            history
+                .series("aaaa-nixos-system-other-24.05.1", &package)
+                .len(),
+            1
+        );
+        assert!(history.series("gggg-firefox-130.0", &package).is_empty());
+    }
+
+    #[test]
+    fn keeps_only_the_latest_snapshots_of_a_series() {
+        let mut graph = generate_demo_graph(10);
+        let mut history = SizeHistory::default();
+        system(&mut graph, "aaaa-hello-2.12");
+        history.record(&graph);
+        for generation in 0..MAX_SNAPSHOTS + 5 {
+            system(
+                &mut graph,
+                &format!("{generation:04}-nixos-system-host-24.05.{generation}"),
+            );
+            history.record(&graph);
+        }
+
+        let package = graph.nodes.keys().next().unwrap().clone();
+        assert_eq!(
+            history
+                .series("zzzz-nixos-system-host-24.05.0", &package)
+                .len(),
+            MAX_SNAPSHOTS
+        );
+        assert_eq!(history.snapshots[0].root_id, "aaaa-hello-2.12");
+        assert_eq!(
+            history.snapshots[1].root_id,
+            "0005-nixos-system-host-24.05.5"
+        );
+    }
+
+    #[test]
+    fn history_keys_ignore_hash_and_version() {
+        assert_eq!(
+            history_key("aaaa-python3-3.12.4-env"),
+            history_key("bbbb-python3-3.12.5-env")
+        );
+        assert_eq!(history_key("aaaa-glibc-2.39-52-bin"), "glibc-bin");
+    }
+}
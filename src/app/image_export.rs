@@ -0,0 +1,562 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context as _, Result};
+use eframe::egui::epaint::{
+    ClippedPrimitive, ClippedShape, ColorMode, Mesh, PathStroke, Primitive, TessellationOptions,
+    Tessellator,
+};
+use eframe::egui::{Color32, ColorImage, Context, Pos2, Rect, Shape, Stroke, TextureId};
+
+use crate::export::xml_escape;
+
+/// Largest factor the canvas is scaled by in a PNG.
+pub(super) const MAX_IMAGE_SCALE: f32 = 4.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum ImageFormat {
+    Png,
+    Svg,
+}
+
+impl ImageFormat {
+    pub(super) const ALL: [Self; 2] = [Self::Png, Self::Svg];
+
+    pub(super) fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Svg => "svg",
+        }
+    }
+
+    pub(super) fn label(self) -> &'static str {
+        match self {
+            Self::Png => "PNG",
+            Self::Svg => "SVG",
+        }
+    }
+}
+
+/// What part of the graph an exported image shows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum ImageExtent {
+    /// The canvas as it is drawn now.
+    View,
+    /// Every drawn node, fitted to the canvas for the exported frame.
+    World,
+}
+
+impl ImageExtent {
+    pub(super) const ALL: [Self; 2] = [Self::View, Self::World];
+
+    pub(super) fn label(self) -> &'static str {
+        match self {
+            Self::View => "Current view",
+            Self::World => "Whole graph",
+        }
+    }
+}
+
+/// An image export waiting for the next drawn frame.
+pub(super) struct ImageRequest {
+    pub(super) path: PathBuf,
+    pub(super) format: ImageFormat,
+    pub(super) extent: ImageExtent,
+    pub(super) scale: f32,
+}
+
+/// Writes the shapes drawn on `canvas` to the requested file. `textures`
+/// holds the CPU copies of user textures, such as the glyph atlas, that a
+/// PNG needs to sample; meshes using any other texture are skipped.
+pub(super) fn write_image(
+    ctx: &Context,
+    request: &ImageRequest,
+    canvas: Rect,
+    shapes: Vec<ClippedShape>,
+    textures: &[(TextureId, Arc<ColorImage>)],
+) -> Result<()> {
+    match request.format {
+        ImageFormat::Png => {
+            let image = render_png(ctx, canvas, request.scale, shapes, textures);
+            write_png(&request.path, &image)
+        }
+        ImageFormat::Svg => fs::write(&request.path, svg_document(&shapes, canvas))
+            .with_context(|| format!("writing {}", request.path.display())),
+    }
+}
+
+fn render_png(
+    ctx: &Context,
+    canvas: Rect,
+    scale: f32,
+    shapes: Vec<ClippedShape>,
+    textures: &[(TextureId, Arc<ColorImage>)],
+) -> ColorImage {
+    // Discs prepared in the font atlas are rasterized for the screen's
+    // pixels per point, so circles are tessellated as paths instead.
+    let (primitives, font_image) = ctx.fonts(|fonts| {
+        let mut tessellator = Tessellator::new(
+            scale,
+            TessellationOptions::default(),
+            fonts.font_image_size(),
+            Vec::new(),
+        );
+        (tessellator.tessellate_shapes(shapes), fonts.image())
+    });
+    let mut images = textures
+        .iter()
+        .map(|(id, image)| (*id, image.as_ref()))
+        .collect::<HashMap<_, _>>();
+    images.insert(TextureId::default(), &font_image);
+    rasterize(&primitives, canvas, scale, &images)
+}
+
+fn write_png(path: &Path, image: &ColorImage) -> Result<()> {
+    let file = File::create(path).with_context(|| format!("creating {}", path.display()))?;
+    let [width, height] = image.size;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let pixels = image
+        .pixels
+        .iter()
+        .flat_map(|pixel| pixel.to_srgba_unmultiplied())
+        .collect::<Vec<_>>();
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&pixels))
+        .with_context(|| format!("writing {}", path.display()))
+}
+
+/// Premultiplied RGBA in 0..=1.
+type Rgba = [f32; 4];
+
+fn to_rgba(color: Color32) -> Rgba {
+    color.to_array().map(|channel| channel as f32 / 255.0)
+}
+
+/// Bilinearly samples `image` at the normalized `uv`.
+fn sample(image: &ColorImage, uv: Pos2) -> Rgba {
+    let [width, height] = image.size;
+    let x = (uv.x * width as f32 - 0.5).clamp(0.0, (width - 1) as f32);
+    let y = (uv.y * height as f32 - 0.5).clamp(0.0, (height - 1) as f32);
+    let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let (tx, ty) = (x.fract(), y.fract());
+    let texel = |x: usize, y: usize| to_rgba(image.pixels[y * width + x]);
+    let (a, b, c, d) = (texel(x0, y0), texel(x1, y0), texel(x0, y1), texel(x1, y1));
+    std::array::from_fn(|channel| {
+        let top = a[channel] + (b[channel] - a[channel]) * tx;
+        let bottom = c[channel] + (d[channel] - c[channel]) * tx;
+        top + (bottom - top) * ty
+    })
+}
+
+/// Fills the triangles of `primitives` into an image of `canvas` scaled by
+/// `scale`, blending premultiplied colors the way egui's own renderers do.
+/// Pixels on an edge shared by two triangles are filled by only one of them,
+/// so translucent meshes show no seams.
+fn rasterize(
+    primitives: &[ClippedPrimitive],
+    canvas: Rect,
+    scale: f32,
+    textures: &HashMap<TextureId, &ColorImage>,
+) -> ColorImage {
+    let width = ((canvas.width() * scale).round() as usize).max(1);
+    let height = ((canvas.height() * scale).round() as usize).max(1);
+    let mut pixels = vec![[0.0_f32; 4]; width * height];
+    let to_pixels = |point: Pos2| ((point - canvas.min) * scale).to_pos2();
+
+    for primitive in primitives {
+        let Primitive::Mesh(mesh) = &primitive.primitive else {
+            continue;
+        };
+        let Some(texture) = textures.get(&mesh.texture_id) else {
+            continue;
+        };
+        let clip_min = to_pixels(primitive.clip_rect.min);
+        let clip_max = to_pixels(primitive.clip_rect.max);
+        let clip_x = (
+            clip_min.x.max(0.0) as usize,
+            (clip_max.x.ceil().max(0.0) as usize).min(width),
+        );
+        let clip_y = (
+            clip_min.y.max(0.0) as usize,
+            (clip_max.y.ceil().max(0.0) as usize).min(height),
+        );
+
+        for triangle in mesh.indices.chunks_exact(3) {
+            let mut vertices = [0, 1, 2].map(|corner| &mesh.vertices[triangle[corner] as usize]);
+            let mut points = vertices.map(|vertex| to_pixels(vertex.pos));
+            let edge =
+                |a: Pos2, b: Pos2, p: Pos2| (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x);
+            let mut area = edge(points[0], points[1], points[2]);
+            if area == 0.0 {
+                continue;
+            }
+            if area < 0.0 {
+                vertices.swap(1, 2);
+                points.swap(1, 2);
+                area = -area;
+            }
+            // Edges are opposite the vertex whose weight they give.
+            let edges = [
+                (points[1], points[2]),
+                (points[2], points[0]),
+                (points[0], points[1]),
+            ];
+            let inclusive = edges.map(|(a, b)| b.y > a.y || (b.y == a.y && b.x < a.x));
+            let colors = vertices.map(|vertex| to_rgba(vertex.color));
+
+            let min_x = points
+                .iter()
+                .map(|p| p.x)
+                .fold(f32::MAX, f32::min)
+                .floor()
+                .max(0.0) as usize;
+            let max_x = points
+                .iter()
+                .map(|p| p.x)
+                .fold(f32::MIN, f32::max)
+                .ceil()
+                .max(0.0) as usize;
+            let min_y = points
+                .iter()
+                .map(|p| p.y)
+                .fold(f32::MAX, f32::min)
+                .floor()
+                .max(0.0) as usize;
+            let max_y = points
+                .iter()
+                .map(|p| p.y)
+                .fold(f32::MIN, f32::max)
+                .ceil()
+                .max(0.0) as usize;
+            for y in min_y.max(clip_y.0)..max_y.min(clip_y.1) {
+                for x in min_x.max(clip_x.0)..max_x.min(clip_x.1) {
+                    let center = Pos2::new(x as f32 + 0.5, y as f32 + 0.5);
+                    let weights = edges.map(|(a, b)| edge(a, b, center));
+                    let inside = weights
+                        .iter()
+                        .zip(inclusive)
+                        .all(|(&weight, inclusive)| weight > 0.0 || (inclusive && weight == 0.0));
+                    if !inside {
+                        continue;
+                    }
+                    let weights = weights.map(|weight| weight / area);
+                    let uv = vertices[0].uv.to_vec2() * weights[0]
+                        + vertices[1].uv.to_vec2() * weights[1]
+                        + vertices[2].uv.to_vec2() * weights[2];
+                    let texel = sample(texture, uv.to_pos2());
+                    let source: Rgba = std::array::from_fn(|channel| {
+                        let color = colors[0][channel] * weights[0]
+                            + colors[1][channel] * weights[1]
+                            + colors[2][channel] * weights[2];
+                        color * texel[channel]
+                    });
+                    let target = &mut pixels[y * width + x];
+                    for channel in 0..4 {
+                        target[channel] = source[channel] + target[channel] * (1.0 - source[3]);
+                    }
+                }
+            }
+        }
+    }
+
+    let pixels = pixels
+        .into_iter()
+        .map(|[r, g, b, a]| {
+            let [r, g, b, a] =
+                [r, g, b, a].map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+            Color32::from_rgba_premultiplied(r, g, b, a)
+        })
+        .collect();
+    ColorImage::new([width, height], pixels)
+}
+
+/// An SVG paint and opacity for `color`, or `none` when it is invisible.
+fn svg_paint(color: Color32) -> String {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    if a == 0 {
+        "\"none\"".to_owned()
+    } else {
+        format!(
+            "\"#{r:02x}{g:02x}{b:02x}\" opacity=\"{:.3}\"",
+            a as f32 / 255.0
+        )
+    }
+}
+
+fn svg_fill(color: Color32) -> String {
+    svg_paint(color).replacen(" opacity", " fill-opacity", 1)
+}
+
+fn svg_stroke(stroke: Stroke) -> String {
+    if stroke.is_empty() {
+        return "stroke=\"none\"".to_owned();
+    }
+    format!(
+        "stroke={} stroke-width=\"{:.2}\"",
+        svg_paint(stroke.color).replacen(" opacity", " stroke-opacity", 1),
+        stroke.width
+    )
+}
+
+fn svg_path_stroke(stroke: &PathStroke) -> String {
+    match stroke.color {
+        ColorMode::Solid(color) => svg_stroke(Stroke::new(stroke.width, color)),
+        // Gradients are not carried over; they fall back to a neutral gray.
+        ColorMode::UV(_) => svg_stroke(Stroke::new(stroke.width, Color32::GRAY)),
+    }
+}
+
+fn svg_points(points: &[Pos2]) -> String {
+    points
+        .iter()
+        .map(|point| format!("{:.2},{:.2}", point.x, point.y))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Appends untextured triangles of `mesh`, each in the mean color of its
+/// corners. Textured meshes, like node glyphs, have no vector form here.
+fn write_svg_mesh(svg: &mut String, mesh: &Mesh) {
+    if mesh.texture_id != TextureId::default() {
+        return;
+    }
+    for triangle in mesh.indices.chunks_exact(3) {
+        let vertices = [0, 1, 2].map(|corner| &mesh.vertices[triangle[corner] as usize]);
+        let [r, g, b, a] = [0, 1, 2, 3].map(|channel| {
+            let total = vertices
+                .iter()
+                .map(|vertex| vertex.color.to_array()[channel] as u32)
+                .sum::<u32>();
+            (total / 3) as u8
+        });
+        let _ = writeln!(
+            svg,
+            r#"<polygon points="{}" fill={}/>"#,
+            svg_points(&vertices.map(|vertex| vertex.pos)),
+            svg_fill(Color32::from_rgba_premultiplied(r, g, b, a))
+        );
+    }
+}
+
+fn write_svg_shape(svg: &mut String, shape: &Shape) {
+    match shape {
+        Shape::Noop | Shape::Callback(_) => {}
+        Shape::Vec(shapes) => {
+            for shape in shapes {
+                write_svg_shape(svg, shape);
+            }
+        }
+        Shape::Circle(circle) => {
+            let _ = writeln!(
+                svg,
+                r#"<circle cx="{:.2}" cy="{:.2}" r="{:.2}" fill={} {}/>"#,
+                circle.center.x,
+                circle.center.y,
+                circle.radius,
+                svg_fill(circle.fill),
+                svg_stroke(circle.stroke)
+            );
+        }
+        Shape::Ellipse(ellipse) => {
+            let _ = writeln!(
+                svg,
+                r#"<ellipse cx="{:.2}" cy="{:.2}" rx="{:.2}" ry="{:.2}" fill={} {}/>"#,
+                ellipse.center.x,
+                ellipse.center.y,
+                ellipse.radius.x,
+                ellipse.radius.y,
+                svg_fill(ellipse.fill),
+                svg_stroke(ellipse.stroke)
+            );
+        }
+        Shape::LineSegment { points, stroke } => {
+            let _ = writeln!(
+                svg,
+                r#"<line x1="{:.2}" y1="{:.2}" x2="{:.2}" y2="{:.2}" stroke-linecap="round" {}/>"#,
+                points[0].x,
+                points[0].y,
+                points[1].x,
+                points[1].y,
+                svg_stroke(*stroke)
+            );
+        }
+        Shape::Path(path) => {
+            let element = if path.closed { "polygon" } else { "polyline" };
+            let fill = if path.closed {
+                svg_fill(path.fill)
+            } else {
+                svg_fill(Color32::TRANSPARENT)
+            };
+            let _ = writeln!(
+                svg,
+                r#"<{element} points="{}" fill={fill} stroke-linejoin="round" {}/>"#,
+                svg_points(&path.points),
+                svg_path_stroke(&path.stroke)
+            );
+        }
+        Shape::Rect(rect) => {
+            let _ = writeln!(
+                svg,
+                r#"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" rx="{:.2}" fill={} {}/>"#,
+                rect.rect.min.x,
+                rect.rect.min.y,
+                rect.rect.width(),
+                rect.rect.height(),
+                rect.corner_radius.average(),
+                svg_fill(rect.fill),
+                svg_stroke(rect.stroke)
+            );
+        }
+        Shape::QuadraticBezier(curve) => {
+            let [start, control, end] = curve.points;
+            let _ = writeln!(
+                svg,
+                r#"<path d="M{:.2},{:.2} Q{:.2},{:.2} {:.2},{:.2}{}" fill={} {}/>"#,
+                start.x,
+                start.y,
+                control.x,
+                control.y,
+                end.x,
+                end.y,
+                if curve.closed { " Z" } else { "" },
+                svg_fill(curve.fill),
+                svg_path_stroke(&curve.stroke)
+            );
+        }
+        Shape::CubicBezier(curve) => {
+            let [start, first, second, end] = curve.points;
+            let _ = writeln!(
+                svg,
+                r#"<path d="M{:.2},{:.2} C{:.2},{:.2} {:.2},{:.2} {:.2},{:.2}{}" fill={} {}/>"#,
+                start.x,
+                start.y,
+                first.x,
+                first.y,
+                second.x,
+                second.y,
+                end.x,
+                end.y,
+                if curve.closed { " Z" } else { "" },
+                svg_fill(curve.fill),
+                svg_path_stroke(&curve.stroke)
+            );
+        }
+        Shape::Text(text) => {
+            let section = text.galley.job.sections.first();
+            let color = text
+                .override_text_color
+                .or_else(|| {
+                    section
+                        .map(|section| section.format.color)
+                        .filter(|&color| color != Color32::PLACEHOLDER)
+                })
+                .unwrap_or(text.fallback_color)
+                .gamma_multiply(text.opacity_factor);
+            let size = section.map_or(14.0, |section| section.format.font_id.size);
+            for placed in &text.galley.rows {
+                let Some(first) = placed.row.glyphs.first() else {
+                    continue;
+                };
+                let _ = writeln!(
+                    svg,
+                    r#"<text x="{:.2}" y="{:.2}" font-family="sans-serif" font-size="{size:.1}" fill={}>{}</text>"#,
+                    text.pos.x + placed.pos.x + first.pos.x,
+                    text.pos.y + placed.pos.y + first.pos.y,
+                    svg_fill(color),
+                    xml_escape(&placed.row.text())
+                );
+            }
+        }
+        Shape::Mesh(mesh) => write_svg_mesh(svg, mesh),
+    }
+}
+
+/// The shapes drawn on `canvas` as an SVG document of the same size in
+/// points, with circles, lines and text kept as vector elements.
+fn svg_document(shapes: &[ClippedShape], canvas: Rect) -> String {
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width:.0}" height="{height:.0}" viewBox="{:.2} {:.2} {width:.2} {height:.2}">"#,
+        canvas.min.x,
+        canvas.min.y,
+        width = canvas.width(),
+        height = canvas.height(),
+    );
+    for shape in shapes {
+        write_svg_shape(&mut svg, &shape.shape);
+    }
+    let _ = writeln!(svg, "</svg>");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use eframe::egui::epaint::{CircleShape, Vertex, WHITE_UV};
+    use eframe::egui::{pos2, vec2};
+
+    use super::*;
+
+    #[test]
+    fn rasterizes_meshes_without_seams() {
+        // Two half-transparent triangles sharing a diagonal cover the square
+        // evenly.
+        let canvas = Rect::from_min_size(pos2(10.0, 10.0), vec2(4.0, 4.0));
+        let color = Color32::from_black_alpha(128);
+        let vertex = |x: f32, y: f32| Vertex {
+            pos: pos2(x, y),
+            uv: WHITE_UV,
+            color,
+        };
+        let mesh = Mesh {
+            indices: vec![0, 1, 2, 0, 2, 3],
+            vertices: vec![
+                vertex(10.0, 10.0),
+                vertex(14.0, 10.0),
+                vertex(14.0, 14.0),
+                vertex(10.0, 14.0),
+            ],
+            texture_id: TextureId::default(),
+        };
+        let white = ColorImage::new([1, 1], vec![Color32::WHITE]);
+        let textures = HashMap::from([(TextureId::default(), &white)]);
+        let primitive = ClippedPrimitive {
+            clip_rect: canvas,
+            primitive: Primitive::Mesh(mesh),
+        };
+
+        let image = rasterize(&[primitive], canvas, 2.0, &textures);
+        assert_eq!(image.size, [8, 8]);
+        assert!(image.pixels.iter().all(|&pixel| pixel == color));
+    }
+
+    #[test]
+    fn svg_keeps_circles_and_lines_as_elements() {
+        let canvas = Rect::from_min_size(pos2(0.0, 0.0), vec2(100.0, 50.0));
+        let shapes = [
+            Shape::Circle(CircleShape::filled(pos2(20.0, 20.0), 5.0, Color32::RED)),
+            Shape::line_segment(
+                [pos2(0.0, 0.0), pos2(10.0, 10.0)],
+                Stroke::new(1.0, Color32::WHITE),
+            ),
+        ]
+        .map(|shape| ClippedShape {
+            clip_rect: canvas,
+            shape,
+        });
+
+        let svg = svg_document(&shapes, canvas);
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.contains(r##"<circle cx="20.00" cy="20.00" r="5.00" fill="#ff0000""##));
+        assert!(svg.contains(r#"<line x1="0.00" y1="0.00" x2="10.00" y2="10.00""#));
+        assert!(svg.trim_end().ends_with("</svg>"));
+    }
+}
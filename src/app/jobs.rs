@@ -0,0 +1,136 @@
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+use anyhow::Result;
+
+use crate::nix::{NixControl, NixSettings};
+
+/// Work running on its own thread: one typed result, the progress hints and
+/// warnings it reports through its `NixControl`, and cancellation through the
+/// same control. Dropping a job cancels it, so replacing or clearing the
+/// state that holds it is enough to stop the worker.
+pub(super) struct Job<T> {
+    rx: Receiver<Result<T, String>>,
+    progress_rx: Receiver<String>,
+    progress: Option<String>,
+    warnings: Vec<String>,
+    control: NixControl,
+}
+
+impl<T: Send + 'static> Job<T> {
+    pub(super) fn spawn(
+        settings: &NixSettings,
+        work: impl FnOnce(&NixControl) -> Result<T> + Send + 'static,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let (progress_tx, progress_rx) = mpsc::channel();
+        let control = NixControl::new(settings).with_progress(progress_tx);
+        let worker_control = control.clone();
+
+        thread::spawn(move || {
+            let result = work(&worker_control).map_err(|error| format!("{error:#}"));
+            let _ = tx.send(result);
+        });
+
+        Self {
+            rx,
+            progress_rx,
+            progress: None,
+            warnings: Vec::new(),
+            control,
+        }
+    }
+}
+
+impl<T> Job<T> {
+    /// The result once the worker has finished, `None` while it runs. A
+    /// worker that died without a result counts as failed.
+    pub(super) fn poll(&mut self) -> Option<Result<T, String>> {
+        self.poll_progress();
+        match self.rx.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                Some(Err("Background worker disconnected".to_owned()))
+            }
+        }
+    }
+
+    /// The latest progress hint. Hints starting with `warning:` are also kept
+    /// until taken with `take_warnings`.
+    pub(super) fn poll_progress(&mut self) -> Option<&str> {
+        while let Ok(hint) = self.progress_rx.try_recv() {
+            if hint.starts_with("warning:") {
+                self.warnings.push(hint.clone());
+            }
+            self.progress = Some(hint);
+        }
+        self.progress.as_deref()
+    }
+
+    pub(super) fn take_warnings(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Store paths handled so far and in total, for workers that count them.
+    pub(super) fn path_progress(&self) -> Option<(usize, usize)> {
+        self.control.path_progress()
+    }
+
+    pub(super) fn cancel(&self) {
+        self.control.cancel();
+    }
+}
+
+impl<T> Drop for Job<T> {
+    fn drop(&mut self) {
+        self.control.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use anyhow::anyhow;
+
+    use super::*;
+
+    fn wait<T>(job: &mut Job<T>) -> Result<T, String> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            if let Some(result) = job.poll() {
+                return result;
+            }
+            assert!(Instant::now() < deadline, "job did not finish");
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn delivers_typed_results_and_errors() {
+        let mut sum = Job::spawn(&NixSettings::default(), |_| Ok(2 + 2));
+        assert_eq!(wait(&mut sum), Ok(4));
+
+        let mut failing = Job::<()>::spawn(&NixSettings::default(), |_| {
+            Err(anyhow!("no such path").context("failed to query"))
+        });
+        assert_eq!(
+            wait(&mut failing),
+            Err("failed to query: no such path".to_owned())
+        );
+    }
+
+    #[test]
+    fn cancels_the_worker() {
+        let mut job = Job::spawn(&NixSettings::default(), |control| {
+            while !control.is_cancelled() {
+                thread::sleep(Duration::from_millis(1));
+            }
+            Err::<(), _>(anyhow!("cancelled"))
+        });
+        assert!(job.poll().is_none());
+        job.cancel();
+        assert_eq!(wait(&mut job), Err("cancelled".to_owned()));
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::VecDeque;
+
+use eframe::egui::{Vec2, vec2};
+
+/// Vertical distance between layers, in world units.
+const LAYER_GAP: f32 = 160.0;
+/// Horizontal space left between neighbouring nodes of a layer.
+const NODE_GAP: f32 = 14.0;
+/// Down and up sweeps of the barycenter heuristic.
+const ORDERING_SWEEPS: usize = 6;
+
+/// The BFS depth of each node from `root`. Nodes the root does not reach
+/// are layered from the nodes nothing references, and whatever is left,
+/// which only cycles can leave, starts again at the top.
+fn assign_layers(outgoing: &[Vec<usize>], root: Option<usize>) -> Vec<usize> {
+    let mut referenced = vec![false; outgoing.len()];
+    for &target in outgoing.iter().flatten() {
+        referenced[target] = true;
+    }
+
+    let mut layers = vec![usize::MAX; outgoing.len()];
+    let mut queue = VecDeque::new();
+    let starts = root
+        .into_iter()
+        .chain((0..outgoing.len()).filter(|&index| !referenced[index]))
+        .chain(0..outgoing.len());
+    for start in starts {
+        if layers[start] != usize::MAX {
+            continue;
+        }
+        layers[start] = 0;
+        queue.push_back(start);
+        while let Some(index) = queue.pop_front() {
+            for &next in &outgoing[index] {
+                if layers[next] == usize::MAX {
+                    layers[next] = layers[index] + 1;
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+    layers
+}
+
+/// Orders each layer to reduce edge crossings with the barycenter heuristic:
+/// sweeping down, a node moves to the mean position of the nodes above that
+/// reference it, and sweeping up, to the mean of those below it references.
+/// BFS layers never have an edge skip down past the next layer, so no dummy
+/// nodes are needed for long edges.
+fn order_layers(outgoing: &[Vec<usize>], layers: &[usize]) -> Vec<Vec<usize>> {
+    let layer_count = layers.iter().max().map_or(0, |&deepest| deepest + 1);
+    let mut ordered = vec![Vec::new(); layer_count];
+    for (index, &layer) in layers.iter().enumerate() {
+        ordered[layer].push(index);
+    }
+
+    let mut incoming = vec![Vec::new(); outgoing.len()];
+    for (source, targets) in outgoing.iter().enumerate() {
+        for &target in targets {
+            incoming[target].push(source);
+        }
+    }
+
+    fn record(layer: &[usize], position: &mut [f32]) {
+        for (slot, &index) in layer.iter().enumerate() {
+            position[index] = slot as f32;
+        }
+    }
+    let mut position = vec![0.0_f32; outgoing.len()];
+    for layer in &ordered {
+        record(layer, &mut position);
+    }
+
+    for sweep in 0..ORDERING_SWEEPS {
+        let downward = sweep % 2 == 0;
+        let layer_indices = (1..layer_count)
+            .map(|step| {
+                if downward {
+                    step
+                } else {
+                    layer_count - 1 - step
+                }
+            })
+            .collect::<Vec<_>>();
+        for layer in layer_indices {
+            let neighbour_layer = if downward { layer - 1 } else { layer + 1 };
+            let barycenters = ordered[layer]
+                .iter()
+                .map(|&index| {
+                    let neighbours = if downward {
+                        &incoming[index]
+                    } else {
+                        &outgoing[index]
+                    };
+                    let (sum, count) = neighbours
+                        .iter()
+                        .filter(|&&neighbour| layers[neighbour] == neighbour_layer)
+                        .fold((0.0, 0), |(sum, count), &neighbour| {
+                            (sum + position[neighbour], count + 1)
+                        });
+                    if count == 0 {
+                        position[index]
+                    } else {
+                        sum / count as f32
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            let mut slots = (0..ordered[layer].len()).collect::<Vec<_>>();
+            slots.sort_by(|&a, &b| barycenters[a].total_cmp(&barycenters[b]));
+            ordered[layer] = slots.into_iter().map(|slot| ordered[layer][slot]).collect();
+            record(&ordered[layer], &mut position);
+        }
+    }
+    ordered
+}
+
+/// Positions for a layered drawing of the graph given by `outgoing`: the
+/// root on top, the paths it references in BFS-depth layers below, each
+/// layer centered and spaced by the nodes' `radii`.
+pub(in crate::app) fn layered_positions(
+    outgoing: &[Vec<usize>],
+    root: Option<usize>,
+    radii: &[f32],
+) -> Vec<Vec2> {
+    let layers = assign_layers(outgoing, root);
+    let mut positions = vec![Vec2::ZERO; outgoing.len()];
+    for (depth, layer) in order_layers(outgoing, &layers).into_iter().enumerate() {
+        let width = layer
+            .iter()
+            .map(|&index| radii[index] * 2.0 + NODE_GAP)
+            .sum::<f32>();
+        let mut x = -width * 0.5;
+        for index in layer {
+            let span = radii[index] * 2.0 + NODE_GAP;
+            positions[index] = vec2(x + span * 0.5, depth as f32 * LAYER_GAP);
+            x += span;
+        }
+    }
+    positions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn crossings(outgoing: &[Vec<usize>], ordered: &[Vec<usize>]) -> usize {
+        let mut slot = vec![0; outgoing.len()];
+        for layer in ordered {
+            for (position, &index) in layer.iter().enumerate() {
+                slot[index] = position;
+            }
+        }
+        let edges = outgoing
+            .iter()
+            .enumerate()
+            .flat_map(|(source, targets)| targets.iter().map(move |&target| (source, target)))
+            .collect::<Vec<_>>();
+        let mut count = 0;
+        for (index, &(a, b)) in edges.iter().enumerate() {
+            for &(c, d) in &edges[index + 1..] {
+                let (sa, sb, sc, sd) = (slot[a], slot[b], slot[c], slot[d]);
+                if (sa < sc && sb > sd) || (sa > sc && sb < sd) {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    #[test]
+    fn layers_by_depth_and_untangles_crossings() {
+        // 0 references 1 and 2; 1 references 4 and 2 references 3, which
+        // start out in crossing order. 5 is only reached by 6, which nothing
+        // references.
+        let outgoing = vec![
+            vec![1, 2],
+            vec![4],
+            vec![3],
+            vec![],
+            vec![],
+            vec![],
+            vec![5],
+        ];
+        let layers = assign_layers(&outgoing, Some(0));
+        assert_eq!(layers, [0, 1, 1, 2, 2, 1, 0]);
+
+        let ordered = order_layers(&outgoing, &layers);
+        assert_eq!(crossings(&outgoing, &ordered), 0);
+
+        let radii = vec![10.0; outgoing.len()];
+        let positions = layered_positions(&outgoing, Some(0), &radii);
+        assert_eq!(positions[1].y, LAYER_GAP);
+        assert_eq!(positions[3].y, 2.0 * LAYER_GAP);
+        assert!((positions[3].x + positions[4].x).abs() < 0.01);
+    }
+
+    #[test]
+    fn layers_cycles_nothing_else_reaches() {
+        let outgoing = vec![vec![1], vec![0]];
+        assert_eq!(assign_layers(&outgoing, None), [0, 1]);
+    }
+}
@@ -0,0 +1,60 @@
+mod layered;
+mod radial;
+
+use eframe::egui::Vec2;
+
+use super::{GraphLayout, ViewModel};
+use layered::layered_positions;
+use radial::radial_positions;
+
+impl ViewModel {
+    /// Whether physics is held off so a computed layout stays as placed.
+    pub(in crate::app) fn layout_frozen(&self) -> bool {
+        self.graph_layout != GraphLayout::Force && self.freeze_layout
+    }
+
+    /// Lays the graph out again around a new selection in the radial layout,
+    /// and glides the camera to it in the middle.
+    pub(in crate::app) fn follow_selection_layout(&mut self) {
+        if self.graph_layout == GraphLayout::Radial {
+            self.apply_graph_layout();
+            self.zoom_to_point(Vec2::ZERO, self.zoom);
+        }
+    }
+
+    /// Places the drawn nodes by the chosen layout in one pass; afterwards
+    /// physics takes over from there unless the layout is frozen. Pinned
+    /// nodes stay where they are.
+    pub(in crate::app) fn apply_graph_layout(&mut self) {
+        let selected = self
+            .selected
+            .as_deref()
+            .map(|id| self.condensed_id(id).to_owned());
+        let Some(cache) = self.graph_cache.as_mut() else {
+            return;
+        };
+        let radii = cache
+            .nodes
+            .iter()
+            .map(|node| node.base_radius)
+            .collect::<Vec<_>>();
+        let positions = match self.graph_layout {
+            GraphLayout::Force => return,
+            GraphLayout::Layered => layered_positions(&cache.outgoing, cache.root_index, &radii),
+            GraphLayout::Radial => {
+                let selected = selected.and_then(|id| cache.index_by_id.get(&id).copied());
+                let Some(center) = selected.or(cache.root_index) else {
+                    return;
+                };
+                radial_positions(&cache.outgoing, center, &radii)
+            }
+        };
+
+        for (node, position) in cache.nodes.iter_mut().zip(positions) {
+            if !node.pinned {
+                node.world_pos = position;
+                node.velocity = Vec2::ZERO;
+            }
+        }
+    }
+}
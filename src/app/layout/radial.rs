@@ -0,0 +1,103 @@
+use std::collections::VecDeque;
+use std::f32::consts::TAU;
+
+use eframe::egui::{Vec2, vec2};
+
+/// Distance between neighbouring rings, in world units; a ring grows past
+/// this when its nodes would not fit around it.
+const RING_GAP: f32 = 150.0;
+/// Space left between neighbouring nodes of a ring.
+const NODE_GAP: f32 = 12.0;
+
+/// Positions for an ego drawing of the graph given by `outgoing`: `center`
+/// in the middle, the paths it references directly on the first ring, those
+/// two references away on the second and so on. Paths it does not reach go
+/// on one last ring. Each node sits near the angle of the node that first
+/// reached it, so branches stay together.
+pub(in crate::app) fn radial_positions(
+    outgoing: &[Vec<usize>],
+    center: usize,
+    radii: &[f32],
+) -> Vec<Vec2> {
+    let mut depth = vec![usize::MAX; outgoing.len()];
+    let mut parent = vec![usize::MAX; outgoing.len()];
+    let mut rings: Vec<Vec<usize>> = Vec::new();
+    let mut queue = VecDeque::from([center]);
+    depth[center] = 0;
+    while let Some(index) = queue.pop_front() {
+        for &next in &outgoing[index] {
+            if depth[next] == usize::MAX {
+                depth[next] = depth[index] + 1;
+                parent[next] = index;
+                if rings.len() < depth[next] {
+                    rings.push(Vec::new());
+                }
+                rings[depth[next] - 1].push(next);
+                queue.push_back(next);
+            }
+        }
+    }
+    let unreached = (0..outgoing.len())
+        .filter(|&index| depth[index] == usize::MAX)
+        .collect::<Vec<_>>();
+    if !unreached.is_empty() {
+        rings.push(unreached);
+    }
+
+    let mut positions = vec![Vec2::ZERO; outgoing.len()];
+    let mut angle = vec![0.0_f32; outgoing.len()];
+    let mut radius = 0.0_f32;
+    for ring in &mut rings {
+        // Parents sit on the ring before, whose angles are already set.
+        let parent_angle = |index: usize| angle.get(parent[index]).copied().unwrap_or(0.0);
+        ring.sort_by(|&a, &b| parent_angle(a).total_cmp(&parent_angle(b)));
+        let spans = ring
+            .iter()
+            .map(|&index| radii[index] * 2.0 + NODE_GAP)
+            .collect::<Vec<_>>();
+        let circumference = spans.iter().sum::<f32>();
+        radius = (radius + RING_GAP).max(circumference / TAU);
+
+        let mut along = 0.0;
+        for (&index, span) in ring.iter().zip(spans) {
+            let theta = (along + span * 0.5) / circumference * TAU;
+            angle[index] = theta;
+            positions[index] = vec2(theta.cos(), theta.sin()) * radius;
+            along += span;
+        }
+    }
+    positions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rings_follow_reference_depth_from_the_center() {
+        // 1 is the center: it references 2 and 3, and 3 references 4. 0
+        // references 1 but is not reached from it.
+        let outgoing = vec![vec![1], vec![2, 3], vec![], vec![4], vec![]];
+        let radii = vec![10.0; outgoing.len()];
+        let positions = radial_positions(&outgoing, 1, &radii);
+
+        let distance = |index: usize| positions[index].length();
+        assert_eq!(positions[1], Vec2::ZERO);
+        assert!((distance(2) - RING_GAP).abs() < 0.01);
+        assert!((distance(3) - RING_GAP).abs() < 0.01);
+        assert!((distance(4) - 2.0 * RING_GAP).abs() < 0.01);
+        assert!((distance(0) - 3.0 * RING_GAP).abs() < 0.01);
+    }
+
+    #[test]
+    fn crowded_rings_grow_to_fit_their_nodes() {
+        let mut outgoing = vec![(1..200).collect::<Vec<_>>()];
+        outgoing.extend((1..200).map(|_| Vec::new()));
+        let radii = vec![10.0; outgoing.len()];
+        let positions = radial_positions(&outgoing, 0, &radii);
+
+        let ring = positions[1].length();
+        assert!(ring > RING_GAP);
+        assert!((positions[1] - positions[2]).length() >= 2.0 * 10.0);
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, anyhow};
+use eframe::egui::{Vec2, vec2};
+use serde::{Deserialize, Serialize};
+
+use super::pins::state_file;
+use crate::util::short_name;
+
+/// Layouts of this many roots are kept, the most recently saved first.
+const MAX_SAVED_LAYOUTS: usize = 8;
+
+/// The share of a saved layout's paths that must still be in a closure for
+/// the layout to be restored into it.
+const MIN_LAYOUT_OVERLAP: f32 = 0.5;
+
+/// Node positions of a settled layout, keyed by the hash-stripped store name
+/// like pins, so rebuilds that only change hashes keep their layout.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(super) struct LayoutSnapshot {
+    pub(super) root: String,
+    pub(super) positions: BTreeMap<String, [f32; 2]>,
+}
+
+/// A saved layout picked for a closure, with the share of its paths found
+/// there.
+pub(super) struct RestoredLayout {
+    pub(super) root: String,
+    pub(super) overlap: f32,
+    pub(super) positions: HashMap<String, Vec2>,
+    /// The layout file this was imported from; imported layouts are placed
+    /// even when layouts are not remembered.
+    pub(super) file: Option<PathBuf>,
+}
+
+/// One node of an exported layout. The full store path is kept for readers;
+/// imports match on `name`.
+#[derive(Serialize, Deserialize)]
+struct LayoutFileNode {
+    id: String,
+    name: String,
+    position: [f32; 2],
+}
+
+/// A layout written by Export layout, to place a closure collected on another
+/// machine the same way.
+#[derive(Serialize, Deserialize)]
+pub(super) struct LayoutFile {
+    root: String,
+    nodes: Vec<LayoutFileNode>,
+}
+
+impl LayoutFile {
+    /// A layout of `positions`, keyed by full store path.
+    pub(super) fn new<'a>(
+        root: &str,
+        positions: impl IntoIterator<Item = (&'a str, Vec2)>,
+    ) -> Self {
+        Self {
+            root: short_name(root).to_owned(),
+            nodes: positions
+                .into_iter()
+                .map(|(id, position)| LayoutFileNode {
+                    id: id.to_owned(),
+                    name: short_name(id).to_owned(),
+                    position: [position.x, position.y],
+                })
+                .collect(),
+        }
+    }
+
+    pub(super) fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// The layout restored into a closure made of `names`, with the share of
+    /// its paths found there.
+    pub(super) fn restore_into(self, names: &HashSet<&str>, file: &Path) -> RestoredLayout {
+        let shared = self
+            .nodes
+            .iter()
+            .filter(|node| names.contains(node.name.as_str()))
+            .count();
+        RestoredLayout {
+            root: self.root,
+            overlap: shared as f32 / self.nodes.len().max(1) as f32,
+            positions: self
+                .nodes
+                .into_iter()
+                .map(|node| (node.name, vec2(node.position[0], node.position[1])))
+                .collect(),
+            file: Some(file.to_path_buf()),
+        }
+    }
+}
+
+pub(super) fn write_layout_file(path: &Path, layout: &LayoutFile) -> Result<()> {
+    let raw = serde_json::to_string_pretty(layout).context("failed to encode layout")?;
+    fs::write(path, raw).with_context(|| format!("failed to write {}", path.display()))
+}
+
+pub(super) fn read_layout_file(path: &Path) -> Result<LayoutFile> {
+    let raw =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    serde_json::from_str(&raw).with_context(|| format!("{} is not a layout file", path.display()))
+}
+
+fn layouts_path() -> Option<PathBuf> {
+    state_file("layouts.json")
+}
+
+fn load_layouts() -> Vec<LayoutSnapshot> {
+    let Some(path) = layouts_path() else {
+        return Vec::new();
+    };
+
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+/// Picks the saved layout sharing the most paths with a closure made of
+/// `names`, if enough of it is still there.
+pub(super) fn find_layout(names: &HashSet<&str>) -> Option<RestoredLayout> {
+    load_layouts()
+        .into_iter()
+        .filter(|layout| !layout.positions.is_empty())
+        .map(|layout| {
+            let shared = layout
+                .positions
+                .keys()
+                .filter(|name| names.contains(name.as_str()))
+                .count();
+            (shared as f32 / layout.positions.len() as f32, layout)
+        })
+        .filter(|(overlap, _)| *overlap >= MIN_LAYOUT_OVERLAP)
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(overlap, layout)| RestoredLayout {
+            root: layout.root,
+            overlap,
+            positions: layout
+                .positions
+                .into_iter()
+                .map(|(name, [x, y])| (name, vec2(x, y)))
+                .collect(),
+            file: None,
+        })
+}
+
+/// Saves `snapshot` in place of an earlier layout of the same root.
+pub(super) fn save_layout(snapshot: LayoutSnapshot) -> Result<()> {
+    let path = layouts_path().ok_or_else(|| anyhow!("no state directory to store layouts in"))?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+
+    let mut layouts = load_layouts();
+    layouts.retain(|layout| layout.root != snapshot.root);
+    layouts.insert(0, snapshot);
+    layouts.truncate(MAX_SAVED_LAYOUTS);
+
+    let raw = serde_json::to_string(&layouts).context("failed to encode layouts")?;
+    fs::write(&path, raw).with_context(|| format!("failed to write {}", path.display()))
+}
@@ -1,30 +1,140 @@
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::mpsc::{self, Receiver, TryRecvError};
-use std::thread;
+use std::time::{Duration, Instant};
 
-use eframe::egui::{self, Context, Pos2, Vec2};
+use eframe::egui::{self, Context, Pos2, Rect, Vec2};
+use serde::{Deserialize, Serialize};
 
-use crate::nix::{SizeMetric, SystemGraph, collect_system_graph};
-use crate::util::format_bytes;
+use crate::export::ExportFormat;
+use crate::findings::Finding;
+use crate::format::{format_bytes, format_count};
+use crate::lockfile::{ClosureLock, LockDeviation};
+use crate::name_filter::NameFilter;
+use crate::nix::{
+    ClosureDiff, ContentEntry, DuplicatePackage, GcRoots, Generation, GraphDelta, NixSettings,
+    NodeClasses, PathPreview, ProfileWatcher, ReferenceMatches, ShardPreview, SizeMetric,
+    StoreWatcher, SystemGraph, TopLevelShare, VerifyOutcome, collect_multi_root_graph,
+    collect_system_graph, enrich_derivations, generate_demo_graph, load_graph, scan_edge_weights,
+    stream_store_shards,
+};
+use crate::scenario::{Scenario, ScenarioReport};
+use crate::util::short_name;
 
+use self::annotations::{Annotation, AnnotationRole, AnnotationShape};
+use self::bookmarks::{NodeBookmark, ViewBookmark};
+use self::glyphs::GlyphAtlas;
+use self::graph::{CameraAnimation, GraphRenderer, install_graph_renderer};
+use self::history::SizeHistory;
+use self::image_export::{ImageExtent, ImageFormat, ImageRequest};
+use self::jobs::Job;
+use self::layouts::RestoredLayout;
+use self::navigation::NavigationHistory;
+use self::selection_sets::SelectionSets;
+use self::settings::Settings;
+use self::toasts::{Toast, ToastLevel, Toasts};
+use self::workspaces::Workspaces;
+
+mod actions;
+mod annotations;
+mod bookmarks;
+mod glyphs;
 mod graph;
 mod highlight;
+mod history;
+mod image_export;
+mod jobs;
+mod layout;
+mod layouts;
+mod navigation;
 mod physics;
+mod pins;
 mod render_utils;
+mod selection_sets;
+mod settings;
+mod toasts;
 mod ui;
+mod workspaces;
+
+pub use self::settings::saved_root;
+
+const ROOT_INPUT_HINT: &str = "/nix/store/…, ~/.nix-profile or nixpkgs#hello";
 
 pub struct NixAnalyzeApp {
     system_path: String,
+    scenario: Option<Arc<Scenario>>,
+    nix_settings: NixSettings,
+    validate_graph: bool,
+    demo_nodes: Option<usize>,
+    whole_store: bool,
+    passes: CollectPasses,
+    graph_file: Option<PathBuf>,
+    extra_roots: Vec<String>,
+    compare_path: Option<String>,
+    layout_file: Option<PathBuf>,
+    root_input: String,
     state: AppState,
-    reload_rx: Option<Receiver<Result<SystemGraph, String>>>,
+    reload_job: Option<LoadJob>,
+    store_watcher: Option<StoreWatcher>,
+    profile_watcher: Option<ProfileWatcher>,
+    /// Opens the diff window on the next loaded graph, for a comparison asked
+    /// for along with a new root.
+    open_diff_on_load: bool,
+    size_history: Arc<SizeHistory>,
+    settings: Settings,
+    toasts: Toasts,
+    /// Draws node circles and edges with instancing; `None` without OpenGL
+    /// 3.3, where the painter draws them.
+    graph_renderer: Option<Arc<GraphRenderer>>,
+}
+
+pub struct AppOptions {
+    pub nix_settings: NixSettings,
+    pub validate_graph: bool,
+    pub demo_nodes: Option<usize>,
+    pub whole_store: bool,
+    pub edge_weights: bool,
+    pub derivation_info: bool,
+    pub graph_file: Option<PathBuf>,
+    /// Further closures merged with the one at the system path.
+    pub extra_roots: Vec<String>,
+    pub compare_path: Option<String>,
+    /// Layout file placing the first graph, matched by hash-stripped name.
+    pub layout_file: Option<PathBuf>,
+}
+
+/// Optional passes run over a freshly collected closure.
+#[derive(Clone, Copy, Default)]
+struct CollectPasses {
+    /// Weigh edges by how many files in the referrer mention the target.
+    edge_weights: bool,
+    /// Read pname, version, outputs and licenses from each path's deriver.
+    derivation_info: bool,
+}
+
+/// How often the views over a streaming whole-store scan are recomputed;
+/// they cost a pass over the whole graph so far.
+const SHARD_REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+struct LoadJob {
+    /// The collected graph; `None` for a whole-store scan, whose graph is
+    /// assembled from the shards it streams.
+    collect: Job<Option<SystemGraph>>,
+    /// The paths of each shard of a whole-store scan as it loads.
+    partial_rx: Receiver<GraphDelta>,
+    /// The graph shown while a whole-store scan runs, grown shard by shard.
+    preview: ShardPreview,
+    /// Whether partial graphs are being shown while the job runs.
+    streamed: bool,
+    /// Whether shards were merged since the views were last recomputed.
+    views_stale: bool,
+    views_refreshed: Instant,
 }
 
 enum AppState {
-    Loading {
-        rx: Receiver<Result<SystemGraph, String>>,
-    },
-    Ready(Box<ViewModel>),
+    Loading(Box<LoadJob>),
+    Ready(Workspaces),
     Error(String),
 }
 
@@ -32,24 +142,177 @@ enum AppState {
 enum DependencyRankingMode {
     Dependencies,
     ReverseDependencies,
+    ClosureCount,
+}
+
+/// Requests from the status bar that only the app can carry out.
+enum StatusBarAction {
+    CancelReload,
+    StopWatching,
+}
+
+enum WorkspaceRequest {
+    Open(String),
+    ShowInFullGraph(String),
+}
+
+/// The parts of the window that name store paths; each shows or hides store
+/// hashes on its own.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum LabelPanel {
+    Controls,
+    Details,
+    Graph,
+    Analysis,
+    Bars,
+}
+
+impl LabelPanel {
+    const ALL: [Self; 5] = [
+        Self::Controls,
+        Self::Details,
+        Self::Graph,
+        Self::Analysis,
+        Self::Bars,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Controls => "Controls panel",
+            Self::Details => "Details panel",
+            Self::Graph => "Graph",
+            Self::Analysis => "Findings, diff and attribution",
+            Self::Bars => "Top bar, tabs and status bar",
+        }
+    }
+}
+
+/// The panels that name paths with their store hash.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+struct StoreHashes(BTreeSet<LabelPanel>);
+
+impl StoreHashes {
+    /// How `panel` names a path: the store name, or `hash-name` while that
+    /// panel shows store hashes.
+    fn label<'a>(&self, panel: LabelPanel, id: &'a str) -> &'a str {
+        if self.shown(panel) {
+            id
+        } else {
+            short_name(id)
+        }
+    }
+
+    fn shown(&self, panel: LabelPanel) -> bool {
+        self.0.contains(&panel)
+    }
+
+    fn set(&mut self, panel: LabelPanel, shown: bool) {
+        if shown {
+            self.0.insert(panel);
+        } else {
+            self.0.remove(&panel);
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum EdgeStyle {
+    Straight,
+    Curved,
+    Orthogonal,
+}
+
+/// How node positions are computed.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum GraphLayout {
+    /// The force simulation alone.
+    Force,
+    /// Layers by depth below the root, then handed to physics.
+    Layered,
+    /// Rings by depth around the selection, or the root.
+    Radial,
+}
+
+/// What fills the leaf cells of the quadtree overlay.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum QuadtreeShading {
+    Outline,
+    Occupancy,
+    Size,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum FrameRateCap {
+    Fps30,
+    Fps60,
+    Uncapped,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 enum SizeRankingMode {
-    NarSize,
-    ClosureSize,
+    Nar,
+    Closure,
+    Retained,
 }
 
 struct ViewModel {
     graph: SystemGraph,
+    /// A copy of `graph` for background jobs to share, taken when the first
+    /// one needs it and dropped whenever the graph changes.
+    shared_graph: Option<Arc<SystemGraph>>,
+    scenario: Option<Arc<Scenario>>,
+    scenario_report: Option<ScenarioReport>,
+    findings: Vec<Finding>,
+    /// Packages present in more than one version.
+    duplicates: Vec<DuplicatePackage>,
+    /// Reference cycles of the graph, each led by the path standing in for
+    /// it in the condensed view.
+    cycles: Vec<Vec<String>>,
+    cycle_by_id: HashMap<String, usize>,
+    condense_cycles: bool,
     metric: SizeMetric,
     min_size_mb: f32,
     max_nodes: usize,
     search: String,
     selected: Option<String>,
+    watch_store: bool,
+    /// Notice when the system profile switches generations, short of
+    /// watching the whole store.
+    watch_profile: bool,
+    /// Reload on a profile switch instead of offering to.
+    auto_reload_profile: bool,
+    /// Where the system profile points since it switched, until reloaded or
+    /// dismissed.
+    profile_switch: Option<PathBuf>,
+    nix_settings: NixSettings,
+    validate_graph: bool,
+    graph_validated: bool,
+    pending_toasts: Vec<Toast>,
+    size_history: Arc<SizeHistory>,
+    is_workspace: bool,
+    workspace_request: Option<WorkspaceRequest>,
+    root_input: String,
+    /// A store path, profile or flake installable to load in place of the
+    /// current root, picked in the top bar.
+    root_request: Option<String>,
+    /// A closure to diff the requested root against once it has loaded.
+    root_request_compare: Option<String>,
+    store_update_status: Option<String>,
+    selected_edge: Option<(String, String)>,
+    reference_scanner_enabled: bool,
     pan: Vec2,
     zoom: f32,
     live_physics: bool,
+    frame_rate_cap: FrameRateCap,
+    idle_throttle: bool,
+    pause_when_unfocused: bool,
+    background_paused: bool,
     physics_intensity: f32,
     physics_repulsion: f32,
     physics_spring: f32,
@@ -57,18 +320,174 @@ struct ViewModel {
     physics_velocity_damping: f32,
     physics_target_spread: f32,
     physics_spread_force: f32,
+    /// Run repulsion and collisions on the thread pool for large graphs.
+    parallel_physics: bool,
     show_quadtree_overlay: bool,
+    quadtree_shading: QuadtreeShading,
+    show_physics_heat: bool,
+    /// Primary drags measure a rectangle instead of moving nodes.
+    measure_mode: bool,
+    /// The measured rectangle's corners in world space.
+    measure_rect: Option<[Vec2; 2]>,
+    /// Annotations of this root, saved across sessions.
+    annotations: Vec<Annotation>,
+    /// Primary drags draw this shape instead of moving nodes.
+    annotation_tool: Option<AnnotationShape>,
+    annotation_role: AnnotationRole,
+    /// Text given to the next annotation drawn.
+    annotation_text: String,
+    /// The corners of the annotation being dragged out, in world space.
+    annotation_draft: Option<[Vec2; 2]>,
+    show_annotations: bool,
+    annotations_status: Option<String>,
+    /// Where the graph was last drawn, for fitting it to the view.
+    canvas_rect: Option<Rect>,
+    show_key_help: bool,
+    /// Set by the search shortcut until the search box takes focus.
+    focus_search: bool,
+    store_hashes: StoreHashes,
+    show_controls_panel: bool,
+    show_details_panel: bool,
+    controls_panel_width: f32,
+    details_panel_width: f32,
+    show_findings_window: bool,
+    show_diff_window: bool,
+    show_lock_window: bool,
+    show_attribution_window: bool,
+    show_generations_window: bool,
+    generations: Option<GenerationBrowser>,
+    lockfile_path: String,
+    lock: Option<ClosureLock>,
+    /// `lock` checked against the current graph.
+    lock_deviations: Vec<LockDeviation>,
+    lockfile_status: Option<String>,
+    compare_path: String,
+    comparison: Option<ComparisonState>,
+    diff_filter: DiffFilter,
+    highlight_diff: bool,
+    gc_roots: Option<GcRootScan>,
+    /// Render only paths that no GC root outside this system keeps alive.
+    gc_sole_only: bool,
+    color_by_root: bool,
+    /// Color nodes by their share of their immediate dominator's retained size.
+    color_by_dominator_share: bool,
+    unique_root_tab: usize,
+    /// Per root of a merged graph, the paths only it reaches.
+    unique_root_rankings: Vec<Vec<String>>,
+    show_legend_overlay: bool,
+    publication_mode: bool,
+    label_pname_only: bool,
+    /// Draw a category glyph inside nodes large enough to hold one.
+    show_node_glyphs: bool,
+    /// Fold small nearby nodes into counted clusters when zoomed far out.
+    cluster_at_low_zoom: bool,
+    node_glyphs: GlyphAtlas,
+    edge_style: EdgeStyle,
+    /// Arrowheads at the referenced end of edges, when zoomed in enough.
+    show_edge_arrows: bool,
+    /// Draw node circles and edges with the instanced GPU renderer when the
+    /// context supports it.
+    gpu_rendering: bool,
+    graph_layout: GraphLayout,
+    /// Keep physics off the nodes of a computed layout.
+    freeze_layout: bool,
+    /// Connect drawn nodes that only reach each other through hidden paths.
+    bridge_hidden: bool,
+    impact_pulse_enabled: bool,
+    impact_pulse: Option<ImpactPulse>,
+    pinned_positions: HashMap<String, Vec2>,
+    static_nodes: HashSet<String>,
+    dragged_node: Option<String>,
+    pins_status: Option<String>,
+    /// Save node positions on reload and exit, and restore them when a
+    /// mostly overlapping closure is loaded.
+    remember_layout: bool,
+    /// The saved layout waiting for the first render graph.
+    saved_layout: Option<RestoredLayout>,
+    layout_status: Option<String>,
+    /// Layout file written by Export layout and read by Import layout.
+    layout_file_path: String,
+    /// Paths picked with Ctrl+click, on top of the single selection.
+    marked: BTreeSet<String>,
+    /// Paths left out of the graph from the multi-selection.
+    hidden_nodes: BTreeSet<String>,
+    hidden_savings: Option<HiddenSavings>,
+    removal_simulation: Option<RemovalSimulation>,
+    attribution: Option<Vec<TopLevelShare>>,
+    /// Paths per store directory, when the graph spans more than one store.
+    store_dirs: Vec<(String, usize)>,
+    /// Store directories whose paths are left out of the graph.
+    hidden_stores: BTreeSet<String>,
+    /// Draw only what was expanded from an anchor instead of filtering by
+    /// size.
+    explore_mode: bool,
+    /// Where exploration starts; the root when unset.
+    explore_anchor: Option<String>,
+    /// Nodes whose direct dependencies are drawn in explore mode.
+    expanded_nodes: BTreeSet<String>,
+    selection_sets: SelectionSets,
+    /// Earlier and later selections with their camera, for Back and Forward.
+    navigation: NavigationHistory,
+    selection_set_name: String,
+    selection_sets_status: Option<String>,
+    set_summaries: HashMap<String, SetSummary>,
+    set_comparison_names: (String, String),
+    set_comparison: Option<SetComparison>,
+    bookmarks: Vec<ViewBookmark>,
+    bookmark_name: String,
+    bookmark_filters: bool,
+    bookmarks_status: Option<String>,
+    node_bookmarks: Vec<NodeBookmark>,
+    node_bookmarks_status: Option<String>,
+    neighborhood_hops: usize,
+    neighborhood_format: ExportFormat,
+    neighborhood_export_path: String,
+    neighborhood_export_status: Option<String>,
+    graph_export_format: ExportFormat,
+    graph_export_path: String,
+    image_export_format: ImageFormat,
+    image_export_extent: ImageExtent,
+    image_export_scale: f32,
+    image_export_path: String,
+    image_export_request: Option<ImageRequest>,
+    badge_classes: NodeClasses,
+    class_include: NodeClasses,
+    class_exclude: NodeClasses,
+    ignore_patterns: String,
+    /// `ignore_patterns` split at commas, refreshed when they are edited.
+    ignore_terms: Vec<String>,
+    effective_totals: Option<EffectiveTotals>,
+    name_filter_expression: String,
+    name_filter: NameFilter,
+    name_filter_error: Option<String>,
     graph_dirty: bool,
     render_graph_revision: u64,
     graph_cache: Option<RenderGraph>,
+    camera_animation: Option<CameraAnimation>,
     search_match_cache: Option<SearchMatchCache>,
+    search_results: Option<SearchResultsCache>,
     details_panel_cache: Option<DetailsPanelCache>,
+    edge_details_cache: Option<EdgeDetailsCache>,
+    edge_reference_scan: Option<EdgeReferenceScan>,
+    verifications: HashMap<String, VerificationState>,
+    attribute_lookups: HashMap<String, AttributeLookup>,
+    content_scans: HashMap<String, ContentScan>,
+    /// Previews fetched for hovered paths, kept for a later hover.
+    path_previews: HashMap<String, PathPreviewState>,
+    hover_card: Option<HoverCard>,
+    /// The node a context menu was last opened on in the graph.
+    context_node: Option<String>,
+    node_report: Option<NodeReport>,
     nar_ranking: Vec<String>,
     closure_ranking: Vec<String>,
+    closure_count_ranking: Vec<String>,
+    retained_ranking: Vec<String>,
     dependency_ranking: Vec<String>,
     reverse_dependency_ranking: Vec<String>,
     nar_rows_visible: usize,
     closure_rows_visible: usize,
+    closure_count_rows_visible: usize,
+    retained_rows_visible: usize,
     dependency_rows_visible: usize,
     referrer_rows_visible: usize,
     size_ranking_mode: SizeRankingMode,
@@ -92,10 +511,72 @@ struct SearchMatchCache {
     matches: Arc<HashSet<usize>>,
 }
 
+/// Every path in the graph matching a search, ranked by a metric.
+struct SearchResultsCache {
+    query: String,
+    metric: SizeMetric,
+    ids: Vec<String>,
+}
+
+/// The paths and bytes left by the class filters and ignore patterns, for the
+/// filters they were counted under.
+struct EffectiveTotals {
+    class_include: NodeClasses,
+    class_exclude: NodeClasses,
+    ignore_terms: Vec<String>,
+    count: usize,
+    bytes: u64,
+}
+
+/// What taking the hidden paths out of the closure would free, for the
+/// hidden set it was computed for.
+struct HiddenSavings {
+    hidden: BTreeSet<String>,
+    paths: usize,
+    nar_size: u64,
+}
+
+/// What taking one path out of a merged closure would free, found by walking
+/// the references from the roots without it.
+struct RemovalSimulation {
+    id: String,
+    state: RemovalState,
+}
+
+enum RemovalState {
+    Running(Box<Job<RemovalSavings>>),
+    Done(Result<RemovalSavings, String>),
+}
+
+struct RemovalSavings {
+    paths: usize,
+    nar_size: u64,
+}
+
+/// A selection set resolved against the loaded graph.
+#[derive(Clone, Copy)]
+struct SetSummary {
+    present: usize,
+    missing: usize,
+    nar_size: u64,
+    closure_paths: usize,
+    closure_size: u64,
+}
+
+/// How the closures of two selection sets overlap, as path counts and bytes.
+struct SetComparison {
+    names: (String, String),
+    only_first: (usize, u64),
+    only_second: (usize, u64),
+    shared: (usize, u64),
+}
+
 struct DetailsPanelCache {
     key: DetailsPanelCacheKey,
     related_nodes: Vec<RelatedNodeEntry>,
     shortest_path_from_root: Option<Vec<String>>,
+    /// Filled the first time the "All paths from root" section is opened.
+    all_root_paths: Option<Vec<Vec<String>>>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -106,9 +587,107 @@ struct DetailsPanelCacheKey {
     related_limit: usize,
 }
 
+struct EdgeDetailsCache {
+    edge: (String, String),
+    is_root_path: bool,
+    unreachable: Vec<String>,
+    freed_bytes: u64,
+}
+
+struct EdgeReferenceScan {
+    edge: (String, String),
+    job: Option<Job<ReferenceMatches>>,
+    result: Option<Result<ReferenceMatches, String>>,
+}
+
+enum VerificationState {
+    Running(Box<Job<VerifyOutcome>>),
+    Done(Result<VerifyOutcome, String>),
+}
+
+enum ContentScan {
+    Running(Job<ContentEntry>),
+    Done(Result<ContentEntry, String>),
+}
+
+enum NodeReportState {
+    Running(Box<Job<String>>),
+    Done(Result<String, String>),
+}
+
+/// The output of a nix command run from a node's context menu.
+struct NodeReport {
+    title: String,
+    state: NodeReportState,
+}
+
+enum PathPreviewState {
+    Running(Job<PathPreview>),
+    Done(Result<PathPreview, String>),
+}
+
+/// The hover readout of one render node, kept while the pointer stays on it.
+struct HoverCard {
+    index: usize,
+    render_graph_revision: u64,
+    metric: SizeMetric,
+    id: String,
+    summary: String,
+    preview: Option<String>,
+}
+
+enum AttributeLookup {
+    Running(Box<Job<Vec<String>>>),
+    Done(Result<Vec<String>, String>),
+}
+
+enum ComparisonState {
+    Running(Job<ClosureDiff>),
+    Done(Result<ClosureDiff, String>),
+}
+
+/// The system generations listed in the generation browser, read in the
+/// background when its window first opens.
+struct GenerationBrowser {
+    generations: GenerationListing,
+    /// Closure sizes by generation number, each queried when its row asks.
+    sizes: HashMap<u64, ClosureSizeQuery>,
+    /// Generations ticked for a diff, in the order they were ticked; at most
+    /// two.
+    diff_marks: Vec<u64>,
+}
+
+enum GenerationListing {
+    Running(Box<Job<Vec<Generation>>>),
+    Done(Result<Vec<Generation>, String>),
+}
+
+enum ClosureSizeQuery {
+    Running(Box<Job<u64>>),
+    Done(Result<u64, String>),
+}
+
+enum GcRootScan {
+    Running(Job<GcRoots>),
+    Done(Result<GcRoots, String>),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DiffFilter {
+    All,
+    Added,
+    Removed,
+    Changed,
+}
+
 struct RenderGraph {
     nodes: Vec<RenderNode>,
     edges: Vec<(usize, usize)>,
+    /// Dashed edges standing in for connections through hidden paths.
+    bridge_edges: Vec<(usize, usize)>,
+    /// Scanned weight of each edge, parallel to `edges`; one when unscanned.
+    edge_weights: Vec<u32>,
+    max_edge_weight: u32,
     index_by_id: HashMap<String, usize>,
     outgoing: Vec<Vec<usize>>,
     incoming: Vec<Vec<usize>>,
@@ -121,8 +700,10 @@ struct RenderGraph {
 
 struct PhysicsScratch {
     forces: Vec<Vec2>,
+    collisions: Vec<Vec2>,
     positions: Vec<Vec2>,
     radii: Vec<f32>,
+    activity: physics::PhysicsActivity,
 }
 
 struct ViewScratch {
@@ -134,6 +715,10 @@ struct ViewScratch {
     draw_order_dirty: bool,
     quadtree_positions: Vec<Vec2>,
     quadtree_cells: Vec<physics::QuadtreeCell>,
+    clusterable: Vec<bool>,
+    node_clusters: Vec<physics::NodeCluster>,
+    /// The cluster each node is folded into this frame.
+    cluster_of: Vec<Option<usize>>,
 }
 
 struct RenderNode {
@@ -142,6 +727,8 @@ struct RenderNode {
     velocity: Vec2,
     metric_value: u64,
     base_radius: f32,
+    pinned: bool,
+    layout_static: bool,
 }
 
 struct HighlightState {
@@ -151,6 +738,15 @@ struct HighlightState {
     root_path_edges: HashSet<(usize, usize)>,
 }
 
+/// The animated "impact radius" pulse spreading out from the selected node.
+struct ImpactPulse {
+    selected: String,
+    graph_revision: u64,
+    started: f64,
+    hops: Vec<u32>,
+    max_hop: u32,
+}
+
 #[derive(Clone)]
 struct RelatedNodeEntry {
     id: String,
@@ -170,57 +766,321 @@ struct PhysicsConfig {
     target_spread: f32,
     spread_force: f32,
     delta_seconds: f32,
+    parallel: bool,
 }
 
 impl NixAnalyzeApp {
-    pub fn new(_cc: &eframe::CreationContext<'_>, system_path: String) -> Self {
-        let state = Self::start_load(system_path.clone());
+    const STORE_WATCH_INTERVAL: Duration = Duration::from_secs(5);
+    const PROFILE_WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+    pub fn new(
+        cc: &eframe::CreationContext<'_>,
+        system_path: String,
+        scenario: Option<Arc<Scenario>>,
+        options: AppOptions,
+    ) -> Self {
+        let passes = CollectPasses {
+            edge_weights: options.edge_weights,
+            derivation_info: options.derivation_info,
+        };
+        let state = AppState::Loading(Box::new(LoadJob::spawn(
+            &system_path,
+            &options.nix_settings,
+            options.demo_nodes,
+            options.whole_store,
+            passes,
+            options.graph_file.clone(),
+            &options.extra_roots,
+        )));
+        let graph_renderer = cc.gl.as_ref().and_then(|gl| {
+            install_graph_renderer(&cc.egui_ctx, gl)
+                .inspect_err(|error| eprintln!("drawing the graph without instancing: {error}"))
+                .ok()
+        });
         Self {
             system_path,
+            scenario,
+            nix_settings: options.nix_settings,
+            validate_graph: options.validate_graph || cfg!(debug_assertions),
+            demo_nodes: options.demo_nodes,
+            whole_store: options.whole_store,
+            passes,
+            graph_file: options.graph_file,
+            extra_roots: options.extra_roots,
+            compare_path: options.compare_path,
+            layout_file: options.layout_file,
+            root_input: String::new(),
             state,
-            reload_rx: None,
+            reload_job: None,
+            store_watcher: None,
+            profile_watcher: None,
+            open_diff_on_load: false,
+            size_history: Arc::new(SizeHistory::load()),
+            settings: Settings::load(),
+            toasts: Toasts::default(),
+            graph_renderer,
         }
     }
+}
 
-    fn spawn_load(system_path: String) -> Receiver<Result<SystemGraph, String>> {
-        let (tx, rx) = mpsc::channel();
+impl NixAnalyzeApp {
+    /// Switches to the closure of another root and starts loading it, leaving
+    /// demo, saved-graph, whole-store and multi-root modes.
+    fn open_root(&mut self, root: &str) -> LoadJob {
+        self.system_path = expand_home(root.trim());
+        self.demo_nodes = None;
+        self.whole_store = false;
+        self.graph_file = None;
+        self.extra_roots.clear();
+        self.store_watcher = None;
+        self.profile_watcher = None;
+        LoadJob::spawn(
+            &self.system_path,
+            &self.nix_settings,
+            None,
+            false,
+            self.passes,
+            None,
+            &[],
+        )
+    }
+}
+
+/// Expands a leading `~/`, so profiles such as `~/.nix-profile` can be typed
+/// as in a shell.
+fn expand_home(root: &str) -> String {
+    match (root.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => Path::new(&home).join(rest).display().to_string(),
+        _ => root.to_owned(),
+    }
+}
+
+/// The store name, without hash, of a profile's target.
+fn profile_name(target: &Path) -> String {
+    target
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map_or_else(
+            || target.display().to_string(),
+            |name| short_name(name).to_owned(),
+        )
+}
+
+/// Adds a loaded closure to the size history shown in the details panel.
+/// Demo graphs, saved graphs, whole-store scans and merged roots are skipped
+/// so that only freshly collected system closures are compared.
+fn record_size_history(
+    history: &mut Arc<SizeHistory>,
+    graph: &SystemGraph,
+    skip: bool,
+    toasts: &mut Toasts,
+) {
+    if skip || !Arc::make_mut(history).record(graph) {
+        return;
+    }
 
-        thread::spawn(move || {
-            let result = collect_system_graph(&system_path).map_err(|error| error.to_string());
-            let _ = tx.send(result);
+    if let Err(error) = history.save() {
+        toasts.push(Toast::new(
+            ToastLevel::Warning,
+            format!("Failed to save size history: {error:#}"),
+        ));
+    }
+}
+
+/// Warns about whatever a restricted-store fallback could not provide.
+fn limitation_toasts(graph: &SystemGraph) -> impl Iterator<Item = Toast> + use<'_> {
+    graph
+        .limitations
+        .iter()
+        .map(|limitation| Toast::new(ToastLevel::Warning, limitation.clone()))
+}
+
+impl LoadJob {
+    fn spawn(
+        system_path: &str,
+        nix_settings: &NixSettings,
+        demo_nodes: Option<usize>,
+        whole_store: bool,
+        passes: CollectPasses,
+        graph_file: Option<PathBuf>,
+        extra_roots: &[String],
+    ) -> Self {
+        let (partial_tx, partial_rx) = mpsc::channel();
+        let system_path = system_path.to_owned();
+        let system_paths = (!extra_roots.is_empty()).then(|| {
+            [system_path.clone()]
+                .into_iter()
+                .chain(extra_roots.iter().cloned())
+                .collect::<Vec<_>>()
+        });
+
+        let collect = Job::spawn(nix_settings, move |control| match demo_nodes {
+            Some(node_count) => Ok(Some(generate_demo_graph(node_count))),
+            None if let Some(path) = graph_file => load_graph(&path).map(Some),
+            None if whole_store => {
+                stream_store_shards(control, &system_path, |shard| {
+                    let _ = partial_tx.send(shard);
+                })?;
+                Ok(None)
+            }
+            None => {
+                let mut graph = match &system_paths {
+                    Some(system_paths) => collect_multi_root_graph(control, system_paths)?,
+                    None => collect_system_graph(control, &system_path)?,
+                };
+                if passes.edge_weights {
+                    scan_edge_weights(control, &mut graph)?;
+                }
+                if passes.derivation_info {
+                    enrich_derivations(control, &mut graph)?;
+                }
+                Ok(Some(graph))
+            }
         });
 
-        rx
+        Self {
+            collect,
+            partial_rx,
+            preview: ShardPreview::default(),
+            streamed: false,
+            views_stale: false,
+            views_refreshed: Instant::now(),
+        }
+    }
+
+    /// The collected graph once the job finished. A whole-store scan not yet
+    /// streamed into a view is assembled from its queued shards; once it was,
+    /// `Ok(None)` reports that the streamed graph is complete.
+    fn poll_graph(&mut self) -> Option<Result<Option<SystemGraph>, String>> {
+        Some(match self.collect.poll()? {
+            Ok(None) if !self.streamed => self
+                .assemble_shards()
+                .map(Some)
+                .ok_or_else(|| "The store scan returned no paths".to_owned()),
+            result => result,
+        })
     }
 
-    fn start_load(system_path: String) -> AppState {
-        AppState::Loading {
-            rx: Self::spawn_load(system_path),
+    /// A graph of the shards queued so far, if any.
+    fn assemble_shards(&mut self) -> Option<SystemGraph> {
+        let mut graph = self.preview.start(self.partial_rx.try_recv().ok()?);
+        for shard in self.partial_rx.try_iter() {
+            self.preview.merge(&mut graph, shard);
         }
+        Some(graph)
+    }
+
+    /// Merges the shards streamed since the last frame into `model`, and
+    /// recomputes its views at most once per `SHARD_REFRESH_INTERVAL`.
+    fn merge_streamed_shards(&mut self, model: &mut ViewModel) {
+        if !self.streamed {
+            return;
+        }
+        let shards = self.partial_rx.try_iter().collect::<Vec<_>>();
+        if !shards.is_empty() {
+            model.merge_store_shards(&mut self.preview, shards);
+            self.views_stale = true;
+        }
+        if self.views_stale && self.views_refreshed.elapsed() >= SHARD_REFRESH_INTERVAL {
+            model.refresh_graph_views();
+            self.views_stale = false;
+            self.views_refreshed = Instant::now();
+        }
+    }
+
+    fn poll_progress(&mut self) -> Option<&str> {
+        self.collect.poll_progress()
+    }
+
+    fn take_warning_toasts(&mut self) -> impl Iterator<Item = Toast> + use<> {
+        self.collect
+            .take_warnings()
+            .into_iter()
+            .map(|warning| Toast::new(ToastLevel::Warning, warning))
     }
 }
 
 impl eframe::App for NixAnalyzeApp {
     fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {
         let mut transition = None;
+        let mut root_request = None;
 
         match &mut self.state {
-            AppState::Loading { rx } => {
-                if let Ok(result) = rx.try_recv() {
+            AppState::Loading(job) => {
+                if let Some(result) = job.poll_graph() {
                     transition = Some(match result {
-                        Ok(graph) => AppState::Ready(Box::new(ViewModel::new(graph))),
+                        Ok(Some(graph)) => {
+                            self.toasts.extend(limitation_toasts(&graph));
+                            record_size_history(
+                                &mut self.size_history,
+                                &graph,
+                                self.demo_nodes.is_some()
+                                    || self.whole_store
+                                    || self.graph_file.is_some()
+                                    || !self.extra_roots.is_empty(),
+                                &mut self.toasts,
+                            );
+                            let mut model = ViewModel::new(graph, self.scenario.clone());
+                            model.nix_settings = self.nix_settings.clone();
+                            model.validate_graph = self.validate_graph;
+                            model.size_history = self.size_history.clone();
+                            self.settings.apply(&mut model);
+                            if let Some(path) = &self.compare_path {
+                                model.start_comparison(path);
+                                model.show_diff_window |=
+                                    std::mem::take(&mut self.open_diff_on_load);
+                            }
+                            if let Some(path) = &self.layout_file {
+                                model.import_layout(path);
+                            }
+                            AppState::Ready(Workspaces::new(model))
+                        }
+                        Ok(None) => AppState::Error("The store scan returned no paths".to_owned()),
                         Err(error) => AppState::Error(error),
                     });
+                } else if let Some(graph) = job.assemble_shards() {
+                    // Show the first shards right away; the job keeps running
+                    // in the status bar and feeds in the remaining ones.
+                    job.streamed = true;
+                    let mut model = ViewModel::new(graph, self.scenario.clone());
+                    model.nix_settings = self.nix_settings.clone();
+                    model.validate_graph = self.validate_graph;
+                    self.settings.apply(&mut model);
+                    transition = Some(AppState::Ready(Workspaces::new(model)));
                 }
 
+                let progress = job.poll_progress().map(str::to_owned);
+                let paths = job.collect.path_progress();
+                self.toasts.extend(job.take_warning_toasts());
                 egui::CentralPanel::default().show(ctx, |ui| {
                     ui.vertical_centered(|ui| {
                         ui.add_space(120.0);
                         ui.heading("Loading NixOS closure graph...");
                         ui.add_space(8.0);
                         ui.spinner();
+                        if let Some((done, total)) = paths {
+                            ui.add_space(6.0);
+                            ui.add(
+                                egui::ProgressBar::new(done as f32 / total as f32)
+                                    .desired_width(320.0)
+                                    .text(format!(
+                                        "{} of {} paths",
+                                        format_count(done as u64),
+                                        format_count(total as u64)
+                                    )),
+                            );
+                        }
+                        if let Some(progress) = &progress {
+                            ui.add_space(6.0);
+                            ui.weak(progress);
+                        }
+                        ui.add_space(10.0);
+                        if ui.button("Cancel").clicked() {
+                            job.collect.cancel();
+                        }
                     });
                 });
+                ctx.request_repaint_after(Duration::from_millis(250));
             }
             AppState::Error(error) => {
                 egui::CentralPanel::default().show(ctx, |ui| {
@@ -229,48 +1089,417 @@ impl eframe::App for NixAnalyzeApp {
                     ui.label(error.as_str());
                     ui.add_space(10.0);
                     if ui.button("Retry").clicked() {
-                        transition = Some(Self::start_load(self.system_path.clone()));
+                        transition = Some(AppState::Loading(Box::new(LoadJob::spawn(
+                            &self.system_path,
+                            &self.nix_settings,
+                            self.demo_nodes,
+                            self.whole_store,
+                            self.passes,
+                            self.graph_file.clone(),
+                            &self.extra_roots,
+                        ))));
                     }
+                    ui.add_space(10.0);
+                    ui.horizontal(|ui| {
+                        let input = ui.add(
+                            egui::TextEdit::singleline(&mut self.root_input)
+                                .hint_text(ROOT_INPUT_HINT)
+                                .desired_width(360.0),
+                        );
+                        let submitted = input.lost_focus()
+                            && ui.input(|input| input.key_pressed(egui::Key::Enter));
+                        if (ui.button("Open another root").clicked() || submitted)
+                            && !self.root_input.trim().is_empty()
+                        {
+                            root_request = Some(std::mem::take(&mut self.root_input));
+                        }
+                    });
                 });
             }
-            AppState::Ready(model) => {
-                let mut reload_requested = false;
-                let is_reloading = self.reload_rx.is_some();
+            AppState::Ready(workspaces) => {
+                let window_inactive = ctx.input(|input| {
+                    let viewport = input.viewport();
+                    viewport.focused == Some(false) || viewport.minimized == Some(true)
+                });
+                workspaces.draw_tabs(ctx);
+
+                let model = workspaces.active_mut();
+                model.background_paused = model.pause_when_unfocused && window_inactive;
+
+                match model.draw_status_bar(
+                    ctx,
+                    self.reload_job.as_mut(),
+                    self.store_watcher.is_some(),
+                ) {
+                    Some(StatusBarAction::CancelReload) => self.reload_job = None,
+                    Some(StatusBarAction::StopWatching) => {
+                        workspaces.full_mut().watch_store = false;
+                    }
+                    None => {}
+                }
+
+                let mut reload_requested = workspaces.full_mut().draw_profile_banner(ctx);
+                let model = workspaces.active_mut();
+                let is_reloading = self.reload_job.is_some();
                 model.show(ctx, &self.system_path, &mut reload_requested, is_reloading);
+                root_request = model.root_request.take();
+                if root_request.is_some()
+                    && let Some(base) = model.root_request_compare.take()
+                {
+                    self.compare_path = Some(base);
+                    self.open_diff_on_load = true;
+                }
+                workspaces.handle_requests();
+
+                // Reloads and store updates always target the full graph.
+                let model = workspaces.full_mut();
+                model.background_paused = model.pause_when_unfocused && window_inactive;
 
-                if reload_requested && self.reload_rx.is_none() {
-                    self.reload_rx = Some(Self::spawn_load(self.system_path.clone()));
+                let profile_watchable =
+                    self.demo_nodes.is_none() && self.graph_file.is_none() && !self.whole_store;
+                // The store watcher already reloads when the profile switches.
+                if model.watch_profile && !model.watch_store && profile_watchable {
+                    if self.profile_watcher.is_none() {
+                        self.profile_watcher = Some(ProfileWatcher::spawn(
+                            self.system_path.clone(),
+                            Self::PROFILE_WATCH_INTERVAL,
+                        ));
+                    }
+                } else {
+                    self.profile_watcher = None;
+                    model.profile_switch = None;
                 }
 
-                if let Some(rx) = self.reload_rx.take() {
-                    match rx.try_recv() {
-                        Ok(result) => {
-                            transition = Some(match result {
-                                Ok(graph) => AppState::Ready(Box::new(ViewModel::new(graph))),
-                                Err(error) => AppState::Error(error),
-                            });
+                if let Some(watcher) = &self.profile_watcher {
+                    if let Some(target) = watcher.rx.try_iter().last() {
+                        if model.auto_reload_profile {
+                            self.toasts.push(Toast::new(
+                                ToastLevel::Info,
+                                format!(
+                                    "System profile switched to {}; reloading",
+                                    profile_name(&target)
+                                ),
+                            ));
+                            reload_requested = true;
+                        } else {
+                            model.profile_switch = Some(target);
                         }
-                        Err(TryRecvError::Empty) => {
-                            self.reload_rx = Some(rx);
+                    }
+                    if !model.background_paused {
+                        ctx.request_repaint_after(Self::PROFILE_WATCH_INTERVAL);
+                    }
+                }
+
+                if reload_requested && self.reload_job.is_none() {
+                    model.profile_switch = None;
+                    self.reload_job = Some(LoadJob::spawn(
+                        &self.system_path,
+                        &self.nix_settings,
+                        self.demo_nodes,
+                        self.whole_store,
+                        self.passes,
+                        self.graph_file.clone(),
+                        &self.extra_roots,
+                    ));
+                }
+
+                if model.watch_store && self.demo_nodes.is_some() {
+                    model.watch_store = false;
+                    model.store_update_status =
+                        Some("store watching is unavailable in demo mode".to_owned());
+                } else if model.watch_store && self.graph_file.is_some() {
+                    model.watch_store = false;
+                    model.store_update_status =
+                        Some("store watching is unavailable for saved graphs".to_owned());
+                } else if model.watch_store && (self.whole_store || !self.extra_roots.is_empty()) {
+                    model.watch_store = false;
+                    model.store_update_status =
+                        Some("store watching only follows a single closure".to_owned());
+                } else if model.watch_store && self.store_watcher.is_none() {
+                    self.store_watcher = Some(StoreWatcher::spawn(
+                        self.system_path.clone(),
+                        &model.graph,
+                        Self::STORE_WATCH_INTERVAL,
+                        &self.nix_settings,
+                    ));
+                } else if !model.watch_store {
+                    self.store_watcher = None;
+                }
+
+                if let Some(watcher) = &self.store_watcher {
+                    watcher.set_paused(model.background_paused);
+                    match watcher.rx.try_recv() {
+                        Ok(Ok(delta)) => {
+                            model.apply_store_delta(delta);
+                            record_size_history(
+                                &mut self.size_history,
+                                &model.graph,
+                                self.demo_nodes.is_some()
+                                    || self.whole_store
+                                    || self.graph_file.is_some()
+                                    || !self.extra_roots.is_empty(),
+                                &mut self.toasts,
+                            );
+                            model.size_history = self.size_history.clone();
+                        }
+                        Ok(Err(error)) => {
+                            self.toasts.push(Toast::new(
+                                ToastLevel::Error,
+                                format!("Store update failed: {error}"),
+                            ));
+                            model.store_update_status =
+                                Some(format!("store update failed: {error}"));
                         }
+                        Err(TryRecvError::Empty) => {}
                         Err(TryRecvError::Disconnected) => {
-                            transition = Some(AppState::Error(
-                                "Background load worker disconnected".to_owned(),
+                            model.store_update_status =
+                                Some("store watcher disconnected".to_owned());
+                            model.watch_store = false;
+                        }
+                    }
+                    if !model.background_paused {
+                        ctx.request_repaint_after(Duration::from_secs(1));
+                    }
+                }
+
+                if let Some(mut job) = self.reload_job.take() {
+                    self.toasts.extend(job.take_warning_toasts());
+                    job.merge_streamed_shards(model);
+
+                    match job.poll_graph() {
+                        Some(Ok(None)) => {
+                            self.toasts.push(Toast::new(
+                                ToastLevel::Info,
+                                format!("Loaded whole store: {} paths", model.graph.node_count()),
+                            ));
+                            model.refresh_graph_views();
+                            if let Some(path) = &self.compare_path {
+                                model.start_comparison(path);
+                            }
+                        }
+                        Some(Err(error)) => transition = Some(AppState::Error(error)),
+                        Some(Ok(Some(graph))) => {
+                            self.toasts.push(Toast::new(
+                                ToastLevel::Info,
+                                format!("Reloaded closure: {} paths", graph.node_count()),
                             ));
+                            self.toasts.extend(limitation_toasts(&graph));
+                            if let Err(error) = model.persist_layout() {
+                                self.toasts.push(Toast::new(
+                                    ToastLevel::Warning,
+                                    format!("Failed to save the layout: {error:#}"),
+                                ));
+                            }
+                            record_size_history(
+                                &mut self.size_history,
+                                &graph,
+                                self.demo_nodes.is_some()
+                                    || self.whole_store
+                                    || self.graph_file.is_some()
+                                    || !self.extra_roots.is_empty(),
+                                &mut self.toasts,
+                            );
+                            let mut next = ViewModel::new(graph, self.scenario.clone());
+                            next.nix_settings = self.nix_settings.clone();
+                            next.validate_graph = self.validate_graph;
+                            next.size_history = self.size_history.clone();
+                            next.watch_store = model.watch_store;
+                            self.settings.capture(model);
+                            self.settings.apply(&mut next);
+                            if let Some(path) = &self.compare_path {
+                                next.start_comparison(path);
+                                next.show_diff_window |=
+                                    std::mem::take(&mut self.open_diff_on_load);
+                            }
+                            transition = Some(AppState::Ready(Workspaces::new(next)));
+                        }
+                        None => {
+                            self.reload_job = Some(job);
                         }
                     }
                 }
+
+                self.toasts.extend(workspaces.take_toasts());
             }
         }
 
+        self.toasts.show(ctx);
+
         if let Some(next_state) = transition {
-            self.reload_rx = None;
-            self.state = next_state;
+            // The store watcher follows the graph it was started from; a new
+            // one starts from the replacing graph.
+            self.store_watcher = None;
+            let previous = std::mem::replace(&mut self.state, next_state);
+            self.reload_job = match previous {
+                AppState::Loading(job) if job.streamed => Some(*job),
+                _ => None,
+            };
+        }
+
+        if let Some(root) = root_request {
+            let job = self.open_root(&root);
+            // A loaded graph stays on screen until the new one replaces it.
+            if matches!(self.state, AppState::Ready(_)) {
+                self.reload_job = Some(job);
+            } else {
+                self.state = AppState::Loading(Box::new(job));
+            }
+        }
+    }
+
+    fn on_exit(&mut self, gl: Option<&eframe::glow::Context>) {
+        if let (Some(renderer), Some(gl)) = (&self.graph_renderer, gl) {
+            renderer.destroy(gl);
+        }
+        let AppState::Ready(workspaces) = &mut self.state else {
+            return;
+        };
+        self.settings.capture(workspaces.full_mut());
+        if self.demo_nodes.is_none()
+            && !self.whole_store
+            && self.graph_file.is_none()
+            && self.extra_roots.is_empty()
+        {
+            self.settings.last_root = Some(self.system_path.clone());
+        }
+        if let Err(error) = self.settings.save() {
+            eprintln!("failed to save settings: {error:#}");
+        }
+        if let Err(error) = workspaces.full_mut().persist_layout() {
+            eprintln!("failed to save layout: {error:#}");
+        }
+    }
+}
+
+impl EdgeStyle {
+    const ALL: [Self; 3] = [Self::Straight, Self::Curved, Self::Orthogonal];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Straight => "Straight",
+            Self::Curved => "Curved",
+            Self::Orthogonal => "Orthogonal",
+        }
+    }
+}
+
+impl GraphLayout {
+    const ALL: [Self; 3] = [Self::Force, Self::Layered, Self::Radial];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Force => "Force-directed",
+            Self::Layered => "Layered",
+            Self::Radial => "Radial",
+        }
+    }
+}
+
+impl QuadtreeShading {
+    const ALL: [Self; 3] = [Self::Outline, Self::Occupancy, Self::Size];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Outline => "Outline",
+            Self::Occupancy => "Occupancy",
+            Self::Size => "Size",
+        }
+    }
+}
+
+impl DiffFilter {
+    const ALL: [Self; 4] = [Self::All, Self::Added, Self::Removed, Self::Changed];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::All => "All",
+            Self::Added => "Added",
+            Self::Removed => "Removed",
+            Self::Changed => "Changed",
+        }
+    }
+}
+
+impl FrameRateCap {
+    const ALL: [Self; 3] = [Self::Fps30, Self::Fps60, Self::Uncapped];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Fps30 => "30",
+            Self::Fps60 => "60",
+            Self::Uncapped => "Uncapped",
+        }
+    }
+
+    fn frame_interval(self) -> Option<Duration> {
+        match self {
+            Self::Fps30 => Some(Duration::from_micros(33_333)),
+            Self::Fps60 => Some(Duration::from_micros(16_667)),
+            Self::Uncapped => None,
+        }
+    }
+
+    fn request_frame(self, ctx: &Context) {
+        match self.frame_interval() {
+            Some(interval) => ctx.request_repaint_after(interval),
+            None => ctx.request_repaint(),
         }
     }
 }
 
 impl ViewModel {
+    pub(in crate::app) fn passes_class_filter(&self, classes: NodeClasses) -> bool {
+        !classes.intersects(self.class_exclude)
+            && (self.class_include.is_empty() || classes.intersects(self.class_include))
+    }
+
+    pub(in crate::app) fn class_filter_active(&self) -> bool {
+        !self.class_include.is_empty() || !self.class_exclude.is_empty()
+    }
+
+    /// The graph as shared with background jobs, copied at most once per
+    /// change to it.
+    pub(in crate::app) fn shared_graph(&mut self) -> Arc<SystemGraph> {
+        self.shared_graph
+            .get_or_insert_with(|| Arc::new(self.graph.clone()))
+            .clone()
+    }
+
+    /// How `panel` names a path; every label of a store path goes through
+    /// here so that each panel's store hash toggle applies to all of them.
+    pub(in crate::app) fn path_label<'a>(&self, panel: LabelPanel, id: &'a str) -> &'a str {
+        self.store_hashes.label(panel, id)
+    }
+
+    pub(in crate::app) fn is_ignored(&self, id: &str) -> bool {
+        if self
+            .scenario
+            .as_ref()
+            .is_some_and(|scenario| scenario.is_ignored(id))
+        {
+            return true;
+        }
+
+        let name = short_name(id);
+        self.ignore_terms
+            .iter()
+            .any(|term| name.contains(term.as_str()))
+    }
+
+    /// Whether nodes are colored by which roots reach them instead of by metric.
+    pub(in crate::app) fn root_coloring(&self) -> bool {
+        self.color_by_root && self.graph.is_multi_root()
+    }
+
+    pub(in crate::app) fn trim_active(&self) -> bool {
+        self.class_filter_active()
+            || !self.ignore_terms.is_empty()
+            || self
+                .scenario
+                .as_ref()
+                .is_some_and(|scenario| !scenario.ignore.is_empty())
+    }
+
     pub(in crate::app) fn metric_threshold_value(&self) -> u64 {
         if self.metric.is_byte_metric() {
             (self.min_size_mb.max(0.0) * 1024.0 * 1024.0) as u64
@@ -297,9 +1526,12 @@ impl ViewModel {
 
     pub(in crate::app) fn format_metric_value(metric: SizeMetric, value: u64) -> String {
         match metric {
-            SizeMetric::NarSize | SizeMetric::ClosureSize => format_bytes(value),
-            SizeMetric::Dependencies => format!("{value} deps"),
-            SizeMetric::ReverseDependencies => format!("{value} refs"),
+            SizeMetric::NarSize | SizeMetric::ClosureSize | SizeMetric::RetainedSize => {
+                format_bytes(value)
+            }
+            SizeMetric::ClosureCount => format!("{} paths", format_count(value)),
+            SizeMetric::Dependencies => format!("{} deps", format_count(value)),
+            SizeMetric::ReverseDependencies => format!("{} refs", format_count(value)),
         }
     }
 }
@@ -0,0 +1,109 @@
+use eframe::egui::Vec2;
+
+/// Entries kept on the back stack; the oldest are dropped first.
+const MAX_ENTRIES: usize = 200;
+
+/// A place navigated to: the selection and the camera looking at it.
+#[derive(Clone, Debug, PartialEq)]
+pub(super) struct NavigationEntry {
+    pub(super) selected: Option<String>,
+    pub(super) pan: Vec2,
+    pub(super) zoom: f32,
+}
+
+/// Back and forward stacks over selection changes, like a browser's: going
+/// somewhere new drops whatever could be gone forward to.
+#[derive(Default)]
+pub(super) struct NavigationHistory {
+    back: Vec<NavigationEntry>,
+    forward: Vec<NavigationEntry>,
+}
+
+impl NavigationHistory {
+    /// Records `left` as the place just navigated away from.
+    pub(super) fn record(&mut self, left: NavigationEntry) {
+        self.forward.clear();
+        if self.back.last() == Some(&left) {
+            return;
+        }
+        if self.back.len() == MAX_ENTRIES {
+            self.back.remove(0);
+        }
+        self.back.push(left);
+    }
+
+    /// Steps back from `current`, which can then be gone forward to.
+    pub(super) fn back(&mut self, current: NavigationEntry) -> Option<NavigationEntry> {
+        let entry = self.back.pop()?;
+        self.forward.push(current);
+        Some(entry)
+    }
+
+    /// Steps forward from `current`, which can then be gone back to.
+    pub(super) fn forward(&mut self, current: NavigationEntry) -> Option<NavigationEntry> {
+        let entry = self.forward.pop()?;
+        self.back.push(current);
+        Some(entry)
+    }
+
+    pub(super) fn can_go_back(&self) -> bool {
+        !self.back.is_empty()
+    }
+
+    pub(super) fn can_go_forward(&self) -> bool {
+        !self.forward.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(selected: &str) -> NavigationEntry {
+        NavigationEntry {
+            selected: Some(selected.to_owned()),
+            pan: Vec2::ZERO,
+            zoom: 1.0,
+        }
+    }
+
+    #[test]
+    fn steps_back_and_forward_through_selections() {
+        let mut history = NavigationHistory::default();
+        history.record(at("a"));
+        history.record(at("b"));
+
+        assert_eq!(history.back(at("c")), Some(at("b")));
+        assert_eq!(history.back(at("b")), Some(at("a")));
+        assert_eq!(history.back(at("a")), None);
+        assert_eq!(history.forward(at("a")), Some(at("b")));
+        assert_eq!(history.forward(at("b")), Some(at("c")));
+        assert!(!history.can_go_forward());
+        assert!(history.can_go_back());
+    }
+
+    #[test]
+    fn new_navigation_drops_the_forward_stack() {
+        let mut history = NavigationHistory::default();
+        history.record(at("a"));
+        history.back(at("b"));
+        assert!(history.can_go_forward());
+
+        history.record(at("a"));
+        assert!(!history.can_go_forward());
+        assert_eq!(history.back(at("d")), Some(at("a")));
+    }
+
+    #[test]
+    fn keeps_a_bounded_back_stack() {
+        let mut history = NavigationHistory::default();
+        for index in 0..MAX_ENTRIES + 10 {
+            history.record(at(&index.to_string()));
+        }
+        let mut steps = 0;
+        while history.back(at("current")).is_some() {
+            steps += 1;
+        }
+        assert_eq!(steps, MAX_ENTRIES);
+    }
+}
@@ -1,3 +1,5 @@
+use std::cmp::Ordering;
+
 use eframe::egui::{Vec2, vec2};
 
 use super::quadtree::QuadNode;
@@ -26,21 +28,22 @@ fn repulsion_between(
     direction * (repulsion_strength / (distance_sq + softening))
 }
 
-fn collide_pair(
+/// The push `from` gets out of its overlap with `to`; `to` gets the
+/// opposite.
+fn collision_push(
     from: usize,
     to: usize,
     positions: &[Vec2],
     radii: &[f32],
     collision_strength: f32,
-    forces: &mut [Vec2],
-) {
+) -> Option<Vec2> {
     let delta = positions[from] - positions[to];
     let distance_sq = delta.length_sq();
 
     let min_distance = (radii[from] + radii[to]) * 4.2;
     let min_distance_sq = min_distance * min_distance;
     if distance_sq >= min_distance_sq {
-        return;
+        return None;
     }
 
     let direction = if distance_sq > DIRECTION_EPSILON_SQ {
@@ -50,9 +53,21 @@ fn collide_pair(
         vec2(angle.cos(), angle.sin())
     };
 
-    let overlap_push = (min_distance - distance_sq.sqrt()) * collision_strength;
-    forces[from] += direction * overlap_push;
-    forces[to] -= direction * overlap_push;
+    Some(direction * (min_distance - distance_sq.sqrt()) * collision_strength)
+}
+
+fn collide_pair(
+    from: usize,
+    to: usize,
+    positions: &[Vec2],
+    radii: &[f32],
+    collision_strength: f32,
+    forces: &mut [Vec2],
+) {
+    if let Some(push) = collision_push(from, to, positions, radii, collision_strength) {
+        forces[from] += push;
+        forces[to] -= push;
+    }
 }
 
 pub(super) fn accumulate_repulsion_for_node(
@@ -200,3 +215,51 @@ pub(super) fn accumulate_collision_pairs(
         }
     }
 }
+
+/// The collision push on one node from every node near it, the same force
+/// `accumulate_collision_pairs` adds for it, so nodes can run in parallel.
+pub(super) fn accumulate_collisions_for_node(
+    node: &QuadNode,
+    index: usize,
+    positions: &[Vec2],
+    radii: &[f32],
+    params: CollisionParams,
+    force: &mut Vec2,
+) {
+    if node.bounds.distance_sq_to_point(positions[index]) > params.max_collision_distance_sq {
+        return;
+    }
+
+    if node.is_leaf() {
+        for &other_index in &node.indices {
+            // Pairs keep the lower index first, so coincident nodes split
+            // along the same direction as in the pairwise pass.
+            let push = match index.cmp(&other_index) {
+                Ordering::Less => collision_push(
+                    index,
+                    other_index,
+                    positions,
+                    radii,
+                    params.collision_strength,
+                ),
+                Ordering::Greater => collision_push(
+                    other_index,
+                    index,
+                    positions,
+                    radii,
+                    params.collision_strength,
+                )
+                .map(|push| -push),
+                Ordering::Equal => None,
+            };
+            if let Some(push) = push {
+                *force += push;
+            }
+        }
+        return;
+    }
+
+    for child in node.children.iter().flatten() {
+        accumulate_collisions_for_node(child, index, positions, radii, params, force);
+    }
+}
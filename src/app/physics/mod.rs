@@ -2,14 +2,35 @@ mod forces;
 mod quadtree;
 
 use eframe::egui::{Vec2, vec2};
+use rayon::prelude::*;
 
 use super::{PhysicsConfig, RenderGraph, RenderNode};
-use forces::{CollisionParams, accumulate_collision_pairs, accumulate_repulsion_for_node};
-pub(in crate::app) use quadtree::QuadtreeCell;
-use quadtree::{QuadNode, collect_quadtree_cells};
+use forces::{
+    CollisionParams, accumulate_collision_pairs, accumulate_collisions_for_node,
+    accumulate_repulsion_for_node,
+};
+pub(in crate::app) use quadtree::{NodeCluster, QuadtreeCell};
+use quadtree::{QuadNode, collect_node_clusters, collect_quadtree_cells};
 
 const BARNES_HUT_THETA: f32 = 0.72;
 
+/// Below this many simulated nodes, spreading the repulsion and collision
+/// passes over threads costs more than it saves.
+pub(in crate::app) const PARALLEL_PHYSICS_MIN_NODES: usize = 1200;
+
+/// Force totals of the last step by source, for the physics heat overlay.
+#[derive(Clone, Copy, Default)]
+pub(in crate::app) struct PhysicsActivity {
+    pub(in crate::app) repulsion: f32,
+    pub(in crate::app) collision: f32,
+    pub(in crate::app) springs: f32,
+    pub(in crate::app) pull: f32,
+    /// The speed limit nodes are clamped to at the current intensity.
+    pub(in crate::app) speed_cap: f32,
+    /// Whether the repulsion and collision passes ran on the thread pool.
+    pub(in crate::app) parallel: bool,
+}
+
 pub(in crate::app) fn quadtree_cells(
     nodes: &[RenderNode],
     positions: &mut Vec<Vec2>,
@@ -26,7 +47,27 @@ pub(in crate::app) fn quadtree_cells(
         return;
     };
 
-    collect_quadtree_cells(&quadtree, 0, cells);
+    collect_quadtree_cells(&quadtree, 0, nodes, cells);
+}
+
+/// Groups the `clusterable` nodes by the quadtree cells at most `max_side`
+/// world units across.
+pub(in crate::app) fn node_clusters(
+    nodes: &[RenderNode],
+    positions: &mut Vec<Vec2>,
+    max_side: f32,
+    clusterable: &[bool],
+    clusters: &mut Vec<NodeCluster>,
+) {
+    positions.clear();
+    positions.extend(nodes.iter().map(|node| node.world_pos));
+
+    clusters.clear();
+    let Some(quadtree) = QuadNode::build(positions) else {
+        return;
+    };
+
+    collect_node_clusters(&quadtree, max_side, clusterable, nodes, clusters);
 }
 
 pub(super) fn step_physics(cache: &mut RenderGraph, config: PhysicsConfig) -> bool {
@@ -38,6 +79,8 @@ pub(super) fn step_physics(cache: &mut RenderGraph, config: PhysicsConfig) -> bo
     let scratch = &mut cache.physics_scratch;
     scratch.forces.resize(node_count, Vec2::ZERO);
     scratch.forces.fill(Vec2::ZERO);
+    scratch.collisions.resize(node_count, Vec2::ZERO);
+    scratch.collisions.fill(Vec2::ZERO);
     scratch.positions.clear();
     scratch.radii.clear();
     scratch
@@ -54,8 +97,10 @@ pub(super) fn step_physics(cache: &mut RenderGraph, config: PhysicsConfig) -> bo
     }
 
     let forces = &mut scratch.forces;
+    let collisions = &mut scratch.collisions;
     let positions = &scratch.positions;
     let radii = &scratch.radii;
+    let mut activity = PhysicsActivity::default();
 
     let intensity = config.intensity.clamp(0.2, 2.5);
     let repulsion_strength = 78_000.0 * intensity * config.repulsion_scale.clamp(0.25, 2.6);
@@ -70,8 +115,19 @@ pub(super) fn step_physics(cache: &mut RenderGraph, config: PhysicsConfig) -> bo
     let damping_factor = damping.powf(time_step_scale);
     let root_index = cache.root_index.filter(|&index| index < node_count);
 
-    if let Some(quadtree) = QuadNode::build(positions) {
-        for (index, force) in forces.iter_mut().enumerate() {
+    // Layout-static nodes sit on a fixed ring and are ignored by every force.
+    let simulated = (0..node_count)
+        .filter(|&index| !cache.nodes[index].layout_static)
+        .collect::<Vec<_>>();
+
+    activity.parallel = config.parallel && simulated.len() >= PARALLEL_PHYSICS_MIN_NODES;
+    let nodes = &cache.nodes;
+
+    if let Some(quadtree) = QuadNode::build_subset(positions, simulated) {
+        let repulse = |(index, force): (usize, &mut Vec2)| {
+            if nodes[index].layout_static {
+                return;
+            }
             accumulate_repulsion_for_node(
                 &quadtree,
                 index,
@@ -81,27 +137,51 @@ pub(super) fn step_physics(cache: &mut RenderGraph, config: PhysicsConfig) -> bo
                 BARNES_HUT_THETA,
                 force,
             );
+        };
+        if activity.parallel {
+            forces.par_iter_mut().enumerate().for_each(repulse);
+        } else {
+            forces.iter_mut().enumerate().for_each(repulse);
         }
 
         let max_collision_distance = (max_radius * 2.0) * 4.2;
+        let params = CollisionParams {
+            collision_strength,
+            max_collision_distance_sq: max_collision_distance * max_collision_distance,
+        };
         if max_collision_distance > 0.0 {
-            accumulate_collision_pairs(
-                &quadtree,
-                &quadtree,
-                true,
-                positions,
-                radii,
-                CollisionParams {
-                    collision_strength,
-                    max_collision_distance_sq: max_collision_distance * max_collision_distance,
-                },
-                forces,
-            );
+            if activity.parallel {
+                collisions
+                    .par_iter_mut()
+                    .enumerate()
+                    .for_each(|(index, collision)| {
+                        if !nodes[index].layout_static {
+                            accumulate_collisions_for_node(
+                                &quadtree, index, positions, radii, params, collision,
+                            );
+                        }
+                    });
+            } else {
+                accumulate_collision_pairs(
+                    &quadtree, &quadtree, true, positions, radii, params, collisions,
+                );
+            }
         }
     }
 
-    for &(from, to) in &cache.edges {
-        if from >= node_count || to >= node_count || from == to {
+    activity.repulsion = forces.iter().map(|force| force.length()).sum();
+    for (force, collision) in forces.iter_mut().zip(collisions.iter()) {
+        activity.collision += collision.length();
+        *force += *collision;
+    }
+
+    for &(from, to) in cache.edges.iter().chain(&cache.bridge_edges) {
+        if from >= node_count
+            || to >= node_count
+            || from == to
+            || cache.nodes[from].layout_static
+            || cache.nodes[to].layout_static
+        {
             continue;
         }
 
@@ -121,13 +201,16 @@ pub(super) fn step_physics(cache: &mut RenderGraph, config: PhysicsConfig) -> bo
 
         forces[from] -= correction;
         forces[to] += correction;
+        activity.springs += correction.length() * 2.0;
     }
 
     for (index, force) in forces.iter_mut().enumerate().take(node_count) {
-        *force -= cache.nodes[index].world_pos * center_pull;
+        let mut pull = cache.nodes[index].world_pos * center_pull;
         if Some(index) == root_index {
-            *force -= cache.nodes[index].world_pos * root_pull;
+            pull += cache.nodes[index].world_pos * root_pull;
         }
+        *force -= pull;
+        activity.pull += pull.length();
     }
 
     let target_radius = (node_count as f32).sqrt() * 42.0 * config.target_spread.clamp(0.6, 2.0);
@@ -136,7 +219,7 @@ pub(super) fn step_physics(cache: &mut RenderGraph, config: PhysicsConfig) -> bo
         let mut radius_sum = 0.0;
         let mut radius_count = 0usize;
         for (index, node) in cache.nodes.iter().enumerate() {
-            if Some(index) == root_index {
+            if Some(index) == root_index || node.layout_static {
                 continue;
             }
             radius_sum += node.world_pos.length();
@@ -161,10 +244,12 @@ pub(super) fn step_physics(cache: &mut RenderGraph, config: PhysicsConfig) -> bo
                     vec2(angle.cos(), angle.sin())
                 };
 
-                *force -= direction * radius_error * spread_force;
+                let mut pull = direction * radius_error * spread_force;
                 if radius > hard_limit {
-                    *force -= direction * (radius - hard_limit) * ((spread_force * 2.6) + 0.02);
+                    pull += direction * (radius - hard_limit) * ((spread_force * 2.6) + 0.02);
                 }
+                *force -= pull;
+                activity.pull += pull.length();
             }
         }
     }
@@ -173,11 +258,24 @@ pub(super) fn step_physics(cache: &mut RenderGraph, config: PhysicsConfig) -> bo
     let max_force_sq = max_force * max_force;
     let max_speed = 11.0 + (intensity * 15.0);
     let max_speed_sq = max_speed * max_speed;
+    activity.speed_cap = max_speed;
+    scratch.activity = activity;
     let min_sleep_speed_sq = 0.02 * 0.02;
     let min_sleep_force_sq = 0.08 * 0.08;
     let mut any_motion = false;
     let mut average_velocity = Vec2::ZERO;
+    let mut any_pinned = false;
+    let mut free_count = 0usize;
     for (index, force_value) in forces.iter().enumerate().take(node_count) {
+        // Pinned nodes act as infinite masses: their forces on others still
+        // apply, but nothing moves them.
+        if cache.nodes[index].pinned || cache.nodes[index].layout_static {
+            cache.nodes[index].velocity = Vec2::ZERO;
+            any_pinned = true;
+            continue;
+        }
+        free_count += 1;
+
         let mut force = *force_value;
         let force_sq = force.length_sq();
         if force_sq > max_force_sq {
@@ -205,13 +303,22 @@ pub(super) fn step_physics(cache: &mut RenderGraph, config: PhysicsConfig) -> bo
         }
     }
 
-    average_velocity /= node_count as f32;
+    average_velocity /= free_count.max(1) as f32;
     if average_velocity.length_sq() > 0.000_001 {
-        for node in &mut cache.nodes {
+        for node in cache
+            .nodes
+            .iter_mut()
+            .filter(|node| !node.pinned && !node.layout_static)
+        {
             node.velocity -= average_velocity;
         }
     }
 
+    // Pinned and static nodes anchor the layout, so only recenter free-floating graphs.
+    if any_pinned {
+        return any_motion;
+    }
+
     let mut centroid = Vec2::ZERO;
     for node in &cache.nodes {
         centroid += node.world_pos;
@@ -1,5 +1,7 @@
 use eframe::egui::{Vec2, vec2};
 
+use super::super::RenderNode;
+
 const QUADTREE_LEAF_CAPACITY: usize = 12;
 const QUADTREE_MAX_DEPTH: usize = 10;
 
@@ -72,6 +74,12 @@ impl QuadBounds {
         self.half_extent * 2.0
     }
 
+    pub(super) fn distance_sq_to_point(self, point: Vec2) -> f32 {
+        let dx = ((self.center.x - point.x).abs() - self.half_extent).max(0.0);
+        let dy = ((self.center.y - point.y).abs() - self.half_extent).max(0.0);
+        (dx * dx) + (dy * dy)
+    }
+
     pub(super) fn distance_sq_to(self, other: Self) -> f32 {
         let dx = (self.center.x - other.center.x).abs() - (self.half_extent + other.half_extent);
         let dy = (self.center.y - other.center.y).abs() - (self.half_extent + other.half_extent);
@@ -94,12 +102,21 @@ pub(in crate::app) struct QuadtreeCell {
     pub half_extent: f32,
     pub depth: usize,
     pub is_leaf: bool,
+    /// Nodes inside the cell.
+    pub count: usize,
+    /// The summed size metric of the nodes inside the cell.
+    pub metric_total: u64,
 }
 
 impl QuadNode {
     pub(super) fn build(positions: &[Vec2]) -> Option<Self> {
+        Self::build_subset(positions, (0..positions.len()).collect())
+    }
+
+    /// Builds a tree over only `indices`, so the other nodes neither exert
+    /// nor receive repulsion and collision forces.
+    pub(super) fn build_subset(positions: &[Vec2], indices: Vec<usize>) -> Option<Self> {
         let bounds = QuadBounds::from_points(positions)?;
-        let indices = (0..positions.len()).collect::<Vec<_>>();
         Some(Self::build_node(bounds, indices, positions, 0))
     }
 
@@ -164,17 +181,90 @@ impl QuadNode {
     }
 }
 
-pub(super) fn collect_quadtree_cells(node: &QuadNode, depth: usize, cells: &mut Vec<QuadtreeCell>) {
+/// Flattens the tree into `cells`, parents before their children, and
+/// returns the summed metric of `node`.
+pub(super) fn collect_quadtree_cells(
+    node: &QuadNode,
+    depth: usize,
+    nodes: &[RenderNode],
+    cells: &mut Vec<QuadtreeCell>,
+) -> u64 {
+    let slot = cells.len();
     cells.push(QuadtreeCell {
         center: node.bounds.center,
         half_extent: node.bounds.half_extent,
         depth,
         is_leaf: node.is_leaf(),
+        count: node.mass as usize,
+        metric_total: 0,
     });
 
+    let mut metric_total = node
+        .indices
+        .iter()
+        .map(|&index| nodes[index].metric_value)
+        .sum::<u64>();
     for child in &node.children {
         if let Some(child) = child.as_ref() {
-            collect_quadtree_cells(child, depth + 1, cells);
+            metric_total += collect_quadtree_cells(child, depth + 1, nodes, cells);
         }
     }
+    cells[slot].metric_total = metric_total;
+    metric_total
+}
+
+/// Small nodes drawn as one circle while the cell holding them is small on
+/// screen.
+pub(in crate::app) struct NodeCluster {
+    /// The members' centroid, in world space.
+    pub center: Vec2,
+    pub members: Vec<usize>,
+    /// The summed size metric of the members.
+    pub metric_total: u64,
+}
+
+impl QuadNode {
+    fn push_indices(&self, indices: &mut Vec<usize>) {
+        indices.extend_from_slice(&self.indices);
+        for child in self.children.iter().flatten() {
+            child.push_indices(indices);
+        }
+    }
+}
+
+/// Clusters the nodes `clusterable` allows in every cell at most `max_side`
+/// across; larger cells are split further. A cell left with a single such
+/// node draws it on its own.
+pub(super) fn collect_node_clusters(
+    node: &QuadNode,
+    max_side: f32,
+    clusterable: &[bool],
+    nodes: &[RenderNode],
+    clusters: &mut Vec<NodeCluster>,
+) {
+    if node.bounds.side_length() > max_side {
+        for child in node.children.iter().flatten() {
+            collect_node_clusters(child, max_side, clusterable, nodes, clusters);
+        }
+        return;
+    }
+
+    let mut members = Vec::new();
+    node.push_indices(&mut members);
+    members.retain(|&index| clusterable[index]);
+    if members.len() < 2 {
+        return;
+    }
+
+    let center = members
+        .iter()
+        .map(|&index| nodes[index].world_pos)
+        .fold(Vec2::ZERO, |sum, position| sum + position)
+        / members.len() as f32;
+    let metric_total = members.iter().map(|&index| nodes[index].metric_value).sum();
+    clusters.push(NodeCluster {
+        center,
+        members,
+        metric_total,
+    });
 }
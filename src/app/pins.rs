@@ -0,0 +1,52 @@
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, anyhow};
+use eframe::egui::{Vec2, vec2};
+
+pub(super) fn state_file(name: &str) -> Option<PathBuf> {
+    let state_dir = env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))?;
+    Some(state_dir.join("nix-analisa").join(name))
+}
+
+/// Pins are keyed by the hash-stripped store name, so a layout survives
+/// rebuilds that only change hashes.
+fn pins_path() -> Option<PathBuf> {
+    state_file("pins.json")
+}
+
+pub(super) fn load_pins() -> HashMap<String, Vec2> {
+    let Some(path) = pins_path() else {
+        return HashMap::new();
+    };
+
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|raw| serde_json::from_str::<HashMap<String, [f32; 2]>>(&raw).ok())
+        .map(|pins| {
+            pins.into_iter()
+                .map(|(name, [x, y])| (name, vec2(x, y)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub(super) fn save_pins(pins: &HashMap<String, Vec2>) -> Result<()> {
+    let path = pins_path().ok_or_else(|| anyhow!("no state directory to store pins in"))?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+
+    let sorted = pins
+        .iter()
+        .map(|(name, position)| (name.as_str(), [position.x, position.y]))
+        .collect::<BTreeMap<_, _>>();
+    let raw = serde_json::to_string_pretty(&sorted).context("failed to encode pins")?;
+    fs::write(&path, raw).with_context(|| format!("failed to write {}", path.display()))
+}
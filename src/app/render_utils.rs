@@ -1,4 +1,72 @@
-use eframe::egui::{Color32, Painter, Pos2, Rect, Stroke, Vec2};
+use eframe::egui::epaint::QuadraticBezierShape;
+use eframe::egui::{Align2, Color32, FontId, Painter, Pos2, Rect, Shape, Stroke, Vec2, vec2};
+
+use crate::nix::{NodeClass, NodeClasses, PathChange, RootMembership};
+use crate::util::{StoreName, parse_store_name};
+
+use super::EdgeStyle;
+
+/// How far curved edges bow out, relative to their length.
+const EDGE_CURVE_BOW: f32 = 0.18;
+
+/// Colors and stroke weights shared by everything drawn on the graph canvas.
+pub(super) struct RenderTheme {
+    pub(super) background: Color32,
+    pub(super) grid: Color32,
+    pub(super) edge: Color32,
+    pub(super) dimmed_edge: Color32,
+    pub(super) node_outline: Color32,
+    pub(super) label: Color32,
+    pub(super) chip_text: Color32,
+    pub(super) chip_fill: Color32,
+    pub(super) overlay_text: Color32,
+    pub(super) overlay_fill: Color32,
+    pub(super) stroke_scale: f32,
+    /// Whether debug and hover overlays are drawn on the canvas.
+    pub(super) show_overlays: bool,
+}
+
+impl RenderTheme {
+    const DARK: Self = Self {
+        background: Color32::from_rgb(19, 23, 29),
+        grid: Color32::from_rgba_unmultiplied_const(60, 70, 80, 70),
+        edge: Color32::from_rgb(72, 72, 72),
+        dimmed_edge: Color32::from_rgb(80, 90, 104),
+        node_outline: Color32::from_rgba_unmultiplied_const(15, 15, 15, 190),
+        label: Color32::from_gray(238),
+        chip_text: Color32::from_gray(168),
+        chip_fill: Color32::from_rgba_unmultiplied_const(62, 70, 82, 190),
+        overlay_text: Color32::from_gray(225),
+        overlay_fill: Color32::from_rgba_unmultiplied_const(12, 15, 20, 215),
+        stroke_scale: 1.0,
+        show_overlays: true,
+    };
+
+    /// White background, dark labels and heavier strokes for exports,
+    /// printing and projectors.
+    const PUBLICATION: Self = Self {
+        background: Color32::WHITE,
+        grid: Color32::TRANSPARENT,
+        edge: Color32::from_rgb(70, 74, 82),
+        dimmed_edge: Color32::from_rgb(160, 166, 176),
+        node_outline: Color32::from_rgb(30, 30, 30),
+        label: Color32::from_gray(20),
+        chip_text: Color32::from_gray(70),
+        chip_fill: Color32::from_gray(228),
+        overlay_text: Color32::from_gray(20),
+        overlay_fill: Color32::from_rgba_unmultiplied_const(245, 245, 245, 235),
+        stroke_scale: 1.8,
+        show_overlays: false,
+    };
+
+    pub(super) fn for_mode(publication: bool) -> &'static Self {
+        if publication {
+            &Self::PUBLICATION
+        } else {
+            &Self::DARK
+        }
+    }
+}
 
 pub(super) fn blend_color(base: Color32, overlay: Color32, amount: f32) -> Color32 {
     let amount = amount.clamp(0.0, 1.0);
@@ -18,8 +86,17 @@ pub(super) fn dim_color(color: Color32, factor: f32) -> Color32 {
     Color32::from_rgba_unmultiplied(color.r(), color.g(), color.b(), alpha)
 }
 
-pub(super) fn draw_background(painter: &Painter, rect: Rect, pan: Vec2, zoom: f32) {
-    painter.rect_filled(rect, 0.0, Color32::from_rgb(19, 23, 29));
+pub(super) fn draw_background(
+    painter: &Painter,
+    rect: Rect,
+    pan: Vec2,
+    zoom: f32,
+    theme: &RenderTheme,
+) {
+    painter.rect_filled(rect, 0.0, theme.background);
+    if theme.grid == Color32::TRANSPARENT {
+        return;
+    }
 
     let step = (56.0 * zoom.clamp(0.6, 1.8)).max(20.0);
     let origin = rect.center() + pan;
@@ -28,7 +105,7 @@ pub(super) fn draw_background(painter: &Painter, rect: Rect, pan: Vec2, zoom: f3
     while x < rect.right() {
         painter.line_segment(
             [Pos2::new(x, rect.top()), Pos2::new(x, rect.bottom())],
-            Stroke::new(1.0, Color32::from_rgba_unmultiplied(60, 70, 80, 70)),
+            Stroke::new(1.0, theme.grid),
         );
         x += step;
     }
@@ -37,7 +114,7 @@ pub(super) fn draw_background(painter: &Painter, rect: Rect, pan: Vec2, zoom: f3
     while y < rect.bottom() {
         painter.line_segment(
             [Pos2::new(rect.left(), y), Pos2::new(rect.right(), y)],
-            Stroke::new(1.0, Color32::from_rgba_unmultiplied(60, 70, 80, 70)),
+            Stroke::new(1.0, theme.grid),
         );
         y += step;
     }
@@ -104,6 +181,174 @@ fn segments_intersect(a1: Pos2, a2: Pos2, b1: Pos2, b2: Pos2) -> bool {
         && (c3 <= 0.0 && c4 >= 0.0 || c3 >= 0.0 && c4 <= 0.0)
 }
 
+/// Control point of a curved edge, bowed to the left of the source→target
+/// direction so edges in opposite directions do not overlap.
+fn edge_control_point(start: Pos2, end: Pos2) -> Pos2 {
+    let direction = end - start;
+    start + direction * 0.5 + vec2(-direction.y, direction.x) * EDGE_CURVE_BOW
+}
+
+/// Elbow of an orthogonal edge: leave the source vertically, cross over
+/// horizontally half-way, then enter the target vertically.
+fn edge_elbows(start: Pos2, end: Pos2) -> [Pos2; 2] {
+    let mid_y = (start.y + end.y) * 0.5;
+    [Pos2::new(start.x, mid_y), Pos2::new(end.x, mid_y)]
+}
+
+/// Point at fraction `t` (0..=1) along an edge as drawn in `style`.
+pub(super) fn edge_point(start: Pos2, end: Pos2, style: EdgeStyle, t: f32) -> Pos2 {
+    match style {
+        EdgeStyle::Straight => start + (end - start) * t,
+        EdgeStyle::Curved => {
+            let control = edge_control_point(start, end);
+            let inverse = 1.0 - t;
+            (start.to_vec2() * (inverse * inverse)
+                + control.to_vec2() * (2.0 * inverse * t)
+                + end.to_vec2() * (t * t))
+                .to_pos2()
+        }
+        EdgeStyle::Orthogonal => {
+            let [first, second] = edge_elbows(start, end);
+            let legs = [(start, first), (first, second), (second, end)];
+            let total = legs.iter().map(|(a, b)| a.distance(*b)).sum::<f32>();
+            let mut remaining = total * t;
+            for (from, to) in legs {
+                let length = from.distance(to);
+                if remaining <= length && length > f32::EPSILON {
+                    return from + (to - from) * (remaining / length);
+                }
+                remaining -= length;
+            }
+            end
+        }
+    }
+}
+
+pub(super) fn draw_edge(
+    painter: &Painter,
+    start: Pos2,
+    end: Pos2,
+    style: EdgeStyle,
+    stroke: Stroke,
+) {
+    match style {
+        EdgeStyle::Straight => {
+            painter.line_segment([start, end], stroke);
+        }
+        EdgeStyle::Curved => {
+            painter.add(QuadraticBezierShape::from_points_stroke(
+                [start, edge_control_point(start, end), end],
+                false,
+                Color32::TRANSPARENT,
+                stroke,
+            ));
+        }
+        EdgeStyle::Orthogonal => {
+            let [first, second] = edge_elbows(start, end);
+            painter.add(Shape::line(vec![start, first, second, end], stroke));
+        }
+    }
+}
+
+/// Draws an arrowhead of `size` where an edge meets the rim of its target,
+/// a node of `target_radius` at `end`, pointing along the edge as drawn.
+pub(super) fn draw_edge_arrow(
+    painter: &Painter,
+    start: Pos2,
+    end: Pos2,
+    style: EdgeStyle,
+    target_radius: f32,
+    size: f32,
+    color: Color32,
+) {
+    let length = start.distance(end);
+    if length <= target_radius + size * 2.0 {
+        return;
+    }
+
+    let tip_t = 1.0 - target_radius / length;
+    let tip = edge_point(start, end, style, tip_t);
+    let back = edge_point(start, end, style, tip_t - size / length);
+    let direction = (tip - back).normalized();
+    if !direction.is_finite() {
+        return;
+    }
+    let base = tip - direction * size;
+    let normal = direction.rot90() * (size * 0.45);
+    painter.add(Shape::convex_polygon(
+        vec![tip, base + normal, base - normal],
+        color,
+        Stroke::NONE,
+    ));
+}
+
+/// Where along an edge drawn in `style` the point nearest to `pointer`
+/// lies, as a fraction of the edge, and how far from it `pointer` is.
+pub(super) fn edge_hit(start: Pos2, end: Pos2, style: EdgeStyle, pointer: Pos2) -> (f32, f32) {
+    const SEGMENTS: usize = 12;
+
+    let mut nearest = (0.0, f32::INFINITY);
+    let mut from = start;
+    for step in 1..=SEGMENTS {
+        let t = step as f32 / SEGMENTS as f32;
+        let to = edge_point(start, end, style, t);
+        let segment = to - from;
+        let along =
+            ((pointer - from).dot(segment) / segment.length_sq().max(f32::EPSILON)).clamp(0.0, 1.0);
+        let distance = (from + segment * along).distance(pointer);
+        if distance < nearest.1 {
+            nearest = ((step as f32 - 1.0 + along) / SEGMENTS as f32, distance);
+        }
+        from = to;
+    }
+    nearest
+}
+
+/// Calls `segment` with each straight piece of an edge as drawn in `style`.
+pub(super) fn edge_segments(
+    start: Pos2,
+    end: Pos2,
+    style: EdgeStyle,
+    mut segment: impl FnMut(Pos2, Pos2),
+) {
+    const CURVE_SEGMENTS: usize = 10;
+
+    match style {
+        EdgeStyle::Straight => segment(start, end),
+        EdgeStyle::Curved => {
+            let mut from = start;
+            for step in 1..=CURVE_SEGMENTS {
+                let to = edge_point(start, end, style, step as f32 / CURVE_SEGMENTS as f32);
+                segment(from, to);
+                from = to;
+            }
+        }
+        EdgeStyle::Orthogonal => {
+            let [first, second] = edge_elbows(start, end);
+            segment(start, first);
+            segment(first, second);
+            segment(second, end);
+        }
+    }
+}
+
+/// Draws the first `fraction` of an edge, following its style.
+pub(super) fn draw_partial_edge(
+    painter: &Painter,
+    start: Pos2,
+    end: Pos2,
+    style: EdgeStyle,
+    fraction: f32,
+    stroke: Stroke,
+) {
+    const SEGMENTS: usize = 16;
+
+    let points = (0..=SEGMENTS)
+        .map(|step| edge_point(start, end, style, fraction * step as f32 / SEGMENTS as f32))
+        .collect::<Vec<_>>();
+    painter.add(Shape::line(points, stroke));
+}
+
 pub(super) fn world_to_screen(rect: Rect, pan: Vec2, zoom: f32, world: Vec2) -> Pos2 {
     rect.center() + pan + world * zoom
 }
@@ -133,6 +378,14 @@ pub(super) fn node_radius(metric: u64, min: u64, max: u64) -> f32 {
     6.0 + (normalize_log(metric, min, max) * 26.0)
 }
 
+/// Widens edges whose source mentions the target in many files, up to 2.5x.
+pub(super) fn edge_weight_scale(weight: u32, max_weight: u32) -> f32 {
+    if max_weight <= 1 {
+        return 1.0;
+    }
+    1.0 + normalize_log(u64::from(weight), 1, u64::from(max_weight)) * 1.5
+}
+
 pub(super) fn metric_color(metric: u64, min: u64, max: u64) -> Color32 {
     let t = normalize_log(metric, min, max);
     let r = (55.0 + (190.0 * t)) as u8;
@@ -140,3 +393,158 @@ pub(super) fn metric_color(metric: u64, min: u64, max: u64) -> Color32 {
     let b = (215.0 - (155.0 * t)) as u8;
     Color32::from_rgb(r, g, b)
 }
+
+/// From slate for a negligible share of a subtree to orange for all of it.
+pub(super) fn share_color(share: f32) -> Color32 {
+    let t = share.clamp(0.0, 1.0);
+    let r = (70.0 + (185.0 * t)) as u8;
+    let g = (90.0 + (30.0 * t)) as u8;
+    let b = (120.0 - (80.0 * t)) as u8;
+    Color32::from_rgb(r, g, b)
+}
+
+pub(super) fn node_class_color(class: NodeClass) -> Color32 {
+    match class {
+        NodeClass::Derivation => Color32::from_rgb(171, 130, 255),
+        NodeClass::FixedOutput => Color32::from_rgb(120, 200, 140),
+        NodeClass::DevOutput => Color32::from_rgb(96, 170, 240),
+        NodeClass::DocOutput => Color32::from_rgb(230, 220, 120),
+        NodeClass::KernelFirmware => Color32::from_rgb(240, 120, 90),
+        NodeClass::Font => Color32::from_rgb(235, 150, 210),
+        NodeClass::Elf => Color32::from_rgb(110, 220, 220),
+        NodeClass::Unsigned => Color32::from_rgb(200, 200, 200),
+    }
+}
+
+/// Green for new paths, red for paths that grew, blue for ones that shrank
+/// or went away.
+pub(super) fn diff_color(change: &PathChange, closure_delta: i64) -> Color32 {
+    match change {
+        PathChange::Added => Color32::from_rgb(110, 210, 120),
+        _ if closure_delta > 0 => Color32::from_rgb(236, 110, 96),
+        _ => Color32::from_rgb(96, 160, 236),
+    }
+}
+
+/// Distinct hues for the roots of a merged graph; shared paths stay grey.
+pub(super) fn root_color(membership: RootMembership) -> Color32 {
+    const ROOT_COLORS: [Color32; 6] = [
+        Color32::from_rgb(96, 160, 236),
+        Color32::from_rgb(236, 110, 96),
+        Color32::from_rgb(110, 210, 120),
+        Color32::from_rgb(206, 120, 230),
+        Color32::from_rgb(240, 190, 80),
+        Color32::from_rgb(80, 210, 210),
+    ];
+
+    match membership {
+        RootMembership::Only(index) => ROOT_COLORS[index % ROOT_COLORS.len()],
+        RootMembership::Partial => Color32::from_rgb(176, 150, 120),
+        RootMembership::Shared => Color32::from_gray(140),
+    }
+}
+
+pub(super) fn draw_class_badges(
+    painter: &Painter,
+    position: Pos2,
+    radius: f32,
+    classes: NodeClasses,
+    theme: &RenderTheme,
+) {
+    let badge_radius = (radius * 0.22).clamp(2.2, 4.5);
+    for (slot, class) in classes.iter().enumerate() {
+        let angle = (-45.0_f32 + slot as f32 * 24.0).to_radians();
+        let center = position + vec2(angle.cos(), angle.sin()) * (radius + badge_radius * 0.6);
+        painter.circle_filled(center, badge_radius, node_class_color(class));
+        painter.circle_stroke(
+            center,
+            badge_radius,
+            Stroke::new(0.8 * theme.stroke_scale, theme.node_outline),
+        );
+    }
+}
+
+/// A pushpin standing on `tip`: a needle leaning right up to a round head.
+pub(super) fn draw_pin_glyph(painter: &Painter, tip: Pos2, size: f32, theme: &RenderTheme) {
+    let head = tip + vec2(size * 0.45, -size);
+    painter.line_segment(
+        [tip, head],
+        Stroke::new(1.2 * theme.stroke_scale, theme.label),
+    );
+    painter.circle_filled(head, size * 0.38, Color32::from_rgb(232, 86, 78));
+    painter.circle_stroke(
+        head,
+        size * 0.38,
+        Stroke::new(0.8 * theme.stroke_scale, theme.node_outline),
+    );
+}
+
+/// The short name a store directory is badged with: the root of a chroot
+/// store, or the directory itself.
+pub(super) fn store_badge(store_dir: &str) -> &str {
+    store_dir
+        .strip_suffix("/nix/store")
+        .filter(|root| !root.is_empty())
+        .unwrap_or(store_dir)
+}
+
+pub(super) fn draw_node_label(
+    painter: &Painter,
+    anchor: Pos2,
+    name: &str,
+    store: Option<&str>,
+    pname_only: bool,
+    theme: &RenderTheme,
+) {
+    let parsed = parse_store_name(name);
+    let text_rect = painter.text(
+        anchor,
+        Align2::LEFT_CENTER,
+        parsed.pname,
+        FontId::proportional(12.0),
+        theme.label,
+    );
+    let mut right = text_rect.right();
+    if !pname_only {
+        right = draw_label_chip(painter, anchor, right, &version_chip_text(parsed), theme);
+    }
+    if let Some(store) = store {
+        draw_label_chip(painter, anchor, right, store, theme);
+    }
+}
+
+fn version_chip_text(parsed: StoreName<'_>) -> String {
+    [
+        parsed.version,
+        parsed.output,
+        parsed.is_derivation.then_some("drv"),
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>()
+    .join(" · ")
+}
+
+/// A muted chip right of `left`; returns where the next one goes.
+fn draw_label_chip(
+    painter: &Painter,
+    anchor: Pos2,
+    left: f32,
+    text: &str,
+    theme: &RenderTheme,
+) -> f32 {
+    if text.is_empty() {
+        return left;
+    }
+
+    let text_color = theme.chip_text;
+    let galley = painter.layout_no_wrap(text.to_owned(), FontId::proportional(10.5), text_color);
+    let chip_size = galley.size() + vec2(8.0, 2.0);
+    let chip_rect = Rect::from_min_size(
+        Pos2::new(left + 5.0, anchor.y - chip_size.y * 0.5),
+        chip_size,
+    );
+    painter.rect_filled(chip_rect, 4.0, theme.chip_fill);
+    painter.galley(chip_rect.min + vec2(4.0, 1.0), galley, text_color);
+    chip_rect.right()
+}
@@ -0,0 +1,38 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, anyhow};
+
+use super::pins::state_file;
+
+/// Named groups of paths, e.g. "GUI stack". Members are hash-stripped store
+/// names, like pins, so a set keeps matching across rebuilds.
+pub(super) type SelectionSets = BTreeMap<String, BTreeSet<String>>;
+
+fn selection_sets_path() -> Option<PathBuf> {
+    state_file("selection-sets.json")
+}
+
+pub(super) fn load_selection_sets() -> SelectionSets {
+    let Some(path) = selection_sets_path() else {
+        return SelectionSets::new();
+    };
+
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+pub(super) fn save_selection_sets(sets: &SelectionSets) -> Result<()> {
+    let path = selection_sets_path()
+        .ok_or_else(|| anyhow!("no state directory to store selection sets in"))?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+
+    let raw = serde_json::to_string_pretty(sets).context("failed to encode selection sets")?;
+    fs::write(&path, raw).with_context(|| format!("failed to write {}", path.display()))
+}
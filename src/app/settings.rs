@@ -0,0 +1,228 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::nix::SizeMetric;
+
+use super::{EdgeStyle, FrameRateCap, GraphLayout, QuadtreeShading, StoreHashes, ViewModel};
+
+fn config_file() -> Option<PathBuf> {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_dir.join("nix-analisa").join("config.toml"))
+}
+
+/// Tunables restored on launch and carried over reloads, stored in
+/// `~/.config/nix-analisa/config.toml`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub(super) struct Settings {
+    /// The root explored last, opened when no `--system-path` is given.
+    pub(super) last_root: Option<String>,
+    /// Kept as a table so keys missing from an older file fall back to the
+    /// defaults of the model they are applied to.
+    #[serde(default)]
+    view: toml::Table,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ViewSettings {
+    metric: SizeMetric,
+    min_size_mb: f32,
+    max_nodes: usize,
+    live_physics: bool,
+    frame_rate_cap: FrameRateCap,
+    idle_throttle: bool,
+    pause_when_unfocused: bool,
+    watch_profile: bool,
+    auto_reload_profile: bool,
+    physics_intensity: f32,
+    physics_repulsion: f32,
+    physics_spring: f32,
+    physics_collision: f32,
+    physics_velocity_damping: f32,
+    physics_target_spread: f32,
+    physics_spread_force: f32,
+    parallel_physics: bool,
+    show_controls_panel: bool,
+    show_details_panel: bool,
+    controls_panel_width: f32,
+    details_panel_width: f32,
+    show_quadtree_overlay: bool,
+    quadtree_shading: QuadtreeShading,
+    show_physics_heat: bool,
+    store_hashes: StoreHashes,
+    remember_layout: bool,
+    show_legend_overlay: bool,
+    publication_mode: bool,
+    label_pname_only: bool,
+    show_node_glyphs: bool,
+    cluster_at_low_zoom: bool,
+    color_by_dominator_share: bool,
+    edge_style: EdgeStyle,
+    show_edge_arrows: bool,
+    gpu_rendering: bool,
+    graph_layout: GraphLayout,
+    freeze_layout: bool,
+    bridge_hidden: bool,
+    impact_pulse_enabled: bool,
+    show_fps_bar: bool,
+    fps_show_current: bool,
+    fps_show_average: bool,
+    fps_show_low: bool,
+    fps_show_high: bool,
+    fps_show_frame_time: bool,
+}
+
+impl ViewSettings {
+    fn capture(model: &ViewModel) -> Self {
+        Self {
+            metric: model.metric,
+            min_size_mb: model.min_size_mb,
+            max_nodes: model.max_nodes,
+            live_physics: model.live_physics,
+            frame_rate_cap: model.frame_rate_cap,
+            idle_throttle: model.idle_throttle,
+            pause_when_unfocused: model.pause_when_unfocused,
+            watch_profile: model.watch_profile,
+            auto_reload_profile: model.auto_reload_profile,
+            physics_intensity: model.physics_intensity,
+            physics_repulsion: model.physics_repulsion,
+            physics_spring: model.physics_spring,
+            physics_collision: model.physics_collision,
+            physics_velocity_damping: model.physics_velocity_damping,
+            physics_target_spread: model.physics_target_spread,
+            physics_spread_force: model.physics_spread_force,
+            parallel_physics: model.parallel_physics,
+            show_controls_panel: model.show_controls_panel,
+            show_details_panel: model.show_details_panel,
+            controls_panel_width: model.controls_panel_width,
+            details_panel_width: model.details_panel_width,
+            show_quadtree_overlay: model.show_quadtree_overlay,
+            quadtree_shading: model.quadtree_shading,
+            show_physics_heat: model.show_physics_heat,
+            store_hashes: model.store_hashes.clone(),
+            remember_layout: model.remember_layout,
+            show_legend_overlay: model.show_legend_overlay,
+            publication_mode: model.publication_mode,
+            label_pname_only: model.label_pname_only,
+            show_node_glyphs: model.show_node_glyphs,
+            cluster_at_low_zoom: model.cluster_at_low_zoom,
+            color_by_dominator_share: model.color_by_dominator_share,
+            edge_style: model.edge_style,
+            show_edge_arrows: model.show_edge_arrows,
+            gpu_rendering: model.gpu_rendering,
+            graph_layout: model.graph_layout,
+            freeze_layout: model.freeze_layout,
+            bridge_hidden: model.bridge_hidden,
+            impact_pulse_enabled: model.impact_pulse_enabled,
+            show_fps_bar: model.show_fps_bar,
+            fps_show_current: model.fps_show_current,
+            fps_show_average: model.fps_show_average,
+            fps_show_low: model.fps_show_low,
+            fps_show_high: model.fps_show_high,
+            fps_show_frame_time: model.fps_show_frame_time,
+        }
+    }
+
+    fn apply(self, model: &mut ViewModel) {
+        model.metric = self.metric;
+        model.min_size_mb = self.min_size_mb;
+        model.max_nodes = self.max_nodes;
+        model.live_physics = self.live_physics;
+        model.frame_rate_cap = self.frame_rate_cap;
+        model.idle_throttle = self.idle_throttle;
+        model.pause_when_unfocused = self.pause_when_unfocused;
+        model.watch_profile = self.watch_profile;
+        model.auto_reload_profile = self.auto_reload_profile;
+        model.physics_intensity = self.physics_intensity;
+        model.physics_repulsion = self.physics_repulsion;
+        model.physics_spring = self.physics_spring;
+        model.physics_collision = self.physics_collision;
+        model.physics_velocity_damping = self.physics_velocity_damping;
+        model.physics_target_spread = self.physics_target_spread;
+        model.physics_spread_force = self.physics_spread_force;
+        model.parallel_physics = self.parallel_physics;
+        model.show_controls_panel = self.show_controls_panel;
+        model.show_details_panel = self.show_details_panel;
+        model.controls_panel_width = self.controls_panel_width;
+        model.details_panel_width = self.details_panel_width;
+        model.show_quadtree_overlay = self.show_quadtree_overlay;
+        model.quadtree_shading = self.quadtree_shading;
+        model.show_physics_heat = self.show_physics_heat;
+        model.store_hashes = self.store_hashes;
+        model.remember_layout = self.remember_layout;
+        model.show_legend_overlay = self.show_legend_overlay;
+        model.publication_mode = self.publication_mode;
+        model.label_pname_only = self.label_pname_only;
+        model.show_node_glyphs = self.show_node_glyphs;
+        model.cluster_at_low_zoom = self.cluster_at_low_zoom;
+        model.color_by_dominator_share = self.color_by_dominator_share;
+        model.edge_style = self.edge_style;
+        model.show_edge_arrows = self.show_edge_arrows;
+        model.gpu_rendering = self.gpu_rendering;
+        model.graph_layout = self.graph_layout;
+        model.freeze_layout = self.freeze_layout;
+        model.bridge_hidden = self.bridge_hidden;
+        model.impact_pulse_enabled = self.impact_pulse_enabled;
+        model.show_fps_bar = self.show_fps_bar;
+        model.fps_show_current = self.fps_show_current;
+        model.fps_show_average = self.fps_show_average;
+        model.fps_show_low = self.fps_show_low;
+        model.fps_show_high = self.fps_show_high;
+        model.fps_show_frame_time = self.fps_show_frame_time;
+        model.graph_dirty = true;
+    }
+}
+
+/// The root explored last in the GUI, if any.
+pub fn saved_root() -> Option<String> {
+    Settings::load().last_root
+}
+
+impl Settings {
+    pub(super) fn load() -> Self {
+        let Some(path) = config_file() else {
+            return Self::default();
+        };
+
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|raw| toml::from_str(&raw).ok())
+            .unwrap_or_default()
+    }
+
+    pub(super) fn save(&self) -> Result<()> {
+        let path =
+            config_file().ok_or_else(|| anyhow!("no config directory to store settings in"))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+
+        let raw = toml::to_string(self).context("failed to encode settings")?;
+        fs::write(&path, raw).with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    /// Applies the saved tunables over `model`'s defaults; a file that no
+    /// longer fits is ignored as a whole.
+    pub(super) fn apply(&self, model: &mut ViewModel) {
+        let Ok(mut view) = toml::Table::try_from(ViewSettings::capture(model)) else {
+            return;
+        };
+        view.extend(self.view.clone());
+        if let Ok(settings) = view.try_into::<ViewSettings>() {
+            settings.apply(model);
+        }
+    }
+
+    pub(super) fn capture(&mut self, model: &ViewModel) {
+        if let Ok(view) = toml::Table::try_from(ViewSettings::capture(model)) {
+            self.view = view;
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use eframe::egui::{self, Align2, Color32, Context, RichText, vec2};
+
+use super::ViewModel;
+
+const TOAST_LIMIT: usize = 5;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(super) enum ToastLevel {
+    Info,
+    Warning,
+    Error,
+}
+
+impl ToastLevel {
+    fn lifetime(self) -> f64 {
+        match self {
+            Self::Info => 4.0,
+            Self::Warning => 7.0,
+            Self::Error => 10.0,
+        }
+    }
+
+    fn color(self) -> Color32 {
+        match self {
+            Self::Info => Color32::from_rgb(120, 200, 140),
+            Self::Warning => Color32::from_rgb(236, 190, 90),
+            Self::Error => Color32::from_rgb(236, 98, 92),
+        }
+    }
+}
+
+pub(super) struct Toast {
+    level: ToastLevel,
+    text: String,
+    shown_at: Option<f64>,
+}
+
+impl Toast {
+    pub(super) fn new(level: ToastLevel, text: impl Into<String>) -> Self {
+        Self {
+            level,
+            text: text.into(),
+            shown_at: None,
+        }
+    }
+}
+
+/// Short-lived notifications stacked in the bottom-right corner, so results
+/// of background work don't silently vanish.
+#[derive(Default)]
+pub(super) struct Toasts {
+    items: VecDeque<Toast>,
+}
+
+impl Toasts {
+    pub(super) fn push(&mut self, toast: Toast) {
+        if self.items.len() == TOAST_LIMIT {
+            self.items.pop_front();
+        }
+        self.items.push_back(toast);
+    }
+
+    pub(super) fn extend(&mut self, toasts: impl IntoIterator<Item = Toast>) {
+        for toast in toasts {
+            self.push(toast);
+        }
+    }
+
+    pub(super) fn show(&mut self, ctx: &Context) {
+        let now = ctx.input(|input| input.time);
+        self.items.retain_mut(|toast| {
+            let shown_at = *toast.shown_at.get_or_insert(now);
+            now - shown_at < toast.level.lifetime()
+        });
+        if self.items.is_empty() {
+            return;
+        }
+
+        let mut dismissed = None;
+        egui::Area::new(egui::Id::new("toasts"))
+            .anchor(Align2::RIGHT_BOTTOM, vec2(-12.0, -40.0))
+            .interactable(true)
+            .show(ctx, |ui| {
+                for (index, toast) in self.items.iter().enumerate() {
+                    let response = egui::Frame::popup(ui.style())
+                        .stroke(egui::Stroke::new(1.0, toast.level.color()))
+                        .show(ui, |ui| {
+                            ui.set_max_width(360.0);
+                            ui.label(RichText::new(&toast.text).color(toast.level.color()));
+                        })
+                        .response
+                        .interact(egui::Sense::click())
+                        .on_hover_text("Click to dismiss.");
+                    if response.clicked() {
+                        dismissed = Some(index);
+                    }
+                }
+            });
+
+        if let Some(index) = dismissed {
+            self.items.remove(index);
+        }
+        ctx.request_repaint_after(Duration::from_millis(250));
+    }
+}
+
+impl ViewModel {
+    pub(in crate::app) fn notify(&mut self, level: ToastLevel, text: impl Into<String>) {
+        self.pending_toasts.push(Toast::new(level, text));
+    }
+}
@@ -0,0 +1,95 @@
+use eframe::egui::{self, RichText, Ui};
+
+use crate::util::{parse_store_name, short_name};
+
+use super::super::ViewModel;
+use super::super::annotations::{AnnotationRole, AnnotationShape, save_annotations};
+
+impl ViewModel {
+    /// The key annotations of this graph are saved under.
+    pub(in crate::app) fn annotation_key(&self) -> String {
+        parse_store_name(short_name(&self.graph.root_id))
+            .pname
+            .to_owned()
+    }
+
+    pub(in crate::app) fn persist_annotations(&mut self) {
+        self.annotations_status = save_annotations(&self.annotation_key(), &self.annotations)
+            .err()
+            .map(|error| format!("Failed to save annotations: {error:#}"));
+    }
+
+    pub(in crate::app) fn draw_annotation_controls(&mut self, ui: &mut Ui) {
+        ui.weak("Pick a tool, then drag on the graph to draw.");
+        let previous_tool = self.annotation_tool;
+        ui.horizontal_wrapped(|ui| {
+            ui.selectable_value(&mut self.annotation_tool, None, "Off");
+            for shape in AnnotationShape::ALL {
+                ui.selectable_value(&mut self.annotation_tool, Some(shape), shape.label());
+            }
+        });
+        if self.annotation_tool != previous_tool && self.annotation_tool.is_some() {
+            self.measure_mode = false;
+        }
+
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt("annotation_role")
+                .selected_text(self.annotation_role.label())
+                .show_ui(ui, |ui| {
+                    for role in AnnotationRole::ALL {
+                        ui.selectable_value(&mut self.annotation_role, role, role.label());
+                    }
+                });
+            ui.add(
+                egui::TextEdit::singleline(&mut self.annotation_text)
+                    .hint_text("Text of the next annotation")
+                    .desired_width(150.0),
+            );
+        });
+        ui.checkbox(&mut self.show_annotations, "Show annotations");
+        if let Some(status) = &self.annotations_status {
+            ui.weak(status.as_str());
+        }
+
+        if self.annotations.is_empty() {
+            return;
+        }
+        ui.separator();
+
+        let mut changed = false;
+        let mut deleted = None;
+        for (index, annotation) in self.annotations.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(RichText::new(annotation.shape.label()).color(annotation.role.color()));
+                egui::ComboBox::from_id_salt(("annotation_role", index))
+                    .width(80.0)
+                    .selected_text(annotation.role.label())
+                    .show_ui(ui, |ui| {
+                        for role in AnnotationRole::ALL {
+                            changed |= ui
+                                .selectable_value(&mut annotation.role, role, role.label())
+                                .changed();
+                        }
+                    });
+                changed |= ui
+                    .add(egui::TextEdit::singleline(&mut annotation.text).desired_width(110.0))
+                    .lost_focus();
+                if ui.small_button("Delete").clicked() {
+                    deleted = Some(index);
+                }
+            });
+        }
+        if ui.button("Clear all").clicked() {
+            self.annotations.clear();
+            changed = true;
+        }
+
+        if let Some(index) = deleted {
+            self.annotations.remove(index);
+            changed = true;
+        }
+        if changed {
+            self.persist_annotations();
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use eframe::egui::{RichText, Ui};
+
+use crate::nix::{attribute_search_url, locate_attribute_paths};
+
+use super::super::jobs::Job;
+use super::super::{AttributeLookup, ViewModel};
+
+impl ViewModel {
+    pub(in crate::app) fn poll_attribute_lookups(&mut self) -> bool {
+        let mut running = false;
+
+        for state in self.attribute_lookups.values_mut() {
+            let AttributeLookup::Running(job) = state else {
+                continue;
+            };
+
+            match job.poll() {
+                Some(result) => *state = AttributeLookup::Done(result),
+                None => running = true,
+            }
+        }
+
+        running
+    }
+
+    fn start_attribute_lookup(&mut self, id: &str) {
+        let database = self.nix_settings.nix_index_db.clone();
+        let store_id = id.to_owned();
+        let job = Job::spawn(&self.nix_settings, move |_| {
+            locate_attribute_paths(database.as_deref(), &store_id)
+        });
+
+        self.attribute_lookups
+            .insert(id.to_owned(), AttributeLookup::Running(Box::new(job)));
+    }
+
+    pub(in crate::app) fn draw_attribute_section(&mut self, ui: &mut Ui, id: &str) {
+        ui.separator();
+        ui.label(RichText::new("Nixpkgs attribute").strong());
+
+        let mut lookup_requested = false;
+        match self.attribute_lookups.get(id) {
+            None => {
+                ui.weak("Find the attribute paths that build this path in the nix-index database.");
+                lookup_requested = ui.button("Look up attribute").clicked();
+            }
+            Some(AttributeLookup::Running(_)) => {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Running nix-locate...");
+                });
+            }
+            Some(AttributeLookup::Done(Err(error))) => {
+                ui.label(format!("Lookup failed: {error}"));
+                lookup_requested = ui.button("Retry").clicked();
+            }
+            Some(AttributeLookup::Done(Ok(paths))) if paths.is_empty() => {
+                ui.label("Not in the nix-index database; it may be local or out of date.");
+            }
+            Some(AttributeLookup::Done(Ok(paths))) => {
+                for path in paths {
+                    ui.horizontal(|ui| {
+                        ui.monospace(path.as_str());
+                        ui.hyperlink_to("search.nixos.org", attribute_search_url(path));
+                    });
+                }
+            }
+        }
+
+        if lookup_requested {
+            self.start_attribute_lookup(id);
+        }
+    }
+}
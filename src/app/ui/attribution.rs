@@ -0,0 +1,176 @@
+use eframe::egui::ecolor::Hsva;
+use eframe::egui::{self, Color32, Rect, Sense, Ui, pos2, vec2};
+
+use crate::format::{format_bytes, format_count};
+
+use super::super::{LabelPanel, ViewModel};
+
+/// References drawn in their own color in the closure bar; the rest are
+/// summed into one segment.
+const BAR_REFERENCES: usize = 8;
+const SHARED_COLOR: Color32 = Color32::from_gray(110);
+const OTHER_COLOR: Color32 = Color32::from_gray(160);
+
+fn reference_color(rank: usize) -> Color32 {
+    let hue = (0.58 + rank as f32 * 0.618_034).fract();
+    Hsva::new(hue, 0.55, 0.85, 1.0).into()
+}
+
+/// Draws `segments` side by side across `rect`, each as wide as its share of
+/// `total`, with its label on hover.
+fn draw_stacked_bar(ui: &mut Ui, rect: Rect, total: u64, segments: &[(Color32, u64, String)]) {
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 3.0, ui.visuals().extreme_bg_color);
+    if total == 0 {
+        return;
+    }
+    let mut x = rect.left();
+    for (index, (color, size, label)) in segments.iter().enumerate() {
+        let width = rect.width() * (*size as f32 / total as f32);
+        if width <= 0.0 {
+            continue;
+        }
+        let segment = Rect::from_min_max(pos2(x, rect.top()), pos2(x + width, rect.bottom()));
+        painter.rect_filled(segment, 0.0, *color);
+        ui.interact(
+            segment,
+            ui.id().with(("attribution_segment", index)),
+            Sense::hover(),
+        )
+        .on_hover_text(format!("{label}: {}", format_bytes(*size)));
+        x += width;
+    }
+}
+
+impl ViewModel {
+    pub(in crate::app) fn draw_attribution_window(&mut self, ui: &mut Ui) {
+        if self.attribution.is_none() {
+            self.attribution = Some(self.graph.top_level_attribution());
+        }
+        let shares = self.attribution.as_deref().unwrap_or_default();
+        let Some(root) = self.graph.nodes.get(&self.graph.root_id) else {
+            return;
+        };
+        if shares.is_empty() {
+            ui.label("The root references no other paths.");
+            return;
+        }
+
+        let exclusive_total = shares.iter().map(|share| share.exclusive_size).sum::<u64>();
+        let shared_total = root
+            .closure_size
+            .saturating_sub(root.nar_size)
+            .saturating_sub(exclusive_total);
+        ui.label(format!(
+            "{} direct references of {}: {} exclusive, {} shared between them, {} in the root itself",
+            format_count(shares.len() as u64),
+            self.path_label(LabelPanel::Analysis, &root.id),
+            format_bytes(exclusive_total),
+            format_bytes(shared_total),
+            format_bytes(root.nar_size)
+        ));
+
+        let mut segments = shares
+            .iter()
+            .take(BAR_REFERENCES)
+            .enumerate()
+            .map(|(rank, share)| {
+                (
+                    reference_color(rank),
+                    share.exclusive_size,
+                    self.path_label(LabelPanel::Analysis, &share.id).to_owned(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let others = shares
+            .iter()
+            .skip(BAR_REFERENCES)
+            .map(|share| share.exclusive_size)
+            .sum::<u64>();
+        if others > 0 {
+            segments.push((
+                OTHER_COLOR,
+                others,
+                format!("{} other references", shares.len() - BAR_REFERENCES),
+            ));
+        }
+        segments.push((
+            SHARED_COLOR,
+            shared_total,
+            "Shared between references".to_owned(),
+        ));
+        segments.push((
+            ui.visuals().weak_text_color(),
+            root.nar_size,
+            "Root".to_owned(),
+        ));
+        let (bar, _) = ui.allocate_exact_size(vec2(ui.available_width(), 20.0), Sense::hover());
+        draw_stacked_bar(ui, bar, root.closure_size, &segments);
+        ui.weak(
+            "Exclusive bytes leave the closure with their reference; shared bytes stay while \
+             any other reference keeps them.",
+        );
+        ui.separator();
+
+        let largest_closure = shares
+            .iter()
+            .map(|share| share.closure_size)
+            .max()
+            .unwrap_or(0);
+        let mut clicked = None;
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, true])
+            .show(ui, |ui| {
+                egui::Grid::new("attribution_table")
+                    .num_columns(5)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.strong("Reference");
+                        ui.strong("Exclusive");
+                        ui.strong("Shared");
+                        ui.strong("Paths");
+                        ui.strong("Closure");
+                        ui.end_row();
+
+                        for (rank, share) in shares.iter().enumerate() {
+                            let label = self.path_label(LabelPanel::Analysis, &share.id);
+                            if ui
+                                .selectable_label(
+                                    self.selected.as_deref() == Some(&share.id),
+                                    label,
+                                )
+                                .on_hover_text(share.id.as_str())
+                                .clicked()
+                            {
+                                clicked = Some(share.id.clone());
+                            }
+                            ui.label(format_bytes(share.exclusive_size));
+                            ui.label(format_bytes(share.shared_size()));
+                            ui.label(format_count(share.exclusive_paths as u64))
+                                .on_hover_text("Paths only this reference keeps in the closure.");
+                            let (bar, _) =
+                                ui.allocate_exact_size(vec2(140.0, 12.0), Sense::hover());
+                            let color = if rank < BAR_REFERENCES {
+                                reference_color(rank)
+                            } else {
+                                OTHER_COLOR
+                            };
+                            draw_stacked_bar(
+                                ui,
+                                bar,
+                                largest_closure,
+                                &[
+                                    (color, share.exclusive_size, "Exclusive".to_owned()),
+                                    (SHARED_COLOR, share.shared_size(), "Shared".to_owned()),
+                                ],
+                            );
+                            ui.end_row();
+                        }
+                    });
+            });
+
+        if let Some(id) = clicked {
+            self.reveal_node(&id);
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use eframe::egui::{self, Key, RichText, Ui, vec2};
+
+use crate::format::format_count;
+use crate::util::short_name;
+
+use super::super::ViewModel;
+use super::super::bookmarks::{
+    BookmarkFilters, NodeBookmark, ViewBookmark, save_bookmarks, save_node_bookmarks,
+};
+
+/// Number keys recalling the bookmark in the matching slot.
+const BOOKMARK_KEYS: [Key; 9] = [
+    Key::Num1,
+    Key::Num2,
+    Key::Num3,
+    Key::Num4,
+    Key::Num5,
+    Key::Num6,
+    Key::Num7,
+    Key::Num8,
+    Key::Num9,
+];
+
+impl ViewModel {
+    fn persist_bookmarks(&mut self) {
+        self.bookmarks_status = save_bookmarks(&self.bookmarks)
+            .err()
+            .map(|error| format!("Failed to save bookmarks: {error:#}"));
+    }
+
+    /// Saves the camera under `name`, replacing a bookmark of that name in
+    /// its slot.
+    fn save_bookmark(&mut self, name: String) {
+        let bookmark = ViewBookmark {
+            name,
+            pan: [self.pan.x, self.pan.y],
+            zoom: self.zoom,
+            filters: self.bookmark_filters.then_some(BookmarkFilters {
+                metric: self.metric,
+                min_size_mb: self.min_size_mb,
+                max_nodes: self.max_nodes,
+            }),
+        };
+        match self
+            .bookmarks
+            .iter_mut()
+            .find(|existing| existing.name == bookmark.name)
+        {
+            Some(existing) => *existing = bookmark,
+            None => self.bookmarks.push(bookmark),
+        }
+        self.persist_bookmarks();
+    }
+
+    fn recall_bookmark(&mut self, slot: usize) {
+        let Some(bookmark) = self.bookmarks.get(slot) else {
+            return;
+        };
+
+        self.pan = vec2(bookmark.pan[0], bookmark.pan[1]);
+        self.zoom = bookmark.zoom;
+        if let Some(filters) = bookmark.filters {
+            if self.metric != filters.metric
+                || self.min_size_mb != filters.min_size_mb
+                || self.max_nodes != filters.max_nodes
+            {
+                self.graph_dirty = true;
+            }
+            self.metric = filters.metric;
+            self.min_size_mb = filters.min_size_mb;
+            self.max_nodes = filters.max_nodes;
+        }
+    }
+
+    /// Recalls bookmarks with 1-9 while no text field has focus.
+    pub(in crate::app) fn handle_bookmark_keys(&mut self, ui: &Ui) {
+        if self.bookmarks.is_empty() || ui.ctx().wants_keyboard_input() {
+            return;
+        }
+
+        let slot = ui.input(|input| {
+            if !input.modifiers.is_none() {
+                return None;
+            }
+            BOOKMARK_KEYS.iter().position(|&key| input.key_pressed(key))
+        });
+        if let Some(slot) = slot {
+            self.recall_bookmark(slot);
+        }
+    }
+
+    pub(in crate::app) fn draw_bookmarks(&mut self, ui: &mut Ui) {
+        ui.weak("Press 1-9 over the graph to jump to the bookmark in that slot.");
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.bookmark_name)
+                    .hint_text("Toolchains")
+                    .desired_width(140.0),
+            );
+            let name = self.bookmark_name.trim().to_owned();
+            if ui
+                .add_enabled(!name.is_empty(), egui::Button::new("Save view"))
+                .on_hover_text("Saving under an existing name replaces that bookmark.")
+                .clicked()
+            {
+                self.save_bookmark(name);
+                self.bookmark_name.clear();
+            }
+        });
+        ui.checkbox(&mut self.bookmark_filters, "Include filters")
+            .on_hover_text("Also restore the size metric, minimum size and node limit.");
+        if let Some(status) = &self.bookmarks_status {
+            ui.weak(status.as_str());
+        }
+
+        if self.bookmarks.is_empty() {
+            return;
+        }
+        ui.separator();
+
+        let mut recalled = None;
+        let mut moved = None;
+        let mut deleted = None;
+        let last = self.bookmarks.len() - 1;
+        for (slot, bookmark) in self.bookmarks.iter().enumerate() {
+            ui.horizontal(|ui| {
+                let key = if slot < BOOKMARK_KEYS.len() {
+                    (slot + 1).to_string()
+                } else {
+                    " ".to_owned()
+                };
+                ui.monospace(key);
+                ui.label(RichText::new(bookmark.name.as_str()).strong())
+                    .on_hover_text(match bookmark.filters {
+                        Some(filters) => format!(
+                            "Zoom {:.2}, at least {:.0} MiB, up to {} nodes",
+                            bookmark.zoom,
+                            filters.min_size_mb,
+                            format_count(filters.max_nodes as u64)
+                        ),
+                        None => format!("Zoom {:.2}", bookmark.zoom),
+                    });
+                if ui.small_button("Go").clicked() {
+                    recalled = Some(slot);
+                }
+                if ui
+                    .add_enabled(slot > 0, egui::Button::new("Up").small())
+                    .on_hover_text("Move to the previous slot.")
+                    .clicked()
+                {
+                    moved = Some((slot, slot - 1));
+                }
+                if ui
+                    .add_enabled(slot < last, egui::Button::new("Down").small())
+                    .on_hover_text("Move to the next slot.")
+                    .clicked()
+                {
+                    moved = Some((slot, slot + 1));
+                }
+                if ui.small_button("Delete").clicked() {
+                    deleted = Some(slot);
+                }
+            });
+        }
+
+        if let Some(slot) = recalled {
+            self.recall_bookmark(slot);
+        }
+        if let Some((from, to)) = moved {
+            self.bookmarks.swap(from, to);
+            self.persist_bookmarks();
+        }
+        if let Some(slot) = deleted {
+            self.bookmarks.remove(slot);
+            self.persist_bookmarks();
+        }
+    }
+
+    fn persist_node_bookmarks(&mut self) {
+        self.node_bookmarks_status = save_node_bookmarks(&self.node_bookmarks)
+            .err()
+            .map(|error| format!("Failed to save starred paths: {error:#}"));
+    }
+
+    pub(in crate::app) fn is_starred(&self, id: &str) -> bool {
+        let name = short_name(id);
+        self.node_bookmarks
+            .iter()
+            .any(|bookmark| bookmark.name == name)
+    }
+
+    /// Stars `id`, or removes its star along with its note.
+    pub(in crate::app) fn toggle_starred(&mut self, id: &str) {
+        let name = short_name(id);
+        match self
+            .node_bookmarks
+            .iter()
+            .position(|bookmark| bookmark.name == name)
+        {
+            Some(index) => {
+                self.node_bookmarks.remove(index);
+            }
+            None => self.node_bookmarks.push(NodeBookmark {
+                name: name.to_owned(),
+                note: String::new(),
+            }),
+        }
+        self.persist_node_bookmarks();
+    }
+
+    /// The path in the loaded graph a starred name stands for.
+    fn resolve_node_bookmark(&self, name: &str) -> Option<String> {
+        self.graph
+            .nodes
+            .keys()
+            .find(|id| short_name(id) == name)
+            .cloned()
+    }
+
+    pub(in crate::app) fn draw_node_bookmarks(&mut self, ui: &mut Ui) {
+        ui.weak("Star a path in the details panel to keep it here, with a note.");
+        if let Some(status) = &self.node_bookmarks_status {
+            ui.weak(status.as_str());
+        }
+        if self.node_bookmarks.is_empty() {
+            return;
+        }
+        ui.separator();
+
+        let mut revealed = None;
+        let mut deleted = None;
+        let mut notes_changed = false;
+        let ids = self
+            .node_bookmarks
+            .iter()
+            .map(|bookmark| self.resolve_node_bookmark(&bookmark.name))
+            .collect::<Vec<_>>();
+        for (index, (bookmark, id)) in self.node_bookmarks.iter_mut().zip(ids).enumerate() {
+            ui.horizontal(|ui| {
+                match id {
+                    Some(id) => {
+                        let selected = self.selected.as_deref() == Some(id.as_str());
+                        if ui
+                            .selectable_label(selected, bookmark.name.as_str())
+                            .on_hover_text("Select and focus this path.")
+                            .clicked()
+                        {
+                            revealed = Some(id);
+                        }
+                    }
+                    None => {
+                        ui.weak(bookmark.name.as_str())
+                            .on_hover_text("Not in the loaded closure.");
+                    }
+                }
+                if ui.small_button("Delete").clicked() {
+                    deleted = Some(index);
+                }
+            });
+            let note = ui.add(
+                egui::TextEdit::singleline(&mut bookmark.note)
+                    .hint_text("Note")
+                    .desired_width(f32::INFINITY),
+            );
+            notes_changed |= note.lost_focus();
+        }
+
+        if let Some(id) = revealed {
+            self.reveal_node(&id);
+        }
+        if let Some(index) = deleted {
+            self.node_bookmarks.remove(index);
+        }
+        if notes_changed || deleted.is_some() {
+            self.persist_node_bookmarks();
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use eframe::egui::{self, Align, Layout, Sense, Ui, vec2};
+
+use crate::format::format_bytes;
+use crate::nix::NodeClass;
+
+use super::super::render_utils::node_class_color;
+use super::super::{EffectiveTotals, ViewModel};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ClassFilterMode {
+    Any,
+    Only,
+    Hide,
+}
+
+impl ViewModel {
+    pub(in crate::app) fn effective_closure_totals(&mut self) -> (usize, u64) {
+        if let Some(totals) = &self.effective_totals
+            && totals.class_include == self.class_include
+            && totals.class_exclude == self.class_exclude
+            && totals.ignore_terms == self.ignore_terms
+        {
+            return (totals.count, totals.bytes);
+        }
+
+        let (count, bytes) = self
+            .graph
+            .nodes
+            .values()
+            .filter(|node| self.passes_class_filter(node.classes) && !self.is_ignored(&node.id))
+            .fold((0, 0), |(count, bytes), node| {
+                (count + 1, bytes + node.nar_size)
+            });
+        self.effective_totals = Some(EffectiveTotals {
+            class_include: self.class_include,
+            class_exclude: self.class_exclude,
+            ignore_terms: self.ignore_terms.clone(),
+            count,
+            bytes,
+        });
+        (count, bytes)
+    }
+
+    fn class_filter_mode(&self, class: NodeClass) -> ClassFilterMode {
+        if self.class_include.contains(class) {
+            ClassFilterMode::Only
+        } else if self.class_exclude.contains(class) {
+            ClassFilterMode::Hide
+        } else {
+            ClassFilterMode::Any
+        }
+    }
+
+    fn set_class_filter_mode(&mut self, class: NodeClass, mode: ClassFilterMode) {
+        self.class_include.set(class, mode == ClassFilterMode::Only);
+        self.class_exclude.set(class, mode == ClassFilterMode::Hide);
+        self.graph_dirty = true;
+    }
+
+    pub(in crate::app) fn draw_effective_closure(&mut self, ui: &mut Ui) {
+        egui::CollapsingHeader::new("Effective closure size")
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.label("Ignore patterns (comma separated)")
+                    .on_hover_text("Hide paths whose name contains any of these substrings.");
+                let response = ui.text_edit_singleline(&mut self.ignore_patterns);
+                if response.changed() {
+                    self.ignore_terms = self
+                        .ignore_patterns
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|pattern| !pattern.is_empty())
+                        .map(str::to_owned)
+                        .collect();
+                    self.graph_dirty = true;
+                }
+
+                let total_count = self.graph.node_count();
+                let total_bytes = self
+                    .graph
+                    .nodes
+                    .values()
+                    .map(|node| node.nar_size)
+                    .sum::<u64>();
+                let (count, bytes) = self.effective_closure_totals();
+                let saved = total_bytes.saturating_sub(bytes);
+                let saved_percent = if total_bytes == 0 {
+                    0.0
+                } else {
+                    saved as f64 / total_bytes as f64 * 100.0
+                };
+
+                ui.label(format!(
+                    "Full closure: {total_count} paths, {}",
+                    format_bytes(total_bytes)
+                ));
+                ui.label(format!("Effective: {count} paths, {}", format_bytes(bytes)));
+                ui.label(format!(
+                    "Potential savings: {} ({saved_percent:.1}%)",
+                    format_bytes(saved)
+                ))
+                .on_hover_text("Sum of NAR sizes excluded by class filters and ignore patterns.");
+            });
+    }
+
+    pub(in crate::app) fn draw_class_legend(&mut self, ui: &mut Ui) {
+        egui::CollapsingHeader::new("Node classes")
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.weak(
+                    "Tick a class to show its badge; use only/hide to filter the rendered graph.",
+                );
+                for class in NodeClass::ALL {
+                    ui.horizontal(|ui| {
+                        let (rect, _) = ui.allocate_exact_size(vec2(12.0, 12.0), Sense::hover());
+                        ui.painter()
+                            .circle_filled(rect.center(), 5.0, node_class_color(class));
+
+                        let mut visible = self.badge_classes.contains(class);
+                        if ui.checkbox(&mut visible, class.label()).changed() {
+                            self.badge_classes.set(class, visible);
+                        }
+
+                        ui.weak(format!("{}", self.graph.class_count(class)));
+
+                        ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                            let mut mode = self.class_filter_mode(class);
+                            let mut changed = false;
+                            changed |= ui
+                                .selectable_value(&mut mode, ClassFilterMode::Hide, "hide")
+                                .on_hover_text("Exclude paths of this class.")
+                                .changed();
+                            changed |= ui
+                                .selectable_value(&mut mode, ClassFilterMode::Only, "only")
+                                .on_hover_text("Show only paths of this (or another 'only') class.")
+                                .changed();
+                            changed |= ui
+                                .selectable_value(&mut mode, ClassFilterMode::Any, "any")
+                                .changed();
+                            if changed {
+                                self.set_class_filter_mode(class, mode);
+                            }
+                        });
+                    });
+                }
+
+                if self.class_filter_active() && ui.button("Clear class filters").clicked() {
+                    for class in NodeClass::ALL {
+                        self.set_class_filter_mode(class, ClassFilterMode::Any);
+                    }
+                }
+            });
+    }
+}
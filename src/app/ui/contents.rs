@@ -0,0 +1,142 @@
+use eframe::egui::{self, RichText, Sense, Ui, vec2};
+
+use crate::format::{format_bytes, format_count};
+use crate::nix::{ContentEntry, ContentKind, scan_contents};
+
+use super::super::jobs::Job;
+use super::super::{ContentScan, ViewModel};
+
+/// Entries listed per directory before the rest are summed up.
+const CONTENT_ROWS: usize = 40;
+
+impl ViewModel {
+    pub(in crate::app) fn poll_content_scans(&mut self) -> bool {
+        let mut running = false;
+
+        for state in self.content_scans.values_mut() {
+            let ContentScan::Running(job) = state else {
+                continue;
+            };
+
+            match job.poll() {
+                Some(result) => *state = ContentScan::Done(result),
+                None => running = true,
+            }
+        }
+
+        running
+    }
+
+    fn start_content_scan(&mut self, id: &str) {
+        let Some(node) = self.graph.nodes.get(id) else {
+            return;
+        };
+
+        let full_path = node.full_path.clone();
+        let job = Job::spawn(&self.nix_settings, move |control| {
+            scan_contents(control, &full_path)
+        });
+
+        self.content_scans
+            .insert(id.to_owned(), ContentScan::Running(job));
+    }
+
+    pub(in crate::app) fn draw_contents_section(&mut self, ui: &mut Ui, id: &str) {
+        ui.separator();
+        ui.label(RichText::new("Contents").strong());
+
+        let mut scan_requested = false;
+        match self.content_scans.get(id) {
+            None => {
+                ui.weak("Break this path down by directory to see which files make it large.");
+                scan_requested = ui.button("Scan contents").clicked();
+            }
+            Some(ContentScan::Running(_)) => {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Scanning...");
+                });
+            }
+            Some(ContentScan::Done(Err(error))) => {
+                ui.label(format!("Scan failed: {error}"));
+                scan_requested = ui.button("Retry").clicked();
+            }
+            Some(ContentScan::Done(Ok(root))) => {
+                ui.weak(format!(
+                    "{} files, {}",
+                    format_count(root.file_count as u64),
+                    format_bytes(root.size)
+                ));
+                egui::ScrollArea::vertical()
+                    .id_salt("contents_scroll")
+                    .max_height(360.0)
+                    .show(ui, |ui| draw_content_children(ui, root, root.size, id));
+            }
+        }
+
+        if scan_requested {
+            self.start_content_scan(id);
+        }
+    }
+}
+
+fn draw_content_children(ui: &mut Ui, directory: &ContentEntry, total: u64, salt: &str) {
+    for child in directory.children.iter().take(CONTENT_ROWS) {
+        let share = if total == 0 {
+            0.0
+        } else {
+            child.size as f32 / total as f32
+        };
+        let label = format!(
+            "{}{}  {}  {:.1}%",
+            child.name,
+            if child.kind == ContentKind::Directory {
+                "/"
+            } else if child.kind == ContentKind::Symlink {
+                " ->"
+            } else {
+                ""
+            },
+            format_bytes(child.size),
+            share * 100.0
+        );
+
+        if child.kind == ContentKind::Directory && !child.children.is_empty() {
+            let salt = format!("{salt}/{}", child.name);
+            ui.horizontal(|ui| {
+                draw_share_bar(ui, share);
+                egui::CollapsingHeader::new(label)
+                    .id_salt(&salt)
+                    .show(ui, |ui| draw_content_children(ui, child, total, &salt));
+            });
+        } else {
+            ui.horizontal(|ui| {
+                draw_share_bar(ui, share);
+                ui.label(label);
+            });
+        }
+    }
+
+    let rest = directory.children.iter().skip(CONTENT_ROWS);
+    let (count, bytes) = rest.fold((0u64, 0u64), |(count, bytes), child| {
+        (count + 1, bytes + child.size)
+    });
+    if count > 0 {
+        ui.weak(format!(
+            "...and {} more entries, {}",
+            format_count(count),
+            format_bytes(bytes)
+        ));
+    }
+}
+
+fn draw_share_bar(ui: &mut Ui, share: f32) {
+    let (rect, _) = ui.allocate_exact_size(vec2(48.0, 8.0), Sense::hover());
+    let visuals = ui.visuals();
+    ui.painter()
+        .rect_filled(rect, 2.0, visuals.extreme_bg_color);
+    let mut filled = rect;
+    filled.set_width(rect.width() * share.clamp(0.0, 1.0));
+    ui.painter()
+        .rect_filled(filled, 2.0, visuals.selection.bg_fill);
+}
@@ -0,0 +1,261 @@
+use std::path::Path;
+use std::process::Command;
+use std::thread;
+
+use anyhow::{Context, Result};
+use eframe::egui::{self, Button, RichText, Ui};
+
+use crate::format::format_bytes;
+use crate::nix::{NixControl, describe_path, store_referrers, why_depends};
+use crate::util::short_name;
+
+use super::super::jobs::Job;
+use super::super::toasts::ToastLevel;
+use super::super::{LabelPanel, NodeReport, NodeReportState, ViewModel};
+
+/// Opens the directory holding `full_path`, or the path itself when it is a
+/// directory, in the desktop's file manager.
+fn open_in_file_manager(full_path: &str) -> Result<()> {
+    let path = Path::new(full_path);
+    let target = if path.is_dir() {
+        path
+    } else {
+        path.parent().unwrap_or(path)
+    };
+    let opener = if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+
+    let mut child = Command::new(opener)
+        .arg(target)
+        .spawn()
+        .with_context(|| format!("failed to run {opener} on {}", target.display()))?;
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+impl ViewModel {
+    /// Actions on `id` offered by a right click on its node or ranking row.
+    pub(in crate::app) fn node_context_menu(&mut self, ui: &mut Ui, id: &str) {
+        let Some(node) = self.graph.nodes.get(id) else {
+            ui.close();
+            return;
+        };
+        let full_path = node.full_path.clone();
+        let reference_count = node.references.len();
+        let is_root = id == self.graph.root_id;
+        let is_drawn = self
+            .graph_cache
+            .as_ref()
+            .is_some_and(|cache| cache.index_by_id.contains_key(id));
+
+        ui.label(RichText::new(self.path_label(LabelPanel::Graph, id)).strong());
+        ui.separator();
+
+        if ui.button("Copy store path").clicked() {
+            ui.ctx().copy_text(full_path.clone());
+            self.notify(ToastLevel::Info, "Copied the store path.");
+        }
+        if ui.button("Open in file manager").clicked()
+            && let Err(error) = open_in_file_manager(&full_path)
+        {
+            self.notify(ToastLevel::Error, format!("{error:#}"));
+        }
+        ui.separator();
+
+        if ui.button("Select").clicked() {
+            self.reveal_node(id);
+        }
+        let pinned = self.pinned_positions.contains_key(short_name(id));
+        if ui
+            .add_enabled(
+                pinned || is_drawn,
+                Button::new(if pinned { "Unpin" } else { "Pin" }),
+            )
+            .on_disabled_hover_text("Only drawn nodes can be pinned.")
+            .clicked()
+        {
+            self.set_pinned(id, !pinned);
+        }
+        if ui
+            .add_enabled(!is_root, Button::new("Hide subtree"))
+            .on_hover_text("Hide this path and every path only it keeps alive.")
+            .clicked()
+        {
+            self.hide_subtree(id);
+        }
+        if self.explore_mode && self.expanded_nodes.contains(id) {
+            if ui.button("Collapse dependencies").clicked() {
+                self.toggle_expanded(id);
+            }
+        } else if ui
+            .add_enabled(
+                reference_count > 0,
+                Button::new(format!("Expand {reference_count} dependencies")),
+            )
+            .on_hover_text("Draw every direct dependency, whatever the size threshold.")
+            .clicked()
+        {
+            self.expand_dependencies(id);
+        }
+        if ui
+            .button("Explore from here")
+            .on_hover_text("Draw only this path, then expand its dependencies one by one.")
+            .clicked()
+        {
+            self.explore_from(id);
+        }
+        ui.separator();
+
+        if ui
+            .add_enabled(!is_root, Button::new("Why depends"))
+            .on_hover_text("Run nix why-depends from the root to this path.")
+            .clicked()
+            && let Some(root) = self.graph.nodes.get(&self.graph.root_id)
+        {
+            let root_path = root.full_path.clone();
+            self.start_node_report(
+                format!(
+                    "Why {} is installed",
+                    self.path_label(LabelPanel::Analysis, id)
+                ),
+                {
+                    let full_path = full_path.clone();
+                    move |control| why_depends(control, &root_path, &full_path)
+                },
+            );
+        }
+        if ui
+            .button("Path info")
+            .on_hover_text("Run nix path-info for sizes and signatures.")
+            .clicked()
+        {
+            self.start_node_report(
+                format!("Path info of {}", self.path_label(LabelPanel::Analysis, id)),
+                {
+                    let full_path = full_path.clone();
+                    move |control| describe_path(control, &full_path)
+                },
+            );
+        }
+        if ui
+            .button("Store referrers")
+            .on_hover_text("List every path in the store that references this one.")
+            .clicked()
+        {
+            self.start_node_report(
+                format!("Referrers of {}", self.path_label(LabelPanel::Analysis, id)),
+                move |control| {
+                    let referrers = store_referrers(control, &full_path)?;
+                    Ok(format!(
+                        "{} referrers in the store\n\n{}",
+                        referrers.len(),
+                        referrers.join("\n")
+                    ))
+                },
+            );
+        }
+    }
+
+    /// Leaves `id` and the paths it dominates out of the graph.
+    fn hide_subtree(&mut self, id: &str) {
+        let dominated = self.graph.dominated_by(id);
+        let count = dominated.len() + 1;
+        self.hidden_nodes.insert(id.to_owned());
+        self.hidden_nodes.extend(dominated);
+        self.graph_dirty = true;
+        let freed = self
+            .hidden_savings()
+            .map(|savings| format_bytes(savings.nar_size))
+            .unwrap_or_default();
+        self.notify(
+            ToastLevel::Info,
+            format!(
+                "Hid {count} paths; removing everything hidden would free {freed}. \
+                 Show them again from the top bar."
+            ),
+        );
+    }
+
+    /// Draws every direct dependency of `id` around it; in explore mode they
+    /// stay drawn until it is collapsed again.
+    fn expand_dependencies(&mut self, id: &str) {
+        if self.explore_mode {
+            self.expand_node(id);
+            return;
+        }
+
+        self.include_node_in_current_graph(id);
+        let Some(references) = self.graph.nodes.get(id).map(|node| node.references.clone()) else {
+            return;
+        };
+
+        for reference in &references {
+            let reference = self.condensed_id(reference).to_owned();
+            let was_drawn = self
+                .graph_cache
+                .as_ref()
+                .is_some_and(|cache| cache.index_by_id.contains_key(&reference));
+            self.include_node_in_current_graph(&reference);
+            if !was_drawn {
+                self.place_next_to(id, &reference);
+            }
+        }
+    }
+
+    fn start_node_report(
+        &mut self,
+        title: String,
+        run: impl FnOnce(&NixControl) -> Result<String> + Send + 'static,
+    ) {
+        self.node_report = Some(NodeReport {
+            title,
+            state: NodeReportState::Running(Box::new(Job::spawn(&self.nix_settings, run))),
+        });
+    }
+
+    pub(in crate::app) fn poll_node_report(&mut self) -> bool {
+        let Some(report) = self.node_report.as_mut() else {
+            return false;
+        };
+        let NodeReportState::Running(job) = &mut report.state else {
+            return false;
+        };
+
+        match job.poll() {
+            Some(result) => report.state = NodeReportState::Done(result),
+            None => return true,
+        }
+        false
+    }
+
+    pub(in crate::app) fn draw_node_report(&self, ui: &mut Ui) {
+        let Some(report) = &self.node_report else {
+            return;
+        };
+
+        match &report.state {
+            NodeReportState::Running(_) => {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Running nix...");
+                });
+            }
+            NodeReportState::Done(Err(error)) => {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+            NodeReportState::Done(Ok(output)) => {
+                if ui.button("Copy").clicked() {
+                    ui.ctx().copy_text(output.clone());
+                }
+                egui::ScrollArea::both()
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        ui.label(RichText::new(output).monospace());
+                    });
+            }
+        }
+    }
+}
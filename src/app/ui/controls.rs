@@ -1,9 +1,16 @@
+use std::ops::RangeInclusive;
+
 use eframe::egui::{self, Align, Key, Layout, Response, Ui};
 
+use crate::format::{ByteUnits, format_bytes, format_count, number_format, set_number_format};
 use crate::nix::SizeMetric;
-use crate::util::short_name;
 
-use super::super::{DependencyRankingMode, SizeRankingMode, ViewModel};
+use super::super::glyphs::NodeGlyph;
+use super::super::physics::PARALLEL_PHYSICS_MIN_NODES;
+use super::super::{
+    DependencyRankingMode, EdgeStyle, FrameRateCap, GraphLayout, LabelPanel, QuadtreeShading,
+    SizeRankingMode, ViewModel,
+};
 
 const SLIDER_KEY_BASE_RATE: f32 = 10.0;
 const SLIDER_KEY_ACCEL_PER_SEC: f32 = 9.0;
@@ -170,20 +177,128 @@ fn apply_slider_arrow_acceleration_usize(
     changed
 }
 
+/// Sliders take keyboard focus only when pressed, so hovering one on the way
+/// to the search box leaves typing alone; Escape gives the focus back.
+fn claim_slider_focus(ui: &Ui, response: &Response) {
+    if response.clicked() || response.drag_started() {
+        response.request_focus();
+    } else if response.has_focus() && ui.input(|input| input.key_pressed(Key::Escape)) {
+        response.surrender_focus();
+    }
+}
+
+/// A slider with a numeric entry for precise values next to it. Arrow keys
+/// nudge the slider while it has focus, speeding up as they are held.
+fn slider_with_entry_f32(
+    ui: &mut Ui,
+    value: &mut f32,
+    range: RangeInclusive<f32>,
+    key_step: f32,
+    snap: Option<f64>,
+    label: &str,
+    hover: &str,
+) -> bool {
+    let (min, max) = (*range.start(), *range.end());
+    ui.horizontal(|ui| {
+        let mut slider = egui::Slider::new(value, range.clone())
+            .show_value(false)
+            .clamping(egui::SliderClamping::Always);
+        if let Some(step) = snap {
+            slider = slider.step_by(step);
+        }
+        let slider = ui.add(slider).on_hover_text(hover);
+        claim_slider_focus(ui, &slider);
+        let mut changed = slider.changed();
+        changed |= apply_slider_arrow_acceleration_f32(ui, &slider, value, min, max, key_step);
+        changed |= ui
+            .add(
+                egui::DragValue::new(value)
+                    .range(range)
+                    .speed(key_step)
+                    .max_decimals(3),
+            )
+            .on_hover_text("Drag, or click to type a value.")
+            .changed();
+        ui.label(label);
+        changed
+    })
+    .inner
+}
+
+fn slider_with_entry_usize(
+    ui: &mut Ui,
+    value: &mut usize,
+    range: RangeInclusive<usize>,
+    key_step: usize,
+    label: &str,
+    hover: &str,
+) -> bool {
+    let (min, max) = (*range.start(), *range.end());
+    ui.horizontal(|ui| {
+        let slider = ui
+            .add(
+                egui::Slider::new(value, range.clone())
+                    .show_value(false)
+                    .step_by(key_step as f64)
+                    .clamping(egui::SliderClamping::Always),
+            )
+            .on_hover_text(hover);
+        claim_slider_focus(ui, &slider);
+        let mut changed = slider.changed();
+        changed |= apply_slider_arrow_acceleration_usize(ui, &slider, value, min, max, key_step);
+        changed |= ui
+            .add(egui::DragValue::new(value).range(range))
+            .on_hover_text("Drag, or click to type a value.")
+            .changed();
+        ui.label(label);
+        changed
+    })
+    .inner
+}
+
 impl ViewModel {
     pub(in crate::app) fn draw_controls(&mut self, ui: &mut Ui) {
         ui.heading("Graph Controls");
         ui.separator();
         ui.add_space(4.0);
 
+        self.draw_scenario_section(ui);
+
         let mut changed = false;
         let mut metric_changed = false;
 
-        ui.label("Search (derivation name)")
-            .on_hover_text("Fuzzy-highlight matching nodes without changing the rendered graph.");
+        ui.label("Search (derivation name)").on_hover_text(
+            "Fuzzy-highlight matching nodes without changing the rendered graph. \
+                 pname:<name> and version:<prefix> match package names and versions exactly.",
+        );
         let search_response = ui.text_edit_singleline(&mut self.search);
+        if std::mem::take(&mut self.focus_search) {
+            search_response.request_focus();
+        }
+        let submitted =
+            search_response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter));
         search_response
             .on_hover_text("Type to pseudo-highlight matching nodes, then click one to select it.");
+        self.draw_search_results(ui, submitted);
+        if ui
+            .add_enabled(
+                !self.search.trim().is_empty(),
+                egui::Button::new("Select all matching"),
+            )
+            .on_hover_text(
+                "Replace the multi-selection with every matching path in the graph, including \
+                 those filtered out of the view.",
+            )
+            .clicked()
+        {
+            self.marked = self.search_matching_ids().into_iter().collect();
+        }
+        if !self.marked.is_empty() {
+            ui.weak(format!(
+                "{}; see Selection sets for bulk actions.",
+                self.marked_summary()
+            ));
+        }
 
         ui.separator();
 
@@ -202,6 +317,13 @@ impl ViewModel {
             changed |= closure_changed;
             metric_changed |= closure_changed;
 
+            let closure_count_changed = ui
+                .selectable_value(&mut self.metric, SizeMetric::ClosureCount, "Closure paths")
+                .on_hover_text("Scale nodes and ranking by how many paths the closure holds.")
+                .changed();
+            changed |= closure_count_changed;
+            metric_changed |= closure_count_changed;
+
             let deps_changed = ui
                 .selectable_value(&mut self.metric, SizeMetric::Dependencies, "Dependencies")
                 .on_hover_text("Scale nodes and ranking by direct dependency count.")
@@ -219,63 +341,237 @@ impl ViewModel {
                 .changed();
             changed |= reverse_deps_changed;
             metric_changed |= reverse_deps_changed;
+
+            let retained_changed = ui
+                .selectable_value(&mut self.metric, SizeMetric::RetainedSize, "Retained size")
+                .on_hover_text(
+                    "Scale nodes and ranking by the space freed if the node were removed.",
+                )
+                .changed();
+            changed |= retained_changed;
+            metric_changed |= retained_changed;
         });
+        ui.checkbox(
+            &mut self.color_by_dominator_share,
+            "Color by share of dominator",
+        )
+        .on_hover_text(
+            "Color each node by the share of its immediate dominator's retained size it retains, \
+             so the child dominating each subtree stands out at any scale.",
+        );
 
         ui.separator();
 
+        changed |= self.draw_name_filter(ui);
+
         let threshold_max = self.min_threshold_max();
         let threshold_label = self.min_threshold_label();
-        let min_threshold_slider = ui
-            .add(
-                egui::Slider::new(&mut self.min_size_mb, 0.0..=threshold_max)
-                    .step_by(5.0)
-                    .text(threshold_label),
-            )
-            .on_hover_text("Hide nodes below this metric value before rendering.");
-        if min_threshold_slider.hovered() {
-            min_threshold_slider.request_focus();
-        }
-        changed |= min_threshold_slider.changed();
-        changed |= apply_slider_arrow_acceleration_f32(
+        changed |= slider_with_entry_f32(
             ui,
-            &min_threshold_slider,
             &mut self.min_size_mb,
-            0.0,
-            threshold_max,
+            0.0..=threshold_max,
             5.0,
+            Some(5.0),
+            threshold_label,
+            "Hide nodes below this metric value before rendering.",
         );
 
         let max_render_nodes_limit = self.graph.node_count().max(2);
-        let max_nodes_slider = ui
-            .add(
-                egui::Slider::new(&mut self.max_nodes, 2..=max_render_nodes_limit)
-                    .step_by(5.0)
-                    .text("Max rendered nodes"),
-            )
-            .on_hover_text("Cap the number of nodes shown to keep rendering responsive.");
-        if max_nodes_slider.hovered() {
-            max_nodes_slider.request_focus();
-        }
-        changed |= max_nodes_slider.changed();
-        changed |= apply_slider_arrow_acceleration_usize(
+        changed |= slider_with_entry_usize(
             ui,
-            &max_nodes_slider,
             &mut self.max_nodes,
-            2,
-            max_render_nodes_limit,
+            2..=max_render_nodes_limit,
             5,
+            "Max rendered nodes",
+            "Cap the number of nodes shown to keep rendering responsive.",
         );
 
+        self.draw_explore_controls(ui);
+
         ui.separator();
 
         ui.checkbox(&mut self.live_physics, "Live physics simulation")
             .on_hover_text("Continuously simulate layout forces while viewing the graph.");
 
+        ui.horizontal(|ui| {
+            ui.label("Frame rate cap");
+            for cap in FrameRateCap::ALL {
+                ui.selectable_value(&mut self.frame_rate_cap, cap, cap.label());
+            }
+        });
+
+        ui.checkbox(&mut self.idle_throttle, "Idle throttling")
+            .on_hover_text("Slow repaints down once the layout has stopped visibly moving.");
+
+        ui.checkbox(&mut self.pause_when_unfocused, "Pause when unfocused")
+            .on_hover_text(
+                "Pause physics, search indexing and store watching while the window is in the background.",
+            );
+
+        ui.add_enabled_ui(!self.watch_store, |ui| {
+            ui.checkbox(&mut self.watch_profile, "Watch system profile")
+                .on_hover_text(
+                    "Notice when the profile switches to a new generation, e.g. after nixos-rebuild switch.",
+                )
+                .on_disabled_hover_text("Watching the store already reloads on a switch.");
+            ui.add_enabled_ui(self.watch_profile, |ui| {
+                ui.checkbox(&mut self.auto_reload_profile, "Reload automatically")
+                    .on_hover_text("Reload on a switch instead of asking first.");
+            });
+        });
+
+        ui.checkbox(&mut self.gpu_rendering, "GPU rendering")
+            .on_hover_text(
+                "Draw node circles and edges as instanced quads in two draw calls; needs OpenGL 3.3 or OpenGL ES 3.0.",
+            );
+
         ui.checkbox(&mut self.show_fps_bar, "FPS Display")
             .on_hover_text("Show a live FPS readout in the header.");
 
         ui.checkbox(&mut self.show_quadtree_overlay, "Show quadtree overlay")
             .on_hover_text("Draw the active quadtree partitions over the graph canvas.");
+        if self.show_quadtree_overlay {
+            ui.horizontal(|ui| {
+                ui.label("Shade cells by");
+                for shading in QuadtreeShading::ALL {
+                    ui.selectable_value(&mut self.quadtree_shading, shading, shading.label());
+                }
+            })
+            .response
+            .on_hover_text(
+                "Fill leaf cells by how many nodes or how much of the size metric they hold per area; hover a cell for its counts.",
+            );
+        }
+
+        ui.checkbox(&mut self.show_physics_heat, "Show physics heat overlay")
+            .on_hover_text(
+                "Color nodes by speed, draw their net force and gray out sleeping nodes, with a breakdown of which forces dominate.",
+            );
+
+        ui.checkbox(&mut self.publication_mode, "Publication mode")
+            .on_hover_text(
+                "White background, dark labels and thicker strokes with debug and hover overlays hidden, for exports and projectors.",
+            );
+
+        let mut number_format = number_format();
+        egui::ComboBox::from_label("Byte units")
+            .selected_text(number_format.units.label())
+            .show_ui(ui, |ui| {
+                for units in ByteUnits::ALL {
+                    if ui
+                        .selectable_value(&mut number_format.units, units, units.label())
+                        .changed()
+                    {
+                        set_number_format(number_format);
+                    }
+                }
+            })
+            .response
+            .on_hover_text("Sizes everywhere, including exports and reports, use these units.");
+
+        if !self.pinned_positions.is_empty()
+            && ui
+                .button(format!("Unpin all ({})", self.pinned_positions.len()))
+                .on_hover_text("Shift+drag nodes with the primary button to pin them in place.")
+                .clicked()
+        {
+            self.unpin_all();
+        }
+
+        if !self.static_nodes.is_empty()
+            && ui
+                .button(format!(
+                    "Return excluded nodes to physics ({})",
+                    self.static_nodes.len()
+                ))
+                .clicked()
+        {
+            self.release_static_nodes();
+        }
+
+        ui.checkbox(&mut self.remember_layout, "Remember layout")
+            .on_hover_text(
+                "Save node positions on reload and exit, and start from them when a mostly overlapping closure is loaded.",
+            );
+        if let Some(status) = &self.layout_status {
+            ui.weak(status.as_str());
+        }
+        ui.collapsing("Layout file", |ui| self.draw_layout_file(ui));
+
+        ui.checkbox(&mut self.label_pname_only, "Label by pname only")
+            .on_hover_text("Hide the version and output chip next to node labels.");
+        ui.checkbox(&mut self.show_node_glyphs, "Category glyphs")
+            .on_hover_text(format!(
+                "Mark large nodes with a glyph for their category: {}.",
+                NodeGlyph::ALL
+                    .iter()
+                    .map(|glyph| glyph.label().to_lowercase())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        ui.checkbox(&mut self.cluster_at_low_zoom, "Cluster when zoomed out")
+            .on_hover_text(
+                "Fold small nearby nodes into one circle with their count while zoomed far out; they separate again as you zoom in.",
+            );
+
+        let mut graph_layout = self.graph_layout;
+        egui::ComboBox::from_label("Layout")
+            .selected_text(graph_layout.label())
+            .show_ui(ui, |ui| {
+                for layout in GraphLayout::ALL {
+                    ui.selectable_value(&mut graph_layout, layout, layout.label());
+                }
+            })
+            .response
+            .on_hover_text(
+                "Layered places the root on top and the paths it references in layers by depth below it, ordered to cross fewer edges. Radial puts the selected node in the middle with the paths it references on rings by depth, and follows the selection.",
+            );
+        if graph_layout != self.graph_layout {
+            self.graph_layout = graph_layout;
+            self.apply_graph_layout();
+            self.fit_to_view();
+        }
+        if self.graph_layout != GraphLayout::Force {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.freeze_layout, "Freeze layout")
+                    .on_hover_text(
+                        "Keep physics off the computed positions; otherwise the simulation relaxes them.",
+                    );
+                if ui.button("Recompute").clicked() {
+                    self.apply_graph_layout();
+                }
+            });
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Edges");
+            for style in EdgeStyle::ALL {
+                ui.selectable_value(&mut self.edge_style, style, style.label());
+            }
+        });
+        ui.checkbox(&mut self.show_edge_arrows, "Edge arrows")
+            .on_hover_text(
+                "Point edges at the path being referenced; drawn once zoomed in far enough.",
+            );
+
+        if ui
+            .checkbox(&mut self.bridge_hidden, "Bridge hidden paths")
+            .on_hover_text(
+                "Draw dashed edges between nodes that only reach each other through hidden, ignored or class-filtered paths.",
+            )
+            .changed()
+        {
+            self.graph_dirty = true;
+        }
+
+        ui.checkbox(&mut self.impact_pulse_enabled, "Impact pulse")
+            .on_hover_text(
+                "Animate a pulse from a newly selected node out to everything that depends on it.",
+            );
+
+        self.draw_class_legend(ui);
+        self.draw_effective_closure(ui);
+        self.draw_gc_roots_section(ui);
 
         ui.collapsing("FPS Display tuning", |ui| {
             ui.add_enabled_ui(self.show_fps_bar, |ui| {
@@ -293,138 +589,81 @@ impl ViewModel {
         });
 
         ui.collapsing("Physics tuning", |ui| {
-            let physics_intensity_slider = ui
-                .add(
-                    egui::Slider::new(&mut self.physics_intensity, 0.2..=2.5)
-                        .text("Intensity")
-                        .clamping(egui::SliderClamping::Always),
-                )
-                .on_hover_text("Overall strength applied to all physics forces.");
-            if physics_intensity_slider.hovered() {
-                physics_intensity_slider.request_focus();
-            }
-            changed |= apply_slider_arrow_acceleration_f32(
+            changed |= slider_with_entry_f32(
                 ui,
-                &physics_intensity_slider,
                 &mut self.physics_intensity,
-                0.2,
-                2.5,
+                0.2..=2.5,
                 default_slider_key_step(0.2, 2.5),
+                None,
+                "Intensity",
+                "Overall strength applied to all physics forces.",
             );
 
-            let physics_repulsion_slider = ui
-                .add(
-                    egui::Slider::new(&mut self.physics_repulsion, 0.25..=2.6)
-                        .text("Repulsion")
-                        .clamping(egui::SliderClamping::Always),
-                )
-                .on_hover_text("How strongly nodes push away from each other.");
-            if physics_repulsion_slider.hovered() {
-                physics_repulsion_slider.request_focus();
-            }
-            changed |= apply_slider_arrow_acceleration_f32(
+            changed |= slider_with_entry_f32(
                 ui,
-                &physics_repulsion_slider,
                 &mut self.physics_repulsion,
-                0.25,
-                2.6,
+                0.25..=2.6,
                 default_slider_key_step(0.25, 2.6),
+                None,
+                "Repulsion",
+                "How strongly nodes push away from each other.",
             );
 
-            let physics_spring_slider = ui
-                .add(
-                    egui::Slider::new(&mut self.physics_spring, 0.2..=2.2)
-                        .text("Edge spring")
-                        .clamping(egui::SliderClamping::Always),
-                )
-                .on_hover_text("How strongly connected nodes pull toward their target distance.");
-            if physics_spring_slider.hovered() {
-                physics_spring_slider.request_focus();
-            }
-            changed |= apply_slider_arrow_acceleration_f32(
+            changed |= slider_with_entry_f32(
                 ui,
-                &physics_spring_slider,
                 &mut self.physics_spring,
-                0.2,
-                2.2,
+                0.2..=2.2,
                 default_slider_key_step(0.2, 2.2),
+                None,
+                "Edge spring",
+                "How strongly connected nodes pull toward their target distance.",
             );
 
-            let physics_collision_slider = ui
-                .add(
-                    egui::Slider::new(&mut self.physics_collision, 0.2..=2.0)
-                        .text("Collision")
-                        .clamping(egui::SliderClamping::Always),
-                )
-                .on_hover_text("Extra separation force to prevent overlap between nearby nodes.");
-            if physics_collision_slider.hovered() {
-                physics_collision_slider.request_focus();
-            }
-            changed |= apply_slider_arrow_acceleration_f32(
+            changed |= slider_with_entry_f32(
                 ui,
-                &physics_collision_slider,
                 &mut self.physics_collision,
-                0.2,
-                2.0,
+                0.2..=2.0,
                 default_slider_key_step(0.2, 2.0),
+                None,
+                "Collision",
+                "Extra separation force to prevent overlap between nearby nodes.",
             );
 
-            let physics_velocity_damping_slider = ui
-                .add(
-                    egui::Slider::new(&mut self.physics_velocity_damping, 0.78..=0.97)
-                        .text("Velocity damping")
-                        .clamping(egui::SliderClamping::Always),
-                )
-                .on_hover_text("How quickly node movement slows each frame.");
-            if physics_velocity_damping_slider.hovered() {
-                physics_velocity_damping_slider.request_focus();
-            }
-            changed |= apply_slider_arrow_acceleration_f32(
+            changed |= slider_with_entry_f32(
                 ui,
-                &physics_velocity_damping_slider,
                 &mut self.physics_velocity_damping,
-                0.78,
-                0.97,
+                0.78..=0.97,
                 default_slider_key_step(0.78, 0.97),
+                None,
+                "Velocity damping",
+                "How quickly node movement slows each frame.",
             );
 
-            let physics_target_spread_slider = ui
-                .add(
-                    egui::Slider::new(&mut self.physics_target_spread, 0.6..=2.0)
-                        .text("Target spread")
-                        .clamping(egui::SliderClamping::Always),
-                )
-                .on_hover_text("Preferred spacing between connected regions of the graph.");
-            if physics_target_spread_slider.hovered() {
-                physics_target_spread_slider.request_focus();
-            }
-            changed |= apply_slider_arrow_acceleration_f32(
+            changed |= slider_with_entry_f32(
                 ui,
-                &physics_target_spread_slider,
                 &mut self.physics_target_spread,
-                0.6,
-                2.0,
+                0.6..=2.0,
                 default_slider_key_step(0.6, 2.0),
+                None,
+                "Target spread",
+                "Preferred spacing between connected regions of the graph.",
             );
 
-            let physics_spread_force_slider = ui
-                .add(
-                    egui::Slider::new(&mut self.physics_spread_force, 0.0..=0.08)
-                        .text("Spread correction")
-                        .clamping(egui::SliderClamping::Always),
-                )
-                .on_hover_text("How aggressively layout drift is corrected over time.");
-            if physics_spread_force_slider.hovered() {
-                physics_spread_force_slider.request_focus();
-            }
-            changed |= apply_slider_arrow_acceleration_f32(
+            changed |= slider_with_entry_f32(
                 ui,
-                &physics_spread_force_slider,
                 &mut self.physics_spread_force,
-                0.0,
-                0.08,
+                0.0..=0.08,
                 default_slider_key_step(0.0, 0.08),
+                None,
+                "Spread correction",
+                "How aggressively layout drift is corrected over time.",
             );
+
+            ui.checkbox(&mut self.parallel_physics, "Parallel physics")
+                .on_hover_text(format!(
+                    "Spread repulsion and collisions over all cores once {} or more nodes are simulated.",
+                    format_count(PARALLEL_PHYSICS_MIN_NODES as u64)
+                ));
         });
 
         if changed {
@@ -445,28 +684,69 @@ impl ViewModel {
                 ui.horizontal(|ui| {
                     ui.selectable_value(
                         &mut self.size_ranking_mode,
-                        SizeRankingMode::NarSize,
+                        SizeRankingMode::Nar,
                         "NAR size",
                     )
                     .on_hover_text("Derivations with the highest NAR size.");
                     ui.selectable_value(
                         &mut self.size_ranking_mode,
-                        SizeRankingMode::ClosureSize,
+                        SizeRankingMode::Closure,
                         "Closure size",
                     )
                     .on_hover_text("Derivations with the highest transitive closure size.");
+                    ui.selectable_value(
+                        &mut self.size_ranking_mode,
+                        SizeRankingMode::Retained,
+                        "Retained size",
+                    )
+                    .on_hover_text(
+                        "Derivations whose removal would free the most space, counting \
+                         only dependencies nothing else needs.",
+                    );
                 });
 
                 ui.add_space(6.0);
 
                 match self.size_ranking_mode {
-                    SizeRankingMode::NarSize => self.draw_metric_ranking(ui, SizeMetric::NarSize),
-                    SizeRankingMode::ClosureSize => {
+                    SizeRankingMode::Nar => self.draw_metric_ranking(ui, SizeMetric::NarSize),
+                    SizeRankingMode::Closure => {
                         self.draw_metric_ranking(ui, SizeMetric::ClosureSize)
                     }
+                    SizeRankingMode::Retained => {
+                        self.draw_metric_ranking(ui, SizeMetric::RetainedSize)
+                    }
                 }
             });
 
+        if self.graph.is_multi_root() {
+            ui.add_space(8.0);
+            egui::CollapsingHeader::new("Roots")
+                .default_open(true)
+                .show(ui, |ui| self.draw_root_rankings(ui));
+        }
+
+        if !self.store_dirs.is_empty() {
+            ui.add_space(8.0);
+            egui::CollapsingHeader::new("Stores")
+                .default_open(true)
+                .show(ui, |ui| self.draw_store_filter(ui));
+        }
+
+        ui.add_space(8.0);
+        egui::CollapsingHeader::new("Selection sets").show(ui, |ui| self.draw_selection_sets(ui));
+
+        ui.add_space(8.0);
+        egui::CollapsingHeader::new("View bookmarks").show(ui, |ui| self.draw_bookmarks(ui));
+
+        ui.add_space(8.0);
+        egui::CollapsingHeader::new("Starred paths").show(ui, |ui| self.draw_node_bookmarks(ui));
+
+        ui.add_space(8.0);
+        egui::CollapsingHeader::new("Annotations").show(ui, |ui| self.draw_annotation_controls(ui));
+
+        ui.add_space(8.0);
+        self.draw_findings(ui);
+
         ui.add_space(8.0);
         egui::CollapsingHeader::new("Dependency rankings")
             .default_open(true)
@@ -484,6 +764,12 @@ impl ViewModel {
                         "Reverse dependencies",
                     )
                     .on_hover_text("Derivations referenced by the highest number of others.");
+                    ui.selectable_value(
+                        &mut self.dependency_ranking_mode,
+                        DependencyRankingMode::ClosureCount,
+                        "Closure paths",
+                    )
+                    .on_hover_text("Derivations whose closures hold the most paths.");
                 });
 
                 ui.add_space(6.0);
@@ -491,10 +777,136 @@ impl ViewModel {
                 match self.dependency_ranking_mode {
                     DependencyRankingMode::Dependencies => self.draw_dependency_ranking(ui),
                     DependencyRankingMode::ReverseDependencies => self.draw_referrer_ranking(ui),
+                    DependencyRankingMode::ClosureCount => {
+                        self.draw_metric_ranking(ui, SizeMetric::ClosureCount)
+                    }
                 }
             });
     }
 
+    /// Paths only one root of a merged graph reaches, e.g. what a dev
+    /// profile adds over the base system.
+    fn draw_explore_controls(&mut self, ui: &mut Ui) {
+        if ui
+            .checkbox(&mut self.explore_mode, "Explore mode")
+            .on_hover_text(
+                "Draw only the anchor and what you expand from it with the +/- badges, \
+                 ignoring the size threshold and node cap.",
+            )
+            .changed()
+        {
+            self.graph_dirty = true;
+        }
+        if !self.explore_mode {
+            return;
+        }
+
+        ui.weak(format!(
+            "From {}, {} expanded",
+            self.path_label(LabelPanel::Controls, self.explore_anchor_id()),
+            format_count(self.expanded_nodes.len() as u64)
+        ));
+        ui.horizontal_wrapped(|ui| {
+            let selected = self.selected.clone();
+            if ui
+                .add_enabled(selected.is_some(), egui::Button::new("From selection"))
+                .on_hover_text("Restart exploring from the selected node.")
+                .clicked()
+                && let Some(id) = selected
+            {
+                self.explore_from(&id);
+            }
+            if ui
+                .add_enabled(
+                    self.explore_anchor.is_some(),
+                    egui::Button::new("From root"),
+                )
+                .clicked()
+            {
+                self.explore_anchor = None;
+                self.collapse_all();
+            }
+            if ui
+                .add_enabled(
+                    !self.expanded_nodes.is_empty(),
+                    egui::Button::new("Collapse all"),
+                )
+                .clicked()
+            {
+                self.collapse_all();
+            }
+        });
+    }
+
+    fn draw_root_rankings(&mut self, ui: &mut Ui) {
+        ui.checkbox(&mut self.color_by_root, "Color by root")
+            .on_hover_text("Color nodes by which roots reach them instead of by the size metric.");
+
+        ui.horizontal_wrapped(|ui| {
+            for (index, root) in self.graph.roots.iter().enumerate() {
+                let label = self.path_label(LabelPanel::Controls, root);
+                ui.selectable_value(&mut self.unique_root_tab, index, label)
+                    .on_hover_text(root.as_str());
+            }
+        });
+
+        let Some(ids) = self.unique_root_rankings.get(self.unique_root_tab) else {
+            return;
+        };
+        let unique_size = ids
+            .iter()
+            .filter_map(|id| self.graph.nodes.get(id))
+            .map(|node| node.nar_size)
+            .sum::<u64>();
+        ui.weak(format!(
+            "{} paths only this root reaches, {} in total",
+            format_count(ids.len() as u64),
+            format_bytes(unique_size)
+        ));
+
+        let row_count = ids.len();
+        let mut selected_id = None;
+        egui::ScrollArea::vertical()
+            .id_salt(("unique_root_scroll", self.unique_root_tab))
+            .max_height(180.0)
+            .auto_shrink([false, false])
+            .show_rows(ui, 22.0, row_count, |ui, row_range| {
+                for index in row_range {
+                    let Some(id) = self.unique_root_rankings[self.unique_root_tab]
+                        .get(index)
+                        .cloned()
+                    else {
+                        continue;
+                    };
+                    let Some(nar_size) = self.graph.nodes.get(&id).map(|node| node.nar_size) else {
+                        continue;
+                    };
+                    let is_selected = self.selected.as_deref() == Some(id.as_str());
+                    let clicked = ui
+                        .horizontal(|ui| {
+                            let label = ui.selectable_label(
+                                is_selected,
+                                self.path_label(LabelPanel::Controls, &id),
+                            );
+                            label.context_menu(|ui| self.node_context_menu(ui, &id));
+                            let clicked = label.clicked();
+                            ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                                ui.label(format_bytes(nar_size));
+                            });
+                            clicked
+                        })
+                        .inner;
+                    if clicked {
+                        selected_id = Some(id);
+                    }
+                }
+            });
+
+        if let Some(id) = selected_id {
+            self.reveal_node(&id);
+        }
+    }
+
     fn draw_metric_ranking(&mut self, ui: &mut Ui, metric: SizeMetric) {
         let rows_visible = self.metric_rows_visible(metric);
         let ids_len = self.metric_ids(metric).len();
@@ -512,10 +924,10 @@ impl ViewModel {
                 }
 
                 for index in row_range {
-                    let Some(id) = self.metric_ids(metric).get(index) else {
+                    let Some(id) = self.metric_ids(metric).get(index).cloned() else {
                         continue;
                     };
-                    let Some(node) = self.graph.nodes.get(id) else {
+                    let Some(node) = self.graph.nodes.get(&id) else {
                         continue;
                     };
 
@@ -524,8 +936,12 @@ impl ViewModel {
 
                     let row_response = ui
                         .horizontal(|ui| {
-                            let clicked =
-                                ui.selectable_label(is_selected, short_name(id)).clicked();
+                            let label = ui.selectable_label(
+                                is_selected,
+                                self.path_label(LabelPanel::Controls, &id),
+                            );
+                            label.context_menu(|ui| self.node_context_menu(ui, &id));
+                            let clicked = label.clicked();
                             ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
                                 ui.label(value_label);
                             });
@@ -534,14 +950,13 @@ impl ViewModel {
                         .inner;
 
                     if row_response {
-                        selected_id = Some(id.clone());
+                        selected_id = Some(id);
                     }
                 }
             });
 
         if let Some(id) = selected_id {
-            self.include_node_in_current_graph(&id);
-            self.set_selected(Some(id));
+            self.reveal_node(&id);
         }
 
         if should_load_more && row_count < ids_len {
@@ -568,10 +983,10 @@ impl ViewModel {
                 }
 
                 for index in row_range {
-                    let Some(id) = self.reverse_dependency_ranking.get(index) else {
+                    let Some(id) = self.reverse_dependency_ranking.get(index).cloned() else {
                         continue;
                     };
-                    let Some(node) = self.graph.nodes.get(id) else {
+                    let Some(node) = self.graph.nodes.get(&id) else {
                         continue;
                     };
 
@@ -580,8 +995,12 @@ impl ViewModel {
 
                     let row_response = ui
                         .horizontal(|ui| {
-                            let clicked =
-                                ui.selectable_label(is_selected, short_name(id)).clicked();
+                            let label = ui.selectable_label(
+                                is_selected,
+                                self.path_label(LabelPanel::Controls, &id),
+                            );
+                            label.context_menu(|ui| self.node_context_menu(ui, &id));
+                            let clicked = label.clicked();
                             ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
                                 ui.label(value_label);
                             });
@@ -590,14 +1009,13 @@ impl ViewModel {
                         .inner;
 
                     if row_response {
-                        selected_id = Some(id.clone());
+                        selected_id = Some(id);
                     }
                 }
             });
 
         if let Some(id) = selected_id {
-            self.include_node_in_current_graph(&id);
-            self.set_selected(Some(id));
+            self.reveal_node(&id);
         }
 
         if should_load_more && row_count < ids_len {
@@ -621,10 +1039,10 @@ impl ViewModel {
                 }
 
                 for index in row_range {
-                    let Some(id) = self.dependency_ranking.get(index) else {
+                    let Some(id) = self.dependency_ranking.get(index).cloned() else {
                         continue;
                     };
-                    let Some(node) = self.graph.nodes.get(id) else {
+                    let Some(node) = self.graph.nodes.get(&id) else {
                         continue;
                     };
 
@@ -633,8 +1051,12 @@ impl ViewModel {
 
                     let row_response = ui
                         .horizontal(|ui| {
-                            let clicked =
-                                ui.selectable_label(is_selected, short_name(id)).clicked();
+                            let label = ui.selectable_label(
+                                is_selected,
+                                self.path_label(LabelPanel::Controls, &id),
+                            );
+                            label.context_menu(|ui| self.node_context_menu(ui, &id));
+                            let clicked = label.clicked();
                             ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
                                 ui.label(value_label);
                             });
@@ -643,14 +1065,13 @@ impl ViewModel {
                         .inner;
 
                     if row_response {
-                        selected_id = Some(id.clone());
+                        selected_id = Some(id);
                     }
                 }
             });
 
         if let Some(id) = selected_id {
-            self.include_node_in_current_graph(&id);
-            self.set_selected(Some(id));
+            self.reveal_node(&id);
         }
 
         if should_load_more && row_count < ids_len {
@@ -662,8 +1083,10 @@ impl ViewModel {
         match metric {
             SizeMetric::NarSize => &self.nar_ranking,
             SizeMetric::ClosureSize => &self.closure_ranking,
+            SizeMetric::ClosureCount => &self.closure_count_ranking,
             SizeMetric::Dependencies => &self.dependency_ranking,
             SizeMetric::ReverseDependencies => &self.reverse_dependency_ranking,
+            SizeMetric::RetainedSize => &self.retained_ranking,
         }
     }
 
@@ -671,8 +1094,10 @@ impl ViewModel {
         match metric {
             SizeMetric::NarSize => self.nar_rows_visible,
             SizeMetric::ClosureSize => self.closure_rows_visible,
+            SizeMetric::ClosureCount => self.closure_count_rows_visible,
             SizeMetric::Dependencies => self.dependency_rows_visible,
             SizeMetric::ReverseDependencies => self.referrer_rows_visible,
+            SizeMetric::RetainedSize => self.retained_rows_visible,
         }
     }
 
@@ -680,8 +1105,10 @@ impl ViewModel {
         match metric {
             SizeMetric::NarSize => self.nar_rows_visible = rows,
             SizeMetric::ClosureSize => self.closure_rows_visible = rows,
+            SizeMetric::ClosureCount => self.closure_count_rows_visible = rows,
             SizeMetric::Dependencies => self.dependency_rows_visible = rows,
             SizeMetric::ReverseDependencies => self.referrer_rows_visible = rows,
+            SizeMetric::RetainedSize => self.retained_rows_visible = rows,
         }
     }
 
@@ -689,8 +1116,10 @@ impl ViewModel {
         match metric {
             SizeMetric::NarSize => "nar_ranking_scroll",
             SizeMetric::ClosureSize => "closure_ranking_scroll",
+            SizeMetric::ClosureCount => "closure_count_ranking_scroll",
             SizeMetric::Dependencies => "dependency_count_ranking_scroll",
             SizeMetric::ReverseDependencies => "reverse_dependency_count_ranking_scroll",
+            SizeMetric::RetainedSize => "retained_ranking_scroll",
         }
     }
 }
@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+
+use eframe::egui::{self, RichText, Ui};
+
+use crate::format::{format_bytes, format_count};
+
+use super::super::{LabelPanel, ViewModel};
+
+impl ViewModel {
+    pub(in crate::app) fn cycle_index(cycles: &[Vec<String>]) -> HashMap<String, usize> {
+        cycles
+            .iter()
+            .enumerate()
+            .flat_map(|(index, cycle)| cycle.iter().map(move |id| (id.clone(), index)))
+            .collect()
+    }
+
+    /// The members of the cycle `id` stands in for, when the condensed view
+    /// is on and `id` leads a cycle.
+    pub(in crate::app) fn condensed_cycle(&self, id: &str) -> Option<&[String]> {
+        if !self.condense_cycles {
+            return None;
+        }
+
+        let cycle = &self.cycles[*self.cycle_by_id.get(id)?];
+        (cycle[0] == id).then_some(cycle.as_slice())
+    }
+
+    /// The path drawn for `id`: its cycle's stand-in in the condensed view,
+    /// otherwise `id` itself.
+    pub(in crate::app) fn condensed_id<'a>(&'a self, id: &'a str) -> &'a str {
+        match self.cycle_by_id.get(id) {
+            Some(&cycle) if self.condense_cycles => &self.cycles[cycle][0],
+            _ => id,
+        }
+    }
+
+    fn cycle_nar_size(&self, cycle: &[String]) -> u64 {
+        cycle
+            .iter()
+            .filter_map(|id| self.graph.nodes.get(id))
+            .map(|node| node.nar_size)
+            .sum()
+    }
+
+    fn select_cycle_member(&mut self, id: &str) {
+        let id = self.condensed_id(id).to_owned();
+        self.reveal_node(&id);
+    }
+
+    pub(in crate::app) fn draw_cycles(&mut self, ui: &mut Ui) {
+        let mut selected_id = None;
+
+        egui::CollapsingHeader::new(format!("Reference cycles ({})", self.cycles.len()))
+            .default_open(true)
+            .show(ui, |ui| {
+                if self.cycles.is_empty() {
+                    ui.label("No reference cycles in this closure.");
+                    ui.weak("Self-references are dropped while collecting.");
+                    return;
+                }
+
+                if ui
+                    .checkbox(&mut self.condense_cycles, "Condensed view")
+                    .on_hover_text("Draw each cycle as one node, led by its largest path.")
+                    .changed()
+                {
+                    self.graph_dirty = true;
+                }
+
+                for (index, cycle) in self.cycles.iter().enumerate() {
+                    egui::CollapsingHeader::new(format!(
+                        "{} and {} more ({})",
+                        self.path_label(LabelPanel::Analysis, &cycle[0]),
+                        format_count(cycle.len() as u64 - 1),
+                        format_bytes(self.cycle_nar_size(cycle))
+                    ))
+                    .id_salt(("cycle", index))
+                    .show(ui, |ui| {
+                        for id in cycle {
+                            if ui
+                                .link(self.path_label(LabelPanel::Analysis, id))
+                                .on_hover_text(id.as_str())
+                                .clicked()
+                            {
+                                selected_id = Some(id.clone());
+                            }
+                        }
+                    });
+                }
+            });
+
+        if let Some(id) = selected_id {
+            self.select_cycle_member(&id);
+        }
+    }
+
+    pub(in crate::app) fn draw_cycle_membership(&mut self, ui: &mut Ui, id: &str) {
+        let Some(&cycle) = self.cycle_by_id.get(id) else {
+            return;
+        };
+
+        ui.separator();
+        ui.label(RichText::new("Reference cycle").strong());
+        let members = &self.cycles[cycle];
+        ui.label(format!(
+            "{} paths reference each other in a loop, {} in total.",
+            format_count(members.len() as u64),
+            format_bytes(self.cycle_nar_size(members))
+        ));
+        if self.condense_cycles {
+            ui.weak(format!(
+                "Drawn as one node for {}.",
+                self.path_label(LabelPanel::Analysis, &members[0])
+            ));
+        }
+
+        let mut selected_id = None;
+        for member in members.iter().filter(|member| *member != id) {
+            if ui
+                .link(self.path_label(LabelPanel::Analysis, member))
+                .on_hover_text(member.as_str())
+                .clicked()
+            {
+                selected_id = Some(member.clone());
+            }
+        }
+        if let Some(member) = selected_id {
+            self.select_cycle_member(&member);
+        }
+    }
+}
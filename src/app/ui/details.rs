@@ -1,20 +1,87 @@
 use std::collections::{HashMap, HashSet};
 
-use eframe::egui::{self, RichText, Ui};
+use eframe::egui::text::{LayoutJob, TextFormat};
+use eframe::egui::{self, Color32, FontId, RichText, Sense, Shape, Stroke, Ui, pos2, vec2};
+
+use crate::format::{format_byte_delta, format_bytes, format_count};
+use crate::nix::PathChange;
+use crate::util::short_name;
+
+use super::super::highlight::{all_paths_from_root, build_highlight_state_for_selected_id};
+use super::super::jobs::Job;
+use super::super::render_utils::diff_color;
+use super::super::toasts::ToastLevel;
+use super::super::{
+    DetailsPanelCache, DetailsPanelCacheKey, LabelPanel, RelatedNodeEntry, RemovalSavings,
+    RemovalSimulation, RemovalState, ViewModel, WorkspaceRequest,
+};
 
-use crate::util::{format_bytes, short_name};
+impl ViewModel {
+    /// Finds in the background what taking `id` out of a merged closure would
+    /// free, for the details panel. With a single root that is the retained
+    /// size, so nothing needs walking.
+    pub(in crate::app) fn simulate_removal(&mut self, id: &str) {
+        if !self.graph.is_multi_root() {
+            return;
+        }
 
-use super::super::highlight::build_highlight_state_for_selected_id;
-use super::super::{DetailsPanelCache, DetailsPanelCacheKey, RelatedNodeEntry, ViewModel};
+        let graph = self.shared_graph();
+        let removed = id.to_owned();
+        let job = Job::spawn(&self.nix_settings, move |_| {
+            let freed = graph.unreachable_without_paths(&HashSet::from([removed.as_str()]));
+            Ok(RemovalSavings {
+                paths: freed.len(),
+                nar_size: freed.iter().map(|id| graph.nodes[id].nar_size).sum(),
+            })
+        });
+        self.removal_simulation = Some(RemovalSimulation {
+            id: id.to_owned(),
+            state: RemovalState::Running(Box::new(job)),
+        });
+    }
+
+    pub(in crate::app) fn poll_removal_simulation(&mut self) -> bool {
+        let Some(simulation) = self.removal_simulation.as_mut() else {
+            return false;
+        };
+        let RemovalState::Running(job) = &mut simulation.state else {
+            return false;
+        };
+
+        match job.poll() {
+            Some(result) => simulation.state = RemovalState::Done(result),
+            None => return true,
+        }
+        false
+    }
 
-impl ViewModel {
     pub(in crate::app) fn draw_details(&mut self, ui: &mut Ui) {
-        ui.heading("Selection Details");
+        ui.horizontal(|ui| {
+            ui.heading("Selection Details");
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                let mut shown = self.store_hashes.shown(LabelPanel::Details);
+                if ui
+                    .checkbox(&mut shown, "Hashes")
+                    .on_hover_text(
+                        "Name paths in this panel by their full store name, hash included. \
+                         View → Store hashes sets the other panels.",
+                    )
+                    .changed()
+                {
+                    self.store_hashes.set(LabelPanel::Details, shown);
+                }
+            });
+        });
         ui.separator();
         ui.add_space(6.0);
 
+        if let Some(edge) = self.selected_edge.clone() {
+            self.draw_edge_details(ui, edge);
+            return;
+        }
+
         let Some(selected_id) = self.selected.clone() else {
-            ui.label("Select a node from the graph or rankings.");
+            ui.label("Select a node or edge from the graph or rankings.");
             return;
         };
 
@@ -23,27 +90,135 @@ impl ViewModel {
             return;
         };
 
-        let node_short = short_name(&node.id).to_string();
+        let node_short = self.path_label(LabelPanel::Details, &node.id).to_string();
         let nar_size = node.nar_size;
         let closure_size = node.closure_size;
         let reference_count = node.references.len();
         let referrer_count = node.referrers.len();
         let deriver = node.deriver.as_deref();
 
-        ui.label(RichText::new(node_short).strong());
-        ui.small(node.id.as_str());
+        let mut simulate_removal = false;
+        let starred = self.is_starred(&selected_id);
+        let mut toggle_star = false;
+        ui.horizontal(|ui| {
+            toggle_star = ui
+                .selectable_label(starred, if starred { "\u{2605}" } else { "\u{2606}" })
+                .on_hover_text(if starred {
+                    "Remove from the starred paths."
+                } else {
+                    "Add to the starred paths in the controls panel."
+                })
+                .clicked();
+            ui.label(RichText::new(node_short).strong());
+        });
+        let copied = ui
+            .add(egui::Label::new(store_path_job(ui, &node.full_path)).sense(Sense::click()))
+            .on_hover_text("Click to copy the full path.")
+            .clicked();
+        if copied {
+            ui.ctx().copy_text(node.full_path.clone());
+        }
         ui.add_space(6.0);
         ui.separator();
 
-        ui.label(format!("Full path: {}", node.full_path));
         ui.label(format!("Node size (narSize): {}", format_bytes(nar_size)));
         ui.label(format!("Closure size: {}", format_bytes(closure_size)));
+        ui.label(format!(
+            "Retained size: {}",
+            format_bytes(node.retained_size)
+        ))
+        .on_hover_text("Space freed by removing this path and everything only it keeps alive.");
+        if node.id != self.graph.root_id && !self.graph.is_multi_root() {
+            ui.label(format!(
+                "Removal saves: {}",
+                format_bytes(node.retained_size)
+            ))
+            .on_hover_text(
+                "With a single root, removing this path frees exactly its retained size.",
+            );
+        } else if node.id != self.graph.root_id {
+            let simulated = self
+                .removal_simulation
+                .as_ref()
+                .filter(|simulation| simulation.id == node.id)
+                .map(|simulation| &simulation.state);
+            ui.horizontal(|ui| match simulated {
+                Some(RemovalState::Done(Ok(savings))) => {
+                    ui.label(format!(
+                        "Removal saves: {} ({} paths)",
+                        format_bytes(savings.nar_size),
+                        format_count(savings.paths as u64)
+                    ))
+                    .on_hover_text(
+                        "Bytes that would leave this closure if every reference to this path \
+                         disappeared: the closure minus what the roots still reach without it.",
+                    );
+                }
+                Some(RemovalState::Running(_)) => {
+                    ui.label("Removal saves:");
+                    ui.spinner();
+                }
+                Some(RemovalState::Done(Err(error))) => {
+                    ui.label("Removal saves:");
+                    ui.weak("unknown").on_hover_text(error.as_str());
+                    simulate_removal = ui.small_button("Retry").clicked();
+                }
+                None => {
+                    ui.label("Removal saves:");
+                    simulate_removal = ui
+                        .small_button("Simulate")
+                        .on_hover_text(
+                            "Walk the closure from the roots without this path and count what \
+                             is left behind.",
+                        )
+                        .clicked();
+                }
+            });
+        }
+        if let Some(dominator) = node.immediate_dominator.as_deref() {
+            ui.label(format!(
+                "Immediate dominator: {}",
+                self.path_label(LabelPanel::Details, dominator)
+            ));
+        }
+        if let Some(share) = self.graph.dominator_share(&node.id) {
+            ui.label(format!("Share of dominator: {:.1}%", share * 100.0))
+                .on_hover_text("This path's retained size relative to its immediate dominator's.");
+        }
         ui.label(format!("Direct dependencies: {reference_count}"));
         ui.label(format!("Reverse dependencies: {referrer_count}"));
 
         if let Some(deriver) = deriver {
             ui.label(format!("Deriver: {deriver}"));
         }
+        if let Some(info) = node.derivation.as_deref() {
+            let unknown = || "unknown".to_owned();
+            ui.label(format!(
+                "Package: {} {}",
+                info.pname.clone().unwrap_or_else(unknown),
+                info.version.clone().unwrap_or_default()
+            ));
+            if !info.outputs.is_empty() {
+                ui.label(format!("Outputs: {}", info.outputs.join(", ")));
+            }
+            ui.label(format!(
+                "License: {}",
+                if info.licenses.is_empty() {
+                    "not recorded in the derivation".to_owned()
+                } else {
+                    info.licenses.join(", ")
+                }
+            ));
+        }
+
+        if !node.classes.is_empty() {
+            let labels = node
+                .classes
+                .iter()
+                .map(|class| class.label())
+                .collect::<Vec<_>>();
+            ui.label(format!("Classes: {}", labels.join(", ")));
+        }
 
         let transitive_delta = closure_size.saturating_sub(nar_size);
         ui.label(format!(
@@ -51,6 +226,87 @@ impl ViewModel {
             format_bytes(transitive_delta)
         ));
 
+        if let Some(diff) = self.closure_diff()
+            && let Some(entry) = diff.entry(&selected_id)
+        {
+            let since = self.path_label(LabelPanel::Details, &diff.base_root);
+            let text = match &entry.change {
+                PathChange::Added => format!("New since {since}"),
+                PathChange::Removed => format!("Removed since {since}"),
+                PathChange::Changed(previous) => format!(
+                    "Replaces {} from {since}: closure {}",
+                    self.path_label(LabelPanel::Details, previous),
+                    format_byte_delta(entry.closure_delta)
+                ),
+            };
+            ui.colored_label(diff_color(&entry.change, entry.closure_delta), text);
+        }
+
+        let history = self.size_history.series(&self.graph.root_id, &selected_id);
+        if history.iter().flatten().count() >= 2 {
+            draw_size_sparkline(ui, &history);
+        }
+
+        if copied {
+            self.notify(ToastLevel::Info, "Copied the store path.");
+        }
+        if toggle_star {
+            self.toggle_starred(&selected_id);
+        }
+        if simulate_removal {
+            self.simulate_removal(&selected_id);
+        }
+
+        let mut pinned = self.pinned_positions.contains_key(short_name(&selected_id));
+        if ui
+            .checkbox(&mut pinned, "Pinned")
+            .on_hover_text("Pinned positions are kept across reloads and sessions.")
+            .changed()
+        {
+            self.set_pinned(&selected_id, pinned);
+        }
+        if let Some(status) = &self.pins_status {
+            ui.weak(status.as_str());
+        }
+
+        let mut layout_static = self.static_nodes.contains(&selected_id);
+        if ui
+            .checkbox(&mut layout_static, "Exclude from physics")
+            .on_hover_text(
+                "Park this node on a ring around the layout and ignore it in the simulation; \
+                 useful for giant hubs.",
+            )
+            .changed()
+        {
+            self.set_layout_static(&selected_id, layout_static);
+        }
+
+        if self.is_workspace {
+            if ui
+                .button("Show in full graph")
+                .on_hover_text("Switch to the full graph tab with this path selected.")
+                .clicked()
+            {
+                self.workspace_request =
+                    Some(WorkspaceRequest::ShowInFullGraph(selected_id.clone()));
+            }
+        } else if ui
+            .button("Open as workspace")
+            .on_hover_text(
+                "Explore this path's closure in its own tab with independent filters and layout.",
+            )
+            .clicked()
+        {
+            self.workspace_request = Some(WorkspaceRequest::Open(selected_id.clone()));
+        }
+
+        self.draw_gc_root_keepers(ui, &selected_id);
+        self.draw_cycle_membership(ui, &selected_id);
+        self.draw_attribute_section(ui, &selected_id);
+        self.draw_verification_section(ui, &selected_id);
+        self.draw_contents_section(ui, &selected_id);
+        self.draw_neighborhood_export(ui, &selected_id);
+
         let (related_nodes, shortest_path_from_root) = self.details_panel_data(&selected_id, 32);
 
         ui.separator();
@@ -107,14 +363,13 @@ impl ViewModel {
 
                         let label = format!(
                             "{}  ({})  [{}]",
-                            short_name(&related.id),
+                            self.path_label(LabelPanel::Details, &related.id),
                             Self::format_metric_value(self.metric, related.metric_value),
                             flags.join(", ")
                         );
 
                         if ui.link(label).on_hover_text(related.id.as_str()).clicked() {
-                            self.include_node_in_current_graph(&related.id);
-                            self.set_selected(Some(related.id.clone()));
+                            self.reveal_node(&related.id);
                         }
                     }
                 });
@@ -133,8 +388,8 @@ impl ViewModel {
             if let Some(parent) = path.get(path.len().saturating_sub(2)) {
                 ui.label(format!(
                     "Included because {} depends on {}.",
-                    short_name(parent),
-                    short_name(&selected_id)
+                    self.path_label(LabelPanel::Details, parent),
+                    self.path_label(LabelPanel::Details, &selected_id)
                 ));
             }
 
@@ -165,9 +420,12 @@ impl ViewModel {
                 for (index, segment) in segments.iter().enumerate() {
                     match segment {
                         PathSegment::Node(id) => {
-                            if ui.link(short_name(id)).on_hover_text(*id).clicked() {
-                                self.include_node_in_current_graph(id);
-                                self.set_selected(Some((*id).to_owned()));
+                            if ui
+                                .link(self.path_label(LabelPanel::Details, id))
+                                .on_hover_text(*id)
+                                .clicked()
+                            {
+                                self.reveal_node(id);
                             }
                         }
                         PathSegment::Ellipsis => {
@@ -180,11 +438,62 @@ impl ViewModel {
                     }
                 }
             });
+
+            self.draw_all_root_paths(ui, &selected_id);
         } else {
             ui.label("No root-reachable path found in the current closure graph.");
         }
     }
 
+    fn draw_all_root_paths(&mut self, ui: &mut Ui, selected_id: &str) {
+        egui::CollapsingHeader::new("All paths from root")
+            .id_salt(("all_root_paths", selected_id))
+            .show(ui, |ui| {
+                let paths = match &mut self.details_panel_cache {
+                    Some(cache) => cache
+                        .all_root_paths
+                        .get_or_insert_with(|| {
+                            all_paths_from_root(&self.graph, selected_id, Self::ROOT_PATH_LIMIT)
+                        })
+                        .clone(),
+                    None => return,
+                };
+
+                if paths.len() >= Self::ROOT_PATH_LIMIT {
+                    ui.weak(format!(
+                        "Showing the first {} paths, shortest first.",
+                        Self::ROOT_PATH_LIMIT
+                    ));
+                } else {
+                    ui.weak(format!("{} distinct paths, shortest first.", paths.len()));
+                }
+
+                egui::ScrollArea::vertical()
+                    .id_salt("all_root_paths_scroll")
+                    .max_height(320.0)
+                    .show(ui, |ui| {
+                        for path in &paths {
+                            ui.horizontal_wrapped(|ui| {
+                                // The root and the selection are the same on every row.
+                                for (index, id) in path.iter().enumerate() {
+                                    if index > 0 {
+                                        ui.weak("->");
+                                    }
+                                    if ui
+                                        .link(self.path_label(LabelPanel::Details, id))
+                                        .on_hover_text(id.as_str())
+                                        .clicked()
+                                    {
+                                        self.reveal_node(id);
+                                    }
+                                }
+                            });
+                            ui.separator();
+                        }
+                    });
+            });
+    }
+
     fn details_panel_data(
         &mut self,
         selected_id: &str,
@@ -217,6 +526,7 @@ impl ViewModel {
             key,
             related_nodes,
             shortest_path_from_root,
+            all_root_paths: None,
         });
 
         let cache = self
@@ -355,3 +665,89 @@ impl ViewModel {
         related
     }
 }
+
+/// Closure size across recorded snapshots, oldest on the left. Gaps mark
+/// snapshots the package was not part of.
+fn draw_size_sparkline(ui: &mut Ui, series: &[Option<u64>]) {
+    let present = series.iter().flatten().copied();
+    let min = present.clone().min().unwrap_or(0);
+    let max = present.max().unwrap_or(0);
+    let first = series.iter().flatten().next().copied().unwrap_or(0);
+    let last = series.iter().rev().flatten().next().copied().unwrap_or(0);
+    let change = format_byte_delta(last as i64 - first as i64);
+
+    ui.label(format!(
+        "Closure size over {} snapshots: {change}",
+        series.len()
+    ));
+    let (rect, response) =
+        ui.allocate_exact_size(vec2(ui.available_width().min(240.0), 34.0), Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 3.0, ui.visuals().extreme_bg_color);
+
+    let inner = rect.shrink(4.0);
+    let span = (max - min).max(1) as f32;
+    let step = inner.width() / (series.len().saturating_sub(1).max(1)) as f32;
+    let point = |index: usize, value: u64| {
+        let height = if max == min {
+            0.5
+        } else {
+            (value - min) as f32 / span
+        };
+        pos2(
+            inner.left() + index as f32 * step,
+            inner.bottom() - height * inner.height(),
+        )
+    };
+
+    let stroke = Stroke::new(1.5, Color32::from_rgb(103, 196, 255));
+    let mut run = Vec::new();
+    for (index, value) in series.iter().enumerate() {
+        if let Some(value) = value {
+            run.push(point(index, *value));
+        } else {
+            painter.add(Shape::line(std::mem::take(&mut run), stroke));
+        }
+    }
+    painter.add(Shape::line(run, stroke));
+    if let Some((index, value)) = series
+        .iter()
+        .enumerate()
+        .rev()
+        .find_map(|(index, value)| value.map(|value| (index, value)))
+    {
+        painter.circle_filled(point(index, value), 2.5, stroke.color);
+    }
+
+    response.on_hover_ui(|ui| {
+        for (index, value) in series.iter().enumerate() {
+            let value = value.map_or_else(|| "absent".to_owned(), format_bytes);
+            ui.label(format!("#{}: {value}", index + 1));
+        }
+    });
+}
+
+/// A full store path with the store directory and hash de-emphasized, so the
+/// name stands out while the path stays complete for copying.
+fn store_path_job(ui: &Ui, full_path: &str) -> LayoutJob {
+    let (directory, base) = full_path
+        .rsplit_once('/')
+        .map_or(("", full_path), |(directory, base)| (directory, base));
+    let (hash, name) = base.split_once('-').unwrap_or(("", base));
+
+    let font = FontId::monospace(11.0);
+    let muted = TextFormat::simple(font.clone(), ui.visuals().weak_text_color());
+    let mut job = LayoutJob::default();
+    if !directory.is_empty() {
+        job.append(&format!("{directory}/"), 0.0, muted.clone());
+    }
+    if !hash.is_empty() {
+        job.append(&format!("{hash}-"), 0.0, muted);
+    }
+    job.append(
+        name,
+        0.0,
+        TextFormat::simple(font, ui.visuals().strong_text_color()),
+    );
+    job
+}
@@ -0,0 +1,161 @@
+use eframe::egui::{self, RichText, Ui};
+
+use crate::format::format_byte_delta;
+use crate::nix::{ClosureDiff, PathChange, collect_closure_diff};
+
+use super::super::jobs::Job;
+use super::super::render_utils::diff_color;
+use super::super::{ComparisonState, DiffFilter, LabelPanel, ViewModel};
+
+impl DiffFilter {
+    fn matches(self, change: &PathChange) -> bool {
+        match self {
+            Self::All => true,
+            Self::Added => *change == PathChange::Added,
+            Self::Removed => *change == PathChange::Removed,
+            Self::Changed => matches!(change, PathChange::Changed(_)),
+        }
+    }
+}
+
+impl ViewModel {
+    pub(in crate::app) fn closure_diff(&self) -> Option<&ClosureDiff> {
+        match &self.comparison {
+            Some(ComparisonState::Done(Ok(diff))) => Some(diff),
+            _ => None,
+        }
+    }
+
+    pub(in crate::app) fn poll_comparison(&mut self) -> bool {
+        let Some(ComparisonState::Running(job)) = &mut self.comparison else {
+            return false;
+        };
+
+        match job.poll() {
+            Some(result) => self.comparison = Some(ComparisonState::Done(result)),
+            None => return true,
+        }
+        false
+    }
+
+    pub(in crate::app) fn start_comparison(&mut self, path: &str) {
+        let base_path = path.to_owned();
+        let current = self.graph.clone();
+        let job = Job::spawn(&self.nix_settings, move |control| {
+            collect_closure_diff(control, &base_path, &current)
+        });
+
+        self.compare_path = path.to_owned();
+        // Replacing a running comparison cancels it.
+        self.comparison = Some(ComparisonState::Running(job));
+    }
+
+    pub(in crate::app) fn draw_diff_window(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Compare with");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.compare_path)
+                    .hint_text("/nix/var/nix/profiles/system-41-link"),
+            );
+            let running = matches!(self.comparison, Some(ComparisonState::Running(..)));
+            if ui
+                .add_enabled(
+                    !running && !self.compare_path.trim().is_empty(),
+                    egui::Button::new("Compare"),
+                )
+                .clicked()
+            {
+                let path = self.compare_path.trim().to_owned();
+                self.start_comparison(&path);
+            }
+        });
+
+        let diff = match &self.comparison {
+            None => {
+                ui.weak("Pick an earlier generation or any other closure to diff against.");
+                return;
+            }
+            Some(ComparisonState::Running(..)) => {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Collecting the comparison closure...");
+                });
+                return;
+            }
+            Some(ComparisonState::Done(Err(error))) => {
+                ui.colored_label(ui.visuals().error_fg_color, error.as_str());
+                return;
+            }
+            Some(ComparisonState::Done(Ok(diff))) => diff,
+        };
+
+        ui.label(format!(
+            "Root closure {} since {}",
+            format_byte_delta(diff.root_closure_delta),
+            self.path_label(LabelPanel::Analysis, &diff.base_root)
+        ));
+        ui.weak(format!(
+            "{} added, {} removed, {} changed",
+            diff.count(|change| *change == PathChange::Added),
+            diff.count(|change| *change == PathChange::Removed),
+            diff.count(|change| matches!(change, PathChange::Changed(_)))
+        ));
+
+        let mut highlight_diff = self.highlight_diff;
+        let mut filter = self.diff_filter;
+        ui.checkbox(&mut highlight_diff, "Highlight changes in the graph")
+            .on_hover_text("Ring added paths green, grown ones red and shrunk ones blue.");
+        ui.horizontal(|ui| {
+            for option in DiffFilter::ALL {
+                ui.selectable_value(&mut filter, option, option.label());
+            }
+        });
+        ui.separator();
+
+        let rows = diff
+            .entries
+            .iter()
+            .filter(|entry| filter.matches(&entry.change))
+            .collect::<Vec<_>>();
+        let mut clicked = None;
+        let row_height = ui.text_style_height(&egui::TextStyle::Body) + 4.0;
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, true])
+            .show_rows(ui, row_height, rows.len(), |ui, range| {
+                for entry in &rows[range] {
+                    let marker = match &entry.change {
+                        PathChange::Added => "+",
+                        PathChange::Removed => "-",
+                        PathChange::Changed(_) => "~",
+                    };
+                    let text = RichText::new(format!(
+                        "{marker} {}  {}",
+                        self.path_label(LabelPanel::Analysis, &entry.id),
+                        format_byte_delta(entry.closure_delta)
+                    ))
+                    .color(diff_color(&entry.change, entry.closure_delta));
+
+                    let mut response = ui.selectable_label(
+                        self.selected.as_deref() == Some(entry.id.as_str()),
+                        text,
+                    );
+                    if let PathChange::Changed(previous) = &entry.change {
+                        response = response.on_hover_text(format!(
+                            "was {}, NAR {}",
+                            self.path_label(LabelPanel::Analysis, previous),
+                            format_byte_delta(entry.nar_delta)
+                        ));
+                    }
+                    if response.clicked() && entry.change != PathChange::Removed {
+                        clicked = Some(entry.id.clone());
+                    }
+                }
+            });
+
+        self.highlight_diff = highlight_diff;
+        self.diff_filter = filter;
+        if let Some(id) = clicked {
+            self.reveal_node(&id);
+        }
+    }
+}
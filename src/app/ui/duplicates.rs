@@ -0,0 +1,83 @@
+use eframe::egui::{self, Ui};
+
+use crate::format::{format_bytes, format_count};
+
+use super::super::{LabelPanel, ViewModel};
+
+impl ViewModel {
+    pub(in crate::app) fn draw_duplicates(&mut self, ui: &mut Ui) {
+        let mut selected_id = None;
+        let mut marked_ids = None;
+
+        let wasted = self
+            .duplicates
+            .iter()
+            .map(|package| package.wasted_bytes)
+            .sum::<u64>();
+        egui::CollapsingHeader::new(format!(
+            "Duplicate packages ({}, ~{})",
+            self.duplicates.len(),
+            format_bytes(wasted)
+        ))
+        .id_salt("duplicate_packages")
+        .default_open(true)
+        .show(ui, |ui| {
+            if self.duplicates.is_empty() {
+                ui.label("Every package is present in one version.");
+                return;
+            }
+            ui.weak("Wasted size counts every version but the largest.");
+
+            for (index, package) in self.duplicates.iter().enumerate() {
+                egui::CollapsingHeader::new(format!(
+                    "{}: {} versions, {} paths (~{} wasted)",
+                    package.pname,
+                    package.versions.len(),
+                    format_count(package.path_count() as u64),
+                    format_bytes(package.wasted_bytes)
+                ))
+                .id_salt(("duplicate", index))
+                .show(ui, |ui| {
+                    if ui
+                        .small_button("Mark all")
+                        .on_hover_text("Add every instance to the multi-selection.")
+                        .clicked()
+                    {
+                        marked_ids = Some(
+                            package
+                                .versions
+                                .iter()
+                                .flat_map(|version| version.ids.iter().cloned())
+                                .collect::<Vec<_>>(),
+                        );
+                    }
+                    for version in &package.versions {
+                        ui.label(format!(
+                            "{} ({})",
+                            version.version,
+                            format_bytes(version.nar_size)
+                        ));
+                        ui.indent(("duplicate_version", index, &version.version), |ui| {
+                            for id in &version.ids {
+                                if ui
+                                    .link(self.path_label(LabelPanel::Analysis, id))
+                                    .on_hover_text(id.as_str())
+                                    .clicked()
+                                {
+                                    selected_id = Some(id.clone());
+                                }
+                            }
+                        });
+                    }
+                });
+            }
+        });
+
+        if let Some(ids) = marked_ids {
+            self.marked.extend(ids);
+        }
+        if let Some(id) = selected_id {
+            self.reveal_node(&id);
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use eframe::egui::{self, RichText, Ui};
+
+use crate::format::format_bytes;
+use crate::nix::scan_references;
+
+use super::super::jobs::Job;
+use super::super::{EdgeDetailsCache, EdgeReferenceScan, LabelPanel, ViewModel};
+
+impl ViewModel {
+    pub(in crate::app) fn draw_edge_details(&mut self, ui: &mut Ui, edge: (String, String)) {
+        let (source_id, target_id) = edge.clone();
+
+        let Some(source) = self.graph.nodes.get(&source_id) else {
+            ui.label("Selected edge no longer exists in the graph state.");
+            return;
+        };
+        if !self.graph.nodes.contains_key(&target_id) {
+            ui.label("Selected edge no longer exists in the graph state.");
+            return;
+        }
+        let source_path = source.full_path.clone();
+
+        ui.label(RichText::new("Dependency edge").strong());
+        ui.horizontal_wrapped(|ui| {
+            if ui
+                .link(self.path_label(LabelPanel::Details, &source_id))
+                .on_hover_text(source_id.as_str())
+                .clicked()
+            {
+                self.set_selected(Some(source_id.clone()));
+            }
+            ui.weak("->");
+            if ui
+                .link(self.path_label(LabelPanel::Details, &target_id))
+                .on_hover_text(target_id.as_str())
+                .clicked()
+            {
+                self.reveal_node(&target_id);
+            }
+        });
+        ui.add_space(6.0);
+        ui.separator();
+
+        let (is_root_path, unreachable_count, freed_bytes) = {
+            let details = self.edge_details_data(&edge);
+            (
+                details.is_root_path,
+                details.unreachable.len(),
+                details.freed_bytes,
+            )
+        };
+
+        if is_root_path {
+            ui.label("On the shortest dependency path from root.");
+        } else {
+            ui.label("Not on the shortest dependency path from root.");
+        }
+
+        ui.separator();
+        ui.label(RichText::new("Removal impact").strong());
+        if unreachable_count == 0 {
+            ui.label("Removing this edge alone frees nothing; the target stays reachable.");
+        } else {
+            ui.label(format!(
+                "Removing this edge drops {unreachable_count} paths ({}).",
+                format_bytes(freed_bytes)
+            ));
+
+            let mut selected_id = None;
+            if let Some(details) = &self.edge_details_cache {
+                egui::ScrollArea::vertical()
+                    .id_salt("edge_unreachable_scroll")
+                    .max_height(160.0)
+                    .auto_shrink([false, true])
+                    .show(ui, |ui| {
+                        for id in &details.unreachable {
+                            if ui
+                                .link(self.path_label(LabelPanel::Details, id))
+                                .on_hover_text(id.as_str())
+                                .clicked()
+                            {
+                                selected_id = Some(id.clone());
+                            }
+                        }
+                    });
+            }
+
+            if let Some(id) = selected_id {
+                self.reveal_node(&id);
+                return;
+            }
+        }
+
+        ui.separator();
+        ui.label(RichText::new("Referencing files").strong());
+        ui.checkbox(&mut self.reference_scanner_enabled, "Reference scanner")
+            .on_hover_text("Scan the source store path for files that embed the target hash.");
+
+        if !self.reference_scanner_enabled {
+            ui.weak(
+                "Enable the reference scanner to list files in the source that mention the target.",
+            );
+            return;
+        }
+
+        self.poll_edge_reference_scan(&edge, &source_path);
+
+        let Some(scan) = &self.edge_reference_scan else {
+            return;
+        };
+
+        match &scan.result {
+            None => {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Scanning source path...");
+                });
+                ui.ctx().request_repaint();
+            }
+            Some(Err(error)) => {
+                ui.label(format!("Scan failed: {error}"));
+            }
+            Some(Ok(matches)) => {
+                if matches.files.is_empty() {
+                    ui.label("No files in the source mention the target hash.");
+                } else {
+                    ui.label(format!(
+                        "{} files reference the target:",
+                        matches.files.len()
+                    ));
+                    egui::ScrollArea::vertical()
+                        .id_salt("edge_reference_files_scroll")
+                        .max_height(220.0)
+                        .auto_shrink([false, true])
+                        .show(ui, |ui| {
+                            for file in &matches.files {
+                                ui.monospace(file.as_str());
+                            }
+                        });
+                }
+                if matches.unreadable > 0 {
+                    ui.weak(format!(
+                        "{} entries could not be read and were skipped.",
+                        matches.unreadable
+                    ));
+                }
+            }
+        }
+    }
+
+    fn edge_details_data(&mut self, edge: &(String, String)) -> &EdgeDetailsCache {
+        let is_cached = self
+            .edge_details_cache
+            .as_ref()
+            .is_some_and(|cache| &cache.edge == edge);
+
+        if !is_cached {
+            let (source_id, target_id) = edge;
+            let is_root_path = self
+                .graph
+                .shortest_path_from_root(target_id)
+                .is_some_and(|path| {
+                    path.windows(2)
+                        .any(|pair| &pair[0] == source_id && &pair[1] == target_id)
+                });
+            let unreachable = self.graph.unreachable_without_edge(source_id, target_id);
+            let freed_bytes = unreachable
+                .iter()
+                .filter_map(|id| self.graph.nodes.get(id))
+                .map(|node| node.nar_size)
+                .sum();
+
+            self.edge_details_cache = Some(EdgeDetailsCache {
+                edge: edge.clone(),
+                is_root_path,
+                unreachable,
+                freed_bytes,
+            });
+        }
+
+        self.edge_details_cache
+            .as_ref()
+            .expect("edge details cache is initialized")
+    }
+
+    fn poll_edge_reference_scan(&mut self, edge: &(String, String), source_path: &str) {
+        let is_current = self
+            .edge_reference_scan
+            .as_ref()
+            .is_some_and(|scan| &scan.edge == edge);
+
+        if !is_current {
+            let source_path = source_path.to_owned();
+            let target_id = edge.1.clone();
+            let job = Job::spawn(&self.nix_settings, move |_| {
+                scan_references(&source_path, &target_id)
+            });
+
+            self.edge_reference_scan = Some(EdgeReferenceScan {
+                edge: edge.clone(),
+                job: Some(job),
+                result: None,
+            });
+        }
+
+        let Some(scan) = self.edge_reference_scan.as_mut() else {
+            return;
+        };
+
+        if let Some(job) = scan.job.as_mut()
+            && let Some(result) = job.poll()
+        {
+            scan.job = None;
+            scan.result = Some(result);
+        }
+    }
+}
@@ -0,0 +1,346 @@
+use std::path::{Path, PathBuf};
+
+use eframe::egui::epaint::ClippedShape;
+use eframe::egui::{self, Rect, RichText, Ui};
+
+use crate::export::{ExportFormat, export_nodes};
+use crate::nix::save_graph;
+use crate::util::{parse_store_name, short_name};
+
+use super::super::ViewModel;
+use super::super::image_export::{
+    ImageExtent, ImageFormat, ImageRequest, MAX_IMAGE_SCALE, write_image,
+};
+use super::super::layouts::{LayoutFile, read_layout_file, write_layout_file};
+use super::super::toasts::ToastLevel;
+
+impl ViewModel {
+    pub(in crate::app) fn draw_neighborhood_export(&mut self, ui: &mut Ui, id: &str) {
+        ui.separator();
+        ui.label(RichText::new("Export selection neighborhood").strong());
+
+        ui.horizontal(|ui| {
+            ui.label("Hops");
+            ui.add(egui::DragValue::new(&mut self.neighborhood_hops).range(0..=8));
+
+            egui::ComboBox::from_id_salt("neighborhood_export_format")
+                .selected_text(self.neighborhood_format.label())
+                .show_ui(ui, |ui| {
+                    for format in ExportFormat::ALL {
+                        ui.selectable_value(&mut self.neighborhood_format, format, format.label());
+                    }
+                });
+        });
+
+        let default_path = self.default_neighborhood_export_path(id);
+        ui.add(
+            egui::TextEdit::singleline(&mut self.neighborhood_export_path)
+                .hint_text(default_path.as_str()),
+        );
+
+        if ui
+            .button("Export")
+            .on_hover_text("Follows references and referrers up to the given number of hops.")
+            .clicked()
+        {
+            self.export_neighborhood(id);
+        }
+
+        if let Some(status) = &self.neighborhood_export_status {
+            ui.weak(status.as_str());
+        }
+    }
+
+    fn default_neighborhood_export_path(&self, id: &str) -> String {
+        format!(
+            "{}-neighborhood.{}",
+            parse_store_name(short_name(id)).pname,
+            self.neighborhood_format.extension()
+        )
+    }
+
+    /// Writes the neighborhood of `id` using the hops, format and path set in
+    /// the details panel.
+    pub(in crate::app) fn export_neighborhood(&mut self, id: &str) {
+        let path = if self.neighborhood_export_path.trim().is_empty() {
+            PathBuf::from(self.default_neighborhood_export_path(id))
+        } else {
+            PathBuf::from(self.neighborhood_export_path.trim())
+        };
+
+        let ids = self.graph.neighborhood(id, self.neighborhood_hops);
+        let (level, status) = match export_nodes(&self.graph, &ids, self.neighborhood_format, &path)
+        {
+            Ok(()) => (
+                ToastLevel::Info,
+                format!("Wrote {} paths to {}", ids.len(), path.display()),
+            ),
+            Err(error) => (ToastLevel::Error, format!("Export failed: {error:#}")),
+        };
+        self.notify(level, status.as_str());
+        self.neighborhood_export_status = Some(status);
+    }
+
+    /// Saves the whole graph next to the working directory, named after the
+    /// root, for reopening with `--load-graph`.
+    pub(in crate::app) fn save_graph_snapshot(&mut self) {
+        let path = PathBuf::from(format!(
+            "{}.graph.json",
+            parse_store_name(short_name(&self.graph.root_id)).pname
+        ));
+        let (level, status) = match save_graph(&self.graph, &path) {
+            Ok(()) => (
+                ToastLevel::Info,
+                format!(
+                    "Saved {} paths to {}",
+                    self.graph.node_count(),
+                    path.display()
+                ),
+            ),
+            Err(error) => (ToastLevel::Error, format!("Saving graph failed: {error:#}")),
+        };
+        self.notify(level, status);
+    }
+
+    pub(in crate::app) fn draw_graph_export(&mut self, ui: &mut Ui) {
+        egui::ComboBox::from_id_salt("graph_export_format")
+            .selected_text(self.graph_export_format.label())
+            .show_ui(ui, |ui| {
+                for format in ExportFormat::ALL {
+                    ui.selectable_value(&mut self.graph_export_format, format, format.label());
+                }
+            });
+
+        let default_path = self.default_graph_export_path();
+        ui.add(
+            egui::TextEdit::singleline(&mut self.graph_export_path)
+                .hint_text(default_path.as_str()),
+        );
+
+        let rendered = self
+            .graph_cache
+            .as_ref()
+            .map_or(&[][..], |cache| cache.nodes.as_slice());
+        if ui
+            .add_enabled(!rendered.is_empty(), egui::Button::new("Export"))
+            .on_hover_text("Includes only the paths that pass the current filters.")
+            .clicked()
+        {
+            let ids = rendered
+                .iter()
+                .map(|node| node.id.clone())
+                .collect::<Vec<_>>();
+            let path = if self.graph_export_path.trim().is_empty() {
+                PathBuf::from(default_path)
+            } else {
+                PathBuf::from(self.graph_export_path.trim())
+            };
+            let (level, status) =
+                match export_nodes(&self.graph, &ids, self.graph_export_format, &path) {
+                    Ok(()) => (
+                        ToastLevel::Info,
+                        format!("Wrote {} paths to {}", ids.len(), path.display()),
+                    ),
+                    Err(error) => (ToastLevel::Error, format!("Export failed: {error:#}")),
+                };
+            self.notify(level, status);
+            ui.close();
+        }
+    }
+
+    fn default_graph_export_path(&self) -> String {
+        format!(
+            "{}-graph.{}",
+            parse_store_name(short_name(&self.graph.root_id)).pname,
+            self.graph_export_format.extension()
+        )
+    }
+
+    pub(in crate::app) fn draw_image_export(&mut self, ui: &mut Ui) {
+        egui::ComboBox::from_id_salt("image_export_format")
+            .selected_text(self.image_export_format.label())
+            .show_ui(ui, |ui| {
+                for format in ImageFormat::ALL {
+                    ui.selectable_value(&mut self.image_export_format, format, format.label());
+                }
+            });
+        egui::ComboBox::from_id_salt("image_export_extent")
+            .selected_text(self.image_export_extent.label())
+            .show_ui(ui, |ui| {
+                for extent in ImageExtent::ALL {
+                    ui.selectable_value(&mut self.image_export_extent, extent, extent.label());
+                }
+            });
+        if self.image_export_format == ImageFormat::Png {
+            ui.horizontal(|ui| {
+                ui.label("Scale");
+                ui.add(
+                    egui::DragValue::new(&mut self.image_export_scale)
+                        .range(1.0..=MAX_IMAGE_SCALE)
+                        .speed(0.05)
+                        .suffix("×"),
+                );
+                if let Some(canvas) = self.canvas_rect {
+                    let size = canvas.size() * self.image_export_scale;
+                    ui.weak(format!("{:.0} × {:.0} px", size.x, size.y));
+                }
+            });
+        }
+
+        let default_path = self.default_image_export_path();
+        ui.add(
+            egui::TextEdit::singleline(&mut self.image_export_path)
+                .hint_text(default_path.as_str()),
+        );
+
+        if ui
+            .add_enabled(self.graph_cache.is_some(), egui::Button::new("Export"))
+            .on_hover_text(
+                "Writes the canvas as drawn on the next frame, with labels, annotations and \
+                 overlays.",
+            )
+            .clicked()
+        {
+            let path = if self.image_export_path.trim().is_empty() {
+                PathBuf::from(default_path)
+            } else {
+                PathBuf::from(self.image_export_path.trim())
+            };
+            self.image_export_request = Some(ImageRequest {
+                path,
+                format: self.image_export_format,
+                extent: self.image_export_extent,
+                scale: self.image_export_scale,
+            });
+            ui.close();
+        }
+    }
+
+    /// Writes the shapes captured from the canvas for `request`.
+    pub(in crate::app) fn write_image_export(
+        &mut self,
+        ctx: &egui::Context,
+        request: &ImageRequest,
+        canvas: Rect,
+        shapes: Vec<ClippedShape>,
+    ) {
+        let textures = self.node_glyphs.image().into_iter().collect::<Vec<_>>();
+        let (level, status) = match write_image(ctx, request, canvas, shapes, &textures) {
+            Ok(()) => (
+                ToastLevel::Info,
+                format!(
+                    "Wrote {} image to {}",
+                    request.format.label(),
+                    request.path.display()
+                ),
+            ),
+            Err(error) => (ToastLevel::Error, format!("Image export failed: {error:#}")),
+        };
+        self.notify(level, status);
+    }
+
+    fn default_image_export_path(&self) -> String {
+        format!(
+            "{}-graph.{}",
+            parse_store_name(short_name(&self.graph.root_id)).pname,
+            self.image_export_format.extension()
+        )
+    }
+
+    fn default_layout_file_path(&self) -> String {
+        format!(
+            "{}-layout.json",
+            parse_store_name(short_name(&self.graph.root_id)).pname
+        )
+    }
+
+    fn layout_file_path(&self) -> PathBuf {
+        match self.layout_file_path.trim() {
+            "" => PathBuf::from(self.default_layout_file_path()),
+            path => PathBuf::from(path),
+        }
+    }
+
+    pub(in crate::app) fn draw_layout_file(&mut self, ui: &mut Ui) {
+        let default_path = self.default_layout_file_path();
+        ui.add(
+            egui::TextEdit::singleline(&mut self.layout_file_path).hint_text(default_path.as_str()),
+        );
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(
+                    self.graph_cache.is_some(),
+                    egui::Button::new("Export layout"),
+                )
+                .on_hover_text("Write the position of every drawn node to a JSON file.")
+                .clicked()
+            {
+                self.export_layout();
+            }
+            if ui
+                .button("Import layout")
+                .on_hover_text(
+                    "Place nodes at the positions in a layout file, matching paths by name \
+                     without the hash so closures from other machines line up.",
+                )
+                .clicked()
+            {
+                let path = self.layout_file_path();
+                self.import_layout(&path);
+            }
+        });
+    }
+
+    fn export_layout(&mut self) {
+        let Some(cache) = &self.graph_cache else {
+            return;
+        };
+        let path = self.layout_file_path();
+        let layout = LayoutFile::new(
+            &self.graph.root_id,
+            cache
+                .nodes
+                .iter()
+                .map(|node| (node.id.as_str(), node.world_pos)),
+        );
+        let (level, status) = match write_layout_file(&path, &layout) {
+            Ok(()) => (
+                ToastLevel::Info,
+                format!(
+                    "Wrote {} positions to {}",
+                    layout.node_count(),
+                    path.display()
+                ),
+            ),
+            Err(error) => (
+                ToastLevel::Error,
+                format!("Layout export failed: {error:#}"),
+            ),
+        };
+        self.notify(level, status.as_str());
+        self.layout_status = Some(status);
+    }
+
+    /// Reads a layout file and places the current graph by it, or the first
+    /// render graph when none is built yet.
+    pub(in crate::app) fn import_layout(&mut self, path: &Path) {
+        let layout = match read_layout_file(path) {
+            Ok(layout) => layout,
+            Err(error) => {
+                let status = format!("Layout import failed: {error:#}");
+                self.notify(ToastLevel::Error, status.as_str());
+                self.layout_status = Some(status);
+                return;
+            }
+        };
+
+        let names = self.graph.nodes.keys().map(|id| short_name(id)).collect();
+        self.saved_layout = Some(layout.restore_into(&names, path));
+        if self.graph_cache.is_some() {
+            self.restore_saved_layout();
+            self.apply_pins();
+            self.apply_static_layout();
+            self.fit_to_view();
+        }
+    }
+}
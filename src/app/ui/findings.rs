@@ -0,0 +1,42 @@
+use eframe::egui::{self, Ui};
+
+use super::super::{LabelPanel, ViewModel};
+
+impl ViewModel {
+    pub(in crate::app) fn draw_findings(&mut self, ui: &mut Ui) {
+        let mut selected_id = None;
+
+        egui::CollapsingHeader::new("Findings")
+            .default_open(true)
+            .show(ui, |ui| {
+                if self.findings.is_empty() {
+                    ui.label("No size-reduction suggestions for this closure.");
+                    return;
+                }
+
+                for (index, finding) in self.findings.iter().enumerate() {
+                    egui::CollapsingHeader::new(finding.summary())
+                        .id_salt(("finding", index))
+                        .show(ui, |ui| {
+                            ui.monospace(finding.advice);
+                            for id in &finding.node_ids {
+                                if ui
+                                    .link(self.path_label(LabelPanel::Analysis, id))
+                                    .on_hover_text(id.as_str())
+                                    .clicked()
+                                {
+                                    selected_id = Some(id.clone());
+                                }
+                            }
+                        });
+                }
+            });
+
+        if let Some(id) = selected_id {
+            self.reveal_node(&id);
+        }
+
+        self.draw_cycles(ui);
+        self.draw_duplicates(ui);
+    }
+}
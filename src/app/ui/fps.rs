@@ -19,7 +19,7 @@ impl ViewModel {
     }
 
     pub(in crate::app) fn fps_display_text(&self) -> Option<String> {
-        if !self.show_fps_bar {
+        if !self.show_fps_bar || self.publication_mode {
             return None;
         }
 
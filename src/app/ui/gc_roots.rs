@@ -0,0 +1,139 @@
+use eframe::egui::{self, RichText, Ui};
+
+use crate::format::{format_bytes, format_count};
+use crate::nix::{GcRoots, collect_gc_roots};
+
+use super::super::jobs::Job;
+use super::super::{GcRootScan, ViewModel};
+
+const KEEPER_ROWS: usize = 12;
+
+impl ViewModel {
+    pub(in crate::app) fn gc_roots(&self) -> Option<&GcRoots> {
+        match &self.gc_roots {
+            Some(GcRootScan::Done(Ok(gc_roots))) => Some(gc_roots),
+            _ => None,
+        }
+    }
+
+    pub(in crate::app) fn poll_gc_roots(&mut self) -> bool {
+        let Some(GcRootScan::Running(job)) = &mut self.gc_roots else {
+            return false;
+        };
+
+        match job.poll() {
+            Some(result) => self.gc_roots = Some(GcRootScan::Done(result)),
+            None => return true,
+        }
+        if self.gc_sole_only {
+            self.graph_dirty = true;
+        }
+        false
+    }
+
+    pub(in crate::app) fn start_gc_root_scan(&mut self) {
+        let graph = self.graph.clone();
+        let job = Job::spawn(&self.nix_settings, move |control| {
+            collect_gc_roots(control, &graph)
+        });
+
+        // Replacing a running scan cancels it.
+        self.gc_roots = Some(GcRootScan::Running(job));
+    }
+
+    pub(in crate::app) fn draw_gc_roots_section(&mut self, ui: &mut Ui) {
+        egui::CollapsingHeader::new("GC roots")
+            .default_open(false)
+            .show(ui, |ui| {
+                let running = matches!(self.gc_roots, Some(GcRootScan::Running(..)));
+                let label = if self.gc_roots.is_some() {
+                    "Rescan GC roots"
+                } else {
+                    "Scan GC roots"
+                };
+                if ui
+                    .add_enabled(!running, egui::Button::new(label))
+                    .on_hover_text(
+                        "Run nix-store --gc --print-roots and find which roots keep each path alive.",
+                    )
+                    .clicked()
+                {
+                    self.start_gc_root_scan();
+                }
+
+                match &self.gc_roots {
+                    None => {
+                        ui.weak("See what a garbage collection would free once this system is gone.");
+                    }
+                    Some(GcRootScan::Running(..)) => {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label("Querying GC roots...");
+                        });
+                    }
+                    Some(GcRootScan::Done(Err(error))) => {
+                        ui.colored_label(ui.visuals().error_fg_color, error.as_str());
+                    }
+                    Some(GcRootScan::Done(Ok(gc_roots))) => {
+                        let (count, bytes) = gc_roots.sole_totals();
+                        ui.label(format!(
+                            "{} GC roots; {} paths ({}) are kept alive only by this system",
+                            format_count(gc_roots.roots.len() as u64),
+                            format_count(count as u64),
+                            format_bytes(bytes)
+                        ));
+                        if gc_roots.unreadable > 0 {
+                            ui.weak(format!(
+                                "{} roots could not be queried and were skipped.",
+                                format_count(gc_roots.unreadable as u64)
+                            ));
+                        }
+                    }
+                }
+
+                if ui
+                    .add_enabled(
+                        self.gc_roots().is_some(),
+                        egui::Checkbox::new(
+                            &mut self.gc_sole_only,
+                            "Only paths reachable solely from this system",
+                        ),
+                    )
+                    .on_hover_text(
+                        "Hide everything another GC root also keeps alive, leaving what would be \
+                         garbage-collected with this system.",
+                    )
+                    .changed()
+                {
+                    self.graph_dirty = true;
+                }
+            });
+    }
+
+    pub(in crate::app) fn draw_gc_root_keepers(&self, ui: &mut Ui, id: &str) {
+        let Some(gc_roots) = self.gc_roots() else {
+            return;
+        };
+
+        ui.separator();
+        ui.label(RichText::new("GC roots").strong());
+        let keepers = gc_roots.keepers(id).collect::<Vec<_>>();
+        if keepers.is_empty() {
+            ui.label("No GC root keeps this path alive; the next garbage collection deletes it.");
+            return;
+        }
+        if gc_roots.is_sole(id) {
+            ui.weak("Only this system keeps it alive.");
+        }
+        for root in keepers.iter().take(KEEPER_ROWS) {
+            ui.monospace(root.link.as_str())
+                .on_hover_text(format!("-> {}", root.target));
+        }
+        if keepers.len() > KEEPER_ROWS {
+            ui.weak(format!(
+                "...and {} more",
+                format_count((keepers.len() - KEEPER_ROWS) as u64)
+            ));
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use eframe::egui::{self, Ui};
+
+use crate::format::{format_bytes, format_utc_datetime};
+use crate::nix::{
+    Generation, NixSettings, SYSTEM_PROFILES_DIR, list_generations, query_closure_size,
+};
+
+use super::super::jobs::Job;
+use super::super::{ClosureSizeQuery, GenerationBrowser, GenerationListing, ViewModel};
+
+impl GenerationBrowser {
+    /// Starts listing the generations in the background.
+    fn open(settings: &NixSettings) -> Self {
+        let job = Job::spawn(settings, |_| {
+            list_generations(Path::new(SYSTEM_PROFILES_DIR))
+        });
+
+        Self {
+            generations: GenerationListing::Running(Box::new(job)),
+            sizes: HashMap::new(),
+            diff_marks: Vec::new(),
+        }
+    }
+
+    fn generation(&self, number: u64) -> Option<&Generation> {
+        let GenerationListing::Done(Ok(generations)) = &self.generations else {
+            return None;
+        };
+        generations
+            .iter()
+            .find(|generation| generation.number == number)
+    }
+
+    /// Queries the closure size of `generation` in the background.
+    fn measure(&mut self, settings: &NixSettings, generation: &Generation) {
+        let target = generation.target.clone();
+        let job = Job::spawn(settings, move |control| {
+            query_closure_size(control, &target)
+        });
+        self.sizes
+            .insert(generation.number, ClosureSizeQuery::Running(Box::new(job)));
+    }
+}
+
+impl ViewModel {
+    pub(in crate::app) fn poll_generations(&mut self) -> bool {
+        let Some(browser) = self.generations.as_mut() else {
+            return false;
+        };
+        let mut running = false;
+
+        if let GenerationListing::Running(job) = &mut browser.generations {
+            match job.poll() {
+                Some(result) => browser.generations = GenerationListing::Done(result),
+                None => running = true,
+            }
+        }
+
+        for query in browser.sizes.values_mut() {
+            if let ClosureSizeQuery::Running(job) = query {
+                match job.poll() {
+                    Some(result) => *query = ClosureSizeQuery::Done(result),
+                    None => running = true,
+                }
+            }
+        }
+
+        running
+    }
+
+    /// Diffs two generations: the newer one is loaded unless it already is,
+    /// and compared against the older one.
+    fn diff_generations(&mut self, base: &Generation, target: &Generation) {
+        let loaded = self
+            .graph
+            .nodes
+            .get(&self.graph.root_id)
+            .is_some_and(|root| root.full_path == target.target);
+        if loaded {
+            self.start_comparison(&base.link);
+            self.show_diff_window = true;
+        } else {
+            self.root_request = Some(target.link.clone());
+            self.root_request_compare = Some(base.link.clone());
+        }
+    }
+
+    pub(in crate::app) fn draw_generations_window(&mut self, ui: &mut Ui) {
+        let browser = self
+            .generations
+            .get_or_insert_with(|| GenerationBrowser::open(&self.nix_settings));
+
+        let mut refresh = false;
+        let mut load = None;
+        let mut diff = None;
+        let mut measure = None;
+        ui.horizontal(|ui| {
+            refresh = ui.button("Refresh").clicked();
+            ui.weak(format!("{SYSTEM_PROFILES_DIR}/system-*-link"));
+        });
+
+        let generations = match &browser.generations {
+            GenerationListing::Running(_) => {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Reading generations...");
+                });
+                &Vec::new()
+            }
+            GenerationListing::Done(Ok(generations)) if generations.is_empty() => {
+                ui.label("No system generations found; this is not a NixOS system profile.");
+                &Vec::new()
+            }
+            GenerationListing::Done(Ok(generations)) => generations,
+            GenerationListing::Done(Err(error)) => {
+                ui.colored_label(ui.visuals().error_fg_color, error.as_str());
+                &Vec::new()
+            }
+        };
+
+        if !generations.is_empty() {
+            ui.horizontal(|ui| {
+                let marked = match browser.diff_marks[..] {
+                    [a, b] => Some((a.min(b), a.max(b))),
+                    _ => None,
+                };
+                let label = match marked {
+                    Some((base, target)) => format!("Diff #{base} → #{target}"),
+                    None => "Diff".to_owned(),
+                };
+                if ui
+                    .add_enabled(marked.is_some(), egui::Button::new(label))
+                    .on_hover_text("Load the newer generation and diff it against the older one.")
+                    .on_disabled_hover_text("Tick two generations to diff them.")
+                    .clicked()
+                {
+                    diff = marked;
+                }
+            });
+            ui.separator();
+        }
+
+        let loaded_root = self
+            .graph
+            .nodes
+            .get(&self.graph.root_id)
+            .map(|root| root.full_path.as_str());
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, true])
+            .show(ui, |ui| {
+                egui::Grid::new("generations_table")
+                    .num_columns(6)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("");
+                        ui.strong("Generation");
+                        ui.strong("Created (UTC)");
+                        ui.strong("Kernel");
+                        ui.strong("Closure");
+                        ui.label("");
+                        ui.end_row();
+
+                        for generation in generations {
+                            let number = generation.number;
+                            let mut marked = browser.diff_marks.contains(&number);
+                            if ui.checkbox(&mut marked, "").changed() {
+                                browser.diff_marks.retain(|&other| other != number);
+                                if marked {
+                                    browser.diff_marks.push(number);
+                                    if browser.diff_marks.len() > 2 {
+                                        browser.diff_marks.remove(0);
+                                    }
+                                }
+                            }
+
+                            let mut label = format!("#{number}");
+                            if generation.current {
+                                label.push_str(" (current)");
+                            }
+                            if loaded_root == Some(generation.target.as_str()) {
+                                ui.strong(label)
+                            } else {
+                                ui.label(label)
+                            }
+                            .on_hover_text(format!("{} → {}", generation.link, generation.target));
+                            ui.label(
+                                generation
+                                    .created
+                                    .map_or_else(|| "—".to_owned(), format_utc_datetime),
+                            );
+                            ui.label(generation.kernel.as_deref().unwrap_or("—"));
+                            match browser.sizes.get(&number) {
+                                Some(ClosureSizeQuery::Done(Ok(size))) => {
+                                    ui.label(format_bytes(*size));
+                                }
+                                Some(ClosureSizeQuery::Done(Err(error))) => {
+                                    ui.weak("unknown").on_hover_text(error.as_str());
+                                }
+                                Some(ClosureSizeQuery::Running(_)) => {
+                                    ui.spinner();
+                                }
+                                None => {
+                                    if ui
+                                        .small_button("Measure")
+                                        .on_hover_text("Query this generation's closure size.")
+                                        .clicked()
+                                    {
+                                        measure = Some(generation.clone());
+                                    }
+                                }
+                            }
+                            if ui
+                                .add_enabled(
+                                    loaded_root != Some(generation.target.as_str()),
+                                    egui::Button::new("Load"),
+                                )
+                                .on_hover_text("Analyze this generation's closure.")
+                                .clicked()
+                            {
+                                load = Some(generation.link.clone());
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+
+        if let Some((base, target)) = diff
+            && let (Some(base), Some(target)) = (
+                browser.generation(base).cloned(),
+                browser.generation(target).cloned(),
+            )
+        {
+            self.diff_generations(&base, &target);
+        }
+        if let Some(generation) = measure
+            && let Some(browser) = self.generations.as_mut()
+        {
+            browser.measure(&self.nix_settings, &generation);
+        }
+        if let Some(link) = load {
+            self.root_request = Some(link);
+        }
+        if refresh {
+            self.generations = None;
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use eframe::egui::{
+    self, Context, Key, KeyboardShortcut, Modifiers, PointerButton, Ui, Vec2, vec2,
+};
+
+use super::super::ViewModel;
+use super::super::actions::Action;
+
+/// Shortcuts bound to actions. They only fire while no text field or slider
+/// has keyboard focus, so typing is never taken over.
+const KEYMAP: [(KeyboardShortcut, Action); 11] = [
+    (plain(Key::Slash), Action::FocusSearch),
+    (plain(Key::Enter), Action::CenterOnSelection),
+    (plain(Key::Plus), Action::ZoomIn),
+    (plain(Key::Equals), Action::ZoomIn),
+    (plain(Key::Minus), Action::ZoomOut),
+    (plain(Key::F), Action::FitToView),
+    (plain(Key::Escape), Action::ClearSelection),
+    (
+        KeyboardShortcut::new(Modifiers::COMMAND, Key::R),
+        Action::ReloadClosure,
+    ),
+    (plain(Key::Questionmark), Action::ToggleKeyHelp),
+    (
+        KeyboardShortcut::new(Modifiers::ALT, Key::ArrowLeft),
+        Action::NavigateBack,
+    ),
+    (
+        KeyboardShortcut::new(Modifiers::ALT, Key::ArrowRight),
+        Action::NavigateForward,
+    ),
+];
+
+/// Arrow keys and the screen direction they walk the selection in.
+const WALK_KEYS: [(Key, Vec2); 4] = [
+    (Key::ArrowLeft, vec2(-1.0, 0.0)),
+    (Key::ArrowRight, vec2(1.0, 0.0)),
+    (Key::ArrowUp, vec2(0.0, -1.0)),
+    (Key::ArrowDown, vec2(0.0, 1.0)),
+];
+
+/// Bindings listed in the help overlay, including those handled elsewhere.
+const KEY_HELP: [(&str, &str); 13] = [
+    ("/", "Focus the search box"),
+    ("Arrow keys", "Select the neighbor in that direction"),
+    ("Enter", "Center the view on the selection"),
+    ("+ / -", "Zoom in / out"),
+    ("F", "Fit the graph to the view"),
+    ("Esc", "Clear the selection"),
+    (
+        "Alt+← / Alt+→",
+        "Back / forward through selections (or mouse side buttons)",
+    ),
+    ("Ctrl+R", "Reload the closure"),
+    ("?", "Show or hide this help"),
+    ("F1", "Show or hide the legend"),
+    ("M", "Measure mode, over the graph"),
+    ("1-9", "Recall a view bookmark, over the graph"),
+    ("Ctrl+click", "Add a node to the multi-selection"),
+];
+
+const fn plain(key: Key) -> KeyboardShortcut {
+    KeyboardShortcut::new(Modifiers::NONE, key)
+}
+
+/// How well the offset to a neighbor lines up with a walk `direction`;
+/// `None` when the neighbor lies behind or beside it.
+fn walk_cost(direction: Vec2, offset: Vec2) -> Option<f32> {
+    let distance = offset.length();
+    if distance <= f32::EPSILON {
+        return None;
+    }
+    let alignment = offset.dot(direction) / distance;
+    // Within 60 degrees of the arrow; nearer and straighter neighbors win.
+    (alignment >= 0.5).then_some(distance * (2.0 - alignment))
+}
+
+impl ViewModel {
+    /// Runs the shortcuts pressed this frame; `keyboard_free` tells whether
+    /// no widget had keyboard focus when the frame started.
+    pub(in crate::app) fn handle_shortcuts(
+        &mut self,
+        ctx: &Context,
+        keyboard_free: bool,
+        is_loading: bool,
+        reload_requested: &mut bool,
+    ) {
+        // The mouse's side buttons step through the history wherever the
+        // pointer is, like in a browser.
+        let (back, forward) = ctx.input(|input| {
+            (
+                input.pointer.button_pressed(PointerButton::Extra1),
+                input.pointer.button_pressed(PointerButton::Extra2),
+            )
+        });
+        if back {
+            self.navigate_back();
+        } else if forward {
+            self.navigate_forward();
+        }
+
+        if !keyboard_free || ctx.wants_keyboard_input() {
+            return;
+        }
+
+        // Escape closes open popups and menus first.
+        let popup_open = egui::Popup::is_any_open(ctx);
+        for (shortcut, action) in KEYMAP {
+            if action == Action::ClearSelection && popup_open {
+                continue;
+            }
+            if self.action_enabled(action, is_loading)
+                && ctx.input_mut(|input| input.consume_shortcut(&shortcut))
+            {
+                self.run_action(ctx, action, reload_requested);
+            }
+        }
+
+        for (key, direction) in WALK_KEYS {
+            if ctx.input_mut(|input| input.consume_key(Modifiers::NONE, key)) {
+                self.walk_selection(direction);
+            }
+        }
+    }
+
+    /// Selects the drawn neighbor of the selection lying closest to
+    /// `direction` on screen, or the root when nothing is selected.
+    fn walk_selection(&mut self, direction: Vec2) {
+        let Some(cache) = self.graph_cache.as_ref() else {
+            return;
+        };
+        let Some(&current) = self
+            .selected
+            .as_ref()
+            .and_then(|id| cache.index_by_id.get(id))
+        else {
+            let root = self.graph.root_id.clone();
+            self.apply_graph_selection(Some(root));
+            return;
+        };
+
+        let origin = cache.nodes[current].world_pos;
+        let next = cache.outgoing[current]
+            .iter()
+            .chain(&cache.incoming[current])
+            .filter_map(|&index| {
+                let cost = walk_cost(direction, cache.nodes[index].world_pos - origin)?;
+                Some((cost, index))
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, index)| cache.nodes[index].id.clone());
+
+        if let Some(id) = next {
+            self.apply_graph_selection(Some(id));
+        }
+    }
+
+    pub(in crate::app) fn draw_key_help(&self, ui: &mut Ui) {
+        egui::Grid::new("key_help")
+            .num_columns(2)
+            .striped(true)
+            .show(ui, |ui| {
+                for (keys, description) in KEY_HELP {
+                    ui.monospace(keys);
+                    ui.label(description);
+                    ui.end_row();
+                }
+            });
+        ui.add_space(4.0);
+        ui.weak("Shortcuts are ignored while a text field or slider has keyboard focus.");
+    }
+}
@@ -0,0 +1,124 @@
+use std::path::PathBuf;
+
+use eframe::egui::{self, Ui};
+
+use crate::format::format_count;
+use crate::lockfile::{ClosureLock, LockDeviation};
+
+use super::super::ViewModel;
+
+impl ViewModel {
+    fn load_lockfile(&mut self, path: PathBuf) {
+        match ClosureLock::load(&path) {
+            Ok(lock) => {
+                self.lock_deviations = lock.check(&self.graph);
+                self.lockfile_status = Some(format!(
+                    "Loaded {} entries from {}",
+                    format_count(lock.len() as u64),
+                    path.display()
+                ));
+                self.lock = Some(lock);
+            }
+            Err(error) => self.lockfile_status = Some(format!("{error:#}")),
+        }
+    }
+
+    fn write_lockfile(&mut self, path: PathBuf) {
+        let lock = ClosureLock::from_graph(&self.graph);
+        self.lockfile_status = Some(match lock.write(&path) {
+            Ok(()) => format!(
+                "Locked {} entries to {}",
+                format_count(lock.len() as u64),
+                path.display()
+            ),
+            Err(error) => format!("{error:#}"),
+        });
+        self.lock_deviations = Vec::new();
+        self.lock = Some(lock);
+    }
+
+    pub(in crate::app) fn draw_lock_window(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Lockfile");
+            ui.add(egui::TextEdit::singleline(&mut self.lockfile_path).hint_text("closure.lock"));
+        });
+        let path = self.lockfile_path.trim();
+        let path = (!path.is_empty()).then(|| PathBuf::from(path));
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(path.is_some(), egui::Button::new("Load"))
+                .on_hover_text("Check the closure against the pname-versions listed in the file.")
+                .clicked()
+                && let Some(path) = path.clone()
+            {
+                self.load_lockfile(path);
+            }
+            if ui
+                .add_enabled(path.is_some(), egui::Button::new("Lock current closure"))
+                .on_hover_text("Write every pname-version in the closure to the file.")
+                .clicked()
+                && let Some(path) = path.clone()
+            {
+                self.write_lockfile(path);
+            }
+        });
+        if let Some(status) = &self.lockfile_status {
+            ui.weak(status.as_str());
+        }
+
+        if self.lock.is_none() {
+            ui.weak("Load a lockfile to review unexpected additions and version drifts.");
+            return;
+        }
+        ui.separator();
+        if self.lock_deviations.is_empty() {
+            ui.label("The closure matches the lockfile.");
+            return;
+        }
+
+        let count = |kind: fn(&LockDeviation) -> bool| {
+            self.lock_deviations
+                .iter()
+                .filter(|deviation| kind(deviation))
+                .count()
+        };
+        ui.label(format!(
+            "{} unexpected, {} drifted, {} missing",
+            count(|deviation| matches!(deviation, LockDeviation::Unexpected { .. })),
+            count(|deviation| matches!(deviation, LockDeviation::Drift { .. })),
+            count(|deviation| matches!(deviation, LockDeviation::Missing { .. }))
+        ));
+
+        let mut clicked = None;
+        let row_height = ui.text_style_height(&egui::TextStyle::Body) + 4.0;
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, true])
+            .show_rows(ui, row_height, self.lock_deviations.len(), |ui, range| {
+                for deviation in &self.lock_deviations[range] {
+                    let marker = match deviation {
+                        LockDeviation::Unexpected { .. } => "+",
+                        LockDeviation::Drift { .. } => "~",
+                        LockDeviation::Missing { .. } => "-",
+                    };
+                    let text = format!("{marker} {}", deviation.summary());
+                    match deviation.id() {
+                        Some(id) => {
+                            if ui
+                                .selectable_label(self.selected.as_deref() == Some(id), text)
+                                .clicked()
+                            {
+                                clicked = Some(id.to_owned());
+                            }
+                        }
+                        None => {
+                            ui.label(text);
+                        }
+                    }
+                }
+            });
+
+        if let Some(id) = clicked {
+            self.reveal_node(&id);
+        }
+    }
+}
@@ -1,4 +1,29 @@
+mod annotations;
+mod attributes;
+mod attribution;
+mod bookmarks;
+mod classes;
+mod contents;
+mod context_menu;
 mod controls;
+mod cycles;
 mod details;
+mod diff;
+mod duplicates;
+mod edge_details;
+mod export;
+mod findings;
 mod fps;
+mod gc_roots;
+mod generations;
+mod keymap;
+mod lockfile;
+mod name_filter;
 mod panels;
+mod scenario;
+mod search;
+mod selection_sets;
+mod status_bar;
+mod stores;
+mod top_bar;
+mod verify;
@@ -0,0 +1,83 @@
+use eframe::egui::{self, RichText, Ui};
+
+use crate::name_filter::NameFilter;
+
+use super::super::ViewModel;
+
+impl ViewModel {
+    fn set_name_filter_expression(&mut self, expression: String) -> bool {
+        self.name_filter_expression = expression;
+        match NameFilter::parse(&self.name_filter_expression) {
+            Ok(filter) => {
+                self.name_filter = filter;
+                self.name_filter_error = None;
+                true
+            }
+            Err(error) => {
+                self.name_filter_error = Some(format!("{error:#}"));
+                false
+            }
+        }
+    }
+
+    /// The name filter box and a chip per active term; returns whether the
+    /// rendered node set changed.
+    pub(in crate::app) fn draw_name_filter(&mut self, ui: &mut Ui) -> bool {
+        ui.label("Name filter").on_hover_text(
+            "Keep only paths matching a glob like python3*, a regex like re:^gtk[34]- or a \
+             store path prefix; prefix a term with ! to hide what it matches instead. \
+             Applied before the size threshold.",
+        );
+        let mut expression = self.name_filter_expression.clone();
+        let response = ui.add(
+            egui::TextEdit::singleline(&mut expression)
+                .hint_text("python3*, !*-doc")
+                .desired_width(f32::INFINITY),
+        );
+        let mut changed = false;
+        if response.changed() {
+            changed |= self.set_name_filter_expression(expression);
+        }
+        if let Some(error) = &self.name_filter_error {
+            ui.colored_label(ui.visuals().error_fg_color, error.as_str());
+        }
+
+        let mut removed = None;
+        if !self.name_filter.is_empty() {
+            ui.horizontal_wrapped(|ui| {
+                for (index, rule) in self.name_filter.rules.iter().enumerate() {
+                    let chip = if rule.exclude {
+                        RichText::new(format!("{} \u{00d7}", rule.text))
+                            .color(ui.visuals().warn_fg_color)
+                    } else {
+                        RichText::new(format!("{} \u{00d7}", rule.text))
+                    };
+                    if ui
+                        .small_button(chip)
+                        .on_hover_text(if rule.exclude {
+                            "Hides matching paths; click to remove."
+                        } else {
+                            "Keeps matching paths; click to remove."
+                        })
+                        .clicked()
+                    {
+                        removed = Some(index);
+                    }
+                }
+            });
+        }
+        if let Some(index) = removed {
+            let expression = self
+                .name_filter
+                .rules
+                .iter()
+                .enumerate()
+                .filter(|&(other, _)| other != index)
+                .map(|(_, rule)| rule.text.as_str())
+                .collect::<Vec<_>>()
+                .join(" ");
+            changed |= self.set_name_filter_expression(expression);
+        }
+        changed
+    }
+}
@@ -1,12 +1,29 @@
-use std::collections::VecDeque;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::sync::Arc;
 
-use eframe::egui::{self, vec2, Align, Context, Layout, Vec2};
+use eframe::egui::{self, vec2, Context, Vec2};
 
-use crate::nix::{SizeMetric, SystemGraph};
-use crate::util::{short_name, stable_pair};
+use crate::export::ExportFormat;
+use crate::findings::collect_findings;
+use crate::name_filter::NameFilter;
+use crate::nix::{GraphDelta, NixSettings, NodeClasses, ShardPreview, SizeMetric, SystemGraph};
+use crate::scenario::Scenario;
+use crate::util::{parse_store_name, short_name, stable_pair};
 
+use super::super::annotations::{AnnotationRole, load_annotations};
+use super::super::bookmarks::{load_bookmarks, load_node_bookmarks};
+use super::super::glyphs::GlyphAtlas;
+use super::super::image_export::{ImageExtent, ImageFormat};
+use super::super::layouts::find_layout;
+use super::super::navigation::NavigationHistory;
+use super::super::pins::load_pins;
 use super::super::render_utils::node_radius;
-use super::super::{DependencyRankingMode, RenderNode, SizeRankingMode, ViewModel};
+use super::super::selection_sets::load_selection_sets;
+use super::super::toasts::ToastLevel;
+use super::super::{
+    DependencyRankingMode, DiffFilter, EdgeStyle, FrameRateCap, GraphLayout, QuadtreeShading,
+    RenderNode, SizeRankingMode, StoreHashes, ViewModel,
+};
 
 impl ViewModel {
     pub(in crate::app) const INITIAL_RANKING_ROWS: usize = 20;
@@ -15,24 +32,82 @@ impl ViewModel {
     pub(in crate::app) const INITIAL_RELATED_ROWS: usize = 24;
     pub(in crate::app) const RELATED_PAGE_ROWS: usize = 24;
     pub(in crate::app) const RELATED_PREFETCH_MARGIN: usize = 4;
+    pub(in crate::app) const ROOT_PATH_LIMIT: usize = 32;
 
-    pub(in crate::app) fn new(graph: SystemGraph) -> Self {
+    pub(in crate::app) fn new(graph: SystemGraph, scenario: Option<Arc<Scenario>>) -> Self {
         let ranking_limit = graph.node_count();
         let nar_ranking = graph.ranked_by_metric(SizeMetric::NarSize, ranking_limit);
         let closure_ranking = graph.ranked_by_metric(SizeMetric::ClosureSize, ranking_limit);
+        let closure_count_ranking = graph.ranked_by_metric(SizeMetric::ClosureCount, ranking_limit);
+        let retained_ranking = graph.ranked_by_metric(SizeMetric::RetainedSize, ranking_limit);
+        let unique_root_rankings = (0..graph.roots.len())
+            .map(|index| graph.unique_to_root(index))
+            .collect();
         let dependency_ranking = graph.ranked_by_dependencies(ranking_limit);
         let reverse_dependency_ranking = graph.ranked_by_referrers(ranking_limit);
+        let scenario_report = scenario.as_ref().map(|scenario| scenario.evaluate(&graph));
+        let findings = collect_findings(&graph);
+        let duplicates = graph.duplicate_packages();
+        let store_dirs = Self::store_dir_counts(&graph);
+        let cycles = graph.reference_cycles();
+        let cycle_by_id = Self::cycle_index(&cycles);
+        let annotations = load_annotations(parse_store_name(short_name(&graph.root_id)).pname);
+        let saved_layout = find_layout(&graph.nodes.keys().map(|id| short_name(id)).collect());
+        let lock = scenario.as_ref().and_then(|scenario| scenario.lock.clone());
+        let lockfile_path = scenario
+            .as_ref()
+            .and_then(|scenario| {
+                scenario
+                    .lockfile
+                    .as_ref()
+                    .map(|path| scenario.resolve(path))
+            })
+            .map(|path| path.display().to_string())
+            .unwrap_or_default();
+        let lock_deviations = lock
+            .as_ref()
+            .map(|lock| lock.check(&graph))
+            .unwrap_or_default();
 
         Self {
             selected: None,
+            watch_store: false,
+            watch_profile: true,
+            auto_reload_profile: false,
+            profile_switch: None,
+            nix_settings: NixSettings::default(),
+            validate_graph: cfg!(debug_assertions),
+            graph_validated: false,
+            pending_toasts: Vec::new(),
+            size_history: Arc::default(),
+            is_workspace: false,
+            workspace_request: None,
+            root_input: String::new(),
+            root_request: None,
+            root_request_compare: None,
+            store_update_status: None,
+            selected_edge: None,
+            reference_scanner_enabled: false,
             max_nodes: 450,
             graph,
+            shared_graph: None,
+            scenario,
+            scenario_report,
+            findings,
+            duplicates,
+            cycles,
+            cycle_by_id,
+            condense_cycles: false,
             metric: SizeMetric::NarSize,
             min_size_mb: 64.0,
             search: String::new(),
             pan: Vec2::ZERO,
             zoom: 1.0,
             live_physics: true,
+            frame_rate_cap: FrameRateCap::Fps60,
+            idle_throttle: true,
+            pause_when_unfocused: true,
+            background_paused: false,
             physics_intensity: 1.0,
             physics_repulsion: 2.6,
             physics_spring: 0.2,
@@ -40,21 +115,141 @@ impl ViewModel {
             physics_velocity_damping: 0.9,
             physics_target_spread: 2.0,
             physics_spread_force: 0.08,
+            parallel_physics: true,
             show_quadtree_overlay: false,
+            quadtree_shading: QuadtreeShading::Occupancy,
+            show_physics_heat: false,
+            measure_mode: false,
+            measure_rect: None,
+            annotations,
+            annotation_tool: None,
+            annotation_role: AnnotationRole::Note,
+            annotation_text: String::new(),
+            annotation_draft: None,
+            show_annotations: true,
+            annotations_status: None,
+            canvas_rect: None,
+            show_key_help: false,
+            focus_search: false,
+            store_hashes: StoreHashes::default(),
+            show_controls_panel: true,
+            show_details_panel: true,
+            controls_panel_width: 350.0,
+            details_panel_width: 360.0,
+            show_findings_window: false,
+            show_diff_window: false,
+            show_lock_window: false,
+            show_attribution_window: false,
+            show_generations_window: false,
+            generations: None,
+            lockfile_path,
+            lock,
+            lock_deviations,
+            lockfile_status: None,
+            compare_path: String::new(),
+            comparison: None,
+            diff_filter: DiffFilter::All,
+            highlight_diff: true,
+            gc_roots: None,
+            gc_sole_only: false,
+            color_by_root: true,
+            color_by_dominator_share: false,
+            unique_root_tab: 0,
+            unique_root_rankings,
+            show_legend_overlay: false,
+            publication_mode: false,
+            label_pname_only: false,
+            show_node_glyphs: true,
+            cluster_at_low_zoom: true,
+            node_glyphs: GlyphAtlas::spawn(),
+            edge_style: EdgeStyle::Straight,
+            show_edge_arrows: true,
+            gpu_rendering: true,
+            graph_layout: GraphLayout::Force,
+            freeze_layout: false,
+            bridge_hidden: false,
+            impact_pulse_enabled: true,
+            impact_pulse: None,
+            pinned_positions: load_pins(),
+            static_nodes: HashSet::new(),
+            dragged_node: None,
+            pins_status: None,
+            remember_layout: true,
+            saved_layout,
+            layout_status: None,
+            layout_file_path: String::new(),
+            marked: BTreeSet::new(),
+            hidden_nodes: BTreeSet::new(),
+            hidden_savings: None,
+            removal_simulation: None,
+            attribution: None,
+            store_dirs,
+            hidden_stores: BTreeSet::new(),
+            explore_mode: false,
+            explore_anchor: None,
+            expanded_nodes: BTreeSet::new(),
+            selection_sets: load_selection_sets(),
+            navigation: NavigationHistory::default(),
+            selection_set_name: String::new(),
+            selection_sets_status: None,
+            bookmarks: load_bookmarks(),
+            bookmark_name: String::new(),
+            bookmark_filters: false,
+            bookmarks_status: None,
+            node_bookmarks: load_node_bookmarks(),
+            node_bookmarks_status: None,
+            set_summaries: HashMap::new(),
+            set_comparison_names: (String::new(), String::new()),
+            set_comparison: None,
+            neighborhood_hops: 1,
+            neighborhood_format: ExportFormat::Dot,
+            neighborhood_export_path: String::new(),
+            neighborhood_export_status: None,
+            graph_export_format: ExportFormat::GraphMl,
+            graph_export_path: String::new(),
+            image_export_format: ImageFormat::Png,
+            image_export_extent: ImageExtent::View,
+            image_export_scale: 2.0,
+            image_export_path: String::new(),
+            image_export_request: None,
+            badge_classes: NodeClasses::all(),
+            class_include: NodeClasses::NONE,
+            class_exclude: NodeClasses::NONE,
+            ignore_patterns: String::new(),
+            ignore_terms: Vec::new(),
+            effective_totals: None,
+            name_filter_expression: String::new(),
+            name_filter: NameFilter::default(),
+            name_filter_error: None,
             graph_dirty: true,
             render_graph_revision: 0,
             graph_cache: None,
+            camera_animation: None,
             search_match_cache: None,
+            search_results: None,
             details_panel_cache: None,
+            edge_details_cache: None,
+            edge_reference_scan: None,
+            verifications: HashMap::new(),
+            attribute_lookups: HashMap::new(),
+            content_scans: HashMap::new(),
+            path_previews: HashMap::new(),
+            hover_card: None,
+            context_node: None,
+            node_report: None,
             nar_ranking,
             closure_ranking,
+            closure_count_ranking,
+            retained_ranking,
             dependency_ranking,
             reverse_dependency_ranking,
             nar_rows_visible: Self::INITIAL_RANKING_ROWS,
             closure_rows_visible: Self::INITIAL_RANKING_ROWS,
+            closure_count_rows_visible: Self::INITIAL_RANKING_ROWS,
+            retained_rows_visible: Self::INITIAL_RANKING_ROWS,
             dependency_rows_visible: Self::INITIAL_RANKING_ROWS,
             referrer_rows_visible: Self::INITIAL_RANKING_ROWS,
-            size_ranking_mode: SizeRankingMode::NarSize,
+            size_ranking_mode: SizeRankingMode::Nar,
             dependency_ranking_mode: DependencyRankingMode::Dependencies,
             related_rows_visible: Self::INITIAL_RELATED_ROWS,
             show_fps_bar: true,
@@ -77,58 +272,102 @@ impl ViewModel {
         reload_requested: &mut bool,
         is_loading: bool,
     ) {
+        let keyboard_free = !ctx.wants_keyboard_input();
         self.update_fps_counter(ctx);
+        if self.poll_verifications()
+            | self.poll_attribute_lookups()
+            | self.poll_content_scans()
+            | self.poll_path_previews()
+            | self.poll_node_report()
+            | self.poll_comparison()
+            | self.poll_gc_roots()
+            | self.poll_generations()
+            | self.poll_removal_simulation()
+        {
+            ctx.request_repaint_after(std::time::Duration::from_millis(200));
+        }
         if self.graph_dirty {
             self.rebuild_render_graph();
         }
 
-        egui::TopBottomPanel::top("top_bar")
-            .resizable(false)
-            .show(ctx, |ui| {
-                ui.horizontal(|ui| {
-                    ui.heading("nix-analisá");
-                    ui.separator();
-                    ui.label(format!("root: {}", short_name(&self.graph.root_id)));
-                    ui.label(format!("store: {}", self.graph.store_dir));
-                    ui.label(format!("system path: {system_path}"));
-                    ui.label(format!("nodes: {}", self.graph.node_count()));
-                    ui.label(format!("edges: {}", self.graph.edge_count));
-                    ui.separator();
-                    let reload_button =
-                        ui.add_enabled(!is_loading, egui::Button::new("Reload closure"));
-                    if reload_button.clicked() {
-                        *reload_requested = true;
-                    }
-                    if ui.button("Rebuild graph").clicked() {
-                        self.graph_cache = None;
-                        self.graph_dirty = true;
-                    }
-                    ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
-                        let visible_graph_text = self.visible_graph_text();
-                        let fps_text = self.fps_display_text();
-
-                        if let Some(visible_graph_text) = visible_graph_text.as_ref() {
-                            ui.label(visible_graph_text);
-                        }
-                        if visible_graph_text.is_some() && fps_text.is_some() {
-                            ui.separator();
-                        }
-                        if let Some(fps_text) = fps_text.as_ref() {
-                            ui.label(fps_text);
-                        }
-                    });
-                });
-            });
+        self.draw_top_bar(ctx, system_path, reload_requested, is_loading);
 
-        egui::SidePanel::left("controls")
+        if let Some(panel) = egui::SidePanel::left("controls")
             .resizable(true)
-            .default_width(350.0)
-            .show(ctx, |ui| self.draw_controls(ui));
+            .default_width(self.controls_panel_width)
+            .show_animated(ctx, self.show_controls_panel, |ui| self.draw_controls(ui))
+        {
+            self.controls_panel_width = panel.response.rect.width();
+        }
 
-        egui::SidePanel::right("details")
+        if let Some(panel) = egui::SidePanel::right("details")
             .resizable(true)
-            .default_width(360.0)
-            .show(ctx, |ui| self.draw_details(ui));
+            .default_width(self.details_panel_width)
+            .show_animated(ctx, self.show_details_panel, |ui| self.draw_details(ui))
+        {
+            self.details_panel_width = panel.response.rect.width();
+        }
+
+        let mut show_findings_window = self.show_findings_window;
+        egui::Window::new("Findings")
+            .open(&mut show_findings_window)
+            .default_width(420.0)
+            .vscroll(true)
+            .show(ctx, |ui| self.draw_findings(ui));
+        self.show_findings_window = show_findings_window;
+
+        let mut show_diff_window = self.show_diff_window;
+        egui::Window::new("Closure diff")
+            .open(&mut show_diff_window)
+            .default_width(440.0)
+            .default_height(480.0)
+            .show(ctx, |ui| self.draw_diff_window(ui));
+        self.show_diff_window = show_diff_window;
+
+        let mut show_lock_window = self.show_lock_window;
+        egui::Window::new("Closure lockfile")
+            .open(&mut show_lock_window)
+            .default_width(440.0)
+            .default_height(480.0)
+            .show(ctx, |ui| self.draw_lock_window(ui));
+        self.show_lock_window = show_lock_window;
+
+        let mut show_attribution_window = self.show_attribution_window;
+        egui::Window::new("Top-level attribution")
+            .open(&mut show_attribution_window)
+            .default_width(560.0)
+            .default_height(480.0)
+            .show(ctx, |ui| self.draw_attribution_window(ui));
+        self.show_attribution_window = show_attribution_window;
+
+        let mut show_generations_window = self.show_generations_window;
+        egui::Window::new("System generations")
+            .open(&mut show_generations_window)
+            .default_width(560.0)
+            .default_height(420.0)
+            .show(ctx, |ui| self.draw_generations_window(ui));
+        self.show_generations_window = show_generations_window;
+
+        if let Some(title) = self.node_report.as_ref().map(|report| report.title.clone()) {
+            let mut open = true;
+            egui::Window::new(title)
+                .id(egui::Id::new("node_report"))
+                .open(&mut open)
+                .default_width(520.0)
+                .default_height(360.0)
+                .show(ctx, |ui| self.draw_node_report(ui));
+            if !open {
+                self.node_report = None;
+            }
+        }
+
+        let mut show_key_help = self.show_key_help;
+        egui::Window::new("Keyboard shortcuts")
+            .open(&mut show_key_help)
+            .resizable(false)
+            .collapsible(false)
+            .show(ctx, |ui| self.draw_key_help(ui));
+        self.show_key_help = show_key_help;
 
         egui::CentralPanel::default().show(ctx, |ui| {
             if is_loading {
@@ -142,9 +381,149 @@ impl ViewModel {
                 self.draw_graph(ui);
             }
         });
+
+        self.handle_shortcuts(ctx, keyboard_free, is_loading, reload_requested);
+    }
+
+    /// Merges the paths that entered and left the closure into the graph.
+    pub(in crate::app) fn apply_store_delta(&mut self, delta: GraphDelta) {
+        self.store_update_status = Some(format!(
+            "store update: +{} / -{} paths",
+            delta.added.len(),
+            delta.removed.len()
+        ));
+        if delta.is_empty() && delta.root_id == self.graph.root_id {
+            return;
+        }
+
+        self.graph.apply_delta(delta);
+        self.shared_graph = None;
+        self.refresh_graph_views();
+    }
+
+    /// Adds the shards of a whole-store scan that is still running. The views
+    /// over the graph catch up on the next `refresh_graph_views`.
+    pub(in crate::app) fn merge_store_shards(
+        &mut self,
+        preview: &mut ShardPreview,
+        shards: Vec<GraphDelta>,
+    ) {
+        let added = shards.iter().map(|shard| shard.added.len()).sum::<usize>();
+        for shard in shards {
+            preview.merge(&mut self.graph, shard);
+        }
+        self.shared_graph = None;
+        self.store_update_status = Some(format!("store update: +{added} / -0 paths"));
+    }
+
+    /// Recomputes everything derived from the graph after it changed, and
+    /// drops selections of paths that are gone.
+    pub(in crate::app) fn refresh_graph_views(&mut self) {
+        let ranking_limit = self.graph.node_count();
+        self.nar_ranking = self
+            .graph
+            .ranked_by_metric(SizeMetric::NarSize, ranking_limit);
+        self.closure_ranking = self
+            .graph
+            .ranked_by_metric(SizeMetric::ClosureSize, ranking_limit);
+        self.closure_count_ranking = self
+            .graph
+            .ranked_by_metric(SizeMetric::ClosureCount, ranking_limit);
+        self.retained_ranking = self
+            .graph
+            .ranked_by_metric(SizeMetric::RetainedSize, ranking_limit);
+        self.unique_root_rankings = (0..self.graph.roots.len())
+            .map(|index| self.graph.unique_to_root(index))
+            .collect();
+        self.dependency_ranking = self.graph.ranked_by_dependencies(ranking_limit);
+        self.reverse_dependency_ranking = self.graph.ranked_by_referrers(ranking_limit);
+        let previous_violations = self
+            .scenario_report
+            .take()
+            .map(|report| report.violations)
+            .unwrap_or_default();
+        self.scenario_report = self
+            .scenario
+            .as_ref()
+            .map(|scenario| scenario.evaluate(&self.graph));
+        let newly_exceeded = self
+            .scenario_report
+            .iter()
+            .flat_map(|report| &report.violations)
+            .filter(|violation| !previous_violations.contains(violation))
+            .cloned()
+            .collect::<Vec<_>>();
+        if let Some(violation) = newly_exceeded.first() {
+            let more = match newly_exceeded.len() {
+                1 => String::new(),
+                count => format!(" (and {} more)", count - 1),
+            };
+            self.notify(
+                ToastLevel::Warning,
+                format!("Scenario budget exceeded: {violation}{more}"),
+            );
+        }
+        self.findings = collect_findings(&self.graph);
+        self.duplicates = self.graph.duplicate_packages();
+        self.cycles = self.graph.reference_cycles();
+        self.cycle_by_id = Self::cycle_index(&self.cycles);
+        if let Some(lock) = &self.lock {
+            self.lock_deviations = lock.check(&self.graph);
+        }
+
+        if self
+            .selected
+            .as_ref()
+            .is_some_and(|id| !self.graph.nodes.contains_key(id))
+        {
+            self.selected = None;
+        }
+        if self.selected_edge.as_ref().is_some_and(|(source, target)| {
+            !self.graph.nodes.contains_key(source) || !self.graph.nodes.contains_key(target)
+        }) {
+            self.selected_edge = None;
+        }
+        self.marked.retain(|id| self.graph.nodes.contains_key(id));
+        self.hidden_nodes
+            .retain(|id| self.graph.nodes.contains_key(id));
+        self.hidden_savings = None;
+        self.removal_simulation = None;
+        self.attribution = None;
+        self.effective_totals = None;
+        self.store_dirs = Self::store_dir_counts(&self.graph);
+        self.hidden_stores
+            .retain(|store| self.store_dirs.iter().any(|(dir, _)| dir == store));
+        self.expanded_nodes
+            .retain(|id| self.graph.nodes.contains_key(id));
+
+        self.graph_validated = false;
+        if self.comparison.is_some() {
+            let path = self.compare_path.clone();
+            self.start_comparison(&path);
+        }
+        if self.gc_roots.is_some() {
+            self.start_gc_root_scan();
+        }
+        self.details_panel_cache = None;
+        self.edge_details_cache = None;
+        self.search_results = None;
+        self.set_summaries.clear();
+        self.set_comparison = None;
+        self.graph_dirty = true;
     }
 
     pub(in crate::app) fn set_selected(&mut self, selected: Option<String>) {
+        if self.selected != selected {
+            let left = self.navigation_entry();
+            self.navigation.record(left);
+        }
+        self.replace_selection(selected);
+    }
+
+    /// Changes the selection without recording it in the navigation history.
+    pub(in crate::app) fn replace_selection(&mut self, selected: Option<String>) {
+        self.selected_edge = None;
+
         let changed = self.selected != selected;
         if !changed {
             return;
@@ -152,6 +531,18 @@ impl ViewModel {
 
         self.selected = selected;
         self.related_rows_visible = Self::INITIAL_RELATED_ROWS;
+        self.follow_selection_layout();
+    }
+
+    pub(in crate::app) fn set_selected_edge(&mut self, edge: Option<(String, String)>) {
+        if self.selected_edge == edge {
+            return;
+        }
+
+        if edge.is_some() {
+            self.selected = None;
+        }
+        self.selected_edge = edge;
     }
 
     pub(in crate::app) fn include_node_in_current_graph(&mut self, node_id: &str) {
@@ -181,12 +572,20 @@ impl ViewModel {
         let initial_speed = 1.15 + (base_radius * 0.022);
         let new_index = cache.nodes.len();
 
+        let pinned_position = self.pinned_positions.get(short_name(node_id)).copied();
+
         cache.nodes.push(RenderNode {
             id: node_id.to_owned(),
-            world_pos: Vec2::ZERO,
-            velocity: direction * initial_speed,
+            world_pos: pinned_position.unwrap_or(Vec2::ZERO),
+            velocity: if pinned_position.is_some() {
+                Vec2::ZERO
+            } else {
+                direction * initial_speed
+            },
             metric_value,
             base_radius,
+            pinned: pinned_position.is_some(),
+            layout_static: false,
         });
         cache.index_by_id.insert(node_id.to_owned(), new_index);
         cache.outgoing.push(Vec::new());
@@ -196,6 +595,7 @@ impl ViewModel {
             cache.root_index = Some(new_index);
         }
 
+        let graph = &self.graph;
         let mut add_edge = |source: usize, target: usize| {
             if source == target {
                 return;
@@ -208,7 +608,10 @@ impl ViewModel {
                 return;
             }
 
+            let weight = graph.edge_weight(&cache.nodes[source].id, &cache.nodes[target].id);
             cache.edges.push((source, target));
+            cache.edge_weights.push(weight);
+            cache.max_edge_weight = cache.max_edge_weight.max(weight);
             cache.outgoing[source].push(target);
             cache.incoming[target].push(source);
         };
@@ -243,5 +646,9 @@ impl ViewModel {
 
         self.visible_node_count = cache.nodes.len();
         self.visible_edge_count = cache.edges.len();
+        if self.static_nodes.contains(node_id) {
+            self.apply_static_layout();
+        }
+        self.check_graph_integrity("including a node");
     }
 }
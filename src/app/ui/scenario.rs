@@ -0,0 +1,89 @@
+use eframe::egui::{self, RichText, Ui};
+
+use crate::format::format_bytes;
+
+use super::super::{LabelPanel, ViewModel};
+
+impl ViewModel {
+    pub(in crate::app) fn draw_scenario_section(&mut self, ui: &mut Ui) {
+        let Some(scenario) = self.scenario.clone() else {
+            return;
+        };
+        let Some(report) = &self.scenario_report else {
+            return;
+        };
+
+        let mut selected_id = None;
+
+        egui::CollapsingHeader::new("Scenario")
+            .default_open(true)
+            .show(ui, |ui| {
+                ui.label(format!(
+                    "Closure: {} across {} paths",
+                    format_bytes(report.total_bytes),
+                    report.path_count
+                ));
+                if !scenario.ignore.is_empty() {
+                    ui.label(format!("Ignoring: {}", scenario.ignore.join(", ")));
+                }
+
+                for category in &report.categories {
+                    let text = format!(
+                        "{}: {} of {}",
+                        category.name,
+                        format_bytes(category.bytes),
+                        format_bytes(category.budget_bytes)
+                    );
+                    if category.bytes > category.budget_bytes {
+                        ui.colored_label(ui.visuals().warn_fg_color, text);
+                    } else {
+                        ui.label(text);
+                    }
+                }
+
+                if report.violations.is_empty() {
+                    ui.label("All budgets satisfied.");
+                } else {
+                    ui.label(RichText::new("Budgets exceeded").strong());
+                    for violation in &report.violations {
+                        ui.label(format!("- {violation}"));
+                    }
+                }
+
+                if !scenario.selections.is_empty() {
+                    ui.separator();
+                    ui.label(RichText::new("Selections").strong());
+                }
+
+                for name in scenario.selections.keys() {
+                    let ids = scenario.selection_ids(&self.graph, name);
+                    let bytes = ids
+                        .iter()
+                        .filter_map(|id| self.graph.nodes.get(id))
+                        .map(|node| node.nar_size)
+                        .sum::<u64>();
+
+                    ui.collapsing(
+                        format!("{name} ({} paths, {})", ids.len(), format_bytes(bytes)),
+                        |ui| {
+                            for id in &ids {
+                                if ui
+                                    .link(self.path_label(LabelPanel::Controls, id))
+                                    .on_hover_text(id.as_str())
+                                    .clicked()
+                                {
+                                    selected_id = Some(id.clone());
+                                }
+                            }
+                        },
+                    );
+                }
+            });
+
+        if let Some(id) = selected_id {
+            self.reveal_node(&id);
+        }
+
+        ui.separator();
+    }
+}
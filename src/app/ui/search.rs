@@ -0,0 +1,93 @@
+use eframe::egui::{self, Align, Layout, Ui};
+
+use crate::format::format_count;
+
+use super::super::{LabelPanel, SearchResultsCache, ViewModel};
+
+impl ViewModel {
+    /// Every path matching the search, largest by the size metric first.
+    fn search_results(&mut self) -> &[String] {
+        let query = self.search.trim();
+        let stale = self
+            .search_results
+            .as_ref()
+            .is_none_or(|cache| cache.query != query || cache.metric != self.metric);
+        if stale {
+            let mut ids = self.search_matching_ids();
+            ids.sort_by(|a, b| {
+                let metric = |id: &String| self.graph.nodes[id].metric(self.metric);
+                metric(b).cmp(&metric(a)).then_with(|| a.cmp(b))
+            });
+            self.search_results = Some(SearchResultsCache {
+                query: query.to_owned(),
+                metric: self.metric,
+                ids,
+            });
+        }
+        self.search_results
+            .as_ref()
+            .map(|cache| cache.ids.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// The matches of a non-empty search under the search box; `submitted`
+    /// jumps to the first one.
+    pub(in crate::app) fn draw_search_results(&mut self, ui: &mut Ui, submitted: bool) {
+        if self.search.trim().is_empty() {
+            return;
+        }
+
+        let metric = self.metric;
+        let ids = self.search_results().to_vec();
+        let drawn = self.graph_cache.as_ref().map_or(0, |cache| {
+            ids.iter()
+                .filter(|id| cache.index_by_id.contains_key(id.as_str()))
+                .count()
+        });
+        if ids.is_empty() {
+            ui.weak("No paths match.");
+            return;
+        }
+        ui.weak(format!(
+            "{} matches, {} drawn; Enter jumps to the first.",
+            format_count(ids.len() as u64),
+            format_count(drawn as u64)
+        ));
+
+        let mut jump_to = submitted.then(|| ids[0].clone());
+        egui::ScrollArea::vertical()
+            .id_salt("search_results_scroll")
+            .max_height(160.0)
+            .auto_shrink([false, true])
+            .show_rows(ui, 22.0, ids.len(), |ui, row_range| {
+                for id in &ids[row_range] {
+                    let Some(node) = self.graph.nodes.get(id) else {
+                        continue;
+                    };
+                    let is_selected = self.selected.as_deref() == Some(id.as_str());
+                    let value_label = Self::format_metric_value(metric, node.metric(metric));
+                    let clicked = ui
+                        .horizontal(|ui| {
+                            let label = ui.selectable_label(
+                                is_selected,
+                                self.path_label(LabelPanel::Controls, id),
+                            );
+                            label.context_menu(|ui| self.node_context_menu(ui, id));
+                            let clicked = label.clicked();
+                            ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                                ui.label(value_label);
+                            });
+                            clicked
+                        })
+                        .inner;
+                    if clicked {
+                        jump_to = Some(id.clone());
+                    }
+                }
+            });
+
+        if let Some(id) = jump_to {
+            self.reveal_node(&id);
+        }
+    }
+}
@@ -0,0 +1,300 @@
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use eframe::egui::{self, RichText, Ui};
+
+use crate::export::export_nodes;
+use crate::format::{format_bytes, format_count};
+use crate::util::short_name;
+
+use super::super::selection_sets::save_selection_sets;
+use super::super::toasts::ToastLevel;
+use super::super::{SetComparison, SetSummary, ViewModel};
+
+impl ViewModel {
+    pub(in crate::app) fn toggle_marked(&mut self, id: &str) {
+        if !self.marked.remove(id) {
+            self.marked.insert(id.to_owned());
+        }
+    }
+
+    /// The ids in the loaded graph whose store names belong to a saved set.
+    fn resolve_selection_set(&self, name: &str) -> Vec<String> {
+        let Some(members) = self.selection_sets.get(name) else {
+            return Vec::new();
+        };
+        self.graph
+            .nodes
+            .keys()
+            .filter(|id| members.contains(short_name(id)))
+            .cloned()
+            .collect()
+    }
+
+    fn summarize(&self, ids: &[String], member_count: usize) -> SetSummary {
+        let closure = self.graph.closure_ids(ids.iter().map(String::as_str));
+        SetSummary {
+            present: ids.len(),
+            missing: member_count.saturating_sub(ids.len()),
+            nar_size: ids.iter().map(|id| self.graph.nodes[id].nar_size).sum(),
+            closure_paths: closure.len(),
+            closure_size: closure
+                .iter()
+                .map(|id| self.graph.nodes[*id].nar_size)
+                .sum(),
+        }
+    }
+
+    fn selection_set_summary(&mut self, name: &str) -> Option<SetSummary> {
+        if let Some(summary) = self.set_summaries.get(name) {
+            return Some(*summary);
+        }
+        let member_count = self.selection_sets.get(name)?.len();
+        let summary = self.summarize(&self.resolve_selection_set(name), member_count);
+        self.set_summaries.insert(name.to_owned(), summary);
+        Some(summary)
+    }
+
+    fn compare_selection_sets(&self, first: &str, second: &str) -> SetComparison {
+        let first_ids = self.resolve_selection_set(first);
+        let second_ids = self.resolve_selection_set(second);
+        let first_closure = self.graph.closure_ids(first_ids.iter().map(String::as_str));
+        let second_closure = self
+            .graph
+            .closure_ids(second_ids.iter().map(String::as_str));
+
+        let tally = |ids: &mut dyn Iterator<Item = &&str>| {
+            ids.fold((0, 0), |(count, bytes), id| {
+                (count + 1, bytes + self.graph.nodes[*id].nar_size)
+            })
+        };
+        SetComparison {
+            names: (first.to_owned(), second.to_owned()),
+            only_first: tally(&mut first_closure.difference(&second_closure)),
+            only_second: tally(&mut second_closure.difference(&first_closure)),
+            shared: tally(&mut first_closure.intersection(&second_closure)),
+        }
+    }
+
+    fn persist_selection_sets(&mut self) {
+        self.set_summaries.clear();
+        self.set_comparison = None;
+        self.selection_sets_status = save_selection_sets(&self.selection_sets)
+            .err()
+            .map(|error| format!("Failed to save selection sets: {error:#}"));
+    }
+
+    pub(in crate::app) fn marked_summary(&self) -> String {
+        let nar_size = self
+            .marked
+            .iter()
+            .filter_map(|id| self.graph.nodes.get(id))
+            .map(|node| node.nar_size)
+            .sum::<u64>();
+        format!(
+            "{} marked, {} own",
+            format_count(self.marked.len() as u64),
+            format_bytes(nar_size)
+        )
+    }
+
+    fn pin_marked(&mut self) {
+        let Some(cache) = &self.graph_cache else {
+            return;
+        };
+
+        for id in &self.marked {
+            if let Some(&index) = cache.index_by_id.get(id) {
+                self.pinned_positions
+                    .insert(short_name(id).to_owned(), cache.nodes[index].world_pos);
+            }
+        }
+        self.apply_pins();
+        self.persist_pins();
+    }
+
+    fn export_marked(&mut self) {
+        let path = PathBuf::from(format!("marked.{}", self.graph_export_format.extension()));
+        let ids = self.marked.iter().cloned().collect::<Vec<_>>();
+        match export_nodes(&self.graph, &ids, self.graph_export_format, &path) {
+            Ok(()) => self.notify(
+                ToastLevel::Info,
+                format!(
+                    "Wrote {} paths to {}",
+                    format_count(ids.len() as u64),
+                    path.display()
+                ),
+            ),
+            Err(error) => self.notify(ToastLevel::Error, format!("Export failed: {error:#}")),
+        }
+    }
+
+    pub(in crate::app) fn draw_selection_sets(&mut self, ui: &mut Ui) {
+        ui.weak("Ctrl+click nodes to add them to the multi-selection.");
+
+        if self.marked.is_empty() {
+            ui.label("Nothing marked.");
+        } else {
+            ui.label(self.marked_summary());
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.selection_set_name)
+                        .hint_text("GUI stack")
+                        .desired_width(140.0),
+                );
+                let name = self.selection_set_name.trim().to_owned();
+                if ui
+                    .add_enabled(!name.is_empty(), egui::Button::new("Save as set"))
+                    .on_hover_text("Saving under an existing name replaces that set.")
+                    .clicked()
+                {
+                    let members = self
+                        .marked
+                        .iter()
+                        .map(|id| short_name(id).to_owned())
+                        .collect::<BTreeSet<_>>();
+                    self.selection_sets.insert(name, members);
+                    self.selection_set_name.clear();
+                    self.persist_selection_sets();
+                }
+                if ui.button("Clear").clicked() {
+                    self.marked.clear();
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui
+                    .button("Pin")
+                    .on_hover_text("Pin every marked node drawn in the graph where it is now.")
+                    .clicked()
+                {
+                    self.pin_marked();
+                }
+                if ui
+                    .button("Hide")
+                    .on_hover_text("Leave the marked paths out of the graph until shown again.")
+                    .clicked()
+                {
+                    self.hidden_nodes.extend(self.marked.iter().cloned());
+                    self.graph_dirty = true;
+                }
+                if ui
+                    .button(format!("Export {}", self.graph_export_format.label()))
+                    .on_hover_text(format!(
+                        "Write the marked paths to marked.{} in the graph export format.",
+                        self.graph_export_format.extension()
+                    ))
+                    .clicked()
+                {
+                    self.export_marked();
+                }
+            });
+        }
+        if !self.hidden_nodes.is_empty()
+            && ui
+                .button(format!(
+                    "Show {} hidden",
+                    format_count(self.hidden_nodes.len() as u64)
+                ))
+                .clicked()
+        {
+            self.hidden_nodes.clear();
+            self.graph_dirty = true;
+        }
+        if let Some(status) = &self.selection_sets_status {
+            ui.weak(status.as_str());
+        }
+
+        if self.selection_sets.is_empty() {
+            return;
+        }
+        ui.separator();
+
+        let names = self.selection_sets.keys().cloned().collect::<Vec<_>>();
+        let mut deleted = None;
+        for name in &names {
+            let Some(summary) = self.selection_set_summary(name) else {
+                continue;
+            };
+            ui.horizontal(|ui| {
+                ui.label(RichText::new(name.as_str()).strong());
+                if ui
+                    .small_button("Recall")
+                    .on_hover_text("Replace the multi-selection with this set.")
+                    .clicked()
+                {
+                    self.marked = self.resolve_selection_set(name).into_iter().collect();
+                }
+                if ui.small_button("Delete").clicked() {
+                    deleted = Some(name.clone());
+                }
+            });
+            let missing = if summary.missing > 0 {
+                format!(
+                    ", {} not in this graph",
+                    format_count(summary.missing as u64)
+                )
+            } else {
+                String::new()
+            };
+            ui.weak(format!(
+                "{} paths, {} own, closure {} across {} paths{missing}",
+                format_count(summary.present as u64),
+                format_bytes(summary.nar_size),
+                format_bytes(summary.closure_size),
+                format_count(summary.closure_paths as u64)
+            ));
+        }
+        if let Some(name) = deleted {
+            self.selection_sets.remove(&name);
+            self.persist_selection_sets();
+        }
+
+        if names.len() < 2 {
+            return;
+        }
+        ui.separator();
+        ui.label(RichText::new("Compare sets").strong());
+        if self.set_comparison_names.0.is_empty() {
+            self.set_comparison_names = (names[0].clone(), names[1].clone());
+        }
+        ui.horizontal(|ui| {
+            for (salt, pick) in [
+                ("set_compare_first", &mut self.set_comparison_names.0),
+                ("set_compare_second", &mut self.set_comparison_names.1),
+            ] {
+                egui::ComboBox::from_id_salt(salt)
+                    .selected_text(pick.as_str())
+                    .show_ui(ui, |ui| {
+                        for name in &names {
+                            ui.selectable_value(pick, name.clone(), name.as_str());
+                        }
+                    });
+            }
+        });
+        let (first, second) = self.set_comparison_names.clone();
+        let comparable = self.selection_sets.contains_key(&first)
+            && self.selection_sets.contains_key(&second)
+            && first != second;
+        if ui
+            .add_enabled(comparable, egui::Button::new("Compare closures"))
+            .clicked()
+        {
+            self.set_comparison = Some(self.compare_selection_sets(&first, &second));
+        }
+
+        if let Some(comparison) = &self.set_comparison {
+            let (first, second) = &comparison.names;
+            for (label, (count, bytes)) in [
+                (format!("Only {first}"), comparison.only_first),
+                (format!("Only {second}"), comparison.only_second),
+                ("Shared".to_owned(), comparison.shared),
+            ] {
+                ui.label(format!(
+                    "{label}: {} paths, {}",
+                    format_count(count as u64),
+                    format_bytes(bytes)
+                ));
+            }
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use eframe::egui::{self, Context, Ui};
+
+use super::super::{
+    AttributeLookup, ClosureSizeQuery, ComparisonState, ContentScan, GenerationListing, LabelPanel,
+    LoadJob, NodeReportState, PathPreviewState, RemovalState, StatusBarAction, VerificationState,
+    ViewModel,
+};
+
+impl ViewModel {
+    /// Lists running background work with a cancel button for each.
+    pub(in crate::app) fn draw_status_bar(
+        &mut self,
+        ctx: &Context,
+        reload: Option<&mut LoadJob>,
+        store_watching: bool,
+    ) -> Option<StatusBarAction> {
+        let mut action = None;
+
+        egui::TopBottomPanel::bottom("status_bar")
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    let mut idle = true;
+
+                    if let Some(job) = reload {
+                        idle = false;
+                        let progress = job.poll_progress().map(str::to_owned);
+                        let label = if job.streamed {
+                            "Loading store shards"
+                        } else {
+                            "Reloading closure"
+                        };
+                        if task_indicator(ui, label, progress.as_deref()) {
+                            action = Some(StatusBarAction::CancelReload);
+                        }
+                    }
+
+                    for (id, state) in &mut self.verifications {
+                        if let VerificationState::Running(job) = state {
+                            idle = false;
+                            let label = format!(
+                                "Verifying {}",
+                                self.store_hashes.label(LabelPanel::Bars, id)
+                            );
+                            let progress = job.poll_progress().map(str::to_owned);
+                            if task_indicator(ui, &label, progress.as_deref()) {
+                                job.cancel();
+                            }
+                        }
+                    }
+
+                    for (id, state) in &mut self.content_scans {
+                        if let ContentScan::Running(job) = state {
+                            idle = false;
+                            let label = format!(
+                                "Scanning contents of {}",
+                                self.store_hashes.label(LabelPanel::Bars, id)
+                            );
+                            let progress = job.poll_progress().map(str::to_owned);
+                            if task_indicator(ui, &label, progress.as_deref()) {
+                                *state = ContentScan::Done(Err("Scan cancelled".to_owned()));
+                            }
+                        }
+                    }
+
+                    for (id, state) in &mut self.attribute_lookups {
+                        if let AttributeLookup::Running(_) = state {
+                            idle = false;
+                            let label = format!(
+                                "Locating {}",
+                                self.store_hashes.label(LabelPanel::Bars, id)
+                            );
+                            if task_indicator(ui, &label, None) {
+                                *state = AttributeLookup::Done(Err("Lookup cancelled".to_owned()));
+                            }
+                        }
+                    }
+
+                    for (id, state) in &mut self.path_previews {
+                        if let PathPreviewState::Running(_) = state {
+                            idle = false;
+                            let label = format!(
+                                "Previewing {}",
+                                self.store_hashes.label(LabelPanel::Bars, id)
+                            );
+                            if task_indicator(ui, &label, None) {
+                                *state =
+                                    PathPreviewState::Done(Err("Preview cancelled".to_owned()));
+                            }
+                        }
+                    }
+
+                    if let Some(report) = self.node_report.as_mut()
+                        && let NodeReportState::Running(job) = &mut report.state
+                    {
+                        idle = false;
+                        let progress = job.poll_progress().map(str::to_owned);
+                        if task_indicator(ui, &report.title, progress.as_deref()) {
+                            report.state =
+                                NodeReportState::Done(Err("Nix command cancelled".to_owned()));
+                        }
+                    }
+
+                    if let Some(simulation) = self.removal_simulation.as_mut()
+                        && let RemovalState::Running(_) = simulation.state
+                    {
+                        idle = false;
+                        let label = format!(
+                            "Simulating removal of {}",
+                            self.store_hashes.label(LabelPanel::Bars, &simulation.id)
+                        );
+                        if task_indicator(ui, &label, None) {
+                            simulation.state =
+                                RemovalState::Done(Err("Simulation cancelled".to_owned()));
+                        }
+                    }
+
+                    if let Some(scan) = self.edge_reference_scan.as_mut()
+                        && scan.job.is_some()
+                    {
+                        idle = false;
+                        let label = format!(
+                            "Scanning {} for references",
+                            self.store_hashes.label(LabelPanel::Bars, &scan.edge.0)
+                        );
+                        if task_indicator(ui, &label, None) {
+                            scan.job = None;
+                            scan.result = Some(Err("Reference scan cancelled".to_owned()));
+                        }
+                    }
+
+                    if let Some(browser) = self.generations.as_mut() {
+                        if let GenerationListing::Running(_) = browser.generations {
+                            idle = false;
+                            if task_indicator(ui, "Listing generations", None) {
+                                browser.generations =
+                                    GenerationListing::Done(Err("Listing cancelled".to_owned()));
+                            }
+                        }
+                        for (number, query) in &mut browser.sizes {
+                            if let ClosureSizeQuery::Running(job) = query {
+                                idle = false;
+                                let label = format!("Measuring generation #{number}");
+                                let progress = job.poll_progress().map(str::to_owned);
+                                if task_indicator(ui, &label, progress.as_deref()) {
+                                    *query = ClosureSizeQuery::Done(Err(
+                                        "Size query cancelled".to_owned()
+                                    ));
+                                }
+                            }
+                        }
+                    }
+
+                    if let Some(ComparisonState::Running(job)) = &mut self.comparison {
+                        idle = false;
+                        let label = format!("Comparing with {}", self.compare_path);
+                        let progress = job.poll_progress().map(str::to_owned);
+                        if task_indicator(ui, &label, progress.as_deref()) {
+                            job.cancel();
+                        }
+                    }
+
+                    if store_watching {
+                        idle = false;
+                        ui.label(if self.background_paused {
+                            "Watching store (paused)"
+                        } else {
+                            "Watching store"
+                        });
+                        if ui.small_button("Stop").clicked() {
+                            action = Some(StatusBarAction::StopWatching);
+                        }
+                        ui.separator();
+                    }
+
+                    if idle {
+                        ui.weak("No background tasks");
+                    }
+                });
+            });
+
+        action
+    }
+}
+
+/// Draws one running task and returns true when its cancel button is clicked.
+fn task_indicator(ui: &mut Ui, label: &str, progress: Option<&str>) -> bool {
+    ui.spinner();
+    ui.label(label);
+    if let Some(progress) = progress {
+        ui.weak(progress);
+    }
+    let cancelled = ui.small_button("Cancel").clicked();
+    ui.separator();
+    cancelled
+}
@@ -0,0 +1,51 @@
+use eframe::egui::Ui;
+
+use crate::format::format_count;
+use crate::nix::SystemGraph;
+
+use super::super::ViewModel;
+use super::super::render_utils::store_badge;
+
+impl ViewModel {
+    /// Paths per store directory, or nothing for a graph from one store.
+    pub(in crate::app) fn store_dir_counts(graph: &SystemGraph) -> Vec<(String, usize)> {
+        let counts = graph.store_dirs();
+        if counts.len() < 2 {
+            return Vec::new();
+        }
+        counts
+            .into_iter()
+            .map(|(dir, count)| (dir.to_owned(), count))
+            .collect()
+    }
+
+    pub(in crate::app) fn draw_store_filter(&mut self, ui: &mut Ui) {
+        ui.weak("Paths of unchecked stores are left out of the graph.");
+        let mut changed = false;
+        for (dir, count) in &self.store_dirs {
+            let mut shown = !self.hidden_stores.contains(dir);
+            if ui
+                .checkbox(
+                    &mut shown,
+                    format!(
+                        "{} ({} paths)",
+                        store_badge(dir),
+                        format_count(*count as u64)
+                    ),
+                )
+                .on_hover_text(dir.as_str())
+                .changed()
+            {
+                if shown {
+                    self.hidden_stores.remove(dir);
+                } else {
+                    self.hidden_stores.insert(dir.clone());
+                }
+                changed = true;
+            }
+        }
+        if changed {
+            self.graph_dirty = true;
+        }
+    }
+}
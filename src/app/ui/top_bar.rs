@@ -0,0 +1,274 @@
+use eframe::egui::{self, Align, Context, Layout, Ui, Vec2};
+
+use std::collections::HashSet;
+
+use crate::format::{format_bytes, format_count};
+
+use super::super::{
+    HiddenSavings, LabelPanel, ROOT_INPUT_HINT, ViewModel, WorkspaceRequest, profile_name,
+};
+
+/// Below this width the stats fold into a popover and the actions into an
+/// overflow menu.
+const COMPACT_TOP_BAR_WIDTH: f32 = 1180.0;
+
+impl ViewModel {
+    pub(in crate::app) fn draw_top_bar(
+        &mut self,
+        ctx: &Context,
+        system_path: &str,
+        reload_requested: &mut bool,
+        is_loading: bool,
+    ) {
+        egui::TopBottomPanel::top("top_bar")
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::MenuBar::new().ui(ui, |ui| {
+                    self.draw_action_menus(ui, reload_requested, is_loading);
+                    self.draw_navigation_buttons(ui);
+                    ui.separator();
+                    ui.strong("nix-analisá");
+                    ui.label(format!(
+                        "root: {}",
+                        self.path_label(LabelPanel::Bars, &self.graph.root_id)
+                    ));
+                    ui.separator();
+
+                    if ui.available_width() < COMPACT_TOP_BAR_WIDTH {
+                        ui.menu_button("Stats", |ui| {
+                            self.draw_graph_stats(ui, system_path);
+                            ui.separator();
+                            self.draw_render_stats(ui);
+                        });
+                        ui.menu_button("…", |ui| {
+                            self.draw_primary_actions(ui, reload_requested, is_loading);
+                        });
+                    } else {
+                        self.draw_graph_stats(ui, system_path);
+                        ui.separator();
+                        self.draw_primary_actions(ui, reload_requested, is_loading);
+                        ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                            self.draw_render_stats(ui);
+                        });
+                    }
+                });
+            });
+    }
+
+    fn draw_navigation_buttons(&mut self, ui: &mut Ui) {
+        if ui
+            .add_enabled(self.navigation.can_go_back(), egui::Button::new("◀"))
+            .on_hover_text("Back to the previous selection (Alt+←)")
+            .clicked()
+        {
+            self.navigate_back();
+        }
+        if ui
+            .add_enabled(self.navigation.can_go_forward(), egui::Button::new("▶"))
+            .on_hover_text("Forward (Alt+→)")
+            .clicked()
+        {
+            self.navigate_forward();
+        }
+    }
+
+    /// What removing every hidden path from the closure would free, computed
+    /// again whenever the hidden set changes; `None` while nothing is hidden.
+    pub(in crate::app) fn hidden_savings(&mut self) -> Option<&HiddenSavings> {
+        if self.hidden_nodes.is_empty() {
+            self.hidden_savings = None;
+            return None;
+        }
+        if self
+            .hidden_savings
+            .as_ref()
+            .is_none_or(|savings| savings.hidden != self.hidden_nodes)
+        {
+            let removed = self
+                .hidden_nodes
+                .iter()
+                .map(String::as_str)
+                .collect::<HashSet<_>>();
+            let freed = self.graph.unreachable_without_paths(&removed);
+            self.hidden_savings = Some(HiddenSavings {
+                hidden: self.hidden_nodes.clone(),
+                paths: freed.len(),
+                nar_size: freed.iter().map(|id| self.graph.nodes[id].nar_size).sum(),
+            });
+        }
+        self.hidden_savings.as_ref()
+    }
+
+    fn draw_hidden_savings(&mut self, ui: &mut Ui) {
+        let hidden = self.hidden_nodes.len();
+        let Some(savings) = self.hidden_savings() else {
+            return;
+        };
+        let text = format!(
+            "hidden: {} paths, frees {}",
+            format_count(hidden as u64),
+            format_bytes(savings.nar_size)
+        );
+        let hover = format!(
+            "Taking the hidden paths out of the closure would drop {} paths, counting those \
+             only the hidden paths keep alive between them. Click to show them again.",
+            format_count(savings.paths as u64)
+        );
+        if ui.button(text).on_hover_text(hover).clicked() {
+            self.hidden_nodes.clear();
+            self.graph_dirty = true;
+        }
+    }
+
+    fn draw_graph_stats(&mut self, ui: &mut Ui, system_path: &str) {
+        if self.store_dirs.is_empty() {
+            ui.label(format!("store: {}", self.graph.store_dir));
+        } else {
+            ui.label(format!("stores: {}", self.store_dirs.len()))
+                .on_hover_text(
+                    self.store_dirs
+                        .iter()
+                        .map(|(dir, count)| format!("{dir}: {} paths", format_count(*count as u64)))
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                );
+        }
+        ui.label(format!("system path: {system_path}"));
+        if self.graph.is_multi_root() {
+            ui.label(format!("roots: {}", self.graph.roots.len()))
+                .on_hover_text(self.graph.roots.join("\n"));
+        }
+        if self.trim_active() {
+            let (filtered_count, _) = self.effective_closure_totals();
+            ui.label(format!(
+                "nodes: {} ({} after trimming)",
+                format_count(self.graph.node_count() as u64),
+                format_count(filtered_count as u64)
+            ));
+        } else {
+            ui.label(format!(
+                "nodes: {}",
+                format_count(self.graph.node_count() as u64)
+            ));
+        }
+        ui.label(format!(
+            "edges: {}",
+            format_count(self.graph.edge_count as u64)
+        ));
+        self.draw_hidden_savings(ui);
+        if !self.graph.limitations.is_empty() {
+            ui.colored_label(ui.visuals().warn_fg_color, "restricted store")
+                .on_hover_text(self.graph.limitations.join("\n\n"));
+        }
+    }
+
+    fn draw_root_picker(&mut self, ui: &mut Ui) {
+        ui.label("Store path, profile or flake installable:");
+        let input = ui.add(
+            egui::TextEdit::singleline(&mut self.root_input)
+                .hint_text(ROOT_INPUT_HINT)
+                .desired_width(320.0),
+        );
+        let submitted = input.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter));
+        let root = self.root_input.trim();
+        if (ui
+            .add_enabled(!root.is_empty(), egui::Button::new("Open"))
+            .clicked()
+            || submitted)
+            && !root.is_empty()
+        {
+            self.root_request = Some(root.to_owned());
+            ui.close();
+        }
+        ui.weak("Flake outputs are evaluated and built or substituted by nix path-info.");
+    }
+
+    fn draw_render_stats(&self, ui: &mut Ui) {
+        let visible_graph_text = self.visible_graph_text();
+        let fps_text = self.fps_display_text();
+
+        if let Some(visible_graph_text) = visible_graph_text.as_ref() {
+            ui.label(visible_graph_text);
+        }
+        if visible_graph_text.is_some() && fps_text.is_some() {
+            ui.separator();
+        }
+        if let Some(fps_text) = fps_text.as_ref() {
+            ui.label(fps_text);
+        }
+    }
+
+    fn draw_primary_actions(&mut self, ui: &mut Ui, reload_requested: &mut bool, is_loading: bool) {
+        if ui
+            .button("Fit graph")
+            .on_hover_text("Zoom and pan so every drawn node is in view (F).")
+            .clicked()
+        {
+            self.fit_to_view();
+        }
+        if self.is_workspace {
+            if ui
+                .button("Back to full graph")
+                .on_hover_text("Select this workspace's root in the full graph.")
+                .clicked()
+            {
+                self.workspace_request = Some(WorkspaceRequest::ShowInFullGraph(
+                    self.graph.root_id.clone(),
+                ));
+            }
+            return;
+        }
+
+        let reload_button = ui.add_enabled(!is_loading, egui::Button::new("Reload closure"));
+        if reload_button.clicked() {
+            *reload_requested = true;
+        }
+        ui.menu_button("Open root…", |ui| self.draw_root_picker(ui))
+            .response
+            .on_hover_text("Explore the closure of another store path, profile or flake output.");
+        ui.menu_button("Export graph…", |ui| self.draw_graph_export(ui))
+            .response
+            .on_hover_text("Write the currently rendered graph for Graphviz or Gephi.");
+        ui.menu_button("Export image…", |ui| self.draw_image_export(ui))
+            .response
+            .on_hover_text("Save the canvas as a PNG or SVG for documentation and bug reports.");
+        ui.checkbox(&mut self.watch_store, "Watch store")
+            .on_hover_text(
+                "Poll the local store database and apply new or removed paths without a full reload.",
+            );
+        if let Some(status) = &self.store_update_status {
+            ui.weak(status.as_str());
+        }
+    }
+
+    /// Offers to reload once the system profile has switched; returns whether
+    /// a reload was asked for.
+    pub(in crate::app) fn draw_profile_banner(&mut self, ctx: &Context) -> bool {
+        let Some(target) = &self.profile_switch else {
+            return false;
+        };
+        let name = profile_name(target);
+        let mut reload = false;
+        let mut dismiss = false;
+        egui::TopBottomPanel::top("profile_banner")
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.strong("Closure changed — reload?");
+                    ui.label(format!("The system profile now points to {name}."))
+                        .on_hover_text(target.display().to_string());
+                    reload = ui.button("Reload").clicked();
+                    dismiss = ui.button("Dismiss").clicked();
+                });
+            });
+        if dismiss {
+            self.profile_switch = None;
+        }
+        reload
+    }
+
+    pub(in crate::app) fn reset_view(&mut self) {
+        self.pan = Vec2::ZERO;
+        self.zoom = 1.0;
+    }
+}
@@ -0,0 +1,93 @@
+use eframe::egui::{Color32, RichText, Ui};
+
+use crate::format::format_bytes;
+use crate::nix::verify_store_path;
+
+use super::super::jobs::Job;
+use super::super::{VerificationState, ViewModel};
+
+impl VerificationState {
+    pub(in crate::app) fn is_mismatch(&self) -> bool {
+        matches!(self, Self::Done(Ok(outcome)) if outcome.is_mismatch())
+    }
+}
+
+impl ViewModel {
+    pub(in crate::app) fn poll_verifications(&mut self) -> bool {
+        let mut running = false;
+
+        for state in self.verifications.values_mut() {
+            let VerificationState::Running(job) = state else {
+                continue;
+            };
+
+            match job.poll() {
+                Some(result) => *state = VerificationState::Done(result),
+                None => running = true,
+            }
+        }
+
+        running
+    }
+
+    pub(in crate::app) fn start_verification(&mut self, id: &str) {
+        let Some(node) = self.graph.nodes.get(id) else {
+            return;
+        };
+
+        let full_path = node.full_path.clone();
+        let nar_size = node.nar_size;
+        let job = Job::spawn(&self.nix_settings, move |control| {
+            verify_store_path(control, &full_path, nar_size)
+        });
+
+        self.verifications
+            .insert(id.to_owned(), VerificationState::Running(Box::new(job)));
+    }
+
+    pub(in crate::app) fn draw_verification_section(&mut self, ui: &mut Ui, id: &str) {
+        ui.separator();
+        ui.label(RichText::new("NAR verification").strong());
+
+        let mut verify_requested = false;
+        match self.verifications.get(id) {
+            None => {
+                ui.weak("Recompute the NAR size and check contents against the store database.");
+                verify_requested = ui.button("Verify NAR").clicked();
+            }
+            Some(VerificationState::Running(..)) => {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Verifying...");
+                });
+            }
+            Some(VerificationState::Done(Err(error))) => {
+                ui.label(format!("Verification failed: {error}"));
+                verify_requested = ui.button("Retry").clicked();
+            }
+            Some(VerificationState::Done(Ok(outcome))) => {
+                if outcome.is_mismatch() {
+                    ui.colored_label(Color32::from_rgb(236, 98, 92), "Mismatch detected");
+                } else {
+                    ui.label("Contents match the recorded metadata.");
+                }
+                ui.label(format!(
+                    "Recorded narSize: {}",
+                    format_bytes(outcome.recorded_nar_size)
+                ));
+                ui.label(format!(
+                    "Recomputed narSize: {}",
+                    format_bytes(outcome.recomputed_nar_size)
+                ));
+                if let Some(message) = &outcome.message {
+                    ui.small(message.as_str());
+                }
+                verify_requested = ui.button("Verify again").clicked();
+            }
+        }
+
+        if verify_requested {
+            self.start_verification(id);
+        }
+    }
+}
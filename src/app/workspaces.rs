@@ -0,0 +1,121 @@
+use eframe::egui::{self, Context};
+
+use super::toasts::Toast;
+use super::{LabelPanel, ViewModel, WorkspaceRequest};
+
+/// The full graph plus any closures opened from it as separate tabs, each
+/// with its own filters, layout and camera.
+pub(super) struct Workspaces {
+    tabs: Vec<Workspace>,
+    active: usize,
+}
+
+struct Workspace {
+    model: Box<ViewModel>,
+}
+
+impl Workspaces {
+    pub(super) fn new(model: ViewModel) -> Self {
+        Self {
+            tabs: vec![Workspace {
+                model: Box::new(model),
+            }],
+            active: 0,
+        }
+    }
+
+    pub(super) fn full_mut(&mut self) -> &mut ViewModel {
+        &mut self.tabs[0].model
+    }
+
+    pub(super) fn active_mut(&mut self) -> &mut ViewModel {
+        &mut self.tabs[self.active].model
+    }
+
+    pub(super) fn take_toasts(&mut self) -> Vec<Toast> {
+        self.tabs
+            .iter_mut()
+            .flat_map(|tab| tab.model.pending_toasts.drain(..))
+            .collect()
+    }
+
+    pub(super) fn draw_tabs(&mut self, ctx: &Context) {
+        if self.tabs.len() < 2 {
+            return;
+        }
+
+        let mut closed = None;
+        egui::TopBottomPanel::top("workspace_tabs")
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    let full = &self.tabs[0].model;
+                    for (index, tab) in self.tabs.iter().enumerate() {
+                        let title = if index == 0 {
+                            "Full graph".to_owned()
+                        } else {
+                            format!(
+                                "Closure of {}",
+                                full.path_label(LabelPanel::Bars, &tab.model.graph.root_id)
+                            )
+                        };
+                        if ui.selectable_label(self.active == index, title).clicked() {
+                            self.active = index;
+                        }
+                        if index > 0
+                            && ui
+                                .small_button("x")
+                                .on_hover_text("Close this workspace.")
+                                .clicked()
+                        {
+                            closed = Some(index);
+                        }
+                        ui.separator();
+                    }
+                });
+            });
+
+        if let Some(index) = closed {
+            self.tabs.remove(index);
+            if self.active >= index {
+                self.active = self.active.saturating_sub(1);
+            }
+        }
+    }
+
+    pub(super) fn handle_requests(&mut self) {
+        let Some(request) = self.tabs[self.active].model.workspace_request.take() else {
+            return;
+        };
+
+        match request {
+            WorkspaceRequest::Open(id) => {
+                let model = &self.tabs[self.active].model;
+                let Some(graph) = model.graph.closure_subgraph(&id) else {
+                    return;
+                };
+
+                let mut next = ViewModel::new(graph, None);
+                next.nix_settings = model.nix_settings.clone();
+                next.validate_graph = model.validate_graph;
+                next.size_history = model.size_history.clone();
+                next.metric = model.metric;
+                next.edge_style = model.edge_style;
+                next.label_pname_only = model.label_pname_only;
+                next.store_hashes = model.store_hashes.clone();
+                next.is_workspace = true;
+                next.pinned_positions.clear();
+
+                self.tabs.push(Workspace {
+                    model: Box::new(next),
+                });
+                self.active = self.tabs.len() - 1;
+            }
+            WorkspaceRequest::ShowInFullGraph(id) => {
+                self.active = 0;
+                let full = self.full_mut();
+                full.reveal_node(&id);
+            }
+        }
+    }
+}
@@ -0,0 +1,313 @@
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::format::format_bytes;
+use crate::nix::SystemGraph;
+use crate::util::short_name;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Json,
+    Csv,
+    Dot,
+    GraphMl,
+}
+
+impl ExportFormat {
+    pub const ALL: [Self; 4] = [Self::Json, Self::Csv, Self::Dot, Self::GraphMl];
+
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "json" => Some(Self::Json),
+            "csv" => Some(Self::Csv),
+            "dot" | "gv" => Some(Self::Dot),
+            "graphml" => Some(Self::GraphMl),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Csv => "csv",
+            Self::Dot => "dot",
+            Self::GraphMl => "graphml",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Json => "JSON",
+            Self::Csv => "CSV",
+            Self::Dot => "DOT",
+            Self::GraphMl => "GraphML",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ExportNode<'a> {
+    id: &'a str,
+    name: &'a str,
+    path: &'a str,
+    nar_size: u64,
+    closure_size: u64,
+    references: Vec<&'a str>,
+}
+
+#[derive(Serialize)]
+struct ExportDocument<'a> {
+    store_dir: &'a str,
+    root: &'a str,
+    nodes: Vec<ExportNode<'a>>,
+}
+
+pub fn export_nodes(
+    graph: &SystemGraph,
+    ids: &[String],
+    format: ExportFormat,
+    path: &Path,
+) -> Result<()> {
+    if let Some(id) = ids.iter().find(|id| !graph.nodes.contains_key(*id)) {
+        return Err(anyhow!("export references unknown node {id}"));
+    }
+
+    let file =
+        File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+    let mut writer = BufWriter::new(file);
+
+    match format {
+        ExportFormat::Json => write_json(graph, ids, &mut writer)?,
+        ExportFormat::Csv => write_csv(graph, ids, &mut writer)?,
+        ExportFormat::Dot => writer.write_all(graph.to_dot(ids).as_bytes())?,
+        ExportFormat::GraphMl => writer.write_all(graph.to_graphml(ids).as_bytes())?,
+    }
+
+    writer
+        .flush()
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+fn write_json(graph: &SystemGraph, ids: &[String], writer: &mut impl Write) -> Result<()> {
+    let included = ids.iter().map(String::as_str).collect::<HashSet<_>>();
+    let nodes = ids
+        .iter()
+        .map(|id| &graph.nodes[id])
+        .map(|node| ExportNode {
+            id: &node.id,
+            name: short_name(&node.id),
+            path: &node.full_path,
+            nar_size: node.nar_size,
+            closure_size: node.closure_size,
+            references: node
+                .references
+                .iter()
+                .map(String::as_str)
+                .filter(|reference| included.contains(reference))
+                .collect(),
+        })
+        .collect();
+
+    let document = ExportDocument {
+        store_dir: &graph.store_dir,
+        root: &graph.root_id,
+        nodes,
+    };
+
+    serde_json::to_writer_pretty(&mut *writer, &document).context("failed to encode JSON")?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+fn write_csv(graph: &SystemGraph, ids: &[String], writer: &mut impl Write) -> Result<()> {
+    writeln!(writer, "id,name,nar_size,closure_size,references,referrers")?;
+    for id in ids {
+        let node = &graph.nodes[id];
+        writeln!(
+            writer,
+            "{},{},{},{},{},{}",
+            csv_field(&node.id),
+            csv_field(short_name(&node.id)),
+            node.nar_size,
+            node.closure_size,
+            node.references.len(),
+            node.referrers.len()
+        )?;
+    }
+    Ok(())
+}
+
+impl SystemGraph {
+    /// The paths in `ids` and the references between them as a Graphviz
+    /// digraph, with sizes as node attributes and edge weights when scanned.
+    pub fn to_dot(&self, ids: &[String]) -> String {
+        let included = ids.iter().map(String::as_str).collect::<HashSet<_>>();
+        let nodes = ids.iter().filter_map(|id| self.nodes.get(id));
+
+        let mut dot = String::new();
+        let _ = writeln!(dot, "digraph closure {{");
+        let _ = writeln!(dot, "  rankdir=LR;");
+        let _ = writeln!(dot, "  node [shape=box, fontname=\"sans-serif\"];");
+        for node in nodes.clone() {
+            let _ = writeln!(
+                dot,
+                "  {} [label=\"{}\\n{}\", nar_size={}, closure_size={}, retained_size={}];",
+                dot_id(&node.id),
+                dot_escape(short_name(&node.id)),
+                format_bytes(node.nar_size),
+                node.nar_size,
+                node.closure_size,
+                node.retained_size
+            );
+        }
+        for node in nodes {
+            for reference in &node.references {
+                if *reference != node.id && included.contains(reference.as_str()) {
+                    let _ = writeln!(
+                        dot,
+                        "  {} -> {} [weight={}];",
+                        dot_id(&node.id),
+                        dot_id(reference),
+                        self.edge_weight(&node.id, reference)
+                    );
+                }
+            }
+        }
+        let _ = writeln!(dot, "}}");
+        dot
+    }
+
+    /// The paths in `ids` and the references between them as a directed
+    /// GraphML document, e.g. for Gephi.
+    pub fn to_graphml(&self, ids: &[String]) -> String {
+        let included = ids.iter().map(String::as_str).collect::<HashSet<_>>();
+        let nodes = ids.iter().filter_map(|id| self.nodes.get(id));
+
+        let mut xml = String::new();
+        let _ = writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+        let _ = writeln!(
+            xml,
+            r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+        );
+        for (key, target, kind) in GRAPHML_KEYS {
+            let _ = writeln!(
+                xml,
+                r#"  <key id="{key}" for="{target}" attr.name="{key}" attr.type="{kind}"/>"#
+            );
+        }
+        let _ = writeln!(xml, r#"  <graph id="closure" edgedefault="directed">"#);
+        for node in nodes.clone() {
+            let _ = writeln!(xml, r#"    <node id="{}">"#, xml_escape(&node.id));
+            let _ = writeln!(
+                xml,
+                r#"      <data key="name">{}</data>"#,
+                xml_escape(short_name(&node.id))
+            );
+            let _ = writeln!(
+                xml,
+                r#"      <data key="nar_size">{}</data>"#,
+                node.nar_size
+            );
+            let _ = writeln!(
+                xml,
+                r#"      <data key="closure_size">{}</data>"#,
+                node.closure_size
+            );
+            let _ = writeln!(
+                xml,
+                r#"      <data key="retained_size">{}</data>"#,
+                node.retained_size
+            );
+            let _ = writeln!(xml, "    </node>");
+        }
+        for node in nodes {
+            for reference in &node.references {
+                if *reference != node.id && included.contains(reference.as_str()) {
+                    let _ = writeln!(
+                        xml,
+                        r#"    <edge source="{}" target="{}"><data key="weight">{}</data></edge>"#,
+                        xml_escape(&node.id),
+                        xml_escape(reference),
+                        self.edge_weight(&node.id, reference)
+                    );
+                }
+            }
+        }
+        let _ = writeln!(xml, "  </graph>");
+        let _ = writeln!(xml, "</graphml>");
+        xml
+    }
+}
+
+const GRAPHML_KEYS: [(&str, &str, &str); 5] = [
+    ("name", "node", "string"),
+    ("nar_size", "node", "long"),
+    ("closure_size", "node", "long"),
+    ("retained_size", "node", "long"),
+    ("weight", "edge", "int"),
+];
+
+pub(crate) fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn dot_id(value: &str) -> String {
+    format!("\"{}\"", dot_escape(value))
+}
+
+fn dot_escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::nix::generate_demo_graph;
+
+    #[test]
+    fn every_format_rejects_unknown_nodes() {
+        let graph = generate_demo_graph(20);
+        let ids = vec![graph.root_id.clone(), "missing-path".to_owned()];
+        let dir = std::env::temp_dir().join(format!("nix-analisa-export-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        for format in ExportFormat::ALL {
+            let path = dir.join(format!("closure.{}", format.extension()));
+            let error = export_nodes(&graph, &ids, format, &path).unwrap_err();
+            assert!(error.to_string().contains("missing-path"), "{format:?}");
+            assert!(!path.exists(), "{format:?}");
+        }
+
+        let path = dir.join("closure.json");
+        let known = graph.nodes.keys().cloned().collect::<Vec<_>>();
+        let exported = export_nodes(&graph, &known, ExportFormat::Json, &path)
+            .map(|()| fs::read_to_string(&path).unwrap());
+        let _ = fs::remove_dir_all(&dir);
+        let document = serde_json::from_str::<serde_json::Value>(&exported.unwrap()).unwrap();
+        assert_eq!(
+            document["nodes"].as_array().unwrap().len(),
+            graph.node_count()
+        );
+    }
+}
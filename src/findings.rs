@@ -0,0 +1,260 @@
+use std::cmp::Reverse;
+use std::collections::BTreeSet;
+
+use crate::format::format_bytes;
+use crate::nix::SystemGraph;
+use crate::util::{parse_store_name, short_name};
+
+const MIB: u64 = 1024 * 1024;
+
+pub enum RuleMatcher {
+    NameSuffix(&'static [&'static str]),
+    NameContains(&'static [&'static str]),
+    MultipleVersions(&'static str),
+}
+
+pub struct SuggestionRule {
+    pub title: &'static str,
+    pub advice: &'static str,
+    pub matcher: RuleMatcher,
+    pub min_bytes: u64,
+}
+
+pub const SUGGESTION_RULES: &[SuggestionRule] = &[
+    SuggestionRule {
+        title: "Man pages",
+        advice: "documentation.man.enable = false;",
+        matcher: RuleMatcher::NameSuffix(&["-man"]),
+        min_bytes: 8 * MIB,
+    },
+    SuggestionRule {
+        title: "Documentation outputs",
+        advice: "documentation.doc.enable = false;",
+        matcher: RuleMatcher::NameSuffix(&["-doc", "-devdoc"]),
+        min_bytes: 8 * MIB,
+    },
+    SuggestionRule {
+        title: "Info pages",
+        advice: "documentation.info.enable = false;",
+        matcher: RuleMatcher::NameSuffix(&["-info"]),
+        min_bytes: 4 * MIB,
+    },
+    SuggestionRule {
+        title: "All glibc locales",
+        advice: "i18n.supportedLocales = [ \"en_US.UTF-8/UTF-8\" ];",
+        matcher: RuleMatcher::NameContains(&["glibc-locales"]),
+        min_bytes: 32 * MIB,
+    },
+    SuggestionRule {
+        title: "Redistributable firmware",
+        advice: "hardware.enableRedistributableFirmware = false; (if the hardware does not need it)",
+        matcher: RuleMatcher::NameContains(&["linux-firmware"]),
+        min_bytes: 64 * MIB,
+    },
+    SuggestionRule {
+        title: "Default font packages",
+        advice: "fonts.enableDefaultPackages = false;",
+        matcher: RuleMatcher::NameContains(&["noto-fonts", "dejavu-fonts"]),
+        min_bytes: 32 * MIB,
+    },
+    SuggestionRule {
+        title: "Development outputs",
+        advice: "Drop \"dev\" from environment.extraOutputsToInstall or the package list.",
+        matcher: RuleMatcher::NameSuffix(&["-dev"]),
+        min_bytes: 32 * MIB,
+    },
+    SuggestionRule {
+        title: "Multiple gcc versions",
+        advice: "Find which packages pin an older stdenv or gcc and align them.",
+        matcher: RuleMatcher::MultipleVersions("gcc"),
+        min_bytes: 0,
+    },
+    SuggestionRule {
+        title: "Multiple LLVM versions",
+        advice: "Find which packages pin a different llvmPackages set and align them.",
+        matcher: RuleMatcher::MultipleVersions("llvm"),
+        min_bytes: 0,
+    },
+    SuggestionRule {
+        title: "Multiple Python interpreters",
+        advice: "Find which packages use a different python3 version and align them.",
+        matcher: RuleMatcher::MultipleVersions("python3"),
+        min_bytes: 0,
+    },
+];
+
+pub struct Finding {
+    pub title: &'static str,
+    pub advice: &'static str,
+    pub node_ids: Vec<String>,
+    pub bytes: u64,
+}
+
+impl Finding {
+    pub fn summary(&self) -> String {
+        format!(
+            "{} ({} paths, ~{})",
+            self.title,
+            self.node_ids.len(),
+            format_bytes(self.bytes)
+        )
+    }
+}
+
+impl RuleMatcher {
+    fn matching_ids(&self, graph: &SystemGraph) -> Vec<String> {
+        match self {
+            Self::NameSuffix(suffixes) => graph
+                .nodes
+                .keys()
+                .filter(|id| {
+                    let name = short_name(id);
+                    suffixes.iter().any(|suffix| name.ends_with(suffix))
+                })
+                .cloned()
+                .collect(),
+            Self::NameContains(markers) => graph
+                .nodes
+                .keys()
+                .filter(|id| {
+                    let name = short_name(id);
+                    markers.iter().any(|marker| name.contains(marker))
+                })
+                .cloned()
+                .collect(),
+            Self::MultipleVersions(pname) => {
+                let matches = graph
+                    .nodes
+                    .keys()
+                    .filter_map(|id| {
+                        let name = parse_store_name(short_name(id));
+                        (name.pname == *pname && !name.is_derivation)
+                            .then_some(name.version)
+                            .flatten()
+                            .map(|version| (id, version))
+                    })
+                    .collect::<Vec<_>>();
+                let versions = matches
+                    .iter()
+                    .map(|(_, version)| *version)
+                    .collect::<BTreeSet<_>>();
+
+                if versions.len() < 2 {
+                    Vec::new()
+                } else {
+                    matches.into_iter().map(|(id, _)| id.clone()).collect()
+                }
+            }
+        }
+    }
+}
+
+pub fn collect_findings(graph: &SystemGraph) -> Vec<Finding> {
+    let mut findings = SUGGESTION_RULES
+        .iter()
+        .filter_map(|rule| {
+            let mut node_ids = rule.matcher.matching_ids(graph);
+            if node_ids.is_empty() {
+                return None;
+            }
+
+            node_ids.sort_by(|a, b| {
+                graph.nodes[b]
+                    .nar_size
+                    .cmp(&graph.nodes[a].nar_size)
+                    .then_with(|| a.cmp(b))
+            });
+            let bytes = node_ids.iter().map(|id| graph.nodes[id].nar_size).sum();
+            if bytes < rule.min_bytes {
+                return None;
+            }
+
+            Some(Finding {
+                title: rule.title,
+                advice: rule.advice,
+                node_ids,
+                bytes,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    findings.sort_by_key(|finding| Reverse(finding.bytes));
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nix::generate_demo_graph;
+
+    fn graph_of(paths: &[(&str, u64)]) -> SystemGraph {
+        let mut graph = generate_demo_graph(2);
+        let template = graph.nodes[&graph.root_id].clone();
+        graph.nodes.clear();
+        for &(id, nar_size) in paths {
+            let mut node = template.clone();
+            node.id = id.to_owned();
+            node.nar_size = nar_size;
+            graph.nodes.insert(node.id.clone(), node);
+        }
+        graph
+    }
+
+    #[test]
+    fn matches_rules_above_their_thresholds() {
+        let graph = graph_of(&[
+            ("aaaa-man-db-2.12.1-man", 10 * MIB),
+            ("bbbb-coreutils-9.5-man", MIB),
+            ("cccc-bash-5.2p37-doc", 2 * MIB),
+            ("dddd-glibc-locales-2.40-66", 200 * MIB),
+            ("eeee-gcc-13.3.0", 3 * MIB),
+            ("ffff-gcc-14.2.0", 4 * MIB),
+            ("gggg-gcc-12.4.0.drv", 1),
+            ("hhhh-python3-3.12.8", 40 * MIB),
+        ]);
+
+        let findings = collect_findings(&graph);
+        let titles = findings
+            .iter()
+            .map(|finding| finding.title)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            titles,
+            ["All glibc locales", "Man pages", "Multiple gcc versions"]
+        );
+        assert_eq!(
+            findings[1].node_ids,
+            ["aaaa-man-db-2.12.1-man", "bbbb-coreutils-9.5-man"]
+        );
+        assert_eq!(findings[1].bytes, 11 * MIB);
+        assert_eq!(findings[2].node_ids, ["ffff-gcc-14.2.0", "eeee-gcc-13.3.0"]);
+    }
+
+    #[test]
+    fn demo_graph_findings_respect_their_rules() {
+        let graph = generate_demo_graph(400);
+
+        let findings = collect_findings(&graph);
+        assert!(
+            findings
+                .windows(2)
+                .all(|pair| pair[0].bytes >= pair[1].bytes)
+        );
+        for finding in &findings {
+            let rule = SUGGESTION_RULES
+                .iter()
+                .find(|rule| rule.title == finding.title)
+                .unwrap();
+            assert!(!finding.node_ids.is_empty());
+            assert!(finding.bytes >= rule.min_bytes);
+            assert_eq!(
+                finding.bytes,
+                finding
+                    .node_ids
+                    .iter()
+                    .map(|id| graph.nodes[id].nar_size)
+                    .sum::<u64>()
+            );
+        }
+    }
+}
@@ -0,0 +1,327 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+
+use anyhow::{Result, bail};
+
+use crate::format::{format_bytes, format_count};
+use crate::nix::{SystemGraph, load_closure_dump};
+use crate::util::{parse_store_name, short_name};
+
+/// One host's closure, loaded from a saved graph or a path-info dump.
+pub struct HostClosure {
+    pub name: String,
+    pub graph: SystemGraph,
+}
+
+impl HostClosure {
+    /// Loads `path`, naming the host after the file, e.g. `web1` for
+    /// `web1.graph.json`.
+    pub fn load(path: &Path) -> Result<Self> {
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.display().to_string());
+        let name = file_name
+            .strip_suffix(".json")
+            .map(|name| name.strip_suffix(".graph").unwrap_or(name))
+            .unwrap_or(&file_name)
+            .to_owned();
+        Ok(Self {
+            name,
+            graph: load_closure_dump(path)?,
+        })
+    }
+}
+
+/// Path count and NAR size of a set of store paths.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PathTotals {
+    pub paths: usize,
+    pub bytes: u64,
+}
+
+impl PathTotals {
+    fn add(&mut self, bytes: u64) {
+        self.paths += 1;
+        self.bytes += bytes;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HostSummary {
+    pub name: String,
+    pub closure: PathTotals,
+    /// Paths no other host has.
+    pub unique: PathTotals,
+    /// The largest unique paths, by NAR size.
+    pub largest_unique: Vec<(String, u64)>,
+}
+
+/// A package whose versions differ between hosts.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VersionDrift {
+    pub pname: String,
+    /// The hosts carrying each version.
+    pub versions: BTreeMap<String, Vec<String>>,
+}
+
+/// Which store paths a fleet of hosts shares, and which only one host has.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FleetReport {
+    pub hosts: Vec<HostSummary>,
+    /// Every path of any host: what a shared binary cache has to hold.
+    pub union: PathTotals,
+    /// Paths on at least two hosts.
+    pub shared: PathTotals,
+    /// Paths on every host.
+    pub common: PathTotals,
+    pub drift: Vec<VersionDrift>,
+}
+
+impl FleetReport {
+    /// Compares the closures of `hosts`, keeping the `top` largest unique
+    /// paths of each host.
+    pub fn compare(hosts: &[HostClosure], top: usize) -> Self {
+        // Store paths are content-addressed by their hash, so equal ids are
+        // the same path on every host.
+        let mut carriers = HashMap::<&str, (u64, Vec<usize>)>::new();
+        for (index, host) in hosts.iter().enumerate() {
+            for (id, node) in &host.graph.nodes {
+                carriers
+                    .entry(id.as_str())
+                    .or_insert_with(|| (node.nar_size, Vec::new()))
+                    .1
+                    .push(index);
+            }
+        }
+
+        let mut union = PathTotals::default();
+        let mut shared = PathTotals::default();
+        let mut common = PathTotals::default();
+        let mut unique = vec![Vec::new(); hosts.len()];
+        for (&id, (bytes, host_indices)) in &carriers {
+            union.add(*bytes);
+            if host_indices.len() > 1 {
+                shared.add(*bytes);
+            }
+            if host_indices.len() == hosts.len() {
+                common.add(*bytes);
+            }
+            if let [only] = host_indices[..] {
+                unique[only].push((id.to_owned(), *bytes));
+            }
+        }
+
+        let hosts_summaries = hosts
+            .iter()
+            .zip(unique)
+            .map(|(host, mut unique_paths)| {
+                let mut closure = PathTotals::default();
+                for node in host.graph.nodes.values() {
+                    closure.add(node.nar_size);
+                }
+                let mut unique = PathTotals::default();
+                for (_, bytes) in &unique_paths {
+                    unique.add(*bytes);
+                }
+                unique_paths.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+                unique_paths.truncate(top);
+                HostSummary {
+                    name: host.name.clone(),
+                    closure,
+                    unique,
+                    largest_unique: unique_paths,
+                }
+            })
+            .collect();
+
+        Self {
+            hosts: hosts_summaries,
+            union,
+            shared,
+            common,
+            drift: version_drift(hosts),
+        }
+    }
+
+    pub fn print(&self) {
+        for host in &self.hosts {
+            println!(
+                "{}: {} paths, {}; unique {} paths, {}",
+                host.name,
+                format_count(host.closure.paths as u64),
+                format_bytes(host.closure.bytes),
+                format_count(host.unique.paths as u64),
+                format_bytes(host.unique.bytes)
+            );
+            for (id, bytes) in &host.largest_unique {
+                println!("{:>12}  {}", format_bytes(*bytes), short_name(id));
+            }
+        }
+        println!();
+        for (label, totals) in [
+            ("all hosts together", self.union),
+            ("on two or more hosts", self.shared),
+            ("on every host", self.common),
+        ] {
+            println!(
+                "{label}: {} paths, {}",
+                format_count(totals.paths as u64),
+                format_bytes(totals.bytes)
+            );
+        }
+
+        if !self.drift.is_empty() {
+            println!();
+            println!("version drift:");
+            for drift in &self.drift {
+                let versions = drift
+                    .versions
+                    .iter()
+                    .map(|(version, hosts)| format!("{version} ({})", hosts.join(", ")))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                println!("  {}: {versions}", drift.pname);
+            }
+        }
+    }
+}
+
+/// Packages present on several hosts at differing sets of versions.
+fn version_drift(hosts: &[HostClosure]) -> Vec<VersionDrift> {
+    let mut versions_by_host = BTreeMap::<&str, Vec<BTreeSet<&str>>>::new();
+    for (index, host) in hosts.iter().enumerate() {
+        for id in host.graph.nodes.keys() {
+            let name = parse_store_name(short_name(id));
+            let Some(version) = name.version.filter(|_| !name.is_derivation) else {
+                continue;
+            };
+            versions_by_host
+                .entry(name.pname)
+                .or_insert_with(|| vec![BTreeSet::new(); hosts.len()])[index]
+                .insert(version);
+        }
+    }
+
+    versions_by_host
+        .into_iter()
+        .filter_map(|(pname, per_host)| {
+            let mut carried = per_host.iter().filter(|versions| !versions.is_empty());
+            let first = carried.next()?;
+            if carried.all(|versions| versions == first) {
+                return None;
+            }
+
+            let mut versions = BTreeMap::<String, Vec<String>>::new();
+            for (host, host_versions) in hosts.iter().zip(&per_host) {
+                for version in host_versions {
+                    versions
+                        .entry((*version).to_owned())
+                        .or_default()
+                        .push(host.name.clone());
+                }
+            }
+            Some(VersionDrift {
+                pname: pname.to_owned(),
+                versions,
+            })
+        })
+        .collect()
+}
+
+/// Loads every dump and prints how their closures overlap.
+pub fn run_fleet(dumps: &[impl AsRef<Path>], top: usize) -> Result<()> {
+    if dumps.len() < 2 {
+        bail!("comparing a fleet needs at least two dumps");
+    }
+    let hosts = dumps
+        .iter()
+        .map(|path| HostClosure::load(path.as_ref()))
+        .collect::<Result<Vec<_>>>()?;
+    FleetReport::compare(&hosts, top).print();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nix::generate_demo_graph;
+
+    #[test]
+    fn splits_paths_into_common_and_unique() {
+        let graph = generate_demo_graph(200);
+        let mut trimmed = graph.clone();
+        let (dropped_id, dropped) = trimmed
+            .nodes
+            .iter()
+            .find(|(id, _)| **id != graph.root_id)
+            .map(|(id, node)| (id.clone(), node.nar_size))
+            .unwrap();
+        trimmed.nodes.remove(&dropped_id);
+        let mut extra = trimmed.nodes[&graph.root_id].clone();
+        extra.id = "zzzz-hello-2.12".to_owned();
+        extra.nar_size = 7;
+        trimmed.nodes.insert(extra.id.clone(), extra);
+
+        let hosts = [
+            HostClosure {
+                name: "a".to_owned(),
+                graph: graph.clone(),
+            },
+            HostClosure {
+                name: "b".to_owned(),
+                graph: trimmed,
+            },
+        ];
+        let report = FleetReport::compare(&hosts, 3);
+        let total = graph.nodes.values().map(|node| node.nar_size).sum::<u64>();
+
+        assert_eq!(report.union.paths, graph.node_count() + 1);
+        assert_eq!(report.union.bytes, total + 7);
+        assert_eq!(report.common.paths, graph.node_count() - 1);
+        assert_eq!(report.common.bytes, total - dropped);
+        assert_eq!(report.shared, report.common);
+        assert_eq!(
+            report.hosts[0].unique,
+            PathTotals {
+                paths: 1,
+                bytes: dropped
+            }
+        );
+        assert_eq!(report.hosts[0].largest_unique, [(dropped_id, dropped)]);
+        assert_eq!(report.hosts[1].unique, PathTotals { paths: 1, bytes: 7 });
+    }
+
+    #[test]
+    fn reports_packages_at_different_versions() {
+        let mut graph = generate_demo_graph(20);
+        let mut host_b = graph.clone();
+        let mut probe = graph.nodes[&graph.root_id].clone();
+        probe.id = "aaaa-fleetprobe-3.0.14".to_owned();
+        graph.nodes.insert(probe.id.clone(), probe.clone());
+        probe.id = "bbbb-fleetprobe-3.3.1".to_owned();
+        host_b.nodes.insert(probe.id.clone(), probe);
+
+        let hosts = [
+            HostClosure {
+                name: "a".to_owned(),
+                graph,
+            },
+            HostClosure {
+                name: "b".to_owned(),
+                graph: host_b,
+            },
+        ];
+        let drift = FleetReport::compare(&hosts, 0).drift;
+        assert_eq!(
+            drift,
+            [VersionDrift {
+                pname: "fleetprobe".to_owned(),
+                versions: BTreeMap::from([
+                    ("3.0.14".to_owned(), vec!["a".to_owned()]),
+                    ("3.3.1".to_owned(), vec!["b".to_owned()]),
+                ]),
+            }]
+        );
+    }
+}
@@ -0,0 +1,260 @@
+use std::env;
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+/// How byte sizes are written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ByteUnits {
+    /// Powers of 1024: KiB, MiB, GiB.
+    #[default]
+    Iec,
+    /// Powers of 1000: kB, MB, GB.
+    Si,
+    /// Exact byte counts with digit grouping.
+    Raw,
+}
+
+impl ByteUnits {
+    pub const ALL: [Self; 3] = [Self::Iec, Self::Si, Self::Raw];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Iec => "IEC (MiB)",
+            Self::Si => "SI (MB)",
+            Self::Raw => "Raw bytes",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "iec" => Some(Self::Iec),
+            "si" => Some(Self::Si),
+            "raw" => Some(Self::Raw),
+            _ => None,
+        }
+    }
+}
+
+/// Languages that write `1.234,5` rather than `1,234.5`.
+const DOT_GROUPED_LOCALES: [&str; 16] = [
+    "bg", "ca", "da", "de", "el", "es", "hr", "id", "it", "nl", "pt", "ro", "sl", "sr", "tr", "vi",
+];
+/// Languages that write `1 234,5`, grouped with a no-break space.
+const SPACE_GROUPED_LOCALES: [&str; 14] = [
+    "cs", "et", "fi", "fr", "hu", "lt", "lv", "nb", "nn", "no", "pl", "ru", "sk", "sv",
+];
+
+/// Units and separators used for every size and count shown to the user,
+/// whether in the GUI, in exports meant for reading, or on the command line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NumberFormat {
+    pub units: ByteUnits,
+    pub decimal_separator: char,
+    pub group_separator: char,
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl NumberFormat {
+    const DEFAULT: Self = Self {
+        units: ByteUnits::Iec,
+        decimal_separator: '.',
+        group_separator: ',',
+    };
+
+    /// Separators for a locale name such as `de_DE.UTF-8` or `fr-CA`.
+    pub fn for_locale(locale: &str) -> Self {
+        let language = locale
+            .split(['_', '-', '.', '@'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let (decimal_separator, group_separator) = if DOT_GROUPED_LOCALES.contains(&&*language) {
+            (',', '.')
+        } else if SPACE_GROUPED_LOCALES.contains(&&*language) {
+            (',', '\u{a0}')
+        } else {
+            ('.', ',')
+        };
+
+        Self {
+            decimal_separator,
+            group_separator,
+            ..Self::DEFAULT
+        }
+    }
+
+    /// Separators for the numeric locale of the environment, following the
+    /// usual `LC_ALL`, `LC_NUMERIC`, `LANG` precedence.
+    pub fn from_env() -> Self {
+        ["LC_ALL", "LC_NUMERIC", "LANG"]
+            .into_iter()
+            .filter_map(|name| env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .map_or(Self::DEFAULT, |locale| Self::for_locale(&locale))
+    }
+
+    pub fn bytes(self, bytes: u64) -> String {
+        const IEC_UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+        const SI_UNITS: [&str; 5] = ["B", "kB", "MB", "GB", "TB"];
+
+        let (base, units) = match self.units {
+            ByteUnits::Iec => (1024.0, IEC_UNITS),
+            ByteUnits::Si => (1000.0, SI_UNITS),
+            ByteUnits::Raw => return format!("{} B", self.count(bytes)),
+        };
+
+        let mut value = bytes as f64;
+        let mut unit = 0usize;
+        while value >= base && unit < units.len() - 1 {
+            value /= base;
+            unit += 1;
+        }
+
+        if unit == 0 {
+            format!("{bytes} {}", units[unit])
+        } else {
+            format!("{} {}", self.decimal(value), units[unit])
+        }
+    }
+
+    /// A size change with an explicit sign, e.g. `+1.50 MiB`.
+    pub fn byte_delta(self, delta: i64) -> String {
+        let sign = if delta < 0 { '-' } else { '+' };
+        format!("{sign}{}", self.bytes(delta.unsigned_abs()))
+    }
+
+    /// An integer with thousands grouped, e.g. `12,345`.
+    pub fn count(self, value: u64) -> String {
+        let digits = value.to_string();
+        let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+        for (index, digit) in digits.chars().enumerate() {
+            if index > 0 && (digits.len() - index).is_multiple_of(3) {
+                grouped.push(self.group_separator);
+            }
+            grouped.push(digit);
+        }
+        grouped
+    }
+
+    fn decimal(self, value: f64) -> String {
+        let formatted = format!("{value:.2}");
+        if self.decimal_separator == '.' {
+            formatted
+        } else {
+            formatted.replace('.', &self.decimal_separator.to_string())
+        }
+    }
+}
+
+static NUMBER_FORMAT: RwLock<NumberFormat> = RwLock::new(NumberFormat::DEFAULT);
+
+pub fn number_format() -> NumberFormat {
+    *NUMBER_FORMAT
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+pub fn set_number_format(format: NumberFormat) {
+    *NUMBER_FORMAT
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = format;
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    number_format().bytes(bytes)
+}
+
+/// Formats a size change with an explicit sign, e.g. `+1.50 MiB`.
+pub fn format_byte_delta(delta: i64) -> String {
+    number_format().byte_delta(delta)
+}
+
+pub fn format_count(value: u64) -> String {
+    number_format().count(value)
+}
+
+/// Formats a point in time as a UTC date and time, e.g. `2024-05-17 09:41`.
+pub fn format_utc_datetime(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let (days, seconds_of_day) = (seconds / 86_400, seconds % 86_400);
+
+    // Civil date from days since 1970-01-01, counting in 400-year eras that
+    // start on March 1st so the leap day comes last.
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}",
+        seconds_of_day / 3_600,
+        seconds_of_day % 3_600 / 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn formats_each_unit_system() {
+        let iec = NumberFormat::DEFAULT;
+        assert_eq!(iec.bytes(512), "512 B");
+        assert_eq!(iec.bytes(1536), "1.50 KiB");
+
+        let si = NumberFormat {
+            units: ByteUnits::Si,
+            ..iec
+        };
+        assert_eq!(si.bytes(1_500_000), "1.50 MB");
+
+        let raw = NumberFormat {
+            units: ByteUnits::Raw,
+            ..iec
+        };
+        assert_eq!(raw.bytes(1_234_567), "1,234,567 B");
+        assert_eq!(raw.byte_delta(-1000), "-1,000 B");
+    }
+
+    #[test]
+    fn follows_locale_separators() {
+        let german = NumberFormat::for_locale("de_DE.UTF-8");
+        assert_eq!(german.bytes(1536), "1,50 KiB");
+        assert_eq!(german.count(1_234_567), "1.234.567");
+
+        let french = NumberFormat::for_locale("fr-CA");
+        assert_eq!(french.count(12_345), "12\u{a0}345");
+
+        assert_eq!(NumberFormat::for_locale("C"), NumberFormat::DEFAULT);
+        assert_eq!(NumberFormat::for_locale("en_US.UTF-8").count(999), "999");
+    }
+
+    #[test]
+    fn formats_utc_dates() {
+        let at =
+            |seconds| format_utc_datetime(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds));
+        assert_eq!(at(0), "1970-01-01 00:00");
+        assert_eq!(at(951_782_400), "2000-02-29 00:00");
+        assert_eq!(at(1_715_938_860), "2024-05-17 09:41");
+        assert_eq!(at(4_107_542_399), "2100-02-28 23:59");
+    }
+}
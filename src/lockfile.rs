@@ -0,0 +1,215 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::nix::SystemGraph;
+use crate::util::{parse_store_name, short_name};
+
+/// The expected `pname-version`s of a closure, one per line. Outputs are not
+/// distinguished, and `#` starts a comment.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClosureLock {
+    /// Expected versions per pname; unversioned names lock the empty string.
+    packages: BTreeMap<String, BTreeSet<String>>,
+}
+
+/// How a closure departs from its lockfile, sorted by kind then name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LockDeviation {
+    /// A path whose pname the lockfile does not list.
+    Unexpected { id: String },
+    /// A path whose pname is locked, but at other versions.
+    Drift { id: String, expected: Vec<String> },
+    /// A locked pname-version no path provides any more.
+    Missing { entry: String },
+}
+
+impl LockDeviation {
+    pub fn id(&self) -> Option<&str> {
+        match self {
+            Self::Unexpected { id } | Self::Drift { id, .. } => Some(id),
+            Self::Missing { .. } => None,
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        match self {
+            Self::Unexpected { id } => format!("{} is not in the lockfile", short_name(id)),
+            Self::Drift { id, expected } => {
+                format!("{} drifted from {}", short_name(id), expected.join(", "))
+            }
+            Self::Missing { entry } => format!("{entry} is no longer in the closure"),
+        }
+    }
+}
+
+fn lock_key(name: &str) -> Option<(&str, &str)> {
+    let name = parse_store_name(name);
+    (!name.is_derivation).then(|| (name.pname, name.version.unwrap_or_default()))
+}
+
+fn entry_name(pname: &str, version: &str) -> String {
+    if version.is_empty() {
+        pname.to_owned()
+    } else {
+        format!("{pname}-{version}")
+    }
+}
+
+impl ClosureLock {
+    pub fn load(path: &Path) -> Result<Self> {
+        let raw = fs::read_to_string(path)
+            .with_context(|| format!("failed to read lockfile {}", path.display()))?;
+        Ok(Self::parse(&raw))
+    }
+
+    pub fn parse(raw: &str) -> Self {
+        let mut lock = Self::default();
+        for line in raw.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if let Some((pname, version)) = lock_key(line).filter(|(pname, _)| !pname.is_empty()) {
+                lock.packages
+                    .entry(pname.to_owned())
+                    .or_default()
+                    .insert(version.to_owned());
+            }
+        }
+        lock
+    }
+
+    /// Locks every pname-version currently in `graph`.
+    pub fn from_graph(graph: &SystemGraph) -> Self {
+        let mut lock = Self::default();
+        for id in graph.nodes.keys() {
+            if let Some((pname, version)) = lock_key(short_name(id)) {
+                lock.packages
+                    .entry(pname.to_owned())
+                    .or_default()
+                    .insert(version.to_owned());
+            }
+        }
+        lock
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.render())
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    fn render(&self) -> String {
+        let mut raw = String::from("# Expected pname-versions of the closure, one per line.\n");
+        for (pname, versions) in &self.packages {
+            for version in versions {
+                raw.push_str(&entry_name(pname, version));
+                raw.push('\n');
+            }
+        }
+        raw
+    }
+
+    pub fn len(&self) -> usize {
+        self.packages.values().map(BTreeSet::len).sum()
+    }
+
+    pub fn check(&self, graph: &SystemGraph) -> Vec<LockDeviation> {
+        let mut present = HashMap::<&str, BTreeSet<&str>>::new();
+        let mut deviations = Vec::new();
+        let mut drifted = BTreeSet::new();
+        for id in graph.nodes.keys() {
+            let Some((pname, version)) = lock_key(short_name(id)) else {
+                continue;
+            };
+            present.entry(pname).or_default().insert(version);
+            match self.packages.get(pname) {
+                None => deviations.push(LockDeviation::Unexpected { id: id.clone() }),
+                Some(versions) if !versions.contains(version) => {
+                    drifted.insert(pname);
+                    deviations.push(LockDeviation::Drift {
+                        id: id.clone(),
+                        expected: versions
+                            .iter()
+                            .map(|version| entry_name(pname, version))
+                            .collect(),
+                    });
+                }
+                Some(_) => {}
+            }
+        }
+
+        // A drifted pname already explains its missing versions.
+        for (pname, versions) in &self.packages {
+            if drifted.contains(pname.as_str()) {
+                continue;
+            }
+            for version in versions {
+                if !present
+                    .get(pname.as_str())
+                    .is_some_and(|present| present.contains(version.as_str()))
+                {
+                    deviations.push(LockDeviation::Missing {
+                        entry: entry_name(pname, version),
+                    });
+                }
+            }
+        }
+
+        deviations.sort_by_cached_key(|deviation| {
+            let rank = match deviation {
+                LockDeviation::Unexpected { .. } => 0,
+                LockDeviation::Drift { .. } => 1,
+                LockDeviation::Missing { .. } => 2,
+            };
+            (rank, deviation.summary())
+        });
+        deviations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nix::generate_demo_graph;
+
+    #[test]
+    fn reports_additions_drifts_and_removals() {
+        let graph = generate_demo_graph(200);
+        let lock = ClosureLock::from_graph(&graph);
+        assert!(lock.check(&graph).is_empty());
+
+        assert_eq!(ClosureLock::parse(&lock.render()), lock);
+
+        let mut edited = lock.clone();
+        let (removed, _) = edited.packages.pop_first().unwrap();
+        let (drifted, versions) = edited
+            .packages
+            .iter_mut()
+            .find(|(_, versions)| versions.len() == 1 && !versions.contains(""))
+            .unwrap();
+        let drifted = drifted.clone();
+        *versions = BTreeSet::from(["0.0.1".to_owned()]);
+        edited
+            .packages
+            .insert("gone".to_owned(), BTreeSet::from([String::new()]));
+
+        let deviations = edited.check(&graph);
+        assert!(deviations.iter().any(|deviation| matches!(
+            deviation,
+            LockDeviation::Unexpected { id } if lock_key(short_name(id)).unwrap().0 == removed
+        )));
+        assert!(deviations.iter().any(|deviation| matches!(
+            deviation,
+            LockDeviation::Drift { id, expected }
+                if lock_key(short_name(id)).unwrap().0 == drifted
+                    && *expected == [format!("{drifted}-0.0.1")]
+        )));
+        assert!(deviations.contains(&LockDeviation::Missing {
+            entry: "gone".to_owned()
+        }));
+        assert!(!deviations.iter().any(|deviation| *deviation
+            == LockDeviation::Missing {
+                entry: format!("{drifted}-0.0.1")
+            }));
+    }
+}
@@ -1,27 +1,444 @@
 mod app;
+mod export;
+mod findings;
+mod fleet;
+mod format;
+mod lockfile;
+mod name_filter;
 mod nix;
+mod scenario;
 mod util;
 
-use clap::Parser;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand};
+
+use export::ExportFormat;
+use scenario::Scenario;
 
 #[derive(Debug, Parser)]
 #[command(author, version, about)]
 struct Args {
-    #[arg(long, default_value = "/run/current-system")]
-    system_path: String,
+    /// Store path, profile or flake installable (e.g. nixpkgs#hello) whose
+    /// closure to explore; repeat to merge several closures. Defaults to the
+    /// root explored last in the GUI, or /run/current-system.
+    #[arg(long, visible_alias = "root")]
+    system_path: Vec<String>,
+
+    /// Seconds to wait for each nix command before killing it.
+    #[arg(long, default_value_t = nix::DEFAULT_NIX_TIMEOUT.as_secs())]
+    nix_timeout: u64,
+
+    /// Save every nix invocation's arguments and output to this directory.
+    #[arg(long, value_name = "DIR", conflicts_with = "replay_nix")]
+    record_nix: Option<PathBuf>,
+
+    /// Replay nix invocations recorded with --record-nix instead of running nix.
+    #[arg(long, value_name = "DIR")]
+    replay_nix: Option<PathBuf>,
+
+    /// The nix executable to run instead of `nix` from PATH.
+    #[arg(long, value_name = "PATH")]
+    nix_bin: Option<PathBuf>,
+
+    /// Extra argument passed to every nix command; may be repeated.
+    #[arg(long, value_name = "ARG", allow_hyphen_values = true)]
+    nix_arg: Vec<String>,
+
+    /// Environment variable set for every nix command; may be repeated.
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_env_var)]
+    nix_env: Vec<(String, String)>,
+
+    /// nix-index database used to look up nixpkgs attribute paths; defaults to
+    /// nix-locate's own.
+    #[arg(long, value_name = "DIR")]
+    nix_index_db: Option<PathBuf>,
+
+    /// Look up per-path metadata, referrers and validity over the nix
+    /// daemon's socket instead of spawning nix; defaults to the system
+    /// daemon's socket when given without a path.
+    #[arg(
+        long,
+        value_name = "PATH",
+        num_args = 0..=1,
+        default_missing_value = nix::DEFAULT_DAEMON_SOCKET
+    )]
+    daemon_socket: Option<PathBuf>,
+
+    /// Read closures straight from the store database instead of spawning
+    /// nix, falling back to nix when it cannot be read; defaults to the local
+    /// store's database when given without a path.
+    #[arg(
+        long,
+        value_name = "PATH",
+        num_args = 0..=1,
+        default_missing_value = nix::DEFAULT_STORE_DB
+    )]
+    store_db: Option<PathBuf>,
+
+    /// Always query nix for the closure instead of reusing the graph cached
+    /// under ~/.cache/nix-analisa for an unchanged root.
+    #[arg(long)]
+    no_graph_cache: bool,
+
+    /// Disable vsync; combine with the in-app frame rate cap to limit CPU use.
+    #[arg(long)]
+    no_vsync: bool,
+
+    /// Check graph invariants after every rebuild (always on in debug builds).
+    #[arg(long)]
+    validate: bool,
+
+    /// Explore a synthetic closure with this many paths instead of querying nix.
+    #[arg(long, value_name = "N")]
+    demo: Option<usize>,
+
+    /// Diff the closure against this one, e.g. an earlier system generation.
+    #[arg(long, value_name = "PATH")]
+    compare_path: Option<String>,
+
+    /// Place the graph by a layout written with Export layout, matching paths
+    /// by name without the hash.
+    #[arg(long, value_name = "FILE")]
+    layout: Option<PathBuf>,
+
+    /// Load every valid store path, shard by shard, instead of one closure.
+    #[arg(long, conflicts_with = "demo")]
+    whole_store: bool,
+
+    /// Weigh each edge by how many files in the referrer mention the target.
+    #[arg(long, conflicts_with_all = ["demo", "whole_store"])]
+    edge_weights: bool,
+
+    /// Read each path's pname, version, outputs and licenses from its
+    /// derivation with `nix derivation show`.
+    #[arg(long, conflicts_with_all = ["demo", "whole_store"])]
+    derivation_info: bool,
+
+    /// Open a graph saved with File → Save graph instead of querying nix.
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["demo", "whole_store", "edge_weights", "derivation_info"]
+    )]
+    load_graph: Option<PathBuf>,
+
+    /// Write the closure as json, csv, dot or graphml instead of opening the GUI.
+    /// Not available with a subcommand; scenarios list theirs in [[export]].
+    #[arg(long, value_name = "FORMAT", value_parser = parse_export_format, requires = "output")]
+    export: Option<ExportFormat>,
+
+    /// Byte units for sizes in the GUI, exports and reports: iec, si or raw.
+    #[arg(long, value_name = "UNITS", value_parser = parse_byte_units)]
+    byte_units: Option<format::ByteUnits>,
+
+    /// Locale for decimal and thousands separators, e.g. de_DE; defaults to
+    /// LC_ALL, LC_NUMERIC or LANG.
+    #[arg(long, value_name = "LOCALE")]
+    number_locale: Option<String>,
+
+    /// File written by --export.
+    #[arg(long, value_name = "FILE", requires = "export")]
+    output: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Run a scenario file, printing a report and writing its exports.
+    Run {
+        scenario: PathBuf,
+
+        /// Open the scenario in the GUI instead of running headlessly.
+        #[arg(long)]
+        gui: bool,
+
+        /// Keep running and report again after every store change.
+        #[arg(long, conflicts_with = "gui")]
+        watch: bool,
+
+        /// With --watch, exit non-zero as soon as a budget is exceeded.
+        #[arg(long, requires = "watch")]
+        exit_on_violation: bool,
+
+        /// Write Prometheus metrics for the closure and its budgets to FILE
+        /// after every report.
+        #[arg(long, value_name = "FILE", conflicts_with = "gui")]
+        metrics: Option<PathBuf>,
+    },
+    /// Compare the closures of several hosts, each a graph saved with File →
+    /// Save graph or the output of `nix path-info --recursive --json`.
+    Fleet {
+        #[arg(required = true, num_args = 2..)]
+        dumps: Vec<PathBuf>,
+
+        /// How many of each host's largest unique paths to list.
+        #[arg(long, value_name = "N", default_value_t = 5)]
+        top: usize,
+    },
+}
+
+fn parse_env_var(raw: &str) -> Result<(String, String), String> {
+    match raw.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_owned(), value.to_owned())),
+        _ => Err(format!("expected KEY=VALUE, got {raw:?}")),
+    }
 }
 
-fn main() -> eframe::Result<()> {
+fn parse_byte_units(raw: &str) -> Result<format::ByteUnits, String> {
+    format::ByteUnits::parse(raw).ok_or_else(|| format!("expected iec, si or raw, got {raw:?}"))
+}
+
+fn parse_export_format(raw: &str) -> Result<ExportFormat, String> {
+    ExportFormat::ALL
+        .into_iter()
+        .find(|format| format.extension() == raw)
+        .ok_or_else(|| format!("expected json, csv, dot or graphml, got {raw:?}"))
+}
+
+fn main() -> anyhow::Result<ExitCode> {
     let args = Args::parse();
-    let system_path = args.system_path;
+    // Clap cannot make an argument conflict with a subcommand.
+    if args.export.is_some() && args.command.is_some() {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--export cannot be used with a subcommand; \
+                 scenarios write their exports from [[export]] tables",
+            )
+            .exit();
+    }
+    let mut number_format = match &args.number_locale {
+        Some(locale) => format::NumberFormat::for_locale(locale),
+        None => format::NumberFormat::from_env(),
+    };
+    if let Some(units) = args.byte_units {
+        number_format.units = units;
+    }
+    format::set_number_format(number_format);
+
+    let scenario = match &args.command {
+        Some(Command::Run { scenario, .. }) => Some(Scenario::load(scenario)?),
+        Some(Command::Fleet { dumps, top }) => {
+            fleet::run_fleet(dumps, *top)?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        None => None,
+    };
+
+    let mut nix_settings = nix::NixSettings {
+        timeout: Duration::from_secs(args.nix_timeout),
+        transcript: match (args.record_nix, args.replay_nix) {
+            (Some(dir), _) => Some(nix::Transcript::Record(dir)),
+            (None, Some(dir)) => Some(nix::Transcript::Replay(dir)),
+            (None, None) => None,
+        },
+        ..nix::NixSettings::default()
+    };
+    // Command-line settings take precedence over the scenario's [nix] table.
+    if let Some(scenario) = &scenario {
+        scenario.nix.apply(&mut nix_settings);
+    }
+    if let Some(binary) = args.nix_bin {
+        nix_settings.binary = binary;
+    }
+    nix_settings.extra_args.extend(args.nix_arg);
+    nix_settings.env.extend(args.nix_env);
+    if let Some(database) = args.nix_index_db {
+        nix_settings.nix_index_db = Some(database);
+    }
+    if let Some(socket) = args.daemon_socket {
+        nix_settings.daemon_socket = Some(socket);
+    }
+    if let Some(database) = args.store_db {
+        nix_settings.store_db = Some(database);
+    }
+    if args.no_graph_cache {
+        nix_settings.graph_cache = false;
+    }
+
+    let mut system_paths = args.system_path;
+    if system_paths.is_empty() {
+        // Only plain GUI sessions pick up where the last one left off.
+        let resume = args.command.is_none()
+            && args.export.is_none()
+            && args.demo.is_none()
+            && !args.whole_store
+            && args.load_graph.is_none();
+        system_paths.push(
+            resume
+                .then(app::saved_root)
+                .flatten()
+                .unwrap_or_else(|| "/run/current-system".to_owned()),
+        );
+    }
+    let system_path = system_paths.remove(0);
+    if !system_paths.is_empty()
+        && (args.demo.is_some() || args.whole_store || args.load_graph.is_some())
+    {
+        anyhow::bail!(
+            "several --system-path roots cannot be combined with --demo, --whole-store or --load-graph"
+        );
+    }
+
+    let app_options = app::AppOptions {
+        nix_settings: nix_settings.clone(),
+        validate_graph: args.validate,
+        demo_nodes: args.demo,
+        whole_store: args.whole_store,
+        edge_weights: args.edge_weights,
+        derivation_info: args.derivation_info,
+        graph_file: args.load_graph,
+        extra_roots: system_paths,
+        compare_path: args.compare_path,
+        layout_file: args.layout,
+    };
+
+    match (args.command, scenario) {
+        (
+            Some(Command::Run {
+                gui,
+                watch,
+                exit_on_violation,
+                metrics,
+                ..
+            }),
+            Some(scenario),
+        ) => {
+            let within_budget = if gui {
+                run_gui(
+                    scenario.root.clone(),
+                    Some(Arc::new(scenario)),
+                    app_options,
+                    !args.no_vsync,
+                )?;
+                true
+            } else if watch {
+                scenario.watch(
+                    &nix_settings,
+                    args.validate,
+                    metrics.as_deref(),
+                    exit_on_violation,
+                )?
+            } else {
+                scenario.run_headless(&nix_settings, args.validate, metrics.as_deref())?
+            };
+            Ok(if within_budget {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            })
+        }
+        (None, None) if let Some((format, output)) = args.export.zip(args.output) => {
+            run_export(&system_path, &app_options, format, &output)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        _ => {
+            let system_path = match args.demo {
+                Some(node_count) => format!("demo ({node_count} paths)"),
+                None => system_path,
+            };
+            run_gui(system_path, None, app_options, !args.no_vsync)?;
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
+
+/// Collects the graph the GUI would open and writes every path, largest
+/// closure first, without creating a window.
+fn run_export(
+    system_path: &str,
+    options: &app::AppOptions,
+    format: ExportFormat,
+    output: &Path,
+) -> anyhow::Result<()> {
+    let control = nix::NixControl::new(&options.nix_settings);
+    let mut graph = if let Some(node_count) = options.demo_nodes {
+        nix::generate_demo_graph(node_count)
+    } else if let Some(path) = &options.graph_file {
+        nix::load_graph(path)?
+    } else if options.whole_store {
+        nix::collect_store_shards(&control, system_path)?
+    } else if !options.extra_roots.is_empty() {
+        let system_paths = [system_path.to_owned()]
+            .into_iter()
+            .chain(options.extra_roots.iter().cloned())
+            .collect::<Vec<_>>();
+        nix::collect_multi_root_graph(&control, &system_paths)?
+    } else {
+        nix::collect_system_graph(&control, system_path)?
+    };
+    if options.edge_weights {
+        nix::scan_edge_weights(&control, &mut graph)?;
+    }
+    if options.derivation_info {
+        nix::enrich_derivations(&control, &mut graph)?;
+    }
+
+    if options.validate_graph {
+        let errors = graph.integrity_errors();
+        if !errors.is_empty() {
+            anyhow::bail!("graph integrity check failed:\n{}", errors.join("\n"));
+        }
+    }
+    for limitation in &graph.limitations {
+        eprintln!("warning: {limitation}");
+    }
+
+    let ids = graph.ranked_by_metric(nix::SizeMetric::ClosureSize, graph.node_count());
+    export::export_nodes(&graph, &ids, format, output)?;
+
+    println!("root: {}", util::short_name(&graph.root_id));
+    for id in graph.ranked_by_metric(nix::SizeMetric::RetainedSize, 10) {
+        let chain = graph
+            .shortest_path_from_root(&id)
+            .unwrap_or_default()
+            .iter()
+            .map(|step| util::short_name(step))
+            .collect::<Vec<_>>()
+            .join(" -> ");
+        println!(
+            "{:>10} retained  {chain}",
+            format::format_bytes(graph.nodes[&id].retained_size)
+        );
+    }
+    println!(
+        "wrote {} paths to {}",
+        format::format_count(ids.len() as u64),
+        output.display()
+    );
+    Ok(())
+}
+
+fn run_gui(
+    system_path: String,
+    scenario: Option<Arc<Scenario>>,
+    app_options: app::AppOptions,
+    vsync: bool,
+) -> anyhow::Result<()> {
     let options = eframe::NativeOptions {
         viewport: eframe::egui::ViewportBuilder::default().with_inner_size([1440.0, 920.0]),
+        vsync,
         ..Default::default()
     };
 
     eframe::run_native(
         "nix-analisá",
         options,
-        Box::new(move |cc| Ok(Box::new(app::NixAnalyzeApp::new(cc, system_path)))),
+        Box::new(move |cc| {
+            Ok(Box::new(app::NixAnalyzeApp::new(
+                cc,
+                system_path,
+                scenario,
+                app_options,
+            )))
+        }),
     )
+    .map_err(|error| anyhow::anyhow!("failed to run GUI: {error}"))
 }
@@ -0,0 +1,178 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+
+use crate::util::short_name;
+
+/// How one term of a name filter matches a path.
+#[derive(Debug)]
+enum NameMatcher {
+    /// A glob over the hash-stripped store name; `*` matches any run of
+    /// characters and `?` any one.
+    Glob(String),
+    /// A regular expression searched for in the hash-stripped store name.
+    Regex(Regex),
+    /// A prefix of the full store path.
+    PathPrefix(String),
+}
+
+impl NameMatcher {
+    fn matches(&self, name: &str, full_path: &str) -> bool {
+        match self {
+            Self::Glob(pattern) => glob_matches(pattern, name),
+            Self::Regex(regex) => regex.is_match(name),
+            Self::PathPrefix(prefix) => full_path.starts_with(prefix.as_str()),
+        }
+    }
+}
+
+/// One term of a name filter, e.g. `python3*` or `!*-doc`.
+#[derive(Debug)]
+pub struct NameRule {
+    /// The term as written, for showing it as a chip.
+    pub text: String,
+    /// Whether the term hides what it matches instead of keeping it.
+    pub exclude: bool,
+    matcher: NameMatcher,
+}
+
+impl NameRule {
+    fn parse(text: &str) -> Result<Self> {
+        let (exclude, pattern) = match text.strip_prefix('!') {
+            Some(pattern) => (true, pattern),
+            None => (false, text),
+        };
+        let matcher = if let Some(expression) = pattern.strip_prefix("re:") {
+            NameMatcher::Regex(
+                Regex::new(expression)
+                    .with_context(|| format!("invalid regular expression in {text:?}"))?,
+            )
+        } else if pattern.starts_with('/') {
+            NameMatcher::PathPrefix(pattern.to_owned())
+        } else {
+            NameMatcher::Glob(pattern.to_owned())
+        };
+        Ok(Self {
+            text: text.to_owned(),
+            exclude,
+            matcher,
+        })
+    }
+}
+
+/// Restricts the rendered paths by name. Terms are separated by spaces or
+/// commas: globs match the store name without its hash, terms starting with
+/// `re:` are regular expressions searched for in it, and terms starting with
+/// `/` are prefixes of the full store path. A `!` in front hides what a term
+/// matches; the other terms keep only what at least one of them matches.
+#[derive(Debug, Default)]
+pub struct NameFilter {
+    pub rules: Vec<NameRule>,
+}
+
+impl NameFilter {
+    pub fn parse(expression: &str) -> Result<Self> {
+        let rules = expression
+            .split([',', ' ', '\t', '\n'])
+            .filter(|term| !term.is_empty() && *term != "!")
+            .map(NameRule::parse)
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Whether the path `id`, stored at `full_path`, passes the filter.
+    pub fn allows(&self, id: &str, full_path: &str) -> bool {
+        let name = short_name(id);
+        let mut has_includes = false;
+        let mut included = false;
+        for rule in &self.rules {
+            let matched = rule.matcher.matches(name, full_path);
+            if rule.exclude {
+                if matched {
+                    return false;
+                }
+            } else {
+                has_includes = true;
+                included |= matched;
+            }
+        }
+        included || !has_includes
+    }
+}
+
+/// Whether `pattern` matches all of `text`, with `*` for any run of
+/// characters and `?` for any one.
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+    let (mut p, mut t) = (0, 0);
+    // The last `*` seen and the text position it currently stands up to.
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, start)) => {
+                    backtrack = Some((star, start + 1));
+                    p = star + 1;
+                    t = start + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allows(expression: &str, id: &str) -> bool {
+        NameFilter::parse(expression)
+            .unwrap()
+            .allows(id, &format!("/nix/store/{id}"))
+    }
+
+    #[test]
+    fn globs_match_whole_names() {
+        assert!(glob_matches("python3*", "python3-3.12.4"));
+        assert!(glob_matches("*-doc", "gtk4-4.14.4-doc"));
+        assert!(glob_matches("gl?bc-*", "glibc-2.39-52"));
+        assert!(glob_matches("*", ""));
+        assert!(!glob_matches("python3*", "my-python3"));
+        assert!(!glob_matches("*-doc", "gtk4-doc-extra"));
+    }
+
+    #[test]
+    fn includes_narrow_and_excludes_hide() {
+        let python = "abc-python3-3.12.4";
+        let python_doc = "abc-python3-3.12.4-doc";
+        let perl = "abc-perl-5.38.2";
+
+        assert!(allows("", perl));
+        assert!(allows("python3*", python));
+        assert!(!allows("python3*", perl));
+        assert!(allows("python3*, perl*", perl));
+        assert!(!allows("python3* !*-doc", python_doc));
+        assert!(allows("!*-doc", perl));
+        assert!(allows(r"re:^perl-5\.\d+", perl));
+        assert!(!allows("!/nix/store/abc-perl", perl));
+        assert!(allows("!/nix/store/abc-perl", python));
+    }
+
+    #[test]
+    fn rejects_invalid_regexes() {
+        assert!(NameFilter::parse("re:(").is_err());
+    }
+}
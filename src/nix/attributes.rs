@@ -0,0 +1,72 @@
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{Context, Result, anyhow};
+
+const SEARCH_URL: &str = "https://search.nixos.org/packages?channel=unstable&show=";
+
+/// Looks a store path up in a nix-index database with `nix-locate`, returning
+/// the nixpkgs attribute paths that build it, without the output suffix.
+///
+/// `database` overrides nix-locate's default of `~/.cache/nix-index`.
+pub fn locate_attribute_paths(database: Option<&Path>, store_id: &str) -> Result<Vec<String>> {
+    let (hash, _) = store_id
+        .split_once('-')
+        .ok_or_else(|| anyhow!("{store_id} is not a store path name"))?;
+
+    let mut command = Command::new("nix-locate");
+    command.args(["--minimal", "--regex", "--hash", hash]);
+    if let Some(database) = database {
+        command.arg("--db").arg(database);
+    }
+    // Every file in the path matches; only the owning attributes matter.
+    let output = command
+        .arg(".")
+        .output()
+        .context("failed to run nix-locate; is nix-index installed?")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "nix-locate failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(parse_attribute_paths(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
+
+/// The search.nixos.org page for an attribute path.
+pub fn attribute_search_url(attribute_path: &str) -> String {
+    format!("{SEARCH_URL}{attribute_path}&query={attribute_path}")
+}
+
+/// Parses `--minimal` lines such as `hello.out` or `(python3Packages.numpy.dev)`.
+fn parse_attribute_paths(output: &str) -> Vec<String> {
+    let mut paths = Vec::new();
+    for line in output.lines() {
+        let attribute = line.trim().trim_start_matches('(').trim_end_matches(')');
+        let attribute = attribute
+            .rsplit_once('.')
+            .map_or(attribute, |(attribute, _output)| attribute);
+        if !attribute.is_empty() && !paths.iter().any(|path| path == attribute) {
+            paths.push(attribute.to_owned());
+        }
+    }
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_outputs_and_duplicates() {
+        let output = "hello.out\nhello.out\n(python3Packages.numpy.dev)\n\n";
+
+        assert_eq!(
+            parse_attribute_paths(output),
+            ["hello", "python3Packages.numpy"]
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::{HashMap, HashSet};
+
+use super::graph::SystemGraph;
+
+/// How much of the closure one direct reference of the root accounts for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TopLevelShare {
+    pub id: String,
+    /// NAR size of the reference's own closure.
+    pub closure_size: u64,
+    /// Bytes only this reference keeps in the closure: its retained size.
+    pub exclusive_size: u64,
+    /// Paths only this reference keeps in the closure, itself included.
+    pub exclusive_paths: usize,
+}
+
+impl TopLevelShare {
+    /// Bytes of this reference's closure that other references keep too.
+    pub fn shared_size(&self) -> u64 {
+        self.closure_size.saturating_sub(self.exclusive_size)
+    }
+}
+
+impl SystemGraph {
+    /// Attributes the closure to the root's direct references, e.g. the
+    /// system path and the kernel of a NixOS system, largest exclusive size
+    /// first. A reference's exclusive share is what removing it would free;
+    /// the rest of its closure is shared with other references.
+    pub fn top_level_attribution(&self) -> Vec<TopLevelShare> {
+        let Some(root) = self.nodes.get(&self.root_id) else {
+            return Vec::new();
+        };
+        let mut seen = HashSet::new();
+        let children = root
+            .references
+            .iter()
+            .filter(|id| **id != self.root_id && self.nodes.contains_key(*id))
+            .filter(|id| seen.insert(id.as_str()))
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        let slot_by_id = children
+            .iter()
+            .enumerate()
+            .map(|(slot, &id)| (id, slot))
+            .collect::<HashMap<_, _>>();
+
+        // A path belongs to the reference whose dominator subtree holds it.
+        let mut exclusive_paths = vec![0; children.len()];
+        for node in self.nodes.values() {
+            let mut cursor = Some(node.id.as_str());
+            while let Some(id) = cursor {
+                if let Some(&slot) = slot_by_id.get(id) {
+                    exclusive_paths[slot] += 1;
+                    break;
+                }
+                cursor = self
+                    .nodes
+                    .get(id)
+                    .and_then(|node| node.immediate_dominator.as_deref());
+            }
+        }
+
+        let mut shares = children
+            .iter()
+            .zip(exclusive_paths)
+            .map(|(&id, exclusive_paths)| {
+                let node = &self.nodes[id];
+                TopLevelShare {
+                    id: id.to_owned(),
+                    closure_size: node.closure_size,
+                    exclusive_size: node.retained_size,
+                    exclusive_paths,
+                }
+            })
+            .collect::<Vec<_>>();
+        shares.sort_by(|a, b| {
+            b.exclusive_size
+                .cmp(&a.exclusive_size)
+                .then_with(|| b.closure_size.cmp(&a.closure_size))
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        shares
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nix::generate_demo_graph;
+
+    #[test]
+    fn exclusive_shares_are_what_removal_frees() {
+        let graph = generate_demo_graph(400);
+        let shares = graph.top_level_attribution();
+        assert_eq!(shares.len(), graph.nodes[&graph.root_id].references.len());
+        assert!(
+            shares
+                .windows(2)
+                .all(|pair| pair[0].exclusive_size >= pair[1].exclusive_size)
+        );
+
+        for share in &shares {
+            let freed = graph.unreachable_without_paths(&HashSet::from([share.id.as_str()]));
+            let freed_size = freed.iter().map(|id| graph.nodes[id].nar_size).sum::<u64>();
+            assert_eq!(share.exclusive_size, freed_size);
+            assert_eq!(share.exclusive_paths, freed.len());
+            assert!(share.exclusive_size <= share.closure_size);
+        }
+    }
+}
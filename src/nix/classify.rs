@@ -0,0 +1,287 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::graph::NodeRecord;
+use super::nix_cmd::NixControl;
+
+use crate::util::short_name;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NodeClass {
+    Derivation,
+    FixedOutput,
+    DevOutput,
+    DocOutput,
+    KernelFirmware,
+    Font,
+    Elf,
+    Unsigned,
+}
+
+impl NodeClass {
+    pub const ALL: [Self; 8] = [
+        Self::Derivation,
+        Self::FixedOutput,
+        Self::DevOutput,
+        Self::DocOutput,
+        Self::KernelFirmware,
+        Self::Font,
+        Self::Elf,
+        Self::Unsigned,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Derivation => ".drv files",
+            Self::FixedOutput => "Fixed-output sources",
+            Self::DevOutput => "Dev outputs",
+            Self::DocOutput => "Doc outputs",
+            Self::KernelFirmware => "Kernels / firmware",
+            Self::Font => "Fonts",
+            Self::Elf => "ELF binaries",
+            Self::Unsigned => "Unsigned paths",
+        }
+    }
+
+    /// The name used in saved graphs and scenario files, e.g. `doc-output`.
+    pub fn key(self) -> &'static str {
+        match self {
+            Self::Derivation => "derivation",
+            Self::FixedOutput => "fixed-output",
+            Self::DevOutput => "dev-output",
+            Self::DocOutput => "doc-output",
+            Self::KernelFirmware => "kernel-firmware",
+            Self::Font => "font",
+            Self::Elf => "elf",
+            Self::Unsigned => "unsigned",
+        }
+    }
+
+    fn bit(self) -> u16 {
+        1 << self as u16
+    }
+}
+
+/// Serialized as a list of class names so that saved graphs survive
+/// reordering the classes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "Vec<NodeClass>", into = "Vec<NodeClass>")]
+pub struct NodeClasses(u16);
+
+impl From<Vec<NodeClass>> for NodeClasses {
+    fn from(classes: Vec<NodeClass>) -> Self {
+        classes
+            .into_iter()
+            .fold(Self::NONE, |classes, class| classes.with(class))
+    }
+}
+
+impl From<NodeClasses> for Vec<NodeClass> {
+    fn from(classes: NodeClasses) -> Self {
+        classes.iter().collect()
+    }
+}
+
+impl NodeClasses {
+    pub const NONE: Self = Self(0);
+
+    pub fn all() -> Self {
+        NodeClass::ALL
+            .into_iter()
+            .fold(Self::NONE, |classes, class| classes.with(class))
+    }
+
+    pub fn contains(self, class: NodeClass) -> bool {
+        self.0 & class.bit() != 0
+    }
+
+    pub fn intersection(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+
+    pub fn intersects(self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn with(self, class: NodeClass) -> Self {
+        Self(self.0 | class.bit())
+    }
+
+    pub fn set(&mut self, class: NodeClass, enabled: bool) {
+        if enabled {
+            self.0 |= class.bit();
+        } else {
+            self.0 &= !class.bit();
+        }
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = NodeClass> {
+        NodeClass::ALL
+            .into_iter()
+            .filter(move |class| self.contains(*class))
+    }
+}
+
+const DOC_SUFFIXES: [&str; 5] = ["-doc", "-man", "-info", "-devdoc", "-devman"];
+const FONT_MARKERS: [&str; 3] = ["-ttf", "-otf", "noto-"];
+const KERNEL_MARKERS: [&str; 3] = ["firmware", "-modules", "-kernel"];
+const ELF_DIRS: [&str; 4] = ["bin", "sbin", "lib", "libexec"];
+const ELF_PROBE_LIMIT: usize = 64;
+const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+
+/// Classes read from a path's name and metadata; see [`classify_elf_paths`]
+/// for [`NodeClass::Elf`].
+pub(super) fn classify_node(id: &str, fixed_output: bool, signed: bool) -> NodeClasses {
+    let name = short_name(id).to_ascii_lowercase();
+    let mut classes = NodeClasses::NONE;
+
+    if name.ends_with(".drv") {
+        classes = classes.with(NodeClass::Derivation);
+    }
+    if fixed_output {
+        classes = classes.with(NodeClass::FixedOutput);
+    }
+    if name.ends_with("-dev") {
+        classes = classes.with(NodeClass::DevOutput);
+    }
+    if DOC_SUFFIXES.iter().any(|suffix| name.ends_with(suffix)) {
+        classes = classes.with(NodeClass::DocOutput);
+    }
+    if is_kernel_name(&name) || KERNEL_MARKERS.iter().any(|marker| name.contains(marker)) {
+        classes = classes.with(NodeClass::KernelFirmware);
+    }
+    if is_font_name(&name) {
+        classes = classes.with(NodeClass::Font);
+    }
+    if !signed && !fixed_output {
+        classes = classes.with(NodeClass::Unsigned);
+    }
+
+    classes
+}
+
+/// Marks the paths that ship ELF binaries by reading the first files of
+/// their binary and library directories. That opens the paths themselves, so
+/// it only runs while they are on this machine's filesystem: not when a
+/// transcript is replayed or another store is queried. Cached and saved
+/// graphs keep the classes found when they were collected.
+pub(super) fn classify_elf_paths(
+    control: &NixControl,
+    store_args: &[&str],
+    nodes: &mut HashMap<String, NodeRecord>,
+) {
+    if !control.reads_local_store(store_args) {
+        return;
+    }
+
+    nodes.par_iter_mut().for_each(|(_, node)| {
+        if !node.classes.contains(NodeClass::Derivation) && contains_elf(Path::new(&node.full_path))
+        {
+            node.classes = node.classes.with(NodeClass::Elf);
+        }
+    });
+}
+
+fn is_kernel_name(name: &str) -> bool {
+    name.strip_prefix("linux-")
+        .is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_digit()))
+}
+
+/// Font packages name a font or fonts, as in `dejavu-fonts` or `unifont`;
+/// tools and libraries that merely handle them, such as `fontconfig` or
+/// `libxfont`, are left out.
+fn is_font_name(name: &str) -> bool {
+    FONT_MARKERS.iter().any(|marker| name.contains(marker))
+        || name.split('-').any(|part| {
+            !part.starts_with("lib") && (part.ends_with("font") || part.ends_with("fonts"))
+        })
+}
+
+fn contains_elf(root: &Path) -> bool {
+    ELF_DIRS.iter().any(|dir| {
+        let Ok(entries) = fs::read_dir(root.join(dir)) else {
+            return false;
+        };
+
+        entries
+            .filter_map(Result::ok)
+            .take(ELF_PROBE_LIMIT)
+            .any(|entry| {
+                entry.file_type().is_ok_and(|kind| kind.is_file()) && has_elf_magic(&entry.path())
+            })
+    })
+}
+
+fn has_elf_magic(path: &Path) -> bool {
+    let mut magic = [0u8; 4];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok_and(|()| magic == ELF_MAGIC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classes(name: &str, fixed_output: bool, signed: bool) -> Vec<NodeClass> {
+        classify_node(&format!("0000-{name}"), fixed_output, signed)
+            .iter()
+            .collect()
+    }
+
+    #[test]
+    fn classifies_paths_by_name() {
+        let cases: &[(&str, &[NodeClass])] = &[
+            ("hello-2.12.drv", &[NodeClass::Derivation]),
+            ("openssl-3.0.13-dev", &[NodeClass::DevOutput]),
+            ("coreutils-9.5-man", &[NodeClass::DocOutput]),
+            ("glib-2.80.0-devdoc", &[NodeClass::DocOutput]),
+            ("linux-6.6.30", &[NodeClass::KernelFirmware]),
+            ("linux-6.6.30-modules", &[NodeClass::KernelFirmware]),
+            ("linux-firmware-20240610", &[NodeClass::KernelFirmware]),
+            ("dejavu-fonts-2.37", &[NodeClass::Font]),
+            ("unifont-15.1.05", &[NodeClass::Font]),
+            ("noto-fonts-color-emoji-2.042", &[NodeClass::Font]),
+            ("freefont-ttf-20120503", &[NodeClass::Font]),
+            // Near misses that only look like one of the classes.
+            ("linux-headers-6.6", &[]),
+            ("linux-pam-1.6.1", &[]),
+            ("fontconfig-2.15.0", &[]),
+            ("libxfont2-2.0.6", &[]),
+            ("gtk-doc-1.33.2", &[]),
+            ("asciidoc-10.2.0", &[]),
+            ("libdevil-1.8.0", &[]),
+            ("drv-tools-1.0", &[]),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(classes(name, false, true), *expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn classifies_paths_by_metadata() {
+        let cases = [
+            (false, true, vec![]),
+            (false, false, vec![NodeClass::Unsigned]),
+            (true, false, vec![NodeClass::FixedOutput]),
+            (true, true, vec![NodeClass::FixedOutput]),
+        ];
+        for (fixed_output, signed, expected) in cases {
+            assert_eq!(
+                classes("source.tar.gz", fixed_output, signed),
+                expected,
+                "fixed output: {fixed_output}, signed: {signed}"
+            );
+        }
+    }
+}
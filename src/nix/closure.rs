@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+
+use rayon::prelude::*;
+
+use super::graph::{NodeRecord, strongly_connected_components};
+
+/// Words of the bitset one pass tracks reachability in, so each pass covers
+/// this many times 64 components.
+const BATCH_WORDS: usize = 8;
+const BATCH_COMPONENTS: usize = BATCH_WORDS * 64;
+
+/// Fills each node's closure size, the NAR size of every path it reaches
+/// including itself, and its closure count, how many paths that is, from
+/// the references in the graph rather than from what nix reported.
+///
+/// Reference cycles are condensed first, so their members share one
+/// closure. Components come out of Tarjan's algorithm dependencies first,
+/// and each pass ORs the bitsets of a batch of them up that order; a
+/// component only reaches those before it, so a pass starts at its batch.
+pub fn fill_closure_sizes(nodes: &mut HashMap<String, NodeRecord>) {
+    let ids = nodes.keys().cloned().collect::<Vec<_>>();
+    let index_by_id = ids
+        .iter()
+        .enumerate()
+        .map(|(index, id)| (id.as_str(), index))
+        .collect::<HashMap<_, _>>();
+    let successors = ids
+        .iter()
+        .map(|id| {
+            nodes[id]
+                .references
+                .iter()
+                .filter_map(|reference| index_by_id.get(reference.as_str()).copied())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let components = strongly_connected_components(&successors);
+    let mut component_of = vec![0; ids.len()];
+    for (component, members) in components.iter().enumerate() {
+        for &member in members {
+            component_of[member] = component;
+        }
+    }
+    let component_sizes = components
+        .iter()
+        .map(|members| {
+            members
+                .iter()
+                .map(|&member| nodes[&ids[member]].nar_size)
+                .sum::<u64>()
+        })
+        .collect::<Vec<_>>();
+    let component_successors = components
+        .iter()
+        .enumerate()
+        .map(|(component, members)| {
+            let mut next = members
+                .iter()
+                .flat_map(|&member| &successors[member])
+                .map(|&successor| component_of[successor])
+                .filter(|&successor| successor != component)
+                .collect::<Vec<_>>();
+            next.sort_unstable();
+            next.dedup();
+            next
+        })
+        .collect::<Vec<_>>();
+
+    let count = components.len();
+    let (sizes, counts) = (0..count.div_ceil(BATCH_COMPONENTS))
+        .into_par_iter()
+        .fold(
+            || (vec![0u64; count], vec![0u64; count], Vec::new()),
+            |(mut sizes, mut counts, mut reached), batch| {
+                let start = batch * BATCH_COMPONENTS;
+                let end = (start + BATCH_COMPONENTS).min(count);
+                reached.clear();
+                reached.resize(count - start, [0u64; BATCH_WORDS]);
+                for component in start..count {
+                    let mut bits = [0u64; BATCH_WORDS];
+                    if component < end {
+                        let bit = component - start;
+                        bits[bit / 64] |= 1 << (bit % 64);
+                    }
+                    for &next in &component_successors[component] {
+                        if next >= start {
+                            for (word, next_word) in bits.iter_mut().zip(reached[next - start]) {
+                                *word |= next_word;
+                            }
+                        }
+                    }
+                    reached[component - start] = bits;
+
+                    for (word_index, mut word) in bits.into_iter().enumerate() {
+                        while word != 0 {
+                            let target = start + word_index * 64 + word.trailing_zeros() as usize;
+                            sizes[component] += component_sizes[target];
+                            counts[component] += components[target].len() as u64;
+                            word &= word - 1;
+                        }
+                    }
+                }
+                (sizes, counts, reached)
+            },
+        )
+        .map(|(sizes, counts, _)| (sizes, counts))
+        .reduce(
+            || (vec![0; count], vec![0; count]),
+            |(mut sizes, mut counts), (more_sizes, more_counts)| {
+                for (size, more) in sizes.iter_mut().zip(more_sizes) {
+                    *size += more;
+                }
+                for (count, more) in counts.iter_mut().zip(more_counts) {
+                    *count += more;
+                }
+                (sizes, counts)
+            },
+        );
+
+    for (index, id) in ids.iter().enumerate() {
+        if let Some(node) = nodes.get_mut(id) {
+            node.closure_size = sizes[component_of[index]];
+            node.closure_count = counts[component_of[index]];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::graph::test_node;
+    use super::*;
+
+    #[test]
+    fn sums_each_path_reachable_once_and_shares_cycles() {
+        let mut nodes = HashMap::from([
+            test_node("root", 100, &["bash", "glibc", "python"]),
+            test_node("bash", 20, &["glibc"]),
+            test_node("glibc", 5, &[]),
+            test_node("python", 40, &["tk", "gone"]),
+            test_node("tk", 10, &["tcl"]),
+            test_node("tcl", 8, &["tk", "glibc"]),
+        ]);
+        fill_closure_sizes(&mut nodes);
+
+        let closure = |id: &str| (nodes[id].closure_size, nodes[id].closure_count);
+        assert_eq!(closure("root"), (183, 6));
+        assert_eq!(closure("bash"), (25, 2));
+        assert_eq!(closure("glibc"), (5, 1));
+        assert_eq!(closure("python"), (63, 4));
+        assert_eq!(closure("tk"), (23, 3));
+        assert_eq!(closure("tcl"), (23, 3));
+    }
+
+    #[test]
+    fn matches_a_walk_across_batches() {
+        // A chain longer than one batch, with a shortcut past every node.
+        let ids = (0..BATCH_COMPONENTS * 2 + 7)
+            .map(|index| format!("{index:04}-path"))
+            .collect::<Vec<_>>();
+        let mut nodes = ids
+            .iter()
+            .enumerate()
+            .map(|(index, id)| {
+                let references = ids[index + 1..]
+                    .iter()
+                    .step_by(2)
+                    .take(2)
+                    .map(String::as_str)
+                    .collect::<Vec<_>>();
+                test_node(id, index as u64 + 1, &references)
+            })
+            .collect::<HashMap<_, _>>();
+        fill_closure_sizes(&mut nodes);
+
+        let last = ids.len() as u64;
+        for (index, id) in ids.iter().enumerate() {
+            let reached = index as u64 + 1..=last;
+            assert_eq!(nodes[id].closure_count, reached.clone().count() as u64);
+            assert_eq!(nodes[id].closure_size, reached.sum::<u64>());
+        }
+    }
+}
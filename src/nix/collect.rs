@@ -1,133 +1,552 @@
 use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader};
 
 use anyhow::{Context, Result, anyhow};
+use rayon::prelude::*;
 
-use super::graph::{NodeRecord, SystemGraph};
-use super::nix_cmd::run_nix;
-use super::parse::{normalize_store_key, parse_path_info_output};
+use super::classify::{classify_elf_paths, classify_node};
+use super::closure::fill_closure_sizes;
+use super::diff::{ClosureDiff, diff_closures};
+use super::disk_cache::{load_cached_graph, store_cached_graph};
+use super::dominators::fill_retained_sizes;
+use super::graph::{GraphDelta, NodeRecord, SystemGraph};
+use super::nix_cmd::{NixControl, stream_nix};
+use super::parse::{RawPathInfo, normalize_store_key, parse_path_info_reader};
+use super::restricted::{collect_restricted, is_permission_error};
+use super::store_db::collect_from_store_db;
 
-pub fn collect_system_graph(system_path: &str) -> Result<SystemGraph> {
-    let root_raw = run_nix(&["path-info", "--json", "--json-format", "2", system_path])
-        .with_context(|| format!("failed to resolve root path for {system_path}"))?;
+/// Paths whose metadata is read by one `nix path-info` call, well below the
+/// argument size limit.
+const CLOSURE_BATCH_PATHS: usize = 2048;
 
-    let (_root_store_dir, root_info) =
-        parse_path_info_output(&root_raw).context("failed to parse root nix path-info output")?;
+/// Splits a path under `<root>/nix/store`, e.g. of an installation mounted
+/// at `/mnt`, into the root of that chroot store and the path inside it.
+fn split_chroot_store(path: &str) -> Option<(&str, &str)> {
+    let start = path.find("/nix/store/")?;
+    (start > 0).then(|| path.split_at(start))
+}
 
-    let root_key = root_info
-        .keys()
-        .next()
-        .cloned()
-        .ok_or_else(|| anyhow!("nix path-info returned empty info for {system_path}"))?;
-    let root_id = normalize_store_key(&root_key);
-
-    let closure_raw = run_nix(&[
-        "path-info",
-        "--recursive",
-        "--closure-size",
-        "--json",
-        "--json-format",
-        "2",
-        system_path,
-    ])
-    .with_context(|| format!("failed to collect recursive closure for {system_path}"))?;
-
-    let (store_dir, closure_info) = parse_path_info_output(&closure_raw)
-        .context("failed to parse recursive closure nix path-info output")?;
-
-    let mut nodes = HashMap::with_capacity(closure_info.len());
-
-    for (raw_key, raw_entry) in closure_info {
-        let id = normalize_store_key(&raw_key);
-        if id.is_empty() {
-            continue;
+/// Collects the closure of `system_path`, from the store database when one
+/// is configured and readable, and otherwise through nix, falling back to
+/// read-only access when the store denies the current user. Paths under
+/// another root's `/nix/store` are read from that chroot store and keep its
+/// location.
+pub fn collect_system_graph(control: &NixControl, system_path: &str) -> Result<SystemGraph> {
+    if let Some((root, logical_path)) = split_chroot_store(system_path) {
+        let mut graph = collect_with_store_args(control, logical_path, &["--store", root])
+            .with_context(|| format!("failed to read the chroot store at {root}"))?;
+        graph.store_dir = format!("{root}{}", graph.store_dir);
+        for node in graph.nodes.values_mut() {
+            node.store_dir = format!("{root}{}", node.store_dir);
+            node.full_path = format!("{root}{}", node.full_path);
         }
+        return Ok(graph);
+    }
 
-        let full_path = if raw_key.starts_with('/') {
-            raw_key
-        } else {
-            format!("{store_dir}/{id}")
-        };
-
-        let mut references = raw_entry
-            .references
-            .into_iter()
-            .map(|reference| normalize_store_key(&reference))
-            .filter(|reference| !reference.is_empty() && reference != &id)
-            .collect::<Vec<_>>();
-        references.sort();
-        references.dedup();
-
-        let closure_size = if raw_entry.closure_size == 0 {
-            raw_entry.nar_size
-        } else {
-            raw_entry.closure_size
-        };
-
-        let deriver = raw_entry
-            .deriver
-            .map(|value| normalize_store_key(&value))
-            .filter(|value| !value.is_empty());
-
-        nodes.insert(
-            id.clone(),
-            NodeRecord {
-                id,
-                full_path,
-                nar_size: raw_entry.nar_size,
-                closure_size,
-                references,
-                referrers: Vec::new(),
-                deriver,
-            },
-        );
+    if let Some(database) = control.store_db() {
+        match collect_from_store_db(control, database, system_path) {
+            Ok(graph) => return Ok(graph),
+            Err(error) => control.report(format!("{error:#}; querying nix instead")),
+        }
+    }
+
+    match collect_cached(control, system_path) {
+        Err(error) if is_permission_error(&error) => {
+            collect_restricted(control, system_path, error)
+        }
+        result => result,
+    }
+}
+
+/// Collects the closure of `system_path` from the local store, or loads it
+/// from the graph cache when its root was collected before. The cache is
+/// left alone while nix invocations are recorded or replayed, so that a
+/// transcript holds every one of them and a replay only sees the transcript.
+fn collect_cached(control: &NixControl, system_path: &str) -> Result<SystemGraph> {
+    if !control.uses_graph_cache() || control.has_transcript() {
+        return collect_with_store_args(control, system_path, &[]);
     }
 
+    let root_id = resolve_root(control, system_path, &[])?;
+    if let Some(graph) = load_cached_graph(control, &root_id) {
+        control.report(format!("loaded the cached graph of {root_id}"));
+        return Ok(graph);
+    }
+    let graph = collect_from_root(control, system_path, root_id, &[])?;
+    // A cache that cannot be written only costs the next launch time.
+    let _ = store_cached_graph(control, &graph);
+    Ok(graph)
+}
+
+/// Lists the paths in the closure of `system_path`, which is much faster than
+/// reading their metadata.
+fn list_closure(
+    control: &NixControl,
+    system_path: &str,
+    store_args: &[&str],
+) -> Result<Vec<String>> {
+    let mut args = store_args.to_vec();
+    args.extend(["path-info", "--recursive", system_path]);
+    stream_nix(control, &args, |stdout| {
+        let mut paths = Vec::new();
+        for line in BufReader::new(stdout).lines() {
+            let line = line.context("failed to read nix path-info --recursive output")?;
+            let path = line.trim();
+            if !path.is_empty() {
+                paths.push(path.to_owned());
+            }
+        }
+        Ok(paths)
+    })
+    .with_context(|| format!("failed to list the closure of {system_path}"))
+}
+
+/// `store_args` are placed before the subcommand to pick another store. The
+/// closure is listed first and its metadata read in batches, so progress
+/// can be reported and a cancelled load stops between batches.
+pub(super) fn collect_with_store_args(
+    control: &NixControl,
+    system_path: &str,
+    store_args: &[&str],
+) -> Result<SystemGraph> {
+    let root_id = resolve_root(control, system_path, store_args)?;
+    collect_from_root(control, system_path, root_id, store_args)
+}
+
+fn collect_from_root(
+    control: &NixControl,
+    system_path: &str,
+    root_id: String,
+    store_args: &[&str],
+) -> Result<SystemGraph> {
+    let closure = list_closure(control, system_path, store_args)?;
+    let (store_dir, nodes) = read_closure_paths(control, system_path, &closure, store_args)?;
     if nodes.is_empty() {
         return Err(anyhow!(
             "no closure nodes were returned by nix path-info for {system_path}"
         ));
     }
 
-    let root_id = if nodes.contains_key(&root_id) {
-        root_id
-    } else {
-        nodes
-            .keys()
-            .next()
-            .cloned()
-            .ok_or_else(|| anyhow!("closure nodes are unexpectedly empty"))?
-    };
+    control.report(format!(
+        "computing dominators and retained sizes for {} paths",
+        nodes.len()
+    ));
+    Ok(finish_graph(nodes, root_id, store_dir, control))
+}
 
-    let known_ids = nodes.keys().cloned().collect::<HashSet<_>>();
-    let mut reverse_refs: HashMap<String, Vec<String>> = HashMap::new();
-    let mut edge_count = 0usize;
-
-    for (id, node) in &mut nodes {
-        node.references
-            .retain(|reference| known_ids.contains(reference));
-        node.references.sort();
-        node.references.dedup();
-
-        edge_count += node.references.len();
-        for reference in &node.references {
-            reverse_refs
-                .entry(reference.clone())
-                .or_default()
-                .push(id.clone());
+/// Reads the metadata of `paths` from the closure of `system_path` in
+/// batches, reporting progress after each one.
+fn read_closure_paths(
+    control: &NixControl,
+    system_path: &str,
+    paths: &[String],
+    store_args: &[&str],
+) -> Result<(String, HashMap<String, NodeRecord>)> {
+    let total = paths.len();
+    control.count_paths(0, total);
+
+    let mut store_dir = "/nix/store".to_owned();
+    let mut nodes = HashMap::with_capacity(total);
+    for batch in paths.chunks(CLOSURE_BATCH_PATHS) {
+        let mut args = store_args.to_vec();
+        args.extend(["path-info", "--json", "--json-format", "2"]);
+        args.extend(batch.iter().map(String::as_str));
+        let (batch_store_dir, info) = stream_nix(control, &args, |stdout| {
+            parse_path_info_reader(stdout).context("failed to parse closure nix path-info output")
+        })
+        .with_context(|| format!("failed to collect the closure of {system_path}"))?;
+
+        store_dir = batch_store_dir;
+        for (raw_key, raw_entry) in info {
+            if let Some(node) = node_record(raw_key, raw_entry, &store_dir) {
+                nodes.insert(node.id.clone(), node);
+            }
         }
+        control.count_paths(nodes.len(), total);
+        control.report(format!("read {} of {total} paths", nodes.len()));
     }
+    classify_elf_paths(control, store_args, &mut nodes);
+    Ok((store_dir, nodes))
+}
 
-    for (id, node) in &mut nodes {
-        if let Some(mut referrers) = reverse_refs.remove(id) {
-            referrers.sort();
-            node.referrers = referrers;
+/// The paths that entered and left the closure of `system_path` since it
+/// held `known`, with metadata read for the new paths only.
+pub(super) fn collect_closure_delta(
+    control: &NixControl,
+    system_path: &str,
+    known: &HashSet<String>,
+) -> Result<GraphDelta> {
+    let (chroot, logical_path) = match split_chroot_store(system_path) {
+        Some((root, logical_path)) => (Some(root), logical_path),
+        None => (None, system_path),
+    };
+    let store_args = chroot.map(|root| vec!["--store", root]).unwrap_or_default();
+
+    let root_id = resolve_root(control, logical_path, &store_args)?;
+    let closure = list_closure(control, logical_path, &store_args)?;
+    let listed = closure
+        .iter()
+        .map(|path| normalize_store_key(path))
+        .collect::<HashSet<_>>();
+    let new_paths = closure
+        .into_iter()
+        .filter(|path| !known.contains(&normalize_store_key(path)))
+        .collect::<Vec<_>>();
+
+    let (_, nodes) = read_closure_paths(control, logical_path, &new_paths, &store_args)?;
+    let mut added = nodes.into_values().collect::<Vec<_>>();
+    if let Some(root) = chroot {
+        for node in &mut added {
+            node.store_dir = format!("{root}{}", node.store_dir);
+            node.full_path = format!("{root}{}", node.full_path);
         }
     }
 
-    Ok(SystemGraph {
+    Ok(GraphDelta {
+        root_id,
+        added,
+        removed: known.difference(&listed).cloned().collect(),
+    })
+}
+
+/// Collects the closure of `base_path`, e.g. an earlier generation from
+/// `/nix/var/nix/profiles`, and diffs `current` against it.
+pub fn collect_closure_diff(
+    control: &NixControl,
+    base_path: &str,
+    current: &SystemGraph,
+) -> Result<ClosureDiff> {
+    let base = collect_system_graph(control, base_path)
+        .with_context(|| format!("failed to collect comparison closure {base_path}"))?;
+    control.report(format!(
+        "aligning {} against {} paths",
+        current.node_count(),
+        base.node_count()
+    ));
+    Ok(diff_closures(&base, current, control))
+}
+
+pub(super) fn resolve_root(
+    control: &NixControl,
+    system_path: &str,
+    store_args: &[&str],
+) -> Result<String> {
+    let mut args = store_args.to_vec();
+    args.extend(["path-info", "--json", "--json-format", "2", system_path]);
+    let (_root_store_dir, root_info) = stream_nix(control, &args, |stdout| {
+        parse_path_info_reader(stdout).context("failed to parse root nix path-info output")
+    })
+    .with_context(|| format!("failed to resolve root path for {system_path}"))?;
+
+    let root_key = root_info
+        .keys()
+        .next()
+        .ok_or_else(|| anyhow!("nix path-info returned empty info for {system_path}"))?;
+    Ok(normalize_store_key(root_key))
+}
+
+pub(super) fn node_record(
+    raw_key: String,
+    raw_entry: RawPathInfo,
+    store_dir: &str,
+) -> Option<NodeRecord> {
+    let id = normalize_store_key(&raw_key);
+    if id.is_empty() {
+        return None;
+    }
+
+    let full_path = if raw_key.starts_with('/') {
+        raw_key
+    } else {
+        format!("{store_dir}/{id}")
+    };
+
+    let mut references = raw_entry
+        .references
+        .into_iter()
+        .map(|reference| normalize_store_key(&reference))
+        .filter(|reference| !reference.is_empty() && reference != &id)
+        .collect::<Vec<_>>();
+    references.sort();
+    references.dedup();
+
+    let closure_size = if raw_entry.closure_size == 0 {
+        raw_entry.nar_size
+    } else {
+        raw_entry.closure_size
+    };
+
+    let classes = classify_node(
+        &id,
+        raw_entry.ca.is_some(),
+        !raw_entry.signatures.is_empty(),
+    );
+
+    let deriver = raw_entry
+        .deriver
+        .map(|value| normalize_store_key(&value))
+        .filter(|value| !value.is_empty());
+
+    Some(NodeRecord {
+        id,
+        full_path,
+        store_dir: store_dir.to_owned(),
+        nar_size: raw_entry.nar_size,
+        closure_size,
+        closure_count: 0,
+        retained_size: 0,
+        immediate_dominator: None,
+        references,
+        referrers: Vec::new(),
+        deriver,
+        derivation: None,
+        classes,
+    })
+}
+
+/// Links references into a graph and sizes every closure, falling back to an
+/// arbitrary root when the requested one was not collected. `nodes` must not
+/// be empty.
+pub(super) fn finish_graph(
+    mut nodes: HashMap<String, NodeRecord>,
+    root_id: String,
+    store_dir: String,
+    control: &NixControl,
+) -> SystemGraph {
+    fill_closure_sizes(&mut nodes);
+    finish_sized_graph(nodes, root_id, store_dir, control)
+}
+
+/// [`finish_graph`] for nodes whose closure sizes were already filled in.
+pub(super) fn finish_sized_graph(
+    mut nodes: HashMap<String, NodeRecord>,
+    root_id: String,
+    store_dir: String,
+    control: &NixControl,
+) -> SystemGraph {
+    // Graphs saved before paths recorded their store came from one store.
+    for node in nodes.values_mut().filter(|node| node.store_dir.is_empty()) {
+        node.store_dir.clone_from(&store_dir);
+    }
+
+    let root_id = if nodes.contains_key(&root_id) {
+        root_id
+    } else {
+        nodes.keys().min().cloned().unwrap_or(root_id)
+    };
+
+    let (mut nodes, edge_count) = link_references(nodes);
+    fill_retained_sizes(&mut nodes, &root_id, control);
+
+    SystemGraph {
         store_dir,
         root_id,
         nodes,
         edge_count,
-    })
+        limitations: Vec::new(),
+        edge_weights: HashMap::new(),
+        roots: Vec::new(),
+        root_membership: HashMap::new(),
+    }
+}
+
+pub(super) fn link_references(
+    nodes: HashMap<String, NodeRecord>,
+) -> (HashMap<String, NodeRecord>, usize) {
+    let mut records = nodes.into_values().collect::<Vec<_>>();
+    records.par_sort_unstable_by(|a, b| a.id.cmp(&b.id));
+
+    let (resolved, edges) = {
+        let index_by_id = records
+            .iter()
+            .enumerate()
+            .map(|(index, record)| (record.id.as_str(), index))
+            .collect::<HashMap<_, _>>();
+
+        let resolved = records
+            .par_iter()
+            .map(|record| {
+                let mut targets = record
+                    .references
+                    .iter()
+                    .filter_map(|reference| index_by_id.get(reference.as_str()).copied())
+                    .collect::<Vec<_>>();
+                targets.sort_unstable();
+                targets.dedup();
+                targets
+            })
+            .collect::<Vec<_>>();
+
+        let mut edges = resolved
+            .par_iter()
+            .enumerate()
+            .flat_map_iter(|(source, targets)| targets.iter().map(move |&target| (target, source)))
+            .collect::<Vec<_>>();
+        edges.par_sort_unstable();
+
+        (resolved, edges)
+    };
+
+    let referrers = edges
+        .par_chunk_by(|a, b| a.0 == b.0)
+        .map(|group| {
+            let ids = group
+                .iter()
+                .map(|&(_, source)| records[source].id.clone())
+                .collect::<Vec<_>>();
+            (group[0].0, ids)
+        })
+        .collect::<Vec<_>>();
+
+    let pruned_references = records
+        .par_iter()
+        .zip(resolved.par_iter())
+        .map(|(record, targets)| {
+            (record.references.len() != targets.len()).then(|| {
+                targets
+                    .iter()
+                    .map(|&target| records[target].id.clone())
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for (target, ids) in referrers {
+        records[target].referrers = ids;
+    }
+    for (record, references) in records.iter_mut().zip(pruned_references) {
+        if let Some(references) = references {
+            record.references = references;
+        }
+    }
+
+    let edge_count = edges.len();
+    let nodes = records
+        .into_iter()
+        .map(|record| (record.id.clone(), record))
+        .collect();
+    (nodes, edge_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Write;
+
+    use super::super::nix_cmd::NixSettings;
+    use super::super::transcript::{Transcript, create_stdout_record, write_entry};
+    use super::*;
+
+    fn record(dir: &std::path::Path, args: &[&str], stdout: &str) {
+        create_stdout_record(dir, args)
+            .unwrap()
+            .write_all(stdout.as_bytes())
+            .unwrap();
+        write_entry(dir, args, true, "").unwrap();
+    }
+
+    #[test]
+    fn deltas_read_only_the_new_paths() {
+        let pid = std::process::id();
+        let dir = std::env::temp_dir().join(format!("nix-analisa-collect-delta-{pid}"));
+        let _ = fs::remove_dir_all(&dir);
+        let id = |name: &str| format!("{pid:032}-{name}");
+        let path = |name: &str| format!("/nix/store/{}", id(name));
+        let (root, kept, added) = (path("next-system"), path("kept"), path("added"));
+        record(
+            &dir,
+            &["path-info", "--json", "--json-format", "2", &root],
+            &format!(r#"{{"{root}": {{"narSize": 1}}}}"#),
+        );
+        record(
+            &dir,
+            &["path-info", "--recursive", &root],
+            &format!("{root}\n{kept}\n{added}\n"),
+        );
+        // Only the paths missing from the known closure are queried.
+        record(
+            &dir,
+            &["path-info", "--json", "--json-format", "2", &root, &added],
+            &format!(
+                r#"{{"{root}": {{"narSize": 1, "references": ["{kept}", "{added}"]}},
+                    "{added}": {{"narSize": 2}}}}"#
+            ),
+        );
+        let control = NixControl::new(&NixSettings {
+            transcript: Some(Transcript::Replay(dir.clone())),
+            ..NixSettings::default()
+        });
+        let known = HashSet::from([id("old-system"), id("kept")]);
+
+        let delta = collect_closure_delta(&control, &root, &known);
+        let _ = fs::remove_dir_all(&dir);
+
+        let delta = delta.unwrap();
+        let mut added_ids = delta
+            .added
+            .iter()
+            .map(|node| node.id.clone())
+            .collect::<Vec<_>>();
+        added_ids.sort();
+        assert_eq!(delta.root_id, id("next-system"));
+        assert_eq!(added_ids, [id("added"), id("next-system")]);
+        assert_eq!(delta.removed, [id("old-system")]);
+    }
+
+    #[test]
+    fn replayed_collections_bypass_the_graph_cache() {
+        let pid = std::process::id();
+        let dir = std::env::temp_dir().join(format!("nix-analisa-collect-replay-{pid}"));
+        let _ = fs::remove_dir_all(&dir);
+        let root = format!("/nix/store/{pid:032}-replayed-system");
+        let dependency = format!("/nix/store/{pid:032}-replayed-dependency");
+        record(
+            &dir,
+            &["path-info", "--json", "--json-format", "2", &root],
+            &format!(r#"{{"{root}": {{"narSize": 1}}}}"#),
+        );
+        record(
+            &dir,
+            &["path-info", "--recursive", &root],
+            &format!("{root}\n{dependency}\n"),
+        );
+        record(
+            &dir,
+            &[
+                "path-info",
+                "--json",
+                "--json-format",
+                "2",
+                &root,
+                &dependency,
+            ],
+            &format!(
+                r#"{{"{root}": {{"narSize": 1, "references": ["{dependency}"]}},
+                    "{dependency}": {{"narSize": 2}}}}"#
+            ),
+        );
+        let control = NixControl::new(&NixSettings {
+            transcript: Some(Transcript::Replay(dir.clone())),
+            graph_cache: true,
+            ..NixSettings::default()
+        });
+
+        let graph = collect_system_graph(&control, &root);
+        let _ = fs::remove_dir_all(&dir);
+
+        let graph = graph.unwrap();
+        assert_eq!(graph.node_count(), 2);
+        assert_eq!(graph.nodes[&graph.root_id].closure_size, 3);
+        assert!(load_cached_graph(&control, &graph.root_id).is_none());
+    }
+
+    #[test]
+    fn chroot_stores_are_split_at_their_nix_store() {
+        assert_eq!(
+            split_chroot_store("/mnt/nix/store/aaaa-nixos-system"),
+            Some(("/mnt", "/nix/store/aaaa-nixos-system"))
+        );
+        assert_eq!(split_chroot_store("/nix/store/aaaa-nixos-system"), None);
+        assert_eq!(split_chroot_store("/run/current-system"), None);
+    }
 }
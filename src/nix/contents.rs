@@ -0,0 +1,162 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow};
+use serde_json::Value;
+
+use super::nix_cmd::{NixControl, run_nix};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContentKind {
+    Directory,
+    File,
+    Symlink,
+}
+
+/// A file or directory inside a store path. Sizes are apparent file sizes,
+/// summed over directories; children are sorted largest first.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContentEntry {
+    pub name: String,
+    pub kind: ContentKind,
+    pub size: u64,
+    pub file_count: usize,
+    pub children: Vec<ContentEntry>,
+}
+
+impl ContentEntry {
+    fn leaf(name: String, kind: ContentKind, size: u64) -> Self {
+        Self {
+            name,
+            kind,
+            size,
+            file_count: 1,
+            children: Vec::new(),
+        }
+    }
+
+    fn directory(name: String, mut children: Vec<Self>) -> Self {
+        children.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+        Self {
+            name,
+            kind: ContentKind::Directory,
+            size: children.iter().map(|child| child.size).sum(),
+            file_count: children.iter().map(|child| child.file_count).sum(),
+            children,
+        }
+    }
+}
+
+/// Breaks a store path down by file. Paths present on disk are walked
+/// directly; others are listed with `nix store ls`, e.g. on a remote store.
+pub fn scan_contents(control: &NixControl, full_path: &str) -> Result<ContentEntry> {
+    let path = Path::new(full_path);
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| full_path.to_owned());
+    if fs::symlink_metadata(path).is_ok() {
+        return scan_directory(path, name);
+    }
+
+    let listing = run_nix(
+        control,
+        &["store", "ls", "--json", "--recursive", "--long", full_path],
+    )?;
+    let listing =
+        serde_json::from_str::<Value>(&listing).context("failed to parse nix store ls output")?;
+    parse_listing(name, &listing)
+}
+
+fn scan_directory(path: &Path, name: String) -> Result<ContentEntry> {
+    let metadata =
+        fs::symlink_metadata(path).with_context(|| format!("failed to stat {}", path.display()))?;
+    let file_type = metadata.file_type();
+    if file_type.is_symlink() {
+        let target = fs::read_link(path)
+            .with_context(|| format!("failed to read link {}", path.display()))?;
+        return Ok(ContentEntry::leaf(
+            name,
+            ContentKind::Symlink,
+            target.as_os_str().len() as u64,
+        ));
+    }
+    if !file_type.is_dir() {
+        return Ok(ContentEntry::leaf(name, ContentKind::File, metadata.len()));
+    }
+
+    let children = fs::read_dir(path)
+        .with_context(|| format!("failed to list {}", path.display()))?
+        .map(|entry| {
+            let entry = entry.with_context(|| format!("failed to list {}", path.display()))?;
+            scan_directory(
+                &entry.path(),
+                entry.file_name().to_string_lossy().into_owned(),
+            )
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(ContentEntry::directory(name, children))
+}
+
+/// Parses the `--json --long` form, e.g.
+/// `{"type":"directory","entries":{"bin":{"type":"regular","size":12}}}`.
+fn parse_listing(name: String, listing: &Value) -> Result<ContentEntry> {
+    match listing.get("type").and_then(Value::as_str) {
+        Some("directory") => {
+            let children = listing
+                .get("entries")
+                .and_then(Value::as_object)
+                .into_iter()
+                .flatten()
+                .map(|(child, listing)| parse_listing(child.clone(), listing))
+                .collect::<Result<Vec<_>>>()?;
+            Ok(ContentEntry::directory(name, children))
+        }
+        Some("regular") => {
+            let size = listing.get("size").and_then(Value::as_u64).unwrap_or(0);
+            Ok(ContentEntry::leaf(name, ContentKind::File, size))
+        }
+        Some("symlink") => {
+            let target = listing.get("target").and_then(Value::as_str).unwrap_or("");
+            Ok(ContentEntry::leaf(
+                name,
+                ContentKind::Symlink,
+                target.len() as u64,
+            ))
+        }
+        other => Err(anyhow!("unexpected entry type {other:?} for {name}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sums_and_sorts_nix_store_listings() {
+        let listing = serde_json::json!({
+            "type": "directory",
+            "entries": {
+                "bin": {"type": "directory", "entries": {
+                    "hello": {"type": "regular", "size": 70},
+                    "hi": {"type": "symlink", "target": "hello"},
+                }},
+                "share": {"type": "directory", "entries": {
+                    "man": {"type": "regular", "size": 300},
+                }},
+                "empty": {"type": "directory", "entries": {}},
+            },
+        });
+
+        let root = parse_listing("abc-hello".to_owned(), &listing).unwrap();
+        assert_eq!(root.size, 375);
+        assert_eq!(root.file_count, 3);
+        let names = root
+            .children
+            .iter()
+            .map(|child| (child.name.as_str(), child.size))
+            .collect::<Vec<_>>();
+        assert_eq!(names, [("share", 300), ("bin", 75), ("empty", 0)]);
+        assert_eq!(root.children[1].children[1].kind, ContentKind::Symlink);
+    }
+}
@@ -0,0 +1,440 @@
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::os::unix::net::UnixStream;
+
+use anyhow::{Context, Result, anyhow};
+use serde_json::Value;
+
+use super::nix_cmd::NixControl;
+use super::parse::RawPathInfo;
+
+pub const DEFAULT_DAEMON_SOCKET: &str = "/nix/var/nix/daemon-socket/socket";
+
+const WORKER_MAGIC_1: u64 = 0x6e69_7863;
+const WORKER_MAGIC_2: u64 = 0x6478_696f;
+
+/// Protocol 1.35: new enough for the daemon's version and trust greeting,
+/// old enough to skip the feature negotiation of 1.38.
+const CLIENT_VERSION: u64 = 1 << 8 | 35;
+/// `QueryPathInfo` reports invalid paths instead of failing from 1.17 on.
+const MIN_MINOR_VERSION: u64 = 17;
+
+const OP_IS_VALID_PATH: u64 = 1;
+const OP_QUERY_REFERRERS: u64 = 6;
+const OP_QUERY_PATH_INFO: u64 = 26;
+
+const STDERR_NEXT: u64 = 0x6f6c_6d67;
+const STDERR_READ: u64 = 0x6461_7461;
+const STDERR_WRITE: u64 = 0x6461_7416;
+const STDERR_LAST: u64 = 0x616c_7473;
+const STDERR_ERROR: u64 = 0x6378_7470;
+const STDERR_START_ACTIVITY: u64 = 0x5354_5254;
+const STDERR_STOP_ACTIVITY: u64 = 0x5354_4f50;
+const STDERR_RESULT: u64 = 0x5253_4c54;
+
+/// Longest string accepted from the daemon, so a corrupt length cannot
+/// allocate the whole address space.
+const MAX_STRING_LEN: u64 = 64 << 20;
+
+/// A store path's metadata as `QueryPathInfo` returns it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(super) struct DaemonPathInfo {
+    pub(super) deriver: Option<String>,
+    pub(super) nar_hash: String,
+    pub(super) references: Vec<String>,
+    pub(super) registration_time: u64,
+    pub(super) nar_size: u64,
+    pub(super) signatures: Vec<String>,
+    pub(super) ca: Option<String>,
+}
+
+impl From<DaemonPathInfo> for RawPathInfo {
+    fn from(info: DaemonPathInfo) -> Self {
+        Self {
+            nar_size: info.nar_size,
+            closure_size: 0,
+            references: info.references,
+            deriver: info.deriver,
+            ca: info.ca.map(Value::String),
+            signatures: info.signatures.into_iter().map(Value::String).collect(),
+            registration_time: Some(info.registration_time),
+        }
+    }
+}
+
+/// A session with the nix daemon speaking its worker protocol, for lookups
+/// that would otherwise spawn a nix process each.
+pub(super) struct DaemonConnection<R: Read = UnixStream, W: Write = UnixStream> {
+    reader: BufReader<R>,
+    writer: BufWriter<W>,
+    minor_version: u64,
+}
+
+impl DaemonConnection {
+    /// Connects to the daemon socket configured for `control`; `None` when
+    /// no socket is configured or nix invocations are being recorded or
+    /// replayed, since daemon traffic is not part of a transcript.
+    pub(super) fn for_control(control: &NixControl) -> Option<Result<Self>> {
+        let socket = control.daemon_socket()?;
+        if control.has_transcript() {
+            return None;
+        }
+
+        Some((|| {
+            let stream = UnixStream::connect(socket).with_context(|| {
+                format!(
+                    "failed to connect to the nix daemon at {}",
+                    socket.display()
+                )
+            })?;
+            stream.set_read_timeout(Some(control.timeout()))?;
+            stream.set_write_timeout(Some(control.timeout()))?;
+            let reader = stream.try_clone()?;
+            Self::handshake(reader, stream)
+        })())
+    }
+}
+
+impl<R: Read, W: Write> DaemonConnection<R, W> {
+    fn handshake(reader: R, writer: W) -> Result<Self> {
+        let mut connection = Self {
+            reader: BufReader::new(reader),
+            writer: BufWriter::new(writer),
+            minor_version: 0,
+        };
+
+        connection.write_u64(WORKER_MAGIC_1)?;
+        connection.writer.flush()?;
+        if connection.read_u64()? != WORKER_MAGIC_2 {
+            return Err(anyhow!("the nix daemon answered with an unknown greeting"));
+        }
+        let server_version = connection.read_u64()?;
+        if server_version >> 8 != CLIENT_VERSION >> 8 || server_version & 0xff < MIN_MINOR_VERSION {
+            return Err(anyhow!(
+                "unsupported nix daemon protocol {}.{}",
+                server_version >> 8,
+                server_version & 0xff
+            ));
+        }
+        connection.minor_version = (server_version & 0xff).min(CLIENT_VERSION & 0xff);
+
+        connection.write_u64(CLIENT_VERSION)?;
+        // No CPU affinity and no reserved space.
+        connection.write_u64(0)?;
+        connection.write_u64(0)?;
+        connection.writer.flush()?;
+
+        if connection.minor_version >= 33 {
+            connection.read_string()?;
+        }
+        if connection.minor_version >= 35 {
+            connection.read_u64()?;
+        }
+        connection.process_stderr()?;
+        Ok(connection)
+    }
+
+    /// The metadata of `full_path`, or `None` when it is not valid.
+    pub(super) fn query_path_info(&mut self, full_path: &str) -> Result<Option<DaemonPathInfo>> {
+        self.start_operation(OP_QUERY_PATH_INFO, full_path)?;
+        if self.read_u64()? == 0 {
+            return Ok(None);
+        }
+
+        let deriver = Some(self.read_string()?).filter(|deriver| !deriver.is_empty());
+        let nar_hash = self.read_string()?;
+        let references = self.read_strings()?;
+        let registration_time = self.read_u64()?;
+        let nar_size = self.read_u64()?;
+        let _ultimate = self.read_u64()?;
+        let signatures = self.read_strings()?;
+        let ca = Some(self.read_string()?).filter(|ca| !ca.is_empty());
+
+        Ok(Some(DaemonPathInfo {
+            deriver,
+            nar_hash,
+            references,
+            registration_time,
+            nar_size,
+            signatures,
+            ca,
+        }))
+    }
+
+    /// Every valid path in the store that references `full_path`.
+    pub(super) fn query_referrers(&mut self, full_path: &str) -> Result<Vec<String>> {
+        self.start_operation(OP_QUERY_REFERRERS, full_path)?;
+        self.read_strings()
+    }
+
+    pub(super) fn is_valid_path(&mut self, full_path: &str) -> Result<bool> {
+        self.start_operation(OP_IS_VALID_PATH, full_path)?;
+        Ok(self.read_u64()? != 0)
+    }
+
+    fn start_operation(&mut self, operation: u64, full_path: &str) -> Result<()> {
+        self.write_u64(operation)?;
+        self.write_string(full_path)?;
+        self.writer.flush()?;
+        self.process_stderr()
+            .with_context(|| format!("the nix daemon failed to look up {full_path}"))
+    }
+
+    /// Skips the log messages the daemon sends before a reply, turning a
+    /// reported error into ours.
+    fn process_stderr(&mut self) -> Result<()> {
+        loop {
+            match self.read_u64()? {
+                STDERR_LAST => return Ok(()),
+                STDERR_NEXT => {
+                    self.read_string()?;
+                }
+                STDERR_START_ACTIVITY => {
+                    // id, level, type, text, fields, parent
+                    self.read_u64()?;
+                    self.read_u64()?;
+                    self.read_u64()?;
+                    self.read_string()?;
+                    self.skip_fields()?;
+                    self.read_u64()?;
+                }
+                STDERR_STOP_ACTIVITY => {
+                    self.read_u64()?;
+                }
+                STDERR_RESULT => {
+                    self.read_u64()?;
+                    self.read_u64()?;
+                    self.skip_fields()?;
+                }
+                STDERR_ERROR => return Err(self.read_error()),
+                STDERR_READ | STDERR_WRITE => {
+                    return Err(anyhow!("the nix daemon asked for data it should not need"));
+                }
+                other => return Err(anyhow!("unknown nix daemon message {other:#x}")),
+            }
+        }
+    }
+
+    fn read_error(&mut self) -> anyhow::Error {
+        let message = (|| {
+            if self.minor_version < 26 {
+                let message = self.read_string()?;
+                self.read_u64()?;
+                return Ok(message);
+            }
+
+            // type, level, name, message, position, traces
+            self.read_string()?;
+            self.read_u64()?;
+            self.read_string()?;
+            let message = self.read_string()?;
+            self.read_u64()?;
+            for _ in 0..self.read_u64()? {
+                self.read_u64()?;
+                self.read_string()?;
+            }
+            Ok::<_, anyhow::Error>(message)
+        })();
+
+        match message {
+            Ok(message) => anyhow!("{}", message.trim()),
+            Err(error) => error.context("failed to read a nix daemon error"),
+        }
+    }
+
+    fn skip_fields(&mut self) -> Result<()> {
+        for _ in 0..self.read_u64()? {
+            match self.read_u64()? {
+                0 => {
+                    self.read_u64()?;
+                }
+                1 => {
+                    self.read_string()?;
+                }
+                other => return Err(anyhow!("unknown nix daemon field type {other}")),
+            }
+        }
+        Ok(())
+    }
+
+    fn read_u64(&mut self) -> Result<u64> {
+        let mut bytes = [0; 8];
+        self.reader
+            .read_exact(&mut bytes)
+            .context("failed to read from the nix daemon")?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn read_string(&mut self) -> Result<String> {
+        let len = self.read_u64()?;
+        if len > MAX_STRING_LEN {
+            return Err(anyhow!("the nix daemon sent a {len} byte string"));
+        }
+
+        let mut bytes = vec![0; len as usize];
+        self.reader
+            .read_exact(&mut bytes)
+            .context("failed to read from the nix daemon")?;
+        io::copy(&mut (&mut self.reader).take(padding(len)), &mut io::sink())?;
+        String::from_utf8(bytes).context("the nix daemon sent a string that is not UTF-8")
+    }
+
+    fn read_strings(&mut self) -> Result<Vec<String>> {
+        (0..self.read_u64()?).map(|_| self.read_string()).collect()
+    }
+
+    fn write_u64(&mut self, value: u64) -> Result<()> {
+        self.writer
+            .write_all(&value.to_le_bytes())
+            .context("failed to write to the nix daemon")
+    }
+
+    fn write_string(&mut self, value: &str) -> Result<()> {
+        self.write_u64(value.len() as u64)?;
+        self.writer.write_all(value.as_bytes())?;
+        self.writer
+            .write_all(&[0; 8][..padding(value.len() as u64) as usize])
+            .context("failed to write to the nix daemon")
+    }
+}
+
+/// Strings are zero-padded to a multiple of eight bytes on the wire.
+fn padding(len: u64) -> u64 {
+    (8 - len % 8) % 8
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    /// The bytes a daemon would send.
+    #[derive(Default)]
+    struct Wire(Vec<u8>);
+
+    impl Wire {
+        fn u64(mut self, value: u64) -> Self {
+            self.0.extend(value.to_le_bytes());
+            self
+        }
+
+        fn string(mut self, value: &str) -> Self {
+            self = self.u64(value.len() as u64);
+            self.0.extend(value.as_bytes());
+            self.0
+                .resize(self.0.len() + padding(value.len() as u64) as usize, 0);
+            self
+        }
+
+        fn greeting(self, minor_version: u64) -> Self {
+            let wire = self.u64(WORKER_MAGIC_2).u64(1 << 8 | minor_version);
+            let wire = if minor_version >= 33 {
+                wire.string("2.24.0")
+            } else {
+                wire
+            };
+            let wire = if minor_version >= 35 {
+                wire.u64(1)
+            } else {
+                wire
+            };
+            wire.u64(STDERR_LAST)
+        }
+    }
+
+    fn connect(wire: Wire) -> Result<DaemonConnection<Cursor<Vec<u8>>, Vec<u8>>> {
+        DaemonConnection::handshake(Cursor::new(wire.0), Vec::new())
+    }
+
+    #[test]
+    fn reads_path_info_after_log_messages() {
+        let wire = Wire::default()
+            .greeting(37)
+            .u64(STDERR_NEXT)
+            .string("querying info")
+            .u64(STDERR_START_ACTIVITY)
+            .u64(1)
+            .u64(3)
+            .u64(105)
+            .string("querying")
+            .u64(2)
+            .u64(0)
+            .u64(7)
+            .u64(1)
+            .string("path")
+            .u64(0)
+            .u64(STDERR_STOP_ACTIVITY)
+            .u64(1)
+            .u64(STDERR_LAST)
+            .u64(1)
+            .string("/nix/store/dddd-bash-5.2.drv")
+            .string("sha256:abc")
+            .u64(2)
+            .string("/nix/store/bbbb-bash-5.2")
+            .string("/nix/store/cccc-glibc-2.39")
+            .u64(1_700_000_000)
+            .u64(20)
+            .u64(0)
+            .u64(1)
+            .string("cache.nixos.org-1:sig")
+            .string("");
+        let mut connection = connect(wire).unwrap();
+        assert_eq!(connection.minor_version, 35);
+
+        let info = connection
+            .query_path_info("/nix/store/bbbb-bash-5.2")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            info,
+            DaemonPathInfo {
+                deriver: Some("/nix/store/dddd-bash-5.2.drv".to_owned()),
+                nar_hash: "sha256:abc".to_owned(),
+                references: vec![
+                    "/nix/store/bbbb-bash-5.2".to_owned(),
+                    "/nix/store/cccc-glibc-2.39".to_owned(),
+                ],
+                registration_time: 1_700_000_000,
+                nar_size: 20,
+                signatures: vec!["cache.nixos.org-1:sig".to_owned()],
+                ca: None,
+            }
+        );
+
+        let request = &connection.writer.get_ref()[32..];
+        assert_eq!(request[..8], OP_QUERY_PATH_INFO.to_le_bytes());
+        assert_eq!(request.len(), 8 + 8 + 24);
+    }
+
+    #[test]
+    fn reports_invalid_paths_and_daemon_errors() {
+        let wire = Wire::default()
+            .greeting(25)
+            .u64(STDERR_LAST)
+            .u64(0)
+            .u64(STDERR_ERROR)
+            .string("path '/nix/store/x' is not in the Nix store")
+            .u64(1)
+            .u64(STDERR_LAST)
+            .u64(2)
+            .string("/nix/store/aaaa-system")
+            .string("/nix/store/eeee-profile");
+        let mut connection = connect(wire).unwrap();
+
+        assert_eq!(connection.query_path_info("/nix/store/gone").unwrap(), None);
+        let error = connection.is_valid_path("/nix/store/x").unwrap_err();
+        assert!(format!("{error:#}").contains("is not in the Nix store"));
+        assert_eq!(
+            connection
+                .query_referrers("/nix/store/bbbb-bash-5.2")
+                .unwrap(),
+            ["/nix/store/aaaa-system", "/nix/store/eeee-profile"]
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_daemons() {
+        assert!(connect(Wire::default().u64(0xdead)).is_err());
+        assert!(connect(Wire::default().greeting(16)).is_err());
+    }
+}
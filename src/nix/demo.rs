@@ -0,0 +1,487 @@
+use std::collections::HashMap;
+
+use super::classify::classify_node;
+use super::closure::fill_closure_sizes;
+use super::collect::link_references;
+use super::dominators::fill_retained_sizes;
+use super::graph::{NodeRecord, SystemGraph};
+use super::nix_cmd::NixControl;
+
+const DEMO_STORE_DIR: &str = "/nix/store";
+const DEMO_SEED: u64 = 0x6e69_782d_6465_6d6f;
+const HASH_ALPHABET: &[u8; 32] = b"0123456789abcdfghijklmnpqrsvwxyz";
+const MAX_REFERENCES: usize = 48;
+
+const PNAMES: &[&str] = &[
+    "glibc",
+    "gcc",
+    "openssl",
+    "zlib",
+    "bash",
+    "coreutils",
+    "python3",
+    "perl",
+    "systemd",
+    "linux",
+    "mesa",
+    "llvm",
+    "libX11",
+    "gtk3",
+    "glib",
+    "dbus",
+    "ncurses",
+    "readline",
+    "curl",
+    "git",
+    "nix",
+    "util-linux",
+    "openssh",
+    "xz",
+    "bzip2",
+    "libffi",
+    "sqlite",
+    "icu",
+    "harfbuzz",
+    "freetype",
+    "fontconfig",
+    "pango",
+    "cairo",
+    "libpng",
+    "libjpeg-turbo",
+    "wayland",
+    "pipewire",
+    "alsa-lib",
+    "firefox",
+    "noto-fonts",
+    "linux-firmware",
+    "man-pages",
+    "hwdata",
+    "tzdata",
+    "gnugrep",
+    "gnused",
+    "gawk",
+    "findutils",
+    "diffutils",
+    "shadow",
+    "linux-pam",
+    "kbd",
+    "kmod",
+    "e2fsprogs",
+    "iproute2",
+    "iptables",
+    "nss",
+    "nspr",
+    "libxml2",
+    "libxslt",
+    "expat",
+    "pcre2",
+    "libidn2",
+    "libunistring",
+    "gmp",
+    "mpfr",
+    "acl",
+    "attr",
+    "libcap",
+    "libseccomp",
+    "lvm2",
+    "cryptsetup",
+    "json-c",
+    "libuv",
+    "nghttp2",
+    "brotli",
+    "zstd",
+    "lz4",
+    "libarchive",
+    "gnutls",
+    "p11-kit",
+    "libtasn1",
+    "krb5",
+    "cyrus-sasl",
+    "openldap",
+    "libgcrypt",
+    "libgpg-error",
+    "gnupg",
+    "vim",
+    "nano",
+    "htop",
+    "tmux",
+    "polkit",
+    "networkmanager",
+    "wpa_supplicant",
+    "bluez",
+    "cups",
+    "ghostscript",
+    "imagemagick",
+    "ffmpeg",
+    "gstreamer",
+    "qtbase",
+    "qtdeclarative",
+    "kdecoration",
+    "rustc",
+    "cargo",
+    "go",
+    "nodejs",
+    "ruby",
+    "lua",
+    "tcl",
+    "tk",
+    "boost",
+    "protobuf",
+    "grpc",
+    "abseil-cpp",
+];
+const PYTHON_PACKAGES: &[&str] = &[
+    "requests",
+    "urllib3",
+    "idna",
+    "certifi",
+    "six",
+    "setuptools",
+    "wheel",
+    "pyyaml",
+    "jinja2",
+    "markupsafe",
+    "click",
+    "attrs",
+    "packaging",
+    "pyparsing",
+    "cryptography",
+    "cffi",
+    "pycparser",
+    "numpy",
+    "pillow",
+    "dbus-python",
+    "pygobject",
+];
+/// Name, size in KiB and the percentage of paths referencing it directly.
+const CORE_PATHS: &[(&str, u64, u64)] = &[
+    ("glibc-2.40-66", 28_000, 70),
+    ("gcc-13.3.0-lib", 7_800, 30),
+    ("zlib-1.3.1", 140, 18),
+    ("bash-5.2p37", 1_600, 14),
+    ("openssl-3.3.2", 6_500, 10),
+    ("xz-5.6.3", 1_100, 6),
+    ("ncurses-6.5", 3_200, 6),
+    ("coreutils-9.5", 8_400, 5),
+];
+const SYLLABLES: &[&str] = &[
+    "ka", "lo", "mi", "ne", "ra", "su", "to", "vi", "xe", "zo", "pan", "gel", "dor", "fin", "mek",
+    "tri", "qua", "bel", "sor", "wen",
+];
+const NAME_SHAPES: &[&str] = &[
+    "lib{}",
+    "{}",
+    "{}-utils",
+    "perl5.38-{}",
+    "{}-tools",
+    "haskell-{}",
+];
+const OUTPUTS: &[(&str, u64)] = &[("dev", 10), ("lib", 8), ("man", 6), ("doc", 3), ("bin", 6)];
+
+/// SplitMix64, which is plenty for deterministic demo data.
+struct DemoRng(u64);
+
+impl DemoRng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut value = self.0;
+        value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        value ^ (value >> 31)
+    }
+
+    fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound.max(1) as u64) as usize
+    }
+
+    fn chance(&mut self, percent: u64) -> bool {
+        self.next_u64() % 100 < percent
+    }
+
+    fn normal(&mut self) -> f64 {
+        let u1 = self.unit().max(f64::MIN_POSITIVE);
+        let u2 = self.unit();
+        (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos()
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len())]
+    }
+}
+
+struct DemoNode {
+    id: String,
+    layer: usize,
+    nar_size: u64,
+    references: Vec<usize>,
+}
+
+/// Generates a synthetic closure of `node_count` paths: a layered DAG with
+/// preferential attachment (so a few paths like glibc are referenced by most
+/// others), log-normal sizes and nixpkgs-like names. The output is
+/// deterministic for a given node count.
+pub fn generate_demo_graph(node_count: usize) -> SystemGraph {
+    let node_count = node_count.max(2);
+    let mut rng = DemoRng(DEMO_SEED ^ node_count as u64);
+    let layer_count = ((node_count as f64).log2().ceil() as usize).clamp(3, 14);
+
+    let mut nodes = vec![DemoNode {
+        id: demo_id(&mut rng, "nixos-system-demo-24.11".to_owned()),
+        layer: 0,
+        nar_size: 48 * 1024,
+        references: Vec::new(),
+    }];
+    let core_layer = layer_count + 1;
+    let core_count = CORE_PATHS.len().min(node_count - 1);
+    for &(name, size_kib, _) in &CORE_PATHS[..core_count] {
+        nodes.push(DemoNode {
+            id: demo_id(&mut rng, name.to_owned()),
+            layer: core_layer,
+            nar_size: size_kib * 1024,
+            references: Vec::new(),
+        });
+    }
+    let mut name_uses = HashMap::<String, usize>::new();
+    for _ in 1 + core_count..node_count {
+        let layer = 1 + ((layer_count as f64) * (rng.unit() + rng.unit()) * 0.5) as usize;
+        let (name, nar_size) = demo_name_and_size(&mut rng, &mut name_uses);
+        nodes.push(DemoNode {
+            id: demo_id(&mut rng, name),
+            layer: layer.min(layer_count),
+            nar_size,
+            references: Vec::new(),
+        });
+    }
+
+    // Core paths such as glibc are referenced directly by a large share of
+    // the closure, which is what makes real closures so hub-heavy.
+    for (index, node) in nodes.iter_mut().enumerate().skip(1) {
+        for (core, &(_, _, percent)) in CORE_PATHS[..core_count].iter().enumerate() {
+            let core_index = 1 + core;
+            if index != core_index
+                && (core_index == 1 || node.layer < core_layer)
+                && rng.chance(percent)
+            {
+                node.references.push(core_index);
+            }
+        }
+    }
+
+    let mut order = (1 + core_count..node_count).collect::<Vec<_>>();
+    order.sort_by_key(|&index| std::cmp::Reverse(nodes[index].layer));
+
+    // Every entry is a candidate target; referenced nodes are pushed again so
+    // uniform picks from the pool favour already popular paths.
+    let mut pool = Vec::<usize>::with_capacity(node_count * 4);
+    let mut start = 0;
+    while start < order.len() {
+        let layer = nodes[order[start]].layer;
+        let end = start
+            + order[start..]
+                .iter()
+                .take_while(|&&index| nodes[index].layer == layer)
+                .count();
+
+        if !pool.is_empty() {
+            for &index in &order[start..end] {
+                let wanted =
+                    ((1.5 / rng.unit().max(1e-6).powf(1.0 / 1.5)) as usize).min(MAX_REFERENCES);
+                for _ in 0..wanted {
+                    let target = pool[rng.below(pool.len())];
+                    if !nodes[index].references.contains(&target) {
+                        nodes[index].references.push(target);
+                        pool.push(target);
+                    }
+                }
+            }
+        }
+
+        pool.extend_from_slice(&order[start..end]);
+        start = end;
+    }
+
+    // Attach every path without a referrer to a shallower one so the whole
+    // closure stays reachable from the root.
+    let mut referenced = vec![false; node_count];
+    for node in &nodes {
+        for &target in &node.references {
+            referenced[target] = true;
+        }
+    }
+    let mut by_layer = vec![Vec::new(); core_layer + 1];
+    for (index, node) in nodes.iter().enumerate() {
+        by_layer[node.layer].push(index);
+    }
+    for index in 1..node_count {
+        if referenced[index] {
+            continue;
+        }
+        let mut layer = nodes[index].layer - 1;
+        while by_layer[layer].is_empty() {
+            layer -= 1;
+        }
+        let parent = by_layer[layer][rng.below(by_layer[layer].len())];
+        nodes[parent].references.push(index);
+    }
+
+    let records = nodes
+        .iter()
+        .map(|node| {
+            let full_path = format!("{DEMO_STORE_DIR}/{}", node.id);
+            let fixed_output = node.id.ends_with("-source") || node.id.ends_with(".tar.gz");
+            let record = NodeRecord {
+                id: node.id.clone(),
+                classes: classify_node(&node.id, fixed_output, rng.chance(92)),
+                full_path,
+                store_dir: DEMO_STORE_DIR.to_owned(),
+                nar_size: node.nar_size,
+                closure_size: 0,
+                closure_count: 0,
+                retained_size: 0,
+                immediate_dominator: None,
+                references: node
+                    .references
+                    .iter()
+                    .map(|&target| nodes[target].id.clone())
+                    .collect(),
+                referrers: Vec::new(),
+                deriver: None,
+                derivation: None,
+            };
+            (record.id.clone(), record)
+        })
+        .collect::<HashMap<_, _>>();
+
+    let (mut nodes_by_id, edge_count) = link_references(records);
+    fill_closure_sizes(&mut nodes_by_id);
+    fill_retained_sizes(&mut nodes_by_id, &nodes[0].id, &NixControl::default());
+    SystemGraph {
+        store_dir: DEMO_STORE_DIR.to_owned(),
+        root_id: nodes[0].id.clone(),
+        nodes: nodes_by_id,
+        edge_count,
+        limitations: Vec::new(),
+        edge_weights: HashMap::new(),
+        roots: Vec::new(),
+        root_membership: HashMap::new(),
+    }
+}
+
+fn demo_id(rng: &mut DemoRng, name: String) -> String {
+    let hash = (0..32)
+        .map(|_| HASH_ALPHABET[rng.below(HASH_ALPHABET.len())] as char)
+        .collect::<String>();
+    format!("{hash}-{name}")
+}
+
+fn demo_name_and_size(rng: &mut DemoRng, name_uses: &mut HashMap<String, usize>) -> (String, u64) {
+    const KIB: f64 = 1024.0;
+
+    let mut pname = if rng.chance(15) {
+        format!("python3.12-{}", rng.pick(PYTHON_PACKAGES))
+    } else if rng.chance(6) {
+        format!("{}-source", rng.pick(PNAMES))
+    } else {
+        rng.pick(PNAMES).to_owned()
+    };
+
+    let uses = name_uses.entry(pname.clone()).or_insert(0);
+    *uses += 1;
+    if *uses > 3 && !pname.ends_with("-source") {
+        let stem = (0..2 + rng.below(2))
+            .map(|_| rng.pick(SYLLABLES))
+            .collect::<String>();
+        pname = rng.pick(NAME_SHAPES).replace("{}", &stem);
+    }
+
+    let version = if rng.chance(8) {
+        format!(
+            "0-unstable-2024-{:02}-{:02}",
+            1 + rng.below(12),
+            1 + rng.below(28)
+        )
+    } else {
+        format!("{}.{}.{}", rng.below(20), rng.below(40), rng.below(12))
+    };
+
+    let mut size = ((192.0 * KIB).ln() + 1.9 * rng.normal()).exp();
+    let mut name = if pname.ends_with("-source") {
+        pname
+    } else {
+        format!("{pname}-{version}")
+    };
+    if let Some(&(output, _)) = OUTPUTS.iter().find(|(_, percent)| rng.chance(*percent)) {
+        name = format!("{name}-{output}");
+        if matches!(output, "man" | "doc") {
+            size *= 0.3;
+        }
+    }
+    if name.contains("firmware") || name.starts_with("linux-") {
+        size *= 40.0;
+    }
+    if name.contains("fonts") || name.starts_with("llvm-") || name.starts_with("gcc-") {
+        size *= 8.0;
+    }
+
+    (name, size.clamp(4.0 * KIB, 2048.0 * KIB * KIB) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::generate_demo_graph;
+
+    #[test]
+    fn demo_graph_is_consistent() {
+        for node_count in [2, 10, 500, 3000] {
+            let graph = generate_demo_graph(node_count);
+            assert_eq!(graph.node_count(), node_count);
+            assert_eq!(graph.integrity_errors(), Vec::<String>::new());
+        }
+    }
+
+    #[test]
+    fn demo_graph_is_reachable_from_root() {
+        let graph = generate_demo_graph(2000);
+        for id in graph.nodes.keys() {
+            assert!(
+                graph.shortest_path_from_root(id).is_some(),
+                "{id} is unreachable"
+            );
+        }
+        assert_eq!(
+            graph.nodes[&graph.root_id].closure_size,
+            graph.nodes.values().map(|node| node.nar_size).sum::<u64>()
+        );
+    }
+
+    #[test]
+    fn closure_subgraphs_are_consistent() {
+        let graph = generate_demo_graph(1500);
+        for id in graph.nodes.keys().take(40) {
+            let subgraph = graph.closure_subgraph(id).unwrap();
+            assert_eq!(&subgraph.root_id, id);
+            assert_eq!(subgraph.integrity_errors(), Vec::<String>::new());
+            assert_eq!(
+                subgraph.nodes[id].closure_size,
+                subgraph
+                    .nodes
+                    .values()
+                    .map(|node| node.nar_size)
+                    .sum::<u64>()
+            );
+        }
+    }
+
+    #[test]
+    fn demo_graph_is_deterministic() {
+        let first = generate_demo_graph(800);
+        let second = generate_demo_graph(800);
+        assert_eq!(first.root_id, second.root_id);
+        assert_eq!(first.edge_count, second.edge_count);
+        assert!(first.nodes.keys().all(|id| second.nodes.contains_key(id)));
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::graph::SystemGraph;
+use super::nix_cmd::{NixControl, run_nix};
+use super::parse::normalize_store_key;
+use crate::util::parse_store_name;
+
+/// Derivations passed to one `nix derivation show`, to keep the command line
+/// short.
+const DERIVATION_BATCH: usize = 256;
+
+/// What the derivation that built a path says about it.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DerivationInfo {
+    pub pname: Option<String>,
+    pub version: Option<String>,
+    pub outputs: Vec<String>,
+    /// Only known when the derivation carries its license as an attribute;
+    /// nixpkgs keeps `meta` out of derivations.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub licenses: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct RawDerivation {
+    #[serde(default)]
+    env: HashMap<String, String>,
+    #[serde(default)]
+    outputs: BTreeMap<String, Value>,
+}
+
+/// Short names of the licenses in a nixpkgs license value: an attribute set
+/// with `spdxId` or `shortName`, a plain string, or a list of either.
+fn license_names(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(name) => vec![name.clone()],
+        Value::Array(licenses) => licenses.iter().flat_map(license_names).collect(),
+        Value::Object(license) => ["spdxId", "shortName", "fullName"]
+            .iter()
+            .find_map(|key| license.get(*key)?.as_str())
+            .map(|name| vec![name.to_owned()])
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+fn derivation_info(raw: RawDerivation) -> DerivationInfo {
+    let env = raw.env;
+    // Structured attributes keep everything in one JSON document.
+    let structured = env
+        .get("__json")
+        .and_then(|json| serde_json::from_str::<Value>(json).ok());
+    let attribute = |key: &str| {
+        env.get(key)
+            .cloned()
+            .or_else(|| structured.as_ref()?.get(key)?.as_str().map(str::to_owned))
+    };
+
+    let name = attribute("name");
+    let parsed = name.as_deref().map(parse_store_name);
+    let licenses = match env.get("license") {
+        Some(license) => license.split_whitespace().map(str::to_owned).collect(),
+        None => structured
+            .as_ref()
+            .and_then(|attrs| {
+                attrs
+                    .get("license")
+                    .or_else(|| attrs.get("meta")?.get("license"))
+            })
+            .map(license_names)
+            .unwrap_or_default(),
+    };
+
+    DerivationInfo {
+        pname: attribute("pname").or_else(|| parsed.map(|name| name.pname.to_owned())),
+        version: attribute("version")
+            .or_else(|| parsed.and_then(|name| name.version.map(str::to_owned))),
+        outputs: raw.outputs.into_keys().collect(),
+        licenses,
+    }
+}
+
+fn parse_derivations(raw: &str) -> Result<HashMap<String, DerivationInfo>> {
+    let derivations: HashMap<String, RawDerivation> =
+        serde_json::from_str(raw).context("invalid JSON from nix derivation show")?;
+    Ok(derivations
+        .into_iter()
+        .map(|(path, raw)| (normalize_store_key(&path), derivation_info(raw)))
+        .collect())
+}
+
+/// Attaches the pname, version, outputs and licenses recorded in each path's
+/// deriver. Derivers that are not in the local store are skipped and listed
+/// in the graph's limitations.
+pub fn enrich_derivations(control: &NixControl, graph: &mut SystemGraph) -> Result<()> {
+    let mut built_by = HashMap::<String, Vec<String>>::new();
+    for node in graph.nodes.values() {
+        if let Some(deriver) = &node.deriver {
+            built_by
+                .entry(deriver.clone())
+                .or_default()
+                .push(node.id.clone());
+        }
+    }
+
+    let mut derivers = built_by
+        .keys()
+        .map(|deriver| format!("{}/{deriver}", graph.store_dir))
+        .filter(|path| Path::new(path).exists())
+        .collect::<Vec<_>>();
+    derivers.sort();
+    let missing = built_by.len() - derivers.len();
+
+    let mut unreadable = 0usize;
+    for (batch_index, batch) in derivers.chunks(DERIVATION_BATCH).enumerate() {
+        if control.is_cancelled() {
+            return Err(anyhow!("derivation metadata lookup cancelled"));
+        }
+        control.report(format!(
+            "reading derivations {}/{}",
+            (batch_index * DERIVATION_BATCH + batch.len()),
+            derivers.len()
+        ));
+
+        let mut args = vec!["derivation", "show"];
+        args.extend(batch.iter().map(String::as_str));
+        let derivations = match run_nix(control, &args).and_then(|raw| parse_derivations(&raw)) {
+            Ok(derivations) => derivations,
+            Err(_) => {
+                unreadable += batch.len();
+                continue;
+            }
+        };
+
+        for (deriver, info) in derivations {
+            for id in built_by.get(&deriver).into_iter().flatten() {
+                if let Some(node) = graph.nodes.get_mut(id) {
+                    node.derivation = Some(Box::new(info.clone()));
+                }
+            }
+        }
+    }
+
+    if missing > 0 {
+        graph.limitations.push(format!(
+            "{missing} derivations are not in the local store; their paths lack derivation metadata."
+        ));
+    }
+    if unreadable > 0 {
+        graph.limitations.push(format!(
+            "{unreadable} derivations could not be read with nix derivation show."
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn reads_names_outputs_and_licenses() {
+        let raw = json!({
+            "/nix/store/aaaa-hello-2.12.1.drv": {
+                "env": { "name": "hello-2.12.1", "pname": "hello", "version": "2.12.1" },
+                "outputs": { "out": { "path": "/nix/store/bbbb-hello-2.12.1" } }
+            },
+            "cccc-source.tar.gz.drv": {
+                "env": {
+                    "name": "zlib-1.3.1.tar.gz",
+                    "__json": "{\"meta\":{\"license\":[{\"spdxId\":\"Zlib\"},\"free\"]}}"
+                },
+                "outputs": { "out": {}, "dev": {} }
+            }
+        });
+
+        let derivations = parse_derivations(&raw.to_string()).unwrap();
+        assert_eq!(
+            derivations["aaaa-hello-2.12.1.drv"],
+            DerivationInfo {
+                pname: Some("hello".to_owned()),
+                version: Some("2.12.1".to_owned()),
+                outputs: vec!["out".to_owned()],
+                licenses: Vec::new(),
+            }
+        );
+
+        let fetched = &derivations["cccc-source.tar.gz.drv"];
+        assert_eq!(fetched.pname.as_deref(), Some("zlib"));
+        assert_eq!(fetched.outputs, ["dev", "out"]);
+        assert_eq!(fetched.licenses, ["Zlib", "free"]);
+    }
+}
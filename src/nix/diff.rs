@@ -0,0 +1,232 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rayon::prelude::*;
+
+use crate::util::{parse_store_name, short_name};
+
+use super::graph::SystemGraph;
+use super::nix_cmd::NixControl;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathChange {
+    Added,
+    Removed,
+    /// The same package and output under a different store path, usually a
+    /// new version or a rebuild. Holds the previous path's id.
+    Changed(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct DiffEntry {
+    /// The path in the current closure, or in the base one when removed.
+    pub id: String,
+    pub change: PathChange,
+    pub nar_delta: i64,
+    pub closure_delta: i64,
+}
+
+/// How a closure differs from a base closure, such as the previous system
+/// generation. Paths present in both with the same id are left out.
+#[derive(Clone, Debug)]
+pub struct ClosureDiff {
+    pub base_root: String,
+    pub root_closure_delta: i64,
+    /// Sorted by the size of the closure delta, largest first.
+    pub entries: Vec<DiffEntry>,
+    index: HashMap<String, usize>,
+}
+
+impl ClosureDiff {
+    pub fn entry(&self, id: &str) -> Option<&DiffEntry> {
+        self.index.get(id).map(|&index| &self.entries[index])
+    }
+
+    pub fn count(&self, matches: impl Fn(&PathChange) -> bool) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| matches(&entry.change))
+            .count()
+    }
+}
+
+/// Paths of one package and output share a key across versions and hashes.
+type PackageKey<'a> = (&'a str, Option<&'a str>, bool);
+
+fn package_key(id: &str) -> PackageKey<'_> {
+    let parsed = parse_store_name(short_name(id));
+    (parsed.pname, parsed.output, parsed.is_derivation)
+}
+
+fn delta(current: u64, base: u64) -> i64 {
+    current as i64 - base as i64
+}
+
+/// Paths of `graph` missing from `other`, keyed by package.
+fn only_in<'a>(graph: &'a SystemGraph, other: &SystemGraph) -> Vec<(PackageKey<'a>, &'a str)> {
+    graph
+        .nodes
+        .par_iter()
+        .filter(|(id, _)| !other.nodes.contains_key(*id))
+        .map(|(id, _)| (package_key(id), id.as_str()))
+        .collect()
+}
+
+/// Matches one package's removed and added paths in order; the surplus on
+/// either side is reported as removed or added.
+fn align_package(
+    base: &SystemGraph,
+    current: &SystemGraph,
+    removed: &[&str],
+    added: &[&str],
+) -> Vec<DiffEntry> {
+    let paired = removed.len().min(added.len());
+    let mut entries = Vec::with_capacity(removed.len().max(added.len()));
+
+    for (previous, id) in removed.iter().zip(added) {
+        let (old, new) = (&base.nodes[*previous], &current.nodes[*id]);
+        entries.push(DiffEntry {
+            id: (*id).to_owned(),
+            change: PathChange::Changed((*previous).to_owned()),
+            nar_delta: delta(new.nar_size, old.nar_size),
+            closure_delta: delta(new.closure_size, old.closure_size),
+        });
+    }
+    for id in &added[paired..] {
+        let node = &current.nodes[*id];
+        entries.push(DiffEntry {
+            id: (*id).to_owned(),
+            change: PathChange::Added,
+            nar_delta: delta(node.nar_size, 0),
+            closure_delta: delta(node.closure_size, 0),
+        });
+    }
+    for id in &removed[paired..] {
+        let node = &base.nodes[*id];
+        entries.push(DiffEntry {
+            id: (*id).to_owned(),
+            change: PathChange::Removed,
+            nar_delta: delta(0, node.nar_size),
+            closure_delta: delta(0, node.closure_size),
+        });
+    }
+
+    entries
+}
+
+/// Pairs paths that left and entered the closure by package, then sizes each
+/// pair; both the lookups and the per-package work run in parallel. The
+/// packages aligned so far are counted as `control`'s path progress.
+pub fn diff_closures(
+    base: &SystemGraph,
+    current: &SystemGraph,
+    control: &NixControl,
+) -> ClosureDiff {
+    let (removed, added) = rayon::join(|| only_in(base, current), || only_in(current, base));
+
+    let mut packages = BTreeMap::<_, (Vec<&str>, Vec<&str>)>::new();
+    for (key, id) in removed {
+        packages.entry(key).or_default().0.push(id);
+    }
+    for (key, id) in added {
+        packages.entry(key).or_default().1.push(id);
+    }
+
+    let package_count = packages.len();
+    control.report(format!("aligning {package_count} changed packages"));
+    let aligned = AtomicUsize::new(0);
+    let mut entries = packages
+        .into_par_iter()
+        .flat_map_iter(|(_, (mut removed, mut added))| {
+            removed.sort_unstable();
+            added.sort_unstable();
+            let entries = align_package(base, current, &removed, &added);
+            let done = aligned.fetch_add(1, Ordering::Relaxed) + 1;
+            control.count_paths(done, package_count);
+            entries
+        })
+        .collect::<Vec<_>>();
+
+    entries.par_sort_unstable_by(|a, b| {
+        b.closure_delta
+            .unsigned_abs()
+            .cmp(&a.closure_delta.unsigned_abs())
+            .then_with(|| a.id.cmp(&b.id))
+    });
+    let index = entries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| (entry.id.clone(), index))
+        .collect();
+
+    let root_size = |graph: &SystemGraph| {
+        graph
+            .nodes
+            .get(&graph.root_id)
+            .map_or(0, |node| node.closure_size)
+    };
+
+    ClosureDiff {
+        base_root: base.root_id.clone(),
+        root_closure_delta: delta(root_size(current), root_size(base)),
+        entries,
+        index,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nix::generate_demo_graph;
+
+    #[test]
+    fn identical_closures_have_no_entries() {
+        let graph = generate_demo_graph(200);
+        let diff = diff_closures(&graph, &graph, &NixControl::default());
+
+        assert!(diff.entries.is_empty());
+        assert_eq!(diff.root_closure_delta, 0);
+    }
+
+    #[test]
+    fn classifies_added_removed_and_changed_paths() {
+        let base = generate_demo_graph(200);
+        let mut current = base.clone();
+
+        let mut ids = base
+            .nodes
+            .keys()
+            .filter(|id| **id != base.root_id)
+            .cloned()
+            .collect::<Vec<_>>();
+        ids.sort();
+
+        let removed = current.nodes.remove(&ids[0]).unwrap();
+        let mut bumped = current.nodes.remove(&ids[1]).unwrap();
+        let bumped_id = format!("0000{}", &ids[1][4..]);
+        bumped.id = bumped_id.clone();
+        bumped.nar_size += 1000;
+        bumped.closure_size += 1000;
+        current.nodes.insert(bumped_id.clone(), bumped);
+
+        let mut added = current.nodes[&ids[2]].clone();
+        added.id = "zzzz-brand-new-1.0".to_owned();
+        current.nodes.insert(added.id.clone(), added);
+
+        let diff = diff_closures(&base, &current, &NixControl::default());
+
+        assert_eq!(diff.entries.len(), 3);
+        assert_eq!(
+            diff.entry(&bumped_id).map(|entry| &entry.change),
+            Some(&PathChange::Changed(ids[1].clone()))
+        );
+        assert_eq!(diff.entry(&bumped_id).unwrap().nar_delta, 1000);
+        assert_eq!(
+            diff.entry("zzzz-brand-new-1.0").map(|entry| &entry.change),
+            Some(&PathChange::Added)
+        );
+        let removed_entry = diff.entry(&removed.id).unwrap();
+        assert_eq!(removed_entry.change, PathChange::Removed);
+        assert_eq!(removed_entry.closure_delta, -(removed.closure_size as i64));
+    }
+}
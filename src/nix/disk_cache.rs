@@ -0,0 +1,121 @@
+use std::cmp::Reverse;
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use super::graph::SystemGraph;
+use super::nix_cmd::NixControl;
+use super::snapshot::{read_graph, write_graph};
+
+/// Cached graphs kept on disk; older ones are removed as new ones are added.
+const CACHED_GRAPHS: usize = 8;
+
+/// `$XDG_CACHE_HOME/nix-analisa`, or `~/.cache/nix-analisa`.
+fn cache_dir() -> Option<PathBuf> {
+    let cache_home = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(cache_home.join("nix-analisa"))
+}
+
+/// Store paths never change, so a graph collected for one root stays valid
+/// for as long as that root exists, in the store it was read from: `store`
+/// is [`NixControl::store_identity`].
+fn cache_file(dir: &Path, root_id: &str, store: u64) -> PathBuf {
+    dir.join(format!("{root_id}-{store:016x}.json"))
+}
+
+/// The graph collected earlier for `root_id` from the store `control`
+/// reads, if it was cached and still reads.
+pub(super) fn load_cached_graph(control: &NixControl, root_id: &str) -> Option<SystemGraph> {
+    read_cached_graph(&cache_dir()?, root_id, control.store_identity())
+}
+
+/// Caches `graph` under its root and the store `control` reads, then drops
+/// the least recently written graphs beyond [`CACHED_GRAPHS`].
+pub(super) fn store_cached_graph(control: &NixControl, graph: &SystemGraph) -> Result<()> {
+    let dir = cache_dir().context("neither XDG_CACHE_HOME nor HOME is set")?;
+    write_cached_graph(&dir, graph, control.store_identity())
+}
+
+fn read_cached_graph(dir: &Path, root_id: &str, store: u64) -> Option<SystemGraph> {
+    let path = cache_file(dir, root_id, store);
+    let file = File::open(&path).ok()?;
+    match read_graph(BufReader::new(file)) {
+        Ok(graph) if graph.root_id == root_id => Some(graph),
+        // Written by a newer version or cut short; collect it again.
+        _ => {
+            let _ = fs::remove_file(&path);
+            None
+        }
+    }
+}
+
+fn write_cached_graph(dir: &Path, graph: &SystemGraph, store: u64) -> Result<()> {
+    fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+
+    let path = cache_file(dir, &graph.root_id, store);
+    let partial = path.with_extension("json.partial");
+    let file = File::create(&partial)
+        .with_context(|| format!("failed to create {}", partial.display()))?;
+    let mut writer = BufWriter::new(file);
+    write_graph(graph, &mut writer)?;
+    writer
+        .flush()
+        .with_context(|| format!("failed to write {}", partial.display()))?;
+    fs::rename(&partial, &path)
+        .with_context(|| format!("failed to move the cached graph to {}", path.display()))?;
+
+    prune_cache(dir);
+    Ok(())
+}
+
+fn prune_cache(dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut cached = entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+        .collect::<Vec<_>>();
+    cached.sort_by_key(|(modified, _)| Reverse(*modified));
+    for (_, path) in cached.into_iter().skip(CACHED_GRAPHS) {
+        let _ = fs::remove_file(path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::demo::generate_demo_graph;
+    use super::*;
+
+    #[test]
+    fn reads_back_cached_graphs_and_keeps_only_the_newest() {
+        let dir = env::temp_dir().join(format!("nix-analisa-cache-{}", std::process::id()));
+        let graph = generate_demo_graph(40);
+        write_cached_graph(&dir, &graph, 1).unwrap();
+
+        let cached = read_cached_graph(&dir, &graph.root_id, 1).unwrap();
+        assert_eq!(cached.node_count(), graph.node_count());
+        assert_eq!(cached.edge_count, graph.edge_count);
+        assert!(read_cached_graph(&dir, "0000-other-root", 1).is_none());
+        assert!(read_cached_graph(&dir, &graph.root_id, 2).is_none());
+
+        fs::write(cache_file(&dir, "0000-truncated", 1), "{\"format\":").unwrap();
+        assert!(read_cached_graph(&dir, "0000-truncated", 1).is_none());
+        assert!(!cache_file(&dir, "0000-truncated", 1).exists());
+
+        for index in 0..CACHED_GRAPHS + 2 {
+            fs::write(cache_file(&dir, &format!("{index:04}-old"), 1), "{}").unwrap();
+        }
+        prune_cache(&dir);
+        let kept = fs::read_dir(&dir).unwrap().count();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(kept, CACHED_GRAPHS);
+    }
+}
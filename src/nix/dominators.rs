@@ -0,0 +1,440 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use rayon::prelude::*;
+
+use super::graph::NodeRecord;
+use super::nix_cmd::NixControl;
+
+/// Fills in each node's immediate dominator and retained size: the NAR size
+/// that would leave the closure if the node were removed, i.e. the node plus
+/// everything only reachable through it.
+///
+/// Dominators are computed from a virtual root whose children are `root_id`,
+/// every path nothing refers to, and then any path still unreached, so that
+/// whole-store graphs with many roots are covered too. Paths dominated only by
+/// the virtual root have no immediate dominator.
+///
+/// Each pass reports through `control`, and the level-by-level ones count
+/// the paths resolved so far as its path progress.
+pub fn fill_retained_sizes(
+    nodes: &mut HashMap<String, NodeRecord>,
+    root_id: &str,
+    control: &NixControl,
+) {
+    let mut ids = nodes.keys().cloned().collect::<Vec<_>>();
+    ids.par_sort_unstable();
+    let count = ids.len();
+
+    let index_by_id = ids
+        .iter()
+        .enumerate()
+        .map(|(index, id)| (id.as_str(), index))
+        .collect::<HashMap<_, _>>();
+    let successors = ids
+        .par_iter()
+        .enumerate()
+        .map(|(index, id)| {
+            nodes[id]
+                .references
+                .iter()
+                .filter_map(|reference| index_by_id.get(reference.as_str()).copied())
+                .filter(|&target| target != index)
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let mut predecessors = vec![Vec::new(); count + 1];
+    for (source, targets) in successors.iter().enumerate() {
+        for &target in targets {
+            predecessors[target].push(source);
+        }
+    }
+    let root = index_by_id.get(root_id).copied();
+
+    // Store closures are acyclic apart from self-references, which are
+    // dropped above; anything else takes the general route.
+    control.report(format!("computing dominators of {count} paths"));
+    let (idom, order) = acyclic_dominators(&successors, &mut predecessors, root, control)
+        .unwrap_or_else(|| iterative_dominators(&successors, &mut predecessors, root, control));
+    control.report(format!("summing retained sizes of {count} paths"));
+    let retained = accumulate_retained(&ids, nodes, &idom, &order, control);
+
+    let virtual_root = count;
+    let updates = ids
+        .iter()
+        .enumerate()
+        .map(|(index, id)| {
+            let immediate_dominator =
+                (idom[index] != virtual_root).then(|| ids[idom[index]].clone());
+            (id.as_str(), (retained[index], immediate_dominator))
+        })
+        .collect::<HashMap<_, _>>();
+    nodes.par_iter_mut().for_each(|(id, node)| {
+        if let Some((retained_size, immediate_dominator)) = updates.get(id.as_str()) {
+            node.retained_size = *retained_size;
+            node.immediate_dominator = immediate_dominator.clone();
+        }
+    });
+}
+
+/// Dominators of a DAG, one topological level at a time: every predecessor
+/// of a level sits on an earlier one, so the nodes of a level are resolved
+/// independently and in parallel. Returns `None` when the graph has a cycle.
+///
+/// Also returns a topological order, in which dominators precede the paths
+/// they dominate.
+fn acyclic_dominators(
+    successors: &[Vec<usize>],
+    predecessors: &mut [Vec<usize>],
+    root: Option<usize>,
+    control: &NixControl,
+) -> Option<(Vec<usize>, Vec<usize>)> {
+    let count = successors.len();
+    let virtual_root = count;
+
+    // Kahn's algorithm, recording the longest distance from a source.
+    let mut in_degree = predecessors[..count]
+        .iter()
+        .map(Vec::len)
+        .collect::<Vec<_>>();
+    let mut level = vec![0usize; count];
+    let mut order = (0..count)
+        .filter(|&index| in_degree[index] == 0)
+        .collect::<Vec<_>>();
+    let mut cursor = 0;
+    while let Some(&node) = order.get(cursor) {
+        cursor += 1;
+        for &child in &successors[node] {
+            level[child] = level[child].max(level[node] + 1);
+            in_degree[child] -= 1;
+            if in_degree[child] == 0 {
+                order.push(child);
+            }
+        }
+    }
+    if order.len() < count {
+        return None;
+    }
+
+    let mut entry_points = root.into_iter().collect::<Vec<_>>();
+    entry_points.extend((0..count).filter(|&index| predecessors[index].is_empty()));
+    for index in entry_points {
+        if !predecessors[index].contains(&virtual_root) {
+            predecessors[index].push(virtual_root);
+        }
+    }
+
+    let mut levels = vec![Vec::new(); level.iter().max().map_or(0, |max| max + 1)];
+    for &node in &order {
+        levels[level[node]].push(node);
+    }
+
+    let mut idom = vec![virtual_root; count + 1];
+    let mut depth = vec![0usize; count + 1];
+    let mut done = 0;
+    for nodes in levels {
+        let resolved = nodes
+            .par_iter()
+            .map(|&node| {
+                let new_idom = predecessors[node]
+                    .iter()
+                    .copied()
+                    .reduce(|a, b| intersect_by_depth(&idom, &depth, a, b))
+                    .unwrap_or(virtual_root);
+                (node, new_idom)
+            })
+            .collect::<Vec<_>>();
+        done += resolved.len();
+        for (node, new_idom) in resolved {
+            idom[node] = new_idom;
+            depth[node] = depth[new_idom] + 1;
+        }
+        control.count_paths(done, count);
+    }
+
+    Some((idom, order))
+}
+
+/// The nearest common ancestor of `a` and `b` in the dominator tree built so far.
+fn intersect_by_depth(idom: &[usize], depth: &[usize], mut a: usize, mut b: usize) -> usize {
+    while a != b {
+        if depth[a] >= depth[b] {
+            a = idom[a];
+        } else {
+            b = idom[b];
+        }
+    }
+    a
+}
+
+/// Cooper, Harvey and Kennedy's iterative algorithm, for graphs with cycles.
+/// Returns the dominators and the reverse postorder they were computed in.
+fn iterative_dominators(
+    successors: &[Vec<usize>],
+    predecessors: &mut [Vec<usize>],
+    root: Option<usize>,
+    control: &NixControl,
+) -> (Vec<usize>, Vec<usize>) {
+    let count = successors.len();
+    let virtual_root = count;
+
+    let mut entry_points = root.into_iter().collect::<Vec<_>>();
+    entry_points.extend((0..count).filter(|&index| predecessors[index].is_empty()));
+    entry_points.extend(0..count);
+
+    // Depth-first postorder from the virtual root; unreached entry points
+    // become its children in turn.
+    let mut postorder = Vec::with_capacity(count + 1);
+    let mut visited = vec![false; count + 1];
+    visited[virtual_root] = true;
+    let mut stack = Vec::new();
+    for start in entry_points {
+        if visited[start] {
+            continue;
+        }
+        visited[start] = true;
+        predecessors[start].push(virtual_root);
+        stack.push((start, 0));
+        while let Some((node, next_child)) = stack.last_mut() {
+            let node = *node;
+            if let Some(&child) = successors[node].get(*next_child) {
+                *next_child += 1;
+                if !visited[child] {
+                    visited[child] = true;
+                    stack.push((child, 0));
+                }
+            } else {
+                postorder.push(node);
+                stack.pop();
+            }
+        }
+    }
+    postorder.push(virtual_root);
+
+    let mut order = vec![0; count + 1];
+    for (position, &node) in postorder.iter().enumerate() {
+        order[node] = position;
+    }
+
+    let mut idom = vec![usize::MAX; count + 1];
+    idom[virtual_root] = virtual_root;
+    let intersect = |idom: &[usize], mut a: usize, mut b: usize| {
+        while a != b {
+            while order[a] < order[b] {
+                a = idom[a];
+            }
+            while order[b] < order[a] {
+                b = idom[b];
+            }
+        }
+        a
+    };
+    let mut changed = true;
+    let mut pass = 0;
+    while changed {
+        changed = false;
+        pass += 1;
+        control.report(format!("dominator pass {pass} over {count} paths"));
+        for &node in postorder.iter().rev().skip(1) {
+            let mut processed = predecessors[node]
+                .iter()
+                .copied()
+                .filter(|&predecessor| idom[predecessor] != usize::MAX);
+            let Some(first) = processed.next() else {
+                continue;
+            };
+            let new_idom = processed.fold(first, |current, predecessor| {
+                intersect(&idom, predecessor, current)
+            });
+            if idom[node] != new_idom {
+                idom[node] = new_idom;
+                changed = true;
+            }
+        }
+    }
+
+    postorder.pop();
+    postorder.reverse();
+    (idom, postorder)
+}
+
+/// Sums NAR sizes up the dominator tree, deepest paths first. `order` lists
+/// dominators before the paths they dominate.
+fn accumulate_retained(
+    ids: &[String],
+    nodes: &HashMap<String, NodeRecord>,
+    idom: &[usize],
+    order: &[usize],
+    control: &NixControl,
+) -> Vec<u64> {
+    let virtual_root = ids.len();
+    let mut depth = vec![0usize; ids.len() + 1];
+    let mut by_depth = Vec::<Vec<usize>>::new();
+    for &node in order {
+        depth[node] = depth[idom[node]] + 1;
+        if by_depth.len() < depth[node] {
+            by_depth.resize_with(depth[node], Vec::new);
+        }
+        by_depth[depth[node] - 1].push(node);
+    }
+
+    let retained = ids
+        .par_iter()
+        .map(|id| AtomicU64::new(nodes[id].nar_size))
+        .collect::<Vec<_>>();
+    // Each depth only adds into the one above it, which is read next.
+    let mut done = 0;
+    for level in by_depth.iter().rev() {
+        level.par_iter().for_each(|&node| {
+            if idom[node] != virtual_root {
+                retained[idom[node]]
+                    .fetch_add(retained[node].load(Ordering::Relaxed), Ordering::Relaxed);
+            }
+        });
+        done += level.len();
+        control.count_paths(done, order.len());
+    }
+
+    retained.into_iter().map(AtomicU64::into_inner).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::graph::test_node;
+    use super::*;
+
+    #[test]
+    fn shared_paths_are_retained_by_their_common_dominator() {
+        // root -> a -> c, root -> b -> c, a -> d
+        let mut nodes = HashMap::from([
+            test_node("root", 1, &["a", "b"]),
+            test_node("a", 10, &["c", "d"]),
+            test_node("b", 100, &["c"]),
+            test_node("c", 1000, &[]),
+            test_node("d", 10000, &["d"]),
+        ]);
+        fill_retained_sizes(&mut nodes, "root", &NixControl::default());
+
+        assert_eq!(nodes["root"].retained_size, 11111);
+        assert_eq!(nodes["a"].retained_size, 10010);
+        assert_eq!(nodes["b"].retained_size, 100);
+        assert_eq!(nodes["c"].retained_size, 1000);
+        assert_eq!(nodes["c"].immediate_dominator.as_deref(), Some("root"));
+        assert_eq!(nodes["d"].immediate_dominator.as_deref(), Some("a"));
+        assert_eq!(nodes["root"].immediate_dominator, None);
+    }
+
+    #[test]
+    fn unreachable_paths_get_their_own_roots() {
+        let mut nodes = HashMap::from([
+            test_node("root", 1, &["shared"]),
+            test_node("other", 2, &["shared", "own"]),
+            test_node("shared", 5, &[]),
+            test_node("own", 7, &[]),
+        ]);
+        fill_retained_sizes(&mut nodes, "root", &NixControl::default());
+
+        assert_eq!(nodes["root"].retained_size, 1);
+        assert_eq!(nodes["other"].retained_size, 9);
+        assert_eq!(nodes["shared"].immediate_dominator, None);
+    }
+
+    fn flow_graph(nodes: &HashMap<String, NodeRecord>) -> (Vec<Vec<usize>>, Vec<Vec<usize>>) {
+        let mut ids = nodes.keys().collect::<Vec<_>>();
+        ids.sort_unstable();
+        let index_by_id = ids
+            .iter()
+            .enumerate()
+            .map(|(index, id)| (id.as_str(), index))
+            .collect::<HashMap<_, _>>();
+        let successors = ids
+            .iter()
+            .map(|id| {
+                nodes[*id]
+                    .references
+                    .iter()
+                    .map(|reference| index_by_id[reference.as_str()])
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let mut predecessors = vec![Vec::new(); ids.len() + 1];
+        for (source, targets) in successors.iter().enumerate() {
+            for &target in targets {
+                predecessors[target].push(source);
+            }
+        }
+        (successors, predecessors)
+    }
+
+    #[test]
+    fn level_parallel_and_iterative_dominators_agree() {
+        let graph = crate::nix::generate_demo_graph(2000);
+        let (successors, predecessors) = flow_graph(&graph.nodes);
+        let mut ids = graph.nodes.keys().collect::<Vec<_>>();
+        ids.sort_unstable();
+        let root = ids.iter().position(|id| **id == graph.root_id);
+        let control = NixControl::default();
+
+        let (parallel, _) =
+            acyclic_dominators(&successors, &mut predecessors.clone(), root, &control)
+                .expect("demo graphs are acyclic");
+        let (iterative, _) =
+            iterative_dominators(&successors, &mut predecessors.clone(), root, &control);
+        assert_eq!(parallel[..ids.len()], iterative[..ids.len()]);
+    }
+
+    #[test]
+    fn cycles_fall_back_to_the_iterative_algorithm() {
+        let mut nodes = HashMap::from([
+            test_node("root", 1, &["a"]),
+            test_node("a", 10, &["b"]),
+            test_node("b", 100, &["a", "c"]),
+            test_node("c", 1000, &[]),
+        ]);
+        let (successors, mut predecessors) = flow_graph(&nodes);
+        assert!(
+            acyclic_dominators(
+                &successors,
+                &mut predecessors,
+                Some(3),
+                &NixControl::default()
+            )
+            .is_none()
+        );
+
+        fill_retained_sizes(&mut nodes, "root", &NixControl::default());
+        assert_eq!(nodes["a"].retained_size, 1110);
+        assert_eq!(nodes["c"].immediate_dominator.as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn reports_each_pass_and_counts_resolved_paths() {
+        let mut graph = crate::nix::generate_demo_graph(500);
+        let (progress_tx, progress_rx) = std::sync::mpsc::channel();
+        let control = NixControl::default().with_progress(progress_tx);
+
+        fill_retained_sizes(&mut graph.nodes, &graph.root_id, &control);
+
+        let reports = progress_rx.try_iter().collect::<Vec<_>>();
+        assert_eq!(
+            reports,
+            [
+                "computing dominators of 500 paths",
+                "summing retained sizes of 500 paths"
+            ]
+        );
+        assert_eq!(control.path_progress(), Some((500, 500)));
+    }
+
+    #[test]
+    fn the_root_retains_the_whole_demo_closure() {
+        let graph = crate::nix::generate_demo_graph(300);
+        let root = &graph.nodes[&graph.root_id];
+
+        assert_eq!(root.retained_size, root.closure_size);
+        for node in graph.nodes.values() {
+            assert!(node.retained_size >= node.nar_size);
+            assert!(node.retained_size <= node.closure_size);
+        }
+    }
+}
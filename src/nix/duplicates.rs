@@ -0,0 +1,145 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use super::graph::SystemGraph;
+use crate::util::{parse_store_name, short_name};
+
+/// The paths of one version of a duplicated package, every output included.
+pub struct PackageVersion {
+    pub version: String,
+    pub ids: Vec<String>,
+    pub nar_size: u64,
+}
+
+/// A package present in more than one version.
+pub struct DuplicatePackage {
+    pub pname: String,
+    /// Largest first.
+    pub versions: Vec<PackageVersion>,
+    /// The NAR size of every version but the largest, which is what aligning
+    /// on one version would save at best.
+    pub wasted_bytes: u64,
+}
+
+impl DuplicatePackage {
+    pub fn path_count(&self) -> usize {
+        self.versions.iter().map(|version| version.ids.len()).sum()
+    }
+}
+
+impl SystemGraph {
+    /// Packages present in more than one version, most wasted bytes first.
+    /// Paths are grouped by the pname and version their derivation records,
+    /// falling back to the store name; `.drv` files and unversioned paths
+    /// are left out.
+    pub fn duplicate_packages(&self) -> Vec<DuplicatePackage> {
+        let mut packages = BTreeMap::<&str, BTreeMap<&str, Vec<&str>>>::new();
+        for (id, node) in &self.nodes {
+            let name = parse_store_name(short_name(id));
+            if name.is_derivation {
+                continue;
+            }
+            let recorded = node.derivation.as_deref();
+            let pname = recorded
+                .and_then(|info| info.pname.as_deref())
+                .unwrap_or(name.pname);
+            let Some(version) = recorded
+                .and_then(|info| info.version.as_deref())
+                .or(name.version)
+            else {
+                continue;
+            };
+            packages
+                .entry(pname)
+                .or_default()
+                .entry(version)
+                .or_default()
+                .push(id);
+        }
+
+        let mut duplicates = packages
+            .into_iter()
+            .filter(|(_, versions)| versions.len() > 1)
+            .map(|(pname, versions)| {
+                let mut versions = versions
+                    .into_iter()
+                    .map(|(version, ids)| {
+                        let mut ids = ids.into_iter().map(str::to_owned).collect::<Vec<_>>();
+                        ids.sort_unstable();
+                        PackageVersion {
+                            version: version.to_owned(),
+                            nar_size: ids.iter().map(|id| self.nodes[id].nar_size).sum(),
+                            ids,
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                versions.sort_by_key(|version| Reverse(version.nar_size));
+                let wasted_bytes = versions[1..].iter().map(|version| version.nar_size).sum();
+                DuplicatePackage {
+                    pname: pname.to_owned(),
+                    versions,
+                    wasted_bytes,
+                }
+            })
+            .collect::<Vec<_>>();
+        duplicates.sort_by_key(|package| Reverse(package.wasted_bytes));
+        duplicates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::super::graph::test_node;
+    use super::*;
+
+    #[test]
+    fn groups_versions_and_sums_what_is_wasted() {
+        let graph = SystemGraph {
+            store_dir: "/nix/store".to_owned(),
+            root_id: "aaaa-nixos-system".to_owned(),
+            nodes: HashMap::from([
+                test_node("aaaa-nixos-system", 1, &[]),
+                test_node("bbbb-glibc-2.39-52", 30, &[]),
+                test_node("cccc-glibc-2.39-52-bin", 5, &[]),
+                test_node("dddd-glibc-2.38-77", 28, &[]),
+                test_node("eeee-python3-3.12.4", 100, &[]),
+                test_node("ffff-python3-3.11.9", 90, &[]),
+                test_node("gggg-python3-3.10.14", 80, &[]),
+                test_node("hhhh-python3-3.10.14.drv", 1, &[]),
+                test_node("iiii-zlib-1.3.1", 2, &[]),
+            ]),
+            edge_count: 0,
+            limitations: Vec::new(),
+            edge_weights: HashMap::new(),
+            roots: Vec::new(),
+            root_membership: HashMap::new(),
+        };
+
+        let duplicates = graph.duplicate_packages();
+        let summary = duplicates
+            .iter()
+            .map(|package| {
+                let versions = package
+                    .versions
+                    .iter()
+                    .map(|version| version.version.as_str())
+                    .collect::<Vec<_>>();
+                (package.pname.as_str(), versions, package.wasted_bytes)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                ("python3", vec!["3.12.4", "3.11.9", "3.10.14"], 170),
+                ("glibc", vec!["2.39-52", "2.38-77"], 28),
+            ]
+        );
+        assert_eq!(duplicates[1].path_count(), 3);
+        assert_eq!(
+            duplicates[1].versions[0].ids,
+            ["bbbb-glibc-2.39-52", "cccc-glibc-2.39-52-bin"]
+        );
+    }
+}
@@ -0,0 +1,236 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{Context, Result, anyhow};
+use rayon::prelude::*;
+
+use super::graph::SystemGraph;
+use super::nix_cmd::{NixControl, run_nix};
+
+/// One garbage collector root: a symlink, or a runtime root such as
+/// `{memory:12}`, and the store path it keeps alive.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GcRoot {
+    pub link: String,
+    /// Store path name of the target, without the store directory.
+    pub target: String,
+}
+
+/// Which GC roots keep each path of a graph alive.
+#[derive(Clone, Debug, Default)]
+pub struct GcRoots {
+    pub roots: Vec<GcRoot>,
+    /// Indices into `roots`, per path id.
+    keepers: HashMap<String, Vec<usize>>,
+    /// Paths no root outside the graph's own roots keeps alive; they would be
+    /// garbage-collected along with this system.
+    sole: HashSet<String>,
+    sole_nar_size: u64,
+    /// Roots whose closure could not be queried, e.g. dangling links.
+    pub unreadable: usize,
+}
+
+impl GcRoots {
+    pub fn keepers(&self, id: &str) -> impl Iterator<Item = &GcRoot> {
+        self.keepers
+            .get(id)
+            .into_iter()
+            .flatten()
+            .map(|&index| &self.roots[index])
+    }
+
+    pub fn is_sole(&self, id: &str) -> bool {
+        self.sole.contains(id)
+    }
+
+    /// Path count and NAR size of what only this system keeps alive.
+    pub fn sole_totals(&self) -> (usize, u64) {
+        (self.sole.len(), self.sole_nar_size)
+    }
+
+    /// Indexes `roots` against `graph`. Closures of roots inside the graph are
+    /// walked locally; `outside` holds the closures of the others.
+    fn index(
+        graph: &SystemGraph,
+        roots: Vec<GcRoot>,
+        outside: &HashMap<String, Vec<String>>,
+        unreadable: usize,
+    ) -> Self {
+        let system = if graph.is_multi_root() {
+            graph
+                .roots
+                .iter()
+                .map(String::as_str)
+                .collect::<HashSet<_>>()
+        } else {
+            HashSet::from([graph.root_id.as_str()])
+        };
+
+        let mut keepers = HashMap::<String, Vec<usize>>::new();
+        let mut shared = HashSet::new();
+        for (index, root) in roots.iter().enumerate() {
+            let closure = match outside.get(&root.target) {
+                Some(closure) => closure.iter().map(String::as_str).collect(),
+                None => graph.closure_ids([root.target.as_str()]),
+            };
+            let external = !system.contains(root.target.as_str());
+            for id in closure {
+                if !graph.nodes.contains_key(id) {
+                    continue;
+                }
+                keepers.entry(id.to_owned()).or_default().push(index);
+                if external {
+                    shared.insert(id);
+                }
+            }
+        }
+
+        let sole = graph
+            .closure_ids(system.iter().copied())
+            .into_iter()
+            .filter(|id| !shared.contains(id))
+            .map(str::to_owned)
+            .collect::<HashSet<_>>();
+        let sole_nar_size = sole.iter().map(|id| graph.nodes[id].nar_size).sum();
+        Self {
+            roots,
+            keepers,
+            sole,
+            sole_nar_size,
+            unreadable,
+        }
+    }
+}
+
+/// Lists the GC roots with `nix-store --gc --print-roots` and works out which
+/// of them keep each path of `graph` alive.
+pub fn collect_gc_roots(control: &NixControl, graph: &SystemGraph) -> Result<GcRoots> {
+    if control.is_replaying() {
+        return Err(anyhow!("GC roots are not part of a recorded transcript"));
+    }
+
+    control.report("listing GC roots".to_owned());
+    let output = control
+        .legacy_command("nix-store")
+        .args(["--gc", "--print-roots"])
+        .output()
+        .context("failed to run nix-store --gc --print-roots")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "nix-store --gc --print-roots failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let roots = parse_gc_roots(&String::from_utf8_lossy(&output.stdout), &graph.store_dir);
+
+    let mut targets = roots
+        .iter()
+        .map(|root| root.target.as_str())
+        .filter(|target| !graph.nodes.contains_key(*target))
+        .collect::<Vec<_>>();
+    targets.sort_unstable();
+    targets.dedup();
+    control.report(format!(
+        "querying the closures of {} GC roots outside the graph",
+        targets.len()
+    ));
+    let closures = targets
+        .par_iter()
+        .map(|target| {
+            let full_path = format!("{}/{target}", graph.store_dir);
+            let closure = run_nix(control, &["path-info", "--recursive", &full_path]).ok()?;
+            let ids = closure
+                .lines()
+                .filter_map(|line| line.trim().strip_prefix(&graph.store_dir))
+                .map(|id| id.trim_start_matches('/').to_owned())
+                .collect::<Vec<_>>();
+            Some(((*target).to_owned(), ids))
+        })
+        .collect::<Vec<_>>();
+    if control.is_cancelled() {
+        return Err(anyhow!("GC root scan cancelled"));
+    }
+
+    let unreadable = closures.iter().filter(|closure| closure.is_none()).count();
+    let outside = closures.into_iter().flatten().collect::<HashMap<_, _>>();
+    // Dangling roots point at paths that are no longer valid; drop them.
+    let roots = roots
+        .into_iter()
+        .filter(|root| graph.nodes.contains_key(&root.target) || outside.contains_key(&root.target))
+        .collect();
+    Ok(GcRoots::index(graph, roots, &outside, unreadable))
+}
+
+/// Parses `LINK -> /nix/store/TARGET` lines.
+fn parse_gc_roots(output: &str, store_dir: &str) -> Vec<GcRoot> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (link, target) = line.trim().rsplit_once(" -> ")?;
+            let target = target.strip_prefix(store_dir)?.trim_start_matches('/');
+            let target = target.split('/').next().filter(|name| !name.is_empty())?;
+            Some(GcRoot {
+                link: link.to_owned(),
+                target: target.to_owned(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nix::generate_demo_graph;
+
+    #[test]
+    fn parses_links_and_runtime_roots() {
+        let output = "/nix/var/nix/profiles/system-41-link -> /nix/store/aaa-nixos-system\n\
+                      {memory:3} -> /nix/store/bbb-bash/bin/bash\n\
+                      /proc/12/maps -> /run/opengl-driver\n";
+
+        assert_eq!(
+            parse_gc_roots(output, "/nix/store"),
+            [
+                GcRoot {
+                    link: "/nix/var/nix/profiles/system-41-link".to_owned(),
+                    target: "aaa-nixos-system".to_owned(),
+                },
+                GcRoot {
+                    link: "{memory:3}".to_owned(),
+                    target: "bbb-bash".to_owned(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn paths_kept_by_other_roots_are_not_sole() {
+        let graph = generate_demo_graph(300);
+        let shared = graph.nodes[&graph.root_id].references[0].clone();
+        let roots = vec![
+            GcRoot {
+                link: "/run/current-system".to_owned(),
+                target: graph.root_id.clone(),
+            },
+            GcRoot {
+                link: "/home/user/result".to_owned(),
+                target: shared.clone(),
+            },
+        ];
+        let gc_roots = GcRoots::index(&graph, roots, &HashMap::new(), 0);
+
+        assert_eq!(gc_roots.keepers(&graph.root_id).count(), 1);
+        assert_eq!(gc_roots.keepers(&shared).count(), 2);
+        assert!(gc_roots.is_sole(&graph.root_id));
+        assert!(!gc_roots.is_sole(&shared));
+        assert!(
+            graph
+                .closure_ids([shared.as_str()])
+                .iter()
+                .all(|id| !gc_roots.is_sole(id))
+        );
+        assert_eq!(
+            gc_roots.sole_totals().0,
+            graph.node_count() - graph.closure_ids([shared.as_str()]).len()
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use std::cmp::Reverse;
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+
+use anyhow::{Context, Result, anyhow};
+
+use crate::util::short_name;
+
+use super::nix_cmd::{NixControl, run_nix};
+use super::parse::parse_path_info_reader;
+
+/// Where NixOS keeps the system profile, with one `system-<n>-link` per
+/// generation next to it.
+pub const SYSTEM_PROFILES_DIR: &str = "/nix/var/nix/profiles";
+
+/// One generation of the system profile.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Generation {
+    pub number: u64,
+    /// The `system-<n>-link` symlink, which can be opened as a root.
+    pub link: String,
+    /// The store path the link points to.
+    pub target: String,
+    /// When the generation was made, from the link's modification time.
+    pub created: Option<SystemTime>,
+    /// The kernel package without hash, e.g. `linux-6.6.30`, for systems that
+    /// boot one.
+    pub kernel: Option<String>,
+    /// Whether the system profile points to this generation.
+    pub current: bool,
+}
+
+fn generation_number(file_name: &str) -> Option<u64> {
+    file_name
+        .strip_prefix("system-")?
+        .strip_suffix("-link")?
+        .parse()
+        .ok()
+}
+
+/// The package holding the image a system's `kernel` link points to, e.g.
+/// `/nix/store/<hash>-linux-6.6.30/bzImage`.
+fn kernel_package(kernel_image: &Path) -> Option<String> {
+    let package = kernel_image.parent()?.file_name()?.to_str()?;
+    Some(short_name(package).to_owned())
+}
+
+/// Lists the system generations in `profiles_dir`, newest first. Links whose
+/// target has been garbage-collected are left out.
+pub fn list_generations(profiles_dir: &Path) -> Result<Vec<Generation>> {
+    let current = fs::canonicalize(profiles_dir.join("system")).ok();
+    let entries = fs::read_dir(profiles_dir)
+        .with_context(|| format!("failed to read {}", profiles_dir.display()))?;
+
+    let mut generations = Vec::new();
+    for entry in entries {
+        let entry = entry.with_context(|| format!("failed to read {}", profiles_dir.display()))?;
+        let Some(number) = entry.file_name().to_str().and_then(generation_number) else {
+            continue;
+        };
+        let link = entry.path();
+        let Ok(target) = fs::canonicalize(&link) else {
+            continue;
+        };
+        generations.push(Generation {
+            number,
+            link: link.display().to_string(),
+            created: fs::symlink_metadata(&link)
+                .and_then(|metadata| metadata.modified())
+                .ok(),
+            kernel: fs::read_link(target.join("kernel"))
+                .ok()
+                .and_then(|image| kernel_package(&image)),
+            current: current.as_ref() == Some(&target),
+            target: target.display().to_string(),
+        });
+    }
+    generations.sort_by_key(|generation| Reverse(generation.number));
+    Ok(generations)
+}
+
+/// The closure size of one store path, without collecting its graph.
+pub fn query_closure_size(control: &NixControl, full_path: &str) -> Result<u64> {
+    let raw = run_nix(
+        control,
+        &["path-info", "--json", "--closure-size", full_path],
+    )?;
+    let (_, info) = parse_path_info_reader(raw.as_bytes())?;
+    info.into_values()
+        .next()
+        .map(|info| info.closure_size)
+        .ok_or_else(|| anyhow!("nix path-info returned nothing for {full_path}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::symlink;
+
+    use super::*;
+
+    #[test]
+    fn parses_generation_links_and_kernels() {
+        assert_eq!(generation_number("system-41-link"), Some(41));
+        assert_eq!(generation_number("system"), None);
+        assert_eq!(generation_number("system-profiles"), None);
+        assert_eq!(generation_number("per-user"), None);
+        assert_eq!(
+            kernel_package(Path::new("/nix/store/aaaa-linux-6.6.30/bzImage")).as_deref(),
+            Some("linux-6.6.30")
+        );
+    }
+
+    #[test]
+    fn lists_generations_newest_first() {
+        let dir = std::env::temp_dir().join(format!("nix-analisa-gens-{}", std::process::id()));
+        let store = dir.join("store");
+        let profiles = dir.join("profiles");
+        for name in [
+            "aaaa-nixos-system-40",
+            "bbbb-nixos-system-41",
+            "cccc-linux-6.6.30",
+        ] {
+            fs::create_dir_all(store.join(name)).unwrap();
+        }
+        fs::create_dir_all(&profiles).unwrap();
+        symlink(
+            store.join("cccc-linux-6.6.30/bzImage"),
+            store.join("bbbb-nixos-system-41/kernel"),
+        )
+        .unwrap();
+        symlink(
+            store.join("aaaa-nixos-system-40"),
+            profiles.join("system-40-link"),
+        )
+        .unwrap();
+        symlink(
+            store.join("bbbb-nixos-system-41"),
+            profiles.join("system-41-link"),
+        )
+        .unwrap();
+        symlink(store.join("gone"), profiles.join("system-39-link")).unwrap();
+        symlink("system-41-link", profiles.join("system")).unwrap();
+
+        let generations = list_generations(&profiles);
+        fs::remove_dir_all(&dir).unwrap();
+        let generations = generations.unwrap();
+
+        assert_eq!(
+            generations
+                .iter()
+                .map(|generation| (generation.number, generation.current))
+                .collect::<Vec<_>>(),
+            vec![(41, true), (40, false)]
+        );
+        assert_eq!(generations[0].kernel.as_deref(), Some("linux-6.6.30"));
+        assert_eq!(generations[1].kernel, None);
+        assert!(generations[0].created.is_some());
+    }
+}
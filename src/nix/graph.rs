@@ -1,28 +1,81 @@
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+use serde::{Deserialize, Serialize};
+
+use super::classify::{NodeClass, NodeClasses};
+use super::collect::{finish_graph, link_references};
+use super::derivations::DerivationInfo;
+use super::dominators::fill_retained_sizes;
+use super::nix_cmd::NixControl;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum SizeMetric {
     NarSize,
     ClosureSize,
+    ClosureCount,
     Dependencies,
     ReverseDependencies,
+    RetainedSize,
 }
 
 impl SizeMetric {
     pub fn is_byte_metric(self) -> bool {
-        matches!(self, Self::NarSize | Self::ClosureSize)
+        matches!(self, Self::NarSize | Self::ClosureSize | Self::RetainedSize)
     }
 }
 
-#[derive(Clone, Debug)]
+/// Fields derived from the rest of the graph are not serialized; they are
+/// recomputed when a saved graph is loaded.
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct NodeRecord {
     pub id: String,
     pub full_path: String,
+    /// The store directory this path was collected from; graphs merged from
+    /// a chroot store hold paths of several.
+    #[serde(default)]
+    pub store_dir: String,
     pub nar_size: u64,
     pub closure_size: u64,
+    /// How many paths the closure holds, this one included.
+    #[serde(skip)]
+    pub closure_count: u64,
+    /// NAR size freed by removing this path and everything only it keeps alive.
+    #[serde(skip)]
+    pub retained_size: u64,
+    /// The nearest path every route from the root to this one goes through.
+    #[serde(skip)]
+    pub immediate_dominator: Option<String>,
     pub references: Vec<String>,
+    #[serde(skip)]
     pub referrers: Vec<String>,
     pub deriver: Option<String>,
+    /// Metadata from the deriver, when derivations were read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub derivation: Option<Box<DerivationInfo>>,
+    pub classes: NodeClasses,
+}
+
+/// A bare `/nix/store` path for tests, keyed by its id so that a list of
+/// them builds the node map with `HashMap::from`.
+#[cfg(test)]
+pub(super) fn test_node(id: &str, nar_size: u64, references: &[&str]) -> (String, NodeRecord) {
+    let record = NodeRecord {
+        id: id.to_owned(),
+        full_path: format!("/nix/store/{id}"),
+        store_dir: "/nix/store".to_owned(),
+        nar_size,
+        closure_size: 0,
+        closure_count: 0,
+        retained_size: 0,
+        immediate_dominator: None,
+        references: references.iter().map(|id| (*id).to_owned()).collect(),
+        referrers: Vec::new(),
+        deriver: None,
+        derivation: None,
+        classes: NodeClasses::default(),
+    };
+    (id.to_owned(), record)
 }
 
 impl NodeRecord {
@@ -30,18 +83,48 @@ impl NodeRecord {
         match metric {
             SizeMetric::NarSize => self.nar_size,
             SizeMetric::ClosureSize => self.closure_size,
+            SizeMetric::ClosureCount => self.closure_count,
             SizeMetric::Dependencies => self.references.len() as u64,
             SizeMetric::ReverseDependencies => self.referrers.len() as u64,
+            SizeMetric::RetainedSize => self.retained_size,
         }
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SystemGraph {
     pub store_dir: String,
     pub root_id: String,
     pub nodes: HashMap<String, NodeRecord>,
+    #[serde(skip)]
     pub edge_count: usize,
+    /// Data that could not be collected, e.g. because store access was restricted.
+    #[serde(default)]
+    pub limitations: Vec<String>,
+    /// How many files in each source mention each target, keyed by source
+    /// then target. Empty unless edge weights were scanned.
+    #[serde(default)]
+    pub edge_weights: HashMap<String, HashMap<String, u32>>,
+    /// Every root of a graph merged from several closures, `root_id` first;
+    /// empty for a single root.
+    #[serde(default)]
+    pub roots: Vec<String>,
+    /// Which of `roots` reach each path, one bit per root.
+    #[serde(skip)]
+    pub root_membership: HashMap<String, u64>,
+}
+
+/// Paths that entered and left a closure, with the metadata of the new ones.
+pub struct GraphDelta {
+    pub root_id: String,
+    pub added: Vec<NodeRecord>,
+    pub removed: Vec<String>,
+}
+
+impl GraphDelta {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
 }
 
 impl SystemGraph {
@@ -49,6 +132,108 @@ impl SystemGraph {
         self.nodes.len()
     }
 
+    /// Drops the removed paths and links in the added ones, then sizes every
+    /// closure again, as collecting the changed closure from scratch would.
+    pub fn apply_delta(&mut self, delta: GraphDelta) {
+        let mut nodes = std::mem::take(&mut self.nodes);
+        for id in &delta.removed {
+            nodes.remove(id);
+        }
+        nodes.extend(delta.added.into_iter().map(|node| (node.id.clone(), node)));
+        for node in nodes.values_mut() {
+            node.referrers.clear();
+        }
+
+        let finished = finish_graph(
+            nodes,
+            delta.root_id,
+            self.store_dir.clone(),
+            &NixControl::default(),
+        );
+        self.root_id = finished.root_id;
+        self.nodes = finished.nodes;
+        self.edge_count = finished.edge_count;
+        self.edge_weights
+            .retain(|source, _| self.nodes.contains_key(source));
+    }
+
+    /// How many paths come from each store directory; more than one entry
+    /// when chroot stores were merged in.
+    pub fn store_dirs(&self) -> BTreeMap<&str, usize> {
+        let mut counts = BTreeMap::new();
+        for node in self.nodes.values() {
+            *counts.entry(node.store_dir.as_str()).or_default() += 1;
+        }
+        counts
+    }
+
+    /// The scanned weight of an edge, or one when it was not scanned.
+    pub fn edge_weight(&self, source: &str, target: &str) -> u32 {
+        self.edge_weights
+            .get(source)
+            .and_then(|targets| targets.get(target))
+            .map_or(1, |&weight| weight.max(1))
+    }
+
+    pub fn class_count(&self, class: NodeClass) -> usize {
+        self.nodes
+            .values()
+            .filter(|node| node.classes.contains(class))
+            .count()
+    }
+
+    /// Lists broken invariants: dangling references, referrers that do not
+    /// mirror references, mismatched ids and a stale edge count.
+    pub fn integrity_errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if !self.nodes.contains_key(&self.root_id) {
+            errors.push(format!("root {} is not a node", self.root_id));
+        }
+
+        let mut references = HashSet::new();
+        for (id, node) in &self.nodes {
+            if &node.id != id {
+                errors.push(format!("node keyed {id} has id {}", node.id));
+            }
+            for reference in &node.references {
+                if !self.nodes.contains_key(reference) {
+                    errors.push(format!("{id} references unknown node {reference}"));
+                } else if !references.insert((id.as_str(), reference.as_str())) {
+                    errors.push(format!("{id} references {reference} twice"));
+                }
+            }
+        }
+
+        let mut referrers = HashSet::new();
+        for (id, node) in &self.nodes {
+            for referrer in &node.referrers {
+                if !referrers.insert((referrer.as_str(), id.as_str())) {
+                    errors.push(format!("{id} lists referrer {referrer} twice"));
+                }
+            }
+        }
+
+        for (source, target) in references.difference(&referrers) {
+            errors.push(format!("{target} is missing referrer {source}"));
+        }
+        for (source, target) in referrers.difference(&references) {
+            errors.push(format!(
+                "{target} lists referrer {source} without a reference"
+            ));
+        }
+
+        if self.edge_count != references.len() {
+            errors.push(format!(
+                "edge count is {} but {} references exist",
+                self.edge_count,
+                references.len()
+            ));
+        }
+
+        errors
+    }
+
     pub fn ranked_by_metric(&self, metric: SizeMetric, limit: usize) -> Vec<String> {
         let mut ids = self.nodes.keys().cloned().collect::<Vec<_>>();
         ids.sort_by(|a, b| {
@@ -93,6 +278,244 @@ impl SystemGraph {
         ids
     }
 
+    pub fn unreachable_without_edge(&self, source: &str, target: &str) -> Vec<String> {
+        let root = self.root_id.as_str();
+        if !self.nodes.contains_key(root) {
+            return Vec::new();
+        }
+
+        let mut queue: VecDeque<&str> = VecDeque::from([root]);
+        let mut visited: HashSet<&str> = HashSet::from([root]);
+
+        while let Some(current) = queue.pop_front() {
+            let Some(node) = self.nodes.get(current) else {
+                continue;
+            };
+
+            for next in &node.references {
+                if current == source && next == target {
+                    continue;
+                }
+
+                let Some((next_key, _)) = self.nodes.get_key_value(next.as_str()) else {
+                    continue;
+                };
+
+                if visited.insert(next_key.as_str()) {
+                    queue.push_back(next_key.as_str());
+                }
+            }
+        }
+
+        let mut unreachable = self
+            .nodes
+            .keys()
+            .filter(|id| !visited.contains(id.as_str()))
+            .cloned()
+            .collect::<Vec<_>>();
+        unreachable.sort();
+        unreachable
+    }
+
+    /// Paths the roots no longer reach once every path in `removed` is taken
+    /// out of the closure, `removed` included, sorted by id. Unlike summing
+    /// retained sizes, this counts paths that only a combination of the
+    /// removed paths keeps alive.
+    pub fn unreachable_without_paths(&self, removed: &HashSet<&str>) -> Vec<String> {
+        let mut queue = std::iter::once(&self.root_id)
+            .chain(&self.roots)
+            .filter_map(|id| self.nodes.get_key_value(id.as_str()))
+            .map(|(id, _)| id.as_str())
+            .filter(|id| !removed.contains(id))
+            .collect::<VecDeque<_>>();
+        let mut visited = queue.iter().copied().collect::<HashSet<_>>();
+
+        while let Some(current) = queue.pop_front() {
+            for next in &self.nodes[current].references {
+                let Some((next_key, _)) = self.nodes.get_key_value(next.as_str()) else {
+                    continue;
+                };
+                if !removed.contains(next_key.as_str()) && visited.insert(next_key.as_str()) {
+                    queue.push_back(next_key.as_str());
+                }
+            }
+        }
+
+        let mut unreachable = self
+            .nodes
+            .keys()
+            .filter(|id| !visited.contains(id.as_str()))
+            .cloned()
+            .collect::<Vec<_>>();
+        unreachable.sort();
+        unreachable
+    }
+
+    /// Paths only reachable through `dominator`, which would leave the
+    /// closure along with it, sorted by id.
+    pub fn dominated_by(&self, dominator: &str) -> Vec<String> {
+        let mut dominated = self
+            .nodes
+            .values()
+            .filter(|node| {
+                let mut cursor = node.immediate_dominator.as_deref();
+                while let Some(id) = cursor {
+                    if id == dominator {
+                        return true;
+                    }
+                    cursor = self
+                        .nodes
+                        .get(id)
+                        .and_then(|node| node.immediate_dominator.as_deref());
+                }
+                false
+            })
+            .map(|node| node.id.clone())
+            .collect::<Vec<_>>();
+        dominated.sort();
+        dominated
+    }
+
+    /// The share of its immediate dominator's retained size that `id`
+    /// retains itself, in `0.0..=1.0`; `None` for the root.
+    pub fn dominator_share(&self, id: &str) -> Option<f32> {
+        let node = self.nodes.get(id)?;
+        let dominator = self.nodes.get(node.immediate_dominator.as_deref()?)?;
+        Some(match dominator.retained_size {
+            0 => 0.0,
+            total => (node.retained_size as f64 / total as f64).min(1.0) as f32,
+        })
+    }
+
+    pub fn neighborhood(&self, center: &str, hops: usize) -> Vec<String> {
+        let Some((center, _)) = self.nodes.get_key_value(center) else {
+            return Vec::new();
+        };
+
+        let mut ordered = vec![center.clone()];
+        let mut visited: HashSet<&str> = HashSet::from([center.as_str()]);
+        let mut frontier = vec![center.as_str()];
+
+        for _ in 0..hops {
+            let mut next_frontier = Vec::new();
+            for current in frontier {
+                let Some(node) = self.nodes.get(current) else {
+                    continue;
+                };
+
+                for next in node.references.iter().chain(&node.referrers) {
+                    let Some((next_key, _)) = self.nodes.get_key_value(next.as_str()) else {
+                        continue;
+                    };
+
+                    if visited.insert(next_key.as_str()) {
+                        ordered.push(next_key.clone());
+                        next_frontier.push(next_key.as_str());
+                    }
+                }
+            }
+
+            if next_frontier.is_empty() {
+                break;
+            }
+            frontier = next_frontier;
+        }
+
+        ordered
+    }
+
+    /// The closure of `root` as a standalone graph rooted there, with
+    /// referrers limited to paths inside the closure.
+    pub fn closure_subgraph(&self, root: &str) -> Option<SystemGraph> {
+        let root = self.nodes.get(root)?;
+        let mut nodes = HashMap::new();
+        let mut stack = vec![root];
+
+        while let Some(node) = stack.pop() {
+            if nodes.contains_key(&node.id) {
+                continue;
+            }
+
+            stack.extend(
+                node.references
+                    .iter()
+                    .filter_map(|reference| self.nodes.get(reference)),
+            );
+            nodes.insert(
+                node.id.clone(),
+                NodeRecord {
+                    referrers: Vec::new(),
+                    ..node.clone()
+                },
+            );
+        }
+
+        let (mut nodes, edge_count) = link_references(nodes);
+        fill_retained_sizes(&mut nodes, &root.id, &NixControl::default());
+        let edge_weights = self
+            .edge_weights
+            .iter()
+            .filter(|(source, _)| nodes.contains_key(*source))
+            .map(|(source, targets)| (source.clone(), targets.clone()))
+            .collect();
+        Some(SystemGraph {
+            store_dir: self.store_dir.clone(),
+            root_id: root.id.clone(),
+            nodes,
+            edge_count,
+            limitations: self.limitations.clone(),
+            edge_weights,
+            roots: Vec::new(),
+            root_membership: HashMap::new(),
+        })
+    }
+
+    /// Reference cycles: strongly connected components of more than one
+    /// path, largest first. Each lists the root, if it is a member, then the
+    /// rest by NAR size, so the first path can stand in for the cycle.
+    /// Self-references are dropped while collecting and never show up here.
+    pub fn reference_cycles(&self) -> Vec<Vec<String>> {
+        let mut ids = self.nodes.keys().map(String::as_str).collect::<Vec<_>>();
+        ids.sort_unstable();
+        let index_by_id = ids
+            .iter()
+            .enumerate()
+            .map(|(index, id)| (*id, index))
+            .collect::<HashMap<_, _>>();
+        let successors = ids
+            .iter()
+            .map(|id| {
+                self.nodes[*id]
+                    .references
+                    .iter()
+                    .filter_map(|reference| index_by_id.get(reference.as_str()).copied())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let mut cycles = strongly_connected_components(&successors)
+            .into_iter()
+            .filter(|component| component.len() > 1)
+            .map(|component| {
+                let mut members = component
+                    .into_iter()
+                    .map(|index| ids[index].to_owned())
+                    .collect::<Vec<_>>();
+                members.sort_by(|a, b| {
+                    (*b == self.root_id)
+                        .cmp(&(*a == self.root_id))
+                        .then_with(|| self.nodes[b].nar_size.cmp(&self.nodes[a].nar_size))
+                        .then_with(|| a.cmp(b))
+                });
+                members
+            })
+            .collect::<Vec<_>>();
+        cycles.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a[0].cmp(&b[0])));
+        cycles
+    }
+
+    /// A shortest reference chain from the root to `target`. Among chains of
+    /// equal length, each step prefers the heaviest edge into the next path.
     pub fn shortest_path_from_root(&self, target: &str) -> Option<Vec<String>> {
         let target = self.nodes.get_key_value(target)?.0.as_str();
         let root = self.root_id.as_str();
@@ -102,11 +525,11 @@ impl SystemGraph {
         }
 
         let mut queue: VecDeque<&str> = VecDeque::new();
-        let mut visited: HashSet<&str> = HashSet::new();
-        let mut parent: HashMap<&str, &str> = HashMap::new();
+        let mut depth: HashMap<&str, usize> = HashMap::new();
+        let mut parent: HashMap<&str, (&str, u32)> = HashMap::new();
 
         queue.push_back(root);
-        visited.insert(root);
+        depth.insert(root, 0);
 
         while let Some(current) = queue.pop_front() {
             if current == target {
@@ -116,21 +539,32 @@ impl SystemGraph {
             let Some(node) = self.nodes.get(current) else {
                 continue;
             };
+            let next_depth = depth[current] + 1;
 
             for next in &node.references {
                 let Some((next_key, _)) = self.nodes.get_key_value(next.as_str()) else {
                     continue;
                 };
                 let next_key = next_key.as_str();
+                let weight = self.edge_weight(current, next_key);
 
-                if visited.insert(next_key) {
-                    parent.insert(next_key, current);
-                    queue.push_back(next_key);
+                match depth.get(next_key) {
+                    None => {
+                        depth.insert(next_key, next_depth);
+                        parent.insert(next_key, (current, weight));
+                        queue.push_back(next_key);
+                    }
+                    Some(&existing) if existing == next_depth => {
+                        if parent.get(next_key).is_some_and(|&(_, best)| weight > best) {
+                            parent.insert(next_key, (current, weight));
+                        }
+                    }
+                    Some(_) => {}
                 }
             }
         }
 
-        if !visited.contains(target) {
+        if !depth.contains_key(target) {
             return None;
         }
 
@@ -139,7 +573,7 @@ impl SystemGraph {
         path.push(cursor.to_owned());
 
         while cursor != root {
-            cursor = *parent.get(cursor)?;
+            cursor = parent.get(cursor)?.0;
             path.push(cursor.to_owned());
         }
 
@@ -147,3 +581,293 @@ impl SystemGraph {
         Some(path)
     }
 }
+
+/// Tarjan's algorithm with an explicit stack, since closures can be deep
+/// enough to overflow the call stack.
+pub(super) fn strongly_connected_components(successors: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    const UNVISITED: usize = usize::MAX;
+
+    let count = successors.len();
+    let mut order = vec![UNVISITED; count];
+    let mut low_link = vec![0; count];
+    let mut on_stack = vec![false; count];
+    let mut stack = Vec::new();
+    let mut components = Vec::new();
+    let mut next_order = 0;
+    // Each frame is a node and how many of its successors were followed.
+    let mut frames: Vec<(usize, usize)> = Vec::new();
+
+    for start in 0..count {
+        if order[start] != UNVISITED {
+            continue;
+        }
+
+        order[start] = next_order;
+        low_link[start] = next_order;
+        next_order += 1;
+        stack.push(start);
+        on_stack[start] = true;
+        frames.push((start, 0));
+
+        while let Some(&(node, cursor)) = frames.last() {
+            if let Some(&next) = successors[node].get(cursor) {
+                let top = frames.len() - 1;
+                frames[top].1 += 1;
+                if order[next] == UNVISITED {
+                    order[next] = next_order;
+                    low_link[next] = next_order;
+                    next_order += 1;
+                    stack.push(next);
+                    on_stack[next] = true;
+                    frames.push((next, 0));
+                } else if on_stack[next] {
+                    low_link[node] = low_link[node].min(order[next]);
+                }
+                continue;
+            }
+
+            frames.pop();
+            if let Some(&(parent, _)) = frames.last() {
+                low_link[parent] = low_link[parent].min(low_link[node]);
+            }
+            if low_link[node] == order[node] {
+                let mut component = Vec::new();
+                while let Some(member) = stack.pop() {
+                    on_stack[member] = false;
+                    component.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                components.push(component);
+            }
+        }
+    }
+
+    components
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::collect::finish_graph;
+    use super::*;
+    use crate::nix::generate_demo_graph;
+
+    #[test]
+    fn neighborhood_follows_both_directions_up_to_the_hop_limit() {
+        // y -> x -> a, root -> a -> b -> c
+        let graph = finish_graph(
+            HashMap::from([
+                test_node("root", 1, &["a"]),
+                test_node("a", 1, &["b"]),
+                test_node("b", 1, &["c"]),
+                test_node("c", 1, &[]),
+                test_node("x", 1, &["a"]),
+                test_node("y", 1, &["x"]),
+            ]),
+            "root".to_owned(),
+            "/nix/store".to_owned(),
+            &NixControl::default(),
+        );
+        let sorted = |ids: &[String]| {
+            let mut ids = ids.to_vec();
+            ids.sort();
+            ids
+        };
+
+        assert_eq!(graph.neighborhood("a", 0), ["a"]);
+        assert_eq!(sorted(&graph.neighborhood("a", 1)), ["a", "b", "root", "x"]);
+        let two_hops = graph.neighborhood("a", 2);
+        assert_eq!(two_hops[0], "a");
+        assert_eq!(sorted(&two_hops[1..4]), ["b", "root", "x"]);
+        assert_eq!(sorted(&two_hops[4..]), ["c", "y"]);
+
+        // Referrers are followed as well as references.
+        assert_eq!(graph.neighborhood("c", 1), ["c", "b"]);
+        assert_eq!(graph.neighborhood("c", 2), ["c", "b", "a"]);
+        assert_eq!(graph.neighborhood("c", 100).len(), graph.node_count());
+        assert!(graph.neighborhood("missing", 3).is_empty());
+    }
+
+    #[test]
+    fn applied_deltas_match_a_fresh_collection() {
+        let graph = |nodes: &[(String, NodeRecord)], root: &str| {
+            finish_graph(
+                nodes.iter().cloned().collect(),
+                root.to_owned(),
+                "/nix/store".to_owned(),
+                &NixControl::default(),
+            )
+        };
+        // The profile switches from root to next, which drops b and adds c.
+        let before = [
+            test_node("root", 1, &["a", "b"]),
+            test_node("a", 2, &[]),
+            test_node("b", 4, &["a"]),
+        ];
+        let after = [
+            test_node("next", 1, &["a", "c"]),
+            test_node("a", 2, &[]),
+            test_node("c", 8, &["a"]),
+        ];
+        let mut updated = graph(&before, "root");
+        updated.edge_weights = HashMap::from([
+            ("root".to_owned(), HashMap::from([("b".to_owned(), 3)])),
+            ("c".to_owned(), HashMap::new()),
+        ]);
+
+        updated.apply_delta(GraphDelta {
+            root_id: "next".to_owned(),
+            added: vec![after[0].1.clone(), after[2].1.clone()],
+            removed: vec!["root".to_owned(), "b".to_owned()],
+        });
+
+        let fresh = graph(&after, "next");
+        assert_eq!(updated.root_id, "next");
+        assert_eq!(updated.edge_count, fresh.edge_count);
+        assert!(updated.integrity_errors().is_empty());
+        for (id, node) in &fresh.nodes {
+            let merged = &updated.nodes[id];
+            assert_eq!(merged.referrers, node.referrers, "{id}");
+            assert_eq!(merged.closure_size, node.closure_size, "{id}");
+            assert_eq!(merged.retained_size, node.retained_size, "{id}");
+        }
+        assert_eq!(updated.node_count(), fresh.node_count());
+        assert_eq!(updated.edge_weights.keys().collect::<Vec<_>>(), ["c"]);
+    }
+
+    #[test]
+    fn reference_cycles_are_strongly_connected_components() {
+        // root -> a -> b -> c -> a, c -> d, root -> e -> root
+        let graph = SystemGraph {
+            store_dir: "/nix/store".to_owned(),
+            root_id: "root".to_owned(),
+            nodes: HashMap::from([
+                test_node("root", 1, &["a", "e"]),
+                test_node("a", 5, &["b"]),
+                test_node("b", 9, &["c"]),
+                test_node("c", 2, &["a", "d"]),
+                test_node("d", 1, &[]),
+                test_node("e", 50, &["root"]),
+            ]),
+            edge_count: 0,
+            limitations: Vec::new(),
+            edge_weights: HashMap::new(),
+            roots: Vec::new(),
+            root_membership: HashMap::new(),
+        };
+
+        assert_eq!(
+            graph.reference_cycles(),
+            [vec!["b", "a", "c"], vec!["root", "e"]]
+        );
+        assert!(generate_demo_graph(300).reference_cycles().is_empty());
+    }
+
+    #[test]
+    fn removing_paths_together_frees_what_they_share() {
+        // b is kept alive by both a and c; d only by a.
+        let graph = SystemGraph {
+            store_dir: "/nix/store".to_owned(),
+            root_id: "root".to_owned(),
+            nodes: HashMap::from([
+                test_node("root", 1, &["a", "c"]),
+                test_node("a", 1, &["b", "d"]),
+                test_node("b", 1, &[]),
+                test_node("c", 1, &["b"]),
+                test_node("d", 1, &[]),
+            ]),
+            edge_count: 0,
+            limitations: Vec::new(),
+            edge_weights: HashMap::new(),
+            roots: Vec::new(),
+            root_membership: HashMap::new(),
+        };
+
+        assert_eq!(
+            graph.unreachable_without_paths(&HashSet::from(["a"])),
+            ["a", "d"]
+        );
+        assert_eq!(
+            graph.unreachable_without_paths(&HashSet::from(["a", "c"])),
+            ["a", "b", "c", "d"]
+        );
+        assert!(graph.unreachable_without_paths(&HashSet::new()).is_empty());
+    }
+
+    #[test]
+    fn cutting_an_edge_drops_what_only_it_reaches() {
+        // root -> a -> b -> c, root -> d -> c; b is only reached through a.
+        let graph = SystemGraph {
+            store_dir: "/nix/store".to_owned(),
+            root_id: "root".to_owned(),
+            nodes: HashMap::from([
+                test_node("root", 1, &["a", "d"]),
+                test_node("a", 1, &["b"]),
+                test_node("b", 1, &["c"]),
+                test_node("c", 1, &[]),
+                test_node("d", 1, &["c"]),
+            ]),
+            edge_count: 0,
+            limitations: Vec::new(),
+            edge_weights: HashMap::new(),
+            roots: Vec::new(),
+            root_membership: HashMap::new(),
+        };
+
+        assert_eq!(graph.unreachable_without_edge("root", "a"), ["a", "b"]);
+        assert_eq!(graph.unreachable_without_edge("a", "b"), ["b"]);
+        assert!(graph.unreachable_without_edge("d", "c").is_empty());
+        assert!(graph.unreachable_without_edge("c", "missing").is_empty());
+    }
+
+    #[test]
+    fn dominated_paths_follow_the_dominator_chain() {
+        let dominated_by = |id: &str, dominator: Option<&str>| {
+            let (id, mut record) = test_node(id, 1, &[]);
+            record.immediate_dominator = dominator.map(str::to_owned);
+            (id, record)
+        };
+        // a keeps b and c alive; d is shared with the root.
+        let graph = SystemGraph {
+            store_dir: "/nix/store".to_owned(),
+            root_id: "root".to_owned(),
+            nodes: HashMap::from([
+                dominated_by("root", None),
+                dominated_by("a", Some("root")),
+                dominated_by("b", Some("a")),
+                dominated_by("c", Some("b")),
+                dominated_by("d", Some("root")),
+            ]),
+            edge_count: 0,
+            limitations: Vec::new(),
+            edge_weights: HashMap::new(),
+            roots: Vec::new(),
+            root_membership: HashMap::new(),
+        };
+
+        assert_eq!(graph.dominated_by("a"), ["b", "c"]);
+        assert!(graph.dominated_by("d").is_empty());
+    }
+
+    #[test]
+    fn dominator_shares_split_each_subtree() {
+        let graph = generate_demo_graph(300);
+        assert_eq!(graph.dominator_share(&graph.root_id), None);
+
+        let mut share_by_dominator = HashMap::<&str, f32>::new();
+        for node in graph.nodes.values() {
+            let Some(share) = graph.dominator_share(&node.id) else {
+                continue;
+            };
+            assert!((0.0..=1.0).contains(&share));
+            *share_by_dominator
+                .entry(node.immediate_dominator.as_deref().unwrap())
+                .or_default() += share;
+        }
+        // A path retains its own NAR size besides its children's subtrees.
+        for total in share_by_dominator.values() {
+            assert!(*total <= 1.0 + 1e-4);
+        }
+    }
+}
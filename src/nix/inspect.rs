@@ -0,0 +1,57 @@
+use anyhow::{Context, Result, anyhow};
+
+use super::daemon::DaemonConnection;
+use super::nix_cmd::{NixControl, run_nix};
+
+/// The reference chain `nix why-depends` prints from `from` down to `to`.
+pub fn why_depends(control: &NixControl, from: &str, to: &str) -> Result<String> {
+    run_nix(control, &["why-depends", from, to])
+}
+
+/// Sizes and signatures of a path as `nix path-info` prints them for people.
+pub fn describe_path(control: &NixControl, full_path: &str) -> Result<String> {
+    run_nix(
+        control,
+        &[
+            "path-info",
+            "--size",
+            "--closure-size",
+            "--human-readable",
+            "--sigs",
+            full_path,
+        ],
+    )
+}
+
+/// Every valid path in the whole store that references `full_path`, not only
+/// those in the loaded closure, sorted.
+pub fn store_referrers(control: &NixControl, full_path: &str) -> Result<Vec<String>> {
+    let mut referrers = match DaemonConnection::for_control(control) {
+        Some(Ok(mut daemon)) => daemon.query_referrers(full_path)?,
+        _ if control.is_replaying() => {
+            return Err(anyhow!(
+                "store referrers are not part of a recorded transcript"
+            ));
+        }
+        _ => {
+            let output = control
+                .legacy_command("nix-store")
+                .args(["--query", "--referrers", full_path])
+                .output()
+                .context("failed to run nix-store --query --referrers")?;
+            if !output.status.success() {
+                return Err(anyhow!(
+                    "nix-store --query --referrers failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ));
+            }
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .map(str::to_owned)
+                .collect()
+        }
+    };
+    referrers.retain(|referrer| referrer != full_path);
+    referrers.sort();
+    Ok(referrers)
+}
@@ -1,7 +1,57 @@
+mod attributes;
+mod attribution;
+mod classify;
+// Reached directly by benches/analysis.rs, which compiles this module.
+pub(crate) mod closure;
 mod collect;
+mod contents;
+mod daemon;
+mod demo;
+mod derivations;
+pub(crate) mod diff;
+mod disk_cache;
+pub(crate) mod dominators;
+mod duplicates;
+mod gc_roots;
+mod generations;
 mod graph;
+mod inspect;
 mod nix_cmd;
 mod parse;
+mod preview;
+mod refscan;
+mod restricted;
+mod roots;
+mod shards;
+#[cfg(all(test, feature = "nix-smoke-test"))]
+mod smoke;
+mod snapshot;
+mod store_db;
+mod transcript;
+mod verify;
+mod watch;
 
-pub use collect::collect_system_graph;
-pub use graph::{SizeMetric, SystemGraph};
+pub use attributes::{attribute_search_url, locate_attribute_paths};
+pub use attribution::TopLevelShare;
+pub use classify::{NodeClass, NodeClasses};
+pub use collect::{collect_closure_diff, collect_system_graph};
+pub use contents::{ContentEntry, ContentKind, scan_contents};
+pub use daemon::DEFAULT_DAEMON_SOCKET;
+pub use demo::generate_demo_graph;
+pub use derivations::enrich_derivations;
+pub use diff::{ClosureDiff, PathChange};
+pub use duplicates::DuplicatePackage;
+pub use gc_roots::{GcRoots, collect_gc_roots};
+pub use generations::{Generation, SYSTEM_PROFILES_DIR, list_generations, query_closure_size};
+pub use graph::{GraphDelta, SizeMetric, SystemGraph};
+pub use inspect::{describe_path, store_referrers, why_depends};
+pub use nix_cmd::{DEFAULT_NIX_TIMEOUT, NixControl, NixSettings};
+pub use preview::{PathPreview, preview_path};
+pub use refscan::{ReferenceMatches, scan_edge_weights, scan_references};
+pub use roots::{RootMembership, collect_multi_root_graph};
+pub use shards::{ShardPreview, collect_store_shards, stream_store_shards};
+pub use snapshot::{load_closure_dump, load_graph, save_graph};
+pub use store_db::DEFAULT_STORE_DB;
+pub use transcript::Transcript;
+pub use verify::{VerifyOutcome, verify_store_path};
+pub use watch::{ProfileWatcher, StoreWatcher};
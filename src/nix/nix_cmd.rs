@@ -1,17 +1,494 @@
-use std::process::Command;
+use std::collections::hash_map::DefaultHasher;
+use std::env;
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result, anyhow};
 
-pub(super) fn run_nix(args: &[&str]) -> Result<String> {
-    let output = Command::new("nix")
+use super::transcript::{TeeReader, Transcript, create_stdout_record, read_entry, write_entry};
+
+pub const DEFAULT_NIX_TIMEOUT: Duration = Duration::from_secs(600);
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// Variables nix inherits from us that pick the store it reads.
+const STORE_ENV: [&str; 4] = ["NIX_REMOTE", "NIX_STORE_DIR", "NIX_STATE_DIR", "NIX_CONFIG"];
+
+/// User-configurable options applied to every nix subprocess.
+#[derive(Clone, Debug)]
+pub struct NixSettings {
+    pub timeout: Duration,
+    pub transcript: Option<Transcript>,
+    /// The nix executable; a bare name is looked up in `PATH`.
+    pub binary: PathBuf,
+    /// Passed before every subcommand, e.g. `--extra-experimental-features`.
+    pub extra_args: Vec<String>,
+    /// Set on every nix process, e.g. `NIX_REMOTE`.
+    pub env: Vec<(String, String)>,
+    /// The nix-index database `nix-locate` reads attribute paths from; its
+    /// default location when unset.
+    pub nix_index_db: Option<PathBuf>,
+    /// The nix daemon's socket, used for per-path lookups in place of
+    /// spawning nix when set.
+    pub daemon_socket: Option<PathBuf>,
+    /// The store database closures are read from in place of spawning nix
+    /// when set; nix is still used when it cannot be read.
+    pub store_db: Option<PathBuf>,
+    /// Reuse the graph cached for a root collected before instead of
+    /// querying its closure again.
+    pub graph_cache: bool,
+}
+
+impl Default for NixSettings {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_NIX_TIMEOUT,
+            transcript: None,
+            binary: PathBuf::from("nix"),
+            extra_args: Vec::new(),
+            env: Vec::new(),
+            nix_index_db: None,
+            daemon_socket: None,
+            store_db: None,
+            graph_cache: true,
+        }
+    }
+}
+
+/// How many paths of a closure have been read, out of how many it holds.
+#[derive(Debug, Default)]
+struct PathProgress {
+    done: AtomicUsize,
+    total: AtomicUsize,
+}
+
+/// Shared controls for the nix subprocesses spawned by one job: a timeout per
+/// command, a cancellation flag, and an optional channel for stderr progress lines.
+#[derive(Clone, Debug)]
+pub struct NixControl {
+    timeout: Duration,
+    transcript: Option<Transcript>,
+    binary: PathBuf,
+    extra_args: Arc<[String]>,
+    env: Arc<[(String, String)]>,
+    daemon_socket: Option<PathBuf>,
+    store_db: Option<PathBuf>,
+    graph_cache: bool,
+    cancelled: Arc<AtomicBool>,
+    progress: Option<Sender<String>>,
+    paths: Arc<PathProgress>,
+}
+
+impl Default for NixControl {
+    fn default() -> Self {
+        Self::new(&NixSettings::default())
+    }
+}
+
+impl NixControl {
+    pub fn new(settings: &NixSettings) -> Self {
+        Self {
+            timeout: settings.timeout,
+            transcript: settings.transcript.clone(),
+            binary: settings.binary.clone(),
+            extra_args: settings.extra_args.clone().into(),
+            env: settings.env.clone().into(),
+            daemon_socket: settings.daemon_socket.clone(),
+            // Transcripts must see every nix invocation.
+            store_db: settings
+                .store_db
+                .clone()
+                .filter(|_| settings.transcript.is_none()),
+            graph_cache: settings.graph_cache,
+            cancelled: Arc::new(AtomicBool::new(false)),
+            progress: None,
+            paths: Arc::default(),
+        }
+    }
+
+    pub fn with_progress(mut self, progress: Sender<String>) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn is_replaying(&self) -> bool {
+        matches!(self.transcript, Some(Transcript::Replay(_)))
+    }
+
+    pub(super) fn has_transcript(&self) -> bool {
+        self.transcript.is_some()
+    }
+
+    /// Whether the paths nix reports can be opened on this machine: no
+    /// transcript is replayed, and neither `store_args`, the extra arguments
+    /// nor `NIX_REMOTE` pick a store other than the local one.
+    pub(super) fn reads_local_store(&self, store_args: &[&str]) -> bool {
+        if self.is_replaying() {
+            return false;
+        }
+
+        let mut args = store_args
+            .iter()
+            .copied()
+            .chain(self.extra_args.iter().map(String::as_str));
+        let named_store = args
+            .by_ref()
+            .find(|arg| *arg == "--store")
+            .and_then(|_| args.next());
+        let remote = self
+            .env
+            .iter()
+            .find(|(key, _)| key == "NIX_REMOTE")
+            .map(|(_, value)| value.clone())
+            .or_else(|| std::env::var("NIX_REMOTE").ok());
+        named_store
+            .into_iter()
+            .chain(remote.as_deref())
+            .all(is_local_store)
+    }
+
+    pub(super) fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub(super) fn daemon_socket(&self) -> Option<&Path> {
+        self.daemon_socket.as_deref()
+    }
+
+    pub(super) fn store_db(&self) -> Option<&Path> {
+        self.store_db.as_deref()
+    }
+
+    /// A command for a legacy tool such as `nix-store`, taken from the same
+    /// directory as the nix binary and run with the same environment.
+    pub(super) fn legacy_command(&self, tool: &str) -> Command {
+        let program = match self.binary.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.join(tool),
+            _ => PathBuf::from(tool),
+        };
+        let mut command = Command::new(program);
+        command.envs(self.env.iter().map(|(key, value)| (key, value)));
+        command
+    }
+
+    /// Sends a progress line of our own alongside nix's stderr.
+    pub(super) fn report(&self, hint: String) {
+        if let Some(progress) = &self.progress {
+            let _ = progress.send(hint);
+        }
+    }
+
+    /// A hash of everything that picks the store closures are read from:
+    /// the nix binary and its extra arguments and environment, the store
+    /// variables it inherits, and the store database. Graphs cached from one
+    /// store are never served for another.
+    pub(super) fn store_identity(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.binary.hash(&mut hasher);
+        self.extra_args.hash(&mut hasher);
+        self.env.hash(&mut hasher);
+        for key in STORE_ENV {
+            env::var_os(key).hash(&mut hasher);
+        }
+        self.store_db.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    pub(super) fn uses_graph_cache(&self) -> bool {
+        self.graph_cache
+    }
+
+    /// Records that `done` of the `total` paths being collected were read.
+    pub(super) fn count_paths(&self, done: usize, total: usize) {
+        self.paths.total.store(total, Ordering::Relaxed);
+        self.paths.done.store(done, Ordering::Relaxed);
+    }
+
+    /// Paths read so far and the size of the closure being collected, once
+    /// it is known.
+    pub fn path_progress(&self) -> Option<(usize, usize)> {
+        let total = self.paths.total.load(Ordering::Relaxed);
+        (total > 0).then(|| (self.paths.done.load(Ordering::Relaxed).min(total), total))
+    }
+}
+
+pub(super) fn run_nix(control: &NixControl, args: &[&str]) -> Result<String> {
+    stream_nix(control, args, |stdout| {
+        let mut output = String::new();
+        stdout
+            .read_to_string(&mut output)
+            .context("nix output was not valid UTF-8")?;
+        Ok(output)
+    })
+}
+
+pub(super) fn stream_nix<T: Send>(
+    control: &NixControl,
+    args: &[&str],
+    parse: impl FnOnce(&mut dyn Read) -> Result<T> + Send,
+) -> Result<T> {
+    if control.is_cancelled() {
+        return Err(anyhow!("nix command cancelled for args {args:?}"));
+    }
+
+    let record_dir = match &control.transcript {
+        Some(Transcript::Replay(dir)) => return replay_nix(control, dir, args, parse),
+        Some(Transcript::Record(dir)) => Some(dir),
+        None => None,
+    };
+    let record = record_dir
+        .map(|dir| create_stdout_record(dir, args))
+        .transpose()?;
+
+    let mut child = Command::new(&control.binary)
+        .args(control.extra_args.iter())
         .args(args)
-        .output()
-        .with_context(|| format!("failed to spawn nix with args: {args:?}"))?;
-
-    if output.status.success() {
-        String::from_utf8(output.stdout).context("nix output was not valid UTF-8")
-    } else {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        Err(anyhow!("nix command failed for args {args:?}: {stderr}"))
+        .envs(control.env.iter().map(|(key, value)| (key, value)))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| {
+            format!(
+                "failed to spawn {} with args: {args:?}",
+                control.binary.display()
+            )
+        })?;
+
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| anyhow!("nix stdout was not captured"))?;
+    let stderr = child
+        .stderr
+        .take()
+        .ok_or_else(|| anyhow!("nix stderr was not captured"))?;
+
+    let started = Instant::now();
+    let stderr_log = Mutex::new(String::new());
+
+    thread::scope(|scope| {
+        let progress = control.progress.clone();
+        let stderr_log = &stderr_log;
+        scope.spawn(move || {
+            for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                let hint = line.trim();
+                if !hint.is_empty()
+                    && let Some(progress) = &progress
+                {
+                    let _ = progress.send(hint.to_owned());
+                }
+
+                let mut log = stderr_log.lock().unwrap_or_else(|error| error.into_inner());
+                log.push_str(&line);
+                log.push('\n');
+            }
+        });
+        let parser = scope.spawn(move || match record {
+            Some(record) => {
+                let mut stdout = TeeReader::new(stdout, record);
+                let parsed = parse(&mut stdout);
+                // Keep the transcript complete even if the parser stopped early.
+                io::copy(&mut stdout, &mut io::sink()).context("failed to record nix output")?;
+                parsed
+            }
+            None => {
+                let mut stdout = stdout;
+                parse(&mut stdout)
+            }
+        });
+
+        let mut interrupted = None;
+        let status = loop {
+            if let Some(status) = child.try_wait().context("failed to poll nix")?
+                && parser.is_finished()
+            {
+                break status;
+            }
+
+            if interrupted.is_none() {
+                if control.is_cancelled() {
+                    interrupted = Some(anyhow!("nix command cancelled for args {args:?}"));
+                } else if started.elapsed() > control.timeout {
+                    interrupted = Some(anyhow!(
+                        "nix command timed out after {}s for args {args:?}",
+                        control.timeout.as_secs()
+                    ));
+                }
+
+                if interrupted.is_some() {
+                    let _ = child.kill();
+                }
+            }
+
+            thread::sleep(POLL_INTERVAL);
+        };
+
+        let parsed = parser
+            .join()
+            .map_err(|_| anyhow!("nix output parser panicked for args {args:?}"))?;
+
+        if let Some(error) = interrupted {
+            return Err(error);
+        }
+
+        let stderr = stderr_log.lock().unwrap_or_else(|error| error.into_inner());
+        if let Some(dir) = record_dir {
+            write_entry(dir, args, status.success(), &stderr)?;
+        }
+
+        if status.success() {
+            parsed
+        } else {
+            Err(anyhow!("nix command failed for args {args:?}: {stderr}"))
+        }
+    })
+}
+
+/// Store URIs that name the local store, directly or through its daemon.
+fn is_local_store(uri: &str) -> bool {
+    let (scheme, _) = uri.split_once('?').unwrap_or((uri, ""));
+    // A local store under another root keeps its paths somewhere else.
+    (matches!(scheme, "" | "auto" | "daemon" | "local") || scheme.starts_with("unix://"))
+        && !uri.contains("root=")
+}
+
+/// Feeds a recorded invocation to `parse` without spawning nix.
+fn replay_nix<T>(
+    control: &NixControl,
+    dir: &Path,
+    args: &[&str],
+    parse: impl FnOnce(&mut dyn Read) -> Result<T>,
+) -> Result<T> {
+    let mut recorded = read_entry(dir, args)?;
+
+    if let Some(progress) = &control.progress {
+        for line in recorded.entry.stderr.lines() {
+            let hint = line.trim();
+            if !hint.is_empty() {
+                let _ = progress.send(hint.to_owned());
+            }
+        }
+    }
+
+    if !recorded.entry.success {
+        return Err(anyhow!(
+            "nix command failed for args {args:?}: {}",
+            recorded.entry.stderr
+        ));
+    }
+
+    parse(&mut recorded.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn control(extra_args: &[&str], remote: &str, transcript: Option<Transcript>) -> NixControl {
+        NixControl::new(&NixSettings {
+            extra_args: extra_args.iter().map(|arg| (*arg).to_owned()).collect(),
+            env: vec![("NIX_REMOTE".to_owned(), remote.to_owned())],
+            transcript,
+            ..NixSettings::default()
+        })
+    }
+
+    #[test]
+    fn only_local_stores_are_read_directly() {
+        assert!(control(&[], "daemon", None).reads_local_store(&[]));
+        assert!(control(&[], "", None).reads_local_store(&["--store", "local?read-only=true"]));
+        assert!(!control(&[], "ssh-ng://builder", None).reads_local_store(&[]));
+        assert!(!control(&["--store", "https://cache.nixos.org"], "", None).reads_local_store(&[]));
+        assert!(!control(&[], "", None).reads_local_store(&["--store", "/mnt"]));
+        assert!(!control(&[], "local?root=/mnt", None).reads_local_store(&[]));
+
+        let replay = Some(Transcript::Replay(std::env::temp_dir()));
+        assert!(!control(&[], "daemon", replay).reads_local_store(&[]));
+    }
+
+    /// Runs `binary` in place of nix, as `--nix-bin` would.
+    fn stub(binary: &str, timeout: Duration) -> NixControl {
+        NixControl::new(&NixSettings {
+            binary: PathBuf::from(binary),
+            timeout,
+            ..NixSettings::default()
+        })
+    }
+
+    #[test]
+    fn timeouts_kill_the_command() {
+        let control = stub("sleep", Duration::from_millis(200));
+        let started = Instant::now();
+
+        let error = run_nix(&control, &["30"]).unwrap_err();
+        assert!(error.to_string().contains("timed out"), "{error}");
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn cancelling_kills_the_command() {
+        let control = stub("sleep", DEFAULT_NIX_TIMEOUT);
+        let canceller = control.clone();
+        let started = Instant::now();
+        let cancel = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            canceller.cancel();
+        });
+
+        let error = run_nix(&control, &["30"]).unwrap_err();
+        cancel.join().unwrap();
+        assert!(error.to_string().contains("cancelled"), "{error}");
+        assert!(started.elapsed() < Duration::from_secs(10));
+
+        // Later commands of a cancelled job are not started at all.
+        let error = run_nix(&control, &["30"]).unwrap_err();
+        assert!(error.to_string().contains("cancelled"), "{error}");
+    }
+
+    #[test]
+    fn forwards_stderr_as_progress_and_reports_failures() {
+        let (progress_tx, progress_rx) = std::sync::mpsc::channel();
+        let control = stub("sh", DEFAULT_NIX_TIMEOUT).with_progress(progress_tx);
+
+        let output = run_nix(&control, &["-c", "echo 'copying path' >&2; echo done"]).unwrap();
+        assert_eq!(output, "done\n");
+        assert_eq!(progress_rx.try_iter().collect::<Vec<_>>(), ["copying path"]);
+
+        let error = run_nix(&control, &["-c", "echo 'path is invalid' >&2; exit 1"]).unwrap_err();
+        assert!(error.to_string().contains("path is invalid"), "{error}");
+    }
+
+    #[test]
+    fn store_identity_follows_what_picks_the_store() {
+        let local = control(&[], "daemon", None).store_identity();
+        assert_eq!(local, control(&[], "daemon", None).store_identity());
+        assert_ne!(
+            local,
+            control(&[], "local?root=/mnt", None).store_identity()
+        );
+        assert_ne!(
+            local,
+            control(&["--store", "/mnt"], "daemon", None).store_identity()
+        );
+
+        let database = NixControl::new(&NixSettings {
+            env: vec![("NIX_REMOTE".to_owned(), "daemon".to_owned())],
+            store_db: Some(PathBuf::from("/mnt/nix/var/nix/db/db.sqlite")),
+            ..NixSettings::default()
+        });
+        assert_ne!(local, database.store_identity());
     }
 }
@@ -1,7 +1,11 @@
 use std::collections::HashMap;
+use std::fmt;
+use std::io::{BufReader, Read};
 
 use anyhow::{Context, Result, anyhow};
 use serde::Deserialize;
+use serde::de::value::MapAccessDeserializer;
+use serde::de::{self, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
 use serde_json::Value;
 
 #[derive(Clone, Debug, Deserialize)]
@@ -14,57 +18,358 @@ pub(super) struct RawPathInfo {
     pub(super) references: Vec<String>,
     #[serde(default)]
     pub(super) deriver: Option<String>,
+    #[serde(default)]
+    pub(super) ca: Option<Value>,
+    #[serde(default)]
+    pub(super) signatures: Vec<Value>,
+    #[serde(default, rename = "registrationTime")]
+    pub(super) registration_time: Option<u64>,
 }
 
-pub(super) fn parse_path_info_output(raw: &str) -> Result<(String, HashMap<String, RawPathInfo>)> {
-    let parsed: Value = serde_json::from_str(raw).context("invalid JSON from nix")?;
-    let object = parsed
-        .as_object()
-        .ok_or_else(|| anyhow!("unexpected JSON type from nix path-info"))?;
-
-    if let Some(info_value) = object.get("info") {
-        let store_dir = object
-            .get("storeDir")
-            .and_then(Value::as_str)
-            .unwrap_or("/nix/store")
-            .to_string();
-        let info_object = info_value
-            .as_object()
-            .ok_or_else(|| anyhow!("invalid info map in JSON"))?;
-        let mut info = HashMap::with_capacity(info_object.len());
-        for (key, value) in info_object {
-            let entry = RawPathInfo::deserialize(value).context("invalid info map in JSON")?;
-            info.insert(key.clone(), entry);
-        }
-        return Ok((store_dir, info));
-    }
+struct PathInfoOutput {
+    store_dir: String,
+    info: HashMap<String, RawPathInfo>,
+}
+
+struct LenientEntry(Option<RawPathInfo>);
+
+impl<'de> Deserialize<'de> for LenientEntry {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct EntryVisitor;
+
+        impl<'de> Visitor<'de> for EntryVisitor {
+            type Value = LenientEntry;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a path-info entry")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+                RawPathInfo::deserialize(MapAccessDeserializer::new(map))
+                    .map(|entry| LenientEntry(Some(entry)))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                while seq.next_element::<IgnoredAny>()?.is_some() {}
+                Ok(LenientEntry(None))
+            }
 
-    let store_dir = object
-        .get("storeDir")
-        .and_then(Value::as_str)
-        .unwrap_or("/nix/store")
-        .to_string();
+            fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+                Ok(LenientEntry(None))
+            }
 
-    let mut info = HashMap::new();
-    for (key, value) in object {
-        if key == "storeDir" || key == "version" {
-            continue;
+            fn visit_bool<E: de::Error>(self, _value: bool) -> Result<Self::Value, E> {
+                Ok(LenientEntry(None))
+            }
+
+            fn visit_i64<E: de::Error>(self, _value: i64) -> Result<Self::Value, E> {
+                Ok(LenientEntry(None))
+            }
+
+            fn visit_u64<E: de::Error>(self, _value: u64) -> Result<Self::Value, E> {
+                Ok(LenientEntry(None))
+            }
+
+            fn visit_f64<E: de::Error>(self, _value: f64) -> Result<Self::Value, E> {
+                Ok(LenientEntry(None))
+            }
+
+            fn visit_str<E: de::Error>(self, _value: &str) -> Result<Self::Value, E> {
+                Ok(LenientEntry(None))
+            }
         }
 
-        if let Ok(entry) = RawPathInfo::deserialize(value) {
-            info.insert(key.clone(), entry);
+        deserializer.deserialize_any(EntryVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for PathInfoOutput {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct OutputVisitor;
+
+        impl<'de> Visitor<'de> for OutputVisitor {
+            type Value = PathInfoOutput;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a nix path-info JSON object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut store_dir = None;
+                let mut info = HashMap::new();
+
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "storeDir" => store_dir = Some(map.next_value::<String>()?),
+                        "version" => {
+                            map.next_value::<IgnoredAny>()?;
+                        }
+                        "info" => {
+                            let entries = map.next_value::<HashMap<String, LenientEntry>>()?;
+                            info.extend(
+                                entries
+                                    .into_iter()
+                                    .filter_map(|(key, entry)| entry.0.map(|entry| (key, entry))),
+                            );
+                        }
+                        // In the flat schema anything that does not read as a
+                        // path-info entry is skipped, malformed entries included.
+                        _ => {
+                            let value = map.next_value::<Value>()?;
+                            if let Ok(entry) = RawPathInfo::deserialize(value) {
+                                info.insert(key, entry);
+                            }
+                        }
+                    }
+                }
+
+                Ok(PathInfoOutput {
+                    store_dir: store_dir.unwrap_or_else(|| "/nix/store".to_owned()),
+                    info,
+                })
+            }
         }
+
+        deserializer.deserialize_map(OutputVisitor)
     }
+}
 
-    if info.is_empty() {
+pub(super) fn parse_path_info_reader(
+    reader: impl Read,
+) -> Result<(String, HashMap<String, RawPathInfo>)> {
+    let output: PathInfoOutput = serde_json::from_reader(BufReader::new(reader))
+        .context("invalid JSON from nix path-info")?;
+
+    if output.info.is_empty() {
         Err(anyhow!(
             "could not parse nix path-info JSON; no entries found"
         ))
     } else {
-        Ok((store_dir, info))
+        Ok((output.store_dir, output.info))
     }
 }
 
 pub(super) fn normalize_store_key(value: &str) -> String {
     value.rsplit('/').next().unwrap_or(value).to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use proptest::prelude::*;
+    use serde_json::{Value, json};
+
+    use super::{normalize_store_key, parse_path_info_reader};
+
+    #[derive(Clone, Debug)]
+    struct Entry {
+        nar_size: Option<u64>,
+        closure_size: Option<u64>,
+        references: Option<Vec<String>>,
+        deriver: Option<String>,
+    }
+
+    fn store_id() -> impl Strategy<Value = String> {
+        "[0-9a-df-np-sv-z]{32}-[a-z][a-z0-9.+_-]{0,24}"
+    }
+
+    fn entry() -> impl Strategy<Value = Entry> {
+        (
+            proptest::option::of(any::<u64>()),
+            proptest::option::of(any::<u64>()),
+            proptest::option::of(proptest::collection::vec(store_id(), 0..4)),
+            proptest::option::of(store_id().prop_map(|id| format!("{id}.drv"))),
+        )
+            .prop_map(|(nar_size, closure_size, references, deriver)| Entry {
+                nar_size,
+                closure_size,
+                references,
+                deriver,
+            })
+    }
+
+    fn entry_json(entry: &Entry) -> Value {
+        let mut object = serde_json::Map::new();
+        if let Some(nar_size) = entry.nar_size {
+            object.insert("narSize".to_owned(), json!(nar_size));
+        }
+        if let Some(closure_size) = entry.closure_size {
+            object.insert("closureSize".to_owned(), json!(closure_size));
+        }
+        if let Some(references) = &entry.references {
+            object.insert("references".to_owned(), json!(references));
+        }
+        if let Some(deriver) = &entry.deriver {
+            object.insert("deriver".to_owned(), json!(deriver));
+        }
+        object.insert("registrationTime".to_owned(), json!(1_700_000_000));
+        Value::Object(object)
+    }
+
+    fn assert_entries_match(
+        parsed: &std::collections::HashMap<String, super::RawPathInfo>,
+        expected: &BTreeMap<String, Entry>,
+        key: impl Fn(&str) -> String,
+    ) -> Result<(), TestCaseError> {
+        prop_assert_eq!(parsed.len(), expected.len());
+        for (id, entry) in expected {
+            let info = parsed.get(&key(id));
+            prop_assert!(info.is_some(), "missing entry for {}", id);
+            let info = info.unwrap();
+            prop_assert_eq!(info.nar_size, entry.nar_size.unwrap_or(0));
+            prop_assert_eq!(info.closure_size, entry.closure_size.unwrap_or(0));
+            prop_assert_eq!(
+                &info.references,
+                &entry.references.clone().unwrap_or_default()
+            );
+            prop_assert_eq!(&info.deriver, &entry.deriver);
+        }
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn parses_info_wrapped_schema(
+            entries in proptest::collection::btree_map(store_id(), entry(), 1..8),
+            store_dir in proptest::option::of("/[a-z]{1,8}/store"),
+        ) {
+            let info = entries
+                .iter()
+                .map(|(id, entry)| (id.clone(), entry_json(entry)))
+                .collect::<serde_json::Map<_, _>>();
+            let mut document = json!({ "version": 2, "info": info });
+            if let Some(store_dir) = &store_dir {
+                document["storeDir"] = json!(store_dir);
+            }
+            let raw = serde_json::to_vec(&document).unwrap();
+
+            let (parsed_store_dir, parsed) = parse_path_info_reader(raw.as_slice()).unwrap();
+            prop_assert_eq!(
+                parsed_store_dir,
+                store_dir.unwrap_or_else(|| "/nix/store".to_owned())
+            );
+            assert_entries_match(&parsed, &entries, str::to_owned)?;
+        }
+
+        #[test]
+        fn parses_flat_schema_with_absolute_keys(
+            entries in proptest::collection::btree_map(store_id(), entry(), 1..8),
+        ) {
+            let document = entries
+                .iter()
+                .map(|(id, entry)| (format!("/nix/store/{id}"), entry_json(entry)))
+                .collect::<serde_json::Map<_, _>>();
+            let raw = serde_json::to_vec(&Value::Object(document)).unwrap();
+
+            let (store_dir, parsed) = parse_path_info_reader(raw.as_slice()).unwrap();
+            prop_assert_eq!(store_dir, "/nix/store");
+            assert_entries_match(&parsed, &entries, |id| format!("/nix/store/{id}"))?;
+        }
+
+        #[test]
+        fn skips_invalid_and_non_object_entries(
+            entries in proptest::collection::btree_map(store_id(), entry(), 1..6),
+            invalid in proptest::collection::btree_set(store_id(), 0..4),
+            scalar in prop_oneof![
+                Just(Value::Null),
+                any::<bool>().prop_map(Value::from),
+                any::<i64>().prop_map(Value::from),
+                ".{0,12}".prop_map(Value::from),
+                Just(json!([1, [2, {"narSize": 3}]])),
+            ],
+        ) {
+            let mut info = entries
+                .iter()
+                .map(|(id, entry)| (id.clone(), entry_json(entry)))
+                .collect::<serde_json::Map<_, _>>();
+            for id in invalid.iter().filter(|id| !entries.contains_key(*id)) {
+                info.insert(id.clone(), scalar.clone());
+            }
+            let raw = serde_json::to_vec(&json!({ "info": info })).unwrap();
+
+            let (_, parsed) = parse_path_info_reader(raw.as_slice()).unwrap();
+            assert_entries_match(&parsed, &entries, str::to_owned)?;
+        }
+
+        #[test]
+        fn hostile_bytes_never_panic(raw in proptest::collection::vec(any::<u8>(), 0..256)) {
+            let _ = parse_path_info_reader(raw.as_slice());
+        }
+
+        #[test]
+        fn hostile_json_never_panics(raw in r#"[\[\]{}":,0-9a-z\\ -]{0,64}"#) {
+            let _ = parse_path_info_reader(raw.as_bytes());
+        }
+
+        #[test]
+        fn wrongly_typed_fields_are_errors(
+            id in store_id(),
+            field in "narSize|closureSize|references|deriver",
+        ) {
+            let document = json!({ "info": { id: { field: { "nested": true } } } });
+            let raw = serde_json::to_vec(&document).unwrap();
+            prop_assert!(parse_path_info_reader(raw.as_slice()).is_err());
+        }
+
+        #[test]
+        fn flat_schema_skips_wrongly_typed_entries(
+            entries in proptest::collection::btree_map(store_id(), entry(), 1..6),
+            malformed in store_id(),
+            field in "narSize|closureSize|references|deriver",
+        ) {
+            prop_assume!(!entries.contains_key(&malformed));
+            let mut document = entries
+                .iter()
+                .map(|(id, entry)| (format!("/nix/store/{id}"), entry_json(entry)))
+                .collect::<serde_json::Map<_, _>>();
+            document.insert(
+                format!("/nix/store/{malformed}"),
+                json!({ field: { "nested": true } }),
+            );
+            let raw = serde_json::to_vec(&Value::Object(document)).unwrap();
+
+            let (_, parsed) = parse_path_info_reader(raw.as_slice()).unwrap();
+            assert_entries_match(&parsed, &entries, |id| format!("/nix/store/{id}"))?;
+        }
+
+        #[test]
+        fn normalize_strips_store_directory(id in store_id(), dir in "(/[a-z]{1,8}){1,3}") {
+            prop_assert_eq!(normalize_store_key(&format!("{dir}/{id}")), id.as_str());
+            prop_assert_eq!(normalize_store_key(&id), id.as_str());
+        }
+
+        #[test]
+        fn normalize_is_idempotent(key in ".{0,48}") {
+            let once = normalize_store_key(&key);
+            prop_assert_eq!(normalize_store_key(&once), once.clone());
+            prop_assert!(!once.contains('/'));
+        }
+    }
+
+    #[test]
+    fn empty_documents_are_errors() {
+        for raw in [
+            "{}",
+            r#"{"info": {}}"#,
+            r#"{"version": 2, "storeDir": "/nix/store"}"#,
+        ] {
+            assert!(parse_path_info_reader(raw.as_bytes()).is_err(), "{raw}");
+        }
+    }
+
+    #[test]
+    fn non_object_documents_are_errors() {
+        for raw in [
+            "",
+            "null",
+            "[]",
+            "42",
+            r#""info""#,
+            "{",
+            r#"{"info": [1, 2]}"#,
+        ] {
+            assert!(parse_path_info_reader(raw.as_bytes()).is_err(), "{raw}");
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result, anyhow};
+use serde_json::Value;
+
+use super::contents::ContentKind;
+use super::daemon::DaemonConnection;
+use super::nix_cmd::{NixControl, run_nix};
+use super::parse::{RawPathInfo, parse_path_info_reader};
+
+/// Top-level entries kept per preview; the rest are only counted.
+const PREVIEW_ENTRIES: usize = 8;
+
+/// The quick look at a path fetched while it is hovered: its top-level
+/// entries and the narinfo fields the closure query leaves out.
+#[derive(Clone, Debug, Default)]
+pub struct PathPreview {
+    /// Top-level entries by name, directories first.
+    pub entries: Vec<(String, ContentKind)>,
+    pub entry_count: usize,
+    pub registered: Option<SystemTime>,
+    pub signatures: Vec<String>,
+    pub content_addressed: bool,
+}
+
+pub fn preview_path(control: &NixControl, full_path: &str) -> Result<PathPreview> {
+    let info = path_info(control, full_path)?;
+
+    let mut entries = top_level_entries(control, full_path)?;
+    entries.sort_by(|a, b| {
+        (a.1 != ContentKind::Directory)
+            .cmp(&(b.1 != ContentKind::Directory))
+            .then_with(|| a.0.cmp(&b.0))
+    });
+    let entry_count = entries.len();
+    entries.truncate(PREVIEW_ENTRIES);
+
+    Ok(PathPreview {
+        entries,
+        entry_count,
+        registered: info
+            .registration_time
+            .map(|seconds| SystemTime::UNIX_EPOCH + Duration::from_secs(seconds)),
+        signatures: info
+            .signatures
+            .iter()
+            .filter_map(Value::as_str)
+            .map(str::to_owned)
+            .collect(),
+        content_addressed: info.ca.is_some_and(|ca| !ca.is_null()),
+    })
+}
+
+/// Asks the daemon directly when a socket is configured, falling back to
+/// `nix path-info` when it cannot be reached.
+fn path_info(control: &NixControl, full_path: &str) -> Result<RawPathInfo> {
+    if let Some(Ok(mut daemon)) = DaemonConnection::for_control(control) {
+        return daemon
+            .query_path_info(full_path)?
+            .map(RawPathInfo::from)
+            .ok_or_else(|| anyhow!("{full_path} is not a valid store path"));
+    }
+
+    let raw = run_nix(control, &["path-info", "--json", full_path])?;
+    let (_, info) = parse_path_info_reader(raw.as_bytes())?;
+    info.into_values()
+        .next()
+        .ok_or_else(|| anyhow!("nix path-info returned nothing for {full_path}"))
+}
+
+/// Lists the path itself on disk, or one level of `nix store ls` otherwise.
+fn top_level_entries(control: &NixControl, full_path: &str) -> Result<Vec<(String, ContentKind)>> {
+    let path = Path::new(full_path);
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        let listing = run_nix(control, &["store", "ls", "--json", "--long", full_path])?;
+        let listing = serde_json::from_str::<Value>(&listing)
+            .context("failed to parse nix store ls output")?;
+        return Ok(parse_top_level(&listing));
+    };
+
+    if !metadata.is_dir() {
+        return Ok(Vec::new());
+    }
+    fs::read_dir(path)
+        .with_context(|| format!("failed to list {}", path.display()))?
+        .map(|entry| {
+            let entry = entry.with_context(|| format!("failed to list {}", path.display()))?;
+            let file_type = entry.file_type()?;
+            let kind = if file_type.is_symlink() {
+                ContentKind::Symlink
+            } else if file_type.is_dir() {
+                ContentKind::Directory
+            } else {
+                ContentKind::File
+            };
+            Ok((entry.file_name().to_string_lossy().into_owned(), kind))
+        })
+        .collect()
+}
+
+/// Reads the entries of a directory listing such as
+/// `{"type":"directory","entries":{"bin":{"type":"directory"}}}`; a single
+/// file has none.
+fn parse_top_level(listing: &Value) -> Vec<(String, ContentKind)> {
+    listing
+        .get("entries")
+        .and_then(Value::as_object)
+        .into_iter()
+        .flatten()
+        .map(|(name, entry)| {
+            let kind = match entry.get("type").and_then(Value::as_str) {
+                Some("directory") => ContentKind::Directory,
+                Some("symlink") => ContentKind::Symlink,
+                _ => ContentKind::File,
+            };
+            (name.clone(), kind)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_top_level_entries_of_listings() {
+        let listing = serde_json::json!({
+            "type": "directory",
+            "entries": {
+                "bin": {"type": "directory"},
+                "lib": {"type": "symlink", "target": "bin"},
+                "README": {"type": "regular", "size": 12},
+            },
+        });
+
+        let mut entries = parse_top_level(&listing);
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            entries,
+            [
+                ("README".to_owned(), ContentKind::File),
+                ("bin".to_owned(), ContentKind::Directory),
+                ("lib".to_owned(), ContentKind::Symlink),
+            ]
+        );
+        assert!(parse_top_level(&serde_json::json!({"type": "regular", "size": 3})).is_empty());
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow};
+use rayon::prelude::*;
+
+use super::graph::SystemGraph;
+use super::nix_cmd::NixControl;
+
+const SCAN_FILE_LIMIT: usize = 20_000;
+
+fn store_hash(id: &str) -> &str {
+    id.split_once('-').map(|(hash, _)| hash).unwrap_or(id)
+}
+
+/// The files of a source path that mention a target's hash.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReferenceMatches {
+    /// Paths relative to the source, sorted.
+    pub files: Vec<String>,
+    /// Entries that could not be read, e.g. files only their owner may read,
+    /// and were skipped.
+    pub unreadable: usize,
+}
+
+pub fn scan_references(source_path: &str, target_id: &str) -> Result<ReferenceMatches> {
+    let needle = store_hash(target_id).as_bytes();
+    let root = Path::new(source_path);
+    let mut files = Vec::new();
+    let mut walk = ScanWalk::default();
+
+    walk.scan(root, root, &mut |relative, contents| {
+        if contains_bytes(contents, needle) {
+            files.push(display_path(relative));
+        }
+    })
+    .with_context(|| format!("failed to scan {source_path} for references"))?;
+
+    files.sort();
+    Ok(ReferenceMatches {
+        files,
+        unreadable: walk.unreadable,
+    })
+}
+
+/// Counts the files and symlinks under `source_path` that mention each of
+/// `target_ids`, walking the tree once for all of them.
+fn count_references(source_path: &str, target_ids: &[String]) -> Result<Vec<u32>> {
+    let needles = target_ids
+        .iter()
+        .map(|id| store_hash(id).as_bytes())
+        .collect::<Vec<_>>();
+    let root = Path::new(source_path);
+    let mut counts = vec![0u32; needles.len()];
+
+    ScanWalk::default()
+        .scan(root, root, &mut |_, contents| {
+            for (count, needle) in counts.iter_mut().zip(&needles) {
+                if contains_bytes(contents, needle) {
+                    *count += 1;
+                }
+            }
+        })
+        .with_context(|| format!("failed to scan {source_path} for references"))?;
+
+    Ok(counts)
+}
+
+/// Weighs every edge by the number of files in the source path that mention
+/// the target. Paths that cannot be read keep the default weight of one and
+/// are listed in the graph's limitations.
+pub fn scan_edge_weights(control: &NixControl, graph: &mut SystemGraph) -> Result<()> {
+    control.report(format!(
+        "scanning {} paths for edge weights",
+        graph.node_count()
+    ));
+    let scanned = graph
+        .nodes
+        .par_iter()
+        .filter(|(_, node)| !node.references.is_empty())
+        .map(|(id, node)| {
+            if control.is_cancelled() {
+                return Err(anyhow!("edge weight scan cancelled"));
+            }
+            Ok((id, count_references(&node.full_path, &node.references).ok()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut unreadable = 0usize;
+    let mut edge_weights = HashMap::with_capacity(scanned.len());
+    for (id, counts) in scanned {
+        let Some(counts) = counts else {
+            unreadable += 1;
+            continue;
+        };
+        let weights = graph.nodes[id]
+            .references
+            .iter()
+            .cloned()
+            .zip(counts)
+            .collect::<HashMap<_, _>>();
+        edge_weights.insert(id.clone(), weights);
+    }
+
+    graph.edge_weights = edge_weights;
+    if unreadable > 0 {
+        graph.limitations.push(format!(
+            "{unreadable} paths could not be read to weigh their edges; those edges count once."
+        ));
+    }
+    Ok(())
+}
+
+/// Progress of one depth-first walk over a source path.
+#[derive(Default)]
+struct ScanWalk {
+    visited: usize,
+    /// Entries below the root that could not be read and were skipped.
+    unreadable: usize,
+}
+
+impl ScanWalk {
+    /// Walks `path` depth-first in sorted order, passing each file's contents
+    /// or symlink's target to `on_entry` along with its path relative to
+    /// `root`. Only an unreadable `root` fails the walk; entries below it that
+    /// cannot be read are counted in `unreadable` instead.
+    fn scan(
+        &mut self,
+        root: &Path,
+        path: &Path,
+        on_entry: &mut impl FnMut(&Path, &[u8]),
+    ) -> Result<()> {
+        match self.scan_entry(root, path, on_entry) {
+            Err(_) if path != root => {
+                self.unreadable += 1;
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    fn scan_entry(
+        &mut self,
+        root: &Path,
+        path: &Path,
+        on_entry: &mut impl FnMut(&Path, &[u8]),
+    ) -> Result<()> {
+        if self.visited >= SCAN_FILE_LIMIT {
+            return Ok(());
+        }
+        self.visited += 1;
+
+        let metadata = fs::symlink_metadata(path)?;
+        let file_type = metadata.file_type();
+        let relative = path.strip_prefix(root).unwrap_or(path);
+
+        if file_type.is_symlink() {
+            let target = fs::read_link(path)?;
+            on_entry(relative, target.as_os_str().as_encoded_bytes());
+        } else if file_type.is_dir() {
+            let mut entries = fs::read_dir(path)?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .collect::<Vec<_>>();
+            entries.sort();
+            for entry in entries {
+                self.scan(root, &entry, on_entry)?;
+            }
+        } else if file_type.is_file() {
+            let contents = fs::read(path)?;
+            on_entry(relative, &contents);
+        }
+
+        Ok(())
+    }
+}
+
+fn display_path(relative: &Path) -> String {
+    if relative.as_os_str().is_empty() {
+        ".".to_owned()
+    } else {
+        relative.to_string_lossy().into_owned()
+    }
+}
+
+fn contains_bytes(haystack: &[u8], needle: &[u8]) -> bool {
+    !needle.is_empty()
+        && haystack.len() >= needle.len()
+        && haystack
+            .windows(needle.len())
+            .any(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_files_mentioning_each_target() {
+        let dir = std::env::temp_dir().join(format!("nix-analisa-refscan-{}", std::process::id()));
+        fs::create_dir_all(dir.join("bin")).unwrap();
+        fs::write(
+            dir.join("bin/a"),
+            "/nix/store/aaaa-glibc/lib /nix/store/bbbb-zlib",
+        )
+        .unwrap();
+        fs::write(dir.join("bin/b"), "/nix/store/aaaa-glibc/lib").unwrap();
+        fs::write(dir.join("README"), "nothing here").unwrap();
+
+        let targets = ["aaaa-glibc-2.39", "bbbb-zlib-1.3", "cccc-bash-5.2"].map(str::to_owned);
+        let counts = count_references(dir.to_str().unwrap(), &targets).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(counts, vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn lists_mentioning_files_and_skips_unreadable_ones() {
+        use std::os::unix::fs::{PermissionsExt, symlink};
+
+        let dir =
+            std::env::temp_dir().join(format!("nix-analisa-refscan-list-{}", std::process::id()));
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        fs::write(dir.join("lib/libfoo.so"), "RPATH=/nix/store/aaaa-glibc/lib").unwrap();
+        fs::write(dir.join("README"), "nothing here").unwrap();
+        symlink("/nix/store/aaaa-glibc/bin/sh", dir.join("sh")).unwrap();
+        let secret = dir.join("secret");
+        fs::write(&secret, "/nix/store/aaaa-glibc").unwrap();
+        fs::set_permissions(&secret, fs::Permissions::from_mode(0o000)).unwrap();
+        // Root reads the file regardless of its mode.
+        let secret_readable = fs::read(&secret).is_ok();
+
+        let matches = scan_references(dir.to_str().unwrap(), "aaaa-glibc-2.39");
+        let missing = scan_references(dir.join("missing").to_str().unwrap(), "aaaa-glibc-2.39");
+        // An entry gone between listing its directory and reading it.
+        let mut walk = ScanWalk::default();
+        let vanished = walk.scan(&dir, &dir.join("vanished"), &mut |_, _| {});
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(vanished.is_ok());
+        assert_eq!(walk.unreadable, 1);
+
+        let matches = matches.unwrap();
+        let mut expected = vec!["lib/libfoo.so", "sh"];
+        if secret_readable {
+            expected.insert(1, "secret");
+        }
+        assert_eq!(matches.files, expected);
+        assert_eq!(matches.unreadable, usize::from(!secret_readable));
+        assert!(missing.is_err());
+    }
+}
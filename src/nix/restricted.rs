@@ -0,0 +1,91 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use super::collect::collect_with_store_args;
+use super::graph::SystemGraph;
+use super::nix_cmd::NixControl;
+use super::store_db::collect_from_store_db;
+use super::watch::NIX_DB_PATH;
+
+/// Fragments of the errors nix and the daemon report when the current user
+/// may not use the store.
+const PERMISSION_MARKERS: [&str; 4] = [
+    "permission denied",
+    "not allowed to connect",
+    "operation not permitted",
+    "cannot open connection to remote store",
+];
+
+/// Opens the local store without taking locks or writing to it, which works
+/// for users the daemon rejects as long as the store itself is readable.
+const READ_ONLY_STORE_ARGS: [&str; 4] = [
+    "--extra-experimental-features",
+    "read-only-local-store",
+    "--store",
+    "local?read-only=true",
+];
+
+pub(super) fn is_permission_error(error: &anyhow::Error) -> bool {
+    let message = format!("{error:#}").to_lowercase();
+    PERMISSION_MARKERS
+        .iter()
+        .any(|marker| message.contains(marker))
+}
+
+/// Retries a collection that was denied store access, first through a
+/// read-only local store and then by reading the store database directly.
+/// Whatever the fallback cannot provide is listed in the graph's limitations.
+pub(super) fn collect_restricted(
+    control: &NixControl,
+    system_path: &str,
+    denied: anyhow::Error,
+) -> Result<SystemGraph> {
+    control.report("store access denied; retrying with a read-only store".to_owned());
+    match collect_with_store_args(control, system_path, &READ_ONLY_STORE_ARGS) {
+        Ok(mut graph) => {
+            graph.limitations.push(
+                "The nix daemon denied access, so the closure was read from the local store in \
+                 read-only mode. NAR verification needs store access and may fail."
+                    .to_owned(),
+            );
+            return Ok(graph);
+        }
+        Err(error) if control.is_replaying() => {
+            return Err(error.context(format!("{denied:#}")));
+        }
+        Err(_) => {}
+    }
+
+    control.report("read-only store unavailable; reading the store database".to_owned());
+    let mut graph = collect_from_store_db(control, Path::new(NIX_DB_PATH), system_path)
+        .with_context(|| {
+            format!("store access was denied and no read-only fallback worked: {denied:#}")
+        })?;
+    graph.limitations.push(
+        "The nix daemon denied access, so the closure was read straight from the store \
+         database. NAR verification is unavailable."
+            .to_owned(),
+    );
+    Ok(graph)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+
+    use super::*;
+
+    #[test]
+    fn detects_daemon_permission_errors() {
+        let error = anyhow!(
+            "nix command failed: error: cannot open connection to remote store 'daemon': \
+             error: reading from file: Connection reset by peer"
+        );
+        assert!(is_permission_error(&error));
+        assert!(is_permission_error(&anyhow!(
+            "opening lock file: Permission denied"
+        )));
+        assert!(!is_permission_error(&anyhow!("path is not valid")));
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{Context, Result, anyhow};
+use rayon::prelude::*;
+
+use super::collect::{collect_system_graph, finish_graph};
+use super::graph::SystemGraph;
+use super::nix_cmd::NixControl;
+
+/// Membership is kept as one bit per root.
+pub const MAX_ROOTS: usize = 64;
+
+/// Which roots of a multi-root graph reach a path.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RootMembership {
+    /// Only the root at this index in [`SystemGraph::roots`].
+    Only(usize),
+    /// Some roots but not all; only possible with three or more.
+    Partial,
+    Shared,
+}
+
+/// Collects the closure of every path in `system_paths` and merges them into
+/// one graph rooted at the first, recording which roots reach each path.
+pub fn collect_multi_root_graph(
+    control: &NixControl,
+    system_paths: &[String],
+) -> Result<SystemGraph> {
+    if system_paths.len() > MAX_ROOTS {
+        return Err(anyhow!(
+            "at most {MAX_ROOTS} roots can be merged, got {}",
+            system_paths.len()
+        ));
+    }
+
+    let mut nodes = HashMap::new();
+    let mut roots = Vec::with_capacity(system_paths.len());
+    let mut limitations = Vec::new();
+    let mut store_dir = None;
+    for system_path in system_paths {
+        control.report(format!("collecting root {system_path}"));
+        let graph = collect_system_graph(control, system_path)
+            .with_context(|| format!("failed to collect root {system_path}"))?;
+        store_dir.get_or_insert(graph.store_dir);
+        limitations.extend(graph.limitations);
+        if !roots.contains(&graph.root_id) {
+            roots.push(graph.root_id);
+        }
+        for (id, node) in graph.nodes {
+            nodes.entry(id).or_insert(node);
+        }
+    }
+
+    let Some(store_dir) = store_dir else {
+        return Err(anyhow!("no roots were given"));
+    };
+    let mut graph = finish_graph(nodes, roots[0].clone(), store_dir, control);
+    limitations.dedup();
+    graph.limitations = limitations;
+    graph.set_roots(roots);
+    Ok(graph)
+}
+
+impl SystemGraph {
+    /// Records the roots of a merged graph and which of them reach each path.
+    /// A single root clears the membership.
+    pub fn set_roots(&mut self, roots: Vec<String>) {
+        let roots = roots
+            .into_iter()
+            .filter(|root| self.nodes.contains_key(root))
+            .take(MAX_ROOTS)
+            .collect::<Vec<_>>();
+        self.root_membership.clear();
+        if roots.len() < 2 {
+            self.roots = Vec::new();
+            return;
+        }
+
+        let closures = roots
+            .par_iter()
+            .map(|root| self.closure_ids([root.as_str()]))
+            .collect::<Vec<_>>();
+        let mut membership = HashMap::<String, u64>::new();
+        for (index, closure) in closures.into_iter().enumerate() {
+            for id in closure {
+                *membership.entry(id.to_owned()).or_default() |= 1 << index;
+            }
+        }
+        self.root_membership = membership;
+        self.roots = roots;
+    }
+
+    pub fn is_multi_root(&self) -> bool {
+        self.roots.len() > 1
+    }
+
+    pub fn membership(&self, id: &str) -> Option<RootMembership> {
+        let mask = *self.root_membership.get(id)?;
+        let all = u64::MAX >> (u64::BITS as usize - self.roots.len());
+        Some(if mask == all {
+            RootMembership::Shared
+        } else if mask.is_power_of_two() {
+            RootMembership::Only(mask.trailing_zeros() as usize)
+        } else {
+            RootMembership::Partial
+        })
+    }
+
+    /// Paths reached only from the root at `root_index`, largest NAR first.
+    pub fn unique_to_root(&self, root_index: usize) -> Vec<String> {
+        let mut ids = self
+            .root_membership
+            .iter()
+            .filter(|&(_, &mask)| mask == 1 << root_index)
+            .map(|(id, _)| id.clone())
+            .collect::<Vec<_>>();
+        ids.sort_by(|a, b| {
+            self.nodes[b]
+                .nar_size
+                .cmp(&self.nodes[a].nar_size)
+                .then_with(|| a.cmp(b))
+        });
+        ids
+    }
+
+    /// Every path reachable from `starts`, including themselves.
+    pub fn closure_ids<'a>(
+        &'a self,
+        starts: impl IntoIterator<Item = &'a str>,
+    ) -> HashSet<&'a str> {
+        let mut visited = starts.into_iter().collect::<HashSet<_>>();
+        let mut stack = visited.iter().copied().collect::<Vec<_>>();
+        while let Some(id) = stack.pop() {
+            for reference in self
+                .nodes
+                .get(id)
+                .into_iter()
+                .flat_map(|node| &node.references)
+            {
+                if visited.insert(reference.as_str()) {
+                    stack.push(reference);
+                }
+            }
+        }
+        visited
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nix::generate_demo_graph;
+
+    #[test]
+    fn classifies_paths_by_the_roots_reaching_them() {
+        let mut graph = generate_demo_graph(300);
+        let mut ids = graph.nodes.keys().cloned().collect::<Vec<_>>();
+        ids.sort();
+        // A leaf-ish second root: something the main root reaches, so its
+        // closure is shared and nothing is unique to it.
+        let second = ids
+            .iter()
+            .find(|id| **id != graph.root_id && !graph.nodes[*id].references.is_empty())
+            .unwrap()
+            .clone();
+        graph.set_roots(vec![graph.root_id.clone(), second.clone()]);
+
+        assert!(graph.is_multi_root());
+        assert_eq!(graph.membership(&second), Some(RootMembership::Shared));
+        assert_eq!(
+            graph.membership(&graph.root_id),
+            Some(RootMembership::Only(0))
+        );
+        assert!(graph.unique_to_root(1).is_empty());
+        let unique = graph.unique_to_root(0);
+        assert!(unique.contains(&graph.root_id));
+        assert!(
+            unique
+                .windows(2)
+                .all(|pair| { graph.nodes[&pair[0]].nar_size >= graph.nodes[&pair[1]].nar_size })
+        );
+
+        graph.set_roots(vec![graph.root_id.clone()]);
+        assert!(!graph.is_multi_root());
+        assert_eq!(graph.membership(&second), None);
+    }
+}
@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result, anyhow};
+
+use super::classify::classify_elf_paths;
+use super::collect::{node_record, resolve_root};
+use super::graph::{GraphDelta, NodeRecord, SystemGraph};
+use super::nix_cmd::{NixControl, run_nix, stream_nix};
+use super::parse::{normalize_store_key, parse_path_info_reader};
+
+/// The alphabet of store hashes; their first character picks the shard.
+const NIX_BASE32: &[u8; 32] = b"0123456789abcdfghijklmnpqrsvwxyz";
+
+/// Paths passed to one `nix path-info` call, well below the argument size limit.
+const SHARD_BATCH_PATHS: usize = 2048;
+
+fn shard_index(path: &str) -> usize {
+    normalize_store_key(path)
+        .bytes()
+        .next()
+        .and_then(|first| NIX_BASE32.iter().position(|&digit| digit == first))
+        .unwrap_or(0)
+}
+
+/// Streams every valid store path to `on_shard`, one hash-prefix shard at a
+/// time. The listing is read in full first so that its nix process exits,
+/// then each shard is read with its own `nix path-info` calls, each under the
+/// usual timeout. Records are moved to `on_shard` rather than kept here.
+///
+/// Closure sizes are not requested, as computing them for every path in the
+/// store is prohibitively slow; each path's closure size is its NAR size and
+/// retained sizes are left unset.
+pub fn stream_store_shards(
+    control: &NixControl,
+    system_path: &str,
+    mut on_shard: impl FnMut(GraphDelta),
+) -> Result<()> {
+    let root_id = resolve_root(control, system_path, &[])?;
+
+    let listing =
+        run_nix(control, &["path-info", "--all"]).context("failed to list valid store paths")?;
+    let mut shards = vec![Vec::new(); NIX_BASE32.len()];
+    for path in listing
+        .lines()
+        .map(str::trim)
+        .filter(|path| !path.is_empty())
+    {
+        shards[shard_index(path)].push(path.to_owned());
+    }
+    drop(listing);
+    if shards.iter().all(Vec::is_empty) {
+        return Err(anyhow!("nix path-info --all returned no store paths"));
+    }
+
+    let mut loaded = 0;
+    for (index, paths) in shards.into_iter().enumerate() {
+        if paths.is_empty() {
+            continue;
+        }
+        let added = read_shard(control, index, &paths)?;
+        loaded += added.len();
+        control.report(format!(
+            "loaded shard {}/{}: {loaded} paths so far",
+            index + 1,
+            NIX_BASE32.len()
+        ));
+        on_shard(GraphDelta {
+            root_id: root_id.clone(),
+            added,
+            removed: Vec::new(),
+        });
+    }
+    Ok(())
+}
+
+/// Collects every valid store path into one graph, shard by shard; see
+/// `stream_store_shards`.
+pub fn collect_store_shards(control: &NixControl, system_path: &str) -> Result<SystemGraph> {
+    let mut preview = ShardPreview::default();
+    let mut graph = None;
+    stream_store_shards(control, system_path, |shard| match &mut graph {
+        None => graph = Some(preview.start(shard)),
+        Some(graph) => preview.merge(graph, shard),
+    })?;
+    graph.ok_or_else(|| anyhow!("no store paths were returned by nix path-info"))
+}
+
+/// Reads the metadata of one shard's `paths`.
+fn read_shard(control: &NixControl, index: usize, paths: &[String]) -> Result<Vec<NodeRecord>> {
+    let mut added = Vec::with_capacity(paths.len());
+    for batch in paths.chunks(SHARD_BATCH_PATHS) {
+        let mut args = vec!["path-info", "--json", "--json-format", "2"];
+        args.extend(batch.iter().map(String::as_str));
+        let (store_dir, info) = stream_nix(control, &args, |stdout| {
+            parse_path_info_reader(stdout).context("failed to parse shard nix path-info output")
+        })
+        .with_context(|| {
+            format!(
+                "failed to collect store shard {}",
+                NIX_BASE32[index] as char
+            )
+        })?;
+
+        let mut batch_nodes = info
+            .into_iter()
+            .filter_map(|(raw_key, raw_entry)| node_record(raw_key, raw_entry, &store_dir))
+            .map(|node| (node.id.clone(), node))
+            .collect();
+        classify_elf_paths(control, &[], &mut batch_nodes);
+        added.extend(batch_nodes.into_values().map(|mut node| {
+            node.closure_count = 1;
+            node
+        }));
+    }
+    Ok(added)
+}
+
+/// Grows a graph from the shards of a whole-store scan while it runs.
+/// References are linked as soon as both of their ends have loaded, so once
+/// the last shard is in the graph is complete.
+#[derive(Default)]
+pub struct ShardPreview {
+    /// Paths referenced before their shard loaded, with their referrers.
+    pending: HashMap<String, Vec<String>>,
+}
+
+impl ShardPreview {
+    /// A graph of the first shard.
+    pub fn start(&mut self, shard: GraphDelta) -> SystemGraph {
+        let store_dir = shard
+            .added
+            .first()
+            .map_or_else(|| "/nix/store".to_owned(), |node| node.store_dir.clone());
+        let mut graph = SystemGraph {
+            store_dir,
+            root_id: shard.root_id.clone(),
+            nodes: HashMap::with_capacity(shard.added.len()),
+            edge_count: 0,
+            limitations: Vec::new(),
+            edge_weights: HashMap::new(),
+            roots: Vec::new(),
+            root_membership: HashMap::new(),
+        };
+        self.merge(&mut graph, shard);
+        graph
+    }
+
+    /// Adds the paths of `shard` to `graph`. The root stays the lowest id
+    /// until the shard holding the real one has loaded.
+    pub fn merge(&mut self, graph: &mut SystemGraph, shard: GraphDelta) {
+        for mut node in shard.added {
+            let (present, missing) = std::mem::take(&mut node.references)
+                .into_iter()
+                .partition::<Vec<_>, _>(|reference| graph.nodes.contains_key(reference));
+            for reference in missing {
+                self.pending
+                    .entry(reference)
+                    .or_default()
+                    .push(node.id.clone());
+            }
+            for reference in &present {
+                if let Some(target) = graph.nodes.get_mut(reference) {
+                    insert_sorted(&mut target.referrers, node.id.clone());
+                }
+            }
+            node.references = present;
+
+            node.referrers = self.pending.remove(&node.id).unwrap_or_default();
+            node.referrers.sort_unstable();
+            for referrer in &node.referrers {
+                if let Some(source) = graph.nodes.get_mut(referrer) {
+                    insert_sorted(&mut source.references, node.id.clone());
+                }
+            }
+
+            graph.edge_count += node.references.len() + node.referrers.len();
+            graph.nodes.insert(node.id.clone(), node);
+        }
+
+        if graph.nodes.contains_key(&shard.root_id) {
+            graph.root_id = shard.root_id;
+        } else if !graph.nodes.contains_key(&graph.root_id)
+            && let Some(lowest) = graph.nodes.keys().min()
+        {
+            graph.root_id = lowest.clone();
+        }
+    }
+}
+
+fn insert_sorted(ids: &mut Vec<String>, id: String) {
+    if let Err(position) = ids.binary_search(&id) {
+        ids.insert(position, id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Write;
+
+    use super::super::collect::finish_graph;
+    use super::super::graph::test_node;
+    use super::super::nix_cmd::NixSettings;
+    use super::super::transcript::{Transcript, create_stdout_record, write_entry};
+    use super::*;
+
+    fn record(dir: &std::path::Path, args: &[&str], stdout: &str) {
+        create_stdout_record(dir, args)
+            .unwrap()
+            .write_all(stdout.as_bytes())
+            .unwrap();
+        write_entry(dir, args, true, "").unwrap();
+    }
+
+    #[test]
+    fn streams_each_shard_of_the_listed_store() {
+        let dir = std::env::temp_dir().join(format!("nix-analisa-shards-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path =
+            |hash: char, name: &str| format!("/nix/store/{}-{name}", hash.to_string().repeat(32));
+        let (a, b, c) = (path('0', "a"), path('1', "b"), path('0', "c"));
+        let json = |paths: &[&str]| {
+            let entries = paths
+                .iter()
+                .map(|path| format!(r#""{path}": {{"narSize": 1, "references": ["{a}"]}}"#))
+                .collect::<Vec<_>>();
+            format!("{{{}}}", entries.join(","))
+        };
+        record(
+            &dir,
+            &["path-info", "--json", "--json-format", "2", &c],
+            &json(&[&c]),
+        );
+        record(&dir, &["path-info", "--all"], &format!("{a}\n{b}\n{c}\n"));
+        record(
+            &dir,
+            &["path-info", "--json", "--json-format", "2", &a, &c],
+            &json(&[&a, &c]),
+        );
+        record(
+            &dir,
+            &["path-info", "--json", "--json-format", "2", &b],
+            &json(&[&b]),
+        );
+        let control = NixControl::new(&NixSettings {
+            transcript: Some(Transcript::Replay(dir.clone())),
+            ..NixSettings::default()
+        });
+
+        let mut shards = Vec::new();
+        stream_store_shards(&control, &c, |shard| shards.push(shard)).unwrap();
+        let mut ids = shards
+            .iter()
+            .map(|shard| {
+                let mut ids = shard
+                    .added
+                    .iter()
+                    .map(|node| short_id(&node.id))
+                    .collect::<Vec<_>>();
+                ids.sort();
+                ids
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(ids.remove(0), ["a", "c"]);
+        assert_eq!(ids.remove(0), ["b"]);
+        assert!(ids.is_empty());
+
+        let graph = collect_store_shards(&control, &c).unwrap();
+        assert_eq!(short_id(&graph.root_id), "c");
+        assert_eq!(graph.node_count(), 3);
+        assert_eq!(graph.edge_count, 2);
+        assert!(
+            graph
+                .nodes
+                .values()
+                .all(|node| node.closure_size == node.nar_size && node.closure_count == 1)
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    fn short_id(id: &str) -> &str {
+        id.rsplit('-').next().unwrap()
+    }
+
+    #[test]
+    fn previews_link_references_across_shards() {
+        // b and c reference paths from later shards; d closes a cycle.
+        let nodes = [
+            test_node("a", 1, &[]),
+            test_node("b", 2, &["a", "d"]),
+            test_node("c", 4, &["d", "e"]),
+            test_node("d", 8, &["a", "b"]),
+            test_node("e", 16, &[]),
+        ];
+        let shard = |ids: &[&str]| GraphDelta {
+            root_id: "c".to_owned(),
+            added: nodes
+                .iter()
+                .filter(|(id, _)| ids.contains(&id.as_str()))
+                .map(|(_, node)| node.clone())
+                .collect(),
+            removed: Vec::new(),
+        };
+
+        let mut preview = ShardPreview::default();
+        let mut graph = preview.start(shard(&["a", "b"]));
+        assert_eq!(graph.root_id, "a");
+        assert_eq!(graph.nodes["b"].references, ["a"]);
+        preview.merge(&mut graph, shard(&["c", "d"]));
+        assert_eq!(graph.root_id, "c");
+        preview.merge(&mut graph, shard(&["e"]));
+
+        let finished = finish_graph(
+            nodes.into_iter().collect(),
+            "c".to_owned(),
+            "/nix/store".to_owned(),
+            &NixControl::default(),
+        );
+        assert_eq!(graph.edge_count, finished.edge_count);
+        for (id, node) in &finished.nodes {
+            assert_eq!(graph.nodes[id].references, node.references, "{id}");
+            assert_eq!(graph.nodes[id].referrers, node.referrers, "{id}");
+        }
+    }
+
+    #[test]
+    fn shards_follow_the_first_hash_character() {
+        assert_eq!(shard_index("/nix/store/0abc-hello-2.12"), 0);
+        assert_eq!(shard_index("zabc-hello-2.12"), 31);
+        assert_eq!(shard_index("/nix/store/fabc-hello-2.12"), 14);
+        assert_eq!(shard_index("/nix/store/eabc-not-base32"), 0);
+    }
+}
@@ -0,0 +1,177 @@
+//! End-to-end checks of collection against a throwaway chroot store, built
+//! with the `nix-store` found in `PATH`. Enabled by the `nix-smoke-test`
+//! feature, for CI machines that have Nix.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use super::collect::collect_system_graph;
+use super::diff::{PathChange, diff_closures};
+use super::graph::{SizeMetric, SystemGraph};
+use super::nix_cmd::{NixControl, NixSettings};
+use crate::util::short_name;
+
+const KIB: usize = 1024;
+
+/// A chroot store under the temporary directory, removed when dropped.
+struct FixtureStore {
+    root: PathBuf,
+    next_hash: usize,
+}
+
+impl FixtureStore {
+    fn new(name: &str) -> Self {
+        let root =
+            std::env::temp_dir().join(format!("nix-analisa-smoke-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        Self { root, next_hash: 0 }
+    }
+
+    fn nix_store(&self, args: &[&str], stdin: &str) -> String {
+        let mut child = Command::new("nix-store")
+            .arg("--store")
+            .arg(&self.root)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("nix-store is on PATH");
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(stdin.as_bytes())
+            .unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success(), "nix-store {args:?} failed");
+        String::from_utf8(output.stdout).unwrap().trim().to_owned()
+    }
+
+    /// Adds a file of `size` bytes with `nix-store --add`; returns its id.
+    fn add_leaf(&self, name: &str, size: usize) -> String {
+        let staging = self.root.join("staging");
+        fs::create_dir_all(&staging).unwrap();
+        let file = staging.join(name);
+        fs::write(&file, vec![b'x'; size]).unwrap();
+        let path = self.nix_store(&["--add", file.to_str().unwrap()], "");
+        short_name(&path).to_owned()
+    }
+
+    /// Writes a path that mentions each of `references` and registers it
+    /// with them, which `nix-store --add` cannot do; returns its id.
+    fn add_referrer(&mut self, name: &str, size: usize, references: &[&str]) -> String {
+        self.next_hash += 1;
+        let id = format!("{:032}-{name}", self.next_hash);
+        let mut contents = vec![b'x'; size];
+        for reference in references {
+            contents.extend(format!("\n/nix/store/{reference}").bytes());
+        }
+        fs::create_dir_all(self.physical("")).unwrap();
+        fs::write(self.physical(&id), contents).unwrap();
+
+        let mut registration = format!("/nix/store/{id}\n\n{}\n", references.len());
+        for reference in references {
+            registration.push_str(&format!("/nix/store/{reference}\n"));
+        }
+        self.nix_store(&["--register-validity"], &registration);
+        id
+    }
+
+    fn physical(&self, id: &str) -> PathBuf {
+        self.root.join("nix/store").join(id)
+    }
+
+    fn collect(&self, id: &str) -> SystemGraph {
+        let settings = NixSettings {
+            extra_args: vec![
+                "--extra-experimental-features".to_owned(),
+                "nix-command".to_owned(),
+            ],
+            ..NixSettings::default()
+        };
+        let control = NixControl::new(&settings);
+        collect_system_graph(&control, path_str(&self.physical(id))).unwrap()
+    }
+}
+
+impl Drop for FixtureStore {
+    fn drop(&mut self) {
+        // Store paths are read-only once registered.
+        let _ = Command::new("chmod")
+            .arg("-R")
+            .arg("u+w")
+            .arg(&self.root)
+            .status();
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+fn path_str(path: &Path) -> &str {
+    path.to_str().unwrap()
+}
+
+fn names(ids: &[String]) -> Vec<&str> {
+    ids.iter()
+        .map(|id| id.split_once('-').map_or(id.as_str(), |(_, name)| name))
+        .collect()
+}
+
+#[test]
+fn collects_ranks_and_diffs_a_fixture_store() {
+    let mut store = FixtureStore::new("pipeline");
+    let libc = store.add_leaf("libc-2.39", 64 * KIB);
+    let zlib = store.add_leaf("zlib-1.3", 30 * KIB);
+    let app_v1 = store.add_referrer("app-1.0", 40 * KIB, &[&libc, &zlib]);
+    let tool = store.add_referrer("tool-0.5", 10 * KIB, &[&libc]);
+    let system_v1 = store.add_referrer("system-1", KIB, &[&app_v1, &tool]);
+    let app_v2 = store.add_referrer("app-2.0", 40 * KIB, &[&libc]);
+    let system_v2 = store.add_referrer("system-2", KIB, &[&app_v2, &tool]);
+
+    let base = store.collect(&system_v1);
+    assert_eq!(base.root_id, system_v1);
+    assert_eq!(base.node_count(), 5);
+    assert_eq!(base.edge_count, 5);
+    assert!(base.integrity_errors().is_empty());
+    assert_eq!(
+        base.store_dir,
+        format!("{}/nix/store", path_str(&store.root))
+    );
+    let mut references = base.nodes[&app_v1].references.clone();
+    references.sort();
+    let mut expected = vec![libc.clone(), zlib.clone()];
+    expected.sort();
+    assert_eq!(references, expected);
+
+    // libc is shared by app and tool, so only the root retains it.
+    let ranked = base.ranked_by_metric(SizeMetric::RetainedSize, 5);
+    assert_eq!(
+        names(&ranked),
+        ["system-1", "app-1.0", "libc-2.39", "zlib-1.3", "tool-0.5"]
+    );
+    assert_eq!(
+        base.nodes[&libc].immediate_dominator.as_ref(),
+        Some(&system_v1)
+    );
+    assert_eq!(
+        names(&base.shortest_path_from_root(&zlib).unwrap()),
+        ["system-1", "app-1.0", "zlib-1.3"]
+    );
+
+    let current = store.collect(&system_v2);
+    assert_eq!(current.node_count(), 4);
+    let diff = diff_closures(&base, &current, &NixControl::default());
+    assert_eq!(diff.count(|change| *change == PathChange::Added), 0);
+    assert_eq!(
+        diff.entry(&app_v2).map(|entry| &entry.change),
+        Some(&PathChange::Changed(app_v1.clone()))
+    );
+    assert_eq!(
+        diff.entry(&zlib).map(|entry| &entry.change),
+        Some(&PathChange::Removed)
+    );
+    assert!(diff.entry(&tool).is_none());
+    assert!(diff.root_closure_delta < 0);
+}
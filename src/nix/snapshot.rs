@@ -0,0 +1,198 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use super::closure::fill_closure_sizes;
+use super::collect::{finish_graph, finish_sized_graph, node_record};
+use super::graph::SystemGraph;
+use super::nix_cmd::NixControl;
+use super::parse::parse_path_info_reader;
+
+const GRAPH_FORMAT: &str = "nix-analisa-graph";
+
+/// Bumped whenever saved graphs change shape; older versions are still read,
+/// newer ones are rejected.
+pub const GRAPH_FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct EnvelopeRef<'a> {
+    format: &'static str,
+    version: u32,
+    graph: &'a SystemGraph,
+}
+
+/// Just enough of a JSON document to tell saved graphs from other dumps.
+#[derive(Deserialize)]
+struct FormatProbe {
+    format: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct Envelope {
+    format: String,
+    version: u32,
+    graph: SystemGraph,
+}
+
+pub fn write_graph(graph: &SystemGraph, writer: impl Write) -> Result<()> {
+    let envelope = EnvelopeRef {
+        format: GRAPH_FORMAT,
+        version: GRAPH_FORMAT_VERSION,
+        graph,
+    };
+    serde_json::to_writer(writer, &envelope).context("failed to encode graph")
+}
+
+/// Reads a graph written by [`write_graph`], re-linking referrers and
+/// recomputing retained sizes.
+pub fn read_graph(reader: impl Read) -> Result<SystemGraph> {
+    let envelope: Envelope = serde_json::from_reader(reader).context("failed to decode graph")?;
+    if envelope.format != GRAPH_FORMAT {
+        return Err(anyhow!("not a saved graph (format {:?})", envelope.format));
+    }
+    if envelope.version > GRAPH_FORMAT_VERSION {
+        return Err(anyhow!(
+            "graph format version {} is newer than the supported version {GRAPH_FORMAT_VERSION}",
+            envelope.version
+        ));
+    }
+
+    let saved = envelope.graph;
+    if !saved.nodes.contains_key(&saved.root_id) {
+        return Err(anyhow!("saved graph root {} is not a node", saved.root_id));
+    }
+    let mut graph = finish_graph(
+        saved.nodes,
+        saved.root_id,
+        saved.store_dir,
+        &NixControl::default(),
+    );
+    graph.limitations = saved.limitations;
+    graph.edge_weights = saved.edge_weights;
+    graph.set_roots(saved.roots);
+    Ok(graph)
+}
+
+pub fn save_graph(graph: &SystemGraph, path: &Path) -> Result<()> {
+    let file =
+        File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+    let mut writer = BufWriter::new(file);
+    write_graph(graph, &mut writer)?;
+    writer
+        .flush()
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+pub fn load_graph(path: &Path) -> Result<SystemGraph> {
+    let file = File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    read_graph(BufReader::new(file)).with_context(|| format!("failed to load {}", path.display()))
+}
+
+/// Reads the output of `nix path-info --recursive --json`, rooted at the
+/// path nothing else references that has the largest closure.
+pub fn read_path_info_dump(reader: impl Read) -> Result<SystemGraph> {
+    let (store_dir, info) = parse_path_info_reader(reader)?;
+    let mut nodes = info
+        .into_iter()
+        .filter_map(|(raw_key, raw_entry)| node_record(raw_key, raw_entry, &store_dir))
+        .map(|record| (record.id.clone(), record))
+        .collect::<HashMap<_, _>>();
+    // Dumps made without --closure-size only carry NAR sizes.
+    fill_closure_sizes(&mut nodes);
+
+    let referenced = nodes
+        .values()
+        .flat_map(|node| &node.references)
+        .collect::<HashSet<_>>();
+    let root_id = nodes
+        .values()
+        .filter(|node| !referenced.contains(&node.id))
+        .max_by(|a, b| a.closure_size.cmp(&b.closure_size).then(b.id.cmp(&a.id)))
+        .or_else(|| nodes.values().max_by_key(|node| node.closure_size))
+        .map(|node| node.id.clone())
+        .ok_or_else(|| anyhow!("path-info dump has no store paths"))?;
+    Ok(finish_sized_graph(
+        nodes,
+        root_id,
+        store_dir,
+        &NixControl::default(),
+    ))
+}
+
+/// Loads either a saved graph or a `nix path-info --recursive --json` dump,
+/// e.g. one copied over from another machine.
+pub fn load_closure_dump(path: &Path) -> Result<SystemGraph> {
+    let raw = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let probe: FormatProbe = serde_json::from_slice(&raw)
+        .with_context(|| format!("{} is not valid JSON", path.display()))?;
+    if probe.format.is_some() {
+        read_graph(raw.as_slice())
+    } else {
+        read_path_info_dump(raw.as_slice())
+    }
+    .with_context(|| format!("failed to load {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nix::generate_demo_graph;
+
+    #[test]
+    fn round_trips_through_the_envelope() {
+        let mut graph = generate_demo_graph(250);
+        graph.limitations.push("partial".to_owned());
+
+        let mut encoded = Vec::new();
+        write_graph(&graph, &mut encoded).unwrap();
+        let decoded = read_graph(encoded.as_slice()).unwrap();
+
+        assert!(decoded.integrity_errors().is_empty());
+        assert_eq!(decoded.root_id, graph.root_id);
+        assert_eq!(decoded.edge_count, graph.edge_count);
+        assert_eq!(decoded.limitations, graph.limitations);
+        for (id, node) in &graph.nodes {
+            let restored = &decoded.nodes[id];
+            assert_eq!(restored.classes, node.classes);
+            assert_eq!(restored.retained_size, node.retained_size);
+            assert_eq!(restored.referrers.len(), node.referrers.len());
+        }
+    }
+
+    #[test]
+    fn roots_path_info_dumps_at_the_largest_unreferenced_closure() {
+        let dump = serde_json::json!({
+            "/nix/store/aaaa-system": {
+                "narSize": 10,
+                "closureSize": 60,
+                "references": ["/nix/store/bbbb-lib"]
+            },
+            "/nix/store/bbbb-lib": { "narSize": 50, "closureSize": 50, "references": [] },
+            "/nix/store/cccc-stray": { "narSize": 5, "closureSize": 5, "references": [] }
+        });
+
+        let graph = read_path_info_dump(dump.to_string().as_bytes()).unwrap();
+        assert_eq!(graph.root_id, "aaaa-system");
+        assert_eq!(graph.node_count(), 3);
+        assert_eq!(graph.nodes["bbbb-lib"].referrers, ["aaaa-system"]);
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let graph = generate_demo_graph(20);
+        let mut encoded = serde_json::to_value(EnvelopeRef {
+            format: GRAPH_FORMAT,
+            version: GRAPH_FORMAT_VERSION,
+            graph: &graph,
+        })
+        .unwrap();
+        encoded["version"] = (GRAPH_FORMAT_VERSION + 1).into();
+
+        let error = read_graph(encoded.to_string().as_bytes()).unwrap_err();
+        assert!(error.to_string().contains("newer"));
+    }
+}
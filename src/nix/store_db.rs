@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow};
+use rusqlite::{Connection, OpenFlags};
+use serde_json::Value;
+
+use super::classify::classify_elf_paths;
+use super::collect::{finish_graph, node_record};
+use super::graph::{NodeRecord, SystemGraph};
+use super::nix_cmd::NixControl;
+use super::parse::RawPathInfo;
+use super::watch::NIX_DB_PATH;
+
+/// Where the local store keeps its database.
+pub const DEFAULT_STORE_DB: &str = NIX_DB_PATH;
+
+/// The store directory paths in the database are recorded under.
+const DATABASE_STORE_DIR: &str = "/nix/store";
+
+/// Every path reachable from `?1` through the Refs table.
+const CLOSURE_CTE: &str = "WITH RECURSIVE closure(id) AS ( \
+         SELECT id FROM ValidPaths WHERE path = ?1 \
+         UNION SELECT Refs.reference FROM Refs JOIN closure ON Refs.referrer = closure.id)";
+
+/// Opens the store database without writing to it. A read-only connection
+/// sees transactions still in the write-ahead log but needs the log's shared
+/// memory file; when that is off limits, the database is opened as
+/// immutable, which takes no locks and reads only what was checkpointed.
+fn open_database(database: &Path) -> Result<Connection> {
+    let flags = OpenFlags::SQLITE_OPEN_READ_ONLY
+        | OpenFlags::SQLITE_OPEN_URI
+        | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+    let mut last_error = None;
+    for mode in ["mode=ro", "immutable=1"] {
+        let uri = format!("file:{}?{mode}", database.display());
+        let probed = Connection::open_with_flags(&uri, flags).and_then(|connection| {
+            connection.query_row("SELECT count(*) FROM ValidPaths WHERE id = 0", [], |_| {
+                Ok(())
+            })?;
+            Ok(connection)
+        });
+        match probed {
+            Ok(connection) => return Ok(connection),
+            Err(error) => last_error = Some(error),
+        }
+    }
+    Err(anyhow!(
+        "failed to open {}: {}",
+        database.display(),
+        last_error
+            .map(|error| error.to_string())
+            .unwrap_or_default()
+    ))
+}
+
+/// Collects the closure of `system_path` from the store database at
+/// `database` without spawning nix. The database only records each path's
+/// own NAR size; closure sizes are summed up as the graph is finished.
+pub(super) fn collect_from_store_db(
+    control: &NixControl,
+    database: &Path,
+    system_path: &str,
+) -> Result<SystemGraph> {
+    let root = fs::canonicalize(system_path)
+        .with_context(|| format!("failed to resolve {system_path}"))?;
+    // A file inside a store path stands for the path that contains it.
+    let root_id = root
+        .strip_prefix(DATABASE_STORE_DIR)
+        .ok()
+        .and_then(|inside| inside.iter().next())
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| anyhow!("{system_path} is not in {DATABASE_STORE_DIR}"))?;
+    let root_path = format!("{DATABASE_STORE_DIR}/{root_id}");
+
+    control.report(format!(
+        "reading the closure of {root_id} from {}",
+        database.display()
+    ));
+    let connection = open_database(database)?;
+    let mut nodes = read_closure(&connection, &root_path)
+        .with_context(|| format!("failed to read the closure from {}", database.display()))?;
+    classify_elf_paths(control, &[], &mut nodes);
+    if nodes.is_empty() {
+        return Err(anyhow!(
+            "{root_path} is not a valid path in {}",
+            database.display()
+        ));
+    }
+    if control.is_cancelled() {
+        return Err(anyhow!("store database read cancelled"));
+    }
+    control.count_paths(nodes.len(), nodes.len());
+
+    control.report(format!(
+        "computing closure sizes, dominators and retained sizes for {} paths",
+        nodes.len()
+    ));
+    Ok(finish_graph(
+        nodes,
+        root_id,
+        DATABASE_STORE_DIR.to_owned(),
+        control,
+    ))
+}
+
+/// Reads the ValidPaths rows of every path in the closure of `root_path`,
+/// with their references from the Refs table.
+fn read_closure(connection: &Connection, root_path: &str) -> Result<HashMap<String, NodeRecord>> {
+    let mut references = HashMap::<i64, Vec<String>>::new();
+    let mut statement = connection.prepare(&format!(
+        "{CLOSURE_CTE} SELECT Refs.referrer, p.path FROM closure \
+         JOIN Refs ON Refs.referrer = closure.id JOIN ValidPaths p ON p.id = Refs.reference"
+    ))?;
+    let rows = statement.query_map([root_path], |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+    })?;
+    for row in rows {
+        let (referrer, reference) = row?;
+        references.entry(referrer).or_default().push(reference);
+    }
+
+    let mut nodes = HashMap::new();
+    let mut statement = connection.prepare(&format!(
+        "{CLOSURE_CTE} SELECT v.id, v.path, v.narSize, v.deriver, v.ca, v.sigs, \
+         v.registrationTime FROM ValidPaths v JOIN closure ON v.id = closure.id"
+    ))?;
+    let mut rows = statement.query([root_path])?;
+    while let Some(row) = rows.next()? {
+        let id: i64 = row.get(0)?;
+        let path: String = row.get(1)?;
+        let text = |index| -> rusqlite::Result<Option<String>> {
+            Ok(row
+                .get::<_, Option<String>>(index)?
+                .filter(|field| !field.is_empty()))
+        };
+        let raw_entry = RawPathInfo {
+            nar_size: row.get::<_, Option<i64>>(2)?.unwrap_or(0).max(0) as u64,
+            closure_size: 0,
+            references: references.remove(&id).unwrap_or_default(),
+            deriver: text(3)?,
+            ca: text(4)?.map(Value::String),
+            signatures: text(5)?
+                .map(|sigs| {
+                    sigs.split_whitespace()
+                        .map(|sig| Value::String(sig.to_owned()))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            registration_time: row
+                .get::<_, Option<i64>>(6)?
+                .and_then(|time| u64::try_from(time).ok()),
+        };
+        if let Some(node) = node_record(path, raw_entry, DATABASE_STORE_DIR) {
+            nodes.insert(node.id.clone(), node);
+        }
+    }
+    Ok(nodes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::closure::fill_closure_sizes;
+    use super::*;
+
+    #[test]
+    fn reads_closures_and_computes_their_sizes() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE ValidPaths (id integer primary key autoincrement not null, \
+                     path text unique not null, hash text not null, registrationTime integer not null, \
+                     deriver text, narSize integer, ultimate integer, sigs text, ca text);
+                 CREATE TABLE Refs (referrer integer not null, reference integer not null, \
+                     primary key (referrer, reference));
+                 INSERT INTO ValidPaths (id, path, hash, registrationTime, deriver, narSize, sigs, ca) VALUES
+                     (1, '/nix/store/aaaa-system', 'sha256:a', 1, NULL, 100, NULL, NULL),
+                     (2, '/nix/store/bbbb-bash-5.2', 'sha256:b', 1, '/nix/store/dddd-bash-5.2.drv', 20, 'cache:sig', NULL),
+                     (3, '/nix/store/cccc-glibc-2.39', 'sha256:c', 1, NULL, 5, NULL, 'fixed:r:sha256:abc'),
+                     (4, '/nix/store/eeee-unrelated', 'sha256:e', 1, NULL, 7, NULL, NULL);
+                 INSERT INTO Refs VALUES (1, 2), (1, 3), (2, 3), (3, 3), (4, 3);",
+            )
+            .unwrap();
+
+        let mut nodes = read_closure(&connection, "/nix/store/aaaa-system").unwrap();
+        fill_closure_sizes(&mut nodes);
+
+        assert_eq!(nodes.len(), 3);
+        assert_eq!(nodes["aaaa-system"].closure_size, 125);
+        assert_eq!(nodes["bbbb-bash-5.2"].closure_size, 25);
+        assert_eq!(nodes["cccc-glibc-2.39"].closure_size, 5);
+        assert_eq!(
+            nodes["bbbb-bash-5.2"].deriver.as_deref(),
+            Some("dddd-bash-5.2.drv")
+        );
+        let mut references = nodes["aaaa-system"].references.clone();
+        references.sort();
+        assert_eq!(references, vec!["bbbb-bash-5.2", "cccc-glibc-2.39"]);
+        assert!(nodes["cccc-glibc-2.39"].references.is_empty());
+        assert!(
+            read_closure(&connection, "/nix/store/ffff-missing")
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
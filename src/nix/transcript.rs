@@ -0,0 +1,161 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+/// Where nix invocations are recorded to or replayed from. Each invocation is
+/// stored as `<key>.json` metadata plus `<key>.stdout`, keyed by its arguments.
+#[derive(Clone, Debug)]
+pub enum Transcript {
+    Record(PathBuf),
+    Replay(PathBuf),
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct TranscriptEntry {
+    pub(super) args: Vec<String>,
+    pub(super) success: bool,
+    pub(super) stderr: String,
+}
+
+pub(super) struct RecordedInvocation {
+    pub(super) entry: TranscriptEntry,
+    pub(super) stdout: File,
+}
+
+fn transcript_key(args: &[&str]) -> String {
+    // FNV-1a, so keys stay stable across Rust releases.
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+    for byte in args.join("\0").bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    let command = args.iter().take(2).copied().collect::<Vec<_>>().join("-");
+    format!("{command}-{hash:016x}")
+}
+
+fn entry_paths(dir: &Path, args: &[&str]) -> (PathBuf, PathBuf) {
+    let key = transcript_key(args);
+    (
+        dir.join(format!("{key}.json")),
+        dir.join(format!("{key}.stdout")),
+    )
+}
+
+pub(super) fn create_stdout_record(dir: &Path, args: &[&str]) -> Result<File> {
+    fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    let (_, stdout_path) = entry_paths(dir, args);
+    File::create(&stdout_path)
+        .with_context(|| format!("failed to create {}", stdout_path.display()))
+}
+
+pub(super) fn write_entry(dir: &Path, args: &[&str], success: bool, stderr: &str) -> Result<()> {
+    let (entry_path, _) = entry_paths(dir, args);
+    let entry = TranscriptEntry {
+        args: args.iter().map(|arg| (*arg).to_owned()).collect(),
+        success,
+        stderr: stderr.to_owned(),
+    };
+    let raw = serde_json::to_string_pretty(&entry).context("failed to encode transcript entry")?;
+    fs::write(&entry_path, raw).with_context(|| format!("failed to write {}", entry_path.display()))
+}
+
+pub(super) fn read_entry(dir: &Path, args: &[&str]) -> Result<RecordedInvocation> {
+    let (entry_path, stdout_path) = entry_paths(dir, args);
+    let raw = fs::read_to_string(&entry_path).with_context(|| {
+        format!(
+            "no recorded nix invocation for args {args:?} in {}",
+            dir.display()
+        )
+    })?;
+    let entry: TranscriptEntry = serde_json::from_str(&raw)
+        .with_context(|| format!("invalid transcript entry {}", entry_path.display()))?;
+    if entry.args != args {
+        return Err(anyhow!(
+            "transcript entry {} was recorded for args {:?}",
+            entry_path.display(),
+            entry.args
+        ));
+    }
+
+    let stdout = File::open(&stdout_path)
+        .with_context(|| format!("failed to open {}", stdout_path.display()))?;
+    Ok(RecordedInvocation { entry, stdout })
+}
+
+/// Copies everything read through it into a transcript file.
+pub(super) struct TeeReader<R> {
+    inner: R,
+    record: File,
+}
+
+impl<R> TeeReader<R> {
+    pub(super) fn new(inner: R, record: File) -> Self {
+        Self { inner, record }
+    }
+}
+
+impl<R: Read> Read for TeeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.record.write_all(&buf[..read])?;
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::super::nix_cmd::{NixControl, NixSettings, run_nix};
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "nix-analisa-transcript-{name}-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn replay_control(dir: &Path) -> NixControl {
+        NixControl::new(&NixSettings {
+            transcript: Some(Transcript::Replay(dir.to_owned())),
+            ..NixSettings::default()
+        })
+    }
+
+    #[test]
+    fn replays_recorded_output() {
+        let dir = scratch_dir("output");
+        let args = ["path-info", "--json", "/run/current-system"];
+        let mut stdout = create_stdout_record(&dir, &args).unwrap();
+        stdout.write_all(b"{\"info\":{}}").unwrap();
+        write_entry(&dir, &args, true, "").unwrap();
+
+        let output = run_nix(&replay_control(&dir), &args).unwrap();
+        assert_eq!(output, "{\"info\":{}}");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn replays_recorded_failure() {
+        let dir = scratch_dir("failure");
+        let args = ["store", "verify", "/nix/store/missing"];
+        create_stdout_record(&dir, &args).unwrap();
+        write_entry(&dir, &args, false, "error: path is not valid\n").unwrap();
+
+        let error = run_nix(&replay_control(&dir), &args).unwrap_err();
+        assert!(error.to_string().contains("path is not valid"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn missing_invocation_is_an_error() {
+        let dir = scratch_dir("missing");
+        assert!(run_nix(&replay_control(&dir), &["path-info", "--json"]).is_err());
+    }
+}
@@ -0,0 +1,46 @@
+use std::io;
+
+use anyhow::{Context, Result};
+
+use super::nix_cmd::{NixControl, run_nix, stream_nix};
+
+#[derive(Clone, Debug)]
+pub struct VerifyOutcome {
+    pub recorded_nar_size: u64,
+    pub recomputed_nar_size: u64,
+    pub contents_valid: bool,
+    pub message: Option<String>,
+}
+
+impl VerifyOutcome {
+    pub fn is_mismatch(&self) -> bool {
+        !self.contents_valid || self.recorded_nar_size != self.recomputed_nar_size
+    }
+}
+
+pub fn verify_store_path(
+    control: &NixControl,
+    full_path: &str,
+    recorded_nar_size: u64,
+) -> Result<VerifyOutcome> {
+    let recomputed_nar_size = dumped_nar_size(control, full_path)?;
+    let (contents_valid, message) =
+        match run_nix(control, &["store", "verify", "--no-trust", full_path]) {
+            Ok(_) => (true, None),
+            Err(error) => (false, Some(error.to_string())),
+        };
+
+    Ok(VerifyOutcome {
+        recorded_nar_size,
+        recomputed_nar_size,
+        contents_valid,
+        message,
+    })
+}
+
+fn dumped_nar_size(control: &NixControl, full_path: &str) -> Result<u64> {
+    stream_nix(control, &["store", "dump-path", full_path], |stdout| {
+        io::copy(stdout, &mut io::sink())
+            .with_context(|| format!("failed to read NAR dump of {full_path}"))
+    })
+}
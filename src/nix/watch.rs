@@ -0,0 +1,270 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use super::collect::collect_closure_delta;
+use super::daemon::DaemonConnection;
+use super::graph::{GraphDelta, SystemGraph};
+use super::nix_cmd::{NixControl, NixSettings};
+
+pub(super) const NIX_DB_PATH: &str = "/nix/var/nix/db/db.sqlite";
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+/// When the store database was last written. Nix keeps it in WAL mode,
+/// where writes land in the `-wal` file until a checkpoint, so that file's
+/// size and modification time count as well.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct StoreFingerprint {
+    db_modified: Option<SystemTime>,
+    wal: Option<(u64, SystemTime)>,
+}
+
+impl StoreFingerprint {
+    fn read() -> Self {
+        Self {
+            db_modified: fs::metadata(NIX_DB_PATH)
+                .and_then(|metadata| metadata.modified())
+                .ok(),
+            wal: fs::metadata(format!("{NIX_DB_PATH}-wal"))
+                .and_then(|metadata| Ok((metadata.len(), metadata.modified()?)))
+                .ok(),
+        }
+    }
+}
+
+/// Where a profile link such as `/run/current-system` resolved at the last
+/// poll.
+struct LinkTarget {
+    system_path: String,
+    last: Option<PathBuf>,
+}
+
+impl LinkTarget {
+    fn new(system_path: String) -> Self {
+        let last = fs::canonicalize(&system_path).ok();
+        Self { system_path, last }
+    }
+
+    fn current(&self) -> Option<&Path> {
+        self.last.as_deref()
+    }
+
+    /// The new target when the link resolves somewhere else than at the last
+    /// poll. A link being replaced can briefly fail to resolve; only a target
+    /// that resolves counts as a switch.
+    fn switched(&mut self) -> Option<PathBuf> {
+        let current = fs::canonicalize(&self.system_path).ok()?;
+        if self.last.as_ref() == Some(&current) {
+            return None;
+        }
+        self.last = Some(current.clone());
+        Some(current)
+    }
+}
+
+/// Sleeps for `interval` in short steps, returning false as soon as
+/// `stopped` does.
+fn wait_interval(interval: Duration, stopped: &impl Fn() -> bool) -> bool {
+    let mut waited = Duration::ZERO;
+    while waited < interval {
+        if stopped() {
+            return false;
+        }
+        thread::sleep(STOP_CHECK_INTERVAL);
+        waited += STOP_CHECK_INTERVAL;
+    }
+    true
+}
+
+/// Calls `poll` every `interval` on a new thread until `stopped` returns true
+/// or `poll` returns false.
+fn spawn_poller(
+    interval: Duration,
+    stopped: impl Fn() -> bool + Send + 'static,
+    mut poll: impl FnMut() -> bool + Send + 'static,
+) {
+    thread::spawn(move || {
+        while wait_interval(interval, &stopped) {
+            if !poll() {
+                return;
+            }
+        }
+    });
+}
+
+/// Whether the daemon still knows `target`: a valid store path's closure
+/// cannot change, so the database changing under it needs no reload.
+/// Without a daemon connection this is never assumed.
+fn root_still_valid(control: &NixControl, target: Option<&Path>) -> bool {
+    let Some(target) = target.and_then(Path::to_str) else {
+        return false;
+    };
+    match DaemonConnection::for_control(control) {
+        Some(Ok(mut daemon)) => daemon.is_valid_path(target).unwrap_or(false),
+        _ => false,
+    }
+}
+
+pub struct StoreWatcher {
+    /// The paths that entered and left the closure, once per store change.
+    pub rx: Receiver<Result<GraphDelta, String>>,
+    control: NixControl,
+    paused: Arc<AtomicBool>,
+}
+
+impl StoreWatcher {
+    /// Watches the closure of `system_path`, starting from the paths in
+    /// `graph`.
+    pub fn spawn(
+        system_path: String,
+        graph: &SystemGraph,
+        interval: Duration,
+        settings: &NixSettings,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let mut known = graph.nodes.keys().cloned().collect::<HashSet<_>>();
+        let control = NixControl::new(settings);
+        let worker_control = control.clone();
+        let paused = Arc::new(AtomicBool::new(false));
+        let worker_paused = Arc::clone(&paused);
+
+        let mut target = LinkTarget::new(system_path.clone());
+        let mut last = StoreFingerprint::read();
+        let mut retry_switch = false;
+        let stop_control = control.clone();
+        spawn_poller(
+            interval,
+            move || stop_control.is_cancelled(),
+            move || {
+                if worker_paused.load(Ordering::Relaxed) {
+                    return true;
+                }
+
+                // A switch whose delta failed stays pending so the next
+                // poll retries it even if the store has settled since.
+                let switched = target.switched().is_some() || retry_switch;
+                let current = StoreFingerprint::read();
+                if !switched && current == last {
+                    return true;
+                }
+                if !switched && root_still_valid(&worker_control, target.current()) {
+                    last = current;
+                    return true;
+                }
+
+                let delta = match collect_closure_delta(&worker_control, &system_path, &known) {
+                    Ok(delta) => delta,
+                    Err(error) => {
+                        retry_switch = switched;
+                        return tx.send(Err(error.to_string())).is_ok();
+                    }
+                };
+                retry_switch = false;
+                if delta.is_empty() {
+                    last = current;
+                    return true;
+                }
+                for id in &delta.removed {
+                    known.remove(id);
+                }
+                known.extend(delta.added.iter().map(|node| node.id.clone()));
+                last = current;
+                tx.send(Ok(delta)).is_ok()
+            },
+        );
+
+        Self {
+            rx,
+            control,
+            paused,
+        }
+    }
+
+    /// Skips store polling until resumed; changes made meanwhile are picked up
+    /// on the first poll after resuming.
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+}
+
+impl Drop for StoreWatcher {
+    fn drop(&mut self) {
+        self.control.cancel();
+    }
+}
+
+/// Follows where a profile symlink such as `/run/current-system` points,
+/// without touching the store: switching generations swaps the link, so its
+/// target changing is the whole signal.
+pub struct ProfileWatcher {
+    /// Each new target of the link, once per switch.
+    pub rx: Receiver<PathBuf>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl ProfileWatcher {
+    pub fn spawn(system_path: String, interval: Duration) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let stopped = Arc::new(AtomicBool::new(false));
+        let worker_stopped = Arc::clone(&stopped);
+
+        let mut target = LinkTarget::new(system_path);
+        spawn_poller(
+            interval,
+            move || worker_stopped.load(Ordering::Relaxed),
+            move || match target.switched() {
+                Some(current) => tx.send(current).is_ok(),
+                None => true,
+            },
+        );
+
+        Self { rx, stopped }
+    }
+}
+
+impl Drop for ProfileWatcher {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn link_targets_report_each_switch_once() {
+        let dir = std::env::temp_dir().join(format!("nix-analisa-watch-{}", std::process::id()));
+        fs::create_dir_all(dir.join("system-1")).unwrap();
+        fs::create_dir_all(dir.join("system-2")).unwrap();
+        let link = dir.join("current-system");
+        std::os::unix::fs::symlink(dir.join("system-1"), &link).unwrap();
+        let switched = fs::canonicalize(dir.join("system-2")).unwrap();
+
+        let mut target = LinkTarget::new(link.to_str().unwrap().to_owned());
+        let unchanged = target.switched();
+        fs::remove_file(&link).unwrap();
+        let dangling = target.switched();
+        std::os::unix::fs::symlink(dir.join("system-2"), &link).unwrap();
+        let first = target.switched();
+        let second = target.switched();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(unchanged, None);
+        assert_eq!(dangling, None);
+        assert_eq!(first, Some(switched.clone()));
+        assert_eq!(second, None);
+        assert_eq!(target.current(), Some(switched.as_path()));
+    }
+
+    #[test]
+    fn waiting_stops_as_soon_as_asked() {
+        assert!(!wait_interval(Duration::from_secs(3600), &|| true));
+        assert!(wait_interval(Duration::ZERO, &|| false));
+    }
+}
@@ -0,0 +1,649 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+
+use crate::export::{ExportFormat, export_nodes};
+use crate::format::{format_bytes, format_count};
+use crate::lockfile::ClosureLock;
+use crate::nix::{
+    NixControl, NixSettings, NodeClass, StoreWatcher, SystemGraph, collect_system_graph,
+};
+use crate::util::short_name;
+
+const MIB: u64 = 1024 * 1024;
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    #[serde(default = "default_root")]
+    pub root: String,
+    #[serde(default)]
+    pub ignore: Vec<String>,
+    #[serde(default)]
+    pub budget: Budget,
+    #[serde(default)]
+    pub selections: BTreeMap<String, Vec<String>>,
+    #[serde(default, rename = "export")]
+    pub exports: Vec<ExportTarget>,
+    #[serde(default)]
+    pub nix: NixConfig,
+    /// Expected pname-versions; every deviation counts as a violation.
+    pub lockfile: Option<PathBuf>,
+    #[serde(skip)]
+    pub lock: Option<ClosureLock>,
+    #[serde(skip)]
+    base_dir: PathBuf,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Budget {
+    pub total_mib: Option<u64>,
+    pub max_paths: Option<usize>,
+    pub max_path_mib: Option<u64>,
+    /// MiB per category: a node class such as `doc-output`, or the name of
+    /// one of the scenario's selections.
+    #[serde(default)]
+    pub categories: BTreeMap<String, u64>,
+}
+
+/// How to invoke nix on hosts where plain `nix` from `PATH` does not work.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NixConfig {
+    pub binary: Option<PathBuf>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+}
+
+impl NixConfig {
+    pub fn apply(&self, settings: &mut NixSettings) {
+        if let Some(binary) = &self.binary {
+            settings.binary = binary.clone();
+        }
+        settings.extra_args.extend(self.args.iter().cloned());
+        settings.env.extend(
+            self.env
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone())),
+        );
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportTarget {
+    pub path: PathBuf,
+    pub format: Option<ExportFormat>,
+    pub selection: Option<String>,
+}
+
+pub struct ScenarioReport {
+    pub total_bytes: u64,
+    pub path_count: usize,
+    pub categories: Vec<CategoryUsage>,
+    pub violations: Vec<String>,
+}
+
+pub struct CategoryUsage {
+    pub name: String,
+    pub bytes: u64,
+    pub budget_bytes: u64,
+}
+
+fn class_by_key(key: &str) -> Option<NodeClass> {
+    NodeClass::ALL.into_iter().find(|class| class.key() == key)
+}
+
+fn default_root() -> String {
+    "/run/current-system".to_owned()
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Self> {
+        let raw = fs::read_to_string(path)
+            .with_context(|| format!("failed to read scenario {}", path.display()))?;
+        let mut scenario: Self = toml::from_str(&raw)
+            .with_context(|| format!("invalid scenario file {}", path.display()))?;
+        scenario.base_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        if let Some(lockfile) = &scenario.lockfile {
+            scenario.lock = Some(ClosureLock::load(&scenario.resolve(lockfile))?);
+        }
+
+        for name in scenario.budget.categories.keys() {
+            if class_by_key(name).is_none() && !scenario.selections.contains_key(name) {
+                return Err(anyhow!(
+                    "budget category {name} is neither a node class nor a selection"
+                ));
+            }
+        }
+
+        for target in &scenario.exports {
+            if let Some(selection) = &target.selection
+                && !scenario.selections.contains_key(selection)
+            {
+                return Err(anyhow!(
+                    "export {} uses unknown selection {selection}",
+                    target.path.display()
+                ));
+            }
+        }
+
+        Ok(scenario)
+    }
+
+    pub fn is_ignored(&self, id: &str) -> bool {
+        let name = short_name(id);
+        self.ignore
+            .iter()
+            .any(|pattern| name.contains(pattern.as_str()))
+    }
+
+    pub fn included_ids(&self, graph: &SystemGraph) -> Vec<String> {
+        let mut ids = graph
+            .nodes
+            .keys()
+            .filter(|id| !self.is_ignored(id))
+            .cloned()
+            .collect::<Vec<_>>();
+        ids.sort_by(|a, b| {
+            graph.nodes[b]
+                .nar_size
+                .cmp(&graph.nodes[a].nar_size)
+                .then_with(|| a.cmp(b))
+        });
+        ids
+    }
+
+    pub fn selection_ids(&self, graph: &SystemGraph, name: &str) -> Vec<String> {
+        let Some(patterns) = self.selections.get(name) else {
+            return Vec::new();
+        };
+
+        self.included_ids(graph)
+            .into_iter()
+            .filter(|id| {
+                let short = short_name(id);
+                patterns
+                    .iter()
+                    .any(|pattern| short.contains(pattern.as_str()))
+            })
+            .collect()
+    }
+
+    pub fn evaluate(&self, graph: &SystemGraph) -> ScenarioReport {
+        let ids = self.included_ids(graph);
+        let total_bytes = ids.iter().map(|id| graph.nodes[id].nar_size).sum::<u64>();
+        let mut violations = Vec::new();
+
+        if let Some(limit) = self.budget.total_mib
+            && total_bytes > limit * MIB
+        {
+            violations.push(format!(
+                "closure is {} (budget {})",
+                format_bytes(total_bytes),
+                format_bytes(limit * MIB)
+            ));
+        }
+
+        if let Some(limit) = self.budget.max_paths
+            && ids.len() > limit
+        {
+            violations.push(format!("closure has {} paths (budget {limit})", ids.len()));
+        }
+
+        if let Some(limit) = self.budget.max_path_mib {
+            for id in &ids {
+                let nar_size = graph.nodes[id].nar_size;
+                if nar_size > limit * MIB {
+                    violations.push(format!(
+                        "{} is {} (per-path budget {})",
+                        short_name(id),
+                        format_bytes(nar_size),
+                        format_bytes(limit * MIB)
+                    ));
+                }
+            }
+        }
+
+        let categories = self
+            .budget
+            .categories
+            .iter()
+            .map(|(name, &limit)| {
+                let bytes = match class_by_key(name) {
+                    Some(class) => ids
+                        .iter()
+                        .map(|id| &graph.nodes[id])
+                        .filter(|node| node.classes.contains(class))
+                        .map(|node| node.nar_size)
+                        .sum(),
+                    None => self
+                        .selection_ids(graph, name)
+                        .iter()
+                        .map(|id| graph.nodes[id].nar_size)
+                        .sum(),
+                };
+                CategoryUsage {
+                    name: name.clone(),
+                    bytes,
+                    budget_bytes: limit * MIB,
+                }
+            })
+            .collect::<Vec<_>>();
+        if let Some(lock) = &self.lock {
+            violations.extend(
+                lock.check(graph)
+                    .iter()
+                    .filter(|deviation| deviation.id().is_none_or(|id| !self.is_ignored(id)))
+                    .map(|deviation| format!("lockfile: {}", deviation.summary())),
+            );
+        }
+
+        for category in &categories {
+            if category.bytes > category.budget_bytes {
+                violations.push(format!(
+                    "{} is {} (category budget {})",
+                    category.name,
+                    format_bytes(category.bytes),
+                    format_bytes(category.budget_bytes)
+                ));
+            }
+        }
+
+        ScenarioReport {
+            total_bytes,
+            path_count: ids.len(),
+            categories,
+            violations,
+        }
+    }
+
+    pub fn export_path(&self, target: &ExportTarget) -> PathBuf {
+        self.resolve(&target.path)
+    }
+
+    /// Resolves `path` relative to the scenario file.
+    pub fn resolve(&self, path: &Path) -> PathBuf {
+        if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            self.base_dir.join(path)
+        }
+    }
+
+    pub fn write_exports(&self, graph: &SystemGraph) -> Result<Vec<PathBuf>> {
+        let mut written = Vec::with_capacity(self.exports.len());
+
+        for target in &self.exports {
+            let path = self.export_path(target);
+            let format = target
+                .format
+                .or_else(|| ExportFormat::from_path(&path))
+                .ok_or_else(|| anyhow!("cannot infer export format for {}", path.display()))?;
+            let ids = match &target.selection {
+                Some(selection) => self.selection_ids(graph, selection),
+                None => self.included_ids(graph),
+            };
+
+            export_nodes(graph, &ids, format, &path)?;
+            written.push(path);
+        }
+
+        Ok(written)
+    }
+
+    pub fn run_headless(
+        &self,
+        nix_settings: &NixSettings,
+        validate: bool,
+        metrics: Option<&Path>,
+    ) -> Result<bool> {
+        let graph = collect_system_graph(&NixControl::new(nix_settings), &self.root)?;
+        let report = self.report(&graph, validate, metrics)?;
+        Ok(report.violations.is_empty())
+    }
+
+    /// Reports once, then again every time the store or the root changes,
+    /// until a budget is exceeded when `exit_on_violation` is set.
+    pub fn watch(
+        &self,
+        nix_settings: &NixSettings,
+        validate: bool,
+        metrics: Option<&Path>,
+        exit_on_violation: bool,
+    ) -> Result<bool> {
+        let mut graph = collect_system_graph(&NixControl::new(nix_settings), &self.root)?;
+        let mut report = self.report(&graph, validate, metrics)?;
+        let watcher = StoreWatcher::spawn(self.root.clone(), &graph, WATCH_INTERVAL, nix_settings);
+        loop {
+            if exit_on_violation && !report.violations.is_empty() {
+                return Ok(false);
+            }
+
+            match watcher.rx.recv() {
+                Ok(Ok(delta)) => graph.apply_delta(delta),
+                Ok(Err(error)) => {
+                    eprintln!("store update failed: {error}");
+                    continue;
+                }
+                Err(_) => return Err(anyhow!("store watcher stopped")),
+            }
+            println!();
+            let previous = report;
+            report = self.report(&graph, validate, metrics)?;
+            for violation in &report.violations {
+                if !previous.violations.contains(violation) {
+                    eprintln!("newly exceeded: {violation}");
+                }
+            }
+        }
+    }
+
+    /// Evaluates `graph`, prints the report and writes the exports and metrics.
+    fn report(
+        &self,
+        graph: &SystemGraph,
+        validate: bool,
+        metrics: Option<&Path>,
+    ) -> Result<ScenarioReport> {
+        if validate {
+            let errors = graph.integrity_errors();
+            if !errors.is_empty() {
+                return Err(anyhow!(
+                    "graph integrity check failed:\n{}",
+                    errors.join("\n")
+                ));
+            }
+        }
+        let report = self.evaluate(graph);
+
+        println!("root: {}", short_name(&graph.root_id));
+        println!(
+            "closure: {} across {} paths ({} ignored)",
+            format_bytes(report.total_bytes),
+            format_count(report.path_count as u64),
+            format_count((graph.node_count() - report.path_count) as u64)
+        );
+
+        for category in &report.categories {
+            println!(
+                "category {}: {} of {}",
+                category.name,
+                format_bytes(category.bytes),
+                format_bytes(category.budget_bytes)
+            );
+        }
+
+        for name in self.selections.keys() {
+            let ids = self.selection_ids(graph, name);
+            let bytes = ids.iter().map(|id| graph.nodes[id].nar_size).sum::<u64>();
+            println!(
+                "selection {name}: {} paths, {}",
+                format_count(ids.len() as u64),
+                format_bytes(bytes)
+            );
+        }
+
+        for path in self.write_exports(graph)? {
+            println!("wrote {}", path.display());
+        }
+        if let Some(path) = metrics {
+            write_metrics(path, &report)?;
+        }
+
+        for violation in &report.violations {
+            eprintln!("budget exceeded: {violation}");
+        }
+
+        Ok(report)
+    }
+}
+
+/// Writes `report` in the Prometheus text format, e.g. for node_exporter's
+/// textfile collector. The file is replaced atomically.
+fn write_metrics(path: &Path, report: &ScenarioReport) -> Result<()> {
+    let mut metrics = String::new();
+    let mut gauge = |name: &str, help: &str, samples: &[(String, u64)]| {
+        let _ = writeln!(metrics, "# HELP nix_analisa_{name} {help}");
+        let _ = writeln!(metrics, "# TYPE nix_analisa_{name} gauge");
+        for (labels, value) in samples {
+            let _ = writeln!(metrics, "nix_analisa_{name}{labels} {value}");
+        }
+    };
+    let category_samples = |value: fn(&CategoryUsage) -> u64| {
+        report
+            .categories
+            .iter()
+            .map(|category| {
+                (
+                    format!("{{category=\"{}\"}}", category.name.replace('"', "\\\"")),
+                    value(category),
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+
+    gauge(
+        "closure_bytes",
+        "NAR size of the closure without ignored paths.",
+        &[(String::new(), report.total_bytes)],
+    );
+    gauge(
+        "closure_paths",
+        "Paths in the closure without ignored paths.",
+        &[(String::new(), report.path_count as u64)],
+    );
+    gauge(
+        "budget_violations",
+        "Scenario budgets currently exceeded.",
+        &[(String::new(), report.violations.len() as u64)],
+    );
+    gauge(
+        "category_bytes",
+        "NAR size of a budgeted category.",
+        &category_samples(|category| category.bytes),
+    );
+    gauge(
+        "category_budget_bytes",
+        "Budget of a category.",
+        &category_samples(|category| category.budget_bytes),
+    );
+
+    let staging = path.with_extension("tmp");
+    fs::write(&staging, metrics)
+        .with_context(|| format!("failed to write {}", staging.display()))?;
+    fs::rename(&staging, path).with_context(|| format!("failed to replace {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nix::generate_demo_graph;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "nix-analisa-scenario-{name}-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn load(dir: &Path, raw: &str) -> Result<Scenario> {
+        let path = dir.join("scenario.toml");
+        fs::write(&path, raw).unwrap();
+        Scenario::load(&path)
+    }
+
+    fn parse(raw: &str) -> Scenario {
+        toml::from_str(raw).unwrap()
+    }
+
+    #[test]
+    fn loads_scenarios_and_rejects_dangling_names() {
+        let dir = scratch_dir("load");
+        let loaded = load(
+            &dir,
+            r#"
+                ignore = ["-doc"]
+                selections.core = ["glibc", "gcc"]
+
+                [budget]
+                total_mib = 512
+                categories = { core = 64, doc-output = 8 }
+
+                [[export]]
+                path = "core.csv"
+                selection = "core"
+            "#,
+        );
+        let unknown_category = load(&dir, "budget.categories.missing = 1");
+        let unknown_selection = load(&dir, "[[export]]\npath = \"a.json\"\nselection = \"x\"");
+        let unknown_field = load(&dir, "budgets = {}");
+        let _ = fs::remove_dir_all(&dir);
+
+        let scenario = loaded.unwrap();
+        assert_eq!(scenario.root, "/run/current-system");
+        assert_eq!(scenario.ignore, ["-doc"]);
+        assert_eq!(scenario.budget.total_mib, Some(512));
+        assert_eq!(scenario.budget.categories["doc-output"], 8);
+        assert_eq!(scenario.selections["core"], ["glibc", "gcc"]);
+        assert_eq!(
+            scenario.export_path(&scenario.exports[0]),
+            dir.join("core.csv")
+        );
+        assert!(
+            unknown_category
+                .unwrap_err()
+                .to_string()
+                .contains("neither a node class nor a selection")
+        );
+        assert!(
+            unknown_selection
+                .unwrap_err()
+                .to_string()
+                .contains("unknown selection x")
+        );
+        assert!(unknown_field.is_err());
+    }
+
+    #[test]
+    fn reports_every_exceeded_budget() {
+        let graph = generate_demo_graph(200);
+        let total = graph.nodes.values().map(|node| node.nar_size).sum::<u64>();
+        let scenario = parse(
+            r#"
+                selections.libc = ["glibc"]
+
+                [budget]
+                total_mib = 1
+                max_paths = 10
+                max_path_mib = 20
+                categories = { libc = 1 }
+            "#,
+        );
+
+        let report = scenario.evaluate(&graph);
+        assert_eq!(report.total_bytes, total);
+        assert_eq!(report.path_count, graph.node_count());
+        assert_eq!(report.categories.len(), 1);
+        assert_eq!(report.categories[0].budget_bytes, MIB);
+        assert!(report.categories[0].bytes >= 28_000 * 1024);
+        let violations = report.violations.join("\n");
+        assert!(violations.contains("closure is"), "{violations}");
+        assert!(violations.contains("closure has 200 paths (budget 10)"));
+        assert!(violations.contains("glibc-2.40-66 is"), "{violations}");
+        assert!(violations.contains("libc is"), "{violations}");
+
+        let generous = parse(
+            r#"
+                [budget]
+                total_mib = 100000
+                max_paths = 1000
+            "#,
+        );
+        assert!(generous.evaluate(&graph).violations.is_empty());
+    }
+
+    #[test]
+    fn ignored_paths_do_not_count_against_budgets() {
+        let graph = generate_demo_graph(200);
+        let scenario = parse(
+            r#"
+                ignore = ["glibc"]
+                budget.max_path_mib = 20
+            "#,
+        );
+
+        let glibc_paths = graph
+            .nodes
+            .keys()
+            .filter(|id| short_name(id).contains("glibc"))
+            .count();
+
+        let report = scenario.evaluate(&graph);
+        assert!(glibc_paths > 0);
+        assert_eq!(report.path_count, graph.node_count() - glibc_paths);
+        assert!(
+            report
+                .violations
+                .iter()
+                .all(|violation| !violation.contains("glibc")),
+            "{:?}",
+            report.violations
+        );
+        assert!(
+            scenario
+                .included_ids(&graph)
+                .iter()
+                .all(|id| !id.contains("glibc"))
+        );
+    }
+
+    #[test]
+    fn headless_report_writes_exports_and_metrics() {
+        let dir = scratch_dir("report");
+        let graph = generate_demo_graph(50);
+        let mut scenario = parse(
+            r#"
+                selections.libc = ["glibc"]
+                budget.max_paths = 10
+
+                [[export]]
+                path = "closure.json"
+
+                [[export]]
+                path = "libc.csv"
+                selection = "libc"
+            "#,
+        );
+        scenario.base_dir = dir.clone();
+        let metrics = dir.join("closure.prom");
+
+        let report = scenario.report(&graph, true, Some(&metrics));
+        let exported = fs::read_to_string(dir.join("libc.csv"));
+        let written = fs::read_to_string(&metrics);
+        let json_written = dir.join("closure.json").exists();
+        let _ = fs::remove_dir_all(&dir);
+
+        let report = report.unwrap();
+        assert_eq!(report.violations, ["closure has 50 paths (budget 10)"]);
+        assert!(json_written);
+        let exported = exported.unwrap();
+        assert_eq!(exported.lines().count(), 2);
+        assert!(exported.lines().nth(1).unwrap().contains("glibc-2.40-66"));
+        let written = written.unwrap();
+        assert!(written.contains("nix_analisa_closure_paths 50\n"));
+        assert!(written.contains("nix_analisa_budget_violations 1\n"));
+    }
+}
@@ -1,25 +1,76 @@
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 
-pub fn format_bytes(bytes: u64) -> String {
-    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+pub fn short_name(id: &str) -> &str {
+    id.split_once('-').map(|(_, rest)| rest).unwrap_or(id)
+}
 
-    let mut value = bytes as f64;
-    let mut unit = 0usize;
-    while value >= 1024.0 && unit < UNITS.len() - 1 {
-        value /= 1024.0;
-        unit += 1;
+const OUTPUT_NAMES: [&str; 14] = [
+    "out", "bin", "dev", "lib", "man", "doc", "devdoc", "devman", "info", "debug", "static",
+    "modules", "terminfo", "getent",
+];
+
+/// A store path name split into package name, version and output, following
+/// the `builtins.parseDrvName` rule of splitting at the first dash that is
+/// followed by something other than a letter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StoreName<'a> {
+    pub pname: &'a str,
+    pub version: Option<&'a str>,
+    pub output: Option<&'a str>,
+    pub is_derivation: bool,
+}
+
+/// Parses a hash-stripped store name such as `glibc-2.39-52-bin`.
+pub fn parse_store_name(name: &str) -> StoreName<'_> {
+    let (name, is_derivation) = match name.strip_suffix(".drv") {
+        Some(stripped) => (stripped, true),
+        None => (name, false),
+    };
+
+    let (base, output) = match name.rsplit_once('-') {
+        Some((base, output))
+            if OUTPUT_NAMES.contains(&output) && split_version(base).1.is_some() =>
+        {
+            (base, Some(output))
+        }
+        _ => (name, None),
+    };
+    let (pname, version) = split_version(base);
+
+    StoreName {
+        pname,
+        version,
+        output,
+        is_derivation,
     }
+}
 
-    if unit == 0 {
-        format!("{} {}", bytes, UNITS[unit])
-    } else {
-        format!("{value:.2} {}", UNITS[unit])
+fn split_version(name: &str) -> (&str, Option<&str>) {
+    let split = name
+        .char_indices()
+        .find(|&(index, c)| c == '-' && index > 0 && starts_version(&name[index + 1..]));
+
+    let Some((index, _)) = split else {
+        return (name, None);
+    };
+
+    let pname = &name[..index];
+    let version = &name[index + 1..];
+    match pname.strip_suffix("-unstable") {
+        Some(stripped) if !stripped.is_empty() => (stripped, Some(&name[stripped.len() + 1..])),
+        _ => (pname, Some(version)),
     }
 }
 
-pub fn short_name(id: &str) -> &str {
-    id.split_once('-').map(|(_, rest)| rest).unwrap_or(id)
+fn starts_version(rest: &str) -> bool {
+    let mut chars = rest.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_digit() => true,
+        Some('v') => chars.next().is_some_and(|c| c.is_ascii_digit()),
+        Some(c) => !c.is_alphabetic(),
+        None => false,
+    }
 }
 
 pub fn stable_pair(id: &str) -> (f32, f32) {
@@ -31,3 +82,123 @@ pub fn stable_pair(id: &str) -> (f32, f32) {
     let y = (((hash >> 32) & 0xffff_ffff) as f64 / u32::MAX as f64) as f32;
     ((x * 2.0) - 1.0, (y * 2.0) - 1.0)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{StoreName, parse_store_name};
+
+    fn parsed(name: &str) -> (&str, Option<&str>, Option<&str>) {
+        let StoreName {
+            pname,
+            version,
+            output,
+            ..
+        } = parse_store_name(name);
+        (pname, version, output)
+    }
+
+    #[test]
+    fn plain_name_and_version() {
+        assert_eq!(parsed("hello-2.12.1"), ("hello", Some("2.12.1"), None));
+        assert_eq!(parsed("bash-5.2p26"), ("bash", Some("5.2p26"), None));
+    }
+
+    #[test]
+    fn multi_dash_pnames() {
+        assert_eq!(
+            parsed("util-linux-minimal-2.39.3"),
+            ("util-linux-minimal", Some("2.39.3"), None)
+        );
+        assert_eq!(
+            parsed("python3.12-requests-2.31.0"),
+            ("python3.12-requests", Some("2.31.0"), None)
+        );
+        assert_eq!(
+            parsed("nixos-system-host-24.05.20240101.abcdef0"),
+            ("nixos-system-host", Some("24.05.20240101.abcdef0"), None)
+        );
+    }
+
+    #[test]
+    fn versions_with_letters_and_dashes() {
+        assert_eq!(
+            parsed("openssl-3.0.13a"),
+            ("openssl", Some("3.0.13a"), None)
+        );
+        assert_eq!(
+            parsed("rustc-1.78.0-rc1"),
+            ("rustc", Some("1.78.0-rc1"), None)
+        );
+        assert_eq!(parsed("glibc-2.39-52"), ("glibc", Some("2.39-52"), None));
+        assert_eq!(
+            parsed("ripgrep-v14.1.0"),
+            ("ripgrep", Some("v14.1.0"), None)
+        );
+    }
+
+    #[test]
+    fn unstable_dates() {
+        assert_eq!(
+            parsed("foo-unstable-2024-01-31"),
+            ("foo", Some("unstable-2024-01-31"), None)
+        );
+        assert_eq!(
+            parsed("bar-baz-0-unstable-2024-05-01"),
+            ("bar-baz", Some("0-unstable-2024-05-01"), None)
+        );
+    }
+
+    #[test]
+    fn output_suffixes() {
+        assert_eq!(
+            parsed("glibc-2.39-52-bin"),
+            ("glibc", Some("2.39-52"), Some("bin"))
+        );
+        assert_eq!(
+            parsed("openssl-3.0.13-dev"),
+            ("openssl", Some("3.0.13"), Some("dev"))
+        );
+        assert_eq!(
+            parsed("gcc-13.2.0-lib"),
+            ("gcc", Some("13.2.0"), Some("lib"))
+        );
+        assert_eq!(
+            parsed("foo-unstable-2024-01-31-man"),
+            ("foo", Some("unstable-2024-01-31"), Some("man"))
+        );
+    }
+
+    #[test]
+    fn unversioned_names_keep_their_suffix() {
+        assert_eq!(
+            parsed("linux-headers-dev"),
+            ("linux-headers-dev", None, None)
+        );
+        assert_eq!(parsed("etc"), ("etc", None, None));
+        assert_eq!(parsed("system-units"), ("system-units", None, None));
+        assert_eq!(
+            parsed("unit-script-foo-start"),
+            ("unit-script-foo-start", None, None)
+        );
+    }
+
+    #[test]
+    fn derivations() {
+        let name = parse_store_name("hello-2.12.1.drv");
+        assert_eq!(name.pname, "hello");
+        assert_eq!(name.version, Some("2.12.1"));
+        assert!(name.is_derivation);
+        assert!(!parse_store_name("hello-2.12.1").is_derivation);
+    }
+
+    #[test]
+    fn degenerate_inputs() {
+        assert_eq!(parsed(""), ("", None, None));
+        assert_eq!(parsed("-1.0"), ("-1.0", None, None));
+        assert_eq!(parsed("foo-"), ("foo-", None, None));
+        assert_eq!(
+            parsed("unstable-2024-01-01"),
+            ("unstable", Some("2024-01-01"), None)
+        );
+    }
+}